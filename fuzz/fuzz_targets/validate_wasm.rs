@@ -15,6 +15,7 @@ fuzz_target!(|data: &[u8]| {
             // Modify these values to explore additional parts of wasmer.
             simd: false,
             threads: false,
+            multi_value: false,
         },
     );
 });
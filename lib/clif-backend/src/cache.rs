@@ -15,19 +15,30 @@ use serde_bench::{deserialize, serialize};
 pub struct CacheGenerator {
     backend_cache: BackendCache,
     memory: Arc<Memory>,
+    has_breakpoints: bool,
 }
 
 impl CacheGenerator {
-    pub fn new(backend_cache: BackendCache, memory: Arc<Memory>) -> Self {
+    pub fn new(backend_cache: BackendCache, memory: Arc<Memory>, has_breakpoints: bool) -> Self {
         Self {
             backend_cache,
             memory,
+            has_breakpoints,
         }
     }
 }
 
 impl CacheGen for CacheGenerator {
     fn generate_cache(&self) -> Result<(Box<[u8]>, Memory), Error> {
+        // The compiled code refers to the handlers of its breakpoints by address, which
+        // wouldn't be valid in a module loaded from the cache.
+        if self.has_breakpoints {
+            return Err(Error::Unknown(
+                "modules with breakpoints, like the ones of metering middlewares, can't be cached"
+                    .to_string(),
+            ));
+        }
+
         // Clone the memory to a new location. This could take a long time,
         // depending on the throughput of your memcpy implementation.
         let compiled_code = (*self.memory).clone();
@@ -2,8 +2,8 @@
 // and subject to the license https://github.com/CraneStation/cranelift/blob/c47ca7bafc8fc48358f1baa72360e61fc1f7a0f2/cranelift-wasm/LICENSE
 
 use crate::{
-    cache::CacheGenerator, get_isa, module, module::Converter, relocation::call_names,
-    resolver::FuncResolverBuilder, signal::Caller, trampoline::Trampolines,
+    cache::CacheGenerator, get_isa, get_isa_with, module, module::Converter,
+    relocation::call_names, resolver::FuncResolverBuilder, signal::Caller, trampoline::Trampolines,
 };
 
 use cranelift_codegen::entity::EntityRef;
@@ -18,7 +18,7 @@ use std::mem;
 use std::sync::{Arc, RwLock};
 use wasmer_runtime_core::error::CompileError;
 use wasmer_runtime_core::{
-    backend::{Backend, CacheGen, Token},
+    backend::{Backend, CacheGen, CompilerConfig, MemoryBoundCheckMode, Token},
     cache::{Artifact, Error as CacheError},
     codegen::*,
     memory::MemoryType,
@@ -26,11 +26,11 @@ use wasmer_runtime_core::{
     structures::{Map, TypedIndex},
     types::{
         FuncIndex, FuncSig, GlobalIndex, LocalFuncIndex, LocalOrImport, MemoryIndex, SigIndex,
-        TableIndex,
+        TableIndex, Type,
     },
     vm,
 };
-use wasmparser::Type as WpType;
+use wasmparser::{Operator, Type as WpType};
 
 pub struct CraneliftModuleCodeGenerator {
     isa: Box<dyn isa::TargetIsa>,
@@ -38,6 +38,8 @@ pub struct CraneliftModuleCodeGenerator {
     pub clif_signatures: Map<SigIndex, ir::Signature>,
     function_signatures: Option<Arc<Map<FuncIndex, SigIndex>>>,
     functions: Vec<CraneliftFunctionCodeGenerator>,
+    memory_bound_check_mode: MemoryBoundCheckMode,
+    generate_debug_info: bool,
 }
 
 impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
@@ -51,6 +53,8 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
             functions: vec![],
             function_signatures: None,
             signatures: None,
+            memory_bound_check_mode: MemoryBoundCheckMode::Default,
+            generate_debug_info: false,
         }
     }
 
@@ -62,7 +66,36 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
         Backend::Cranelift
     }
 
-    fn check_precondition(&mut self, _module_info: &ModuleInfo) -> Result<(), CodegenError> {
+    fn feed_compiler_config(&mut self, config: &CompilerConfig) -> Result<(), CodegenError> {
+        self.memory_bound_check_mode = config.memory_bound_check_mode;
+        self.generate_debug_info = config.generate_debug_info;
+        if config.deterministic {
+            self.isa = get_isa_with(true).map_err(|e| CodegenError {
+                message: format!("cannot canonicalize NaNs: {}", e),
+            })?;
+        }
+        Ok(())
+    }
+
+    fn check_precondition(&mut self, module_info: &ModuleInfo) -> Result<(), CodegenError> {
+        // Results are returned in registers only: rax/rdx for integers and xmm0/xmm1 for
+        // floats and vectors.
+        for (_, sig) in module_info.signatures.iter() {
+            let int_results = sig
+                .returns()
+                .iter()
+                .filter(|&&ty| ty == Type::I32 || ty == Type::I64)
+                .count();
+            let float_results = sig.returns().len() - int_results;
+            if int_results > 2 || float_results > 2 {
+                return Err(CodegenError {
+                    message: format!(
+                        "signature {:?} has too many results for the Cranelift backend",
+                        sig
+                    ),
+                });
+            }
+        }
         Ok(())
     }
 
@@ -85,7 +118,10 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
             ),
         );
 
-        let func = ir::Function::with_name_signature(name, sig);
+        let mut func = ir::Function::with_name_signature(name, sig);
+        if self.generate_debug_info {
+            func.collect_debug_info();
+        }
 
         //func_translator.translate(body_bytes, body_offset, &mut func, &mut func_env)?;
 
@@ -93,11 +129,14 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
             func,
             func_translator,
             next_local: 0,
+            code_offset: 0,
             position: Position::default(),
+            breakpoints: vec![],
             func_env: FunctionEnvironment {
                 module_info: Arc::clone(&module_info),
                 target_config: self.isa.frontend_config().clone(),
                 clif_signatures: self.clif_signatures.clone(),
+                memory_bound_check_mode: self.memory_bound_check_mode,
             },
         };
 
@@ -141,12 +180,18 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
         module_info: &ModuleInfo,
     ) -> Result<(Caller, Box<dyn CacheGen>), CodegenError> {
         let mut func_bodies: Map<LocalFuncIndex, ir::Function> = Map::new();
+        let mut breakpoints = vec![];
         for f in self.functions.into_iter() {
             func_bodies.push(f.func);
+            breakpoints.extend(f.breakpoints);
         }
 
-        let (func_resolver_builder, handler_data) =
-            FuncResolverBuilder::new(&*self.isa, func_bodies, module_info)?;
+        let (func_resolver_builder, handler_data) = FuncResolverBuilder::new(
+            &*self.isa,
+            func_bodies,
+            module_info,
+            self.generate_debug_info,
+        )?;
 
         let trampolines = Arc::new(Trampolines::new(&*self.isa, module_info));
 
@@ -166,10 +211,11 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
         let cache_gen = Box::new(CacheGenerator::new(
             backend_cache,
             Arc::clone(&func_resolver.memory),
+            !breakpoints.is_empty(),
         ));
 
         Ok((
-            Caller::new(handler_data, trampolines, func_resolver),
+            Caller::new(handler_data, trampolines, func_resolver, breakpoints),
             cache_gen,
         ))
     }
@@ -237,14 +283,20 @@ pub struct CraneliftFunctionCodeGenerator {
     func: Function,
     func_translator: FuncTranslator,
     next_local: usize,
+    /// Offset of the current wasm operator from the start of the code section contents, used
+    /// as the source location of the instructions generated for it.
+    code_offset: u32,
     position: Position,
     func_env: FunctionEnvironment,
+    /// The handlers of the breakpoints of the function, which its code refers to by address.
+    breakpoints: Vec<Box<BreakpointHandler>>,
 }
 
 pub struct FunctionEnvironment {
     module_info: Arc<RwLock<ModuleInfo>>,
     target_config: isa::TargetFrontendConfig,
     clif_signatures: Map<SigIndex, ir::Signature>,
+    memory_bound_check_mode: MemoryBoundCheckMode,
 }
 
 impl FuncEnvironment for FunctionEnvironment {
@@ -429,35 +481,43 @@ impl FuncEnvironment for FunctionEnvironment {
             )
         };
 
-        match description.memory_type() {
-            mem_type @ MemoryType::Dynamic => {
-                let local_memory_bound = func.create_global_value(ir::GlobalValueData::Load {
-                    base: local_memory_ptr,
-                    offset: (vm::LocalMemory::offset_bound() as i32).into(),
-                    global_type: ptr_type,
-                    readonly: false,
-                });
-
-                Ok(func.create_heap(ir::HeapData {
-                    base: local_memory_base,
-                    min_size: (description.minimum.bytes().0 as u64).into(),
-                    offset_guard_size: mem_type.guard_size().into(),
-                    style: ir::HeapStyle::Dynamic {
-                        bound_gv: local_memory_bound,
-                    },
-                    index_type: ir::types::I32,
-                }))
+        // Dynamic memories have no guard region large enough to catch every
+        // out-of-bounds access, so they are always checked explicitly.
+        let mem_type = description.memory_type();
+        let explicit_bound_check = match self.memory_bound_check_mode {
+            MemoryBoundCheckMode::Default | MemoryBoundCheckMode::Disable => {
+                mem_type == MemoryType::Dynamic
             }
-            mem_type @ MemoryType::Static | mem_type @ MemoryType::SharedStatic => Ok(func
-                .create_heap(ir::HeapData {
-                    base: local_memory_base,
-                    min_size: (description.minimum.bytes().0 as u64).into(),
-                    offset_guard_size: mem_type.guard_size().into(),
-                    style: ir::HeapStyle::Static {
-                        bound: mem_type.bounds().unwrap().into(),
-                    },
-                    index_type: ir::types::I32,
-                })),
+            MemoryBoundCheckMode::Enable => true,
+        };
+
+        if explicit_bound_check {
+            let local_memory_bound = func.create_global_value(ir::GlobalValueData::Load {
+                base: local_memory_ptr,
+                offset: (vm::LocalMemory::offset_bound() as i32).into(),
+                global_type: ptr_type,
+                readonly: false,
+            });
+
+            Ok(func.create_heap(ir::HeapData {
+                base: local_memory_base,
+                min_size: (description.minimum.bytes().0 as u64).into(),
+                offset_guard_size: mem_type.guard_size().into(),
+                style: ir::HeapStyle::Dynamic {
+                    bound_gv: local_memory_bound,
+                },
+                index_type: ir::types::I32,
+            }))
+        } else {
+            Ok(func.create_heap(ir::HeapData {
+                base: local_memory_base,
+                min_size: (description.minimum.bytes().0 as u64).into(),
+                offset_guard_size: mem_type.guard_size().into(),
+                style: ir::HeapStyle::Static {
+                    bound: mem_type.bounds().unwrap().into(),
+                },
+                index_type: ir::types::I32,
+            }))
         }
     }
 
@@ -994,13 +1054,16 @@ impl FunctionCodeGenerator<CodegenError> for CraneliftFunctionCodeGenerator {
         Ok(())
     }
 
+    fn begin_operator(&mut self, code_offset: u32) -> Result<(), CodegenError> {
+        self.code_offset = code_offset;
+        Ok(())
+    }
+
     fn feed_event(&mut self, event: Event, _module_info: &ModuleInfo) -> Result<(), CodegenError> {
         let op = match event {
             Event::Wasm(x) => x,
             Event::WasmOwned(ref x) => x,
-            Event::Internal(_x) => {
-                return Ok(());
-            }
+            Event::Internal(x) => return self.feed_internal_event(x),
         };
 
         //let builder = self.builder.as_mut().unwrap();
@@ -1011,11 +1074,20 @@ impl FunctionCodeGenerator<CodegenError> for CraneliftFunctionCodeGenerator {
             return Ok(());
         }
 
+        if let Operator::I32Wait { .. } | Operator::I64Wait { .. } | Operator::Wake { .. } = *op {
+            return Err(CodegenError {
+                message: "memory.atomic.wait and memory.atomic.notify are not supported by the Cranelift backend".to_string(),
+            });
+        }
+
         let mut builder = FunctionBuilder::new(
             &mut self.func,
             &mut self.func_translator.func_ctx,
             &mut self.position,
         );
+        // Tag the generated instructions with the operator they come from so that
+        // `func.srclocs` can be mapped back to the wasm code after compilation.
+        builder.set_srcloc(ir::SourceLoc::new(self.code_offset));
         let state = &mut self.func_translator.state;
         translate_operator(op, &mut builder, state, &mut self.func_env)?;
         Ok(())
@@ -1055,6 +1127,170 @@ impl FunctionCodeGenerator<CodegenError> for CraneliftFunctionCodeGenerator {
     }
 }
 
+impl CraneliftFunctionCodeGenerator {
+    fn feed_internal_event(&mut self, event: InternalEvent) -> Result<(), CodegenError> {
+        let state = &mut self.func_translator.state;
+        if state.control_stack.is_empty() || !state.reachable {
+            return Ok(());
+        }
+
+        let call_conv = self.func_env.target_config().default_call_conv;
+        let mut builder = FunctionBuilder::new(
+            &mut self.func,
+            &mut self.func_translator.func_ctx,
+            &mut self.position,
+        );
+        builder.set_srcloc(ir::SourceLoc::new(self.code_offset));
+        let flags = ir::MemFlags::trusted();
+
+        match event {
+            InternalEvent::FunctionBegin(_) | InternalEvent::FunctionEnd => {}
+            InternalEvent::Breakpoint(callback) => {
+                let callback = register_breakpoint(&mut self.breakpoints, callback);
+                call_breakpoint(&mut builder, call_conv, callback);
+            }
+            InternalEvent::GetInternal(idx) => {
+                let internals = internals_ptr(&mut builder);
+                let value =
+                    builder
+                        .ins()
+                        .load(ir::types::I64, flags, internals, internal_offset(idx));
+                state.stack.push(value);
+            }
+            InternalEvent::SetInternal(idx) => {
+                let value = state.stack.pop().ok_or_else(|| CodegenError {
+                    message: "SetInternal: the value stack is empty".to_string(),
+                })?;
+                let internals = internals_ptr(&mut builder);
+                builder
+                    .ins()
+                    .store(flags, value, internals, internal_offset(idx));
+            }
+            InternalEvent::AddInternal(idx, value) => {
+                let internals = internals_ptr(&mut builder);
+                let offset = internal_offset(idx);
+                let old_value = builder.ins().load(ir::types::I64, flags, internals, offset);
+                let new_value = builder.ins().iadd_imm(old_value, value as i64);
+                builder.ins().store(flags, new_value, internals, offset);
+            }
+            InternalEvent::BreakpointIfInternalAtLeast(idx, limit, callback) => {
+                let callback = register_breakpoint(&mut self.breakpoints, callback);
+                let internals = internals_ptr(&mut builder);
+                let value =
+                    builder
+                        .ins()
+                        .load(ir::types::I64, flags, internals, internal_offset(idx));
+                let reached = builder.ins().icmp_imm(
+                    ir::condcodes::IntCC::UnsignedGreaterThanOrEqual,
+                    value,
+                    limit as i64,
+                );
+
+                let breakpoint_block = builder.create_ebb();
+                let continue_block = builder.create_ebb();
+                builder.ins().brnz(reached, breakpoint_block, &[]);
+                builder.ins().jump(continue_block, &[]);
+                builder.switch_to_block(breakpoint_block);
+                builder.seal_block(breakpoint_block);
+                call_breakpoint(&mut builder, call_conv, callback);
+                builder.ins().jump(continue_block, &[]);
+                builder.switch_to_block(continue_block);
+                builder.seal_block(continue_block);
+            }
+            InternalEvent::IncrementCounter(idx, counter) => {
+                let internals = internals_ptr(&mut builder);
+                let buffer =
+                    builder
+                        .ins()
+                        .load(ir::types::I64, flags, internals, internal_offset(idx));
+
+                // The counters aren't incremented until a buffer is attached.
+                let increment_block = builder.create_ebb();
+                let continue_block = builder.create_ebb();
+                builder.ins().brz(buffer, continue_block, &[]);
+                builder.ins().jump(increment_block, &[]);
+                builder.switch_to_block(increment_block);
+                builder.seal_block(increment_block);
+                let counter_ptr = builder.ins().iadd_imm(buffer, counter as i64 * 8);
+                let count = builder.ins().load(ir::types::I64, flags, counter_ptr, 0);
+                let count = builder.ins().iadd_imm(count, 1);
+                builder.ins().store(flags, count, counter_ptr, 0);
+                builder.ins().jump(continue_block, &[]);
+                builder.switch_to_block(continue_block);
+                builder.seal_block(continue_block);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keeps `callback` alive as long as the compiled module, and returns its address.
+fn register_breakpoint(
+    breakpoints: &mut Vec<Box<BreakpointHandler>>,
+    callback: BreakpointHandler,
+) -> u64 {
+    let callback = Box::new(callback);
+    let raw = &*callback as *const BreakpointHandler as u64;
+    breakpoints.push(callback);
+    raw
+}
+
+/// Loads the pointer to the internal fields of the instance running the function.
+fn internals_ptr(builder: &mut FunctionBuilder) -> ir::Value {
+    let vmctx = builder
+        .func
+        .special_param(ir::ArgumentPurpose::VMContext)
+        .expect("missing vmctx parameter");
+    builder.ins().load(
+        ir::types::I64,
+        ir::MemFlags::trusted(),
+        vmctx,
+        vm::Ctx::offset_internals() as i32,
+    )
+}
+
+/// Returns the offset of internal field `idx` from the pointer returned by `internals_ptr`.
+fn internal_offset(idx: u32) -> i32 {
+    let idx = idx as usize;
+    assert!(idx < vm::INTERNALS_SIZE);
+    (idx * 8) as i32
+}
+
+/// Calls the breakpoint handler at address `callback`.
+fn call_breakpoint(builder: &mut FunctionBuilder, call_conv: CallConv, callback: u64) {
+    let signature = builder.import_signature(ir::Signature {
+        call_conv,
+        params: vec![
+            ir::AbiParam::new(ir::types::I64),
+            ir::AbiParam::new(ir::types::I64),
+        ],
+        returns: vec![],
+    });
+    let vmctx = builder
+        .func
+        .special_param(ir::ArgumentPurpose::VMContext)
+        .expect("missing vmctx parameter");
+    let trampoline = builder
+        .ins()
+        .iconst(ir::types::I64, run_breakpoint as usize as i64);
+    let callback = builder.ins().iconst(ir::types::I64, callback as i64);
+    builder
+        .ins()
+        .call_indirect(signature, trampoline, &[vmctx, callback]);
+}
+
+/// Runs the handler of a breakpoint hit by compiled code. The execution resumes if the
+/// handler returns `Ok`, and traps with its error otherwise.
+unsafe extern "C" fn run_breakpoint(ctx: *mut vm::Ctx, callback: *const BreakpointHandler) {
+    let info = BreakpointInfo {
+        fault: None,
+        ctx: ctx.as_mut(),
+    };
+    if let Err(error) = (*callback)(info) {
+        (&*(*ctx).module).runnable_module.do_early_trap(error)
+    }
+}
+
 #[derive(Debug)]
 pub struct CodegenError {
     pub message: String,
@@ -1126,6 +1362,7 @@ fn declare_wasm_parameters(builder: &mut FunctionBuilder, entry_block: Ebb) -> u
 
             let param_value = builder.ebb_params(entry_block)[i];
             builder.def_var(local, param_value);
+            builder.set_val_label(param_value, ir::ValueLabel::new(local.index()));
         }
         if param_type.purpose == ir::ArgumentPurpose::VMContext {
             let param_value = builder.ebb_params(entry_block)[i];
@@ -0,0 +1,509 @@
+//! Debug info for the code compiled by Cranelift.
+//!
+//! Each Cranelift instruction is tagged with the code section offset of the
+//! wasm operator it was translated from, and the values of wasm locals are
+//! labelled with the indices of the locals. After compilation these become a
+//! DWARF line table and the locations of the locals for the machine code,
+//! which are put in an ELF object and registered with debuggers through the
+//! GDB JIT interface. When the module carries its own `.debug_line` section,
+//! the rows point at the original source files; otherwise they point at
+//! `module.wasm`, with the code section offset of each operator as the line
+//! number.
+
+use byteorder::{ByteOrder, LittleEndian as LE, WriteBytesExt};
+use cranelift_codegen::{ir, isa, Context};
+use cranelift_wasm::get_vmctx_value_label;
+use faerie::{Artifact, Decl, SectionKind};
+use gimli::write::{
+    Address, AttributeValue, DwarfUnit, EndianVec, Expression, FileId, LineProgram, LineString,
+    Location, LocationList, Sections, Unit, UnitEntryId,
+};
+use gimli::{
+    ColumnType, DebugLine, DebugLineOffset, Encoding, Format, LineEncoding, LittleEndian, Register,
+    X86_64,
+};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    str,
+};
+use target_lexicon::{BinaryFormat, Triple};
+use wasmer_runtime_core::module::ModuleInfo;
+
+/// The file that line numbers refer to when the module has no debug info of its own.
+const WASM_FILE: &[u8] = b"module.wasm";
+
+/// A compiled function, as described to the debugger.
+pub struct FunctionDebugInfo<'a> {
+    pub name: String,
+    pub address: u64,
+    pub code: &'a [u8],
+    /// See `function_lines`.
+    pub lines: &'a [(u32, u32)],
+    /// See `function_variables`.
+    pub variables: &'a [Variable],
+}
+
+/// Where the value of a wasm local is.
+#[derive(Clone, Copy)]
+enum VariableLocation {
+    Register(Register),
+    /// An offset from the frame pointer.
+    Frame(i64),
+}
+
+/// A wasm local of a compiled function.
+pub struct Variable {
+    index: u32,
+    ty: ir::Type,
+    /// Ranges of offsets into the machine code of the function, with the location of the
+    /// local in each of them.
+    ranges: Vec<(u32, u32, VariableLocation)>,
+}
+
+/// Returns pairs of offsets into the machine code of `func` and code section offsets of the
+/// wasm operators they were generated from, sorted by the former.
+///
+/// `func` must have just been compiled, so that its instruction offsets are known.
+pub fn function_lines(func: &ir::Function, isa: &dyn isa::TargetIsa) -> Vec<(u32, u32)> {
+    let encinfo = isa.encoding_info();
+    let mut lines: Vec<(u32, u32)> = vec![];
+    for ebb in func.layout.ebbs() {
+        for (offset, inst, _size) in func.inst_offsets(ebb, &encinfo) {
+            let srcloc = func.srclocs[inst];
+            if srcloc.is_default() {
+                continue;
+            }
+            if lines.last().map(|&(_, code_offset)| code_offset) != Some(srcloc.bits()) {
+                lines.push((offset, srcloc.bits()));
+            }
+        }
+    }
+    lines
+}
+
+/// Returns the wasm locals of the function in `context`, with where their values are in its
+/// machine code, sorted by index.
+///
+/// The function must have just been compiled, with its value labels collected.
+pub fn function_variables(
+    context: &Context,
+    isa: &dyn isa::TargetIsa,
+) -> Result<Vec<Variable>, String> {
+    let func = &context.func;
+    let values_labels = match func.dfg.values_labels {
+        Some(ref values_labels) => values_labels,
+        None => return Ok(vec![]),
+    };
+    let mut types = HashMap::new();
+    for (&value, assignments) in values_labels {
+        if let ir::ValueLabelAssignments::Starts(starts) = assignments {
+            for start in starts {
+                types
+                    .entry(start.label)
+                    .or_insert_with(|| func.dfg.value_type(value));
+            }
+        }
+    }
+
+    let mut variables = vec![];
+    for (label, ranges) in context
+        .build_value_labels_ranges(isa)
+        .map_err(|e| e.to_string())?
+    {
+        // All the other labels are the indices of wasm locals.
+        if label == get_vmctx_value_label() {
+            continue;
+        }
+        let ty = match types.get(&label) {
+            Some(&ty) => ty,
+            None => continue,
+        };
+        let ranges = ranges
+            .iter()
+            .filter_map(|range| {
+                let location = match range.loc {
+                    ir::ValueLoc::Reg(reg) => VariableLocation::Register(dwarf_register(isa, reg)?),
+                    // Stack slot offsets are from the stack pointer before the call, which is
+                    // past the return address and the saved frame pointer.
+                    ir::ValueLoc::Stack(slot) => {
+                        VariableLocation::Frame(i64::from(func.stack_slots[slot].offset?) + 16)
+                    }
+                    ir::ValueLoc::Unassigned => return None,
+                };
+                Some((range.start, range.end, location))
+            })
+            .collect();
+        variables.push(Variable {
+            index: label.as_u32(),
+            ty,
+            ranges,
+        });
+    }
+    variables.sort_by_key(|variable| variable.index);
+    Ok(variables)
+}
+
+/// Returns the DWARF register for the register unit `reg`. Only x86-64 is known.
+fn dwarf_register(isa: &dyn isa::TargetIsa, reg: isa::RegUnit) -> Option<Register> {
+    if isa.name() != "x86" || isa.pointer_bits() != 64 {
+        return None;
+    }
+    let name = isa.register_info().display_regunit(reg).to_string();
+    let name = name.trim_start_matches('%');
+    (0..=X86_64::XMM15.0)
+        .map(Register)
+        .find(|&register| X86_64::register_name(register) == Some(name))
+}
+
+/// Rows of the `.debug_line` section of a wasm module.
+struct WasmLines {
+    files: Vec<Vec<u8>>,
+    /// Code section offsets with the file, line and column starting there, sorted by offset.
+    /// The ends of sequences have no file.
+    rows: Vec<(u64, Option<usize>, u64, u64)>,
+}
+
+impl WasmLines {
+    fn parse(section: &[u8]) -> gimli::Result<WasmLines> {
+        let debug_line = DebugLine::new(section, LittleEndian);
+        let mut files = vec![];
+        let mut file_indices = HashMap::new();
+        let mut rows = vec![];
+
+        let mut offset = 0;
+        while offset < section.len() {
+            let program = debug_line.program(DebugLineOffset(offset), 4, None, None)?;
+            let header = program.header();
+            offset += header.unit_length() + header.format().initial_length_size() as usize;
+
+            let mut program_rows = program.rows();
+            while let Some((header, row)) = program_rows.next_row()? {
+                if row.end_sequence() {
+                    rows.push((row.address(), None, 0, 0));
+                    continue;
+                }
+                let file = match row.file(header) {
+                    Some(file) => file,
+                    None => continue,
+                };
+                let mut path = match file.path_name() {
+                    gimli::AttributeValue::String(path) => path.slice().to_vec(),
+                    _ => continue,
+                };
+                if path.first() != Some(&b'/') {
+                    if let Some(gimli::AttributeValue::String(dir)) = file.directory(header) {
+                        if !dir.slice().is_empty() {
+                            let mut full_path = dir.slice().to_vec();
+                            full_path.push(b'/');
+                            full_path.extend_from_slice(&path);
+                            path = full_path;
+                        }
+                    }
+                }
+                if path.is_empty() || path.contains(&0) {
+                    continue;
+                }
+                let index = *file_indices.entry(path.clone()).or_insert_with(|| {
+                    files.push(path);
+                    files.len() - 1
+                });
+                let column = match row.column() {
+                    ColumnType::LeftEdge => 0,
+                    ColumnType::Column(column) => column,
+                };
+                rows.push((row.address(), Some(index), row.line().unwrap_or(0), column));
+            }
+        }
+        // At equal offsets, the end of one sequence comes before the start of the next one.
+        rows.sort_by_key(|&(address, file, _, _)| (address, file.is_some()));
+
+        Ok(WasmLines { files, rows })
+    }
+
+    /// Returns the file, line and column of the code at `code_offset`.
+    fn lookup(&self, code_offset: u64) -> Option<(usize, u64, u64)> {
+        // The number of rows starting at or before `code_offset`.
+        let after = match self.rows.binary_search_by(|row| {
+            if row.0 <= code_offset {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        }) {
+            Ok(index) | Err(index) => index,
+        };
+        if after == 0 {
+            return None;
+        }
+        let (_, file, line, column) = self.rows[after - 1];
+        file.map(|file| (file, line, column))
+    }
+}
+
+/// Builds an ELF object describing `functions`, all of which are within the code at
+/// `code_address`, for the GDB JIT interface.
+pub fn emit_object(
+    info: &ModuleInfo,
+    code_address: u64,
+    code_size: u64,
+    functions: &[FunctionDebugInfo],
+) -> Result<Vec<u8>, String> {
+    // Invalid debug info in the module only costs the mapping to its source files.
+    let wasm_lines = info
+        .custom_sections(".debug_line")
+        .first()
+        .and_then(|section| WasmLines::parse(section).ok());
+
+    let encoding = Encoding {
+        format: Format::Dwarf32,
+        version: 4,
+        address_size: 8,
+    };
+    let mut dwarf = DwarfUnit::new(encoding);
+    let mut program = LineProgram::new(
+        encoding,
+        LineEncoding::default(),
+        LineString::String(b".".to_vec()),
+        LineString::String(WASM_FILE.to_vec()),
+        None,
+    );
+    let directory = program.default_directory();
+    let wasm_file = program.add_file(LineString::String(WASM_FILE.to_vec()), directory, None);
+    let mut files: HashMap<usize, FileId> = HashMap::new();
+
+    for function in functions {
+        program.begin_sequence(Some(Address::Constant(function.address)));
+        for &(native_offset, code_offset) in function.lines {
+            let (file, line, column) = match wasm_lines
+                .as_ref()
+                .and_then(|wasm_lines| Some((wasm_lines, wasm_lines.lookup(code_offset as u64)?)))
+            {
+                Some((wasm_lines, (file, line, column))) => {
+                    let file = *files.entry(file).or_insert_with(|| {
+                        program.add_file(
+                            LineString::String(wasm_lines.files[file].clone()),
+                            directory,
+                            None,
+                        )
+                    });
+                    (file, line, column)
+                }
+                None => (wasm_file, code_offset as u64, 0),
+            };
+            let row = program.row();
+            row.address_offset = native_offset as u64;
+            row.file = file;
+            row.line = line;
+            row.column = column;
+            program.generate_row();
+        }
+        program.end_sequence(function.code.len() as u64);
+    }
+    dwarf.unit.line_program = program;
+
+    let root = dwarf.unit.root();
+    let entry = dwarf.unit.get_mut(root);
+    entry.set(
+        gimli::DW_AT_producer,
+        AttributeValue::String(b"wasmer-clif-backend".to_vec()),
+    );
+    entry.set(
+        gimli::DW_AT_name,
+        AttributeValue::String(WASM_FILE.to_vec()),
+    );
+    entry.set(gimli::DW_AT_comp_dir, AttributeValue::String(b".".to_vec()));
+    entry.set(
+        gimli::DW_AT_low_pc,
+        AttributeValue::Address(Address::Constant(code_address)),
+    );
+    entry.set(gimli::DW_AT_high_pc, AttributeValue::Udata(code_size));
+    let mut base_types = HashMap::new();
+    for function in functions {
+        let subprogram = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+        let entry = dwarf.unit.get_mut(subprogram);
+        entry.set(
+            gimli::DW_AT_name,
+            AttributeValue::String(function.name.as_bytes().to_vec()),
+        );
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(function.address)),
+        );
+        entry.set(
+            gimli::DW_AT_high_pc,
+            AttributeValue::Udata(function.code.len() as u64),
+        );
+
+        for variable in function.variables {
+            let locations = variable
+                .ranges
+                .iter()
+                .map(|&(start, end, location)| {
+                    let mut data = Expression::new();
+                    match location {
+                        VariableLocation::Register(register) => data.op_reg(register),
+                        VariableLocation::Frame(offset) => data.op_breg(X86_64::RBP, offset),
+                    }
+                    Location::StartEnd {
+                        begin: Address::Constant(function.address + u64::from(start)),
+                        end: Address::Constant(function.address + u64::from(end)),
+                        data,
+                    }
+                })
+                .collect();
+            let locations = dwarf.unit.locations.add(LocationList(locations));
+            let ty = match base_types.get(&variable.ty) {
+                Some(&ty) => ty,
+                None => {
+                    let ty = add_base_type(&mut dwarf.unit, variable.ty);
+                    base_types.insert(variable.ty, ty);
+                    ty
+                }
+            };
+            let id = dwarf.unit.add(subprogram, gimli::DW_TAG_variable);
+            let entry = dwarf.unit.get_mut(id);
+            entry.set(
+                gimli::DW_AT_name,
+                AttributeValue::String(format!("var{}", variable.index).into_bytes()),
+            );
+            entry.set(gimli::DW_AT_type, AttributeValue::UnitRef(ty));
+            entry.set(
+                gimli::DW_AT_location,
+                AttributeValue::LocationListRef(locations),
+            );
+        }
+    }
+
+    let mut sections = Sections::new(EndianVec::new(LittleEndian));
+    dwarf.write(&mut sections).map_err(|e| e.to_string())?;
+
+    let mut artifact = Artifact::new(Triple::host(), String::from("module"));
+    // Each function is in its own text section, whose address is set once the object is
+    // emitted.
+    let mut symbols = HashSet::new();
+    let mut addresses = HashMap::new();
+    for function in functions {
+        let mut symbol = function.name.clone();
+        let mut suffix = 0;
+        while !symbols.insert(symbol.clone()) {
+            suffix += 1;
+            symbol = format!("{}.{}", function.name, suffix);
+        }
+        addresses.insert(format!(".text.{}", symbol), function.address);
+        artifact
+            .declare_with(symbol, Decl::function().global(), function.code.to_vec())
+            .map_err(|e| e.to_string())?;
+    }
+    sections.for_each(|id, data| {
+        if data.slice().is_empty() {
+            return Ok(());
+        }
+        artifact
+            .declare_with(
+                id.name(),
+                Decl::section(SectionKind::Debug),
+                data.slice().to_vec(),
+            )
+            .map_err(|e| e.to_string())
+    })?;
+
+    let mut object = artifact
+        .emit_as(BinaryFormat::Elf)
+        .map_err(|e| e.to_string())?;
+    place_sections(&mut object, &addresses);
+    Ok(object)
+}
+
+/// Adds a base type for values of type `ty` to `unit`.
+fn add_base_type(unit: &mut Unit, ty: ir::Type) -> UnitEntryId {
+    let root = unit.root();
+    let id = unit.add(root, gimli::DW_TAG_base_type);
+    let entry = unit.get_mut(id);
+    entry.set(
+        gimli::DW_AT_name,
+        AttributeValue::String(ty.to_string().into_bytes()),
+    );
+    let encoding = if ty.is_float() {
+        gimli::DW_ATE_float
+    } else {
+        gimli::DW_ATE_signed
+    };
+    entry.set(gimli::DW_AT_encoding, AttributeValue::Encoding(encoding));
+    entry.set(
+        gimli::DW_AT_byte_size,
+        AttributeValue::Data1(ty.bytes() as u8),
+    );
+    id
+}
+
+const SHT_SYMTAB: u32 = 2;
+
+/// Turns the relocatable ELF object `object` into an executable whose sections named in
+/// `addresses` are loaded at those addresses, where their code already is, so that debuggers
+/// use the addresses in the debug info as they are.
+fn place_sections(object: &mut Vec<u8>, addresses: &HashMap<String, u64>) {
+    const PHDR_SIZE: u16 = 56;
+
+    let shoff = LE::read_u64(&object[0x28..]) as usize;
+    let shentsize = LE::read_u16(&object[0x3a..]) as usize;
+    let shnum = LE::read_u16(&object[0x3c..]) as usize;
+    let shstrndx = LE::read_u16(&object[0x3e..]) as usize;
+    let header = |index: usize| shoff + index * shentsize;
+    let shstrtab = LE::read_u64(&object[header(shstrndx) + 0x18..]) as usize;
+
+    let mut section_addresses = vec![None; shnum];
+    let mut segments = vec![];
+    for (index, section_address) in section_addresses.iter_mut().enumerate() {
+        let name = &object[shstrtab + LE::read_u32(&object[header(index)..]) as usize..];
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+        let address = match str::from_utf8(name)
+            .ok()
+            .and_then(|name| addresses.get(name))
+        {
+            Some(&address) => address,
+            None => continue,
+        };
+        LE::write_u64(&mut object[header(index) + 0x10..], address);
+        *section_address = Some(address);
+        let offset = LE::read_u64(&object[header(index) + 0x18..]);
+        let size = LE::read_u64(&object[header(index) + 0x20..]);
+        segments.push((offset, address, size));
+    }
+
+    // The values of symbols are relative to their sections in relocatable objects.
+    for index in 0..shnum {
+        if LE::read_u32(&object[header(index) + 0x4..]) != SHT_SYMTAB {
+            continue;
+        }
+        let offset = LE::read_u64(&object[header(index) + 0x18..]) as usize;
+        let size = LE::read_u64(&object[header(index) + 0x20..]) as usize;
+        for symbol in (offset..offset + size).step_by(24) {
+            let section = LE::read_u16(&object[symbol + 0x6..]) as usize;
+            if let Some(&Some(address)) = section_addresses.get(section) {
+                let value = LE::read_u64(&object[symbol + 0x8..]);
+                LE::write_u64(&mut object[symbol + 0x8..], value + address);
+            }
+        }
+    }
+
+    // A PT_LOAD segment for each of the placed sections.
+    while object.len() % 8 != 0 {
+        object.push(0);
+    }
+    let phoff = object.len() as u64;
+    for &(offset, address, size) in &segments {
+        object.write_u32::<LE>(1).unwrap();
+        object.write_u32::<LE>(0x5).unwrap(); // PF_R | PF_X
+        object.write_u64::<LE>(offset).unwrap();
+        object.write_u64::<LE>(address).unwrap();
+        object.write_u64::<LE>(address).unwrap();
+        object.write_u64::<LE>(size).unwrap();
+        object.write_u64::<LE>(size).unwrap();
+        object.write_u64::<LE>(1).unwrap();
+    }
+    LE::write_u16(&mut object[0x10..], 2); // ET_EXEC
+    LE::write_u64(&mut object[0x20..], phoff);
+    LE::write_u16(&mut object[0x36..], PHDR_SIZE);
+    LE::write_u16(&mut object[0x38..], segments.len() as u16);
+}
@@ -17,6 +17,7 @@
 
 mod cache;
 mod code;
+mod debug;
 mod libcalls;
 mod module;
 mod relocation;
@@ -28,7 +29,6 @@ use cranelift_codegen::{
     isa,
     settings::{self, Configurable},
 };
-use target_lexicon::Triple;
 
 #[macro_use]
 extern crate serde_derive;
@@ -37,20 +37,32 @@ extern crate rayon;
 extern crate serde;
 
 fn get_isa() -> Box<dyn isa::TargetIsa> {
+    get_isa_with(false).unwrap()
+}
+
+/// Builds the native target ISA, canonicalizing the NaNs produced by float
+/// operations if `nan_canonicalization` is set.
+fn get_isa_with(nan_canonicalization: bool) -> Result<Box<dyn isa::TargetIsa>, settings::SetError> {
     let flags = {
         let mut builder = settings::builder();
-        builder.set("opt_level", "speed_and_size").unwrap();
-        builder.set("jump_tables_enabled", "false").unwrap();
+        builder.set("opt_level", "speed_and_size")?;
+        builder.set("jump_tables_enabled", "false")?;
+        builder.set("enable_simd", "true")?;
 
         if cfg!(not(test)) {
-            builder.set("enable_verifier", "false").unwrap();
+            builder.set("enable_verifier", "false")?;
+        }
+        if nan_canonicalization {
+            builder.set("enable_nan_canonicalization", "true")?;
         }
 
         let flags = settings::Flags::new(builder);
         debug_assert_eq!(flags.opt_level(), settings::OptLevel::SpeedAndSize);
         flags
     };
-    isa::lookup(Triple::host()).unwrap().finish(flags)
+    // Use the native builder so that host features like SSE4.1, which the
+    // SIMD lowerings rely on, are detected.
+    Ok(cranelift_native::builder().unwrap().finish(flags))
 }
 
 /// The current version of this crate
@@ -58,6 +70,8 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 use wasmer_runtime_core::codegen::SimpleStreamingCompilerGen;
 
+pub use code::CraneliftModuleCodeGenerator as ModuleCodeGenerator;
+
 /// Streaming compiler implementation for the Cranelift backed. Compiles web assembly binary into
 /// machine code.
 pub type CraneliftCompiler = SimpleStreamingCompilerGen<
@@ -37,15 +37,17 @@ impl Module {
         let cache_gen = Box::new(CacheGenerator::new(
             backend_cache,
             Arc::clone(&func_resolver.memory),
+            false,
         ));
 
-        let runnable_module = Caller::new(handler_data, trampolines, func_resolver);
+        let runnable_module = Caller::new(handler_data, trampolines, func_resolver, vec![]);
 
         Ok(ModuleInner {
             runnable_module: Box::new(runnable_module),
             cache_gen,
 
             info,
+            memory_images: Default::default(),
         })
     }
 }
@@ -63,6 +63,17 @@ pub struct LocalRelocation {
     pub target: FuncIndex,
 }
 
+/// A reference from a function's code to its constant pool (e.g. a `v128.const`).
+#[derive(Debug, Clone)]
+pub struct ConstantRelocation {
+    /// The offset where to apply the relocation.
+    pub offset: binemit::CodeOffset,
+    /// Relocation type.
+    pub reloc: binemit::Reloc,
+    /// The offset of the constant from the start of the function.
+    pub constant_offset: binemit::CodeOffset,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum VmCallKind {
     StaticMemoryGrow,
@@ -95,6 +106,7 @@ pub struct RelocSink {
     /// Relocations recorded for the function.
     pub external_relocs: Vec<ExternalRelocation>,
     pub local_relocs: Vec<LocalRelocation>,
+    pub constant_relocs: Vec<ConstantRelocation>,
 }
 
 impl binemit::RelocSink for RelocSink {
@@ -203,8 +215,17 @@ impl binemit::RelocSink for RelocSink {
         }
     }
 
-    fn reloc_constant(&mut self, _: u32, _: cranelift_codegen::binemit::Reloc, _: u32) {
-        unimplemented!("RelocSink::reloc_constant")
+    fn reloc_constant(
+        &mut self,
+        offset: binemit::CodeOffset,
+        reloc: binemit::Reloc,
+        constant_offset: binemit::CodeOffset,
+    ) {
+        self.constant_relocs.push(ConstantRelocation {
+            offset,
+            reloc,
+            constant_offset,
+        });
     }
 
     fn reloc_jt(
@@ -239,6 +260,7 @@ impl RelocSink {
         Self {
             external_relocs: Vec::new(),
             local_relocs: Vec::new(),
+            constant_relocs: Vec::new(),
         }
     }
 }
@@ -1,16 +1,17 @@
 use crate::{
     cache::BackendCache,
+    debug::{self, FunctionDebugInfo, Variable},
     libcalls,
     relocation::{
-        ExternalRelocation, LibCall, LocalRelocation, LocalTrapSink, Reloc, RelocSink,
-        RelocationType, TrapSink, VmCall, VmCallKind,
+        ConstantRelocation, ExternalRelocation, LibCall, LocalRelocation, LocalTrapSink, Reloc,
+        RelocSink, RelocationType, TrapSink, VmCall, VmCallKind,
     },
     signal::HandlerData,
     trampoline::Trampolines,
 };
 use byteorder::{ByteOrder, LittleEndian};
 use cranelift_codegen::{
-    binemit::{Stackmap, StackmapSink},
+    binemit::{self, Stackmap, StackmapSink},
     ir, isa, Context,
 };
 use rayon::prelude::*;
@@ -27,9 +28,10 @@ use wasmer_runtime_core::{
     },
     cache::Error as CacheError,
     error::{CompileError, CompileResult},
+    jit_debug::{self, JitDebugEntry},
     module::ModuleInfo,
     structures::{Map, SliceMap, TypedIndex},
-    types::{FuncSig, LocalFuncIndex, SigIndex},
+    types::{FuncIndex, FuncSig, LocalFuncIndex, SigIndex},
     vm, vmcalls,
 };
 
@@ -56,8 +58,10 @@ pub struct FuncResolverBuilder {
     map: Map<LocalFuncIndex, usize>,
     memory: Memory,
     local_relocs: Map<LocalFuncIndex, Box<[LocalRelocation]>>,
+    constant_relocs: Map<LocalFuncIndex, Box<[ConstantRelocation]>>,
     external_relocs: Map<LocalFuncIndex, Box<[ExternalRelocation]>>,
     import_len: usize,
+    debug_object: Option<Vec<u8>>,
 }
 
 pub struct NoopStackmapSink {}
@@ -84,8 +88,11 @@ impl FuncResolverBuilder {
                 map: backend_cache.offsets,
                 memory: code,
                 local_relocs: Map::new(),
+                constant_relocs: Map::new(),
                 external_relocs: backend_cache.external_relocs,
                 import_len: info.imported_functions.len(),
+                // Debug info isn't cached.
+                debug_object: None,
             },
             Arc::new(Trampolines::from_trampoline_cache(
                 backend_cache.trampolines,
@@ -98,55 +105,78 @@ impl FuncResolverBuilder {
         isa: &dyn isa::TargetIsa,
         function_bodies: Map<LocalFuncIndex, ir::Function>,
         info: &ModuleInfo,
+        generate_debug_info: bool,
     ) -> CompileResult<(Self, HandlerData)> {
         let num_func_bodies = function_bodies.len();
         let mut local_relocs = Map::with_capacity(num_func_bodies);
+        let mut constant_relocs = Map::with_capacity(num_func_bodies);
         let mut external_relocs = Map::with_capacity(num_func_bodies);
 
         let mut trap_sink = TrapSink::new();
 
-        let compiled_functions: Result<Vec<(Vec<u8>, (RelocSink, LocalTrapSink))>, CompileError> =
-            function_bodies
-                .into_vec()
-                .par_iter()
-                .map_init(
-                    || Context::new(),
-                    |ctx, func| {
-                        let mut code_buf = Vec::new();
-                        ctx.func = func.to_owned();
-                        let mut reloc_sink = RelocSink::new();
-                        let mut local_trap_sink = LocalTrapSink::new();
-                        let mut stackmap_sink = NoopStackmapSink {};
-                        ctx.compile_and_emit(
-                            isa,
-                            &mut code_buf,
-                            &mut reloc_sink,
-                            &mut local_trap_sink,
-                            &mut stackmap_sink,
-                        )
-                        .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
-                        ctx.clear();
-                        Ok((code_buf, (reloc_sink, local_trap_sink)))
-                    },
-                )
-                .collect();
+        #[allow(clippy::type_complexity)]
+        let compiled_functions: Result<
+            Vec<(
+                Vec<u8>,
+                (RelocSink, LocalTrapSink, (Vec<(u32, u32)>, Vec<Variable>)),
+            )>,
+            CompileError,
+        > = function_bodies
+            .into_vec()
+            .par_iter()
+            .map_init(
+                || Context::new(),
+                |ctx, func| {
+                    let mut code_buf = Vec::new();
+                    ctx.func = func.to_owned();
+                    let mut reloc_sink = RelocSink::new();
+                    let mut local_trap_sink = LocalTrapSink::new();
+                    let mut stackmap_sink = NoopStackmapSink {};
+                    ctx.compile_and_emit(
+                        isa,
+                        &mut code_buf,
+                        &mut reloc_sink,
+                        &mut local_trap_sink,
+                        &mut stackmap_sink,
+                    )
+                    .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
+                    let debug_data = if generate_debug_info {
+                        let variables = debug::function_variables(ctx, isa)
+                            .map_err(|msg| CompileError::InternalError { msg })?;
+                        (debug::function_lines(&ctx.func, isa), variables)
+                    } else {
+                        (vec![], vec![])
+                    };
+                    ctx.clear();
+                    Ok((code_buf, (reloc_sink, local_trap_sink, debug_data)))
+                },
+            )
+            .collect();
 
         let compiled_functions = compiled_functions?;
         let mut total_size = 0;
         // We separate into two iterators, one iterable and one into iterable
-        let (code_bufs, sinks): (Vec<Vec<u8>>, Vec<(RelocSink, LocalTrapSink)>) =
-            compiled_functions.into_iter().unzip();
-        for (code_buf, (reloc_sink, mut local_trap_sink)) in code_bufs.iter().zip(sinks.into_iter())
+        #[allow(clippy::type_complexity)]
+        let (code_bufs, sinks): (
+            Vec<Vec<u8>>,
+            Vec<(RelocSink, LocalTrapSink, (Vec<(u32, u32)>, Vec<Variable>))>,
+        ) = compiled_functions.into_iter().unzip();
+        let mut function_debug_data = Vec::with_capacity(num_func_bodies);
+        for (code_buf, (reloc_sink, mut local_trap_sink, debug_data)) in
+            code_bufs.iter().zip(sinks.into_iter())
         {
             // Clear the local trap sink and consolidate all trap info
             // into a single location.
             trap_sink.drain_local(total_size, &mut local_trap_sink);
 
-            // Round up each function's size to pointer alignment.
-            total_size += round_up(code_buf.len(), mem::size_of::<usize>());
+            // Round up each function's size, with room for the jump of `FuncResolver::patch`,
+            // to pointer alignment.
+            total_size += round_up(code_buf.len().max(JUMP_SIZE), mem::size_of::<usize>());
 
             local_relocs.push(reloc_sink.local_relocs.into_boxed_slice());
+            constant_relocs.push(reloc_sink.constant_relocs.into_boxed_slice());
             external_relocs.push(reloc_sink.external_relocs.into_boxed_slice());
+            function_debug_data.push(debug_data);
         }
 
         let mut memory = Memory::with_size(total_size)
@@ -175,7 +205,8 @@ impl FuncResolverBuilder {
 
         let mut previous_end = 0;
         for compiled in code_bufs.iter() {
-            let new_end = previous_end + round_up(compiled.len(), mem::size_of::<usize>());
+            let new_end =
+                previous_end + round_up(compiled.len().max(JUMP_SIZE), mem::size_of::<usize>());
             unsafe {
                 memory.as_slice_mut()[previous_end..previous_end + compiled.len()]
                     .copy_from_slice(&compiled[..]);
@@ -184,6 +215,33 @@ impl FuncResolverBuilder {
             previous_end = new_end;
         }
 
+        let debug_object = if generate_debug_info {
+            let functions: Vec<FunctionDebugInfo> = map
+                .iter()
+                .zip(code_bufs.iter().zip(function_debug_data.iter()))
+                .map(|((index, &offset), (code_buf, (lines, variables)))| {
+                    let func_index = FuncIndex::new(index.index() + info.imported_functions.len());
+                    FunctionDebugInfo {
+                        name: info
+                            .func_names
+                            .get(&func_index)
+                            .cloned()
+                            .unwrap_or_else(|| format!("wasm-function[{}]", func_index.index())),
+                        address: memory.as_ptr() as u64 + offset as u64,
+                        code: code_buf,
+                        lines,
+                        variables,
+                    }
+                })
+                .collect();
+            Some(
+                debug::emit_object(info, memory.as_ptr() as u64, total_size as u64, &functions)
+                    .map_err(|msg| CompileError::InternalError { msg })?,
+            )
+        } else {
+            None
+        };
+
         let handler_data =
             HandlerData::new(Arc::new(trap_sink), memory.as_ptr() as _, memory.size());
 
@@ -191,11 +249,14 @@ impl FuncResolverBuilder {
             map,
             memory,
             local_relocs,
+            constant_relocs,
             external_relocs,
             import_len: info.imported_functions.len(),
+            debug_object,
         };
 
         func_resolver_builder.relocate_locals();
+        func_resolver_builder.relocate_constants()?;
 
         Ok((func_resolver_builder, handler_data))
     }
@@ -226,6 +287,34 @@ impl FuncResolverBuilder {
         }
     }
 
+    fn relocate_constants(&mut self) -> CompileResult<()> {
+        for (index, relocs) in self.constant_relocs.iter() {
+            let func_addr = lookup_func(&self.map, &self.memory, index)
+                .unwrap()
+                .as_ptr() as usize;
+
+            for reloc in relocs.iter() {
+                match reloc.reloc {
+                    // The constant pool follows the function body, and the displacement
+                    // is relative to the end of the 4-byte field.
+                    binemit::Reloc::X86PCRel4 => unsafe {
+                        let reloc_address = func_addr + reloc.offset as usize;
+                        let constant_address = func_addr + reloc.constant_offset as usize;
+                        let reloc_delta = constant_address.wrapping_sub(reloc_address + 4);
+
+                        write_unaligned(reloc_address as *mut u32, reloc_delta as u32);
+                    },
+                    reloc => {
+                        return Err(CompileError::InternalError {
+                            msg: format!("unsupported constant relocation: {}", reloc),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn finalize(
         mut self,
         signatures: &SliceMap<SigIndex, FuncSig>,
@@ -333,10 +422,14 @@ impl FuncResolverBuilder {
             trampolines: trampolines.to_trampoline_cache(),
         };
 
+        // Only now that the code is final can the debugger see it.
+        let debug_entry = self.debug_object.map(jit_debug::register);
+
         Ok((
             FuncResolver {
                 map: self.map,
                 memory: Arc::new(self.memory),
+                _debug_entry: debug_entry,
             },
             backend_cache,
         ))
@@ -350,14 +443,56 @@ unsafe impl Send for FuncResolver {}
 pub struct FuncResolver {
     map: Map<LocalFuncIndex, usize>,
     pub(crate) memory: Arc<Memory>,
+    /// Unregisters the debug info of the code when the module is dropped.
+    _debug_entry: Option<JitDebugEntry>,
 }
 
 impl FuncResolver {
     pub fn lookup(&self, index: LocalFuncIndex) -> Option<NonNull<vm::Func>> {
         lookup_func(&self.map, &self.memory, index)
     }
+
+    pub fn code(&self) -> &[u8] {
+        unsafe { self.memory.as_slice() }
+    }
+
+    pub fn local_function_offsets(&self) -> Vec<usize> {
+        self.map.iter().map(|(_, &offset)| offset).collect()
+    }
+
+    /// Makes the function at `index` jump to `target`, by writing the jump over its entry.
+    ///
+    /// The code must not run on other threads meanwhile.
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    pub unsafe fn patch(&self, index: LocalFuncIndex, target: usize) -> bool {
+        let offset = match self.map.get(index) {
+            Some(&offset) => offset,
+            None => return false,
+        };
+        // movabsq $target, %r11; jmpq *%r11
+        let mut jump = [0x49, 0xbb, 0, 0, 0, 0, 0, 0, 0, 0, 0x41, 0xff, 0xe3];
+        LittleEndian::write_u64(&mut jump[2..10], target as u64);
+        assert_eq!(jump.len(), JUMP_SIZE);
+
+        let code = self.memory.as_ptr();
+        let size = self.memory.size();
+        if libc::mprotect(code as _, size, libc::PROT_READ | libc::PROT_WRITE) != 0 {
+            return false;
+        }
+        code.add(offset)
+            .copy_from_nonoverlapping(jump.as_ptr(), jump.len());
+        libc::mprotect(code as _, size, libc::PROT_READ | libc::PROT_EXEC) == 0
+    }
+
+    #[cfg(not(all(unix, target_arch = "x86_64")))]
+    pub unsafe fn patch(&self, _index: LocalFuncIndex, _target: usize) -> bool {
+        false
+    }
 }
 
+/// The size of the jump written by `FuncResolver::patch`, which every function has room for.
+const JUMP_SIZE: usize = 13;
+
 #[inline]
 fn round_up(n: usize, multiple: usize) -> usize {
     (n + multiple - 1) & !(multiple - 1)
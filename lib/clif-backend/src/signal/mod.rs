@@ -7,7 +7,9 @@ use libc::c_void;
 use std::{any::Any, cell::Cell, ptr::NonNull, sync::Arc};
 use wasmer_runtime_core::{
     backend::RunnableModule,
+    codegen::BreakpointHandler,
     module::ModuleInfo,
+    structures::TypedIndex,
     typed_func::{Trampoline, Wasm, WasmTrapInfo},
     types::{LocalFuncIndex, SigIndex},
     vm,
@@ -38,6 +40,8 @@ pub struct Caller {
     handler_data: HandlerData,
     trampolines: Arc<Trampolines>,
     resolver: FuncResolver,
+    /// The handlers of the breakpoints of the compiled code, which refers to them by address.
+    _breakpoints: Vec<Box<BreakpointHandler>>,
 }
 
 impl Caller {
@@ -45,11 +49,13 @@ impl Caller {
         handler_data: HandlerData,
         trampolines: Arc<Trampolines>,
         resolver: FuncResolver,
+        breakpoints: Vec<Box<BreakpointHandler>>,
     ) -> Self {
         Self {
             handler_data,
             trampolines,
             resolver,
+            _breakpoints: breakpoints,
         }
     }
 }
@@ -112,6 +118,19 @@ impl RunnableModule for Caller {
         TRAP_EARLY_DATA.with(|cell| cell.set(Some(data)));
         trigger_trap()
     }
+
+    fn get_code(&self) -> Option<&[u8]> {
+        Some(self.resolver.code())
+    }
+
+    fn get_local_function_offsets(&self) -> Option<Vec<usize>> {
+        Some(self.resolver.local_function_offsets())
+    }
+
+    unsafe fn patch_local_function(&self, idx: usize, target_address: usize) -> bool {
+        self.resolver
+            .patch(LocalFuncIndex::new(idx), target_address)
+    }
 }
 
 unsafe impl Send for HandlerData {}
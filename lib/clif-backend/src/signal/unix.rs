@@ -18,6 +18,8 @@ use nix::sys::signal::{
 use std::cell::{Cell, UnsafeCell};
 use std::ptr;
 use std::sync::Once;
+#[cfg(target_arch = "x86_64")]
+use wasmer_runtime_core::fault::{capture_backtrace, get_fault_info};
 use wasmer_runtime_core::typed_func::WasmTrapInfo;
 
 extern "C" fn signal_trap_handler(
@@ -94,6 +96,7 @@ pub fn call_protected<T>(
                             TrapCode::IndirectCallToNull => WasmTrapInfo::CallIndirectOOB,
                             TrapCode::HeapOutOfBounds => WasmTrapInfo::MemoryOutOfBounds,
                             TrapCode::TableOutOfBounds => WasmTrapInfo::CallIndirectOOB,
+                            TrapCode::UnreachableCodeReached => WasmTrapInfo::Unreachable,
                             _ => WasmTrapInfo::Unknown,
                         },
                         Ok(SIGSEGV) | Ok(SIGBUS) => WasmTrapInfo::MemoryOutOfBounds,
@@ -137,6 +140,9 @@ pub unsafe fn do_unwind(signum: i32, siginfo: *const c_void, ucontext: *const c_
     }
 
     CAUGHT_ADDRESSES.with(|cell| cell.set(get_faulting_addr_and_ip(siginfo, ucontext)));
+    // Cranelift code keeps frame pointers, so the wasm frames can be walked.
+    #[cfg(target_arch = "x86_64")]
+    capture_backtrace(&get_fault_info(siginfo, ucontext));
 
     longjmp(jmp_buf as *mut ::nix::libc::c_void, signum)
 }
@@ -169,31 +169,33 @@ fn generate_func(func_sig: &FuncSig) -> ir::Function {
 
     let mut args_vec = Vec::with_capacity(func_sig.params().len() + 1);
     args_vec.push(vmctx_ptr);
-    for (index, wasm_ty) in func_sig.params().iter().enumerate() {
+    // Arguments and results are laid out in consecutive `u64` slots, with `V128`
+    // values taking two of them.
+    let mut args_offset = 0;
+    for wasm_ty in func_sig.params().iter() {
         let mem_flags = ir::MemFlags::trusted();
 
         let val = pos.ins().load(
             wasm_ty_to_clif(*wasm_ty),
             mem_flags,
             args_ptr,
-            (index * mem::size_of::<u64>()) as i32,
+            args_offset as i32,
         );
         args_vec.push(val);
+        args_offset += slot_size(*wasm_ty);
     }
 
     let call_inst = pos.ins().call_indirect(export_sig_ref, func_ptr, &args_vec);
 
     let return_values = pos.func.dfg.inst_results(call_inst).to_vec();
 
-    for (index, return_val) in return_values.iter().enumerate() {
+    let mut returns_offset = 0;
+    for (return_val, wasm_ty) in return_values.iter().zip(func_sig.returns().iter()) {
         let mem_flags = ir::MemFlags::trusted();
 
-        pos.ins().store(
-            mem_flags,
-            *return_val,
-            returns_ptr,
-            (index * mem::size_of::<u64>()) as i32,
-        );
+        pos.ins()
+            .store(mem_flags, *return_val, returns_ptr, returns_offset as i32);
+        returns_offset += slot_size(*wasm_ty);
     }
 
     pos.ins().return_(&[]);
@@ -201,6 +203,13 @@ fn generate_func(func_sig: &FuncSig) -> ir::Function {
     func
 }
 
+fn slot_size(ty: Type) -> usize {
+    match ty {
+        Type::V128 => 2 * mem::size_of::<u64>(),
+        _ => mem::size_of::<u64>(),
+    }
+}
+
 fn wasm_ty_to_clif(ty: Type) -> ir::types::Type {
     match ty {
         Type::I32 => ir::types::I32,
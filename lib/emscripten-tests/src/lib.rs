@@ -2,9 +2,13 @@
 mod tests {
     use std::sync::Arc;
     use wabt::wat2wasm;
-    use wasmer_emscripten::is_emscripten_module;
+    use wasmer_emscripten::{
+        generate_emscripten_env, is_emscripten_module, run_emscripten_instance, EmscriptenGlobals,
+        SideModuleCompiler,
+    };
     use wasmer_runtime_core::backend::Compiler;
     use wasmer_runtime_core::compile_with;
+    use wasmer_runtime_core::{error::CallResult, memory::Memory};
 
     #[cfg(feature = "clif")]
     fn get_compiler() -> impl Compiler {
@@ -50,4 +54,472 @@ mod tests {
         let module = Arc::new(module);
         assert!(!is_emscripten_module(&module));
     }
+
+    /// Runs the `_main` of the emscripten module of `wast`, once `setup` has prepared its globals,
+    /// and returns the result along with the memory of the module.
+    fn run_wast(
+        wast: &[u8],
+        setup: impl FnOnce(&mut EmscriptenGlobals),
+    ) -> (CallResult<()>, Memory) {
+        let wasm_binary = wat2wasm(wast.to_vec()).expect("Can't convert to wasm");
+        let module =
+            compile_with(&wasm_binary[..], &get_compiler()).expect("WASM can't be compiled");
+        let mut globals = EmscriptenGlobals::new(&module).expect("globals are valid");
+        setup(&mut globals);
+        let import_object = generate_emscripten_env(&mut globals);
+        let mut instance = module
+            .instantiate(&import_object)
+            .expect("WASM can't be instantiated");
+        let result = run_emscripten_instance(
+            &module,
+            &mut instance,
+            &mut globals,
+            "test",
+            vec![],
+            None,
+            vec![],
+        );
+        (result, globals.memory.clone())
+    }
+
+    fn read_u32(memory: &Memory, address: u32) -> u32 {
+        memory.view::<u32>()[address as usize / 4].get()
+    }
+
+    fn write_cstr(memory: &Memory, address: u32, string: &str) {
+        let view = memory.view::<u8>();
+        for (cell, byte) in view[address as usize..]
+            .iter()
+            .zip(string.bytes().chain(Some(0)))
+        {
+            cell.set(byte);
+        }
+    }
+
+    #[test]
+    fn should_load_side_modules() {
+        const SIDE_MODULE_BYTES: &[u8] = include_bytes!("tests/side_module.wast");
+        let side_module = wat2wasm(SIDE_MODULE_BYTES.to_vec()).expect("Can't convert to wasm");
+        // side modules start with a `dylink` section: no data, no functions, nothing needed
+        let dylink = b"\x06dylink\x00\x00\x00\x00\x00";
+        let mut wasm = side_module[..8].to_vec();
+        wasm.extend_from_slice(&[0, dylink.len() as u8]);
+        wasm.extend_from_slice(dylink);
+        wasm.extend_from_slice(&side_module[8..]);
+        let path = std::env::temp_dir().join("wasmer-emscripten-side-module.wasm");
+        std::fs::write(&path, wasm).unwrap();
+
+        const WAST_BYTES: &[u8] = include_bytes!("tests/dlopen.wast");
+        let (result, memory) = run_wast(WAST_BYTES, |globals| {
+            let compiler: SideModuleCompiler = Arc::new(|wasm: &[u8]| {
+                compile_with(wasm, &get_compiler()).map_err(|e| format!("{:?}", e))
+            });
+            globals.side_module_compiler = Some(compiler);
+            write_cstr(&globals.memory, 256, path.to_str().unwrap());
+        });
+        result.expect("the module runs");
+        assert_ne!(read_u32(&memory, 32), 0);
+        assert_eq!(read_u32(&memory, 16), 42);
+        // looking up a missing symbol fails, and sets the error
+        assert_eq!(read_u32(&memory, 20), 0);
+        assert_eq!(read_u32(&memory, 24), 1);
+        assert_eq!(read_u32(&memory, 28), 0);
+    }
+
+    #[test]
+    fn should_longjmp_to_setjmp() {
+        use wasmer_runtime_core::error::{CallError, RuntimeError};
+
+        const WAST_BYTES: &[u8] = include_bytes!("tests/setjmp.wast");
+        let run = |arg: u32| {
+            run_wast(WAST_BYTES, |globals| {
+                globals.memory.view::<u32>()[2].set(arg);
+            })
+        };
+
+        let (result, memory) = run(42);
+        result.expect("the module runs");
+        assert_eq!(read_u32(&memory, 20), 1);
+        assert_eq!(read_u32(&memory, 16), 42);
+
+        // without a jump, `setjmp` only returns once
+        let (result, memory) = run(0);
+        result.expect("the module runs");
+        assert_eq!(read_u32(&memory, 20), 0);
+
+        // the traps aren't taken for jumps: `invoke_vi` raises them again
+        let (result, memory) = run(1);
+        match result {
+            Err(CallError::Runtime(RuntimeError::Error { data, .. })) => {
+                match data.downcast_ref::<RuntimeError>() {
+                    Some(RuntimeError::Trap { .. }) => {}
+                    _ => panic!("unexpected error data"),
+                }
+            }
+            _ => panic!("the trap isn't raised again"),
+        }
+        assert_eq!(read_u32(&memory, 20), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_only_open_granted_sockets() {
+        use std::net::{SocketAddr, TcpListener};
+        use wasmer_emscripten::NetworkGrants;
+
+        /// Writes `addr` as a `sockaddr_in` of emscripten's libc.
+        fn write_sockaddr(memory: &Memory, address: u32, addr: &SocketAddr) {
+            let ip = match addr {
+                SocketAddr::V4(addr) => addr.ip().octets(),
+                SocketAddr::V6(_) => panic!("only IPv4 addresses are written"),
+            };
+            let mut bytes = vec![2, 0];
+            bytes.extend_from_slice(&addr.port().to_be_bytes());
+            bytes.extend_from_slice(&ip);
+            bytes.extend_from_slice(&[0; 8]);
+            let view = memory.view::<u8>();
+            for (cell, byte) in view[address as usize..].iter().zip(bytes) {
+                cell.set(byte);
+            }
+        }
+
+        const WAST_BYTES: &[u8] = include_bytes!("tests/sockets.wast");
+        const EACCES: i32 = 13;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let granted = listener.local_addr().unwrap();
+        // a free port, as its listener is closed right away
+        let other = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let run = |network: NetworkGrants| {
+            run_wast(WAST_BYTES, |globals| {
+                write_sockaddr(&globals.memory, 128, &granted);
+                write_sockaddr(&globals.memory, 160, &other);
+                globals.network = network;
+            })
+        };
+
+        // without any grant, no socket can be created
+        let (result, memory) = run(NetworkGrants::default());
+        result.expect("the module runs");
+        assert_eq!(read_u32(&memory, 16) as i32, -EACCES);
+
+        let mut network = NetworkGrants::default();
+        network.allow_connect(granted);
+        let (result, memory) = run(network);
+        result.expect("the module runs");
+        assert!(read_u32(&memory, 16) as i32 >= 0);
+        assert_eq!(read_u32(&memory, 20), 0);
+        assert_eq!(read_u32(&memory, 24) as i32, -EACCES);
+
+        // binding is granted apart from connecting
+        let mut network = NetworkGrants::default();
+        network.allow_listen(other);
+        let (result, memory) = run(network);
+        result.expect("the module runs");
+        assert!(read_u32(&memory, 16) as i32 >= 0);
+        assert_eq!(read_u32(&memory, 20) as i32, -EACCES);
+        assert_eq!(read_u32(&memory, 24), 0);
+    }
+
+    #[test]
+    fn should_serve_files_from_the_vfs() {
+        use wasmer_emscripten::EmscriptenVfs;
+        use wasmer_wasi::state::MemFs;
+
+        let fs = Arc::new(MemFs::new());
+        fs.create_dir_all("/data").unwrap();
+        fs.write_file("/data/in.txt", "contents").unwrap();
+        let mut vfs = EmscriptenVfs::new(fs.clone());
+        vfs.preopen_dir("/data");
+
+        const WAST_BYTES: &[u8] = include_bytes!("tests/vfs.wast");
+        let (result, memory) = run_wast(WAST_BYTES, |globals| {
+            write_cstr(&globals.memory, 256, "/data/in.txt");
+            write_cstr(&globals.memory, 320, "/data/../data/out.txt");
+            // the paths out of the directories of the vfs don't exist, even on the host
+            write_cstr(&globals.memory, 384, "/data/../etc/passwd");
+            globals.vfs = Some(vfs);
+        });
+        result.expect("the module runs");
+
+        // the fds of the vfs are far above the fds of the host
+        assert!(read_u32(&memory, 16) >= 1 << 30);
+        assert_eq!(read_u32(&memory, 20), 8);
+        let contents: Vec<u8> = memory.view::<u8>()[512..520]
+            .iter()
+            .map(|cell| cell.get())
+            .collect();
+        assert_eq!(contents, b"contents");
+        assert_eq!(read_u32(&memory, 24), 0);
+
+        assert!(read_u32(&memory, 28) >= 1 << 30);
+        assert_eq!(read_u32(&memory, 32), 5);
+        assert_eq!(read_u32(&memory, 36), 0);
+        assert_eq!(fs.read_file("/data/out.txt").unwrap(), b"hello");
+
+        const ENOENT: i32 = 2;
+        assert_eq!(read_u32(&memory, 40) as i32, -ENOENT);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn should_render_to_the_graphics_host() {
+        use std::collections::VecDeque;
+        use std::sync::Mutex;
+        use wasmer_emscripten::{GraphicsEvent, GraphicsHost};
+        use wasmer_runtime_core::types::Value;
+
+        #[derive(Default)]
+        struct RecordingHost {
+            windows: Mutex<Vec<(String, u32, u32)>>,
+            frames: Mutex<Vec<(u32, u32, u32, Vec<u8>)>>,
+            events: Mutex<VecDeque<GraphicsEvent>>,
+            gl_calls: Mutex<Vec<(String, Vec<Value>)>>,
+        }
+
+        impl GraphicsHost for RecordingHost {
+            fn open_window(&self, title: &str, width: u32, height: u32) {
+                let window = (title.to_string(), width, height);
+                self.windows.lock().unwrap().push(window);
+            }
+
+            fn present_pixels(&self, width: u32, height: u32, pitch: u32, pixels: &[u8]) {
+                let frame = (width, height, pitch, pixels.to_vec());
+                self.frames.lock().unwrap().push(frame);
+            }
+
+            fn poll_event(&self) -> Option<GraphicsEvent> {
+                self.events.lock().unwrap().pop_front()
+            }
+
+            fn gl_call(&self, _memory: &Memory, name: &str, args: &[Value]) -> Option<i32> {
+                let call = (name.to_string(), args.to_vec());
+                self.gl_calls.lock().unwrap().push(call);
+                None
+            }
+        }
+
+        let host = Arc::new(RecordingHost::default());
+        host.events
+            .lock()
+            .unwrap()
+            .push_back(GraphicsEvent::KeyDown {
+                scancode: 4,
+                keycode: 97,
+            });
+
+        const WAST_BYTES: &[u8] = include_bytes!("tests/graphics.wast");
+        let (result, memory) = run_wast(WAST_BYTES, |globals| {
+            globals.graphics = Some(host.clone() as Arc<dyn GraphicsHost>);
+        });
+        result.expect("the module runs");
+
+        assert_ne!(read_u32(&memory, 16), 0);
+        assert_eq!(read_u32(&memory, 20), 0);
+        assert_eq!(read_u32(&memory, 24), 0);
+        assert_eq!(*host.windows.lock().unwrap(), [("demo".to_string(), 2, 2)]);
+        // the surfaces are RGBA
+        let red = [0xff, 0, 0, 0xff];
+        let pixels: Vec<u8> = red.iter().cycle().take(16).cloned().collect();
+        assert_eq!(*host.frames.lock().unwrap(), [(2, 2, 8, pixels)]);
+
+        // the key event fills the `SDL_KeyboardEvent`, and no event is left
+        assert_eq!(read_u32(&memory, 28), 1);
+        assert_eq!(read_u32(&memory, 64), 0x300);
+        assert_eq!(read_u32(&memory, 80), 4);
+        assert_eq!(read_u32(&memory, 84), 97);
+        assert_eq!(read_u32(&memory, 32), 0);
+
+        // the GL calls go to the host first, and the headless implementation gives the ids
+        let gl_calls = host.gl_calls.lock().unwrap();
+        assert_eq!(gl_calls[0].0, "glClear");
+        assert_eq!(gl_calls[0].1, [Value::I32(0x4000)]);
+        assert_eq!(gl_calls[1].0, "glGenBuffers");
+        let (first, second) = (read_u32(&memory, 128), read_u32(&memory, 132));
+        assert!(first != 0 && second != 0 && first != second);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_translate_the_termios_ioctls() {
+        use std::os::unix::{io::AsRawFd, net::UnixStream};
+
+        // the terminal is the secondary side of a pseudoterminal
+        let (primary, secondary) = unsafe {
+            let primary = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(primary >= 0);
+            assert_eq!(libc::grantpt(primary), 0);
+            assert_eq!(libc::unlockpt(primary), 0);
+            let secondary = libc::open(libc::ptsname(primary), libc::O_RDWR | libc::O_NOCTTY);
+            assert!(secondary >= 0);
+            (primary, secondary)
+        };
+        let (socket, _other) = UnixStream::pair().unwrap();
+
+        const WAST_BYTES: &[u8] = include_bytes!("tests/tty.wast");
+        let (result, memory) = run_wast(WAST_BYTES, |globals| {
+            let view = globals.memory.view::<u32>();
+            view[2].set(secondary as u32);
+            view[11].set(socket.as_raw_fd() as u32);
+        });
+        result.expect("the module runs");
+        assert_eq!(read_u32(&memory, 16), 0);
+        assert_eq!(read_u32(&memory, 20), 0);
+        assert_eq!(read_u32(&memory, 24), 0);
+
+        // the terminal got the flags in the layout of the host
+        let mut host: libc::termios = unsafe { std::mem::zeroed() };
+        assert_eq!(unsafe { libc::tcgetattr(secondary, &mut host) }, 0);
+        assert_eq!(host.c_lflag & (libc::ECHO | libc::ICANON), 0);
+        assert_ne!(host.c_lflag & libc::ISIG, 0);
+
+        // and the program reads them back in its own layout, at 38400 bauds
+        const GUEST_ECHO: u32 = 0o10;
+        const GUEST_ICANON: u32 = 0o2;
+        const GUEST_ISIG: u32 = 0o1;
+        let lflag = read_u32(&memory, 256 + 12);
+        assert_eq!(lflag & (GUEST_ECHO | GUEST_ICANON), 0);
+        assert_eq!(lflag & GUEST_ISIG, GUEST_ISIG);
+        assert_eq!(read_u32(&memory, 256 + 52), 0o17);
+        assert_eq!(read_u32(&memory, 256 + 56), 0o17);
+
+        const ENOTTY: i32 = 25;
+        assert_eq!(read_u32(&memory, 28) as i32, -ENOTTY);
+
+        unsafe {
+            libc::close(secondary);
+            libc::close(primary);
+        }
+    }
+
+    #[test]
+    fn should_detect_the_abi_of_emscripten() {
+        use wasmer_emscripten::EmscriptenAbi;
+
+        let detect = |imports_and_exports: &str| {
+            let wat = format!("(module {})", imports_and_exports);
+            let wasm_binary = wat2wasm(wat).expect("Can't convert to wasm");
+            let module =
+                compile_with(&wasm_binary[..], &get_compiler()).expect("WASM can't be compiled");
+            EmscriptenAbi::detect(&module)
+        };
+
+        assert_eq!(
+            detect(r#"(import "env" "abortOnCannotGrowMemory" (func (result i32)))"#),
+            EmscriptenAbi::LegacyFastcomp
+        );
+        assert_eq!(
+            detect(r#"(import "env" "abortOnCannotGrowMemory" (func (param i32) (result i32)))"#),
+            EmscriptenAbi::Fastcomp
+        );
+        assert_eq!(detect(""), EmscriptenAbi::Fastcomp);
+        assert_eq!(
+            detect(
+                r#"(import "wasi_snapshot_preview1" "fd_write"
+                     (func (param i32 i32 i32 i32) (result i32)))"#
+            ),
+            EmscriptenAbi::Upstream
+        );
+        assert_eq!(
+            detect(r#"(func (export "__wasm_call_ctors"))"#),
+            EmscriptenAbi::Upstream
+        );
+    }
+
+    #[test]
+    fn should_report_unsupported_imports() {
+        use wasmer_emscripten::{check_emscripten_imports, emscripten_abi_version};
+
+        let wat = r#"
+            (module
+              (import "env" "memory" (memory 256 256))
+              (import "env" "table" (table 4 anyfunc))
+              (import "env" "_getenv" (func (param i32) (result i32)))
+              (import "env" "_not_in_wasmer" (func))
+              (import "env" "notInWasmerEither" (global i32)))
+        "#;
+        let wasm_binary = wat2wasm(wat).expect("Can't convert to wasm");
+        // the metadata 0.1 of the ABI 0.20, in a custom section right after the header
+        let metadata = b"\x13emscripten_metadata\x00\x01\x00\x14";
+        let mut wasm = wasm_binary[..8].to_vec();
+        wasm.extend_from_slice(&[0, metadata.len() as u8]);
+        wasm.extend_from_slice(metadata);
+        wasm.extend_from_slice(&wasm_binary[8..]);
+
+        let module = compile_with(&wasm[..], &get_compiler()).expect("WASM can't be compiled");
+        assert_eq!(emscripten_abi_version(&module), Some((0, 20)));
+        let mut globals = EmscriptenGlobals::new(&module).expect("globals are valid");
+        let import_object = generate_emscripten_env(&mut globals);
+        assert_eq!(
+            check_emscripten_imports(&module, &import_object),
+            Err(
+                "This module was built by emscripten fastcomp (ABI 0.20), and wasmer doesn't \
+                 support these imports of it: env._not_in_wasmer, env.notInWasmerEither"
+                    .to_string()
+            )
+        );
+    }
+
+    // The other backends don't support the threads feature.
+    #[cfg(feature = "llvm")]
+    #[test]
+    fn should_run_pthreads_in_instances_of_their_own() {
+        use wasmer_runtime_core::{
+            backend::{CompilerConfig, Features},
+            compile_with_config,
+            table::Element,
+        };
+
+        const WAST_BYTES: &[u8] = include_bytes!("tests/pthread.wast");
+        let mut features = wabt::Features::new();
+        features.enable_threads();
+        let wasm_binary = wabt::wat2wasm_with_features(WAST_BYTES.to_vec(), features)
+            .expect("Can't convert to wasm");
+        let module = compile_with_config(
+            &wasm_binary[..],
+            &get_compiler(),
+            CompilerConfig {
+                features: Features {
+                    threads: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .expect("WASM can't be compiled");
+        let mut globals = EmscriptenGlobals::new(&module).expect("globals are valid");
+        let import_object = generate_emscripten_env(&mut globals);
+        let mut instance = module
+            .instantiate(&import_object)
+            .expect("WASM can't be instantiated");
+        // a function added to the table at runtime, as `addFunction` does
+        let forty_two = instance.dyn_func("_forty_two").unwrap();
+        globals
+            .table
+            .set(2, Element::Anyfunc(forty_two.into()))
+            .unwrap();
+
+        let result = run_emscripten_instance(
+            &module,
+            &mut instance,
+            &mut globals,
+            "test",
+            vec![],
+            None,
+            vec![],
+        );
+        result.expect("the module runs");
+        let memory = &globals.memory;
+        assert_eq!(read_u32(memory, 24), 0);
+        assert_eq!(read_u32(memory, 32), 0);
+        assert_eq!(read_u32(memory, 36), 7);
+        // the thread didn't initialize the memory again
+        assert_eq!(read_u32(memory, 16), 111);
+        assert_eq!(read_u32(memory, 20), 42);
+        // the functions of the module run in the instance of the thread
+        assert_eq!(read_u32(memory, 40), read_u32(memory, 28));
+        assert_ne!(read_u32(memory, 40), read_u32(memory, 44));
+    }
 }
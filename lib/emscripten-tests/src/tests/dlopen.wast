@@ -0,0 +1,35 @@
+(module
+ (type $i (func (result i32)))
+ (import "env" "memory" (memory 256 256))
+ (import "env" "table" (table 4 anyfunc))
+ (import "env" "_dlopen" (func $dlopen (param i32 i32) (result i32)))
+ (import "env" "_dlsym" (func $dlsym (param i32 i32) (result i32)))
+ (import "env" "_dlclose" (func $dlclose (param i32) (result i32)))
+ (import "env" "_dlerror" (func $dlerror (result i32)))
+ ;; the path of the side module is written at 256 by the test
+ (data (i32.const 128) "answer\00")
+ (data (i32.const 144) "missing\00")
+ ;; a bump allocator from 8MiB, its top at 12
+ (func $malloc (export "_malloc") (param i32) (result i32)
+  (local i32)
+  (set_local 1 (i32.load (i32.const 12)))
+  (if (i32.eqz (get_local 1))
+   (then (set_local 1 (i32.const 8388608))))
+  (i32.store (i32.const 12) (i32.add (get_local 1) (get_local 0)))
+  (get_local 1))
+ (func (export "_free") (param i32))
+ ;; stores the handle of the side module at 32, its answer at 16, the result of looking up a
+ ;; missing symbol at 20, whether there was an error then at 24, and the result of dlclose at 28
+ (func (export "_main") (result i32)
+  (local $handle i32)
+  (set_local $handle (call $dlopen (i32.const 256) (i32.const 0)))
+  (i32.store (i32.const 32) (get_local $handle))
+  (if (i32.eqz (get_local $handle))
+   (then (return (i32.const 1))))
+  (i32.store (i32.const 16)
+   (call_indirect (type $i) (call $dlsym (get_local $handle) (i32.const 128))))
+  (i32.store (i32.const 20) (call $dlsym (get_local $handle) (i32.const 144)))
+  (i32.store (i32.const 24) (i32.ne (call $dlerror) (i32.const 0)))
+  (i32.store (i32.const 28) (call $dlclose (get_local $handle)))
+  (i32.const 0))
+)
@@ -0,0 +1,40 @@
+(module
+ (import "env" "memory" (memory 256 256))
+ (import "env" "table" (table 4 anyfunc))
+ (import "env" "_SDL_WM_SetCaption" (func $set_caption (param i32 i32)))
+ (import "env" "_SDL_SetVideoMode" (func $set_video_mode (param i32 i32 i32 i32) (result i32)))
+ (import "env" "_SDL_MapRGB" (func $map_rgb (param i32 i32 i32 i32) (result i32)))
+ (import "env" "_SDL_FillRect" (func $fill_rect (param i32 i32 i32) (result i32)))
+ (import "env" "_SDL_Flip" (func $flip (param i32) (result i32)))
+ (import "env" "_SDL_PollEvent" (func $poll_event (param i32) (result i32)))
+ (import "env" "_glClear" (func $gl_clear (param i32)))
+ (import "env" "_glGenBuffers" (func $gl_gen_buffers (param i32 i32)))
+ (data (i32.const 256) "demo\00")
+ ;; a bump allocator from 8MiB, its top at 12
+ (func (export "_malloc") (param i32) (result i32)
+  (local i32)
+  (set_local 1 (i32.load (i32.const 12)))
+  (if (i32.eqz (get_local 1))
+   (then (set_local 1 (i32.const 8388608))))
+  (i32.store (i32.const 12) (i32.add (get_local 1) (get_local 0)))
+  (get_local 1))
+ (func (export "_free") (param i32))
+ ;; fills a 2x2 screen with red and shows it, then polls two events to 64, and makes GL calls.
+ ;; Stores the screen at 16, the results of filling it at 20 and showing it at 24, the results of
+ ;; polling at 28 and 32, and the ids of two GL buffers at 128
+ (func (export "_main") (result i32)
+  (local $screen i32)
+  (call $set_caption (i32.const 256) (i32.const 0))
+  (set_local $screen (call $set_video_mode (i32.const 2) (i32.const 2) (i32.const 32) (i32.const 0)))
+  (i32.store (i32.const 16) (get_local $screen))
+  (i32.store (i32.const 20)
+   (call $fill_rect (get_local $screen) (i32.const 0)
+    (call $map_rgb (i32.const 0) (i32.const 255) (i32.const 0) (i32.const 0))))
+  (i32.store (i32.const 24) (call $flip (get_local $screen)))
+  (i32.store (i32.const 28) (call $poll_event (i32.const 64)))
+  (i32.store (i32.const 32) (call $poll_event (i32.const 0)))
+  ;; GL_COLOR_BUFFER_BIT
+  (call $gl_clear (i32.const 16384))
+  (call $gl_gen_buffers (i32.const 2) (i32.const 128))
+  (i32.const 0))
+)
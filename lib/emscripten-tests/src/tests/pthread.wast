@@ -0,0 +1,47 @@
+(module
+ (type $i (func (result i32)))
+ (type $ii (func (param i32) (result i32)))
+ (import "env" "memory" (memory 256 256 shared))
+ (import "env" "table" (table 4 anyfunc))
+ (import "env" "_pthread_create" (func $pthread_create (param i32 i32 i32 i32) (result i32)))
+ (import "env" "_pthread_join" (func $pthread_join (param i32 i32) (result i32)))
+ (import "env" "_pthread_self" (func $pthread_self (result i32)))
+ ;; a counter, which would be reset if the thread initialized the memory again
+ (data (i32.const 16) "\64\00\00\00")
+ (elem (i32.const 1) $routine)
+ ;; a bump allocator from 8MiB, its top at 12
+ (func $memalign (export "_memalign") (param i32 i32) (result i32)
+  (local i32)
+  (set_local 2 (i32.load (i32.const 12)))
+  (if (i32.eqz (get_local 2))
+   (then (set_local 2 (i32.const 8388608))))
+  (set_local 2
+   (i32.and
+    (i32.add (get_local 2) (i32.sub (get_local 0) (i32.const 1)))
+    (i32.sub (i32.const 0) (get_local 0))))
+  (i32.store (i32.const 12) (i32.add (get_local 2) (get_local 1)))
+  (get_local 2))
+ (func (export "_malloc") (param i32) (result i32)
+  (call $memalign (i32.const 16) (get_local 0)))
+ (func (export "_free") (param i32))
+ (func (export "dynCall_ii") (param i32 i32) (result i32)
+  (call_indirect (type $ii) (get_local 1) (get_local 0)))
+ (func (export "_forty_two") (result i32)
+  (i32.const 42))
+ ;; increments the counter, stores the result of the function the test puts at 2 in the table at
+ ;; 20 and the thread it runs in at 40
+ (func $routine (param i32) (result i32)
+  (i32.store (i32.const 16) (i32.add (i32.load (i32.const 16)) (i32.const 1)))
+  (i32.store (i32.const 20) (call_indirect (type $i) (i32.const 2)))
+  (i32.store (i32.const 40) (call $pthread_self))
+  (i32.add (get_local 0) (i32.const 1)))
+ ;; stores the result of pthread_create at 24, the thread at 28, the result of pthread_join at 32,
+ ;; the value the thread returned at 36 and the main thread at 44
+ (func (export "_main") (result i32)
+  (i32.store (i32.const 16) (i32.add (i32.load (i32.const 16)) (i32.const 10)))
+  (i32.store (i32.const 44) (call $pthread_self))
+  (i32.store (i32.const 24)
+   (call $pthread_create (i32.const 28) (i32.const 0) (i32.const 1) (i32.const 6)))
+  (i32.store (i32.const 32) (call $pthread_join (i32.load (i32.const 28)) (i32.const 36)))
+  (i32.const 0))
+)
@@ -0,0 +1,62 @@
+(module
+ (type $vi (func (param i32)))
+ (import "env" "memory" (memory 256 256))
+ (import "env" "table" (table 4 anyfunc))
+ (import "env" "_saveSetjmp" (func $saveSetjmp (param i32 i32 i32 i32) (result i32)))
+ (import "env" "_testSetjmp" (func $testSetjmp (param i32 i32 i32) (result i32)))
+ (import "env" "_longjmp" (func $longjmp (param i32 i32)))
+ (import "env" "getTempRet0" (func $getTempRet0 (result i32)))
+ (import "env" "invoke_vi" (func $invoke_vi (param i32 i32)))
+ (elem (i32.const 1) $jump)
+ ;; a bump allocator from 8MiB, its top at 12
+ (func $malloc (export "_malloc") (param i32) (result i32)
+  (local i32)
+  (set_local 1 (i32.load (i32.const 12)))
+  (if (i32.eqz (get_local 1))
+   (then (set_local 1 (i32.const 8388608))))
+  (i32.store (i32.const 12) (i32.add (get_local 1) (get_local 0)))
+  (get_local 1))
+ (func (export "_free") (param i32))
+ (func (export "stackSave") (result i32)
+  (i32.const 0))
+ (func (export "stackRestore") (param i32))
+ ;; __THREW__ is at 0 and __threwValue at 4
+ (func (export "setThrew") (param i32 i32)
+  (if (i32.eqz (i32.load (i32.const 0)))
+   (then
+    (i32.store (i32.const 0) (get_local 0))
+    (i32.store (i32.const 4) (get_local 1)))))
+ (func (export "dynCall_vi") (param i32 i32)
+  (call_indirect (type $vi) (get_local 1) (get_local 0)))
+ ;; returns for 0, traps for 1, and jumps to the `setjmp` of the jmp_buf at 64 with the others
+ (func $jump (param i32)
+  (if (i32.eqz (get_local 0))
+   (then (return)))
+  (if (i32.eq (get_local 0) (i32.const 1))
+   (then (unreachable)))
+  (call $longjmp (i32.const 64) (get_local 0)))
+ ;; `if (setjmp(buf)) { ... } else jump(arg)` as emscripten compiles it, with the argument written
+ ;; at 8 by the test. Stores the label of the `setjmp` the jump returned to at 20, and the value
+ ;; `setjmp` returned then at 16
+ (func (export "_main") (result i32)
+  (local $table i32)
+  (local $size i32)
+  (local $threw i32)
+  (set_local $table (call $malloc (i32.const 40)))
+  (i32.store (get_local $table) (i32.const 0))
+  (set_local $table
+   (call $saveSetjmp (i32.const 64) (i32.const 1) (get_local $table) (i32.const 4)))
+  (set_local $size (call $getTempRet0))
+  (i32.store (i32.const 0) (i32.const 0))
+  (call $invoke_vi (i32.const 1) (i32.load (i32.const 8)))
+  (set_local $threw (i32.load (i32.const 0)))
+  (i32.store (i32.const 0) (i32.const 0))
+  (if (i32.and
+       (i32.ne (get_local $threw) (i32.const 0))
+       (i32.ne (i32.load (i32.const 4)) (i32.const 0)))
+   (then
+    (i32.store (i32.const 20)
+     (call $testSetjmp (i32.load (get_local $threw)) (get_local $table) (get_local $size)))
+    (i32.store (i32.const 16) (i32.load (i32.const 4)))))
+  (i32.const 0))
+)
@@ -0,0 +1,37 @@
+(module
+ (import "env" "memory" (memory 256 256))
+ (import "env" "table" (table 4 anyfunc))
+ (import "env" "___syscall102" (func $syscall102 (param i32 i32) (result i32)))
+ ;; a bump allocator from 8MiB, its top at 12
+ (func (export "_malloc") (param i32) (result i32)
+  (local i32)
+  (set_local 1 (i32.load (i32.const 12)))
+  (if (i32.eqz (get_local 1))
+   (then (set_local 1 (i32.const 8388608))))
+  (i32.store (i32.const 12) (i32.add (get_local 1) (get_local 0)))
+  (get_local 1))
+ (func (export "_free") (param i32))
+ ;; calls socketcall with the arguments written at 80
+ (func $socketcall (param $call i32) (param i32 i32 i32) (result i32)
+  (i32.store (i32.const 64) (get_local $call))
+  (i32.store (i32.const 68) (i32.const 80))
+  (i32.store (i32.const 80) (get_local 1))
+  (i32.store (i32.const 84) (get_local 2))
+  (i32.store (i32.const 88) (get_local 3))
+  (call $syscall102 (i32.const 102) (i32.const 64)))
+ ;; stores the result of creating a TCP socket at 16, and if it succeeds, the results of
+ ;; connecting it to the address the test writes at 128 at 20, and of binding another one to the
+ ;; address at 160 at 24
+ (func (export "_main") (result i32)
+  (local $fd i32)
+  (set_local $fd (call $socketcall (i32.const 1) (i32.const 2) (i32.const 1) (i32.const 0)))
+  (i32.store (i32.const 16) (get_local $fd))
+  (if (i32.lt_s (get_local $fd) (i32.const 0))
+   (then (return (i32.const 0))))
+  (i32.store (i32.const 20)
+   (call $socketcall (i32.const 3) (get_local $fd) (i32.const 128) (i32.const 16)))
+  (set_local $fd (call $socketcall (i32.const 1) (i32.const 2) (i32.const 1) (i32.const 0)))
+  (i32.store (i32.const 24)
+   (call $socketcall (i32.const 2) (get_local $fd) (i32.const 160) (i32.const 16)))
+  (i32.const 0))
+)
@@ -0,0 +1,32 @@
+(module
+ (import "env" "memory" (memory 256 256))
+ (import "env" "table" (table 4 anyfunc))
+ (import "env" "___syscall54" (func $ioctl (param i32 i32) (result i32)))
+ ;; a bump allocator from 8MiB, its top at 12
+ (func (export "_malloc") (param i32) (result i32)
+  (local i32)
+  (set_local 1 (i32.load (i32.const 12)))
+  (if (i32.eqz (get_local 1))
+   (then (set_local 1 (i32.const 8388608))))
+  (i32.store (i32.const 12) (i32.add (get_local 1) (get_local 0)))
+  (get_local 1))
+ (func (export "_free") (param i32))
+ ;; runs the ioctl `request` of `fd` with `argp`
+ (func $tty_ioctl (param $fd i32) (param $request i32) (param $argp i32) (result i32)
+  (i32.store (i32.const 64) (get_local $fd))
+  (i32.store (i32.const 68) (get_local $request))
+  (i32.store (i32.const 72) (get_local $argp))
+  (call $ioctl (i32.const 54) (i32.const 64)))
+ ;; turns off the echo and the canonical mode of the terminal whose fd the test writes at 8, like
+ ;; a line editor does, reading its `struct termios` to 128 and back to 256 once set. Stores the
+ ;; results of TCGETS at 16, of TCSETS at 20, of TCGETS again at 24, and of TCGETS on the fd the
+ ;; test writes at 44, which isn't a terminal, at 28
+ (func (export "_main") (result i32)
+  (i32.store (i32.const 16) (call $tty_ioctl (i32.load (i32.const 8)) (i32.const 21505) (i32.const 128)))
+  ;; c_lflag &= ~(ECHO | ICANON)
+  (i32.store (i32.const 140) (i32.and (i32.load (i32.const 140)) (i32.const -11)))
+  (i32.store (i32.const 20) (call $tty_ioctl (i32.load (i32.const 8)) (i32.const 21506) (i32.const 128)))
+  (i32.store (i32.const 24) (call $tty_ioctl (i32.load (i32.const 8)) (i32.const 21505) (i32.const 256)))
+  (i32.store (i32.const 28) (call $tty_ioctl (i32.load (i32.const 44)) (i32.const 21505) (i32.const 256)))
+  (i32.const 0))
+)
@@ -0,0 +1,49 @@
+(module
+ (import "env" "memory" (memory 256 256))
+ (import "env" "table" (table 4 anyfunc))
+ (import "env" "___syscall3" (func $read (param i32 i32) (result i32)))
+ (import "env" "___syscall4" (func $write (param i32 i32) (result i32)))
+ (import "env" "___syscall5" (func $open (param i32 i32) (result i32)))
+ (import "env" "___syscall6" (func $close (param i32 i32) (result i32)))
+ (data (i32.const 192) "hello")
+ ;; a bump allocator from 8MiB, its top at 12
+ (func (export "_malloc") (param i32) (result i32)
+  (local i32)
+  (set_local 1 (i32.load (i32.const 12)))
+  (if (i32.eqz (get_local 1))
+   (then (set_local 1 (i32.const 8388608))))
+  (i32.store (i32.const 12) (i32.add (get_local 1) (get_local 0)))
+  (get_local 1))
+ (func (export "_free") (param i32))
+ ;; writes the varargs of a syscall at 64
+ (func $args (param i32 i32 i32) (result i32)
+  (i32.store (i32.const 64) (get_local 0))
+  (i32.store (i32.const 68) (get_local 1))
+  (i32.store (i32.const 72) (get_local 2))
+  (i32.const 64))
+ ;; reads the file at the path the test writes at 256 to 512, writes "hello" to the file at the
+ ;; path at 320 and opens the one at the path at 384. Stores the fd of the first file at 16, the
+ ;; number of bytes read at 20, the result of closing it at 24, the fd of the second file at 28,
+ ;; the number of bytes written at 32, the result of closing it at 36, and the result of opening
+ ;; the third file at 40
+ (func (export "_main") (result i32)
+  (local $fd i32)
+  (set_local $fd
+   (call $open (i32.const 5) (call $args (i32.const 256) (i32.const 0) (i32.const 0))))
+  (i32.store (i32.const 16) (get_local $fd))
+  (i32.store (i32.const 20)
+   (call $read (i32.const 3) (call $args (get_local $fd) (i32.const 512) (i32.const 64))))
+  (i32.store (i32.const 24)
+   (call $close (i32.const 6) (call $args (get_local $fd) (i32.const 0) (i32.const 0))))
+  ;; O_WRONLY | O_CREAT
+  (set_local $fd
+   (call $open (i32.const 5) (call $args (i32.const 320) (i32.const 65) (i32.const 420))))
+  (i32.store (i32.const 28) (get_local $fd))
+  (i32.store (i32.const 32)
+   (call $write (i32.const 4) (call $args (get_local $fd) (i32.const 192) (i32.const 5))))
+  (i32.store (i32.const 36)
+   (call $close (i32.const 6) (call $args (get_local $fd) (i32.const 0) (i32.const 0))))
+  (i32.store (i32.const 40)
+   (call $open (i32.const 5) (call $args (i32.const 384) (i32.const 0) (i32.const 0))))
+  (i32.const 0))
+)
@@ -0,0 +1,268 @@
+//! The generations of emscripten whose programs wasmer runs.
+//!
+//! Emscripten changes the imports and exports of the programs it builds between its releases,
+//! so the glue finds out from the module which one built it, with `EmscriptenAbi::detect`, and
+//! adapts to it: the signature of `abortOnCannotGrowMemory`, the constructors to run, and the
+//! WASI functions the programs of the LLVM backend do their I/O with.
+//!
+//! `check_emscripten_imports` lists the imports of a program which the glue doesn't provide, so
+//! that it fails before running rather than when it gets to one of them.
+
+use crate::{exit, vfs};
+use lazy_static::lazy_static;
+use std::{ffi::c_void, fmt};
+use wasmer_runtime_core::{
+    import::{ImportObject, LikeNamespace, Namespace},
+    module::{ImportName, Module},
+    types::{FuncSig, Type},
+    vm::Ctx,
+};
+use wasmer_wasi::types::{__WASI_EBADF, __WASI_EINVAL, __WASI_EIO, __WASI_ESUCCESS};
+
+lazy_static! {
+    static ref OLD_ABORT_ON_CANNOT_GROW_MEMORY_SIG: FuncSig =
+        { FuncSig::new(vec![], vec![Type::I32]) };
+}
+
+/// The custom section written by `-s EMIT_EMSCRIPTEN_METADATA=1`
+const METADATA_SECTION: &str = "emscripten_metadata";
+
+/// Which emscripten built a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmscriptenAbi {
+    /// The older releases of the asm2wasm backend, whose `abortOnCannotGrowMemory` takes no
+    /// argument
+    LegacyFastcomp,
+    /// The asm2wasm backend, up to 1.38: the C functions are prefixed with `_`, and the
+    /// constructors are run by `globalCtors`
+    Fastcomp,
+    /// The LLVM backend, from 1.39: the C functions aren't prefixed, the constructors are run by
+    /// `__wasm_call_ctors`, and the I/O is done with the functions of WASI
+    Upstream,
+}
+
+impl EmscriptenAbi {
+    /// Finds out which emscripten built `module` from its imports and its exports.
+    pub fn detect(module: &Module) -> Self {
+        let info = module.info();
+        let imports_wasi = info.imported_functions.iter().any(|(_, name)| {
+            let namespace = info.namespace_table.get(name.namespace_index);
+            namespace == "wasi_snapshot_preview1" || namespace == "wasi_unstable"
+        });
+        if imports_wasi || info.exports.contains_key("__wasm_call_ctors") {
+            return EmscriptenAbi::Upstream;
+        }
+
+        for (
+            index,
+            ImportName {
+                namespace_index,
+                name_index,
+            },
+        ) in &info.imported_functions
+        {
+            let namespace = info.namespace_table.get(*namespace_index);
+            let name = info.name_table.get(*name_index);
+            if name == "abortOnCannotGrowMemory" && namespace == "env" {
+                let sig_index = info.func_assoc[index.convert_up(info)];
+                if info.signatures[sig_index] == *OLD_ABORT_ON_CANNOT_GROW_MEMORY_SIG {
+                    return EmscriptenAbi::LegacyFastcomp;
+                }
+                break;
+            }
+        }
+        EmscriptenAbi::Fastcomp
+    }
+}
+
+impl fmt::Display for EmscriptenAbi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmscriptenAbi::LegacyFastcomp => write!(f, "an older release of emscripten fastcomp"),
+            EmscriptenAbi::Fastcomp => write!(f, "emscripten fastcomp"),
+            EmscriptenAbi::Upstream => write!(f, "the LLVM backend of emscripten"),
+        }
+    }
+}
+
+/// Returns the ABI version recorded in the `emscripten_metadata` section of `module`, if it has
+/// one.  The section starts with the version of the metadata, then the one of the ABI, as
+/// LEB128 numbers.
+pub fn emscripten_abi_version(module: &Module) -> Option<(u32, u32)> {
+    let section = *module.custom_sections(METADATA_SECTION).first()?;
+    let mut bytes = section.iter();
+    let mut read = || {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = *bytes.next()?;
+            value |= u32::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    };
+    let (_metadata_major, _metadata_minor) = (read()?, read()?);
+    Some((read()?, read()?))
+}
+
+/// Checks that `import_object` provides all the imports of `module`.
+///
+/// The error names which emscripten built the module, and lists the imports it lacks.
+pub fn check_emscripten_imports(
+    module: &Module,
+    import_object: &ImportObject,
+) -> Result<(), String> {
+    let info = module.info();
+    let missing: Vec<String> = info
+        .imported_functions
+        .iter()
+        .map(|(_, name)| name)
+        .chain(info.imported_globals.iter().map(|(_, (name, _))| name))
+        .chain(info.imported_memories.iter().map(|(_, (name, _))| name))
+        .chain(info.imported_tables.iter().map(|(_, (name, _))| name))
+        .filter_map(|import_name| {
+            let namespace = info.namespace_table.get(import_name.namespace_index);
+            let name = info.name_table.get(import_name.name_index);
+            match import_object.maybe_with_namespace(namespace, |ns| ns.get_export(name)) {
+                Some(_) => None,
+                None => Some(format!("{}.{}", namespace, name)),
+            }
+        })
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let version = match emscripten_abi_version(module) {
+        Some((major, minor)) => format!(" (ABI {}.{})", major, minor),
+        None => String::new(),
+    };
+    Err(format!(
+        "This module was built by {}{}, and wasmer doesn't support these imports of it: {}",
+        EmscriptenAbi::detect(module),
+        version,
+        missing.join(", ")
+    ))
+}
+
+// The functions of WASI imported by the programs of the LLVM backend, on the host files like the
+// syscalls
+
+/// Runs `io` on the buffers of the `iovs_len` iovecs at `iovs`, until one of them isn't filled.
+fn host_iovecs(
+    ctx: &Ctx,
+    iovs: u32,
+    iovs_len: u32,
+    mut io: impl FnMut(*mut c_void, usize) -> isize,
+) -> isize {
+    let memory = ctx.memory(0);
+    let view = memory.view::<u32>();
+    let mut total = 0;
+    for i in 0..iovs_len {
+        let base = view[(iovs / 4 + i * 2) as usize].get();
+        let len = view[(iovs / 4 + i * 2 + 1) as usize].get() as usize;
+        let buf = emscripten_memory_pointer!(memory, base) as *mut c_void;
+        let ret = io(buf, len);
+        if ret < 0 {
+            return if total > 0 { total } else { ret };
+        }
+        total += ret;
+        if (ret as usize) < len {
+            break;
+        }
+    }
+    total
+}
+
+/// Writes the result of an I/O to `ptr`, and returns the WASI errno of the call.
+fn io_result(ctx: &Ctx, ret: isize, ptr: u32) -> i32 {
+    if ret < 0 {
+        return i32::from(__WASI_EIO);
+    }
+    ctx.memory(0).view::<u32>()[(ptr / 4) as usize].set(ret as u32);
+    i32::from(__WASI_ESUCCESS)
+}
+
+pub fn fd_write(ctx: &mut Ctx, fd: i32, iovs: u32, iovs_len: u32, nwritten: u32) -> i32 {
+    debug!("emscripten::fd_write {}", fd);
+    let ret = match vfs::writev(ctx, fd, iovs, iovs_len) {
+        Some(ret) => ret as isize,
+        None => host_iovecs(ctx, iovs, iovs_len, |buf, len| unsafe {
+            libc::write(fd, buf, len as _) as isize
+        }),
+    };
+    io_result(ctx, ret, nwritten)
+}
+
+pub fn fd_read(ctx: &mut Ctx, fd: i32, iovs: u32, iovs_len: u32, nread: u32) -> i32 {
+    debug!("emscripten::fd_read {}", fd);
+    let ret = match vfs::readv(ctx, fd, iovs, iovs_len) {
+        Some(ret) => ret as isize,
+        None => host_iovecs(ctx, iovs, iovs_len, |buf, len| unsafe {
+            libc::read(fd, buf, len as _) as isize
+        }),
+    };
+    io_result(ctx, ret, nread)
+}
+
+pub fn fd_close(ctx: &mut Ctx, fd: i32) -> i32 {
+    debug!("emscripten::fd_close {}", fd);
+    let ret = match vfs::close(ctx, fd) {
+        Some(ret) => ret,
+        None => unsafe { libc::close(fd) },
+    };
+    if ret < 0 {
+        return i32::from(__WASI_EBADF);
+    }
+    i32::from(__WASI_ESUCCESS)
+}
+
+/// `fd_seek`, with its 64 bits offset split in two as emscripten legalizes it.
+pub fn fd_seek(
+    ctx: &mut Ctx,
+    fd: i32,
+    offset_low: u32,
+    offset_high: i32,
+    whence: i32,
+    newoffset: u32,
+) -> i32 {
+    debug!("emscripten::fd_seek {}", fd);
+    let offset = (i64::from(offset_high) << 32) | i64::from(offset_low);
+    // the values of `whence` in WASI are the ones of `SEEK_SET`, `SEEK_CUR` and `SEEK_END`
+    if whence < 0 || whence > 2 {
+        return i32::from(__WASI_EINVAL);
+    }
+    let position = match vfs::lseek(ctx, fd, offset, whence) {
+        Some(Ok(position)) => position,
+        Some(Err(_)) => return i32::from(__WASI_EINVAL),
+        None => match unsafe { libc::lseek(fd, offset as _, whence) } {
+            position if position < 0 => return i32::from(__WASI_EBADF),
+            position => position as i64,
+        },
+    };
+    let view = ctx.memory(0).view::<u8>();
+    for (cell, byte) in view[newoffset as usize..]
+        .iter()
+        .zip(position.to_le_bytes().iter())
+    {
+        cell.set(*byte);
+    }
+    i32::from(__WASI_ESUCCESS)
+}
+
+pub fn proc_exit(ctx: &mut Ctx, code: i32) {
+    debug!("emscripten::proc_exit {}", code);
+    exit::exit(ctx, code);
+}
+
+/// Returns the WASI functions imported by the programs of the LLVM backend.
+pub(crate) fn wasi_namespace() -> Namespace {
+    namespace! {
+        "fd_write" => func!(crate::abi::fd_write),
+        "fd_read" => func!(crate::abi::fd_read),
+        "fd_close" => func!(crate::abi::fd_close),
+        "fd_seek" => func!(crate::abi::fd_seek),
+        "proc_exit" => func!(crate::abi::proc_exit),
+    }
+}
@@ -143,10 +143,10 @@ macro_rules! invoke {
         let result = get_emscripten_data($ctx).$name.as_ref().expect(concat!("Dynamic call is None: ", stringify!($name))).call($($arg),*);
         match result {
             Ok(v) => v,
-            Err(_e) => {
+            Err(e) => {
                 get_emscripten_data($ctx).stack_restore.as_ref().expect("stack_restore is None").call(sp).expect("stack_restore call failed");
-                // TODO: We should check if _e != "longjmp" and if that's the case, re-throw the error
                 // JS version is: if (e !== e+0 && e !== 'longjmp') throw e;
+                crate::jmp::rethrow_unless_longjmp(e);
                 get_emscripten_data($ctx).set_threw.as_ref().expect("set_threw is None").call(1, 0).expect("set_threw call failed");
                 0 as _
             }
@@ -159,10 +159,10 @@ macro_rules! invoke_no_return {
         let result = get_emscripten_data($ctx).$name.as_ref().expect(concat!("Dynamic call is None: ", stringify!($name))).call($($arg),*);
         match result {
             Ok(v) => v,
-            Err(_e) => {
+            Err(e) => {
                 get_emscripten_data($ctx).stack_restore.as_ref().expect("stack_restore is None").call(sp).expect("stack_restore call failed");
-                // TODO: We should check if _e != "longjmp" and if that's the case, re-throw the error
                 // JS version is: if (e !== e+0 && e !== 'longjmp') throw e;
+                crate::jmp::rethrow_unless_longjmp(e);
                 get_emscripten_data($ctx).set_threw.as_ref().expect("set_threw is None").call(1, 0).expect("set_threw call failed");
             }
         }
@@ -502,52 +502,28 @@ pub fn invoke_iiijj(
 }
 pub fn invoke_j(ctx: &mut Ctx, index: i32) -> i32 {
     debug!("emscripten::invoke_j");
-    if let Some(dyn_call_j) = &get_emscripten_data(ctx).dyn_call_j {
-        dyn_call_j.call(index).unwrap()
-    } else {
-        panic!("dyn_call_j is set to None");
-    }
+    invoke!(ctx, dyn_call_j, index)
 }
 pub fn invoke_ji(ctx: &mut Ctx, index: i32, a1: i32) -> i32 {
     debug!("emscripten::invoke_ji");
-    if let Some(dyn_call_ji) = &get_emscripten_data(ctx).dyn_call_ji {
-        dyn_call_ji.call(index, a1).unwrap()
-    } else {
-        panic!("dyn_call_ji is set to None");
-    }
+    invoke!(ctx, dyn_call_ji, index, a1)
 }
 pub fn invoke_jii(ctx: &mut Ctx, index: i32, a1: i32, a2: i32) -> i32 {
     debug!("emscripten::invoke_jii");
-    if let Some(dyn_call_jii) = &get_emscripten_data(ctx).dyn_call_jii {
-        dyn_call_jii.call(index, a1, a2).unwrap()
-    } else {
-        panic!("dyn_call_jii is set to None");
-    }
+    invoke!(ctx, dyn_call_jii, index, a1, a2)
 }
 
 pub fn invoke_jij(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32) -> i32 {
     debug!("emscripten::invoke_jij");
-    if let Some(dyn_call_jij) = &get_emscripten_data(ctx).dyn_call_jij {
-        dyn_call_jij.call(index, a1, a2, a3).unwrap()
-    } else {
-        panic!("dyn_call_jij is set to None");
-    }
+    invoke!(ctx, dyn_call_jij, index, a1, a2, a3)
 }
 pub fn invoke_jjj(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32) -> i32 {
     debug!("emscripten::invoke_jjj");
-    if let Some(dyn_call_jjj) = &get_emscripten_data(ctx).dyn_call_jjj {
-        dyn_call_jjj.call(index, a1, a2, a3, a4).unwrap()
-    } else {
-        panic!("dyn_call_jjj is set to None");
-    }
+    invoke!(ctx, dyn_call_jjj, index, a1, a2, a3, a4)
 }
 pub fn invoke_viiij(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32, a5: i32) {
     debug!("emscripten::invoke_viiij");
-    if let Some(dyn_call_viiij) = &get_emscripten_data(ctx).dyn_call_viiij {
-        dyn_call_viiij.call(index, a1, a2, a3, a4, a5).unwrap();
-    } else {
-        panic!("dyn_call_viiij is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_viiij, index, a1, a2, a3, a4, a5)
 }
 pub fn invoke_viiijiiii(
     ctx: &mut Ctx,
@@ -563,13 +539,20 @@ pub fn invoke_viiijiiii(
     a9: i32,
 ) {
     debug!("emscripten::invoke_viiijiiii");
-    if let Some(dyn_call_viiijiiii) = &get_emscripten_data(ctx).dyn_call_viiijiiii {
-        dyn_call_viiijiiii
-            .call(index, a1, a2, a3, a4, a5, a6, a7, a8, a9)
-            .unwrap();
-    } else {
-        panic!("dyn_call_viiijiiii is set to None");
-    }
+    invoke_no_return!(
+        ctx,
+        dyn_call_viiijiiii,
+        index,
+        a1,
+        a2,
+        a3,
+        a4,
+        a5,
+        a6,
+        a7,
+        a8,
+        a9
+    )
 }
 pub fn invoke_viiijiiiiii(
     ctx: &mut Ctx,
@@ -587,29 +570,30 @@ pub fn invoke_viiijiiiiii(
     a11: i32,
 ) {
     debug!("emscripten::invoke_viiijiiiiii");
-    if let Some(dyn_call_viiijiiiiii) = &get_emscripten_data(ctx).dyn_call_viiijiiiiii {
-        dyn_call_viiijiiiiii
-            .call(index, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11)
-            .unwrap();
-    } else {
-        panic!("dyn_call_viiijiiiiii is set to None");
-    }
+    invoke_no_return!(
+        ctx,
+        dyn_call_viiijiiiiii,
+        index,
+        a1,
+        a2,
+        a3,
+        a4,
+        a5,
+        a6,
+        a7,
+        a8,
+        a9,
+        a10,
+        a11
+    )
 }
 pub fn invoke_viij(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32) {
     debug!("emscripten::invoke_viij");
-    if let Some(dyn_call_viij) = &get_emscripten_data(ctx).dyn_call_viij {
-        dyn_call_viij.call(index, a1, a2, a3, a4).unwrap();
-    } else {
-        panic!("dyn_call_viij is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_viij, index, a1, a2, a3, a4)
 }
 pub fn invoke_viiji(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32, a5: i32) {
     debug!("emscripten::invoke_viiji");
-    if let Some(dyn_call_viiji) = &get_emscripten_data(ctx).dyn_call_viiji {
-        dyn_call_viiji.call(index, a1, a2, a3, a4, a5).unwrap();
-    } else {
-        panic!("dyn_call_viiji is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_viiji, index, a1, a2, a3, a4, a5)
 }
 pub fn invoke_viijiii(
     ctx: &mut Ctx,
@@ -623,13 +607,7 @@ pub fn invoke_viijiii(
     a7: i32,
 ) {
     debug!("emscripten::invoke_viijiii");
-    if let Some(dyn_call_viijiii) = &get_emscripten_data(ctx).dyn_call_viijiii {
-        dyn_call_viijiii
-            .call(index, a1, a2, a3, a4, a5, a6, a7)
-            .unwrap();
-    } else {
-        panic!("dyn_call_viijiii is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_viijiii, index, a1, a2, a3, a4, a5, a6, a7)
 }
 pub fn invoke_viijj(
     ctx: &mut Ctx,
@@ -642,19 +620,11 @@ pub fn invoke_viijj(
     a6: i32,
 ) {
     debug!("emscripten::invoke_viijj");
-    if let Some(dyn_call_viijj) = &get_emscripten_data(ctx).dyn_call_viijj {
-        dyn_call_viijj.call(index, a1, a2, a3, a4, a5, a6).unwrap();
-    } else {
-        panic!("dyn_call_viijj is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_viijj, index, a1, a2, a3, a4, a5, a6)
 }
 pub fn invoke_vj(ctx: &mut Ctx, index: i32, a1: i32, a2: i32) {
     debug!("emscripten::invoke_vj");
-    if let Some(dyn_call_vj) = &get_emscripten_data(ctx).dyn_call_vj {
-        dyn_call_vj.call(index, a1, a2).unwrap();
-    } else {
-        panic!("dyn_call_vj is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_vj, index, a1, a2)
 }
 pub fn invoke_vjji(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32, a5: i32) {
     debug!("emscripten::invoke_vjji");
@@ -662,19 +632,11 @@ pub fn invoke_vjji(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32
 }
 pub fn invoke_vij(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32) {
     debug!("emscripten::invoke_vij");
-    if let Some(dyn_call_vij) = &get_emscripten_data(ctx).dyn_call_vij {
-        dyn_call_vij.call(index, a1, a2, a3).unwrap();
-    } else {
-        panic!("dyn_call_vij is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_vij, index, a1, a2, a3)
 }
 pub fn invoke_viji(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32) {
     debug!("emscripten::invoke_viji");
-    if let Some(dyn_call_viji) = &get_emscripten_data(ctx).dyn_call_viji {
-        dyn_call_viji.call(index, a1, a2, a3, a4).unwrap()
-    } else {
-        panic!("dyn_call_viji is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_viji, index, a1, a2, a3, a4)
 }
 pub fn invoke_vijiii(
     ctx: &mut Ctx,
@@ -687,19 +649,11 @@ pub fn invoke_vijiii(
     a6: i32,
 ) {
     debug!("emscripten::invoke_vijiii");
-    if let Some(dyn_call_vijiii) = &get_emscripten_data(ctx).dyn_call_vijiii {
-        dyn_call_vijiii.call(index, a1, a2, a3, a4, a5, a6).unwrap()
-    } else {
-        panic!("dyn_call_vijiii is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_vijiii, index, a1, a2, a3, a4, a5, a6)
 }
 pub fn invoke_vijj(ctx: &mut Ctx, index: i32, a1: i32, a2: i32, a3: i32, a4: i32, a5: i32) {
     debug!("emscripten::invoke_vijj");
-    if let Some(dyn_call_vijj) = &get_emscripten_data(ctx).dyn_call_vijj {
-        dyn_call_vijj.call(index, a1, a2, a3, a4, a5).unwrap()
-    } else {
-        panic!("dyn_call_vijj is set to None");
-    }
+    invoke_no_return!(ctx, dyn_call_vijj, index, a1, a2, a3, a4, a5)
 }
 pub fn invoke_vidd(ctx: &mut Ctx, index: i32, a1: i32, a2: f64, a3: f64) {
     debug!("emscripten::invoke_viid");
@@ -30,6 +30,24 @@ pub fn call_malloc(ctx: &mut Ctx, size: u32) -> u32 {
         .unwrap()
 }
 
+pub fn call_free(ctx: &mut Ctx, pointer: u32) {
+    get_emscripten_data(ctx)
+        .free
+        .as_ref()
+        .unwrap()
+        .call(pointer)
+        .unwrap()
+}
+
+pub fn call_realloc(ctx: &mut Ctx, pointer: u32, size: u32) -> u32 {
+    get_emscripten_data(ctx)
+        .realloc
+        .as_ref()
+        .unwrap()
+        .call(pointer, size)
+        .unwrap()
+}
+
 #[warn(dead_code)]
 pub fn call_malloc_with_cast<T: Copy, Ty>(ctx: &mut Ctx, size: u32) -> WasmPtr<T, Ty> {
     WasmPtr::new(call_malloc(ctx, size))
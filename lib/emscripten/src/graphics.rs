@@ -0,0 +1,912 @@
+//! The SDL, EGL and GL imports of graphical emscripten programs, behind the `graphics` feature.
+//!
+//! There's no canvas outside of the browser, so these run the program headless: windows,
+//! surfaces and GL contexts are created but never shown, GL objects get ids, and the queries a
+//! program checks before rendering succeed.  The embedder can take over the rendering by setting
+//! `EmscriptenGlobals::graphics` to its own `GraphicsHost`, which gets the pixels of the SDL
+//! screen when the program presents it, the GL calls of the program, and supplies its input
+//! events.
+//!
+//! The surfaces are 32 bits RGBA, like the canvas of emscripten, whatever the program asks for.
+
+use crate::env::{call_free, call_malloc, get_emscripten_data};
+use crate::utils::read_string_from_wasm;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+use wasmer_runtime_core::{import::Namespace, memory::Memory, types::Value, vm::Ctx};
+
+/// An input event given to the program by `SDL_PollEvent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsEvent {
+    /// The user closed the window
+    Quit,
+    /// A key was pressed, with its SDL scancode and keycode
+    KeyDown { scancode: i32, keycode: i32 },
+    /// A key was released, with its SDL scancode and keycode
+    KeyUp { scancode: i32, keycode: i32 },
+    /// The mouse moved to `x`, `y` in the window
+    MouseMotion { x: i32, y: i32 },
+}
+
+/// What graphical programs render to, and get their input from.
+///
+/// Every method has a default doing nothing, which runs the program headless.
+pub trait GraphicsHost: Send + Sync {
+    /// Called when the program opens its window, or sets its video mode.
+    fn open_window(&self, _title: &str, _width: u32, _height: u32) {}
+
+    /// Called with the pixels of the SDL screen when the program presents it: `height` rows of
+    /// `pitch` bytes, with the red, green, blue and alpha bytes of each pixel in this order.
+    fn present_pixels(&self, _width: u32, _height: u32, _pitch: u32, _pixels: &[u8]) {}
+
+    /// Called when the program swaps the buffers of its GL context or EGL surface.
+    fn swap_buffers(&self) {}
+
+    /// Returns the next input event, if there's one.
+    fn poll_event(&self) -> Option<GraphicsEvent> {
+        None
+    }
+
+    /// Runs the GL function `name`, like `glClear`, with the arguments of the program.  The
+    /// pointers among them are offsets in `memory`.
+    ///
+    /// Returns the result of the call, or `None` to let the headless implementation run it.
+    fn gl_call(&self, _memory: &Memory, _name: &str, _args: &[Value]) -> Option<i32> {
+        None
+    }
+}
+
+/// The host of the programs whose embedder didn't give one.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeadlessGraphics;
+
+impl GraphicsHost for HeadlessGraphics {}
+
+/// The graphics of an instance.
+pub(crate) struct GraphicsState {
+    host: Arc<dyn GraphicsHost>,
+    start: Instant,
+    /// The surface returned by `SDL_SetVideoMode`
+    screen: u32,
+    title: String,
+    /// The events polled from the host and not given to the program yet
+    pending_events: VecDeque<GraphicsEvent>,
+    /// The ids given to windows, contexts and GL objects
+    next_id: u32,
+    /// The strings allocated for `SDL_GetError` and `glGetString`
+    strings: HashMap<&'static str, u32>,
+}
+
+impl GraphicsState {
+    pub(crate) fn new(host: Option<Arc<dyn GraphicsHost>>) -> Self {
+        Self {
+            host: host.unwrap_or_else(|| Arc::new(HeadlessGraphics)),
+            start: Instant::now(),
+            screen: 0,
+            title: String::new(),
+            pending_events: VecDeque::new(),
+            next_id: 1,
+            strings: HashMap::new(),
+        }
+    }
+}
+
+fn state(ctx: &mut Ctx) -> &mut GraphicsState {
+    &mut get_emscripten_data(ctx).graphics
+}
+
+fn host(ctx: &mut Ctx) -> Arc<dyn GraphicsHost> {
+    state(ctx).host.clone()
+}
+
+fn next_id(ctx: &mut Ctx) -> u32 {
+    let state = state(ctx);
+    let id = state.next_id;
+    state.next_id += 1;
+    id
+}
+
+/// Returns a C string holding `string` in the memory of the program, allocated once.
+fn static_string(ctx: &mut Ctx, string: &'static str) -> u32 {
+    if let Some(ptr) = state(ctx).strings.get(string) {
+        return *ptr;
+    }
+    let ptr = call_malloc(ctx, string.len() as u32 + 1);
+    let view = ctx.memory(0).view::<u8>();
+    for (cell, byte) in view[ptr as usize..]
+        .iter()
+        .zip(string.bytes().chain(Some(0)))
+    {
+        cell.set(byte);
+    }
+    state(ctx).strings.insert(string, ptr);
+    ptr
+}
+
+fn load(ctx: &Ctx, addr: u32) -> u32 {
+    ctx.memory(0).view::<u32>()[(addr / 4) as usize].get()
+}
+
+fn store(ctx: &Ctx, addr: u32, value: u32) {
+    ctx.memory(0).view::<u32>()[(addr / 4) as usize].set(value)
+}
+
+fn store_byte(ctx: &Ctx, addr: u32, value: u8) {
+    ctx.memory(0).view::<u8>()[addr as usize].set(value)
+}
+
+// SDL
+
+/// `SDL_PIXELFORMAT_ABGR8888`, the red byte first in memory
+const SDL_PIXELFORMAT_ABGR8888: u32 = 0x1676_2004;
+
+// `SDL_Surface`
+const SURFACE_FLAGS: u32 = 0;
+const SURFACE_FORMAT: u32 = 4;
+const SURFACE_W: u32 = 8;
+const SURFACE_H: u32 = 12;
+const SURFACE_PITCH: u32 = 16;
+const SURFACE_PIXELS: u32 = 20;
+const SURFACE_CLIP_RECT: u32 = 36;
+const SURFACE_REFCOUNT: u32 = 56;
+const SURFACE_SIZE: u32 = 60;
+
+// `SDL_PixelFormat`
+const FORMAT_FORMAT: u32 = 0;
+const FORMAT_BITS_PER_PIXEL: u32 = 8;
+const FORMAT_BYTES_PER_PIXEL: u32 = 9;
+const FORMAT_RMASK: u32 = 12;
+const FORMAT_GMASK: u32 = 16;
+const FORMAT_BMASK: u32 = 20;
+const FORMAT_AMASK: u32 = 24;
+const FORMAT_RSHIFT: u32 = 32;
+const FORMAT_GSHIFT: u32 = 33;
+const FORMAT_BSHIFT: u32 = 34;
+const FORMAT_ASHIFT: u32 = 35;
+const FORMAT_REFCOUNT: u32 = 36;
+const FORMAT_SIZE: u32 = 44;
+
+// `SDL_Event`
+const SDL_QUIT: u32 = 0x100;
+const SDL_KEYDOWN: u32 = 0x300;
+const SDL_KEYUP: u32 = 0x301;
+const SDL_MOUSEMOTION: u32 = 0x400;
+const SDL_PRESSED: u8 = 1;
+const SDL_EVENT_SIZE: u32 = 56;
+
+fn allocate_zeroed(ctx: &mut Ctx, size: u32) -> u32 {
+    let ptr = call_malloc(ctx, size);
+    let view = ctx.memory(0).view::<u8>();
+    for cell in &view[ptr as usize..(ptr + size) as usize] {
+        cell.set(0);
+    }
+    ptr
+}
+
+fn create_surface(ctx: &mut Ctx, flags: u32, width: i32, height: i32) -> u32 {
+    let (width, height) = (width.max(0) as u32, height.max(0) as u32);
+    let format = allocate_zeroed(ctx, FORMAT_SIZE);
+    store(ctx, format + FORMAT_FORMAT, SDL_PIXELFORMAT_ABGR8888);
+    store_byte(ctx, format + FORMAT_BITS_PER_PIXEL, 32);
+    store_byte(ctx, format + FORMAT_BYTES_PER_PIXEL, 4);
+    store(ctx, format + FORMAT_RMASK, 0x0000_00ff);
+    store(ctx, format + FORMAT_GMASK, 0x0000_ff00);
+    store(ctx, format + FORMAT_BMASK, 0x00ff_0000);
+    store(ctx, format + FORMAT_AMASK, 0xff00_0000);
+    store_byte(ctx, format + FORMAT_RSHIFT, 0);
+    store_byte(ctx, format + FORMAT_GSHIFT, 8);
+    store_byte(ctx, format + FORMAT_BSHIFT, 16);
+    store_byte(ctx, format + FORMAT_ASHIFT, 24);
+    store(ctx, format + FORMAT_REFCOUNT, 1);
+
+    let pixels = allocate_zeroed(ctx, width * height * 4);
+    let surface = allocate_zeroed(ctx, SURFACE_SIZE);
+    store(ctx, surface + SURFACE_FLAGS, flags);
+    store(ctx, surface + SURFACE_FORMAT, format);
+    store(ctx, surface + SURFACE_W, width);
+    store(ctx, surface + SURFACE_H, height);
+    store(ctx, surface + SURFACE_PITCH, width * 4);
+    store(ctx, surface + SURFACE_PIXELS, pixels);
+    // the clip rect covers the whole surface
+    store(ctx, surface + SURFACE_CLIP_RECT + 8, width);
+    store(ctx, surface + SURFACE_CLIP_RECT + 12, height);
+    store(ctx, surface + SURFACE_REFCOUNT, 1);
+    surface
+}
+
+/// Gives the pixels of `surface` to the host, if it's the screen.
+fn present(ctx: &mut Ctx, surface: u32) {
+    if surface == 0 || surface != state(ctx).screen {
+        return;
+    }
+    let width = load(ctx, surface + SURFACE_W);
+    let height = load(ctx, surface + SURFACE_H);
+    let pitch = load(ctx, surface + SURFACE_PITCH);
+    let pixels = load(ctx, surface + SURFACE_PIXELS) as usize;
+    let len = (pitch * height) as usize;
+    let bytes: Vec<u8> = ctx.memory(0).view::<u8>()[pixels..pixels + len]
+        .iter()
+        .map(|cell| cell.get())
+        .collect();
+    host(ctx).present_pixels(width, height, pitch, &bytes);
+}
+
+pub fn sdl_init(ctx: &mut Ctx, _flags: u32) -> i32 {
+    debug!("emscripten::SDL_Init");
+    state(ctx).start = Instant::now();
+    0
+}
+
+pub fn sdl_init_sub_system(_ctx: &mut Ctx, _flags: u32) -> i32 {
+    debug!("emscripten::SDL_InitSubSystem");
+    0
+}
+
+pub fn sdl_quit_sub_system(_ctx: &mut Ctx, _flags: u32) {
+    debug!("emscripten::SDL_QuitSubSystem");
+}
+
+pub fn sdl_was_init(_ctx: &mut Ctx, flags: u32) -> u32 {
+    debug!("emscripten::SDL_WasInit");
+    flags
+}
+
+pub fn sdl_quit(_ctx: &mut Ctx) {
+    debug!("emscripten::SDL_Quit");
+}
+
+pub fn sdl_get_ticks(ctx: &mut Ctx) -> u32 {
+    state(ctx).start.elapsed().as_millis() as u32
+}
+
+pub fn sdl_delay(_ctx: &mut Ctx, ms: u32) {
+    thread::sleep(Duration::from_millis(ms.into()));
+}
+
+pub fn sdl_get_error(ctx: &mut Ctx) -> u32 {
+    static_string(ctx, "")
+}
+
+pub fn sdl_set_video_mode(ctx: &mut Ctx, width: i32, height: i32, _bpp: i32, flags: u32) -> u32 {
+    debug!("emscripten::SDL_SetVideoMode {}x{}", width, height);
+    let surface = create_surface(ctx, flags, width, height);
+    let title = {
+        let state = state(ctx);
+        state.screen = surface;
+        state.title.clone()
+    };
+    host(ctx).open_window(&title, width.max(0) as u32, height.max(0) as u32);
+    surface
+}
+
+pub fn sdl_create_rgb_surface(
+    ctx: &mut Ctx,
+    flags: u32,
+    width: i32,
+    height: i32,
+    _depth: i32,
+    _rmask: u32,
+    _gmask: u32,
+    _bmask: u32,
+    _amask: u32,
+) -> u32 {
+    debug!("emscripten::SDL_CreateRGBSurface {}x{}", width, height);
+    create_surface(ctx, flags, width, height)
+}
+
+pub fn sdl_free_surface(ctx: &mut Ctx, surface: u32) {
+    debug!("emscripten::SDL_FreeSurface {}", surface);
+    if surface == 0 || surface == state(ctx).screen {
+        return;
+    }
+    let format = load(ctx, surface + SURFACE_FORMAT);
+    let pixels = load(ctx, surface + SURFACE_PIXELS);
+    call_free(ctx, pixels);
+    call_free(ctx, format);
+    call_free(ctx, surface);
+}
+
+pub fn sdl_lock_surface(_ctx: &mut Ctx, _surface: u32) -> i32 {
+    0
+}
+
+pub fn sdl_unlock_surface(ctx: &mut Ctx, surface: u32) {
+    // emscripten shows the screen once it's unlocked
+    present(ctx, surface);
+}
+
+pub fn sdl_flip(ctx: &mut Ctx, surface: u32) -> i32 {
+    present(ctx, surface);
+    0
+}
+
+pub fn sdl_update_rect(ctx: &mut Ctx, surface: u32, _x: i32, _y: i32, _w: u32, _h: u32) {
+    present(ctx, surface);
+}
+
+pub fn sdl_map_rgb(_ctx: &mut Ctx, _format: u32, r: u32, g: u32, b: u32) -> u32 {
+    map_rgba(r, g, b, 0xff)
+}
+
+pub fn sdl_map_rgba(_ctx: &mut Ctx, _format: u32, r: u32, g: u32, b: u32, a: u32) -> u32 {
+    map_rgba(r, g, b, a)
+}
+
+fn map_rgba(r: u32, g: u32, b: u32, a: u32) -> u32 {
+    (r & 0xff) | (g & 0xff) << 8 | (b & 0xff) << 16 | (a & 0xff) << 24
+}
+
+pub fn sdl_fill_rect(ctx: &mut Ctx, surface: u32, rect: u32, color: u32) -> i32 {
+    if surface == 0 {
+        return -1;
+    }
+    let width = load(ctx, surface + SURFACE_W) as i32;
+    let height = load(ctx, surface + SURFACE_H) as i32;
+    let pitch = load(ctx, surface + SURFACE_PITCH);
+    let pixels = load(ctx, surface + SURFACE_PIXELS);
+    let (x, y, w, h) = if rect == 0 {
+        (0, 0, width, height)
+    } else {
+        (
+            load(ctx, rect) as i32,
+            load(ctx, rect + 4) as i32,
+            load(ctx, rect + 8) as i32,
+            load(ctx, rect + 12) as i32,
+        )
+    };
+    let (x0, y0) = (x.max(0), y.max(0));
+    let (x1, y1) = ((x + w).min(width), (y + h).min(height));
+    let view = ctx.memory(0).view::<u32>();
+    for row in y0..y1 {
+        let start = (pixels + row as u32 * pitch) / 4;
+        for column in x0..x1 {
+            view[(start + column as u32) as usize].set(color);
+        }
+    }
+    0
+}
+
+pub fn sdl_poll_event(ctx: &mut Ctx, event: u32) -> i32 {
+    let host = host(ctx);
+    let state = state(ctx);
+    while let Some(polled) = host.poll_event() {
+        state.pending_events.push_back(polled);
+    }
+    // without an event to fill, only tell whether there's one
+    if event == 0 {
+        return !state.pending_events.is_empty() as i32;
+    }
+    let polled = match state.pending_events.pop_front() {
+        Some(polled) => polled,
+        None => return 0,
+    };
+    let timestamp = state.start.elapsed().as_millis() as u32;
+
+    let view = ctx.memory(0).view::<u8>();
+    for cell in &view[event as usize..(event + SDL_EVENT_SIZE) as usize] {
+        cell.set(0);
+    }
+    store(ctx, event + 4, timestamp);
+    match polled {
+        GraphicsEvent::Quit => store(ctx, event, SDL_QUIT),
+        GraphicsEvent::KeyDown { scancode, keycode }
+        | GraphicsEvent::KeyUp { scancode, keycode } => {
+            let pressed = match polled {
+                GraphicsEvent::KeyDown { .. } => true,
+                _ => false,
+            };
+            store(ctx, event, if pressed { SDL_KEYDOWN } else { SDL_KEYUP });
+            store_byte(ctx, event + 12, if pressed { SDL_PRESSED } else { 0 });
+            store(ctx, event + 16, scancode as u32);
+            store(ctx, event + 20, keycode as u32);
+        }
+        GraphicsEvent::MouseMotion { x, y } => {
+            store(ctx, event, SDL_MOUSEMOTION);
+            store(ctx, event + 20, x as u32);
+            store(ctx, event + 24, y as u32);
+        }
+    }
+    1
+}
+
+pub fn sdl_wm_set_caption(ctx: &mut Ctx, title: u32, _icon: u32) {
+    let title = read_string_from_wasm(ctx.memory(0), title);
+    debug!("emscripten::SDL_WM_SetCaption {}", title);
+    state(ctx).title = title;
+}
+
+pub fn sdl_show_cursor(_ctx: &mut Ctx, _toggle: i32) -> i32 {
+    // SDL_ENABLE
+    1
+}
+
+pub fn sdl_enable_key_repeat(_ctx: &mut Ctx, _delay: i32, _interval: i32) -> i32 {
+    0
+}
+
+pub fn sdl_gl_set_attribute(_ctx: &mut Ctx, _attribute: i32, _value: i32) -> i32 {
+    0
+}
+
+pub fn sdl_gl_swap_buffers(ctx: &mut Ctx) {
+    host(ctx).swap_buffers();
+}
+
+pub fn sdl_create_window(
+    ctx: &mut Ctx,
+    title: u32,
+    _x: i32,
+    _y: i32,
+    width: i32,
+    height: i32,
+    _flags: u32,
+) -> u32 {
+    let title = read_string_from_wasm(ctx.memory(0), title);
+    debug!(
+        "emscripten::SDL_CreateWindow {} {}x{}",
+        title, width, height
+    );
+    host(ctx).open_window(&title, width.max(0) as u32, height.max(0) as u32);
+    next_id(ctx)
+}
+
+pub fn sdl_destroy_window(_ctx: &mut Ctx, _window: u32) {}
+
+pub fn sdl_gl_create_context(ctx: &mut Ctx, _window: u32) -> u32 {
+    next_id(ctx)
+}
+
+pub fn sdl_gl_delete_context(_ctx: &mut Ctx, _context: u32) {}
+
+pub fn sdl_gl_make_current(_ctx: &mut Ctx, _window: u32, _context: u32) -> i32 {
+    0
+}
+
+pub fn sdl_gl_swap_window(ctx: &mut Ctx, _window: u32) {
+    host(ctx).swap_buffers();
+}
+
+pub fn sdl_gl_set_swap_interval(_ctx: &mut Ctx, _interval: i32) -> i32 {
+    0
+}
+
+// EGL, with the handles of emscripten
+
+const EGL_TRUE: i32 = 1;
+const EGL_SUCCESS: i32 = 0x3000;
+const EGL_DEFAULT_DISPLAY: u32 = 62000;
+const EGL_CONFIG: u32 = 62002;
+const EGL_CONTEXT: u32 = 62004;
+const EGL_SURFACE: u32 = 62006;
+
+pub fn egl_get_display(_ctx: &mut Ctx, _native_display: u32) -> u32 {
+    EGL_DEFAULT_DISPLAY
+}
+
+pub fn egl_initialize(ctx: &mut Ctx, _display: u32, major: u32, minor: u32) -> i32 {
+    if major != 0 {
+        store(ctx, major, 1);
+    }
+    if minor != 0 {
+        store(ctx, minor, 4);
+    }
+    EGL_TRUE
+}
+
+pub fn egl_choose_config(
+    ctx: &mut Ctx,
+    _display: u32,
+    _attrib_list: u32,
+    configs: u32,
+    config_size: i32,
+    num_config: u32,
+) -> i32 {
+    if configs != 0 && config_size > 0 {
+        store(ctx, configs, EGL_CONFIG);
+    }
+    if num_config != 0 {
+        store(ctx, num_config, 1);
+    }
+    EGL_TRUE
+}
+
+pub fn egl_get_config_attrib(
+    ctx: &mut Ctx,
+    _display: u32,
+    _config: u32,
+    _attribute: i32,
+    value: u32,
+) -> i32 {
+    if value != 0 {
+        store(ctx, value, 0);
+    }
+    EGL_TRUE
+}
+
+pub fn egl_create_window_surface(
+    _ctx: &mut Ctx,
+    _display: u32,
+    _config: u32,
+    _window: u32,
+    _attrib_list: u32,
+) -> u32 {
+    EGL_SURFACE
+}
+
+pub fn egl_create_context(
+    _ctx: &mut Ctx,
+    _display: u32,
+    _config: u32,
+    _share_context: u32,
+    _attrib_list: u32,
+) -> u32 {
+    EGL_CONTEXT
+}
+
+pub fn egl_make_current(
+    _ctx: &mut Ctx,
+    _display: u32,
+    _draw: u32,
+    _read: u32,
+    _context: u32,
+) -> i32 {
+    EGL_TRUE
+}
+
+pub fn egl_swap_buffers(ctx: &mut Ctx, _display: u32, _surface: u32) -> i32 {
+    host(ctx).swap_buffers();
+    EGL_TRUE
+}
+
+pub fn egl_swap_interval(_ctx: &mut Ctx, _display: u32, _interval: i32) -> i32 {
+    EGL_TRUE
+}
+
+pub fn egl_bind_api(_ctx: &mut Ctx, _api: u32) -> i32 {
+    EGL_TRUE
+}
+
+pub fn egl_destroy(_ctx: &mut Ctx, _display: u32, _object: u32) -> i32 {
+    EGL_TRUE
+}
+
+pub fn egl_terminate(_ctx: &mut Ctx, _display: u32) -> i32 {
+    EGL_TRUE
+}
+
+pub fn egl_get_error(_ctx: &mut Ctx) -> i32 {
+    EGL_SUCCESS
+}
+
+// GL
+
+fn gl_call(ctx: &mut Ctx, name: &str, args: &[Value]) -> Option<i32> {
+    let host = host(ctx);
+    host.gl_call(ctx.memory(0), name, args)
+}
+
+/// Defines the GL functions which only the host implements, and `insert_gl_functions` adding them.
+macro_rules! gl_functions {
+    ( $( $name:ident = $gl_name:expr, ( $( $arg:ident : $ty:ty ),* ); )* ) => {
+        $(
+            pub fn $name(ctx: &mut Ctx, $( $arg: $ty ),*) {
+                gl_call(ctx, $gl_name, &[ $( Value::from($arg) ),* ]);
+            }
+        )*
+
+        fn insert_gl_functions(namespace: &mut Namespace) {
+            $(
+                namespace.insert(concat!("_", $gl_name), func!(crate::graphics::$name));
+            )*
+        }
+    };
+}
+
+gl_functions! {
+    gl_active_texture = "glActiveTexture", (texture: i32);
+    gl_attach_shader = "glAttachShader", (program: i32, shader: i32);
+    gl_bind_attrib_location = "glBindAttribLocation", (program: i32, index: i32, name: i32);
+    gl_bind_buffer = "glBindBuffer", (target: i32, buffer: i32);
+    gl_bind_framebuffer = "glBindFramebuffer", (target: i32, framebuffer: i32);
+    gl_bind_renderbuffer = "glBindRenderbuffer", (target: i32, renderbuffer: i32);
+    gl_bind_texture = "glBindTexture", (target: i32, texture: i32);
+    gl_blend_func = "glBlendFunc", (sfactor: i32, dfactor: i32);
+    gl_buffer_data = "glBufferData", (target: i32, size: i32, data: i32, usage: i32);
+    gl_buffer_sub_data = "glBufferSubData", (target: i32, offset: i32, size: i32, data: i32);
+    gl_clear = "glClear", (mask: i32);
+    gl_clear_color = "glClearColor", (red: f32, green: f32, blue: f32, alpha: f32);
+    gl_clear_depthf = "glClearDepthf", (depth: f32);
+    gl_compile_shader = "glCompileShader", (shader: i32);
+    gl_cull_face = "glCullFace", (mode: i32);
+    gl_delete_buffers = "glDeleteBuffers", (n: i32, buffers: i32);
+    gl_delete_framebuffers = "glDeleteFramebuffers", (n: i32, framebuffers: i32);
+    gl_delete_program = "glDeleteProgram", (program: i32);
+    gl_delete_renderbuffers = "glDeleteRenderbuffers", (n: i32, renderbuffers: i32);
+    gl_delete_shader = "glDeleteShader", (shader: i32);
+    gl_delete_textures = "glDeleteTextures", (n: i32, textures: i32);
+    gl_depth_func = "glDepthFunc", (func: i32);
+    gl_depth_mask = "glDepthMask", (flag: i32);
+    gl_disable = "glDisable", (cap: i32);
+    gl_disable_vertex_attrib_array = "glDisableVertexAttribArray", (index: i32);
+    gl_draw_arrays = "glDrawArrays", (mode: i32, first: i32, count: i32);
+    gl_draw_elements = "glDrawElements", (mode: i32, count: i32, ty: i32, indices: i32);
+    gl_enable = "glEnable", (cap: i32);
+    gl_enable_vertex_attrib_array = "glEnableVertexAttribArray", (index: i32);
+    gl_finish = "glFinish", ();
+    gl_flush = "glFlush", ();
+    gl_framebuffer_texture_2d = "glFramebufferTexture2D",
+        (target: i32, attachment: i32, textarget: i32, texture: i32, level: i32);
+    gl_pixel_storei = "glPixelStorei", (pname: i32, param: i32);
+    gl_read_pixels = "glReadPixels",
+        (x: i32, y: i32, width: i32, height: i32, format: i32, ty: i32, pixels: i32);
+    gl_scissor = "glScissor", (x: i32, y: i32, width: i32, height: i32);
+    gl_shader_source = "glShaderSource", (shader: i32, count: i32, string: i32, length: i32);
+    gl_tex_image_2d = "glTexImage2D", (
+        target: i32,
+        level: i32,
+        internalformat: i32,
+        width: i32,
+        height: i32,
+        border: i32,
+        format: i32,
+        ty: i32,
+        pixels: i32
+    );
+    gl_tex_parameteri = "glTexParameteri", (target: i32, pname: i32, param: i32);
+    gl_tex_sub_image_2d = "glTexSubImage2D", (
+        target: i32,
+        level: i32,
+        xoffset: i32,
+        yoffset: i32,
+        width: i32,
+        height: i32,
+        format: i32,
+        ty: i32,
+        pixels: i32
+    );
+    gl_uniform1f = "glUniform1f", (location: i32, v0: f32);
+    gl_uniform1i = "glUniform1i", (location: i32, v0: i32);
+    gl_uniform2f = "glUniform2f", (location: i32, v0: f32, v1: f32);
+    gl_uniform3f = "glUniform3f", (location: i32, v0: f32, v1: f32, v2: f32);
+    gl_uniform4f = "glUniform4f", (location: i32, v0: f32, v1: f32, v2: f32, v3: f32);
+    gl_uniform_matrix4fv = "glUniformMatrix4fv",
+        (location: i32, count: i32, transpose: i32, value: i32);
+    gl_use_program = "glUseProgram", (program: i32);
+    gl_vertex_attrib_pointer = "glVertexAttribPointer",
+        (index: i32, size: i32, ty: i32, normalized: i32, stride: i32, pointer: i32);
+    gl_viewport = "glViewport", (x: i32, y: i32, width: i32, height: i32);
+}
+
+/// Runs a `glGen*`, giving ids to the objects if the host doesn't.
+fn gl_gen(ctx: &mut Ctx, name: &str, n: i32, ids: u32) {
+    if gl_call(ctx, name, &[Value::I32(n), Value::I32(ids as i32)]).is_some() {
+        return;
+    }
+    for i in 0..n.max(0) as u32 {
+        let id = next_id(ctx);
+        store(ctx, ids + i * 4, id);
+    }
+}
+
+pub fn gl_gen_buffers(ctx: &mut Ctx, n: i32, buffers: u32) {
+    gl_gen(ctx, "glGenBuffers", n, buffers);
+}
+
+pub fn gl_gen_textures(ctx: &mut Ctx, n: i32, textures: u32) {
+    gl_gen(ctx, "glGenTextures", n, textures);
+}
+
+pub fn gl_gen_framebuffers(ctx: &mut Ctx, n: i32, framebuffers: u32) {
+    gl_gen(ctx, "glGenFramebuffers", n, framebuffers);
+}
+
+pub fn gl_gen_renderbuffers(ctx: &mut Ctx, n: i32, renderbuffers: u32) {
+    gl_gen(ctx, "glGenRenderbuffers", n, renderbuffers);
+}
+
+pub fn gl_create_program(ctx: &mut Ctx) -> u32 {
+    match gl_call(ctx, "glCreateProgram", &[]) {
+        Some(program) => program as u32,
+        None => next_id(ctx),
+    }
+}
+
+pub fn gl_create_shader(ctx: &mut Ctx, ty: i32) -> u32 {
+    match gl_call(ctx, "glCreateShader", &[Value::I32(ty)]) {
+        Some(shader) => shader as u32,
+        None => next_id(ctx),
+    }
+}
+
+/// `GL_INFO_LOG_LENGTH`
+const GL_INFO_LOG_LENGTH: i32 = 0x8B84;
+
+/// Runs a `glGet*iv` of shaders and programs: headless, they compiled and linked without logs.
+fn gl_get_object_iv(ctx: &mut Ctx, name: &str, object: i32, pname: i32, params: u32) {
+    let args = [
+        Value::I32(object),
+        Value::I32(pname),
+        Value::I32(params as i32),
+    ];
+    if gl_call(ctx, name, &args).is_none() && params != 0 {
+        store(ctx, params, (pname != GL_INFO_LOG_LENGTH) as u32);
+    }
+}
+
+pub fn gl_get_shaderiv(ctx: &mut Ctx, shader: i32, pname: i32, params: u32) {
+    gl_get_object_iv(ctx, "glGetShaderiv", shader, pname, params);
+}
+
+pub fn gl_get_programiv(ctx: &mut Ctx, program: i32, pname: i32, params: u32) {
+    gl_get_object_iv(ctx, "glGetProgramiv", program, pname, params);
+}
+
+/// Runs a `glGet*InfoLog`: headless, the logs are empty.
+fn gl_get_info_log(ctx: &mut Ctx, name: &str, object: i32, buf_size: i32, length: u32, log: u32) {
+    let args = [
+        Value::I32(object),
+        Value::I32(buf_size),
+        Value::I32(length as i32),
+        Value::I32(log as i32),
+    ];
+    if gl_call(ctx, name, &args).is_some() {
+        return;
+    }
+    if length != 0 {
+        store(ctx, length, 0);
+    }
+    if log != 0 && buf_size > 0 {
+        store_byte(ctx, log, 0);
+    }
+}
+
+pub fn gl_get_shader_info_log(ctx: &mut Ctx, shader: i32, buf_size: i32, length: u32, log: u32) {
+    gl_get_info_log(ctx, "glGetShaderInfoLog", shader, buf_size, length, log);
+}
+
+pub fn gl_get_program_info_log(ctx: &mut Ctx, program: i32, buf_size: i32, length: u32, log: u32) {
+    gl_get_info_log(ctx, "glGetProgramInfoLog", program, buf_size, length, log);
+}
+
+pub fn gl_get_attrib_location(ctx: &mut Ctx, program: i32, name: i32) -> i32 {
+    gl_call(
+        ctx,
+        "glGetAttribLocation",
+        &[Value::I32(program), Value::I32(name)],
+    )
+    .unwrap_or(0)
+}
+
+pub fn gl_get_uniform_location(ctx: &mut Ctx, program: i32, name: i32) -> i32 {
+    match gl_call(
+        ctx,
+        "glGetUniformLocation",
+        &[Value::I32(program), Value::I32(name)],
+    ) {
+        Some(location) => location,
+        None => next_id(ctx) as i32,
+    }
+}
+
+pub fn gl_get_integerv(ctx: &mut Ctx, pname: i32, params: u32) {
+    if gl_call(
+        ctx,
+        "glGetIntegerv",
+        &[Value::I32(pname), Value::I32(params as i32)],
+    )
+    .is_some()
+        || params == 0
+    {
+        return;
+    }
+    let value = match pname {
+        // GL_MAX_TEXTURE_SIZE
+        0x0D33 => 4096,
+        // GL_MAX_VERTEX_ATTRIBS
+        0x8869 => 16,
+        _ => 0,
+    };
+    store(ctx, params, value);
+}
+
+pub fn gl_get_string(ctx: &mut Ctx, name: i32) -> u32 {
+    if let Some(string) = gl_call(ctx, "glGetString", &[Value::I32(name)]) {
+        return string as u32;
+    }
+    let string = match name {
+        // GL_VENDOR
+        0x1F00 => "wasmer",
+        // GL_RENDERER
+        0x1F01 => "headless",
+        // GL_VERSION
+        0x1F02 => "OpenGL ES 2.0",
+        // GL_SHADING_LANGUAGE_VERSION
+        0x8B8C => "OpenGL ES GLSL ES 1.00",
+        // GL_EXTENSIONS
+        0x1F03 => "",
+        _ => return 0,
+    };
+    static_string(ctx, string)
+}
+
+pub fn gl_get_error(ctx: &mut Ctx) -> i32 {
+    // GL_NO_ERROR
+    gl_call(ctx, "glGetError", &[]).unwrap_or(0)
+}
+
+/// Adds the SDL, EGL and GL imports to the `env` namespace.
+pub(crate) fn insert_graphics_functions(namespace: &mut Namespace) {
+    let graphics_ns = namespace! {
+        "_SDL_Init" => func!(crate::graphics::sdl_init),
+        "_SDL_InitSubSystem" => func!(crate::graphics::sdl_init_sub_system),
+        "_SDL_QuitSubSystem" => func!(crate::graphics::sdl_quit_sub_system),
+        "_SDL_WasInit" => func!(crate::graphics::sdl_was_init),
+        "_SDL_Quit" => func!(crate::graphics::sdl_quit),
+        "_SDL_GetTicks" => func!(crate::graphics::sdl_get_ticks),
+        "_SDL_Delay" => func!(crate::graphics::sdl_delay),
+        "_SDL_GetError" => func!(crate::graphics::sdl_get_error),
+        "_SDL_SetVideoMode" => func!(crate::graphics::sdl_set_video_mode),
+        "_SDL_CreateRGBSurface" => func!(crate::graphics::sdl_create_rgb_surface),
+        "_SDL_FreeSurface" => func!(crate::graphics::sdl_free_surface),
+        "_SDL_LockSurface" => func!(crate::graphics::sdl_lock_surface),
+        "_SDL_UnlockSurface" => func!(crate::graphics::sdl_unlock_surface),
+        "_SDL_Flip" => func!(crate::graphics::sdl_flip),
+        "_SDL_UpdateRect" => func!(crate::graphics::sdl_update_rect),
+        "_SDL_MapRGB" => func!(crate::graphics::sdl_map_rgb),
+        "_SDL_MapRGBA" => func!(crate::graphics::sdl_map_rgba),
+        "_SDL_FillRect" => func!(crate::graphics::sdl_fill_rect),
+        "_SDL_PollEvent" => func!(crate::graphics::sdl_poll_event),
+        "_SDL_WM_SetCaption" => func!(crate::graphics::sdl_wm_set_caption),
+        "_SDL_ShowCursor" => func!(crate::graphics::sdl_show_cursor),
+        "_SDL_EnableKeyRepeat" => func!(crate::graphics::sdl_enable_key_repeat),
+        "_SDL_GL_SetAttribute" => func!(crate::graphics::sdl_gl_set_attribute),
+        "_SDL_GL_SwapBuffers" => func!(crate::graphics::sdl_gl_swap_buffers),
+        "_SDL_CreateWindow" => func!(crate::graphics::sdl_create_window),
+        "_SDL_DestroyWindow" => func!(crate::graphics::sdl_destroy_window),
+        "_SDL_GL_CreateContext" => func!(crate::graphics::sdl_gl_create_context),
+        "_SDL_GL_DeleteContext" => func!(crate::graphics::sdl_gl_delete_context),
+        "_SDL_GL_MakeCurrent" => func!(crate::graphics::sdl_gl_make_current),
+        "_SDL_GL_SwapWindow" => func!(crate::graphics::sdl_gl_swap_window),
+        "_SDL_GL_SetSwapInterval" => func!(crate::graphics::sdl_gl_set_swap_interval),
+
+        "_eglGetDisplay" => func!(crate::graphics::egl_get_display),
+        "_eglInitialize" => func!(crate::graphics::egl_initialize),
+        "_eglChooseConfig" => func!(crate::graphics::egl_choose_config),
+        "_eglGetConfigAttrib" => func!(crate::graphics::egl_get_config_attrib),
+        "_eglCreateWindowSurface" => func!(crate::graphics::egl_create_window_surface),
+        "_eglCreateContext" => func!(crate::graphics::egl_create_context),
+        "_eglMakeCurrent" => func!(crate::graphics::egl_make_current),
+        "_eglSwapBuffers" => func!(crate::graphics::egl_swap_buffers),
+        "_eglSwapInterval" => func!(crate::graphics::egl_swap_interval),
+        "_eglBindAPI" => func!(crate::graphics::egl_bind_api),
+        "_eglDestroyContext" => func!(crate::graphics::egl_destroy),
+        "_eglDestroySurface" => func!(crate::graphics::egl_destroy),
+        "_eglTerminate" => func!(crate::graphics::egl_terminate),
+        "_eglGetError" => func!(crate::graphics::egl_get_error),
+
+        "_glGenBuffers" => func!(crate::graphics::gl_gen_buffers),
+        "_glGenTextures" => func!(crate::graphics::gl_gen_textures),
+        "_glGenFramebuffers" => func!(crate::graphics::gl_gen_framebuffers),
+        "_glGenRenderbuffers" => func!(crate::graphics::gl_gen_renderbuffers),
+        "_glCreateProgram" => func!(crate::graphics::gl_create_program),
+        "_glCreateShader" => func!(crate::graphics::gl_create_shader),
+        "_glGetShaderiv" => func!(crate::graphics::gl_get_shaderiv),
+        "_glGetProgramiv" => func!(crate::graphics::gl_get_programiv),
+        "_glGetShaderInfoLog" => func!(crate::graphics::gl_get_shader_info_log),
+        "_glGetProgramInfoLog" => func!(crate::graphics::gl_get_program_info_log),
+        "_glGetAttribLocation" => func!(crate::graphics::gl_get_attrib_location),
+        "_glGetUniformLocation" => func!(crate::graphics::gl_get_uniform_location),
+        "_glGetIntegerv" => func!(crate::graphics::gl_get_integerv),
+        "_glGetString" => func!(crate::graphics::gl_get_string),
+        "_glGetError" => func!(crate::graphics::gl_get_error),
+    };
+    use wasmer_runtime_core::import::LikeNamespace;
+    for (name, export) in graphics_ns.get_exports() {
+        namespace.insert(name, export.to_export());
+    }
+    insert_gl_functions(namespace);
+}
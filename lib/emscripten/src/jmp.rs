@@ -1,8 +1,8 @@
-use super::env::get_emscripten_data;
+use super::env::{call_realloc, get_emscripten_data};
 use super::process::abort_with_message;
 use libc::c_int;
 // use std::cell::UnsafeCell;
-use wasmer_runtime_core::vm::Ctx;
+use wasmer_runtime_core::{error::RuntimeError, typed_func::raise_trap, vm::Ctx};
 
 /// setjmp
 pub fn __setjmp(ctx: &mut Ctx, _env_addr: u32) -> c_int {
@@ -27,23 +27,23 @@ pub fn __setjmp(ctx: &mut Ctx, _env_addr: u32) -> c_int {
 }
 
 /// longjmp
-#[allow(unreachable_code)]
-pub fn __longjmp(ctx: &mut Ctx, _env_addr: u32, _val: c_int) {
+pub fn __longjmp(ctx: &mut Ctx, env_addr: u32, val: c_int) -> Result<(), LongJump> {
     debug!("emscripten::__longjmp (longmp)");
-    abort_with_message(ctx, "missing function: _longjmp");
-    // unsafe {
-    //     // We retrieve the jump index from the env address
-    //     let jump_index = emscripten_memory_pointer!(ctx.memory(0), env_addr) as *mut i8;
-    //     let jumps = &mut get_emscripten_data(ctx).jumps;
-    //     // We get the real jump buffer from the jumps vector, using the retrieved index
-    //     let jump_buf = &jumps[*jump_index as usize];
-    //     longjmp(jump_buf.get() as _, val)
-    // };
+    _longjmp(ctx, env_addr as i32, val)
 }
 
+/// What `longjmp` unwinds the wasm stack with, up to the `invoke_*` function which called the
+/// function of the matching `setjmp`.
+#[derive(Debug)]
+pub struct LongJump;
+
 /// _longjmp
-// This function differs from the js implementation, it should return Result<(), &'static str>
-pub fn _longjmp(ctx: &mut Ctx, env_addr: i32, val: c_int) -> Result<(), ()> {
+///
+/// Like the js implementation, it records the jump with `setThrew` and unwinds up to the
+/// `invoke_*` function, after which the code compiled by emscripten finds the `setjmp` returning
+/// with `testSetjmp`.
+pub fn _longjmp(ctx: &mut Ctx, env_addr: i32, val: c_int) -> Result<(), LongJump> {
+    debug!("emscripten::_longjmp");
     let val = if val == 0 { 1 } else { val };
     get_emscripten_data(ctx)
         .set_threw
@@ -51,8 +51,73 @@ pub fn _longjmp(ctx: &mut Ctx, env_addr: i32, val: c_int) -> Result<(), ()> {
         .expect("set_threw is None")
         .call(env_addr, val)
         .expect("set_threw failed to call");
-    // TODO: return Err("longjmp")
-    Err(())
+    Err(LongJump)
+}
+
+/// Raises `error` again from the `invoke_*` function which got it, unless it's a `longjmp`.
+///
+/// This must only be called from an `invoke_*` import function, see `raise_trap`.
+pub(crate) fn rethrow_unless_longjmp(error: RuntimeError) {
+    unsafe {
+        match error {
+            RuntimeError::Error { ref data, .. } if data.is::<LongJump>() => (),
+            RuntimeError::Error { data, .. } => raise_trap(data),
+            error => raise_trap(Box::new(error)),
+        }
+    }
+}
+
+/// emscripten: saveSetjmp(env: *mut jmp_buf, label: c_int, table: *mut c_int, size: c_int) -> *mut c_int
+///
+/// Gives a new id to the `setjmp` of `env` and records it along with `label` in `table`, the
+/// `(id, label)` pairs of the calling function ended by a 0 id.  The table is grown with
+/// `realloc` when it's full, and its new size returned in `tempRet0`.
+#[allow(non_snake_case)]
+pub fn _saveSetjmp(ctx: &mut Ctx, env: u32, label: i32, table: u32, size: u32) -> u32 {
+    debug!("emscripten::_saveSetjmp");
+    let id = {
+        let data = get_emscripten_data(ctx);
+        data.setjmp_id = data.setjmp_id.wrapping_add(1);
+        data.setjmp_id
+    };
+    ctx.memory(0).view::<u32>()[(env / 4) as usize].set(id);
+
+    let (mut table, mut size) = (table, size);
+    loop {
+        let view = ctx.memory(0).view::<u32>();
+        let slot = (0..size)
+            .map(|i| (table / 4 + i * 2) as usize)
+            .find(|&slot| view[slot].get() == 0);
+        if let Some(slot) = slot {
+            view[slot].set(id);
+            view[slot + 1].set(label as u32);
+            // the end of the table
+            view[slot + 2].set(0);
+            get_emscripten_data(ctx).temp_ret_0 = size as i32;
+            return table;
+        }
+        size *= 2;
+        table = call_realloc(ctx, table, 8 * (size + 1));
+    }
+}
+
+/// emscripten: testSetjmp(id: c_int, table: *mut c_int, size: c_int) -> c_int
+///
+/// Returns the label `saveSetjmp` recorded along with `id` in `table`, or 0 if the `longjmp`
+/// doesn't return to a `setjmp` of the calling function.
+#[allow(non_snake_case)]
+pub fn _testSetjmp(ctx: &mut Ctx, id: u32, table: u32, size: u32) -> i32 {
+    debug!("emscripten::_testSetjmp");
+    let view = ctx.memory(0).view::<u32>();
+    for i in 0..size {
+        let slot = (table / 4 + i * 2) as usize;
+        match view[slot].get() {
+            0 => break,
+            current if current == id => return view[slot + 1].get() as i32,
+            _ => (),
+        }
+    }
+    0
 }
 
 // extern "C" {
@@ -13,7 +13,6 @@
 #[macro_use]
 extern crate wasmer_runtime_core;
 
-use lazy_static::lazy_static;
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -28,7 +27,7 @@ use wasmer_runtime_core::{
     memory::Memory,
     module::ImportName,
     table::Table,
-    types::{ElementType, FuncSig, MemoryDescriptor, TableDescriptor, Type, Value},
+    types::{ElementType, MemoryDescriptor, TableDescriptor, Value},
     units::Pages,
     vm::Ctx,
     Func, Instance, IsExport, Module,
@@ -45,6 +44,7 @@ type LibcDir = u8;
 mod macros;
 
 // EMSCRIPTEN APIS
+mod abi;
 mod bitwise;
 mod emscripten_target;
 mod env;
@@ -52,6 +52,8 @@ mod errno;
 mod exception;
 mod exec;
 mod exit;
+#[cfg(feature = "graphics")]
+mod graphics;
 mod inet;
 mod io;
 mod jmp;
@@ -60,6 +62,7 @@ mod linking;
 mod lock;
 mod math;
 mod memory;
+mod net;
 mod process;
 mod pthread;
 mod ptr;
@@ -67,27 +70,36 @@ mod signal;
 mod storage;
 mod syscalls;
 mod time;
+#[cfg(unix)]
+mod tty;
 mod ucontext;
 mod unistd;
 mod utils;
 mod varargs;
-
+mod vfs;
+
+pub use self::abi::{check_emscripten_imports, emscripten_abi_version, EmscriptenAbi};
+#[cfg(feature = "graphics")]
+use self::graphics::GraphicsState;
+#[cfg(feature = "graphics")]
+pub use self::graphics::{GraphicsEvent, GraphicsHost, HeadlessGraphics};
+use self::linking::DynamicLinker;
+pub use self::linking::SideModuleCompiler;
+pub use self::net::NetworkGrants;
+use self::pthread::ThreadState;
 pub use self::storage::{align_memory, static_alloc};
 pub use self::utils::{
     allocate_cstr_on_stack, allocate_on_stack, get_emscripten_memory_size, get_emscripten_metadata,
     get_emscripten_table_size, is_emscripten_module,
 };
+pub use self::vfs::EmscriptenVfs;
+use self::vfs::VfsState;
 
 // TODO: Magic number - how is this calculated?
 const TOTAL_STACK: u32 = 5_242_880;
 // TODO: make this variable
 const STATIC_BUMP: u32 = 215_536;
 
-lazy_static! {
-    static ref OLD_ABORT_ON_CANNOT_GROW_MEMORY_SIG: FuncSig =
-        { FuncSig::new(vec![], vec![Type::I32]) };
-}
-
 // The address globals begin at. Very low in memory, for code size and optimization opportunities.
 // Above 0 is static memory, starting with globals.
 // Then the stack.
@@ -100,10 +112,13 @@ pub struct EmscriptenData<'a> {
 
     pub malloc: Option<Func<'a, u32, u32>>,
     pub free: Option<Func<'a, u32>>,
+    pub realloc: Option<Func<'a, (u32, u32), u32>>,
     pub memalign: Option<Func<'a, (u32, u32), u32>>,
     pub memset: Option<Func<'a, (u32, u32, u32), u32>>,
     pub stack_alloc: Option<Func<'a, u32, u32>>,
     pub jumps: Vec<UnsafeCell<[u32; 27]>>,
+    /// The id of the last `setjmp`, given by `saveSetjmp`
+    pub setjmp_id: u32,
     pub opened_dirs: HashMap<i32, Box<*mut LibcDir>>,
 
     pub dyn_call_i: Option<Func<'a, i32, i32>>,
@@ -171,6 +186,12 @@ pub struct EmscriptenData<'a> {
     pub stack_restore: Option<Func<'a, (i32)>>,
     pub set_threw: Option<Func<'a, (i32, i32)>>,
     pub mapped_dirs: HashMap<String, PathBuf>,
+    pub linker: DynamicLinker,
+    pub thread: ThreadState,
+    pub network: NetworkGrants,
+    pub(crate) vfs: Option<VfsState>,
+    #[cfg(feature = "graphics")]
+    pub(crate) graphics: GraphicsState,
 }
 
 impl<'a> EmscriptenData<'a> {
@@ -181,6 +202,10 @@ impl<'a> EmscriptenData<'a> {
     ) -> EmscriptenData<'a> {
         let malloc = instance.func("_malloc").or(instance.func("malloc")).ok();
         let free = instance.func("_free").or(instance.func("free")).ok();
+        let realloc = instance
+            .func("_realloc")
+            .or(instance.func("realloc"))
+            .ok();
         let memalign = instance
             .func("_memalign")
             .or(instance.func("memalign"))
@@ -255,10 +280,12 @@ impl<'a> EmscriptenData<'a> {
 
             malloc,
             free,
+            realloc,
             memalign,
             memset,
             stack_alloc,
             jumps: Vec::new(),
+            setjmp_id: 0,
             opened_dirs: HashMap::new(),
 
             dyn_call_i,
@@ -321,12 +348,18 @@ impl<'a> EmscriptenData<'a> {
             stack_restore,
             set_threw,
             mapped_dirs,
+            linker: DynamicLinker::default(),
+            thread: ThreadState::default(),
+            network: NetworkGrants::default(),
+            vfs: None,
+            #[cfg(feature = "graphics")]
+            graphics: GraphicsState::new(None),
         }
     }
 }
 
 pub fn run_emscripten_instance(
-    _module: &Module,
+    module: &Module,
     instance: &mut Instance,
     globals: &mut EmscriptenGlobals,
     path: &str,
@@ -334,18 +367,34 @@ pub fn run_emscripten_instance(
     entrypoint: Option<String>,
     mapped_dirs: Vec<(String, PathBuf)>,
 ) -> CallResult<()> {
-    let mut data = EmscriptenData::new(instance, &globals.data, mapped_dirs.into_iter().collect());
+    let linker = DynamicLinker::new(instance, globals);
+    let mapped_dirs: HashMap<String, PathBuf> = mapped_dirs.into_iter().collect();
+    let mut data = EmscriptenData::new(instance, &globals.data, mapped_dirs.clone());
+    data.linker = linker;
+    data.network = globals.network.clone();
+    data.vfs = globals.vfs.clone().map(VfsState::new);
+    #[cfg(feature = "graphics")]
+    data.graphics = GraphicsState::new(globals.graphics.clone());
     let data_ptr = &mut data as *mut _ as *mut c_void;
     instance.context_mut().data = data_ptr;
 
+    // the threads of the programs built with `-s USE_PTHREADS=1` instantiate the module again
+    pthread::init_main_thread(module, instance, globals, mapped_dirs)?;
+
     // ATINIT
     // (used by C++)
-    if let Ok(_func) = instance.dyn_func("globalCtors") {
-        instance.call("globalCtors", &[])?;
-    }
+    if globals.data.abi == EmscriptenAbi::Upstream {
+        if let Ok(_func) = instance.dyn_func("__wasm_call_ctors") {
+            instance.call("__wasm_call_ctors", &[])?;
+        }
+    } else {
+        if let Ok(_func) = instance.dyn_func("globalCtors") {
+            instance.call("globalCtors", &[])?;
+        }
 
-    if let Ok(_func) = instance.dyn_func("___emscripten_environ_constructor") {
-        instance.call("___emscripten_environ_constructor", &[])?;
+        if let Ok(_func) = instance.dyn_func("___emscripten_environ_constructor") {
+            instance.call("___emscripten_environ_constructor", &[])?;
+        }
     }
 
     // println!("running emscripten instance");
@@ -423,6 +472,21 @@ pub fn emscripten_set_up_memory(
     Ok(())
 }
 
+/// Copies the static data of the program to `memory`, for the programs built with
+/// `--memory-init-file 1`, like the ones built with `-s USE_PTHREADS=1`.
+pub fn emscripten_load_memory_initializer(memory: &Memory, data: &[u8]) -> Result<(), String> {
+    let start = STATIC_BASE as usize;
+    let view = memory.view::<u8>();
+    if start + data.len() > view.len() {
+        return Err("memory initializer beyond memory len".to_string());
+    }
+    for (cell, byte) in view[start..start + data.len()].iter().zip(data) {
+        cell.set(*byte);
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
 pub struct EmscriptenGlobalsData {
     abort: u64,
     // Env namespace
@@ -433,7 +497,7 @@ pub struct EmscriptenGlobalsData {
     memory_base: u32,
     table_base: u32,
     temp_double_ptr: u32,
-    use_old_abort_on_cannot_grow_memory: bool,
+    abi: EmscriptenAbi,
 }
 
 pub struct EmscriptenGlobals {
@@ -445,30 +509,20 @@ pub struct EmscriptenGlobals {
     pub memory_min: Pages,
     pub memory_max: Option<Pages>,
     pub null_func_names: Vec<String>,
+    /// Compiles the side modules loaded with `dlopen`, which fails if it's `None`
+    pub side_module_compiler: Option<SideModuleCompiler>,
+    /// The addresses the sockets of the program may connect and bind to, none by default
+    pub network: NetworkGrants,
+    /// Serves the files of the program instead of the host filesystem, if it's set
+    pub vfs: Option<EmscriptenVfs>,
+    /// Renders the SDL, EGL and GL calls of the program, which runs headless if it's `None`
+    #[cfg(feature = "graphics")]
+    pub graphics: Option<std::sync::Arc<dyn GraphicsHost>>,
 }
 
 impl EmscriptenGlobals {
     pub fn new(module: &Module /*, static_bump: u32 */) -> Result<Self, String> {
-        let mut use_old_abort_on_cannot_grow_memory = false;
-        for (
-            index,
-            ImportName {
-                namespace_index,
-                name_index,
-            },
-        ) in &module.info().imported_functions
-        {
-            let namespace = module.info().namespace_table.get(*namespace_index);
-            let name = module.info().name_table.get(*name_index);
-            if name == "abortOnCannotGrowMemory" && namespace == "env" {
-                let sig_index = module.info().func_assoc[index.convert_up(module.info())];
-                let expected_sig = &module.info().signatures[sig_index];
-                if *expected_sig == *OLD_ABORT_ON_CANNOT_GROW_MEMORY_SIG {
-                    use_old_abort_on_cannot_grow_memory = true;
-                }
-                break;
-            }
-        }
+        let abi = EmscriptenAbi::detect(module);
 
         let (table_min, table_max) = get_emscripten_table_size(&module)?;
         let (memory_min, memory_max, shared) = get_emscripten_memory_size(&module)?;
@@ -516,7 +570,7 @@ impl EmscriptenGlobals {
                 memory_base,
                 table_base,
                 temp_double_ptr,
-                use_old_abort_on_cannot_grow_memory,
+                abi,
             }
         };
 
@@ -545,12 +599,17 @@ impl EmscriptenGlobals {
             memory_min,
             memory_max,
             null_func_names,
+            side_module_compiler: None,
+            network: NetworkGrants::default(),
+            vfs: None,
+            #[cfg(feature = "graphics")]
+            graphics: None,
         })
     }
 }
 
 pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject {
-    let abort_on_cannot_grow_memory_export = if globals.data.use_old_abort_on_cannot_grow_memory {
+    let abort_on_cannot_grow_memory_export = if globals.data.abi == EmscriptenAbi::LegacyFastcomp {
         func!(crate::memory::abort_on_cannot_grow_memory_old).to_export()
     } else {
         func!(crate::memory::abort_on_cannot_grow_memory).to_export()
@@ -849,6 +908,8 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         "__longjmp" => func!(crate::jmp::__longjmp),
         "_longjmp" => func!(crate::jmp::_longjmp),
         "_emscripten_longjmp" => func!(crate::jmp::_longjmp),
+        "_saveSetjmp" => func!(crate::jmp::_saveSetjmp),
+        "_testSetjmp" => func!(crate::jmp::_testSetjmp),
 
         // Bitwise
         "_llvm_bswap_i64" => func!(crate::bitwise::_llvm_bswap_i64),
@@ -937,6 +998,10 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         "_pthread_setcancelstate" => func!(crate::pthread::_pthread_setcancelstate),
         "_pthread_setspecific" => func!(crate::pthread::_pthread_setspecific),
         "_pthread_sigmask" => func!(crate::pthread::_pthread_sigmask),
+        "_emscripten_futex_wait" => func!(crate::pthread::_emscripten_futex_wait),
+        "_emscripten_futex_wake" => func!(crate::pthread::_emscripten_futex_wake),
+        "_emscripten_has_threading_support" => func!(crate::pthread::_emscripten_has_threading_support),
+        "_emscripten_num_logical_cores" => func!(crate::pthread::_emscripten_num_logical_cores),
         "___gxx_personality_v0" => func!(crate::emscripten_target::___gxx_personality_v0),
         "_gai_strerror" => func!(crate::env::_gai_strerror),
         "_getdtablesize" => func!(crate::emscripten_target::_getdtablesize),
@@ -997,6 +1062,9 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         "_confstr" => func!(crate::unistd::confstr),
     };
 
+    #[cfg(feature = "graphics")]
+    crate::graphics::insert_graphics_functions(&mut env_ns);
+
     // Compatibility with newer versions of Emscripten
     use crate::wasmer_runtime_core::import::LikeNamespace;
     for (k, v) in env_ns.get_exports() {
@@ -1012,7 +1080,7 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         env_ns.insert(null_func_name.as_str(), Func::new(nullfunc).to_export());
     }
 
-    let import_object: ImportObject = imports! {
+    let mut import_object: ImportObject = imports! {
         "env" => env_ns,
         "global" => {
           "NaN" => Global::new(Value::F64(f64::NAN)),
@@ -1028,6 +1096,9 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
             "f64-to-int" => func!(crate::math::f64_to_int),
         },
     };
+    if globals.data.abi == EmscriptenAbi::Upstream {
+        import_object.register("wasi_snapshot_preview1", crate::abi::wasi_namespace());
+    }
 
     import_object
 }
@@ -1,27 +1,411 @@
-use wasmer_runtime_core::vm::Ctx;
+//! Dynamic loading of emscripten side modules, for `dlopen`, `dlsym` and `dlclose`.
+//!
+//! A side module, built with `-s SIDE_MODULE=1`, imports the memory and the table of the main
+//! module.  Loading one reserves room for its data in the memory, with `malloc`, and for its
+//! functions at the end of the table, then instantiates it with `__memory_base` and
+//! `__table_base` pointing there.  Its other imports are resolved against the emscripten
+//! environment, the exports of the main module and those of the side modules loaded before it.
+//!
+//! Side modules are compiled by the `side_module_compiler` of `EmscriptenGlobals`; without one,
+//! `dlopen` fails.  Every library is loaded with `RTLD_NOW | RTLD_GLOBAL`, whatever the flags.
 
-// TODO: Need to implement.
+use crate::env::{call_free, call_malloc, get_emscripten_data};
+use crate::utils::{copy_cstr_into_wasm, get_cstr_path, read_string_from_wasm};
+use crate::{generate_emscripten_env, EmscriptenGlobals};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::CString,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use wasmer_runtime_core::{
+    export::Export,
+    global::Global,
+    import::{ImportObject, LikeNamespace, Namespace},
+    module::ExternDescriptor,
+    table::{Element, Table},
+    types::Value,
+    vm::Ctx,
+    Func, Instance, Module,
+};
+
+/// Compiles the side modules loaded by `dlopen`.
+pub type SideModuleCompiler = Arc<dyn Fn(&[u8]) -> Result<Module, String>>;
+
+/// The layout a side module asks for, from its `dylink` section.
+#[derive(Debug)]
+struct DylinkSection {
+    memory_size: u32,
+    /// The log2 of the alignment of its data
+    memory_alignment: u32,
+    table_size: u32,
+    /// The side modules it depends on
+    needed: Vec<String>,
+}
+
+/// A side module loaded by `dlopen`.
+struct SideModule {
+    path: PathBuf,
+    instance: Instance,
+    memory_base: u32,
+    /// The table indices of the functions returned by `dlsym`
+    function_pointers: HashMap<String, u32>,
+    /// The number of `dlopen`s not closed yet
+    references: u32,
+}
+
+impl SideModule {
+    /// Returns the address of the function or of the data `symbol` exported by the module.
+    fn resolve(&mut self, table: &Table, symbol: &str) -> Option<u32> {
+        if let Some(&index) = self.function_pointers.get(symbol) {
+            return Some(index);
+        }
+        if let Ok(func) = self.instance.dyn_func(symbol) {
+            let index = table.grow(1).ok()?;
+            table.set(index, Element::Anyfunc(func.into())).ok()?;
+            self.function_pointers.insert(symbol.to_string(), index);
+            return Some(index);
+        }
+        // data symbols are exported as their offset from `__memory_base`
+        match self.instance.exports().find(|(name, _)| name == symbol) {
+            Some((_, Export::Global(global))) => match global.get() {
+                Value::I32(offset) => Some(self.memory_base.wrapping_add(offset as u32)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The side modules loaded by the instance, and what they're linked against.
+#[derive(Default)]
+pub struct DynamicLinker {
+    compiler: Option<SideModuleCompiler>,
+    /// The imports of the main module along with its exports
+    imports: Option<ImportObject>,
+    table: Option<Table>,
+    libraries: BTreeMap<u32, SideModule>,
+    next_handle: u32,
+    /// The error `dlerror` returns next
+    error: Option<String>,
+    /// The last string returned by `dlerror`, freed by the next call
+    error_ptr: u32,
+}
+
+impl DynamicLinker {
+    /// Creates the linker of the side modules loaded by `instance`, the main module.
+    pub(crate) fn new(instance: &Instance, globals: &mut EmscriptenGlobals) -> Self {
+        let mut imports = generate_emscripten_env(globals);
+        // the main module only provides the functions the environment doesn't
+        let exports: Vec<_> = instance
+            .exports()
+            .filter(|(name, _)| {
+                imports
+                    .maybe_with_namespace("env", |env| env.get_export(name))
+                    .is_none()
+            })
+            .map(|(name, export)| ("env".to_string(), name, export))
+            .collect();
+        imports.extend(exports);
+
+        DynamicLinker {
+            compiler: globals.side_module_compiler.clone(),
+            imports: Some(imports),
+            table: Some(globals.table.clone()),
+            next_handle: 1,
+            ..DynamicLinker::default()
+        }
+    }
+
+    /// Returns the address of `symbol` in the library `handle`, or in any of them if `None`.
+    fn resolve(&mut self, handle: Option<u32>, symbol: &str) -> Option<u32> {
+        let table = self.table.as_ref()?;
+        self.libraries
+            .iter_mut()
+            .filter(|(library, _)| handle.map_or(true, |handle| handle == **library))
+            .find_map(|(_, library)| library.resolve(table, symbol))
+    }
+
+    /// Returns the export `name` of the side modules, for the imports of the next one.
+    fn find_export(&self, name: &str) -> Option<Export> {
+        self.libraries.values().find_map(|library| {
+            library
+                .instance
+                .exports()
+                .find(|(export, _)| export == name)
+                .map(|(_, export)| export)
+        })
+    }
+}
+
+/// Parses the `dylink` custom section, which is the first section of side modules.
+fn parse_dylink_section(wasm: &[u8]) -> Option<DylinkSection> {
+    fn read_leb(bytes: &[u8], offset: &mut usize) -> Option<u32> {
+        let mut result = 0u32;
+        let mut shift = 0;
+        loop {
+            let byte = *bytes.get(*offset)?;
+            *offset += 1;
+            result |= u32::from(byte & 0x7f).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(result);
+            }
+            shift += 7;
+        }
+    }
+    fn read_string(bytes: &[u8], offset: &mut usize) -> Option<String> {
+        let len = read_leb(bytes, offset)? as usize;
+        let string = bytes.get(*offset..offset.checked_add(len)?)?;
+        *offset += len;
+        String::from_utf8(string.to_vec()).ok()
+    }
+
+    // the header, then the id of a custom section
+    if wasm.get(..4)? != b"\0asm" || *wasm.get(8)? != 0 {
+        return None;
+    }
+    let mut offset = 9;
+    read_leb(wasm, &mut offset)?;
+    if read_string(wasm, &mut offset)? != "dylink" {
+        return None;
+    }
+    let memory_size = read_leb(wasm, &mut offset)?;
+    let memory_alignment = read_leb(wasm, &mut offset)?;
+    let table_size = read_leb(wasm, &mut offset)?;
+    let _table_alignment = read_leb(wasm, &mut offset)?;
+    let needed = (0..read_leb(wasm, &mut offset)?)
+        .map(|_| read_string(wasm, &mut offset))
+        .collect::<Option<_>>()?;
+    Some(DylinkSection {
+        memory_size,
+        memory_alignment,
+        table_size,
+        needed,
+    })
+}
+
+/// Loads the side module at `path`, or takes one more reference to it if it's loaded.
+fn load_library(ctx: &mut Ctx, path: &Path) -> Result<u32, String> {
+    let linker = &mut get_emscripten_data(ctx).linker;
+    if let Some((handle, library)) = linker
+        .libraries
+        .iter_mut()
+        .find(|(_, library)| library.path == path)
+    {
+        library.references += 1;
+        return Ok(*handle);
+    }
+    let compiler = linker
+        .compiler
+        .clone()
+        .ok_or_else(|| "loading side modules isn't enabled".to_string())?;
+
+    let wasm = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let dylink = parse_dylink_section(&wasm)
+        .ok_or_else(|| format!("{}: not a side module", path.display()))?;
+    for needed in &dylink.needed {
+        load_library(ctx, &path.with_file_name(needed))?;
+    }
+    let module = compiler(&wasm).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    // reserve room for the data and the functions of the module
+    let memory_base = if dylink.memory_size > 0 {
+        let alignment = 1u32 << dylink.memory_alignment.min(16);
+        let ptr = call_malloc(ctx, dylink.memory_size + alignment);
+        let memory_base = (ptr + alignment - 1) & !(alignment - 1);
+        let start = memory_base as usize;
+        for cell in &ctx.memory(0).view::<u8>()[start..start + dylink.memory_size as usize] {
+            cell.set(0);
+        }
+        memory_base
+    } else {
+        0
+    };
+    let linker = &mut get_emscripten_data(ctx).linker;
+    let table = linker.table.clone().ok_or("the module has no table")?;
+    let table_base = table
+        .grow(dylink.table_size)
+        .map_err(|e| format!("can't grow the table: {:?}", e))?;
+
+    let mut namespaces: HashMap<String, Namespace> = HashMap::new();
+    if let Some(imports) = &linker.imports {
+        for (namespace, name, export) in imports.clone_ref() {
+            namespaces
+                .entry(namespace)
+                .or_insert_with(Namespace::new)
+                .insert(name, export);
+        }
+    }
+    let env = namespaces
+        .entry("env".to_string())
+        .or_insert_with(Namespace::new);
+    for name in &["__memory_base", "memoryBase", "gb"] {
+        env.insert(*name, Global::new(Value::I32(memory_base as i32)));
+    }
+    for name in &["__table_base", "tableBase", "fb"] {
+        env.insert(*name, Global::new(Value::I32(table_base as i32)));
+    }
+    for import in module.imports() {
+        let name = import.name;
+        if import.namespace != "env" || env.contains_key(name.as_str()) {
+            continue;
+        }
+        if let ExternDescriptor::Function(_) = import.ty {
+            if name.starts_with("g$") {
+                // the address of a data symbol, through the GOT
+                let symbol = name[2..].to_string();
+                env.insert(
+                    name.as_str(),
+                    Func::new(move |ctx: &mut Ctx| -> i32 {
+                        let linker = &mut get_emscripten_data(ctx).linker;
+                        linker.resolve(None, &symbol).unwrap_or(0) as i32
+                    }),
+                );
+                continue;
+            }
+            if name.starts_with("fp$") {
+                // the table index of a function, named `fp$<name>$<signature>`
+                let symbol = name[3..].rsplitn(2, '$').last().unwrap_or("").to_string();
+                env.insert(
+                    name.as_str(),
+                    Func::new(move |ctx: &mut Ctx| -> i32 {
+                        let linker = &mut get_emscripten_data(ctx).linker;
+                        linker.resolve(None, &symbol).unwrap_or(0) as i32
+                    }),
+                );
+                continue;
+            }
+            if name.starts_with("nullFunc_") {
+                env.insert(name.as_str(), Func::new(crate::nullfunc));
+                continue;
+            }
+        }
+        if let Some(export) = linker.find_export(&name) {
+            env.insert(name.as_str(), export);
+        }
+    }
+    let mut import_object = ImportObject::new();
+    for (name, namespace) in namespaces {
+        import_object.register(name, namespace);
+    }
+
+    let mut instance = module
+        .instantiate(&import_object)
+        .map_err(|e| format!("{}: {:?}", path.display(), e))?;
+    // the imported functions of the environment find the emscripten data through the context
+    instance.context_mut().data = ctx.data;
+    if instance.dyn_func("__post_instantiate").is_ok() {
+        instance
+            .call("__post_instantiate", &[])
+            .map_err(|e| format!("{}: {:?}", path.display(), e))?;
+    }
+
+    let linker = &mut get_emscripten_data(ctx).linker;
+    let handle = linker.next_handle;
+    linker.next_handle += 1;
+    linker.libraries.insert(
+        handle,
+        SideModule {
+            path: path.to_path_buf(),
+            instance,
+            memory_base,
+            function_pointers: HashMap::new(),
+            references: 1,
+        },
+    );
+    Ok(handle)
+}
+
+fn set_error(ctx: &mut Ctx, error: String) {
+    debug!("emscripten::dl error: {}", error);
+    get_emscripten_data(ctx).linker.error = Some(error);
+}
 
 /// emscripten: dlopen(filename: *const c_char, flag: c_int) -> *mut c_void
-pub fn _dlopen(_ctx: &mut Ctx, _filename: u32, _flag: u32) -> i32 {
+pub fn _dlopen(ctx: &mut Ctx, filename: u32, _flag: u32) -> i32 {
     debug!("emscripten::_dlopen");
-    -1
+    if filename == 0 {
+        set_error(ctx, "dlopen(NULL) is not supported".to_string());
+        return 0;
+    }
+    let path = {
+        let filename_ptr = emscripten_memory_pointer!(ctx.memory(0), filename) as *const i8;
+        match get_cstr_path(ctx, filename_ptr) {
+            Some(path) => PathBuf::from(path.to_string_lossy().to_string()),
+            None => PathBuf::from(read_string_from_wasm(ctx.memory(0), filename)),
+        }
+    };
+    match load_library(ctx, &path) {
+        Ok(handle) => handle as i32,
+        Err(error) => {
+            set_error(ctx, error);
+            0
+        }
+    }
 }
 
 /// emscripten: dlclose(handle: *mut c_void) -> c_int
-pub fn _dlclose(_ctx: &mut Ctx, _filename: u32) -> i32 {
+pub fn _dlclose(ctx: &mut Ctx, handle: u32) -> i32 {
     debug!("emscripten::_dlclose");
-    -1
+    let linker = &mut get_emscripten_data(ctx).linker;
+    let references = match linker.libraries.get_mut(&handle) {
+        Some(library) => {
+            library.references -= 1;
+            library.references
+        }
+        None => {
+            set_error(ctx, format!("invalid handle {}", handle));
+            return -1;
+        }
+    };
+    if references == 0 {
+        // the memory and the table slots of the module aren't reused
+        linker.libraries.remove(&handle);
+    }
+    0
 }
 
 /// emscripten: dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void
-pub fn _dlsym(_ctx: &mut Ctx, _filepath: u32, _symbol: u32) -> i32 {
+pub fn _dlsym(ctx: &mut Ctx, handle: u32, symbol: u32) -> i32 {
     debug!("emscripten::_dlsym");
-    -1
+    let symbol = read_string_from_wasm(ctx.memory(0), symbol);
+    let linker = &mut get_emscripten_data(ctx).linker;
+    let address = match handle {
+        h if h != 0 && !linker.libraries.contains_key(&h) => Err(format!("invalid handle {}", h)),
+        // RTLD_DEFAULT looks the symbol up in every library
+        h => {
+            let handle = if h == 0 { None } else { Some(h) };
+            // the C symbols are prefixed with an underscore by fastcomp
+            linker
+                .resolve(handle, &format!("_{}", symbol))
+                .or_else(|| linker.resolve(handle, &symbol))
+                .ok_or_else(|| format!("symbol not found: {}", symbol))
+        }
+    };
+    match address {
+        Ok(address) => address as i32,
+        Err(error) => {
+            set_error(ctx, error);
+            0
+        }
+    }
 }
 
 /// emscripten: dlerror() -> *mut c_char
-pub fn _dlerror(_ctx: &mut Ctx) -> i32 {
+pub fn _dlerror(ctx: &mut Ctx) -> i32 {
     debug!("emscripten::_dlerror");
-    -1
+    let linker = &mut get_emscripten_data(ctx).linker;
+    let previous = std::mem::replace(&mut linker.error_ptr, 0);
+    let error = linker.error.take();
+    if previous != 0 {
+        call_free(ctx, previous);
+    }
+    match error.and_then(|error| CString::new(error).ok()) {
+        Some(error) => {
+            let error_ptr = unsafe { copy_cstr_into_wasm(ctx, error.as_ptr()) };
+            get_emscripten_data(ctx).linker.error_ptr = error_ptr;
+            error_ptr as i32
+        }
+        None => 0,
+    }
 }
@@ -0,0 +1,92 @@
+//! The network access granted to emscripten programs.
+//!
+//! Programs built with emscripten's POSIX sockets emulation use the socketcall syscall, which
+//! wasmer runs on host sockets.  They can only connect (or send datagrams) to the addresses
+//! granted with `NetworkGrants::allow_connect`, and bind to the ones granted with
+//! `NetworkGrants::allow_listen`; without any grant, creating a socket fails with `EACCES`.
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use wasmer_runtime_core::vm::Ctx;
+
+/// `AF_INET` of emscripten's libc
+const GUEST_AF_INET: u16 = 2;
+/// `AF_INET6` of emscripten's libc
+const GUEST_AF_INET6: u16 = 10;
+
+/// What the socket calls which weren't granted fail with
+#[allow(dead_code)] // it's only used in `syscalls/unix.rs`.
+pub(crate) const EACCES: i32 = 13;
+
+/// The addresses a program may connect to and bind to.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NetworkGrants {
+    connect: Vec<SocketAddr>,
+    listen: Vec<SocketAddr>,
+}
+
+impl NetworkGrants {
+    /// Lets the program connect and send datagrams to `addr`.
+    pub fn allow_connect(&mut self, addr: SocketAddr) -> &mut Self {
+        self.connect.push(addr);
+
+        self
+    }
+
+    /// Lets the program bind to `addr`, to listen on it or receive datagrams.
+    pub fn allow_listen(&mut self, addr: SocketAddr) -> &mut Self {
+        self.listen.push(addr);
+
+        self
+    }
+
+    /// Returns whether connecting to `addr` was granted.
+    pub fn can_connect(&self, addr: &SocketAddr) -> bool {
+        self.connect.contains(addr)
+    }
+
+    /// Returns whether binding to `addr` was granted.
+    pub fn can_listen(&self, addr: &SocketAddr) -> bool {
+        self.listen.contains(addr)
+    }
+
+    /// Returns whether any address was granted, the program may create sockets only then.
+    pub fn is_empty(&self) -> bool {
+        self.connect.is_empty() && self.listen.is_empty()
+    }
+}
+
+/// Reads the `sockaddr_in` or `sockaddr_in6` at `addr` in the memory of the program.
+///
+/// Returns `None` for the other families, or if `len` is too short for the family.
+#[allow(dead_code)] // it's only used in `syscalls/unix.rs`.
+pub(crate) fn read_guest_socket_addr(ctx: &Ctx, addr: u32, len: u32) -> Option<SocketAddr> {
+    let view = ctx.memory(0).view::<u8>();
+    let start = addr as usize;
+    let end = start.checked_add(len as usize)?;
+    if len < 4 || end > view.len() {
+        return None;
+    }
+    let bytes: Vec<u8> = view[start..end].iter().map(|cell| cell.get()).collect();
+    let family = u16::from_le_bytes([bytes[0], bytes[1]]);
+    // the port and the address are in network byte order
+    let port = u16::from_be_bytes([bytes[2], bytes[3]]);
+    match family {
+        GUEST_AF_INET if bytes.len() >= 8 => {
+            let ip = Ipv4Addr::new(bytes[4], bytes[5], bytes[6], bytes[7]);
+            Some(SocketAddr::V4(SocketAddrV4::new(ip, port)))
+        }
+        GUEST_AF_INET6 if bytes.len() >= 28 => {
+            let flowinfo = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&bytes[8..24]);
+            let scope_id = u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]);
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(octets),
+                port,
+                flowinfo,
+                scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
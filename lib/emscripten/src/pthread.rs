@@ -1,4 +1,419 @@
-use wasmer_runtime_core::vm::Ctx;
+//! pthreads, for the programs built with `-s USE_PTHREADS=1`.
+//!
+//! Such a program imports a shared memory.  Every thread it creates runs on a host thread of its
+//! own, in a new instance of the module importing the same memory, with its stack and its
+//! thread-specific data allocated with the `malloc` of the program, like the workers of the web.
+//! The futexes its mutexes and condition variables are built on wait on the host address of the
+//! word, so the threads of all the instances see each other.
+//!
+//! The instances of the threads don't apply the data segments to the memory again nor run the
+//! start function. Each gets a copy of the table of the main thread as it is when the thread is
+//! created, so the functions added at runtime, by `dlopen` or `addFunction`, can be called from
+//! it, while the functions of the module itself run in the instance of the thread.
+//!
+//! In the other programs, `pthread_create` fails with `EAGAIN`.
+
+use crate::env::{call_free, call_malloc, call_memalign, get_emscripten_data};
+use crate::vfs::{EmscriptenVfs, VfsState};
+use crate::{
+    generate_emscripten_env, EmscriptenData, EmscriptenGlobals, EmscriptenGlobalsData,
+    NetworkGrants,
+};
+#[cfg(feature = "graphics")]
+use crate::{graphics::GraphicsState, GraphicsHost};
+use std::{
+    collections::HashMap,
+    ffi::c_void,
+    path::PathBuf,
+    sync::{atomic::Ordering, Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use wasmer_runtime_core::{
+    error::{CallError, CallResult, RuntimeError},
+    memory::{
+        atomic::{notify, wait32, WaitResult},
+        Memory,
+    },
+    table::Table,
+    types::{TableDescriptor, Value},
+    units::Pages,
+    vm::Ctx,
+    Instance, Module,
+};
+
+// The errno values of the emscripten libc
+const ESRCH: i32 = 3;
+const EAGAIN: i32 = 11;
+const EINVAL: i32 = 22;
+const EDEADLK: i32 = 35;
+const ETIMEDOUT: i32 = 110;
+
+// The layout of `struct pthread` in the musl of emscripten, which puts fields of its own first
+const PTHREAD_THREAD_STATUS: u32 = 0;
+const PTHREAD_THREAD_EXIT_CODE: u32 = 4;
+const PTHREAD_SELF: u32 = 24;
+const PTHREAD_TID: u32 = 52;
+const PTHREAD_PID: u32 = 56;
+const PTHREAD_DETACHED: u32 = 80;
+const PTHREAD_STACK: u32 = 92;
+const PTHREAD_STACK_SIZE: u32 = 96;
+const PTHREAD_TSD: u32 = 116;
+const PTHREAD_ATTR: u32 = 120;
+const PTHREAD_ROBUST_LIST: u32 = 168;
+const PTHREAD_SIZE: u32 = 244;
+
+const PTHREAD_KEYS_MAX: u32 = 128;
+/// The size musl leaves out of the stack size it stores in `pthread_attr_t`
+const MUSL_DEFAULT_STACK_SIZE: u32 = 81_920;
+/// The stack size of the threads created without attributes, as on Linux
+const DEFAULT_PTHREAD_STACK_SIZE: u32 = 2 * 1024 * 1024;
+const STACK_ALIGN: u32 = 16;
+/// The pid emscripten gives the process
+const PID: u32 = 42;
+
+/// What the threads of a program need to instantiate it again, shared by all of them.
+pub(crate) struct ThreadRuntime {
+    module: Module,
+    memory: Memory,
+    /// The table of the main thread
+    table: Table,
+    globals: EmscriptenGlobalsData,
+    memory_min: Pages,
+    memory_max: Option<Pages>,
+    null_func_names: Vec<String>,
+    mapped_dirs: HashMap<String, PathBuf>,
+    network: NetworkGrants,
+    vfs: Option<EmscriptenVfs>,
+    #[cfg(feature = "graphics")]
+    graphics: Option<Arc<dyn GraphicsHost>>,
+    /// The `pthread_t` of the thread running `main`
+    main_thread: u32,
+    /// The threads not joined nor detached yet, by `pthread_t`
+    threads: Mutex<HashMap<u32, Thread>>,
+}
+
+struct Thread {
+    handle: JoinHandle<()>,
+    /// The stack allocated for the thread, freed along with it
+    stack: Option<u32>,
+    exited: bool,
+}
+
+/// The pthread an instance runs.
+#[derive(Default)]
+pub struct ThreadState {
+    /// Its `pthread_t`, 0 if the program isn't multithreaded
+    pub self_ptr: u32,
+    runtime: Option<Arc<ThreadRuntime>>,
+}
+
+/// What a thread calling `pthread_exit` unwinds with, up to the host thread running it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadExit(pub i32);
+
+impl ThreadExit {
+    fn from_call_error(error: &CallError) -> Option<ThreadExit> {
+        match error {
+            CallError::Runtime(RuntimeError::Error { data, .. }) => {
+                data.downcast_ref::<ThreadExit>().cloned()
+            }
+            _ => None,
+        }
+    }
+}
+
+fn load(memory: &Memory, ptr: u32) -> u32 {
+    memory.view::<u32>().atomically()[ptr as usize / 4].load(Ordering::SeqCst)
+}
+
+fn store(memory: &Memory, ptr: u32, value: u32) {
+    memory.view::<u32>().atomically()[ptr as usize / 4].store(value, Ordering::SeqCst)
+}
+
+/// Returns the host address of the word at `ptr`, the key of the futexes waiting on it.
+fn futex_address(memory: &Memory, ptr: u32) -> Option<*const u32> {
+    if ptr % 4 != 0 {
+        return None;
+    }
+    memory
+        .view::<u32>()
+        .get(ptr as usize / 4)
+        .map(|cell| cell.as_ptr() as *const u32)
+}
+
+/// Returns whether `ptr` points to a `struct pthread`.
+fn is_pthread(memory: &Memory, ptr: u32) -> bool {
+    ptr != 0
+        && ptr % 4 == 0
+        && (ptr as usize + PTHREAD_SIZE as usize) <= memory.size().bytes().0
+        && load(memory, ptr + PTHREAD_SELF) == ptr
+}
+
+/// Allocates and initializes a `struct pthread` along with its thread-specific data.
+fn allocate_pthread(ctx: &mut Ctx) -> u32 {
+    let tsd = call_malloc(ctx, PTHREAD_KEYS_MAX * 4);
+    if tsd == 0 {
+        return 0;
+    }
+    let pthread = call_malloc(ctx, PTHREAD_SIZE);
+    if pthread == 0 {
+        call_free(ctx, tsd);
+        return 0;
+    }
+    let memory = ctx.memory(0);
+    for cell in &memory.view::<u8>()[tsd as usize..(tsd + PTHREAD_KEYS_MAX * 4) as usize] {
+        cell.set(0);
+    }
+    for cell in &memory.view::<u8>()[pthread as usize..(pthread + PTHREAD_SIZE) as usize] {
+        cell.set(0);
+    }
+    // `self` tells the live threads apart, and the robust list starts empty
+    store(memory, pthread + PTHREAD_SELF, pthread);
+    store(
+        memory,
+        pthread + PTHREAD_ROBUST_LIST,
+        pthread + PTHREAD_ROBUST_LIST,
+    );
+    store(memory, pthread + PTHREAD_TSD, tsd);
+    store(memory, pthread + PTHREAD_TID, pthread);
+    store(memory, pthread + PTHREAD_PID, PID);
+    pthread
+}
+
+fn free_pthread(ctx: &mut Ctx, pthread: u32, stack: Option<u32>) {
+    let tsd = load(ctx.memory(0), pthread + PTHREAD_TSD);
+    call_free(ctx, tsd);
+    call_free(ctx, pthread);
+    if let Some(stack) = stack {
+        call_free(ctx, stack);
+    }
+}
+
+/// Tells the module which thread it runs, if it keeps track of it.
+fn register_pthread(instance: &mut Instance, pthread: u32, is_main: bool) -> CallResult<()> {
+    if instance.dyn_func("__register_pthread_ptr").is_ok() {
+        let is_main = Value::I32(is_main as i32);
+        instance.call(
+            "__register_pthread_ptr",
+            &[Value::I32(pthread as i32), is_main.clone(), is_main],
+        )?;
+    }
+    Ok(())
+}
+
+/// Sets up the main thread of `instance`, if the program is multithreaded.
+///
+/// The emscripten data of the instance must be set.
+pub(crate) fn init_main_thread(
+    module: &Module,
+    instance: &mut Instance,
+    globals: &EmscriptenGlobals,
+    mapped_dirs: HashMap<String, PathBuf>,
+) -> CallResult<()> {
+    if !globals.memory.descriptor().shared {
+        return Ok(());
+    }
+    let pthread = allocate_pthread(instance.context_mut());
+    let runtime = ThreadRuntime {
+        module: module.clone(),
+        memory: globals.memory.clone(),
+        table: globals.table.clone(),
+        globals: globals.data.clone(),
+        memory_min: globals.memory_min,
+        memory_max: globals.memory_max,
+        null_func_names: globals.null_func_names.clone(),
+        mapped_dirs,
+        network: globals.network.clone(),
+        vfs: globals.vfs.clone(),
+        #[cfg(feature = "graphics")]
+        graphics: globals.graphics.clone(),
+        main_thread: pthread,
+        threads: Mutex::new(HashMap::new()),
+    };
+    get_emscripten_data(instance.context_mut()).thread = ThreadState {
+        self_ptr: pthread,
+        runtime: Some(Arc::new(runtime)),
+    };
+
+    register_pthread(instance, pthread, true)?;
+    if instance
+        .dyn_func("_emscripten_register_main_browser_thread_id")
+        .is_ok()
+    {
+        instance.call(
+            "_emscripten_register_main_browser_thread_id",
+            &[Value::I32(pthread as i32)],
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns a table holding the current elements of `table`.
+fn copy_table(table: &Table) -> Table {
+    let size = table.size();
+    let copy = Table::new(TableDescriptor {
+        minimum: size,
+        ..table.descriptor()
+    })
+    .unwrap();
+    for index in 0..size {
+        if let Some(element) = table.get(index) {
+            let _ = copy.set(index, element);
+        }
+    }
+    copy
+}
+
+/// Runs `start_routine(arg)` in a new instance of the program.
+fn run_pthread(
+    runtime: Arc<ThreadRuntime>,
+    pthread: u32,
+    stack_base: u32,
+    stack_size: u32,
+    start_routine: u32,
+    arg: u32,
+) {
+    let mut globals = EmscriptenGlobals {
+        data: runtime.globals.clone(),
+        memory: runtime.memory.clone(),
+        table: copy_table(&runtime.table),
+        memory_min: runtime.memory_min,
+        memory_max: runtime.memory_max,
+        null_func_names: runtime.null_func_names.clone(),
+        side_module_compiler: None,
+        network: runtime.network.clone(),
+        vfs: runtime.vfs.clone(),
+        #[cfg(feature = "graphics")]
+        graphics: runtime.graphics.clone(),
+    };
+    let import_object = generate_emscripten_env(&mut globals);
+    let mut instance = match runtime.module.instantiate_thread(&import_object) {
+        Ok(instance) => instance,
+        Err(e) => {
+            debug!(
+                "emscripten::pthread {} can't be instantiated: {:?}",
+                pthread, e
+            );
+            exit_pthread(&runtime, None, pthread, -2);
+            return;
+        }
+    };
+
+    let mut data = EmscriptenData::new(&mut instance, &globals.data, runtime.mapped_dirs.clone());
+    data.network = runtime.network.clone();
+    data.vfs = runtime.vfs.clone().map(VfsState::new);
+    #[cfg(feature = "graphics")]
+    data.graphics = GraphicsState::new(runtime.graphics.clone());
+    data.thread = ThreadState {
+        self_ptr: pthread,
+        runtime: Some(runtime.clone()),
+    };
+    let data_ptr = &mut data as *mut _ as *mut c_void;
+    instance.context_mut().data = data_ptr;
+
+    let result = register_pthread(&mut instance, pthread, false).and_then(|()| {
+        // the stack grows upwards
+        if instance.dyn_func("establishStackSpace").is_ok() {
+            instance.call(
+                "establishStackSpace",
+                &[
+                    Value::I32(stack_base as i32),
+                    Value::I32((stack_base + stack_size) as i32),
+                ],
+            )?;
+        }
+        instance.call(
+            "dynCall_ii",
+            &[Value::I32(start_routine as i32), Value::I32(arg as i32)],
+        )
+    });
+    let exit_code = match result {
+        Ok(values) => match values.first() {
+            Some(Value::I32(value)) => *value,
+            _ => 0,
+        },
+        Err(e) => match ThreadExit::from_call_error(&e) {
+            Some(ThreadExit(status)) => status,
+            None => {
+                debug!("emscripten::pthread {} crashed: {:?}", pthread, e);
+                // what emscripten returns for the threads which crashed
+                -2
+            }
+        },
+    };
+    if instance.dyn_func("___pthread_tsd_run_dtors").is_ok() {
+        let _ = instance.call("___pthread_tsd_run_dtors", &[]);
+    }
+    exit_pthread(&runtime, Some(instance.context_mut()), pthread, exit_code);
+}
+
+/// Marks `pthread` as exited, waking up the threads joining it, or frees it if it's detached.
+fn exit_pthread(runtime: &ThreadRuntime, ctx: Option<&mut Ctx>, pthread: u32, exit_code: i32) {
+    let memory = &runtime.memory;
+    let mut threads = runtime.threads.lock().unwrap();
+    store(memory, pthread + PTHREAD_THREAD_EXIT_CODE, exit_code as u32);
+    if load(memory, pthread + PTHREAD_DETACHED) != 0 {
+        let thread = threads.remove(&pthread);
+        drop(threads);
+        if let (Some(thread), Some(ctx)) = (thread, ctx) {
+            free_pthread(ctx, pthread, thread.stack);
+        }
+        return;
+    }
+    if let Some(thread) = threads.get_mut(&pthread) {
+        thread.exited = true;
+    }
+    store(memory, pthread + PTHREAD_THREAD_STATUS, 1);
+    if let Some(address) = futex_address(memory, pthread + PTHREAD_THREAD_STATUS) {
+        notify(address as *const u8, u32::max_value());
+    }
+}
+
+/// emscripten: emscripten_futex_wait(addr: *mut c_void, val: u32, max_wait_ms: f64) -> c_int
+pub fn _emscripten_futex_wait(ctx: &mut Ctx, addr: u32, val: u32, max_wait_ms: f64) -> i32 {
+    trace!("emscripten::_emscripten_futex_wait({}, {})", addr, val);
+    let address = match futex_address(ctx.memory(0), addr) {
+        Some(address) => address,
+        None => return -EINVAL,
+    };
+    // an infinite wait is passed as `Infinity`
+    let timeout = if max_wait_ms.is_finite() {
+        Some(Duration::from_micros(
+            (max_wait_ms.max(0.0) * 1000.0) as u64,
+        ))
+    } else {
+        None
+    };
+    match unsafe { wait32(address, val, timeout) } {
+        WaitResult::Ok => 0,
+        WaitResult::NotEqual => -EAGAIN,
+        WaitResult::TimedOut => -ETIMEDOUT,
+    }
+}
+
+/// emscripten: emscripten_futex_wake(addr: *mut c_void, count: c_int) -> c_int
+pub fn _emscripten_futex_wake(ctx: &mut Ctx, addr: u32, count: i32) -> i32 {
+    trace!("emscripten::_emscripten_futex_wake({}, {})", addr, count);
+    match futex_address(ctx.memory(0), addr) {
+        Some(address) if count >= 0 => notify(address as *const u8, count as u32) as i32,
+        _ => -EINVAL,
+    }
+}
+
+pub fn _emscripten_has_threading_support(ctx: &mut Ctx) -> i32 {
+    trace!("emscripten::_emscripten_has_threading_support");
+    get_emscripten_data(ctx).thread.runtime.is_some() as i32
+}
+
+pub fn _emscripten_num_logical_cores(_ctx: &mut Ctx) -> i32 {
+    trace!("emscripten::_emscripten_num_logical_cores");
+    #[cfg(unix)]
+    let cores = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) } as i32;
+    #[cfg(not(unix))]
+    let cores = 1;
+    cores.max(1)
+}
 
 pub fn _pthread_attr_destroy(_ctx: &mut Ctx, _a: i32) -> i32 {
     trace!("emscripten::_pthread_attr_destroy");
@@ -81,24 +496,134 @@ pub fn _pthread_condattr_setclock(_ctx: &mut Ctx, _a: i32, _b: i32) -> i32 {
     0
 }
 
-pub fn _pthread_create(_ctx: &mut Ctx, _a: i32, _b: i32, _c: i32, _d: i32) -> i32 {
+/// emscripten: pthread_create(thread: *mut pthread_t, attr: *const pthread_attr_t,
+///                             start_routine: fn(*mut c_void) -> *mut c_void,
+///                             arg: *mut c_void) -> c_int
+pub fn _pthread_create(
+    ctx: &mut Ctx,
+    thread_ptr: u32,
+    attr: u32,
+    start_routine: u32,
+    arg: u32,
+) -> i32 {
     trace!("emscripten::_pthread_create");
-    // 11 seems to mean "no"
-    11
-}
-
-pub fn _pthread_detach(_ctx: &mut Ctx, _a: i32) -> i32 {
+    let runtime = match &get_emscripten_data(ctx).thread.runtime {
+        Some(runtime) => runtime.clone(),
+        None => return EAGAIN,
+    };
+    let (stack_size, stack_top, detached) = if attr != 0 {
+        let memory = ctx.memory(0);
+        (
+            load(memory, attr) + MUSL_DEFAULT_STACK_SIZE,
+            load(memory, attr + 8),
+            load(memory, attr + 12) != 0,
+        )
+    } else {
+        (DEFAULT_PTHREAD_STACK_SIZE, 0, false)
+    };
+    // the stack given in the attributes is described by its top, as it grows downwards in musl
+    let (stack_base, own_stack) = if stack_top == 0 {
+        (call_memalign(ctx, STACK_ALIGN, stack_size), true)
+    } else {
+        (stack_top.wrapping_sub(stack_size), false)
+    };
+    if stack_base == 0 {
+        return EAGAIN;
+    }
+    let stack = if own_stack { Some(stack_base) } else { None };
+    let pthread = allocate_pthread(ctx);
+    if pthread == 0 {
+        if let Some(stack) = stack {
+            call_free(ctx, stack);
+        }
+        return EAGAIN;
+    }
+    let memory = ctx.memory(0);
+    store(memory, pthread + PTHREAD_DETACHED, detached as u32);
+    store(memory, pthread + PTHREAD_STACK, stack_base + stack_size);
+    store(memory, pthread + PTHREAD_STACK_SIZE, stack_size);
+    store(memory, pthread + PTHREAD_ATTR, stack_size);
+    store(memory, pthread + PTHREAD_ATTR + 8, stack_base + stack_size);
+    store(memory, pthread + PTHREAD_ATTR + 12, detached as u32);
+    store(memory, thread_ptr, pthread);
+
+    // the thread is registered before it can exit
+    let mut threads = runtime.threads.lock().unwrap();
+    let spawned = {
+        let runtime = runtime.clone();
+        thread::Builder::new()
+            .name(format!("pthread {}", pthread))
+            .spawn(move || {
+                run_pthread(runtime, pthread, stack_base, stack_size, start_routine, arg)
+            })
+    };
+    match spawned {
+        Ok(handle) => {
+            threads.insert(
+                pthread,
+                Thread {
+                    handle,
+                    stack,
+                    exited: false,
+                },
+            );
+            0
+        }
+        Err(e) => {
+            drop(threads);
+            debug!("emscripten::_pthread_create: {}", e);
+            free_pthread(ctx, pthread, stack);
+            EAGAIN
+        }
+    }
+}
+
+/// emscripten: pthread_detach(thread: pthread_t) -> c_int
+pub fn _pthread_detach(ctx: &mut Ctx, pthread: u32) -> i32 {
     trace!("emscripten::_pthread_detach");
-    0
-}
-
-pub fn _pthread_equal(_ctx: &mut Ctx, _a: i32, _b: i32) -> i32 {
+    let runtime = match &get_emscripten_data(ctx).thread.runtime {
+        Some(runtime) => runtime.clone(),
+        None => return 0,
+    };
+    let memory = ctx.memory(0);
+    if !is_pthread(memory, pthread) {
+        return ESRCH;
+    }
+    let mut threads = runtime.threads.lock().unwrap();
+    if load(memory, pthread + PTHREAD_DETACHED) != 0 {
+        return EINVAL;
+    }
+    store(memory, pthread + PTHREAD_DETACHED, 1);
+    // a thread which exited already is freed now, otherwise when it exits
+    if threads.get(&pthread).map_or(false, |thread| thread.exited) {
+        let thread = threads.remove(&pthread).unwrap();
+        drop(threads);
+        free_pthread(ctx, pthread, thread.stack);
+    }
+    0
+}
+
+pub fn _pthread_equal(_ctx: &mut Ctx, a: i32, b: i32) -> i32 {
     trace!("emscripten::_pthread_equal");
-    0
+    (a == b) as i32
 }
 
-pub fn _pthread_exit(_ctx: &mut Ctx, _a: i32) -> () {
+/// emscripten: pthread_exit(retval: *mut c_void)
+///
+/// The threads created by the program unwind up to their host thread.  The main thread exits
+/// the process, without waiting for the other threads.
+pub fn _pthread_exit(ctx: &mut Ctx, status: i32) -> Result<(), ThreadExit> {
     trace!("emscripten::_pthread_exit");
+    let state = &get_emscripten_data(ctx).thread;
+    let created = match &state.runtime {
+        Some(runtime) => runtime.main_thread != state.self_ptr,
+        None => false,
+    };
+    if created {
+        return Err(ThreadExit(status));
+    }
+    crate::exit::exit(ctx, status);
+    Ok(())
 }
 
 pub fn _pthread_getattr_np(_ctx: &mut Ctx, _thread: i32, _attr: i32) -> i32 {
@@ -111,14 +636,46 @@ pub fn _pthread_getspecific(_ctx: &mut Ctx, _a: i32) -> i32 {
     0
 }
 
-pub fn _pthread_join(_ctx: &mut Ctx, _a: i32, _b: i32) -> i32 {
+/// emscripten: pthread_join(thread: pthread_t, retval: *mut *mut c_void) -> c_int
+pub fn _pthread_join(ctx: &mut Ctx, pthread: u32, retval: u32) -> i32 {
     trace!("emscripten::_pthread_join");
-    0
-}
-
-pub fn _pthread_self(_ctx: &mut Ctx) -> i32 {
+    let state = &get_emscripten_data(ctx).thread;
+    let (self_ptr, runtime) = match &state.runtime {
+        Some(runtime) => (state.self_ptr, runtime.clone()),
+        None => return ESRCH,
+    };
+    if pthread == self_ptr {
+        return EDEADLK;
+    }
+    let memory = ctx.memory(0);
+    if !is_pthread(memory, pthread) {
+        return ESRCH;
+    }
+    if load(memory, pthread + PTHREAD_DETACHED) != 0 {
+        return EINVAL;
+    }
+    if let Some(status) = futex_address(memory, pthread + PTHREAD_THREAD_STATUS) {
+        while load(memory, pthread + PTHREAD_THREAD_STATUS) != 1 {
+            unsafe { wait32(status, 0, None) };
+        }
+    }
+    if retval != 0 {
+        let exit_code = load(memory, pthread + PTHREAD_THREAD_EXIT_CODE);
+        store(memory, retval, exit_code);
+    }
+    store(memory, pthread + PTHREAD_DETACHED, 1);
+
+    let thread = runtime.threads.lock().unwrap().remove(&pthread);
+    if let Some(thread) = thread {
+        let _ = thread.handle.join();
+        free_pthread(ctx, pthread, thread.stack);
+    }
+    0
+}
+
+pub fn _pthread_self(ctx: &mut Ctx) -> i32 {
     trace!("emscripten::_pthread_self");
-    0
+    get_emscripten_data(ctx).thread.self_ptr as i32
 }
 
 pub fn _pthread_key_create(_ctx: &mut Ctx, _a: i32, _b: i32) -> i32 {
@@ -13,6 +13,7 @@ pub use self::windows::*;
 use crate::{
     ptr::{Array, WasmPtr},
     utils::{copy_stat_into_wasm, get_cstr_path, get_current_directory},
+    vfs,
 };
 
 use super::varargs::VarArgs;
@@ -48,9 +49,33 @@ use wasmer_runtime_core::vm::Ctx;
 use super::env;
 use std::cell::Cell;
 #[allow(unused_imports)]
-use std::io::Error;
+use std::io::{Error, ErrorKind};
 use std::slice;
 
+/// Reads from the host `fd`, retrying the reads interrupted by a signal before reading anything,
+/// so that reading an interactive stdin waits for the input instead of failing, which the libc
+/// would take as the end of the input.
+unsafe fn read_retrying(fd: c_int, buf: *mut c_void, count: usize) -> isize {
+    loop {
+        let ret = read(fd, buf, count as _);
+        if ret >= 0 || Error::last_os_error().kind() != ErrorKind::Interrupted {
+            return ret as isize;
+        }
+    }
+}
+
+/// Returns what a syscall failing with the last host error returns.
+fn host_error() -> i32 {
+    #[cfg(unix)]
+    {
+        -crate::tty::last_guest_errno()
+    }
+    #[cfg(not(unix))]
+    {
+        -1
+    }
+}
+
 /// exit
 pub fn ___syscall1(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) {
     debug!("emscripten::___syscall1 (exit) {}", _which);
@@ -68,9 +93,15 @@ pub fn ___syscall3(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
     let buf: u32 = varargs.get(ctx);
     let count: i32 = varargs.get(ctx);
     debug!("=> fd: {}, buf_offset: {}, count: {}", fd, buf, count);
+    if let Some(ret) = vfs::read(ctx, fd, buf, count as u32) {
+        return ret;
+    }
     let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as *mut c_void;
-    let ret = unsafe { read(fd, buf_addr, count as _) };
+    let ret = unsafe { read_retrying(fd, buf_addr, count as usize) };
     debug!("=> ret: {}", ret);
+    if ret < 0 {
+        return host_error();
+    }
     ret as _
 }
 
@@ -81,6 +112,9 @@ pub fn ___syscall4(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
     let buf: i32 = varargs.get(ctx);
     let count: i32 = varargs.get(ctx);
     debug!("=> fd: {}, buf: {}, count: {}", fd, buf, count);
+    if let Some(ret) = vfs::write(ctx, fd, buf as u32, count as u32) {
+        return ret;
+    }
     let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as *const c_void;
     unsafe { write(fd, buf_addr, count as _) as i32 }
 }
@@ -90,6 +124,9 @@ pub fn ___syscall6(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
     debug!("emscripten::___syscall6 (close) {}", _which);
     let fd: i32 = varargs.get(ctx);
     debug!("fd: {}", fd);
+    if let Some(ret) = vfs::close(ctx, fd) {
+        return ret;
+    }
     unsafe { close(fd) }
 }
 
@@ -168,6 +205,9 @@ pub fn ___syscall38(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> i32 {
     debug!("emscripten::___syscall38 (rename)");
     let old_path = varargs.get_str(ctx);
     let new_path = varargs.get_str(ctx);
+    if let Some(ret) = vfs::rename(ctx, old_path, new_path) {
+        return ret;
+    }
     let real_old_path_owned = get_cstr_path(ctx, old_path as *const _);
     let real_old_path = if let Some(ref rp) = real_old_path_owned {
         rp.as_c_str().as_ptr()
@@ -194,6 +234,9 @@ pub fn ___syscall38(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> i32 {
 pub fn ___syscall40(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall40 (rmdir)");
     let pathname_addr = varargs.get_str(ctx);
+    if let Some(ret) = vfs::rmdir(ctx, pathname_addr) {
+        return ret;
+    }
     let real_path_owned = get_cstr_path(ctx, pathname_addr as *const _);
     let real_path = if let Some(ref rp) = real_path_owned {
         rp.as_c_str().as_ptr()
@@ -405,11 +448,25 @@ pub fn ___syscall140(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
     // -> c_int
     debug!("emscripten::___syscall140 (lseek) {}", _which);
     let fd: i32 = varargs.get(ctx);
-    let _offset_high: u32 = varargs.get(ctx); // We don't use the offset high as emscripten skips it
+    let offset_high: u32 = varargs.get(ctx); // Only the vfs uses the offset high, emscripten skips it
     let offset_low: u32 = varargs.get(ctx);
     let result_ptr_value: WasmPtr<i64> = varargs.get(ctx);
     let whence: i32 = varargs.get(ctx);
     let offset = offset_low;
+    if let Some(result) = vfs::lseek(
+        ctx,
+        fd,
+        (i64::from(offset_high) << 32) | i64::from(offset_low),
+        whence,
+    ) {
+        return match result {
+            Ok(position) => {
+                result_ptr_value.deref(ctx.memory(0)).unwrap().set(position);
+                0
+            }
+            Err(errno) => -errno,
+        };
+    }
     let ret = unsafe { lseek(fd, offset as _, whence) as i64 };
 
     let result_ptr = result_ptr_value.deref(ctx.memory(0)).unwrap();
@@ -444,6 +501,9 @@ pub fn ___syscall145(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> i32
     }
 
     debug!("=> fd: {}, iov: {}, iovcnt = {}", fd, iov, iovcnt);
+    if let Some(ret) = vfs::readv(ctx, fd, iov as u32, iovcnt as u32) {
+        return ret;
+    }
     let mut ret = 0;
     unsafe {
         for i in 0..iovcnt {
@@ -451,13 +511,19 @@ pub fn ___syscall145(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> i32
                 emscripten_memory_pointer!(ctx.memory(0), (iov + i * 8)) as *mut GuestIovec;
             let iov_base = emscripten_memory_pointer!(ctx.memory(0), (*guest_iov_addr).iov_base)
                 as *mut c_void;
-            let iov_len = (*guest_iov_addr).iov_len as _;
+            let iov_len = (*guest_iov_addr).iov_len as usize;
             // debug!("=> iov_addr: {:?}, {:?}", iov_base, iov_len);
-            let curr = read(fd, iov_base, iov_len);
+            let curr = read_retrying(fd, iov_base, iov_len);
             if curr < 0 {
-                return -1;
+                // like readv, what was read before the error is returned
+                return if ret > 0 { ret as _ } else { host_error() };
             }
             ret += curr;
+            // a short read means there's no more input for now, so reading the next buffers
+            // would wait for more instead of returning the input to the program
+            if (curr as usize) < iov_len {
+                break;
+            }
         }
         // debug!(" => ret: {}", ret);
         ret as _
@@ -480,6 +546,9 @@ pub fn ___syscall146(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
     }
 
     debug!("=> fd: {}, iov: {}, iovcnt = {}", fd, iov, iovcnt);
+    if let Some(ret) = vfs::writev(ctx, fd, iov as u32, iovcnt as u32) {
+        return ret;
+    }
     let mut ret = 0;
     for i in 0..iovcnt {
         unsafe {
@@ -534,6 +603,9 @@ pub fn ___syscall195(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
     debug!("emscripten::___syscall195 (stat64) {}", _which);
     let pathname_addr = varargs.get_str(ctx);
     let buf: u32 = varargs.get(ctx);
+    if let Some(ret) = vfs::stat(ctx, pathname_addr, buf, true) {
+        return ret;
+    }
 
     let real_path_owned = get_cstr_path(ctx, pathname_addr as *const _);
     let real_path = if let Some(ref rp) = real_path_owned {
@@ -566,6 +638,9 @@ pub fn ___syscall197(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
 
     let fd: c_int = varargs.get(ctx);
     let buf: u32 = varargs.get(ctx);
+    if let Some(ret) = vfs::fstat(ctx, fd, buf) {
+        return ret;
+    }
 
     unsafe {
         let mut stat = std::mem::zeroed();
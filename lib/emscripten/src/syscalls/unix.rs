@@ -31,8 +31,6 @@ use libc::{
     getsockopt,
     getuid,
     gid_t,
-    in_addr_t,
-    in_port_t,
     ioctl,
     lchown,
     link,
@@ -72,26 +70,28 @@ use libc::{
     // sockaddr_in,
     FIOCLEX,
     FIONBIO,
+    FIONREAD,
     F_GETFD,
     F_SETFD,
     SOL_SOCKET,
+    TIOCGPGRP,
     TIOCGWINSZ,
     TIOCSPGRP,
     // TCGETS,
     // TCSETSW,
 };
 
-// They are not exposed in in Rust libc in macOS
-const TCGETS: u64 = 0x5401;
-const TCSETSW: u64 = 0x5403;
-
 // `libc` constants as provided by `emscripten`. Maybe move to own file?
 const WASM_FIONBIO: u32 = 0x5421;
 const WASM_FIOCLEX: u32 = 0x5451;
+const WASM_FIONREAD: u32 = 0x541B;
+const WASM_TIOCGPGRP: u32 = 0x540F;
 const WASM_TIOCSPGRP: u32 = 0x5410;
 const WASM_TIOCGWINSZ: u32 = 0x5413;
 const WASM_TCGETS: u32 = 0x5401;
+const WASM_TCSETS: u32 = 0x5402;
 const WASM_TCSETSW: u32 = 0x5403;
+const WASM_TCSETSF: u32 = 0x5404;
 
 // Based on @syrusakbary sugerence at
 // https://github.com/wasmerio/wasmer/pull/532#discussion_r300837800
@@ -99,10 +99,10 @@ fn translate_ioctl(wasm_ioctl: u32) -> c_ulong {
     match wasm_ioctl {
         WASM_FIOCLEX => FIOCLEX,
         WASM_TIOCGWINSZ => TIOCGWINSZ,
+        WASM_TIOCGPGRP => TIOCGPGRP,
         WASM_TIOCSPGRP => TIOCSPGRP,
         WASM_FIONBIO => FIONBIO,
-        WASM_TCGETS => TCGETS,
-        WASM_TCSETSW => TCSETSW,
+        WASM_FIONREAD => FIONREAD,
         _otherwise => {
             unimplemented!("The ioctl {} is not yet implemented", wasm_ioctl);
         }
@@ -113,11 +113,15 @@ fn translate_ioctl(wasm_ioctl: u32) -> c_ulong {
 use std::ffi::CStr;
 use wasmer_runtime_core::vm::Ctx;
 
-use crate::env::EmSockAddr;
+use crate::env::{get_emscripten_data, EmSockAddr};
+use crate::net::{read_guest_socket_addr, EACCES};
+use crate::tty::{self, SetTermios};
 use crate::utils::{self, get_cstr_path};
+use crate::vfs;
 #[allow(unused_imports)]
 use std::io::Error;
 use std::mem;
+use std::net::SocketAddr;
 
 // Linking to functions that are not provided by rust libc
 #[cfg(target_os = "macos")]
@@ -146,6 +150,9 @@ pub fn ___syscall5(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
     let pathname_addr = varargs.get_str(ctx);
     let flags: i32 = varargs.get(ctx);
     let mode: u32 = varargs.get(ctx);
+    if let Some(fd) = vfs::open(ctx, pathname_addr, flags) {
+        return fd;
+    }
     let real_path_owned = utils::get_cstr_path(ctx, pathname_addr as *const _);
     let real_path = if let Some(ref rp) = real_path_owned {
         rp.as_c_str().as_ptr()
@@ -364,6 +371,9 @@ pub fn ___syscall34(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
 pub fn ___syscall39(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall39 (mkdir) {}", _which);
     let pathname_addr = varargs.get_str(ctx);
+    if let Some(ret) = vfs::mkdir(ctx, pathname_addr) {
+        return ret;
+    }
     let real_path_owned = utils::get_cstr_path(ctx, pathname_addr as *const _);
     let real_path = if let Some(ref rp) = real_path_owned {
         rp.as_c_str().as_ptr()
@@ -459,8 +469,21 @@ pub fn ___syscall54(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
 
     // Got the equivalents here: https://code.woboq.org/linux/linux/include/uapi/asm-generic/ioctls.h.html
     match request {
-        WASM_FIOCLEX | WASM_FIONBIO | WASM_TIOCGWINSZ | WASM_TIOCSPGRP | WASM_TCGETS
-        | WASM_TCSETSW => {
+        WASM_TCGETS => {
+            let argp: u32 = varargs.get(ctx);
+            tty::get_termios(ctx, fd, argp)
+        }
+        WASM_TCSETS | WASM_TCSETSW | WASM_TCSETSF => {
+            let argp: u32 = varargs.get(ctx);
+            let action = match request {
+                WASM_TCSETS => SetTermios::Now,
+                WASM_TCSETSW => SetTermios::Drain,
+                _ => SetTermios::Flush,
+            };
+            tty::set_termios(ctx, fd, action, argp)
+        }
+        WASM_FIOCLEX | WASM_FIONBIO | WASM_FIONREAD | WASM_TIOCGWINSZ | WASM_TIOCGPGRP
+        | WASM_TIOCSPGRP => {
             let argp: u32 = varargs.get(ctx);
             let argp_ptr = emscripten_memory_pointer!(ctx.memory(0), argp) as *mut c_void;
             let translated_request = translate_ioctl(request);
@@ -475,6 +498,9 @@ pub fn ___syscall54(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
             if request == WASM_TIOCGWINSZ && ret == -1 {
                 return 0;
             }
+            if ret == -1 {
+                return -tty::last_guest_errno();
+            }
             ret
         }
         _ => {
@@ -497,21 +523,6 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
     let call: u32 = varargs.get(ctx);
     let mut socket_varargs: VarArgs = varargs.get(ctx);
 
-    // migrating to EmSockAddr, port being separate here is nice, should update that too
-    #[repr(C)]
-    pub struct GuestSockaddrIn {
-        pub sin_family: sa_family_t, // u16
-        pub sin_port: in_port_t,     // u16
-        pub sin_addr: GuestInAddr,   // u32
-        pub sin_zero: [u8; 8],       // u8 * 8
-                                     // 2 + 2 + 4 + 8 = 16
-    }
-
-    #[repr(C)]
-    pub struct GuestInAddr {
-        pub s_addr: in_addr_t, // u32
-    }
-
     match call {
         1 => {
             debug!("socket: socket");
@@ -520,7 +531,14 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
             let ty_and_flags: i32 = socket_varargs.get(ctx);
             let protocol: i32 = socket_varargs.get(ctx);
             let ty = ty_and_flags & (!SOCK_NON_BLOCK) & (!SOCK_CLOEXC);
+            if get_emscripten_data(ctx).network.is_empty() {
+                debug!("=> no network access was granted");
+                return -EACCES;
+            }
             let fd = unsafe { socket(domain, ty, protocol) };
+            if fd < 0 {
+                return fd;
+            }
 
             if ty_and_flags & SOCK_CLOEXC != 0 {
                 // set_cloexec
@@ -530,8 +548,10 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
             }
 
             if ty_and_flags & SOCK_NON_BLOCK != 0 {
-                // do something here
-                unimplemented!("non blocking sockets");
+                unsafe {
+                    let flags = fcntl(fd, libc::F_GETFL);
+                    fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+                };
             }
 
             // why is this here?
@@ -559,24 +579,28 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
             // TODO: Emscripten has a different signature.
             let socket = socket_varargs.get(ctx);
             let address: u32 = socket_varargs.get(ctx);
-            let address_len = socket_varargs.get(ctx);
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-
-            // Debug received address
-            let _proper_address = address as *const GuestSockaddrIn;
-            debug!(
-                    "=> address.sin_family: {:?}, address.sin_port: {:?}, address.sin_addr.s_addr: {:?}",
-                unsafe { (*_proper_address).sin_family }, unsafe { (*_proper_address).sin_port }, unsafe { (*_proper_address).sin_addr.s_addr }
-                );
+            let address_len: u32 = socket_varargs.get(ctx);
+            let address = match read_guest_socket_addr(ctx, address, address_len) {
+                Some(address) if get_emscripten_data(ctx).network.can_listen(&address) => address,
+                address => {
+                    debug!("=> binding to {:?} was not granted", address);
+                    return -EACCES;
+                }
+            };
+            let (host_address, host_address_len) = host_sockaddr(&address);
 
-            let status = unsafe { bind(socket, address, address_len) };
-            // debug!("=> status: {}", status);
+            let status = unsafe {
+                bind(
+                    socket,
+                    &host_address as *const _ as *const sockaddr,
+                    host_address_len,
+                )
+            };
             debug!(
-                "=> socketfd: {}, address: {:?}, address_len: {} = status: {}",
-                socket, address, address_len, status
+                "=> socketfd: {}, address: {} = status: {}",
+                socket, address, status
             );
             status
-            // -1
         }
         3 => {
             debug!("socket: connect");
@@ -584,9 +608,28 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
             // TODO: Emscripten has a different signature.
             let socket = socket_varargs.get(ctx);
             let address: u32 = socket_varargs.get(ctx);
-            let address_len = socket_varargs.get(ctx);
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            unsafe { connect(socket, address, address_len) }
+            let address_len: u32 = socket_varargs.get(ctx);
+            let address = match read_guest_socket_addr(ctx, address, address_len) {
+                Some(address) if get_emscripten_data(ctx).network.can_connect(&address) => address,
+                address => {
+                    debug!("=> connecting to {:?} was not granted", address);
+                    return -EACCES;
+                }
+            };
+            let (host_address, host_address_len) = host_sockaddr(&address);
+
+            let status = unsafe {
+                connect(
+                    socket,
+                    &host_address as *const _ as *const sockaddr,
+                    host_address_len,
+                )
+            };
+            debug!(
+                "=> socketfd: {}, address: {} = status: {}",
+                socket, address, status
+            );
+            status
         }
         4 => {
             debug!("socket: listen");
@@ -687,18 +730,60 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
                 emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t;
             unsafe { getpeername(socket, address, address_len_addr) }
         }
+        9 => {
+            debug!("socket: send");
+            // send (socket: c_int, buf: *const c_void, len: size_t, flags: c_int) -> ssize_t
+            let socket: i32 = socket_varargs.get(ctx);
+            let buf: u32 = socket_varargs.get(ctx);
+            let len: u32 = socket_varargs.get(ctx);
+            let flags: i32 = socket_varargs.get(ctx);
+            let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
+            unsafe { libc::send(socket, buf_addr, len as usize, flags) as i32 }
+        }
+        10 => {
+            debug!("socket: recv");
+            // recv (socket: c_int, buf: *mut c_void, len: size_t, flags: c_int) -> ssize_t
+            let socket: i32 = socket_varargs.get(ctx);
+            let buf: u32 = socket_varargs.get(ctx);
+            let len: u32 = socket_varargs.get(ctx);
+            let flags: i32 = socket_varargs.get(ctx);
+            let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
+            unsafe { libc::recv(socket, buf_addr, len as usize, flags) as i32 }
+        }
         11 => {
             debug!("socket: sendto");
             // sendto (socket: c_int, buf: *const c_void, len: size_t, flags: c_int, addr: *const sockaddr, addrlen: socklen_t) -> ssize_t
+            // `send` of emscripten's libc is a `sendto` without address
             let socket = socket_varargs.get(ctx);
             let buf: u32 = socket_varargs.get(ctx);
-            let flags = socket_varargs.get(ctx);
-            let len: i32 = socket_varargs.get(ctx);
+            let len: u32 = socket_varargs.get(ctx);
+            let flags: i32 = socket_varargs.get(ctx);
             let address: u32 = socket_varargs.get(ctx);
-            let address_len = socket_varargs.get(ctx);
+            let address_len: u32 = socket_varargs.get(ctx);
             let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            unsafe { sendto(socket, buf_addr, flags, len, address, address_len) as i32 }
+            if address == 0 {
+                return unsafe {
+                    sendto(socket, buf_addr, len as usize, flags, std::ptr::null(), 0) as i32
+                };
+            }
+            let address = match read_guest_socket_addr(ctx, address, address_len) {
+                Some(address) if get_emscripten_data(ctx).network.can_connect(&address) => address,
+                address => {
+                    debug!("=> sending to {:?} was not granted", address);
+                    return -EACCES;
+                }
+            };
+            let (host_address, host_address_len) = host_sockaddr(&address);
+            unsafe {
+                sendto(
+                    socket,
+                    buf_addr,
+                    len as usize,
+                    flags,
+                    &host_address as *const _ as *const sockaddr,
+                    host_address_len,
+                ) as i32
+            }
         }
         12 => {
             debug!("socket: recvfrom");
@@ -710,9 +795,15 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
             let address: u32 = socket_varargs.get(ctx);
             let address_len: u32 = socket_varargs.get(ctx);
             let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            let address_len_addr =
-                emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t;
+            // `recv` of emscripten's libc is a `recvfrom` without address
+            let (address, address_len_addr) = if address == 0 {
+                (std::ptr::null_mut(), std::ptr::null_mut())
+            } else {
+                (
+                    emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr,
+                    emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t,
+                )
+            };
             unsafe {
                 recvfrom(
                     socket,
@@ -724,6 +815,13 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
                 ) as i32
             }
         }
+        13 => {
+            debug!("socket: shutdown");
+            // shutdown (socket: c_int, how: c_int) -> c_int
+            let socket: i32 = socket_varargs.get(ctx);
+            let how: i32 = socket_varargs.get(ctx);
+            unsafe { libc::shutdown(socket, how) }
+        }
         14 => {
             debug!("socket: setsockopt");
             // OSX and BSD have completely different values, be very careful here
@@ -785,6 +883,42 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
     }
 }
 
+/// Lays out `addr` as a `sockaddr` of the host, the `sockaddr` of the guest is Linux's
+fn host_sockaddr(addr: &SocketAddr) -> (libc::sockaddr_storage, socklen_t) {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let host_addr = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+            host_addr.sin_family = libc::AF_INET as sa_family_t;
+            host_addr.sin_port = addr.port().to_be();
+            host_addr.sin_addr = libc::in_addr {
+                s_addr: u32::from_ne_bytes(addr.ip().octets()),
+            };
+            #[cfg(target_os = "macos")]
+            {
+                host_addr.sin_len = mem::size_of::<libc::sockaddr_in>() as u8;
+            }
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let host_addr = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+            host_addr.sin6_family = libc::AF_INET6 as sa_family_t;
+            host_addr.sin6_port = addr.port().to_be();
+            host_addr.sin6_flowinfo = addr.flowinfo().to_be();
+            host_addr.sin6_addr = libc::in6_addr {
+                s6_addr: addr.ip().octets(),
+            };
+            host_addr.sin6_scope_id = addr.scope_id();
+            #[cfg(target_os = "macos")]
+            {
+                host_addr.sin6_len = mem::size_of::<libc::sockaddr_in6>() as u8;
+            }
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, len as socklen_t)
+}
+
 /// OSX and BSD have completely different values, we must translate from emscripten's Linuxy
 /// value into one that we can pass to native syscalls
 fn translate_socket_name_flag(name: i32) -> i32 {
@@ -934,18 +1068,82 @@ pub fn ___syscall142(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
     let readfds: u32 = varargs.get(ctx);
     let writefds: u32 = varargs.get(ctx);
     let exceptfds: u32 = varargs.get(ctx);
-    let _timeout: i32 = varargs.get(ctx);
+    let timeout: u32 = varargs.get(ctx);
 
-    if nfds > 1024 {
+    if nfds < 0 || nfds > 1024 {
         // EINVAL
         return -22;
     }
-    assert!(exceptfds == 0, "`exceptfds` is not supporrted");
 
-    let readfds_ptr = emscripten_memory_pointer!(ctx.memory(0), readfds) as _;
-    let writefds_ptr = emscripten_memory_pointer!(ctx.memory(0), writefds) as _;
+    let mut host_readfds = read_guest_fd_set(ctx, readfds, nfds);
+    let mut host_writefds = read_guest_fd_set(ctx, writefds, nfds);
+    let mut host_exceptfds = read_guest_fd_set(ctx, exceptfds, nfds);
+    // the `struct timeval` of emscripten has 32 bits fields
+    let mut host_timeout = if timeout == 0 {
+        None
+    } else {
+        let view = ctx.memory(0).view::<i32>();
+        Some(libc::timeval {
+            tv_sec: view[(timeout / 4) as usize].get() as _,
+            tv_usec: view[(timeout / 4 + 1) as usize].get() as _,
+        })
+    };
+
+    fn as_mut_ptr<T>(value: &mut Option<T>) -> *mut T {
+        value
+            .as_mut()
+            .map_or(std::ptr::null_mut(), |value| value as *mut T)
+    }
+    let ret = unsafe {
+        select(
+            nfds,
+            as_mut_ptr(&mut host_readfds),
+            as_mut_ptr(&mut host_writefds),
+            as_mut_ptr(&mut host_exceptfds),
+            as_mut_ptr(&mut host_timeout),
+        )
+    };
+    debug!("=> nfds: {} = {}", nfds, ret);
+
+    write_guest_fd_set(ctx, readfds, nfds, &host_readfds);
+    write_guest_fd_set(ctx, writefds, nfds, &host_writefds);
+    write_guest_fd_set(ctx, exceptfds, nfds, &host_exceptfds);
+    ret
+}
+
+/// Copies the `fd_set` of the guest at `addr` to one of the host, whose layout differs.
+fn read_guest_fd_set(ctx: &Ctx, addr: u32, nfds: i32) -> Option<libc::fd_set> {
+    if addr == 0 {
+        return None;
+    }
+    let view = ctx.memory(0).view::<u32>();
+    let mut fd_set: libc::fd_set = unsafe { mem::zeroed() };
+    unsafe { libc::FD_ZERO(&mut fd_set) };
+    for fd in 0..nfds {
+        let word = view[(addr / 4) as usize + (fd / 32) as usize].get();
+        if word & (1 << (fd % 32)) != 0 {
+            unsafe { libc::FD_SET(fd, &mut fd_set) };
+        }
+    }
+    Some(fd_set)
+}
 
-    unsafe { select(nfds, readfds_ptr, writefds_ptr, 0 as _, 0 as _) }
+/// Copies the `fd_set` of the host back to the guest at `addr`.
+fn write_guest_fd_set(ctx: &Ctx, addr: u32, nfds: i32, fd_set: &Option<libc::fd_set>) {
+    let fd_set = match fd_set {
+        Some(fd_set) => fd_set,
+        None => return,
+    };
+    let view = ctx.memory(0).view::<u32>();
+    for fd in 0..nfds {
+        let cell = &view[(addr / 4) as usize + (fd / 32) as usize];
+        let mask = 1 << (fd % 32);
+        if unsafe { libc::FD_ISSET(fd, fd_set) } {
+            cell.set(cell.get() | mask);
+        } else {
+            cell.set(cell.get() & !mask);
+        }
+    }
 }
 
 /// fdatasync
@@ -993,6 +1191,9 @@ pub fn ___syscall196(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
         path
     };
     let buf_ptr: u32 = varargs.get(ctx);
+    if let Some(ret) = vfs::stat(ctx, path, buf_ptr, false) {
+        return ret;
+    }
     unsafe {
         let mut stat: stat = std::mem::zeroed();
 
@@ -1027,8 +1228,6 @@ pub fn ___syscall199(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
 // dirent structure is
 // i64, i64, u16 (280), i8, [i8; 256]
 pub fn ___syscall220(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
-    use super::super::env::get_emscripten_data;
-
     let fd: i32 = varargs.get(ctx);
     let dirp_addr: i32 = varargs.get(ctx);
     let count: u32 = varargs.get(ctx);
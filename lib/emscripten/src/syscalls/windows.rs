@@ -1,5 +1,6 @@
 use crate::utils::{copy_cstr_into_wasm, get_cstr_path};
 use crate::varargs::VarArgs;
+use crate::vfs;
 use libc::mkdir;
 use libc::open;
 use std::env;
@@ -26,6 +27,9 @@ pub fn ___syscall5(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     };
     let flags: i32 = varargs.get(ctx);
     let mode: u32 = varargs.get(ctx);
+    if let Some(fd) = vfs::open(ctx, pathname_addr, flags) {
+        return fd;
+    }
     let path_str = unsafe { std::ffi::CStr::from_ptr(real_path).to_str().unwrap() };
     match path_str {
         "/dev/urandom" => {
@@ -101,6 +105,9 @@ pub fn ___syscall39(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     #[cfg(not(feature = "debug"))]
     let _ = which;
     let pathname_addr = varargs.get_str(ctx);
+    if let Some(ret) = vfs::mkdir(ctx, pathname_addr) {
+        return ret;
+    }
     let real_path_owned = get_cstr_path(ctx, pathname_addr);
     let real_path = if let Some(ref rp) = real_path_owned {
         rp.as_c_str().as_ptr()
@@ -0,0 +1,221 @@
+//! The terminal of emscripten programs.
+//!
+//! A program reads the stdin of wasmer, so an interactive program like a REPL or a shell gets
+//! its input as it's typed: its reads block until there's some, and aren't failed by the
+//! signals interrupting them.  Its `termios` ioctls run with `tcgetattr` and `tcsetattr`,
+//! translating the flags between the Linux layout of emscripten's libc and the one of the host,
+//! so that it can turn off the echo or the canonical mode, like line editors do.
+
+use libc::{
+    tcflag_t, tcgetattr, tcsetattr, termios, BRKINT, CLOCAL, CREAD, CS5, CS6, CS7, CS8, CSIZE,
+    CSTOPB, EAGAIN, EBADF, ECHO, ECHOE, ECHOK, ECHONL, EFAULT, EINTR, EINVAL, ENOTTY, HUPCL,
+    ICANON, ICRNL, IEXTEN, IGNBRK, IGNCR, IGNPAR, INLCR, INPCK, ISIG, ISTRIP, IXANY, IXOFF, IXON,
+    NOFLSH, ONLCR, OPOST, PARENB, PARMRK, PARODD, TCSADRAIN, TCSAFLUSH, TCSANOW, TOSTOP, VEOF,
+    VEOL, VERASE, VINTR, VKILL, VMIN, VQUIT, VSTART, VSTOP, VSUSP, VTIME,
+};
+use std::{io::Error, mem};
+use wasmer_runtime_core::vm::Ctx;
+
+// The `struct termios` of emscripten's libc
+const TERMIOS_IFLAG: usize = 0;
+const TERMIOS_OFLAG: usize = 4;
+const TERMIOS_CFLAG: usize = 8;
+const TERMIOS_LFLAG: usize = 12;
+const TERMIOS_CC: usize = 17;
+const TERMIOS_ISPEED: usize = 52;
+const TERMIOS_OSPEED: usize = 56;
+const TERMIOS_SIZE: usize = 60;
+
+/// The speed the terminal is said to have, `B38400`
+const GUEST_B38400: u32 = 0o17;
+
+/// The guest `c_iflag` bits, and the host ones
+const INPUT_FLAGS: &[(u32, tcflag_t)] = &[
+    (0o1, IGNBRK),
+    (0o2, BRKINT),
+    (0o4, IGNPAR),
+    (0o10, PARMRK),
+    (0o20, INPCK),
+    (0o40, ISTRIP),
+    (0o100, INLCR),
+    (0o200, IGNCR),
+    (0o400, ICRNL),
+    (0o2000, IXON),
+    (0o4000, IXANY),
+    (0o10000, IXOFF),
+];
+
+/// The guest `c_oflag` bits, and the host ones
+const OUTPUT_FLAGS: &[(u32, tcflag_t)] = &[(0o1, OPOST), (0o4, ONLCR)];
+
+/// The guest `c_cflag` bits, and the host ones
+const CONTROL_FLAGS: &[(u32, tcflag_t)] = &[
+    (0o100, CSTOPB),
+    (0o200, CREAD),
+    (0o400, PARENB),
+    (0o1000, PARODD),
+    (0o2000, HUPCL),
+    (0o4000, CLOCAL),
+];
+
+/// The guest `CSIZE` values, and the host ones
+const CHARACTER_SIZES: &[(u32, tcflag_t)] = &[(0, CS5), (0o20, CS6), (0o40, CS7), (0o60, CS8)];
+const GUEST_CSIZE: u32 = 0o60;
+
+/// The guest `c_lflag` bits, and the host ones
+const LOCAL_FLAGS: &[(u32, tcflag_t)] = &[
+    (0o1, ISIG),
+    (0o2, ICANON),
+    (0o10, ECHO),
+    (0o20, ECHOE),
+    (0o40, ECHOK),
+    (0o100, ECHONL),
+    (0o200, NOFLSH),
+    (0o400, TOSTOP),
+    (0o100000, IEXTEN),
+];
+
+/// The guest `c_cc` indices, and the host ones
+const CONTROL_CHARACTERS: &[(usize, usize)] = &[
+    (0, VINTR),
+    (1, VQUIT),
+    (2, VERASE),
+    (3, VKILL),
+    (4, VEOF),
+    (5, VTIME),
+    (6, VMIN),
+    (8, VSTART),
+    (9, VSTOP),
+    (10, VSUSP),
+    (11, VEOL),
+];
+
+/// `TCSETS`, `TCSETSW` and `TCSETSF`, which `set_termios` runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SetTermios {
+    Now,
+    Drain,
+    Flush,
+}
+
+/// Returns the last host error as an errno of emscripten's libc.
+pub(crate) fn last_guest_errno() -> i32 {
+    match Error::last_os_error().raw_os_error() {
+        Some(EINTR) => 4,
+        Some(EBADF) => 9,
+        Some(EAGAIN) => 11,
+        Some(EFAULT) => 14,
+        Some(EINVAL) => 22,
+        Some(ENOTTY) => 25,
+        _ => 5,
+    }
+}
+
+fn guest_flags(host: tcflag_t, table: &[(u32, tcflag_t)]) -> u32 {
+    table
+        .iter()
+        .filter(|(_, host_flag)| host & host_flag == *host_flag)
+        .fold(0, |flags, (guest_flag, _)| flags | guest_flag)
+}
+
+/// Returns the host flags `host`, with the ones of the table set as in `guest`.
+fn host_flags(guest: u32, host: tcflag_t, table: &[(u32, tcflag_t)]) -> tcflag_t {
+    table.iter().fold(host, |flags, (guest_flag, host_flag)| {
+        if guest & guest_flag == *guest_flag {
+            flags | host_flag
+        } else {
+            flags & !host_flag
+        }
+    })
+}
+
+/// `TCGETS`: writes the attributes of the terminal `fd` to the `struct termios` at `termios_ptr`.
+pub(crate) fn get_termios(ctx: &Ctx, fd: i32, termios_ptr: u32) -> i32 {
+    let mut host: termios = unsafe { mem::zeroed() };
+    if unsafe { tcgetattr(fd, &mut host) } != 0 {
+        return -last_guest_errno();
+    }
+
+    let character_size = CHARACTER_SIZES
+        .iter()
+        .find(|(_, host_size)| host.c_cflag & CSIZE == *host_size)
+        .map_or(GUEST_CSIZE, |(guest_size, _)| *guest_size);
+    let mut guest = [0u8; TERMIOS_SIZE];
+    let mut put = |offset: usize, value: u32| {
+        guest[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    };
+    put(TERMIOS_IFLAG, guest_flags(host.c_iflag, INPUT_FLAGS));
+    put(TERMIOS_OFLAG, guest_flags(host.c_oflag, OUTPUT_FLAGS));
+    put(
+        TERMIOS_CFLAG,
+        guest_flags(host.c_cflag, CONTROL_FLAGS) | character_size | GUEST_B38400,
+    );
+    put(TERMIOS_LFLAG, guest_flags(host.c_lflag, LOCAL_FLAGS));
+    put(TERMIOS_ISPEED, GUEST_B38400);
+    put(TERMIOS_OSPEED, GUEST_B38400);
+    for (guest_index, host_index) in CONTROL_CHARACTERS {
+        guest[TERMIOS_CC + guest_index] = host.c_cc[*host_index];
+    }
+
+    let view = ctx.memory(0).view::<u8>();
+    let start = termios_ptr as usize;
+    match view.get(start..start + TERMIOS_SIZE) {
+        Some(cells) => {
+            for (cell, byte) in cells.iter().zip(guest.iter()) {
+                cell.set(*byte);
+            }
+            0
+        }
+        // EFAULT
+        None => -14,
+    }
+}
+
+/// `TCSETS`, `TCSETSW` and `TCSETSF`: sets the attributes of the terminal `fd` to the
+/// `struct termios` at `termios_ptr`.  The attributes which aren't translated keep their value.
+pub(crate) fn set_termios(ctx: &Ctx, fd: i32, action: SetTermios, termios_ptr: u32) -> i32 {
+    let view = ctx.memory(0).view::<u8>();
+    let start = termios_ptr as usize;
+    let guest: Vec<u8> = match view.get(start..start + TERMIOS_SIZE) {
+        Some(cells) => cells.iter().map(|cell| cell.get()).collect(),
+        // EFAULT
+        None => return -14,
+    };
+    let get = |offset: usize| {
+        u32::from_le_bytes([
+            guest[offset],
+            guest[offset + 1],
+            guest[offset + 2],
+            guest[offset + 3],
+        ])
+    };
+
+    let mut host: termios = unsafe { mem::zeroed() };
+    if unsafe { tcgetattr(fd, &mut host) } != 0 {
+        return -last_guest_errno();
+    }
+    let cflag = get(TERMIOS_CFLAG);
+    host.c_iflag = host_flags(get(TERMIOS_IFLAG), host.c_iflag, INPUT_FLAGS);
+    host.c_oflag = host_flags(get(TERMIOS_OFLAG), host.c_oflag, OUTPUT_FLAGS);
+    host.c_cflag = host_flags(cflag, host.c_cflag, CONTROL_FLAGS);
+    if let Some((_, host_size)) = CHARACTER_SIZES
+        .iter()
+        .find(|(guest_size, _)| cflag & GUEST_CSIZE == *guest_size)
+    {
+        host.c_cflag = (host.c_cflag & !CSIZE) | host_size;
+    }
+    host.c_lflag = host_flags(get(TERMIOS_LFLAG), host.c_lflag, LOCAL_FLAGS);
+    for (guest_index, host_index) in CONTROL_CHARACTERS {
+        host.c_cc[*host_index] = guest[TERMIOS_CC + guest_index];
+    }
+
+    let action = match action {
+        SetTermios::Now => TCSANOW,
+        SetTermios::Drain => TCSADRAIN,
+        SetTermios::Flush => TCSAFLUSH,
+    };
+    if unsafe { tcsetattr(fd, action, &host) } != 0 {
+        return -last_guest_errno();
+    }
+    0
+}
@@ -15,6 +15,7 @@ use wasmer_runtime_core::{
     units::Pages,
     vm::Ctx,
 };
+use wasmer_wasi::types::*;
 
 /// We check if a provided module is an Emscripten generated one
 pub fn is_emscripten_module(module: &Module) -> bool {
@@ -223,6 +224,37 @@ pub unsafe fn copy_stat_into_wasm(ctx: &mut Ctx, buf: u32, stat: &stat) {
     (*stat_ptr).st_ino = stat.st_ino as _;
 }
 
+/// Like `copy_stat_into_wasm`, for the stats of the files of a `WasiFsBackend`.
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe fn copy_filestat_into_wasm(ctx: &mut Ctx, buf: u32, stat: &__wasi_filestat_t) {
+    let mode = match stat.st_filetype {
+        __WASI_FILETYPE_DIRECTORY => 0o040_755,
+        __WASI_FILETYPE_SYMBOLIC_LINK => 0o120_777,
+        __WASI_FILETYPE_CHARACTER_DEVICE => 0o020_666,
+        __WASI_FILETYPE_BLOCK_DEVICE => 0o060_666,
+        __WASI_FILETYPE_SOCKET_DGRAM | __WASI_FILETYPE_SOCKET_STREAM => 0o140_666,
+        _ => 0o100_644,
+    };
+    let stat_ptr = emscripten_memory_pointer!(ctx.memory(0), buf) as *mut GuestStat;
+    (*stat_ptr).st_dev = stat.st_dev as _;
+    (*stat_ptr).__st_dev_padding = 0;
+    (*stat_ptr).__st_ino_truncated = stat.st_ino as _;
+    (*stat_ptr).st_mode = mode;
+    (*stat_ptr).st_nlink = stat.st_nlink as _;
+    (*stat_ptr).st_uid = 0;
+    (*stat_ptr).st_gid = 0;
+    (*stat_ptr).st_rdev = 0;
+    (*stat_ptr).__st_rdev_padding = 0;
+    (*stat_ptr).st_size = stat.st_size as _;
+    (*stat_ptr).st_blksize = 4096;
+    (*stat_ptr).st_blocks = ((stat.st_size + 511) / 512) as _;
+    // the timestamps of WASI are in nanoseconds
+    (*stat_ptr).st_atime = stat.st_atim / 1_000_000_000;
+    (*stat_ptr).st_mtime = stat.st_mtim / 1_000_000_000;
+    (*stat_ptr).st_ctime = stat.st_ctim / 1_000_000_000;
+    (*stat_ptr).st_ino = stat.st_ino as _;
+}
+
 #[allow(dead_code)] // it's used in `env/windows/mod.rs`.
 pub fn read_string_from_wasm(memory: &Memory, offset: u32) -> String {
     let v: Vec<u8> = memory.view()[(offset as usize)..]
@@ -0,0 +1,463 @@
+//! The files of emscripten programs served by a `WasiFsBackend`, the filesystem of WASI.
+//!
+//! By default the filesystem syscalls of emscripten go straight to the host.  When the embedder
+//! sets `EmscriptenGlobals::vfs`, the paths the program opens, stats, creates and removes are
+//! looked up in the preopened and mapped directories of the `EmscriptenVfs`, and reached through
+//! its backend, exactly like the files of WASI modules: `HostFs`, `MemFs`, `OverlayFs` or a
+//! backend of the embedder serve both ABIs.  The paths outside of these directories don't exist.
+//!
+//! The fds of these files are numbered from `FIRST_VFS_FD`, so that stdio, pipes and sockets
+//! keep the fds of the host.  Each thread of the program has its own fds.
+
+use crate::env::get_emscripten_data;
+use crate::utils::copy_filestat_into_wasm;
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    io::{Read, Seek, SeekFrom, Write},
+    os::raw::c_char,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+use wasmer_runtime_core::{memory::Memory, vm::Ctx};
+use wasmer_wasi::{
+    state::{OpenOptions, WasiFile, WasiFsBackend, WasiFsError},
+    types::*,
+};
+
+// `errno` of emscripten's libc
+const ENOENT: i32 = 2;
+const EINTR: i32 = 4;
+const EIO: i32 = 5;
+const EBADF: i32 = 9;
+const EAGAIN: i32 = 11;
+const EACCES: i32 = 13;
+const EFAULT: i32 = 14;
+const EEXIST: i32 = 17;
+const ENODEV: i32 = 19;
+const ENOTDIR: i32 = 20;
+const EISDIR: i32 = 21;
+const EINVAL: i32 = 22;
+const EMFILE: i32 = 24;
+const ENOSPC: i32 = 28;
+const EROFS: i32 = 30;
+const EPIPE: i32 = 32;
+const ENAMETOOLONG: i32 = 36;
+const ENOTEMPTY: i32 = 39;
+const ELOOP: i32 = 40;
+const ENOTSUP: i32 = 95;
+const ETIMEDOUT: i32 = 110;
+const EDQUOT: i32 = 122;
+
+// `open` flags of emscripten's libc
+const O_ACCMODE: i32 = 0o3;
+const O_WRONLY: i32 = 0o1;
+const O_RDWR: i32 = 0o2;
+const O_CREAT: i32 = 0o100;
+const O_EXCL: i32 = 0o200;
+const O_TRUNC: i32 = 0o1000;
+const O_APPEND: i32 = 0o2000;
+const O_DIRECTORY: i32 = 0o200000;
+
+/// The first fd given to the files of the vfs, far above the fds of the host.
+const FIRST_VFS_FD: i32 = 1 << 30;
+
+/// The directories of a `WasiFsBackend` visible to an emscripten program.
+#[derive(Debug, Clone)]
+pub struct EmscriptenVfs {
+    backend: Arc<dyn WasiFsBackend>,
+    /// The normalized paths the program sees, and the paths in the backend they map to
+    dirs: Vec<(PathBuf, PathBuf)>,
+}
+
+impl EmscriptenVfs {
+    /// Serves the files of `backend`, none of which is visible until a directory is preopened
+    /// or mapped.
+    pub fn new(backend: Arc<dyn WasiFsBackend>) -> Self {
+        Self {
+            backend,
+            dirs: vec![],
+        }
+    }
+
+    /// Makes the directory at `path` in the backend visible to the program at the same path.
+    pub fn preopen_dir<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.map_dir(path.as_ref(), path.as_ref())
+    }
+
+    /// Makes the directory at `path` in the backend visible to the program as `alias`.
+    ///
+    /// The relative paths of the program are looked up in the directory mapped to `.`.
+    pub fn map_dir<A: AsRef<Path>, P: AsRef<Path>>(&mut self, alias: A, path: P) -> &mut Self {
+        if let Some(alias) = normalize(alias.as_ref()) {
+            self.dirs.push((alias, path.as_ref().to_path_buf()));
+        }
+
+        self
+    }
+
+    /// Returns the path in the backend of `path`, if it's in one of the directories.
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        let path = normalize(path)?;
+        let (alias, dir) = self
+            .dirs
+            .iter()
+            .filter(|(alias, _)| alias.has_root() == path.has_root() && path.starts_with(alias))
+            .max_by_key(|(alias, _)| alias.components().count())?;
+        let rest = path.strip_prefix(alias).ok()?;
+        if rest.as_os_str().is_empty() {
+            Some(dir.clone())
+        } else {
+            Some(dir.join(rest))
+        }
+    }
+}
+
+/// Removes the `.` of `path` and applies its `..`, failing if it goes above its start.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    let mut depth = 0;
+    for component in path.components() {
+        match component {
+            Component::RootDir => normalized.push("/"),
+            Component::CurDir => (),
+            Component::ParentDir if depth > 0 => {
+                normalized.pop();
+                depth -= 1;
+            }
+            Component::Normal(name) => {
+                normalized.push(name);
+                depth += 1;
+            }
+            _ => return None,
+        }
+    }
+    Some(normalized)
+}
+
+/// Returns the `errno` of emscripten matching `error`.
+fn errno(error: WasiFsError) -> i32 {
+    match error.into_wasi_err() {
+        __WASI_EACCES | __WASI_EPERM | __WASI_ENOTCAPABLE => EACCES,
+        __WASI_EAGAIN => EAGAIN,
+        __WASI_EBADF => EBADF,
+        __WASI_EDQUOT => EDQUOT,
+        __WASI_EEXIST => EEXIST,
+        __WASI_EINTR => EINTR,
+        __WASI_EINVAL => EINVAL,
+        __WASI_EISDIR => EISDIR,
+        __WASI_ELOOP => ELOOP,
+        __WASI_EMFILE => EMFILE,
+        __WASI_ENAMETOOLONG => ENAMETOOLONG,
+        __WASI_ENODEV => ENODEV,
+        __WASI_ENOENT => ENOENT,
+        __WASI_ENOSPC => ENOSPC,
+        __WASI_ENOTDIR => ENOTDIR,
+        __WASI_ENOTEMPTY => ENOTEMPTY,
+        __WASI_ENOTSUP => ENOTSUP,
+        __WASI_EPIPE => EPIPE,
+        __WASI_EROFS => EROFS,
+        __WASI_ETIMEDOUT => ETIMEDOUT,
+        _ => EIO,
+    }
+}
+
+enum VfsFd {
+    File {
+        file: Box<dyn WasiFile>,
+        path: PathBuf,
+    },
+    Dir {
+        path: PathBuf,
+    },
+}
+
+/// The fds of the files of the vfs opened by an instance.
+pub(crate) struct VfsState {
+    vfs: EmscriptenVfs,
+    fds: HashMap<i32, VfsFd>,
+    next_fd: i32,
+}
+
+impl VfsState {
+    pub(crate) fn new(vfs: EmscriptenVfs) -> Self {
+        Self {
+            vfs,
+            fds: HashMap::new(),
+            next_fd: FIRST_VFS_FD,
+        }
+    }
+
+    fn open(&mut self, path: &Path, flags: i32) -> Result<i32, i32> {
+        let path = self.vfs.resolve(path).ok_or(ENOENT)?;
+        let backend = &self.vfs.backend;
+        let entry = match backend.metadata(&path) {
+            Ok(stat) if stat.st_filetype == __WASI_FILETYPE_DIRECTORY => {
+                if flags & O_ACCMODE != 0 || flags & O_CREAT != 0 {
+                    return Err(EISDIR);
+                }
+                VfsFd::Dir { path }
+            }
+            _ if flags & O_DIRECTORY != 0 => return Err(ENOTDIR),
+            _ => {
+                let options = OpenOptions {
+                    read: flags & O_ACCMODE != O_WRONLY,
+                    write: flags & O_ACCMODE == O_WRONLY || flags & O_ACCMODE == O_RDWR,
+                    append: flags & O_APPEND != 0,
+                    truncate: flags & O_TRUNC != 0,
+                    create: flags & O_CREAT != 0,
+                    create_new: flags & O_CREAT != 0 && flags & O_EXCL != 0,
+                };
+                let file = backend.open(&path, &options).map_err(errno)?;
+                VfsFd::File { file, path }
+            }
+        };
+        let fd = self.next_fd;
+        self.next_fd += 1;
+        self.fds.insert(fd, entry);
+        Ok(fd)
+    }
+
+    fn file(&mut self, fd: i32) -> Result<&mut Box<dyn WasiFile>, i32> {
+        match self.fds.get_mut(&fd) {
+            Some(VfsFd::File { file, .. }) => Ok(file),
+            Some(VfsFd::Dir { .. }) => Err(EISDIR),
+            None => Err(EBADF),
+        }
+    }
+
+    fn fd_stat(&self, fd: i32) -> Result<__wasi_filestat_t, i32> {
+        let path = match &self.fds[&fd] {
+            VfsFd::File { path, .. } | VfsFd::Dir { path } => path,
+        };
+        self.vfs.backend.metadata(path).map_err(errno)
+    }
+}
+
+fn guest_path(path: *const c_char) -> PathBuf {
+    let path = unsafe { CStr::from_ptr(path) };
+    PathBuf::from(path.to_string_lossy().into_owned())
+}
+
+fn read_guest_bytes(memory: &Memory, addr: u32, len: u32) -> Result<Vec<u8>, i32> {
+    let view = memory.view::<u8>();
+    let start = addr as usize;
+    let end = start + len as usize;
+    if end > view.len() {
+        return Err(EFAULT);
+    }
+    Ok(view[start..end].iter().map(|cell| cell.get()).collect())
+}
+
+fn write_guest_bytes(memory: &Memory, addr: u32, bytes: &[u8]) -> Result<(), i32> {
+    let view = memory.view::<u8>();
+    let start = addr as usize;
+    if start + bytes.len() > view.len() {
+        return Err(EFAULT);
+    }
+    for (cell, byte) in view[start..start + bytes.len()].iter().zip(bytes) {
+        cell.set(*byte);
+    }
+    Ok(())
+}
+
+/// Reads the `struct iovec`s of the guest, as `(base, len)`.
+fn read_guest_iovecs(memory: &Memory, iov: u32, iovcnt: u32) -> Result<Vec<(u32, u32)>, i32> {
+    let bytes = read_guest_bytes(memory, iov, iovcnt * 8)?;
+    Ok(bytes
+        .chunks(8)
+        .map(|iovec| {
+            let base = u32::from_le_bytes([iovec[0], iovec[1], iovec[2], iovec[3]]);
+            let len = u32::from_le_bytes([iovec[4], iovec[5], iovec[6], iovec[7]]);
+            (base, len)
+        })
+        .collect())
+}
+
+fn into_syscall_result(result: Result<i32, i32>) -> i32 {
+    result.unwrap_or_else(|errno| -errno)
+}
+
+// The functions below return `None` when the vfs isn't used, for the syscalls to go to the host:
+// the path ones when there's no vfs, and the fd ones when the fd isn't a file of the vfs.
+
+/// open
+pub(crate) fn open(ctx: &mut Ctx, path: *const c_char, flags: i32) -> Option<i32> {
+    let path = guest_path(path);
+    let state = get_emscripten_data(ctx).vfs.as_mut()?;
+    let result = state.open(&path, flags);
+    debug!("=> vfs path: {:?}, flags: {} = {:?}", path, flags, result);
+    Some(into_syscall_result(result))
+}
+
+/// close
+pub(crate) fn close(ctx: &mut Ctx, fd: i32) -> Option<i32> {
+    let state = get_emscripten_data(ctx).vfs.as_mut()?;
+    state.fds.remove(&fd)?;
+    Some(0)
+}
+
+/// read
+pub(crate) fn read(ctx: &mut Ctx, fd: i32, buf: u32, count: u32) -> Option<i32> {
+    readv_slices(ctx, fd, vec![(buf, count)])
+}
+
+/// readv
+pub(crate) fn readv(ctx: &mut Ctx, fd: i32, iov: u32, iovcnt: u32) -> Option<i32> {
+    let iovecs = match read_guest_iovecs(ctx.memory(0), iov, iovcnt) {
+        Ok(iovecs) => iovecs,
+        Err(errno) if is_vfs_fd(ctx, fd) => return Some(-errno),
+        Err(_) => return None,
+    };
+    readv_slices(ctx, fd, iovecs)
+}
+
+fn readv_slices(ctx: &mut Ctx, fd: i32, iovecs: Vec<(u32, u32)>) -> Option<i32> {
+    let memory = ctx.memory(0).clone();
+    let state = get_emscripten_data(ctx).vfs.as_mut()?;
+    if !state.fds.contains_key(&fd) {
+        return None;
+    }
+    let result = state.file(fd).and_then(|file| {
+        let mut total = 0;
+        for (base, len) in iovecs {
+            let mut bytes = vec![0; len as usize];
+            let read = file.read(&mut bytes).map_err(|e| errno(e.into()))?;
+            write_guest_bytes(&memory, base, &bytes[..read])?;
+            total += read;
+            if read < bytes.len() {
+                break;
+            }
+        }
+        Ok(total as i32)
+    });
+    Some(into_syscall_result(result))
+}
+
+/// write
+pub(crate) fn write(ctx: &mut Ctx, fd: i32, buf: u32, count: u32) -> Option<i32> {
+    writev_slices(ctx, fd, vec![(buf, count)])
+}
+
+/// writev
+pub(crate) fn writev(ctx: &mut Ctx, fd: i32, iov: u32, iovcnt: u32) -> Option<i32> {
+    let iovecs = match read_guest_iovecs(ctx.memory(0), iov, iovcnt) {
+        Ok(iovecs) => iovecs,
+        Err(errno) if is_vfs_fd(ctx, fd) => return Some(-errno),
+        Err(_) => return None,
+    };
+    writev_slices(ctx, fd, iovecs)
+}
+
+fn writev_slices(ctx: &mut Ctx, fd: i32, iovecs: Vec<(u32, u32)>) -> Option<i32> {
+    let memory = ctx.memory(0).clone();
+    let state = get_emscripten_data(ctx).vfs.as_mut()?;
+    if !state.fds.contains_key(&fd) {
+        return None;
+    }
+    let result = state.file(fd).and_then(|file| {
+        let mut total = 0;
+        for (base, len) in iovecs {
+            let bytes = read_guest_bytes(&memory, base, len)?;
+            file.write_all(&bytes).map_err(|e| errno(e.into()))?;
+            total += bytes.len();
+        }
+        Ok(total as i32)
+    });
+    Some(into_syscall_result(result))
+}
+
+/// lseek, returns the new offset
+pub(crate) fn lseek(ctx: &mut Ctx, fd: i32, offset: i64, whence: i32) -> Option<Result<i64, i32>> {
+    let state = get_emscripten_data(ctx).vfs.as_mut()?;
+    if !state.fds.contains_key(&fd) {
+        return None;
+    }
+    let position = match whence {
+        0 if offset >= 0 => SeekFrom::Start(offset as u64),
+        1 => SeekFrom::Current(offset),
+        2 => SeekFrom::End(offset),
+        _ => return Some(Err(EINVAL)),
+    };
+    Some(state.file(fd).and_then(|file| {
+        file.seek(position)
+            .map(|offset| offset as i64)
+            .map_err(|e| errno(e.into()))
+    }))
+}
+
+/// fstat64
+pub(crate) fn fstat(ctx: &mut Ctx, fd: i32, buf: u32) -> Option<i32> {
+    let state = get_emscripten_data(ctx).vfs.as_ref()?;
+    if !state.fds.contains_key(&fd) {
+        return None;
+    }
+    let result = state.fd_stat(fd);
+    Some(into_syscall_result(result.map(|stat| {
+        unsafe { copy_filestat_into_wasm(ctx, buf, &stat) };
+        0
+    })))
+}
+
+/// stat64 and lstat64
+pub(crate) fn stat(
+    ctx: &mut Ctx,
+    path: *const c_char,
+    buf: u32,
+    follow_symlinks: bool,
+) -> Option<i32> {
+    let path = guest_path(path);
+    let vfs = &get_emscripten_data(ctx).vfs.as_ref()?.vfs;
+    let result = vfs.resolve(&path).ok_or(ENOENT).and_then(|path| {
+        if follow_symlinks {
+            vfs.backend.metadata(&path).map_err(errno)
+        } else {
+            vfs.backend.symlink_metadata(&path).map_err(errno)
+        }
+    });
+    debug!("=> vfs path: {:?} = {:?}", path, result);
+    Some(into_syscall_result(result.map(|stat| {
+        unsafe { copy_filestat_into_wasm(ctx, buf, &stat) };
+        0
+    })))
+}
+
+/// mkdir
+pub(crate) fn mkdir(ctx: &mut Ctx, path: *const c_char) -> Option<i32> {
+    let path = guest_path(path);
+    let vfs = &get_emscripten_data(ctx).vfs.as_ref()?.vfs;
+    let result = vfs
+        .resolve(&path)
+        .ok_or(ENOENT)
+        .and_then(|path| vfs.backend.create_dir(&path).map_err(errno));
+    Some(into_syscall_result(result.map(|()| 0)))
+}
+
+/// rmdir
+pub(crate) fn rmdir(ctx: &mut Ctx, path: *const c_char) -> Option<i32> {
+    let path = guest_path(path);
+    let vfs = &get_emscripten_data(ctx).vfs.as_ref()?.vfs;
+    let result = vfs
+        .resolve(&path)
+        .ok_or(ENOENT)
+        .and_then(|path| vfs.backend.remove_dir(&path).map_err(errno));
+    Some(into_syscall_result(result.map(|()| 0)))
+}
+
+/// rename
+pub(crate) fn rename(ctx: &mut Ctx, from: *const c_char, to: *const c_char) -> Option<i32> {
+    let (from, to) = (guest_path(from), guest_path(to));
+    let vfs = &get_emscripten_data(ctx).vfs.as_ref()?.vfs;
+    let result = match (vfs.resolve(&from), vfs.resolve(&to)) {
+        (Some(from), Some(to)) => vfs.backend.rename(&from, &to).map_err(errno),
+        _ => Err(ENOENT),
+    };
+    Some(into_syscall_result(result.map(|()| 0)))
+}
+
+fn is_vfs_fd(ctx: &mut Ctx, fd: i32) -> bool {
+    match &get_emscripten_data(ctx).vfs {
+        Some(state) => state.fds.contains_key(&fd),
+        None => false,
+    }
+}
@@ -3,15 +3,19 @@
 #include <memory>
 #include <setjmp.h>
 
+#ifdef _WIN32
+#include <windows.h>
+#else
 extern "C" void __register_frame(uint8_t *);
 extern "C" void __deregister_frame(uint8_t *);
+#endif
 
 MemoryManager::~MemoryManager() {
   deregisterEHFrames();
-  // Deallocate all of the allocated memory.
-  callbacks.dealloc_memory(code_section.base, code_section.size);
-  callbacks.dealloc_memory(read_section.base, read_section.size);
-  callbacks.dealloc_memory(readwrite_section.base, readwrite_section.size);
+  // The sections share one allocation, starting with the code section.
+  callbacks.dealloc_memory(code_section.base, code_section.size +
+                                                  read_section.size +
+                                                  readwrite_section.size);
 }
 void unwinding_setjmp(jmp_buf stack_out, void (*func)(void *), void *userdata) {
   if (setjmp(stack_out)) {
@@ -61,6 +65,10 @@ void unsafe_unwind(WasmException *exception) {
   }
 }
 
+// Used by the Windows exception handler to decide whether a fault happened
+// while wasm code was running on this thread and can be turned into a trap.
+extern "C" bool has_unwind_point() { return unwind_state != nullptr; }
+
 uint8_t *MemoryManager::allocateCodeSection(uintptr_t size, unsigned alignment,
                                             unsigned section_id,
                                             llvm::StringRef section_name) {
@@ -99,57 +107,63 @@ void MemoryManager::reserveAllocationSpace(uintptr_t code_size,
     }
     return (ptr + align - 1) & ~(align - 1);
   };
-  uint8_t *code_ptr_out = nullptr;
-  size_t code_size_out = 0;
-  auto code_result =
-      callbacks.alloc_memory(aligner(code_size, 4096), PROTECT_READ_WRITE,
-                             &code_ptr_out, &code_size_out);
-  assert(code_result == RESULT_OK);
-  code_section = Section{code_ptr_out, code_size_out};
-  code_bump_ptr = (uintptr_t)code_ptr_out;
-  code_start_ptr = (uintptr_t)code_ptr_out;
+  // The sections are allocated together, code first, so that the whole
+  // object is within 4GiB of its first section. The unwind tables of COFF
+  // objects refer to the code and to each other relative to it.
+  size_t code_alloc_size = aligner(code_size, 4096);
+  size_t read_alloc_size = aligner(read_data_size, 4096);
+  size_t readwrite_alloc_size = aligner(read_write_data_size, 4096);
+  uint8_t *ptr_out = nullptr;
+  size_t size_out = 0;
+  auto result = callbacks.alloc_memory(
+      code_alloc_size + read_alloc_size + readwrite_alloc_size,
+      PROTECT_READ_WRITE, &ptr_out, &size_out);
+  assert(result == RESULT_OK);
+
+  code_section = Section{ptr_out, code_alloc_size};
+  code_bump_ptr = (uintptr_t)ptr_out;
+  code_start_ptr = (uintptr_t)ptr_out;
   this->code_size = code_size;
 
-  uint8_t *read_ptr_out = nullptr;
-  size_t read_size_out = 0;
-  auto read_result =
-      callbacks.alloc_memory(aligner(read_data_size, 4096), PROTECT_READ_WRITE,
-                             &read_ptr_out, &read_size_out);
-  assert(read_result == RESULT_OK);
-  read_section = Section{read_ptr_out, read_size_out};
-  read_bump_ptr = (uintptr_t)read_ptr_out;
-
-  uint8_t *readwrite_ptr_out = nullptr;
-  size_t readwrite_size_out = 0;
-  auto readwrite_result = callbacks.alloc_memory(
-      aligner(read_write_data_size, 4096), PROTECT_READ_WRITE,
-      &readwrite_ptr_out, &readwrite_size_out);
-  assert(readwrite_result == RESULT_OK);
-  readwrite_section = Section{readwrite_ptr_out, readwrite_size_out};
-  readwrite_bump_ptr = (uintptr_t)readwrite_ptr_out;
+  read_section = Section{ptr_out + code_alloc_size, read_alloc_size};
+  read_bump_ptr = (uintptr_t)read_section.base;
+
+  readwrite_section = Section{read_section.base + read_alloc_size,
+                              size_out - code_alloc_size - read_alloc_size};
+  readwrite_bump_ptr = (uintptr_t)readwrite_section.base;
 }
 
 bool MemoryManager::needsToReserveAllocationSpace() { return true; }
 
 void MemoryManager::registerEHFrames(uint8_t *addr, uint64_t LoadAddr,
                                      size_t size) {
-// We don't know yet how to do this on Windows, so we hide this on compilation
-// so we can compile and pass spectests on unix systems
-#ifndef _WIN32
   eh_frame_ptr = addr;
   eh_frame_size = size;
   eh_frames_registered = true;
+#ifdef _WIN32
+  // The section is the `.pdata` of the object, a table of `RUNTIME_FUNCTION`s
+  // whose addresses are relative to the lowest section, which is the code
+  // section. Registering it lets the unwinder walk the frames of the
+  // functions, which `longjmp` does to reach the unwind point.
+  if (!RtlAddFunctionTable((PRUNTIME_FUNCTION)addr,
+                           (DWORD)(size / sizeof(RUNTIME_FUNCTION)),
+                           (DWORD64)code_section.base)) {
+    eh_frames_registered = false;
+  }
+#else
   callbacks.visit_fde(addr, size, __register_frame);
 #endif
 }
 
 void MemoryManager::deregisterEHFrames() {
-// We don't know yet how to do this on Windows, so we hide this on compilation
-// so we can compile and pass spectests on unix systems
-#ifndef _WIN32
-  if (eh_frames_registered) {
-    callbacks.visit_fde(eh_frame_ptr, eh_frame_size, __deregister_frame);
+  if (!eh_frames_registered) {
+    return;
   }
+  eh_frames_registered = false;
+#ifdef _WIN32
+  RtlDeleteFunctionTable((PRUNTIME_FUNCTION)eh_frame_ptr);
+#else
+  callbacks.visit_fde(eh_frame_ptr, eh_frame_size, __deregister_frame);
 #endif
 }
 
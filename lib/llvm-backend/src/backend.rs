@@ -28,6 +28,7 @@ use wasmer_runtime_core::{
         CacheGen, RunnableModule,
     },
     cache::Error as CacheError,
+    codegen::{BreakpointHandler, BreakpointInfo},
     module::ModuleInfo,
     state::ModuleStateMap,
     structures::TypedIndex,
@@ -51,7 +52,6 @@ extern "C" {
     fn llvm_backend_get_code_size(module: *const LLVMModule) -> usize;
 
     fn throw_trap(ty: i32) -> !;
-    fn throw_breakpoint(ty: i64) -> !;
 
     /// This should be the same as spliting up the fat pointer into two arguments,
     /// but this is cleaner, I think?
@@ -74,6 +74,29 @@ extern "C" {
 
 static SIGNAL_HANDLER_INSTALLED: Once = Once::new();
 
+/// Runs the handler of a breakpoint hit by compiled code. The execution
+/// resumes if the handler returns `Ok`, and unwinds with its error otherwise.
+#[cfg_attr(nightly, unwind(allowed))]
+unsafe extern "C" fn call_breakpoint(ctx: *mut vm::Ctx, callback: *const BreakpointHandler) {
+    let info = BreakpointInfo {
+        fault: None,
+        ctx: ctx.as_mut(),
+    };
+    if let Err(error) = (*callback)(info) {
+        #[cfg(unix)]
+        wasmer_runtime_core::backtrace::capture_unwind();
+        throw_any(Box::leak(error))
+    }
+}
+
+/// Raises a trap of compiled code, capturing its backtrace first.
+#[cfg_attr(nightly, unwind(allowed))]
+unsafe extern "C" fn trap_with_backtrace(ty: i32) -> ! {
+    #[cfg(unix)]
+    wasmer_runtime_core::backtrace::capture_unwind();
+    throw_trap(ty)
+}
+
 fn get_callbacks() -> Callbacks {
     extern "C" fn alloc_memory(
         size: usize,
@@ -121,8 +144,12 @@ fn get_callbacks() -> Callbacks {
             fn_name!("vm.memory.grow.static.import") => vmcalls::imported_static_memory_grow as _,
             fn_name!("vm.memory.size.static.import") => vmcalls::imported_static_memory_size as _,
 
-            fn_name!("vm.exception.trap") => throw_trap as _,
-            fn_name!("vm.breakpoint") => throw_breakpoint as _,
+            fn_name!("vm.memory.atomic.wait32") => vmcalls::memory_atomic_wait32 as _,
+            fn_name!("vm.memory.atomic.wait64") => vmcalls::memory_atomic_wait64 as _,
+            fn_name!("vm.memory.atomic.notify") => vmcalls::memory_atomic_notify as _,
+
+            fn_name!("vm.exception.trap") => trap_with_backtrace as _,
+            fn_name!("vm.breakpoint") => call_breakpoint as _,
 
             _ => ptr::null(),
         }
@@ -167,6 +194,9 @@ pub struct LLVMBackend {
     buffer: Arc<Buffer>,
     msm: Option<ModuleStateMap>,
     local_func_id_to_offset: Vec<usize>,
+    /// The handlers of the breakpoints in the compiled code, which refers to them by address.
+    #[allow(dead_code)]
+    breakpoints: Vec<Box<BreakpointHandler>>,
 }
 
 impl LLVMBackend {
@@ -176,7 +206,9 @@ impl LLVMBackend {
         _stackmaps: &StackmapRegistry,
         _module_info: &ModuleInfo,
         target_machine: &TargetMachine,
+        breakpoints: Vec<Box<BreakpointHandler>>,
     ) -> (Self, LLVMCache) {
+        let has_breakpoints = !breakpoints.is_empty();
         let memory_buffer = target_machine
             .write_to_memory_buffer(&module.borrow_mut(), FileType::Object)
             .unwrap();
@@ -317,8 +349,12 @@ impl LLVMBackend {
                         buffer: Arc::clone(&buffer),
                         msm: Some(msm),
                         local_func_id_to_offset,
+                        breakpoints,
+                    },
+                    LLVMCache {
+                        buffer,
+                        has_breakpoints,
                     },
-                    LLVMCache { buffer },
                 );
             }
         }
@@ -330,12 +366,19 @@ impl LLVMBackend {
                 buffer: Arc::clone(&buffer),
                 msm: None,
                 local_func_id_to_offset: vec![],
+                breakpoints,
+            },
+            LLVMCache {
+                buffer,
+                has_breakpoints,
             },
-            LLVMCache { buffer },
         )
     }
 
-    pub unsafe fn from_buffer(memory: Memory) -> Result<(Self, LLVMCache), String> {
+    pub unsafe fn from_buffer(
+        memory: Memory,
+        module_info: &ModuleInfo,
+    ) -> Result<(Self, LLVMCache), String> {
         let callbacks = get_callbacks();
         let mut module: *mut LLVMModule = ptr::null_mut();
 
@@ -351,6 +394,31 @@ impl LLVMBackend {
             crate::platform::install_signal_handler();
         });
 
+        // The object is relocated again on load, so recover the function offsets
+        // from the symbol table instead of storing them in the cache.
+        let code_ptr = llvm_backend_get_code_ptr(module) as usize;
+        let code_len = llvm_backend_get_code_size(module);
+        let mut local_func_id_to_offset =
+            Vec::with_capacity(module_info.func_assoc.len() - module_info.imported_functions.len());
+        for index in module_info.imported_functions.len()..module_info.func_assoc.len() {
+            let name = if cfg!(target_os = "macos") {
+                format!("_fn{}", index)
+            } else {
+                format!("fn{}", index)
+            };
+
+            let c_str = CString::new(name).unwrap();
+            let addr = get_func_symbol(module, c_str.as_ptr()) as usize;
+            if addr < code_ptr || addr >= code_ptr + code_len {
+                module_delete(module);
+                return Err(format!(
+                    "function {} is missing from the cached object",
+                    index
+                ));
+            }
+            local_func_id_to_offset.push(addr - code_ptr);
+        }
+
         let buffer = Arc::new(Buffer::Memory(memory));
 
         Ok((
@@ -358,9 +426,13 @@ impl LLVMBackend {
                 module,
                 buffer: Arc::clone(&buffer),
                 msm: None,
-                local_func_id_to_offset: vec![],
+                local_func_id_to_offset,
+                breakpoints: vec![],
+            },
+            LLVMCache {
+                buffer,
+                has_breakpoints: false,
             },
-            LLVMCache { buffer },
         ))
     }
 }
@@ -439,10 +511,21 @@ unsafe impl Sync for LLVMCache {}
 
 pub struct LLVMCache {
     buffer: Arc<Buffer>,
+    /// Whether the compiled code calls breakpoint handlers, which it refers to by address.
+    has_breakpoints: bool,
 }
 
 impl CacheGen for LLVMCache {
     fn generate_cache(&self) -> Result<(Box<[u8]>, Memory), CacheError> {
+        // The addresses of the handlers would dangle once the module is dropped, and a
+        // module loaded from the cache would call them.
+        if self.has_breakpoints {
+            return Err(CacheError::Unknown(
+                "modules with breakpoints, like the ones of metering middlewares, can't be cached"
+                    .to_string(),
+            ));
+        }
+
         let mut memory = Memory::with_size_protect(self.buffer.len(), Protect::ReadWrite)
             .map_err(CacheError::SerializeError)?;
 
@@ -7,15 +7,16 @@ use crate::{
     trampolines::generate_trampolines,
 };
 use inkwell::{
+    attributes::{Attribute, AttributeLoc},
     builder::Builder,
     context::Context,
     module::{Linkage, Module},
     passes::PassManager,
     targets::{CodeModel, InitializationConfig, RelocMode, Target, TargetMachine},
-    types::{BasicType, BasicTypeEnum, FunctionType, PointerType, VectorType},
+    types::{BasicType, BasicTypeEnum, FloatType, FunctionType, IntType, PointerType, VectorType},
     values::{
-        BasicValue, BasicValueEnum, FloatValue, FunctionValue, IntValue, PhiValue, PointerValue,
-        VectorValue,
+        BasicValue, BasicValueEnum, FloatValue, FunctionValue, InstructionValue, IntValue,
+        PhiValue, PointerValue, VectorValue,
     },
     AddressSpace, AtomicOrdering, AtomicRMWBinOp, FloatPredicate, IntPredicate, OptimizationLevel,
 };
@@ -27,7 +28,7 @@ use std::{
     sync::{Arc, RwLock},
 };
 use wasmer_runtime_core::{
-    backend::{Backend, CacheGen, CompilerConfig, Token},
+    backend::{Backend, CacheGen, CompilerConfig, MemoryBoundCheckMode, Token},
     cache::{Artifact, Error as CacheError},
     codegen::*,
     memory::MemoryType,
@@ -560,6 +561,7 @@ fn resolve_memory_ptr(
     function: &FunctionValue,
     state: &mut State,
     ctx: &mut CtxType,
+    memory_bound_check_mode: MemoryBoundCheckMode,
     memarg: &MemoryImmediate,
     ptr_ty: PointerType,
     value_size: usize,
@@ -596,9 +598,20 @@ fn resolve_memory_ptr(
         MemoryCache::Static {
             base_ptr,
             bounds,
+            ptr_to_bounds,
             minimum,
             maximum,
-        } => (base_ptr, bounds, minimum, maximum),
+        } => {
+            let bounds = match memory_bound_check_mode {
+                // The cached bounds go stale once the memory grows, which is fine
+                // when they are only used as a hint but not for explicit checks.
+                MemoryBoundCheckMode::Enable => {
+                    builder.build_load(ptr_to_bounds, "bounds").into_int_value()
+                }
+                MemoryBoundCheckMode::Default | MemoryBoundCheckMode::Disable => bounds,
+            };
+            (base_ptr, bounds, minimum, maximum)
+        }
     };
     let mem_base = builder
         .build_bitcast(mem_base, intrinsics.i8_ptr_ty, &state.var_name())
@@ -611,9 +624,19 @@ fn resolve_memory_ptr(
         builder.build_int_z_extend(var_offset_i32, intrinsics.i64_ty, &state.var_name());
     let effective_offset = builder.build_int_add(var_offset, imm_offset, &state.var_name());
 
-    if let MemoryCache::Dynamic { .. } = memory_cache {
-        // If the memory is dynamic, do a bounds check. For static we rely on
-        // the size being a multiple of the page size and hitting a guard page.
+    // Unless every access is checked, only dynamic memories get a bounds check. For
+    // static we rely on the size being a multiple of the page size and hitting a guard
+    // page, but dynamic memories have no guard region large enough to catch every
+    // out-of-bounds access.
+    let need_check = match memory_bound_check_mode {
+        MemoryBoundCheckMode::Default | MemoryBoundCheckMode::Disable => match memory_cache {
+            MemoryCache::Dynamic { .. } => true,
+            MemoryCache::Static { .. } => false,
+        },
+        MemoryBoundCheckMode::Enable => true,
+    };
+
+    if need_check {
         let value_size_v = intrinsics.i64_ty.const_int(value_size as u64, false);
         let ptr_in_bounds = if effective_offset.is_const() {
             let load_offset_end = effective_offset.const_add(value_size_v);
@@ -771,6 +794,47 @@ fn finalize_opcode_stack_map(
     });
 }
 
+/// Returns the pointer through which an imported mutable global of type `ty`
+/// is accessed atomically, and the float and integer types its value is
+/// bitcast between. LLVM only has atomic loads and stores of integers, so
+/// `f32` and `f64` globals are accessed as `i32` and `i64`.
+fn atomic_global_ptr(
+    builder: &Builder,
+    intrinsics: &Intrinsics,
+    ptr_to_value: PointerValue,
+    ty: Type,
+) -> (PointerValue, Option<(FloatType, IntType)>) {
+    let (float_ty, int_ty, int_ptr_ty) = match ty {
+        Type::F32 => (intrinsics.f32_ty, intrinsics.i32_ty, intrinsics.i32_ptr_ty),
+        Type::F64 => (intrinsics.f64_ty, intrinsics.i64_ty, intrinsics.i64_ptr_ty),
+        Type::I32 | Type::I64 | Type::V128 => return (ptr_to_value, None),
+    };
+    let ptr = builder.build_pointer_cast(ptr_to_value, int_ptr_ty, "global_bits_ptr");
+    (ptr, Some((float_ty, int_ty)))
+}
+
+/// Makes a load or store of an imported mutable global atomic, so that
+/// the host can update the global while the instance runs. Without it, a
+/// load in a loop could be hoisted out of the loop.
+fn make_access_atomic(access: InstructionValue, value: BasicValueEnum) {
+    if let BasicValueEnum::IntValue(value) = value {
+        access
+            .set_alignment(value.get_type().get_bit_width() / 8)
+            .unwrap();
+        access
+            .set_atomic_ordering(AtomicOrdering::Monotonic)
+            .unwrap();
+    }
+}
+
+/// Returns whether memory 0, the one accessed by atomic wait and notify, is shared.
+fn memory_is_shared(info: &ModuleInfo) -> bool {
+    match MemoryIndex::new(0).local_or_import(info) {
+        LocalOrImport::Local(local) => info.memories[local].shared,
+        LocalOrImport::Import(import) => info.imported_memories[import].1.shared,
+    }
+}
+
 fn trap_if_misaligned(
     builder: &Builder,
     intrinsics: &Intrinsics,
@@ -824,6 +888,120 @@ fn trap_if_misaligned(
     builder.position_at_end(&continue_block);
 }
 
+/// Loads the internal field at `field_ptr`, the one of index `idx`.
+///
+/// Internal fields hold the counts of metering and other middlewares, which the host reads
+/// and writes between and during calls. The access is volatile, so that the optimizer
+/// neither merges the updates of successive blocks, nor hoists them out of loops, nor moves
+/// them across the breakpoints that check them: the counts stay exact however the function
+/// is optimized.
+fn load_internal_volatile(
+    builder: &Builder,
+    intrinsics: &Intrinsics,
+    module: Rc<RefCell<Module>>,
+    field_ptr: PointerValue,
+    idx: u32,
+) -> IntValue {
+    let value = builder.build_load(field_ptr, "internal");
+    let load = value.as_instruction_value().unwrap();
+    load.set_volatile(true).unwrap();
+    tbaa_label(module, intrinsics, "internal", load, Some(idx));
+    value.into_int_value()
+}
+
+/// Stores `value` in the internal field at `field_ptr`, the one of index `idx`. See
+/// `load_internal_volatile`.
+fn store_internal_volatile(
+    builder: &Builder,
+    intrinsics: &Intrinsics,
+    module: Rc<RefCell<Module>>,
+    field_ptr: PointerValue,
+    idx: u32,
+    value: IntValue,
+) {
+    let store = builder.build_store(field_ptr, value);
+    store.set_volatile(true).unwrap();
+    tbaa_label(module, intrinsics, "internal", store, Some(idx));
+}
+
+/// Adds `value` to the internal field at `field_ptr`, which is how metering accounts the
+/// points of a block.
+fn add_to_internal(
+    builder: &Builder,
+    intrinsics: &Intrinsics,
+    module: Rc<RefCell<Module>>,
+    field_ptr: PointerValue,
+    idx: u32,
+    value: u64,
+) {
+    let old_value = load_internal_volatile(builder, intrinsics, module.clone(), field_ptr, idx);
+    let new_value = builder.build_int_add(old_value, intrinsics.i64_ty.const_int(value, false), "");
+    store_internal_volatile(builder, intrinsics, module, field_ptr, idx, new_value);
+}
+
+/// Calls the breakpoint handler at address `callback` if the internal field at `field_ptr`
+/// is at least `limit`, which is how metering stops the instance when it runs out of
+/// points. The check is marked unlikely, so that the path of a metered function that
+/// doesn't break stays straight.
+fn breakpoint_if_internal_at_least(
+    builder: &Builder,
+    intrinsics: &Intrinsics,
+    context: &Context,
+    function: &FunctionValue,
+    module: Rc<RefCell<Module>>,
+    ctx: BasicValueEnum,
+    field_ptr: PointerValue,
+    idx: u32,
+    limit: u64,
+    callback: u64,
+) {
+    let field_value = load_internal_volatile(builder, intrinsics, module, field_ptr, idx);
+    let reached = builder.build_int_compare(
+        IntPredicate::UGE,
+        field_value,
+        intrinsics.i64_ty.const_int(limit, false),
+        "",
+    );
+    let reached = builder
+        .build_call(
+            intrinsics.expect_i1,
+            &[
+                reached.as_basic_value_enum(),
+                intrinsics.i1_ty.const_int(0, false).as_basic_value_enum(),
+            ],
+            "",
+        )
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_int_value();
+
+    let breakpoint_block = context.append_basic_block(function, "internal_breakpoint");
+    let continue_block = context.append_basic_block(function, "internal_continue");
+    builder.build_conditional_branch(reached, &breakpoint_block, &continue_block);
+    builder.position_at_end(&breakpoint_block);
+    let callback = intrinsics.i64_ty.const_int(callback, false);
+    builder.build_call(
+        intrinsics.breakpoint,
+        &[ctx, callback.as_basic_value_enum()],
+        "",
+    );
+    builder.build_unconditional_branch(&continue_block);
+    builder.position_at_end(&continue_block);
+}
+
+/// Keeps `callback` alive as long as the compiled module, and returns the address that
+/// `vm.breakpoint` takes.
+fn register_breakpoint(
+    breakpoints: &RefCell<Vec<Box<BreakpointHandler>>>,
+    callback: BreakpointHandler,
+) -> u64 {
+    let callback = Box::new(callback);
+    let raw = &*callback as *const BreakpointHandler as u64;
+    breakpoints.borrow_mut().push(callback);
+    raw
+}
+
 #[derive(Debug)]
 pub struct CodegenError {
     pub message: String,
@@ -837,7 +1015,10 @@ pub unsafe extern "C" fn callback_trampoline(
     callback: *mut BreakpointHandler,
 ) {
     let callback = Box::from_raw(callback);
-    let result: Result<(), Box<dyn std::any::Any>> = callback(BreakpointInfo { fault: None });
+    let result: Result<(), Box<dyn std::any::Any>> = callback(BreakpointInfo {
+        fault: None,
+        ctx: None,
+    });
     match result {
         Ok(()) => *b = None,
         Err(e) => *b = Some(e),
@@ -854,10 +1035,13 @@ pub struct LLVMModuleCodeGenerator {
     function_signatures: Option<Arc<Map<FuncIndex, SigIndex>>>,
     llvm_functions: Rc<RefCell<HashMap<FuncIndex, FunctionValue>>>,
     func_import_count: usize,
-    personality_func: FunctionValue,
+    personality_func: Option<FunctionValue>,
     module: Rc<RefCell<Module>>,
     stackmaps: Rc<RefCell<StackmapRegistry>>,
+    breakpoints: Rc<RefCell<Vec<Box<BreakpointHandler>>>>,
     track_state: bool,
+    memory_bound_check_mode: MemoryBoundCheckMode,
+    huge_function_threshold: Option<usize>,
     target_machine: TargetMachine,
 }
 
@@ -876,9 +1060,12 @@ pub struct LLVMFunctionCodeGenerator {
     ctx: Option<CtxType<'static>>,
     unreachable_depth: usize,
     stackmaps: Rc<RefCell<StackmapRegistry>>,
+    breakpoints: Rc<RefCell<Vec<Box<BreakpointHandler>>>>,
     index: usize,
     opcode_offset: usize,
     track_state: bool,
+    memory_bound_check_mode: MemoryBoundCheckMode,
+    huge_function_threshold: Option<usize>,
     module: Rc<RefCell<Module>>,
 }
 
@@ -1012,11 +1199,11 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                         return Ok(());
                     }
                     InternalEvent::Breakpoint(callback) => {
-                        let raw = Box::into_raw(Box::new(callback)) as u64;
+                        let raw = register_breakpoint(&self.breakpoints, callback);
                         let callback = intrinsics.i64_ty.const_int(raw, false);
                         builder.build_call(
-                            intrinsics.throw_breakpoint,
-                            &[callback.as_basic_value_enum()],
+                            intrinsics.breakpoint,
+                            &[ctx.basic(), callback.as_basic_value_enum()],
                             "",
                         );
                         return Ok(());
@@ -1053,6 +1240,96 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                             );
                         }
                     }
+                    InternalEvent::AddInternal(idx, value) => {
+                        if state.reachable {
+                            let field_ptr = ctx.internal_field(
+                                idx as usize,
+                                intrinsics,
+                                self.module.clone(),
+                                builder,
+                            );
+                            add_to_internal(
+                                builder,
+                                intrinsics,
+                                self.module.clone(),
+                                field_ptr,
+                                idx,
+                                value,
+                            );
+                        }
+                    }
+                    InternalEvent::BreakpointIfInternalAtLeast(idx, limit, callback) => {
+                        if state.reachable {
+                            let field_ptr = ctx.internal_field(
+                                idx as usize,
+                                intrinsics,
+                                self.module.clone(),
+                                builder,
+                            );
+                            let callback = register_breakpoint(&self.breakpoints, callback);
+                            breakpoint_if_internal_at_least(
+                                builder,
+                                intrinsics,
+                                context,
+                                &function,
+                                self.module.clone(),
+                                ctx.basic(),
+                                field_ptr,
+                                idx,
+                                limit,
+                                callback,
+                            );
+                        }
+                    }
+                    InternalEvent::IncrementCounter(idx, counter) => {
+                        if state.reachable {
+                            let idx = idx as usize;
+                            let field_ptr =
+                                ctx.internal_field(idx, intrinsics, self.module.clone(), builder);
+                            let buffer = load_internal_volatile(
+                                builder,
+                                intrinsics,
+                                self.module.clone(),
+                                field_ptr,
+                                idx as u32,
+                            );
+                            let attached = builder.build_int_compare(
+                                IntPredicate::NE,
+                                buffer,
+                                intrinsics.i64_zero,
+                                "",
+                            );
+
+                            let increment_block =
+                                context.append_basic_block(&function, "counter_increment");
+                            let continue_block =
+                                context.append_basic_block(&function, "counter_continue");
+                            builder.build_conditional_branch(
+                                attached,
+                                &increment_block,
+                                &continue_block,
+                            );
+                            builder.position_at_end(&increment_block);
+                            let counters =
+                                builder.build_int_to_ptr(buffer, intrinsics.i64_ptr_ty, "");
+                            let counter_ptr = unsafe {
+                                builder.build_in_bounds_gep(
+                                    counters,
+                                    &[intrinsics.i64_ty.const_int(counter as u64, false)],
+                                    "counter_ptr",
+                                )
+                            };
+                            let old_value = builder.build_load(counter_ptr, "counter");
+                            let new_value = builder.build_int_add(
+                                old_value.into_int_value(),
+                                intrinsics.i64_ty.const_int(1, false),
+                                "",
+                            );
+                            builder.build_store(counter_ptr, new_value);
+                            builder.build_unconditional_branch(&continue_block);
+                            builder.position_at_end(&continue_block);
+                        }
+                    }
                 }
                 return Ok(());
             }
@@ -1641,7 +1918,17 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                         state.push1(value);
                     }
                     GlobalCache::Mut { ptr_to_value } => {
-                        let value = builder.build_load(ptr_to_value, "global_value");
+                        let imported_ty = match index.local_or_import(info) {
+                            LocalOrImport::Import(import) => {
+                                Some(info.imported_globals[import].1.ty)
+                            }
+                            LocalOrImport::Local(_) => None,
+                        };
+                        let (ptr, bitcast_tys) = match imported_ty {
+                            Some(ty) => atomic_global_ptr(builder, intrinsics, ptr_to_value, ty),
+                            None => (ptr_to_value, None),
+                        };
+                        let value = builder.build_load(ptr, "global_value");
                         tbaa_label(
                             self.module.clone(),
                             intrinsics,
@@ -1649,6 +1936,15 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                             value.as_instruction_value().unwrap(),
                             Some(global_index),
                         );
+                        if imported_ty.is_some() {
+                            make_access_atomic(value.as_instruction_value().unwrap(), value);
+                        }
+                        let value = match bitcast_tys {
+                            Some((float_ty, _)) => {
+                                builder.build_bitcast(value, float_ty, "global_value")
+                            }
+                            None => value,
+                        };
                         state.push1(value);
                     }
                 }
@@ -1660,7 +1956,23 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                 let global_cache = ctx.global_cache(index, intrinsics, self.module.clone());
                 match global_cache {
                     GlobalCache::Mut { ptr_to_value } => {
-                        let store = builder.build_store(ptr_to_value, value);
+                        let imported_ty = match index.local_or_import(module_info) {
+                            LocalOrImport::Import(import) => {
+                                Some(module_info.imported_globals[import].1.ty)
+                            }
+                            LocalOrImport::Local(_) => None,
+                        };
+                        let (ptr, bitcast_tys) = match imported_ty {
+                            Some(ty) => atomic_global_ptr(builder, intrinsics, ptr_to_value, ty),
+                            None => (ptr_to_value, None),
+                        };
+                        let value = match bitcast_tys {
+                            Some((_, int_ty)) => {
+                                builder.build_bitcast(value, int_ty, "global_bits")
+                            }
+                            None => value,
+                        };
+                        let store = builder.build_store(ptr, value);
                         tbaa_label(
                             self.module.clone(),
                             intrinsics,
@@ -1668,6 +1980,9 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                             store,
                             Some(global_index),
                         );
+                        if imported_ty.is_some() {
+                            make_access_atomic(store, value);
+                        }
                     }
                     GlobalCache::Const { value: _ } => {
                         return Err(CodegenError {
@@ -4464,6 +4779,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -4492,6 +4808,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i64_ptr_ty,
                     8,
@@ -4520,6 +4837,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.f32_ptr_ty,
                     4,
@@ -4548,6 +4866,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.f64_ptr_ty,
                     8,
@@ -4576,6 +4895,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i128_ptr_ty,
                     16,
@@ -4606,6 +4926,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -4624,6 +4945,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i64_ptr_ty,
                     8,
@@ -4643,6 +4965,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.f32_ptr_ty,
                     4,
@@ -4662,6 +4985,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.f64_ptr_ty,
                     8,
@@ -4681,6 +5005,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i128_ptr_ty,
                     16,
@@ -4698,6 +5023,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -4731,6 +5057,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -4764,6 +5091,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -4796,6 +5124,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -4828,6 +5157,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -4862,6 +5192,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -4895,6 +5226,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -4928,6 +5260,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -4961,6 +5294,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -4994,6 +5328,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -5029,6 +5364,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -5049,6 +5385,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -5069,6 +5406,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -5376,6 +5714,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -5411,6 +5750,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -5446,6 +5786,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -5481,6 +5822,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i64_ptr_ty,
                     8,
@@ -5525,6 +5867,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -5554,6 +5897,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i64_ptr_ty,
                     8,
@@ -5583,6 +5927,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -5616,6 +5961,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -5649,6 +5995,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -5682,6 +6029,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -5715,6 +6063,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -5749,6 +6098,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -5778,6 +6128,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i64_ptr_ty,
                     8,
@@ -5807,6 +6158,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -5839,6 +6191,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -5870,6 +6223,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -5901,6 +6255,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -5943,6 +6298,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -5985,6 +6341,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -6024,6 +6381,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -6066,6 +6424,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -6108,6 +6467,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -6150,6 +6510,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i64_ptr_ty,
                     8,
@@ -6189,6 +6550,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -6231,6 +6593,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -6273,6 +6636,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -6312,6 +6676,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -6354,6 +6719,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -6396,6 +6762,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -6438,6 +6805,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i64_ptr_ty,
                     8,
@@ -6477,6 +6845,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -6519,6 +6888,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -6561,6 +6931,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -6600,6 +6971,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -6642,6 +7014,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -6684,6 +7057,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -6726,6 +7100,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i64_ptr_ty,
                     8,
@@ -6765,6 +7140,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -6807,6 +7183,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -6849,6 +7226,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -6889,6 +7267,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -6931,6 +7310,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -6973,6 +7353,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -7015,6 +7396,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i64_ptr_ty,
                     8,
@@ -7054,6 +7436,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -7096,6 +7479,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -7138,6 +7522,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -7177,6 +7562,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -7219,6 +7605,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -7261,6 +7648,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -7303,6 +7691,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i64_ptr_ty,
                     8,
@@ -7342,6 +7731,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -7384,6 +7774,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -7426,6 +7817,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -7465,6 +7857,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -7507,6 +7900,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -7549,6 +7943,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -7591,6 +7986,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i64_ptr_ty,
                     8,
@@ -7631,6 +8027,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -7681,6 +8078,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -7731,6 +8129,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -7773,6 +8172,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i8_ptr_ty,
                     1,
@@ -7823,6 +8223,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i16_ptr_ty,
                     2,
@@ -7873,6 +8274,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i32_ptr_ty,
                     4,
@@ -7923,6 +8325,7 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                     &function,
                     &mut state,
                     &mut ctx,
+                    self.memory_bound_check_mode,
                     memarg,
                     intrinsics.i64_ptr_ty,
                     8,
@@ -7955,6 +8358,120 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                 state.push1(old);
             }
 
+            Operator::I32Wait { ref memarg } => {
+                let (expected, timeout) = state.pop2()?;
+                let effective_address = resolve_memory_ptr(
+                    builder,
+                    intrinsics,
+                    context,
+                    self.module.clone(),
+                    &function,
+                    &mut state,
+                    &mut ctx,
+                    self.memory_bound_check_mode,
+                    memarg,
+                    intrinsics.i32_ptr_ty,
+                    4,
+                )?;
+                trap_if_misaligned(
+                    builder,
+                    intrinsics,
+                    context,
+                    &function,
+                    memarg,
+                    effective_address,
+                );
+                if memory_is_shared(info) {
+                    let result = builder.build_call(
+                        intrinsics.memory_atomic_wait32,
+                        &[effective_address.as_basic_value_enum(), expected, timeout],
+                        &state.var_name(),
+                    );
+                    state.push1(result.try_as_basic_value().left().unwrap());
+                } else {
+                    // Waiting on an unshared memory traps, as nothing could notify it.
+                    // There is no dedicated trap code for it.
+                    builder.build_call(
+                        intrinsics.throw_trap,
+                        &[intrinsics.trap_unreachable],
+                        "throw",
+                    );
+                    builder.build_unreachable();
+                    state.reachable = false;
+                }
+            }
+            Operator::I64Wait { ref memarg } => {
+                let (expected, timeout) = state.pop2()?;
+                let effective_address = resolve_memory_ptr(
+                    builder,
+                    intrinsics,
+                    context,
+                    self.module.clone(),
+                    &function,
+                    &mut state,
+                    &mut ctx,
+                    self.memory_bound_check_mode,
+                    memarg,
+                    intrinsics.i64_ptr_ty,
+                    8,
+                )?;
+                trap_if_misaligned(
+                    builder,
+                    intrinsics,
+                    context,
+                    &function,
+                    memarg,
+                    effective_address,
+                );
+                if memory_is_shared(info) {
+                    let result = builder.build_call(
+                        intrinsics.memory_atomic_wait64,
+                        &[effective_address.as_basic_value_enum(), expected, timeout],
+                        &state.var_name(),
+                    );
+                    state.push1(result.try_as_basic_value().left().unwrap());
+                } else {
+                    // Waiting on an unshared memory traps, as nothing could notify it.
+                    // There is no dedicated trap code for it.
+                    builder.build_call(
+                        intrinsics.throw_trap,
+                        &[intrinsics.trap_unreachable],
+                        "throw",
+                    );
+                    builder.build_unreachable();
+                    state.reachable = false;
+                }
+            }
+            Operator::Wake { ref memarg } => {
+                let count = state.pop1()?;
+                let effective_address = resolve_memory_ptr(
+                    builder,
+                    intrinsics,
+                    context,
+                    self.module.clone(),
+                    &function,
+                    &mut state,
+                    &mut ctx,
+                    self.memory_bound_check_mode,
+                    memarg,
+                    intrinsics.i32_ptr_ty,
+                    4,
+                )?;
+                trap_if_misaligned(
+                    builder,
+                    intrinsics,
+                    context,
+                    &function,
+                    memarg,
+                    effective_address,
+                );
+                let result = builder.build_call(
+                    intrinsics.memory_atomic_notify,
+                    &[effective_address.as_basic_value_enum(), count],
+                    &state.var_name(),
+                );
+                state.push1(result.try_as_basic_value().left().unwrap());
+            }
             Operator::MemoryGrow { reserved } => {
                 let memory_index = MemoryIndex::new(reserved as usize);
                 let func_value = match memory_index.local_or_import(info) {
@@ -8059,6 +8576,20 @@ impl FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator {
                 });
             }
         }
+
+        // Optimizing huge functions can take minutes; leave them to the
+        // fast instruction selector instead.
+        if let Some(threshold) = self.huge_function_threshold {
+            if self.opcode_offset > threshold {
+                let context = self.context.as_ref().unwrap();
+                for name in &["optnone", "noinline"] {
+                    let attribute =
+                        context.create_enum_attribute(Attribute::get_named_enum_kind_id(name), 0);
+                    self.function.add_attribute(AttributeLoc::Function, attribute);
+                }
+            }
+        }
+
         Ok(())
     }
 }
@@ -8097,7 +8628,17 @@ impl ModuleCodeGenerator<LLVMFunctionCodeGenerator, LLVMBackend, CodegenError>
                 info: true,
                 machine_code: true,
             }),
-            _ => unimplemented!("compile to target other than x86-64 is not supported"),
+            _ if triple.starts_with("aarch64") || triple.starts_with("arm64") => {
+                Target::initialize_aarch64(&InitializationConfig {
+                    asm_parser: true,
+                    asm_printer: true,
+                    base: true,
+                    disassembler: true,
+                    info: true,
+                    machine_code: true,
+                })
+            }
+            _ => unimplemented!("compile to target other than x86-64 or aarch64 is not supported"),
         }
 
         let target = Target::from_triple(&triple).unwrap();
@@ -8119,11 +8660,18 @@ impl ModuleCodeGenerator<LLVMFunctionCodeGenerator, LLVMBackend, CodegenError>
 
         let intrinsics = Intrinsics::declare(&module, &context);
 
-        let personality_func = module.add_function(
-            "__gxx_personality_v0",
-            intrinsics.i32_ty.fn_type(&[], false),
-            Some(Linkage::External),
-        );
+        // Traps are raised with `longjmp` rather than through landing pads, so the
+        // personality function only matters for DWARF unwind tables. COFF objects
+        // use SEH unwind info and have no `__gxx_personality_v0` to link against.
+        let personality_func = if triple.contains("windows") {
+            None
+        } else {
+            Some(module.add_function(
+                "__gxx_personality_v0",
+                intrinsics.i32_ty.fn_type(&[], false),
+                Some(Linkage::External),
+            ))
+        };
 
         LLVMModuleCodeGenerator {
             context: Some(context),
@@ -8138,7 +8686,10 @@ impl ModuleCodeGenerator<LLVMFunctionCodeGenerator, LLVMBackend, CodegenError>
             func_import_count: 0,
             personality_func,
             stackmaps: Rc::new(RefCell::new(StackmapRegistry::default())),
+            breakpoints: Rc::new(RefCell::new(vec![])),
             track_state: false,
+            memory_bound_check_mode: MemoryBoundCheckMode::Default,
+            huge_function_threshold: None,
             target_machine,
         }
     }
@@ -8174,7 +8725,20 @@ impl ModuleCodeGenerator<LLVMFunctionCodeGenerator, LLVMBackend, CodegenError>
         let func_sig = self.signatures_raw[sig_id].clone();
 
         let function = &self.llvm_functions.borrow_mut()[&func_index];
-        function.set_personality_function(self.personality_func);
+        if let Some(personality_func) = self.personality_func {
+            function.set_personality_function(personality_func);
+        }
+        // The signal handler walks the frame pointers to capture backtraces.
+        function.add_attribute(
+            AttributeLoc::Function,
+            context.create_string_attribute("no-frame-pointer-elim", "true"),
+        );
+        // Traps unwind the functions with `longjmp`, which needs their unwind
+        // tables on Windows.
+        function.add_attribute(
+            AttributeLoc::Function,
+            context.create_enum_attribute(Attribute::get_named_enum_kind_id("uwtable"), 0),
+        );
 
         let mut state = State::new();
         let entry_block = context.append_basic_block(&function, "entry");
@@ -8240,9 +8804,12 @@ impl ModuleCodeGenerator<LLVMFunctionCodeGenerator, LLVMBackend, CodegenError>
             ctx: None,
             unreachable_depth: 0,
             stackmaps: self.stackmaps.clone(),
+            breakpoints: self.breakpoints.clone(),
             index: local_func_index,
             opcode_offset: 0,
             track_state: self.track_state,
+            memory_bound_check_mode: self.memory_bound_check_mode,
+            huge_function_threshold: self.huge_function_threshold,
             module: self.module.clone(),
         };
         self.functions.push(code);
@@ -8317,12 +8884,15 @@ impl ModuleCodeGenerator<LLVMFunctionCodeGenerator, LLVMBackend, CodegenError>
             &*stackmaps,
             module_info,
             &self.target_machine,
+            self.breakpoints.replace(vec![]),
         );
         Ok((backend, Box::new(cache_gen)))
     }
 
     fn feed_compiler_config(&mut self, config: &CompilerConfig) -> Result<(), CodegenError> {
         self.track_state = config.track_state;
+        self.memory_bound_check_mode = config.memory_bound_check_mode;
+        self.huge_function_threshold = config.huge_function_threshold;
         Ok(())
     }
 
@@ -8368,13 +8938,14 @@ impl ModuleCodeGenerator<LLVMFunctionCodeGenerator, LLVMBackend, CodegenError>
     unsafe fn from_cache(artifact: Artifact, _: Token) -> Result<ModuleInner, CacheError> {
         let (info, _, memory) = artifact.consume();
         let (backend, cache_gen) =
-            LLVMBackend::from_buffer(memory).map_err(CacheError::DeserializeError)?;
+            LLVMBackend::from_buffer(memory, &info).map_err(CacheError::DeserializeError)?;
 
         Ok(ModuleInner {
             runnable_module: Box::new(backend),
             cache_gen: Box::new(cache_gen),
 
             info,
+            memory_images: Default::default(),
         })
     }
 }
@@ -143,8 +143,12 @@ pub struct Intrinsics {
     pub memory_size_static_import: FunctionValue,
     pub memory_size_shared_import: FunctionValue,
 
+    pub memory_atomic_wait32: FunctionValue,
+    pub memory_atomic_wait64: FunctionValue,
+    pub memory_atomic_notify: FunctionValue,
+
     pub throw_trap: FunctionValue,
-    pub throw_breakpoint: FunctionValue,
+    pub breakpoint: FunctionValue,
 
     pub experimental_stackmap: FunctionValue,
 
@@ -199,6 +203,8 @@ impl Intrinsics {
         let f32x4_ty_basic = f32x4_ty.as_basic_type_enum();
         let f64x2_ty_basic = f64x2_ty.as_basic_type_enum();
         let i8_ptr_ty_basic = i8_ptr_ty.as_basic_type_enum();
+        let i32_ptr_ty_basic = i32_ptr_ty.as_basic_type_enum();
+        let i64_ptr_ty_basic = i64_ptr_ty.as_basic_type_enum();
 
         let ctx_ty = context.opaque_struct_type("ctx");
         let ctx_ptr_ty = ctx_ty.ptr_type(AddressSpace::Generic);
@@ -501,6 +507,22 @@ impl Intrinsics {
                 ret_i32_take_ctx_i32,
                 None,
             ),
+
+            memory_atomic_wait32: module.add_function(
+                "vm.memory.atomic.wait32",
+                i32_ty.fn_type(&[i32_ptr_ty_basic, i32_ty_basic, i64_ty_basic], false),
+                None,
+            ),
+            memory_atomic_wait64: module.add_function(
+                "vm.memory.atomic.wait64",
+                i32_ty.fn_type(&[i64_ptr_ty_basic, i64_ty_basic, i64_ty_basic], false),
+                None,
+            ),
+            memory_atomic_notify: module.add_function(
+                "vm.memory.atomic.notify",
+                i32_ty.fn_type(&[i32_ptr_ty_basic, i32_ty_basic], false),
+                None,
+            ),
             throw_trap: module.add_function(
                 "vm.exception.trap",
                 void_ty.fn_type(&[i32_ty_basic], false),
@@ -517,9 +539,9 @@ impl Intrinsics {
                 ),
                 None,
             ),
-            throw_breakpoint: module.add_function(
+            breakpoint: module.add_function(
                 "vm.breakpoint",
-                void_ty.fn_type(&[i64_ty_basic], false),
+                void_ty.fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i64_ty_basic], false),
                 None,
             ),
             ctx_ptr_ty,
@@ -551,9 +573,6 @@ impl Intrinsics {
         intrinsics
             .throw_trap
             .add_attribute(AttributeLoc::Function, noreturn);
-        intrinsics
-            .throw_breakpoint
-            .add_attribute(AttributeLoc::Function, noreturn);
 
         intrinsics
     }
@@ -572,6 +591,7 @@ pub enum MemoryCache {
     Static {
         base_ptr: PointerValue,
         bounds: IntValue,
+        ptr_to_bounds: PointerValue,
         minimum: Pages,
         maximum: Option<Pages>,
     },
@@ -776,6 +796,7 @@ impl<'a> CtxType<'a> {
                     MemoryCache::Static {
                         base_ptr,
                         bounds,
+                        ptr_to_bounds,
                         minimum,
                         maximum,
                     }
@@ -60,18 +60,39 @@ pub unsafe fn install_signal_handler() {
     sigaction(SIGSEGV, &sa).unwrap();
     sigaction(SIGBUS, &sa).unwrap();
     sigaction(SIGILL, &sa).unwrap();
+    // `llvm.trap` lowers to `brk` on aarch64, which raises SIGTRAP instead of SIGILL.
+    #[cfg(target_arch = "aarch64")]
+    sigaction(nix::sys::signal::SIGTRAP, &sa).unwrap();
 }
 
 #[cfg_attr(nightly, unwind(allowed))]
 extern "C" fn signal_trap_handler(
     _signum: ::nix::libc::c_int,
-    _siginfo: *mut siginfo_t,
-    _ucontext: *mut c_void,
+    siginfo: *mut siginfo_t,
+    ucontext: *mut c_void,
 ) {
     unsafe {
         if SigSet::all().thread_unblock().is_err() {
             std::process::abort();
         }
+        // The frames are found through the frame pointers, since the system
+        // unwinder can't be used from a signal handler.
+        #[cfg(all(any(target_os = "linux", target_os = "macos"), target_arch = "x86_64"))]
+        {
+            use wasmer_runtime_core::fault::{capture_backtrace, get_fault_info};
+            capture_backtrace(&get_fault_info(siginfo as *const c_void, ucontext));
+        }
+        #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+        {
+            let _ = siginfo;
+            let (pc, fp) = aarch64_pc_and_fp(ucontext);
+            wasmer_runtime_core::backtrace::capture_frames(pc, fp);
+        }
+        #[cfg(not(any(
+            all(any(target_os = "linux", target_os = "macos"), target_arch = "x86_64"),
+            all(target_os = "linux", target_arch = "aarch64")
+        )))]
+        let _ = (siginfo, ucontext);
         // Apparently, we can unwind from arbitary instructions, as long
         // as we don't need to catch the exception inside the function that
         // was interrupted.
@@ -81,6 +102,36 @@ extern "C" fn signal_trap_handler(
     }
 }
 
+/// Reads the program counter and the frame pointer (x29) of the interrupted
+/// code from the `ucontext_t` of a signal.
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+unsafe fn aarch64_pc_and_fp(ucontext: *const c_void) -> (usize, usize) {
+    // The kernel's `struct sigcontext`.
+    #[allow(dead_code)]
+    #[repr(C, align(16))]
+    struct sigcontext {
+        fault_address: u64,
+        regs: [u64; 31],
+        sp: u64,
+        pc: u64,
+        pstate: u64,
+    }
+
+    // The kernel's `struct ucontext`, whose signal mask is padded to 1024 bits.
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct ucontext_t {
+        uc_flags: u64,
+        uc_link: *mut c_void,
+        uc_stack: [u64; 3],
+        uc_sigmask: [u64; 16],
+        uc_mcontext: sigcontext,
+    }
+
+    let mcontext = &(*(ucontext as *const ucontext_t)).uc_mcontext;
+    (mcontext.pc as usize, mcontext.regs[29] as usize)
+}
+
 pub unsafe fn alloc_memory(
     size: usize,
     protect: MemProtect,
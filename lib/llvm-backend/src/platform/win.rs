@@ -2,18 +2,81 @@ use super::common::round_up_to_page_size;
 use crate::structs::{LLVMResult, MemProtect};
 use std::ptr;
 
+use winapi::um::errhandlingapi::AddVectoredExceptionHandler;
 use winapi::um::memoryapi::{VirtualAlloc, VirtualFree};
+use winapi::um::minwinbase::{
+    EXCEPTION_ACCESS_VIOLATION, EXCEPTION_ILLEGAL_INSTRUCTION, EXCEPTION_INT_DIVIDE_BY_ZERO,
+    EXCEPTION_INT_OVERFLOW,
+};
 use winapi::um::winnt::{
-    MEM_COMMIT, MEM_DECOMMIT, MEM_RESERVE, PAGE_EXECUTE_READ, PAGE_NOACCESS, PAGE_READONLY,
-    PAGE_READWRITE,
+    CONTEXT, EXCEPTION_POINTERS, LONG, MEM_COMMIT, MEM_DECOMMIT, MEM_RESERVE, PAGE_EXECUTE_READ,
+    PAGE_NOACCESS, PAGE_READONLY, PAGE_READWRITE,
 };
+use winapi::vc::excpt::{EXCEPTION_CONTINUE_EXECUTION, EXCEPTION_CONTINUE_SEARCH};
+
+extern "C" {
+    fn throw_trap(ty: i32) -> !;
+    fn has_unwind_point() -> bool;
+}
+
+// Mirrors `WasmTrapType` in `cpp/object_loader.hh`.
+const TRAP_MEMORY_OUT_OF_BOUNDS: i32 = 2;
+const TRAP_ILLEGAL_ARITHMETIC: i32 = 4;
+const TRAP_UNKNOWN: i32 = 5;
 
 pub unsafe fn visit_fde(_addr: *mut u8, _size: usize, _visitor: extern "C" fn(*mut u8)) {
-    // Do nothing on Windows
+    // Objects have no `.eh_frame` on Windows: the memory manager registers
+    // their `.pdata` with `RtlAddFunctionTable` instead.
 }
 
 pub unsafe fn install_signal_handler() {
-    // Do nothing on Windows
+    // Ask to be called first so faults in wasm code are seen before any
+    // frame-based SEH handler gets a chance to swallow them.
+    AddVectoredExceptionHandler(1, Some(exception_handler));
+}
+
+unsafe extern "system" fn exception_handler(info: *mut EXCEPTION_POINTERS) -> LONG {
+    // Faults outside of a wasm call belong to someone else.
+    if !has_unwind_point() {
+        return EXCEPTION_CONTINUE_SEARCH;
+    }
+
+    let record = &*(*info).ExceptionRecord;
+
+    let trap = match record.ExceptionCode {
+        EXCEPTION_ACCESS_VIOLATION => TRAP_MEMORY_OUT_OF_BOUNDS,
+        EXCEPTION_INT_DIVIDE_BY_ZERO | EXCEPTION_INT_OVERFLOW => TRAP_ILLEGAL_ARITHMETIC,
+        EXCEPTION_ILLEGAL_INSTRUCTION => TRAP_UNKNOWN,
+        _ => return EXCEPTION_CONTINUE_SEARCH,
+    };
+
+    redirect_to_throw_trap(&mut *(*info).ContextRecord, trap)
+}
+
+/// Resumes the faulting thread as if the faulting instruction had called
+/// `throw_trap`. We cannot unwind from inside the exception dispatcher, while
+/// `throw_trap` longjmps back to the closest unwind point.
+///
+/// The `longjmp` of MSVC unwinds the frames it crosses, so the fake call
+/// pushes the faulting address as its return address: the unwinder then
+/// goes on through the faulting function with its registered unwind table.
+#[cfg(target_arch = "x86_64")]
+unsafe fn redirect_to_throw_trap(context: &mut CONTEXT, trap: i32) -> LONG {
+    // There is no red zone on Windows, so the return address goes right
+    // below the stack pointer, leaving the stack aligned the way it is
+    // right after a `call`. The first argument is passed in rcx.
+    context.Rsp -= 8;
+    *(context.Rsp as *mut u64) = context.Rip;
+    context.Rip = throw_trap as usize as u64;
+    context.Rcx = trap as u64;
+    EXCEPTION_CONTINUE_EXECUTION
+}
+
+/// The registers of the other architectures aren't handled yet, so their
+/// faults are left to the other handlers.
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn redirect_to_throw_trap(_context: &mut CONTEXT, _trap: i32) -> LONG {
+    EXCEPTION_CONTINUE_SEARCH
 }
 
 pub unsafe fn alloc_memory(
@@ -166,10 +166,17 @@ fn get_compiler(limit: u64, metering: bool) -> impl Compiler {
 compile_error!("compiler not specified, activate a compiler via features");
 
 #[cfg(feature = "clif")]
-fn get_compiler(_limit: u64, metering: bool) -> impl Compiler {
-    compile_error!("cranelift does not implement metering");
-    use wasmer_clif_backend::CraneliftCompiler;
-    CraneliftCompiler::new()
+fn get_compiler(limit: u64, metering: bool) -> impl Compiler {
+    use wasmer_clif_backend::ModuleCodeGenerator as CraneliftMCG;
+    use wasmer_runtime_core::codegen::{MiddlewareChain, StreamingCompiler};
+    let c: StreamingCompiler<CraneliftMCG, _, _, _, _> = StreamingCompiler::new(move || {
+        let mut chain = MiddlewareChain::new();
+        if metering {
+            chain.push(Metering::new(limit));
+        }
+        chain
+    });
+    c
 }
 
 fn gas(ctx: &mut Ctx, gas_amount: u32) {
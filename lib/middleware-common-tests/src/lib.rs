@@ -1,41 +1,59 @@
-#[cfg(all(test, any(feature = "singlepass", feature = "llvm")))]
+#[cfg(all(test, any(feature = "singlepass", feature = "llvm", feature = "clif")))]
 mod tests {
     use wabt::wat2wasm;
 
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use wasmer_middleware_common::call_trace::{CallTrace, CallTraceHandler};
+    use wasmer_middleware_common::coverage::{Coverage, CoverageCounters, CoverageMap};
     use wasmer_middleware_common::metering::*;
-    use wasmer_runtime_core::codegen::{MiddlewareChain, StreamingCompiler};
+    use wasmer_middleware_common::opcode_filter::{ForbiddenOpcode, OpcodeClass, OpcodeFilter};
+    use wasmer_middleware_common::profiling::{FunctionProfile, ProfileCounters, Profiler};
+    use wasmer_runtime_core::call_depth::{CallDepth, CallStackExhausted};
+    use wasmer_runtime_core::codegen::{
+        Event, EventSink, FunctionMiddleware, InternalEvent, MiddlewareChain, StreamingCompiler,
+    };
+    use wasmer_runtime_core::fuel::OutOfFuel;
+    use wasmer_runtime_core::module::ModuleInfo;
+    use wasmer_runtime_core::types::{FuncIndex, Value};
+    use wasmer_runtime_core::vm::{Ctx, InternalField};
     use wasmer_runtime_core::{backend::Compiler, compile_with, imports, Func};
 
+    /// Returns a compiler running the middlewares of `chain` on the backend under test.
     #[cfg(feature = "llvm")]
-    fn get_compiler(limit: u64) -> impl Compiler {
+    fn get_compiler_with_chain(chain: impl Fn() -> MiddlewareChain) -> impl Compiler {
         use wasmer_llvm_backend::ModuleCodeGenerator as LLVMMCG;
-        let c: StreamingCompiler<LLVMMCG, _, _, _, _> = StreamingCompiler::new(move || {
-            let mut chain = MiddlewareChain::new();
-            chain.push(Metering::new(limit));
-            chain
-        });
+        let c: StreamingCompiler<LLVMMCG, _, _, _, _> = StreamingCompiler::new(chain);
         c
     }
 
     #[cfg(feature = "singlepass")]
-    fn get_compiler(limit: u64) -> impl Compiler {
+    fn get_compiler_with_chain(chain: impl Fn() -> MiddlewareChain) -> impl Compiler {
         use wasmer_singlepass_backend::ModuleCodeGenerator as SinglePassMCG;
-        let c: StreamingCompiler<SinglePassMCG, _, _, _, _> = StreamingCompiler::new(move || {
-            let mut chain = MiddlewareChain::new();
-            chain.push(Metering::new(limit));
-            chain
-        });
+        let c: StreamingCompiler<SinglePassMCG, _, _, _, _> = StreamingCompiler::new(chain);
         c
     }
 
-    #[cfg(not(any(feature = "llvm", feature = "clif", feature = "singlepass")))]
-    compile_error!("compiler not specified, activate a compiler via features");
-
     #[cfg(feature = "clif")]
-    fn get_compiler(_limit: u64) -> impl Compiler {
-        compile_error!("cranelift does not implement metering");
-        use wasmer_clif_backend::CraneliftCompiler;
-        CraneliftCompiler::new()
+    fn get_compiler_with_chain(chain: impl Fn() -> MiddlewareChain) -> impl Compiler {
+        use wasmer_clif_backend::ModuleCodeGenerator as CraneliftMCG;
+        let c: StreamingCompiler<CraneliftMCG, _, _, _, _> = StreamingCompiler::new(chain);
+        c
+    }
+
+    /// Returns a compiler running the middleware made by `middleware` on the backend under test.
+    fn get_compiler_with<M: FunctionMiddleware + 'static>(
+        middleware: impl Fn() -> M,
+    ) -> impl Compiler {
+        get_compiler_with_chain(move || {
+            let mut chain = MiddlewareChain::new();
+            chain.push(middleware());
+            chain
+        })
+    }
+
+    fn get_compiler(limit: u64) -> impl Compiler {
+        get_compiler_with(move || Metering::new(limit))
     }
 
     // Assemblyscript
@@ -120,6 +138,56 @@ mod tests {
         assert_eq!(get_points_used(&instance), 74);
     }
 
+    #[test]
+    fn test_points_exact_in_optimized_loop() {
+        // A loop the optimizer would like to fold, or at least to keep the counter of in a
+        // register: the points used must not depend on how the backend compiles it.
+        static LOOP_WAT: &'static str = r#"
+            (module
+              (func (export "sum") (param $n i32) (result i32) (local $i i32) (local $acc i32)
+                block $done
+                  loop $top
+                    get_local $i
+                    get_local $n
+                    i32.ge_u
+                    br_if $done
+                    get_local $acc
+                    get_local $i
+                    i32.add
+                    set_local $acc
+                    get_local $i
+                    i32.const 1
+                    i32.add
+                    set_local $i
+                    br $top
+                  end
+                end
+                get_local $acc))
+            "#;
+        let wasm_binary = wat2wasm(LOOP_WAT).unwrap();
+        let module = compile_with(&wasm_binary, &get_compiler(1_000_000)).unwrap();
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+
+        for &n in &[0u32, 1, 10, 1_000] {
+            set_points_used(&mut instance, 0u64);
+            let sum: Func<i32, i32> = instance.func("sum").unwrap();
+            assert_eq!(sum.call(n as i32), Ok((n * n.saturating_sub(1) / 2) as i32));
+            // The block and the loop cost a point each, every iteration 13, and the last check
+            // of the condition and the return 6.
+            assert_eq!(get_points_used(&instance), 13 * n as u64 + 8);
+        }
+    }
+
+    // The compiled code refers to the handlers of its breakpoints by address, which wouldn't
+    // be valid in a module loaded from a cache.
+    #[cfg(any(feature = "llvm", feature = "clif"))]
+    #[test]
+    fn test_metered_module_is_not_cached() {
+        let wasm_binary = wat2wasm(WAT).unwrap();
+        let module = compile_with(&wasm_binary, &get_compiler(100)).unwrap();
+        assert!(module.cache().is_err());
+    }
+
     #[test]
     fn test_traps_after_costly_call() {
         use wasmer_runtime_core::error::RuntimeError;
@@ -139,7 +207,7 @@ mod tests {
 
         let err = result.unwrap_err();
         match err {
-            RuntimeError::Error { data } => {
+            RuntimeError::Error { data, .. } => {
                 assert!(data.downcast_ref::<ExecutionLimitExceededError>().is_some());
             }
             _ => unreachable!(),
@@ -148,4 +216,554 @@ mod tests {
         // verify it used the correct number of points
         assert_eq!(get_points_used(&instance), 109); // Used points will be slightly more than `limit` because of the way we do gas checking.
     }
+
+    #[test]
+    fn test_traps_when_out_of_fuel() {
+        use wasmer_runtime_core::error::RuntimeError;
+        let wasm_binary = wat2wasm(WAT).unwrap();
+        let module = compile_with(&wasm_binary, &get_compiler(1_000)).unwrap();
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+
+        // A new instance has all the fuel of the limit.
+        assert_eq!(instance.fuel_remaining(), Some(1_000));
+        instance.set_fuel(100);
+
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+        match add_to.call(10_000_000, 4).unwrap_err() {
+            RuntimeError::Error { data, .. } => {
+                assert!(data.downcast_ref::<OutOfFuel>().is_some());
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(instance.fuel_remaining(), Some(0));
+
+        // Refueling makes the instance usable again.
+        instance.set_fuel(100);
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+        assert_eq!(add_to.call(3, 4), Ok(7));
+        assert_eq!(instance.fuel_remaining(), Some(26));
+    }
+
+    #[test]
+    fn test_fuel_past_the_compiled_limit() {
+        use wasmer_runtime_core::error::RuntimeError;
+        let wasm_binary = wat2wasm(WAT).unwrap();
+        let module = compile_with(&wasm_binary, &get_compiler(100)).unwrap();
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+
+        instance.set_fuel(10_000);
+        assert_eq!(instance.fuel_remaining(), Some(10_000));
+
+        // `add_to(x, _)` costs 21 points per iteration, and 11 more.
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+        assert_eq!(add_to.call(3, 4), Ok(7));
+        assert_eq!(instance.fuel_remaining(), Some(9_926));
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+        assert_eq!(add_to.call(100, 4), Ok(4_954));
+        assert_eq!(instance.fuel_remaining(), Some(7_815));
+
+        // The instance still runs out of the fuel it was given.
+        instance.set_fuel(150);
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+        match add_to.call(100, 4).unwrap_err() {
+            RuntimeError::Error { data, .. } => {
+                assert!(data.downcast_ref::<OutOfFuel>().is_some());
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(instance.fuel_remaining(), Some(0));
+    }
+
+    #[test]
+    fn test_fuel_handler_resumes_execution() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+        use wasmer_runtime_core::vm::Ctx;
+        let wasm_binary = wat2wasm(WAT).unwrap();
+        let module = compile_with(&wasm_binary, &get_compiler(1_000)).unwrap();
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+
+        let refuels = Arc::new(AtomicUsize::new(0));
+        let handler_refuels = refuels.clone();
+        instance.set_fuel(100);
+        instance.set_fuel_handler(Some(Arc::new(move |_: &mut Ctx| {
+            handler_refuels.fetch_add(1, Ordering::SeqCst);
+            Some(100)
+        })));
+
+        // The call runs out of fuel many times, and resumes every time.
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+        assert_eq!(add_to.call(1_000, 4), Ok(499_504));
+        assert!(refuels.load(Ordering::SeqCst) > 1);
+    }
+
+    #[test]
+    fn test_traps_past_max_call_depth() {
+        use wasmer_runtime_core::error::RuntimeError;
+        static RECURSIVE_WAT: &'static str = r#"
+            (module
+              (func $depth (export "depth") (param i32) (result i32)
+                get_local 0
+                i32.eqz
+                if (result i32)
+                  i32.const 1
+                  return
+                else
+                  get_local 0
+                  i32.const 1
+                  i32.sub
+                  call $depth
+                  i32.const 1
+                  i32.add
+                end)
+              (func $forever (export "forever")
+                call $forever))
+            "#;
+        let wasm_binary = wat2wasm(RECURSIVE_WAT).unwrap();
+        let module =
+            compile_with(&wasm_binary, &get_compiler_with(|| CallDepth::new(1_000))).unwrap();
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+
+        instance.set_max_call_depth(100);
+
+        let depth: Func<i32, i32> = instance.func("depth").unwrap();
+        assert_eq!(depth.call(99), Ok(100));
+        match depth.call(100).unwrap_err() {
+            RuntimeError::Error { data, .. } => {
+                assert!(data.downcast_ref::<CallStackExhausted>().is_some());
+            }
+            _ => unreachable!(),
+        }
+
+        let forever: Func<(), ()> = instance.func("forever").unwrap();
+        match forever.call().unwrap_err() {
+            RuntimeError::Error { data, .. } => {
+                assert!(data.downcast_ref::<CallStackExhausted>().is_some());
+            }
+            _ => unreachable!(),
+        }
+
+        // The frames left by the traps don't count against later calls.
+        assert_eq!(depth.call(99), Ok(100));
+
+        // Without a runtime limit, the depth given to the middleware applies.
+        let module = compile_with(&wasm_binary, &get_compiler_with(|| CallDepth::new(10))).unwrap();
+        let instance = module.instantiate(&imports! {}).unwrap();
+        let depth: Func<i32, i32> = instance.func("depth").unwrap();
+        assert_eq!(depth.call(9), Ok(10));
+        assert!(depth.call(10).is_err());
+    }
+
+    static FLOAT_WAT: &'static str = r#"
+        (module
+          (func (export "add") (param i32 i32) (result i32)
+            get_local 0
+            get_local 1
+            i32.add)
+          (func (export "half") (param f64) (result f64)
+            get_local 0
+            f64.const 2
+            f64.div))
+        "#;
+
+    #[test]
+    fn test_opcode_filter_rejects_forbidden_instructions() {
+        let wasm_binary = wat2wasm(FLOAT_WAT).unwrap();
+
+        let compiler = get_compiler_with(|| OpcodeFilter::deny(&[OpcodeClass::Float]));
+        assert!(compile_with(&wasm_binary, &compiler).is_err());
+
+        let compiler = get_compiler_with(|| OpcodeFilter::allow(&[OpcodeClass::Float]));
+        assert!(compile_with(&wasm_binary, &compiler).is_ok());
+
+        let compiler = get_compiler_with(|| {
+            OpcodeFilter::deny(&[OpcodeClass::Simd, OpcodeClass::IndirectCall])
+        });
+        assert!(compile_with(&wasm_binary, &compiler).is_ok());
+    }
+
+    #[test]
+    fn test_opcode_filter_traps_on_forbidden_instructions() {
+        use wasmer_runtime_core::error::RuntimeError;
+        let wasm_binary = wat2wasm(FLOAT_WAT).unwrap();
+        let compiler = get_compiler_with(|| OpcodeFilter::deny(&[OpcodeClass::Float]).trap());
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+        let instance = module.instantiate(&imports! {}).unwrap();
+
+        let add: Func<(i32, i32), i32> = instance.func("add").unwrap();
+        assert_eq!(add.call(1, 2), Ok(3));
+
+        let half: Func<f64, f64> = instance.func("half").unwrap();
+        match half.call(1.0).unwrap_err() {
+            RuntimeError::Error { data, .. } => {
+                assert_eq!(
+                    data.downcast_ref::<ForbiddenOpcode>(),
+                    Some(&ForbiddenOpcode {
+                        class: OpcodeClass::Float
+                    })
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Traced {
+        Enter(u32, Vec<Value>),
+        Exit(u32),
+    }
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Traced>>);
+
+    impl CallTraceHandler for Recorder {
+        fn enter(&self, _ctx: Option<&mut Ctx>, func_index: FuncIndex, args: &[Value]) {
+            let func_index = func_index.index() as u32;
+            self.0
+                .lock()
+                .unwrap()
+                .push(Traced::Enter(func_index, args.to_vec()));
+        }
+
+        fn exit(&self, _ctx: Option<&mut Ctx>, func_index: FuncIndex) {
+            let func_index = func_index.index() as u32;
+            self.0.lock().unwrap().push(Traced::Exit(func_index));
+        }
+    }
+
+    #[test]
+    fn test_call_trace_reports_entries_and_exits() {
+        use wasmer_runtime_core::structures::TypedIndex;
+        static TRACED_WAT: &'static str = r#"
+            (module
+              (func $scale (param f64 i32) (result f64)
+                get_local 1
+                i32.eqz
+                if
+                  f64.const 0
+                  return
+                end
+                get_local 0
+                get_local 1
+                f64.convert_s/i32
+                f64.mul)
+              (func (export "run") (param i32) (result f64)
+                f64.const 1.5
+                get_local 0
+                call $scale))
+            "#;
+        let wasm_binary = wat2wasm(TRACED_WAT).unwrap();
+        let recorder = Arc::new(Recorder::default());
+        let compiler =
+            get_compiler_with(|| CallTrace::with_handler(recorder.clone()).capture_args());
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+        let instance = module.instantiate(&imports! {}).unwrap();
+
+        let run: Func<i32, f64> = instance.func("run").unwrap();
+        assert_eq!(run.call(2), Ok(3.0));
+        assert_eq!(run.call(0), Ok(0.0));
+
+        let traced = recorder.0.lock().unwrap();
+        assert_eq!(
+            *traced,
+            [
+                Traced::Enter(1, vec![Value::I32(2)]),
+                Traced::Enter(0, vec![Value::F64(1.5), Value::I32(2)]),
+                Traced::Exit(0),
+                Traced::Exit(1),
+                Traced::Enter(1, vec![Value::I32(0)]),
+                Traced::Enter(0, vec![Value::F64(1.5), Value::I32(0)]),
+                Traced::Exit(0),
+                Traced::Exit(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_call_trace_captures_every_argument() {
+        static MANY_ARGS_WAT: &'static str = r#"
+            (module
+              (func (export "last")
+                (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i64) (result i64)
+                get_local 9))
+            "#;
+        let wasm_binary = wat2wasm(MANY_ARGS_WAT).unwrap();
+        let recorder = Arc::new(Recorder::default());
+        let compiler =
+            get_compiler_with(|| CallTrace::with_handler(recorder.clone()).capture_args());
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+        let instance = module.instantiate(&imports! {}).unwrap();
+
+        let args: Vec<_> = (0..9).map(Value::I32).chain(vec![Value::I64(9)]).collect();
+        assert_eq!(instance.call("last", &args).unwrap(), [Value::I64(9)]);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [Traced::Enter(0, args), Traced::Exit(0)]
+        );
+    }
+
+    #[test]
+    fn test_coverage_counts_blocks() {
+        static BRANCHY_WAT: &'static str = r#"
+            (module
+              (func (export "abs") (param i32) (result i32)
+                get_local 0
+                i32.const 0
+                i32.lt_s
+                if (result i32)
+                  i32.const 0
+                  get_local 0
+                  i32.sub
+                else
+                  get_local 0
+                end))
+            "#;
+        let wasm_binary = wat2wasm(BRANCHY_WAT).unwrap();
+        let map = CoverageMap::new();
+        let module = compile_with(
+            &wasm_binary,
+            &get_compiler_with(|| Coverage::new(map.clone())),
+        )
+        .unwrap();
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+
+        // The function entry, both branches of the `if`, and the code after it.
+        let offsets = map.offsets();
+        assert_eq!(offsets.len(), 4);
+        assert!(offsets.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let mut counters = CoverageCounters::new(map.clone());
+        counters.run(&mut instance, |instance| {
+            let abs: Func<i32, i32> = instance.func("abs").unwrap();
+            assert_eq!(abs.call(-3), Ok(3));
+            assert_eq!(abs.call(-4), Ok(4));
+            assert_eq!(abs.call(5), Ok(5));
+        });
+
+        // Calls made without the counters attached aren't counted.
+        let abs: Func<i32, i32> = instance.func("abs").unwrap();
+        assert_eq!(abs.call(6), Ok(6));
+
+        let counts: Vec<_> = counters
+            .report()
+            .into_iter()
+            .map(|(_, count)| count)
+            .collect();
+        assert_eq!(counts, [3, 2, 1, 3]);
+
+        counters.reset();
+        assert!(counters.report().iter().all(|&(_, count)| count == 0));
+
+        // Counters made for another module still hold every counter of this one.
+        let mut counters = CoverageCounters::new(CoverageMap::new());
+        counters.run(&mut instance, |instance| {
+            let abs: Func<i32, i32> = instance.func("abs").unwrap();
+            assert_eq!(abs.call(-3), Ok(3));
+        });
+        assert!(counters.report().is_empty());
+    }
+
+    #[test]
+    fn test_profile_counts_entries_and_back_edges() {
+        use wasmer_runtime_core::structures::TypedIndex;
+        let wasm_binary = wat2wasm(WAT).unwrap();
+        let module = compile_with(&wasm_binary, &get_compiler_with(Profiler::new)).unwrap();
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+
+        let mut counters = ProfileCounters::new();
+        counters.run(&mut instance, |instance| {
+            let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+            assert_eq!(add_to.call(3, 4), Ok(7));
+            assert_eq!(add_to.call(5, 4), Ok(14));
+        });
+
+        // `add_to` loops once more than its first argument, and branches back to the start of
+        // its loop once per iteration.
+        assert_eq!(
+            counters.profile()[0],
+            FunctionProfile {
+                func_index: FuncIndex::new(0),
+                entries: 2,
+                back_edges: 8,
+            }
+        );
+    }
+
+    /// Counts the calls to each function in an internal field, and hits a breakpoint from the
+    /// `threshold`th call on.
+    struct CallThreshold {
+        threshold: u64,
+        hits: Arc<AtomicUsize>,
+    }
+
+    static CALL_COUNT_FIELD: InternalField = InternalField::allocate();
+
+    impl FunctionMiddleware for CallThreshold {
+        type Error = String;
+        fn feed_event<'a, 'b: 'a>(
+            &mut self,
+            op: Event<'a, 'b>,
+            _module_info: &ModuleInfo,
+            sink: &mut EventSink<'a, 'b>,
+        ) -> Result<(), Self::Error> {
+            let is_begin = match op {
+                Event::Internal(InternalEvent::FunctionBegin(_)) => true,
+                _ => false,
+            };
+            sink.push(op);
+            if is_begin {
+                let index = CALL_COUNT_FIELD.index() as u32;
+                let hits = self.hits.clone();
+                sink.push(Event::Internal(InternalEvent::AddInternal(index, 1)));
+                sink.push(Event::Internal(InternalEvent::BreakpointIfInternalAtLeast(
+                    index,
+                    self.threshold,
+                    Box::new(move |_| {
+                        hits.fetch_add(1, Ordering::SeqCst);
+                        Ok(())
+                    }),
+                )));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_breakpoint_if_internal_at_least() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let compiler = {
+            let hits = hits.clone();
+            get_compiler_with_chain(move || {
+                let mut chain = MiddlewareChain::new();
+                chain.push(CallThreshold {
+                    threshold: 3,
+                    hits: hits.clone(),
+                });
+                chain
+            })
+        };
+        let wasm_binary = wat2wasm(WAT).unwrap();
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+        drop(compiler);
+        let instance = module.instantiate(&imports! {}).unwrap();
+
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+        for _ in 0..5 {
+            assert_eq!(add_to.call(3, 4), Ok(7));
+        }
+        assert_eq!(instance.get_internal(&CALL_COUNT_FIELD), 5);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        // The handlers are owned by the compiled module, rather than leaked.
+        drop(instance);
+        drop(module);
+        assert_eq!(Arc::strong_count(&hits), 1);
+    }
+}
+
+#[cfg(all(test, feature = "managed"))]
+mod tiering_tests {
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::time::{Duration, Instant};
+    use wabt::wat2wasm;
+    use wasmer_middleware_common::entry_counter::{get_entry_count_ctx, EntryCounter};
+    use wasmer_runtime_core::backend::{Compiler, CompilerConfig};
+    use wasmer_runtime_core::codegen::{MiddlewareChain, StreamingCompiler};
+    use wasmer_runtime_core::tiering::{
+        run_tiering, HotnessCounter, HotnessThreshold, ShellExitOperation,
+    };
+    use wasmer_runtime_core::vm::Ctx;
+    use wasmer_runtime_core::{compile_with_config, func, imports, Func};
+    use wasmer_singlepass_backend::{ModuleCodeGenerator as SinglePassMCG, SinglePassCompiler};
+
+    // `$work` is entered once per iteration, until `tiered_up` returns 1.
+    static LOOP_WAT: &'static str = r#"
+        (module
+          (import "env" "tiered_up" (func $tiered_up (result i32)))
+          (func $work (result i32)
+            call $tiered_up)
+          (func (export "_start")
+            (block
+              (loop
+                call $work
+                br_if 1
+                br 0))))
+        "#;
+
+    static LAST_ENTRY_COUNT: AtomicU64 = AtomicU64::new(0);
+    static TIMED_OUT: AtomicBool = AtomicBool::new(false);
+
+    fn get_entry_counting_compiler() -> impl Compiler {
+        let c: StreamingCompiler<SinglePassMCG, _, _, _, _> = StreamingCompiler::new(|| {
+            let mut chain = MiddlewareChain::new();
+            chain.push(EntryCounter);
+            chain.push(HotnessCounter::new(100));
+            chain
+        });
+        c
+    }
+
+    #[test]
+    fn test_tier_up_once_hot() {
+        // `$work` gets hot and is switched over alone, while `_start` keeps running its loop in
+        // the baseline code. The optimized code doesn't count entries, so the count stops
+        // growing once `$work` runs it.
+        let start_time = Instant::now();
+        let tiered_up = move |ctx: &mut Ctx| -> i32 {
+            let count = get_entry_count_ctx(ctx);
+            if count == LAST_ENTRY_COUNT.swap(count, Ordering::SeqCst) {
+                return 1;
+            }
+            if start_time.elapsed() > Duration::from_secs(60) {
+                TIMED_OUT.store(true, Ordering::SeqCst);
+                return 1;
+            }
+            0
+        };
+
+        let wasm_binary = wat2wasm(LOOP_WAT).unwrap();
+        let module = compile_with_config(
+            &wasm_binary,
+            &get_entry_counting_compiler(),
+            CompilerConfig {
+                track_state: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let import_object = imports! {
+            "env" => {
+                "tiered_up" => func!(tiered_up),
+            },
+        };
+        let mut instance = module.instantiate(&import_object).unwrap();
+        let start_raw: extern "C" fn(&mut Ctx) = {
+            let start: Func<(), ()> = instance.func("_start").unwrap();
+            unsafe { std::mem::transmute(start.get_vm_func()) }
+        };
+
+        unsafe {
+            run_tiering(
+                module.info(),
+                &wasm_binary,
+                None,
+                start_raw,
+                &mut instance,
+                vec![Box::new(|| -> Box<dyn Compiler> {
+                    Box::new(SinglePassCompiler::new())
+                })],
+                Some(HotnessThreshold { entries: 1000 }),
+                |_| -> ShellExitOperation { panic!("the instance was interrupted") },
+            )
+            .unwrap();
+        }
+
+        assert!(
+            !TIMED_OUT.load(Ordering::SeqCst),
+            "the instance never tiered up"
+        );
+        assert!(LAST_ENTRY_COUNT.load(Ordering::SeqCst) >= 1000);
+    }
 }
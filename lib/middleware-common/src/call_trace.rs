@@ -1,28 +1,182 @@
+use std::{cell::RefCell, mem, sync::Arc};
 use wasmer_runtime_core::{
-    codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
+    codegen::{BreakpointInfo, Event, EventSink, FunctionExits, FunctionMiddleware, InternalEvent},
     module::ModuleInfo,
+    structures::TypedIndex,
+    types::{FuncIndex, Type, Value},
+    vm::{Ctx, InternalField},
+    wasmparser::Operator,
 };
 
-pub struct CallTrace;
+/// Each argument is carried from the wasm stack through this internal field, to a breakpoint
+/// collecting it.
+static ARG_FIELD: InternalField = InternalField::allocate();
+
+thread_local! {
+    /// The arguments collected so far for the function being entered on this thread.
+    static ARGS: RefCell<Vec<Value>> = RefCell::new(Vec::new());
+}
+
+/// Receives the function entries and exits traced by `CallTrace`.
+///
+/// The hooks run on the guest stack, in the middle of the traced function. `ctx` is the context
+/// of the running instance, if the backend knows it.
+pub trait CallTraceHandler: Send + Sync {
+    /// Called when the function at `func_index` is entered, with its first arguments if
+    /// arguments are captured.
+    fn enter(&self, ctx: Option<&mut Ctx>, func_index: FuncIndex, args: &[Value]);
+
+    /// Called when the function at `func_index` returns. Traps and unwinds aren't reported.
+    fn exit(&self, ctx: Option<&mut Ctx>, func_index: FuncIndex) {
+        let _ = (ctx, func_index);
+    }
+}
+
+/// Prints the entered functions to stderr.
+struct PrintEntries;
+
+impl CallTraceHandler for PrintEntries {
+    fn enter(&self, _ctx: Option<&mut Ctx>, func_index: FuncIndex, _args: &[Value]) {
+        eprintln!("func ({})", func_index.index());
+    }
+}
+
+/// CallTrace is a compiler middleware that calls hooks when local functions are entered and
+/// when they return.
+///
+/// The exits of a function are found by `FunctionExits`, so that its exit is traced whether it
+/// returns with `return`, with a branch to its outermost label, or by falling through its end.
+/// Capturing arguments stops at the first `v128` argument.
+pub struct CallTrace {
+    handler: Arc<dyn CallTraceHandler>,
+    capture_args: bool,
+    func_index: FuncIndex,
+    exits: FunctionExits,
+}
+
+impl CallTrace {
+    /// Creates a middleware printing the index of every entered function to stderr.
+    pub fn new() -> CallTrace {
+        CallTrace::with_handler(Arc::new(PrintEntries))
+    }
+
+    /// Creates a middleware reporting the function entries and exits to `handler`, without
+    /// capturing arguments.
+    pub fn with_handler(handler: Arc<dyn CallTraceHandler>) -> CallTrace {
+        CallTrace {
+            handler,
+            capture_args: false,
+            func_index: FuncIndex::new(0),
+            exits: FunctionExits::new(),
+        }
+    }
+
+    /// Passes the arguments of the entered functions to the enter hook.
+    pub fn capture_args(mut self) -> CallTrace {
+        self.capture_args = true;
+        self
+    }
+}
+
+impl Default for CallTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl FunctionMiddleware for CallTrace {
     type Error = String;
     fn feed_event<'a, 'b: 'a>(
         &mut self,
         op: Event<'a, 'b>,
-        _module_info: &ModuleInfo,
+        module_info: &ModuleInfo,
         sink: &mut EventSink<'a, 'b>,
     ) -> Result<(), Self::Error> {
         match op {
-            Event::Internal(InternalEvent::FunctionBegin(id)) => sink.push(Event::Internal(
-                InternalEvent::Breakpoint(Box::new(move |_| {
-                    eprintln!("func ({})", id);
-                    Ok(())
-                })),
-            )),
-            _ => {}
+            Event::Internal(InternalEvent::FunctionBegin(id)) => {
+                self.func_index =
+                    FuncIndex::new(module_info.imported_functions.len() + id as usize);
+                let sig = &module_info.signatures[module_info.func_assoc[self.func_index]];
+                sink.push(op);
+
+                if self.capture_args {
+                    for (i, &ty) in sig.params().iter().enumerate() {
+                        if ty == Type::V128 {
+                            break;
+                        }
+                        push_capture(sink, i, ty);
+                    }
+                }
+                let handler = self.handler.clone();
+                let func_index = self.func_index;
+                sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
+                    move |info: BreakpointInfo| {
+                        let args = ARGS.with(|args| mem::replace(&mut *args.borrow_mut(), vec![]));
+                        handler.enter(info.ctx, func_index, &args);
+                        Ok(())
+                    },
+                ))));
+
+                self.exits.begin(self.func_index, module_info, sink);
+            }
+            op => {
+                let handler = &self.handler;
+                let func_index = self.func_index;
+                self.exits
+                    .push(op, sink, move || exit_event(handler.clone(), func_index));
+            }
         }
-        sink.push(op);
         Ok(())
     }
 }
+
+fn exit_event<'a, 'b: 'a>(
+    handler: Arc<dyn CallTraceHandler>,
+    func_index: FuncIndex,
+) -> Event<'a, 'b> {
+    Event::Internal(InternalEvent::Breakpoint(Box::new(
+        move |info: BreakpointInfo| {
+            handler.exit(info.ctx, func_index);
+            Ok(())
+        },
+    )))
+}
+
+/// Pushes the operators storing the argument `index`, as 64 bits, into the argument field, and
+/// a breakpoint adding it to the arguments of the entered function.
+fn push_capture<'a, 'b: 'a>(sink: &mut EventSink<'a, 'b>, index: usize, ty: Type) {
+    sink.push(Event::WasmOwned(Operator::GetLocal {
+        local_index: index as u32,
+    }));
+    match ty {
+        Type::I32 => sink.push(Event::WasmOwned(Operator::I64ExtendUI32)),
+        Type::F32 => {
+            sink.push(Event::WasmOwned(Operator::I32ReinterpretF32));
+            sink.push(Event::WasmOwned(Operator::I64ExtendUI32));
+        }
+        Type::F64 => sink.push(Event::WasmOwned(Operator::I64ReinterpretF64)),
+        Type::I64 | Type::V128 => {}
+    }
+    sink.push(Event::Internal(InternalEvent::SetInternal(
+        ARG_FIELD.index() as _,
+    )));
+    sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
+        move |info: BreakpointInfo| {
+            if let Some(ctx) = info.ctx {
+                let arg = value_from_bits(ty, ctx.get_internal(&ARG_FIELD));
+                ARGS.with(|args| args.borrow_mut().push(arg));
+            }
+            Ok(())
+        },
+    ))));
+}
+
+fn value_from_bits(ty: Type, bits: u64) -> Value {
+    match ty {
+        Type::I32 => Value::I32(bits as u32 as i32),
+        Type::I64 => Value::I64(bits as i64),
+        Type::F32 => Value::F32(f32::from_bits(bits as u32)),
+        Type::F64 => Value::F64(f64::from_bits(bits)),
+        Type::V128 => Value::V128(bits as u128),
+    }
+}
@@ -0,0 +1,29 @@
+use wasmer_runtime_core::{vm::InternalField, Instance};
+
+/// Runs `f` with `counters` attached to `instance` through `field`, so that the code of the
+/// instance increments them, and detaches them afterwards.
+///
+/// `counters` must hold every counter the code of the instance increments through `field`.
+pub(crate) fn run_attached<R>(
+    field: &'static InternalField,
+    counters: &mut [u64],
+    instance: &mut Instance,
+    f: impl FnOnce(&mut Instance) -> R,
+) -> R {
+    instance.set_internal(field, counters.as_mut_ptr() as u64);
+    let mut attached = Attached { field, instance };
+    f(&mut *attached.instance)
+}
+
+/// Detaches the counters from an instance when dropped, even if the closure run with them
+/// panics, since the code would otherwise keep writing to them once they are freed.
+struct Attached<'a> {
+    field: &'static InternalField,
+    instance: &'a mut Instance,
+}
+
+impl<'a> Drop for Attached<'a> {
+    fn drop(&mut self) {
+        self.instance.set_internal(self.field, 0);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::counters::run_attached;
+use std::sync::{Arc, Mutex};
+use wasmer_runtime_core::{
+    codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
+    module::ModuleInfo,
+    vm::InternalField,
+    wasmparser::Operator,
+    Instance,
+};
+
+/// The internal field holding the address of the counters of an instance, or zero when no
+/// counters are attached.
+static INTERNAL_FIELD: InternalField = InternalField::allocate();
+
+/// The name the number of counters of a module is recorded under.
+const COUNTERS_VALUE: &str = "coverage.counters";
+
+/// The basic blocks instrumented by `Coverage` in a module, as the offsets of their first
+/// operator in the module binary.
+///
+/// A map collects the blocks of a single compilation, so a new map must be used for every
+/// module.
+#[derive(Default)]
+pub struct CoverageMap {
+    offsets: Mutex<Vec<u32>>,
+}
+
+impl CoverageMap {
+    /// Creates an empty map, to be filled by compiling a module with `Coverage`.
+    pub fn new() -> Arc<CoverageMap> {
+        Arc::new(CoverageMap::default())
+    }
+
+    /// Returns the offsets of the instrumented blocks, in the order of their counters.
+    pub fn offsets(&self) -> Vec<u32> {
+        self.offsets.lock().unwrap().clone()
+    }
+
+    fn add_block(&self, offset: u32) -> u32 {
+        let mut offsets = self.offsets.lock().unwrap();
+        offsets.push(offset);
+        (offsets.len() - 1) as u32
+    }
+}
+
+/// Coverage is a compiler middleware that counts how many times each basic block of a module
+/// is entered.
+///
+/// A block starts at the entry of a function and after every instruction that can transfer
+/// control. The counters of an instance are only updated while `CoverageCounters::run` is
+/// running it; the rest of the time the instrumentation is a check of an internal field.
+pub struct Coverage {
+    map: Arc<CoverageMap>,
+    block_start: bool,
+}
+
+impl Coverage {
+    /// Creates a middleware recording the instrumented blocks in `map`.
+    pub fn new(map: Arc<CoverageMap>) -> Coverage {
+        Coverage {
+            map,
+            block_start: false,
+        }
+    }
+}
+
+impl FunctionMiddleware for Coverage {
+    type Error = String;
+    fn feed_event<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        _module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+    ) -> Result<(), Self::Error> {
+        match op {
+            Event::Internal(InternalEvent::FunctionBegin(_)) => {
+                self.block_start = true;
+            }
+            Event::Wasm(&ref op) | Event::WasmOwned(ref op) => {
+                if self.block_start {
+                    self.block_start = false;
+                    let counter = self.map.add_block(sink.source_offset());
+                    sink.push(Event::Internal(InternalEvent::IncrementCounter(
+                        INTERNAL_FIELD.index() as _,
+                        counter,
+                    )));
+                }
+                match *op {
+                    Operator::Loop { .. }
+                    | Operator::If { .. }
+                    | Operator::Else
+                    | Operator::End
+                    | Operator::Br { .. }
+                    | Operator::BrIf { .. }
+                    | Operator::BrTable { .. }
+                    | Operator::Return
+                    | Operator::Unreachable => {
+                        self.block_start = true;
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        sink.push(op);
+        Ok(())
+    }
+
+    fn finalize_module(&mut self, module_info: &mut ModuleInfo) {
+        let counters = self.map.offsets.lock().unwrap().len();
+        module_info
+            .middleware_values
+            .insert(COUNTERS_VALUE.to_string(), counters as u64);
+    }
+}
+
+/// The block counters of an instance compiled with `Coverage`.
+pub struct CoverageCounters {
+    map: Arc<CoverageMap>,
+    counters: Vec<u64>,
+}
+
+impl CoverageCounters {
+    /// Creates zeroed counters for the blocks of `map`.
+    pub fn new(map: Arc<CoverageMap>) -> CoverageCounters {
+        let counters = vec![0; map.offsets.lock().unwrap().len()];
+        CoverageCounters { map, counters }
+    }
+
+    /// Runs `f` with these counters attached to `instance`, so that the blocks it executes
+    /// are counted. Nothing is counted if the module of `instance` wasn't compiled with
+    /// `Coverage`.
+    pub fn run<R>(&mut self, instance: &mut Instance, f: impl FnOnce(&mut Instance) -> R) -> R {
+        // The code of the module increments counters up to the number it was compiled with,
+        // whatever map the counters were created for.
+        let module = instance.module();
+        let len = match module.info().middleware_values.get(COUNTERS_VALUE) {
+            Some(&len) => len as usize,
+            None => return f(instance),
+        };
+        if self.counters.len() < len {
+            self.counters.resize(len, 0);
+        }
+        run_attached(&INTERNAL_FIELD, &mut self.counters, instance, f)
+    }
+
+    /// Returns the offset of each block in the module binary, with the number of times it
+    /// was entered.
+    pub fn report(&self) -> Vec<(u32, u64)> {
+        self.map
+            .offsets()
+            .into_iter()
+            .zip(self.counters.iter().cloned().chain(std::iter::repeat(0)))
+            .collect()
+    }
+
+    /// Sets all the counters back to zero.
+    pub fn reset(&mut self) {
+        for counter in &mut self.counters {
+            *counter = 0;
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use wasmer_runtime_core::{
+    codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
+    module::ModuleInfo,
+    vm::{Ctx, InternalField},
+    Instance,
+};
+
+static INTERNAL_FIELD: InternalField = InternalField::allocate();
+
+/// EntryCounter is a compiler middleware that counts how many times local functions are entered.
+///
+/// The counter is shared by all functions of an instance. The tiering controller counts the
+/// entries of each function with `tiering::HotnessCounter` instead.
+pub struct EntryCounter;
+
+impl FunctionMiddleware for EntryCounter {
+    type Error = String;
+    fn feed_event<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        _module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+    ) -> Result<(), Self::Error> {
+        let is_function_begin = match op {
+            Event::Internal(InternalEvent::FunctionBegin(_)) => true,
+            _ => false,
+        };
+        sink.push(op);
+        if is_function_begin {
+            sink.push(Event::Internal(InternalEvent::AddInternal(
+                INTERNAL_FIELD.index() as _,
+                1,
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the internal field that holds the function entry count.
+pub fn entry_count_field() -> &'static InternalField {
+    &INTERNAL_FIELD
+}
+
+/// Returns the number of function entries recorded for an Instance.
+pub fn get_entry_count(instance: &Instance) -> u64 {
+    instance.get_internal(&INTERNAL_FIELD)
+}
+
+/// Returns the number of function entries recorded in a Ctx.
+pub fn get_entry_count_ctx(ctx: &Ctx) -> u64 {
+    ctx.get_internal(&INTERNAL_FIELD)
+}
@@ -11,4 +11,9 @@
 #![doc(html_logo_url = "https://avatars3.githubusercontent.com/u/44205449?s=200&v=4")]
 
 pub mod call_trace;
+mod counters;
+pub mod coverage;
+pub mod entry_counter;
 pub mod metering;
+pub mod opcode_filter;
+pub mod profiling;
@@ -0,0 +1,498 @@
+use std::fmt;
+use wasmer_runtime_core::{
+    codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
+    module::ModuleInfo,
+    wasmparser::Operator,
+};
+
+/// A class of WebAssembly instructions that an `OpcodeFilter` can forbid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OpcodeClass {
+    /// Instructions operating on or producing `f32` and `f64` values, including conversions,
+    /// reinterpretations, float loads and stores, and float SIMD lanes.
+    Float,
+    /// Instructions of the SIMD proposal.
+    Simd,
+    /// Atomic memory accesses, fences, waits and wakes of the threads proposal.
+    Atomic,
+    /// `call_indirect`.
+    IndirectCall,
+}
+
+impl OpcodeClass {
+    /// All the instruction classes.
+    pub const ALL: [OpcodeClass; 4] = [
+        OpcodeClass::Float,
+        OpcodeClass::Simd,
+        OpcodeClass::Atomic,
+        OpcodeClass::IndirectCall,
+    ];
+
+    /// Returns whether `op` belongs to this class.
+    pub fn contains(self, op: &Operator) -> bool {
+        match self {
+            OpcodeClass::Float => is_float(op),
+            OpcodeClass::Simd => is_simd(op),
+            OpcodeClass::Atomic => is_atomic(op),
+            OpcodeClass::IndirectCall => match *op {
+                Operator::CallIndirect { .. } => true,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Whether `op` operates on or produces `f32` or `f64` values.
+fn is_float(op: &Operator) -> bool {
+    match *op {
+        Operator::F32Abs
+        | Operator::F32Add
+        | Operator::F32Ceil
+        | Operator::F32Const { .. }
+        | Operator::F32ConvertSI32
+        | Operator::F32ConvertSI64
+        | Operator::F32ConvertUI32
+        | Operator::F32ConvertUI64
+        | Operator::F32Copysign
+        | Operator::F32DemoteF64
+        | Operator::F32Div
+        | Operator::F32Eq
+        | Operator::F32Floor
+        | Operator::F32Ge
+        | Operator::F32Gt
+        | Operator::F32Le
+        | Operator::F32Load { .. }
+        | Operator::F32Lt
+        | Operator::F32Max
+        | Operator::F32Min
+        | Operator::F32Mul
+        | Operator::F32Ne
+        | Operator::F32Nearest
+        | Operator::F32Neg
+        | Operator::F32ReinterpretI32
+        | Operator::F32Sqrt
+        | Operator::F32Store { .. }
+        | Operator::F32Sub
+        | Operator::F32Trunc
+        | Operator::F32x4Abs
+        | Operator::F32x4Add
+        | Operator::F32x4ConvertSI32x4
+        | Operator::F32x4ConvertUI32x4
+        | Operator::F32x4Div
+        | Operator::F32x4Eq
+        | Operator::F32x4ExtractLane { .. }
+        | Operator::F32x4Ge
+        | Operator::F32x4Gt
+        | Operator::F32x4Le
+        | Operator::F32x4Lt
+        | Operator::F32x4Max
+        | Operator::F32x4Min
+        | Operator::F32x4Mul
+        | Operator::F32x4Ne
+        | Operator::F32x4Neg
+        | Operator::F32x4ReplaceLane { .. }
+        | Operator::F32x4Splat
+        | Operator::F32x4Sqrt
+        | Operator::F32x4Sub
+        | Operator::F64Abs
+        | Operator::F64Add
+        | Operator::F64Ceil
+        | Operator::F64Const { .. }
+        | Operator::F64ConvertSI32
+        | Operator::F64ConvertSI64
+        | Operator::F64ConvertUI32
+        | Operator::F64ConvertUI64
+        | Operator::F64Copysign
+        | Operator::F64Div
+        | Operator::F64Eq
+        | Operator::F64Floor
+        | Operator::F64Ge
+        | Operator::F64Gt
+        | Operator::F64Le
+        | Operator::F64Load { .. }
+        | Operator::F64Lt
+        | Operator::F64Max
+        | Operator::F64Min
+        | Operator::F64Mul
+        | Operator::F64Ne
+        | Operator::F64Nearest
+        | Operator::F64Neg
+        | Operator::F64PromoteF32
+        | Operator::F64ReinterpretI64
+        | Operator::F64Sqrt
+        | Operator::F64Store { .. }
+        | Operator::F64Sub
+        | Operator::F64Trunc
+        | Operator::F64x2Abs
+        | Operator::F64x2Add
+        | Operator::F64x2ConvertSI64x2
+        | Operator::F64x2ConvertUI64x2
+        | Operator::F64x2Div
+        | Operator::F64x2Eq
+        | Operator::F64x2ExtractLane { .. }
+        | Operator::F64x2Ge
+        | Operator::F64x2Gt
+        | Operator::F64x2Le
+        | Operator::F64x2Lt
+        | Operator::F64x2Max
+        | Operator::F64x2Min
+        | Operator::F64x2Mul
+        | Operator::F64x2Ne
+        | Operator::F64x2Neg
+        | Operator::F64x2ReplaceLane { .. }
+        | Operator::F64x2Splat
+        | Operator::F64x2Sqrt
+        | Operator::F64x2Sub
+        | Operator::I32ReinterpretF32
+        | Operator::I32TruncSF32
+        | Operator::I32TruncSF64
+        | Operator::I32TruncSSatF32
+        | Operator::I32TruncSSatF64
+        | Operator::I32TruncUF32
+        | Operator::I32TruncUF64
+        | Operator::I32TruncUSatF32
+        | Operator::I32TruncUSatF64
+        | Operator::I32x4TruncSF32x4Sat
+        | Operator::I32x4TruncUF32x4Sat
+        | Operator::I64ReinterpretF64
+        | Operator::I64TruncSF32
+        | Operator::I64TruncSF64
+        | Operator::I64TruncSSatF32
+        | Operator::I64TruncSSatF64
+        | Operator::I64TruncUF32
+        | Operator::I64TruncUF64
+        | Operator::I64TruncUSatF32
+        | Operator::I64TruncUSatF64
+        | Operator::I64x2TruncSF64x2Sat
+        | Operator::I64x2TruncUF64x2Sat => true,
+        _ => false,
+    }
+}
+
+/// Whether `op` belongs to the SIMD proposal.
+fn is_simd(op: &Operator) -> bool {
+    match *op {
+        Operator::F32x4Abs
+        | Operator::F32x4Add
+        | Operator::F32x4ConvertSI32x4
+        | Operator::F32x4ConvertUI32x4
+        | Operator::F32x4Div
+        | Operator::F32x4Eq
+        | Operator::F32x4ExtractLane { .. }
+        | Operator::F32x4Ge
+        | Operator::F32x4Gt
+        | Operator::F32x4Le
+        | Operator::F32x4Lt
+        | Operator::F32x4Max
+        | Operator::F32x4Min
+        | Operator::F32x4Mul
+        | Operator::F32x4Ne
+        | Operator::F32x4Neg
+        | Operator::F32x4ReplaceLane { .. }
+        | Operator::F32x4Splat
+        | Operator::F32x4Sqrt
+        | Operator::F32x4Sub
+        | Operator::F64x2Abs
+        | Operator::F64x2Add
+        | Operator::F64x2ConvertSI64x2
+        | Operator::F64x2ConvertUI64x2
+        | Operator::F64x2Div
+        | Operator::F64x2Eq
+        | Operator::F64x2ExtractLane { .. }
+        | Operator::F64x2Ge
+        | Operator::F64x2Gt
+        | Operator::F64x2Le
+        | Operator::F64x2Lt
+        | Operator::F64x2Max
+        | Operator::F64x2Min
+        | Operator::F64x2Mul
+        | Operator::F64x2Ne
+        | Operator::F64x2Neg
+        | Operator::F64x2ReplaceLane { .. }
+        | Operator::F64x2Splat
+        | Operator::F64x2Sqrt
+        | Operator::F64x2Sub
+        | Operator::I16x8Add
+        | Operator::I16x8AddSaturateS
+        | Operator::I16x8AddSaturateU
+        | Operator::I16x8AllTrue
+        | Operator::I16x8AnyTrue
+        | Operator::I16x8Eq
+        | Operator::I16x8ExtractLaneS { .. }
+        | Operator::I16x8ExtractLaneU { .. }
+        | Operator::I16x8GeS
+        | Operator::I16x8GeU
+        | Operator::I16x8GtS
+        | Operator::I16x8GtU
+        | Operator::I16x8LeS
+        | Operator::I16x8LeU
+        | Operator::I16x8LoadSplat { .. }
+        | Operator::I16x8LtS
+        | Operator::I16x8LtU
+        | Operator::I16x8Mul
+        | Operator::I16x8Ne
+        | Operator::I16x8Neg
+        | Operator::I16x8ReplaceLane { .. }
+        | Operator::I16x8Shl
+        | Operator::I16x8ShrS
+        | Operator::I16x8ShrU
+        | Operator::I16x8Splat
+        | Operator::I16x8Sub
+        | Operator::I16x8SubSaturateS
+        | Operator::I16x8SubSaturateU
+        | Operator::I32x4Add
+        | Operator::I32x4AllTrue
+        | Operator::I32x4AnyTrue
+        | Operator::I32x4Eq
+        | Operator::I32x4ExtractLane { .. }
+        | Operator::I32x4GeS
+        | Operator::I32x4GeU
+        | Operator::I32x4GtS
+        | Operator::I32x4GtU
+        | Operator::I32x4LeS
+        | Operator::I32x4LeU
+        | Operator::I32x4LoadSplat { .. }
+        | Operator::I32x4LtS
+        | Operator::I32x4LtU
+        | Operator::I32x4Mul
+        | Operator::I32x4Ne
+        | Operator::I32x4Neg
+        | Operator::I32x4ReplaceLane { .. }
+        | Operator::I32x4Shl
+        | Operator::I32x4ShrS
+        | Operator::I32x4ShrU
+        | Operator::I32x4Splat
+        | Operator::I32x4Sub
+        | Operator::I32x4TruncSF32x4Sat
+        | Operator::I32x4TruncUF32x4Sat
+        | Operator::I64x2Add
+        | Operator::I64x2AllTrue
+        | Operator::I64x2AnyTrue
+        | Operator::I64x2ExtractLane { .. }
+        | Operator::I64x2LoadSplat { .. }
+        | Operator::I64x2Neg
+        | Operator::I64x2ReplaceLane { .. }
+        | Operator::I64x2Shl
+        | Operator::I64x2ShrS
+        | Operator::I64x2ShrU
+        | Operator::I64x2Splat
+        | Operator::I64x2Sub
+        | Operator::I64x2TruncSF64x2Sat
+        | Operator::I64x2TruncUF64x2Sat
+        | Operator::I8x16Add
+        | Operator::I8x16AddSaturateS
+        | Operator::I8x16AddSaturateU
+        | Operator::I8x16AllTrue
+        | Operator::I8x16AnyTrue
+        | Operator::I8x16Eq
+        | Operator::I8x16ExtractLaneS { .. }
+        | Operator::I8x16ExtractLaneU { .. }
+        | Operator::I8x16GeS
+        | Operator::I8x16GeU
+        | Operator::I8x16GtS
+        | Operator::I8x16GtU
+        | Operator::I8x16LeS
+        | Operator::I8x16LeU
+        | Operator::I8x16LoadSplat { .. }
+        | Operator::I8x16LtS
+        | Operator::I8x16LtU
+        | Operator::I8x16Mul
+        | Operator::I8x16Ne
+        | Operator::I8x16Neg
+        | Operator::I8x16ReplaceLane { .. }
+        | Operator::I8x16Shl
+        | Operator::I8x16ShrS
+        | Operator::I8x16ShrU
+        | Operator::I8x16Splat
+        | Operator::I8x16Sub
+        | Operator::I8x16SubSaturateS
+        | Operator::I8x16SubSaturateU
+        | Operator::V128And
+        | Operator::V128Bitselect
+        | Operator::V128Const { .. }
+        | Operator::V128Load { .. }
+        | Operator::V128Not
+        | Operator::V128Or
+        | Operator::V128Store { .. }
+        | Operator::V128Xor
+        | Operator::V8x16Shuffle { .. }
+        | Operator::V8x16Swizzle => true,
+        _ => false,
+    }
+}
+
+/// Whether `op` is an atomic instruction of the threads proposal.
+fn is_atomic(op: &Operator) -> bool {
+    match *op {
+        Operator::Fence { .. }
+        | Operator::I32AtomicLoad { .. }
+        | Operator::I32AtomicLoad16U { .. }
+        | Operator::I32AtomicLoad8U { .. }
+        | Operator::I32AtomicRmw16UAdd { .. }
+        | Operator::I32AtomicRmw16UAnd { .. }
+        | Operator::I32AtomicRmw16UCmpxchg { .. }
+        | Operator::I32AtomicRmw16UOr { .. }
+        | Operator::I32AtomicRmw16USub { .. }
+        | Operator::I32AtomicRmw16UXchg { .. }
+        | Operator::I32AtomicRmw16UXor { .. }
+        | Operator::I32AtomicRmw8UAdd { .. }
+        | Operator::I32AtomicRmw8UAnd { .. }
+        | Operator::I32AtomicRmw8UCmpxchg { .. }
+        | Operator::I32AtomicRmw8UOr { .. }
+        | Operator::I32AtomicRmw8USub { .. }
+        | Operator::I32AtomicRmw8UXchg { .. }
+        | Operator::I32AtomicRmw8UXor { .. }
+        | Operator::I32AtomicRmwAdd { .. }
+        | Operator::I32AtomicRmwAnd { .. }
+        | Operator::I32AtomicRmwCmpxchg { .. }
+        | Operator::I32AtomicRmwOr { .. }
+        | Operator::I32AtomicRmwSub { .. }
+        | Operator::I32AtomicRmwXchg { .. }
+        | Operator::I32AtomicRmwXor { .. }
+        | Operator::I32AtomicStore { .. }
+        | Operator::I32AtomicStore16 { .. }
+        | Operator::I32AtomicStore8 { .. }
+        | Operator::I32Wait { .. }
+        | Operator::I64AtomicLoad { .. }
+        | Operator::I64AtomicLoad16U { .. }
+        | Operator::I64AtomicLoad32U { .. }
+        | Operator::I64AtomicLoad8U { .. }
+        | Operator::I64AtomicRmw16UAdd { .. }
+        | Operator::I64AtomicRmw16UAnd { .. }
+        | Operator::I64AtomicRmw16UCmpxchg { .. }
+        | Operator::I64AtomicRmw16UOr { .. }
+        | Operator::I64AtomicRmw16USub { .. }
+        | Operator::I64AtomicRmw16UXchg { .. }
+        | Operator::I64AtomicRmw16UXor { .. }
+        | Operator::I64AtomicRmw32UAdd { .. }
+        | Operator::I64AtomicRmw32UAnd { .. }
+        | Operator::I64AtomicRmw32UCmpxchg { .. }
+        | Operator::I64AtomicRmw32UOr { .. }
+        | Operator::I64AtomicRmw32USub { .. }
+        | Operator::I64AtomicRmw32UXchg { .. }
+        | Operator::I64AtomicRmw32UXor { .. }
+        | Operator::I64AtomicRmw8UAdd { .. }
+        | Operator::I64AtomicRmw8UAnd { .. }
+        | Operator::I64AtomicRmw8UCmpxchg { .. }
+        | Operator::I64AtomicRmw8UOr { .. }
+        | Operator::I64AtomicRmw8USub { .. }
+        | Operator::I64AtomicRmw8UXchg { .. }
+        | Operator::I64AtomicRmw8UXor { .. }
+        | Operator::I64AtomicRmwAdd { .. }
+        | Operator::I64AtomicRmwAnd { .. }
+        | Operator::I64AtomicRmwCmpxchg { .. }
+        | Operator::I64AtomicRmwOr { .. }
+        | Operator::I64AtomicRmwSub { .. }
+        | Operator::I64AtomicRmwXchg { .. }
+        | Operator::I64AtomicRmwXor { .. }
+        | Operator::I64AtomicStore { .. }
+        | Operator::I64AtomicStore16 { .. }
+        | Operator::I64AtomicStore32 { .. }
+        | Operator::I64AtomicStore8 { .. }
+        | Operator::I64Wait { .. }
+        | Operator::Wake { .. } => true,
+        _ => false,
+    }
+}
+
+impl fmt::Display for OpcodeClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            OpcodeClass::Float => "float",
+            OpcodeClass::Simd => "SIMD",
+            OpcodeClass::Atomic => "atomic",
+            OpcodeClass::IndirectCall => "indirect call",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The trap raised by a forbidden instruction of a module compiled with
+/// `OpcodeFilter::trap`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ForbiddenOpcode {
+    /// The class of the instruction that was executed.
+    pub class: OpcodeClass,
+}
+
+impl fmt::Display for ForbiddenOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "executed a forbidden {} instruction", self.class)
+    }
+}
+
+impl std::error::Error for ForbiddenOpcode {}
+
+/// OpcodeFilter is a compiler middleware that forbids classes of WebAssembly instructions, e.g.
+/// floats on platforms that need deterministic execution.
+///
+/// By default a module using a forbidden instruction fails to compile. With `trap`, it compiles,
+/// and the instruction traps with `ForbiddenOpcode` when executed instead, so that modules
+/// carrying unused float code can still run.
+///
+/// Instructions the parser supports but that aren't listed in any class here are allowed.
+pub struct OpcodeFilter {
+    denied: Vec<OpcodeClass>,
+    trap: bool,
+}
+
+impl OpcodeFilter {
+    /// Creates a filter forbidding the instructions of `classes`.
+    pub fn deny(classes: &[OpcodeClass]) -> OpcodeFilter {
+        OpcodeFilter {
+            denied: classes.to_vec(),
+            trap: false,
+        }
+    }
+
+    /// Creates a filter forbidding the instructions of every class but `classes`. Instructions
+    /// that don't belong to any class, such as integer arithmetic, are always allowed.
+    pub fn allow(classes: &[OpcodeClass]) -> OpcodeFilter {
+        let denied: Vec<_> = OpcodeClass::ALL
+            .iter()
+            .cloned()
+            .filter(|class| !classes.contains(class))
+            .collect();
+        OpcodeFilter::deny(&denied)
+    }
+
+    /// Makes forbidden instructions trap when executed, instead of failing the compilation.
+    pub fn trap(mut self) -> OpcodeFilter {
+        self.trap = true;
+        self
+    }
+
+    fn classify(&self, op: &Operator) -> Option<OpcodeClass> {
+        if self.denied.is_empty() {
+            return None;
+        }
+        self.denied.iter().cloned().find(|class| class.contains(op))
+    }
+}
+
+impl FunctionMiddleware for OpcodeFilter {
+    type Error = String;
+    fn feed_event<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        _module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+    ) -> Result<(), Self::Error> {
+        let class = match op {
+            Event::Wasm(&ref op) | Event::WasmOwned(ref op) => match self.classify(op) {
+                Some(class) if !self.trap => {
+                    return Err(format!("forbidden {} instruction: {:?}", class, op));
+                }
+                class => class,
+            },
+            _ => None,
+        };
+        if let Some(class) = class {
+            sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
+                move |_| Err(Box::new(ForbiddenOpcode { class })),
+            ))));
+        }
+        sink.push(op);
+        Ok(())
+    }
+}
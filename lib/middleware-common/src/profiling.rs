@@ -0,0 +1,133 @@
+use crate::counters::run_attached;
+use wasmer_runtime_core::{
+    codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
+    module::ModuleInfo,
+    structures::TypedIndex,
+    types::FuncIndex,
+    vm::InternalField,
+    wasmparser::Operator,
+    Instance,
+};
+
+/// The internal field holding the address of the `ProfileCounters` of the instance being
+/// profiled, or zero outside of `ProfileCounters::run`.
+static INTERNAL_FIELD: InternalField = InternalField::allocate();
+
+/// Every local function has three counters: its entries, the entries into its loops, and the
+/// iterations of its loops. The back-edges are the iterations that didn't enter a loop.
+const COUNTERS_PER_FUNCTION: usize = 3;
+const ENTRIES: u32 = 0;
+const LOOP_ENTRIES: u32 = 1;
+const LOOP_ITERATIONS: u32 = 2;
+
+/// Profiler is a compiler middleware that counts how many times local functions are entered,
+/// and how many times their loops branch back to their start.
+///
+/// Function entries and loop iterations are counted while `ProfileCounters::run` runs an
+/// instance. Outside of it, each instrumented entry and loop costs a load and a branch.
+#[derive(Default)]
+pub struct Profiler {
+    func: u32,
+}
+
+impl Profiler {
+    /// Creates a profiling middleware.
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    fn increment<'a, 'b: 'a>(&self, counter: u32) -> Event<'a, 'b> {
+        Event::Internal(InternalEvent::IncrementCounter(
+            INTERNAL_FIELD.index() as _,
+            self.func * COUNTERS_PER_FUNCTION as u32 + counter,
+        ))
+    }
+}
+
+impl FunctionMiddleware for Profiler {
+    type Error = String;
+    fn feed_event<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        _module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+    ) -> Result<(), Self::Error> {
+        match op {
+            Event::Internal(InternalEvent::FunctionBegin(id)) => {
+                self.func = id;
+                sink.push(op);
+                sink.push(self.increment(ENTRIES));
+                return Ok(());
+            }
+            Event::Wasm(&Operator::Loop { .. }) | Event::WasmOwned(Operator::Loop { .. }) => {
+                sink.push(self.increment(LOOP_ENTRIES));
+                sink.push(op);
+                sink.push(self.increment(LOOP_ITERATIONS));
+                return Ok(());
+            }
+            _ => {}
+        }
+        sink.push(op);
+        Ok(())
+    }
+}
+
+/// The number of times a function was entered, and its loops branched back to their start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionProfile {
+    /// The index of the function in the module.
+    pub func_index: FuncIndex,
+    /// The number of calls to the function.
+    pub entries: u64,
+    /// The number of branches from the loops of the function back to their start.
+    pub back_edges: u64,
+}
+
+/// The profile counters of an instance compiled with `Profiler`.
+#[derive(Default)]
+pub struct ProfileCounters {
+    imported_functions: usize,
+    counters: Vec<u64>,
+}
+
+impl ProfileCounters {
+    /// Creates zeroed counters.
+    pub fn new() -> ProfileCounters {
+        ProfileCounters::default()
+    }
+
+    /// Runs `f` with these counters attached to `instance`, so that the function entries and
+    /// the loop iterations it executes are counted.
+    ///
+    /// The counters are meant for the instances of a single module.
+    pub fn run<R>(&mut self, instance: &mut Instance, f: impl FnOnce(&mut Instance) -> R) -> R {
+        let module = instance.module();
+        let info = module.info();
+        self.imported_functions = info.imported_functions.len();
+        let len = (info.func_assoc.len() - self.imported_functions) * COUNTERS_PER_FUNCTION;
+        if self.counters.len() < len {
+            self.counters.resize(len, 0);
+        }
+        run_attached(&INTERNAL_FIELD, &mut self.counters, instance, f)
+    }
+
+    /// Returns a snapshot of the profile of every local function.
+    pub fn profile(&self) -> Vec<FunctionProfile> {
+        self.counters
+            .chunks(COUNTERS_PER_FUNCTION)
+            .enumerate()
+            .map(|(local_index, counters)| FunctionProfile {
+                func_index: FuncIndex::new(self.imported_functions + local_index),
+                entries: counters[ENTRIES as usize],
+                back_edges: counters[LOOP_ITERATIONS as usize] - counters[LOOP_ENTRIES as usize],
+            })
+            .collect()
+    }
+
+    /// Sets all the counters back to zero.
+    pub fn reset(&mut self) {
+        for counter in &mut self.counters {
+            *counter = 0;
+        }
+    }
+}
@@ -3,6 +3,30 @@ extern crate cbindgen;
 use cbindgen::{Builder, Language};
 use std::{env, fs, path::PathBuf};
 
+/// Returns the names of the items of the `wasm_c_api` module, which
+/// `wasm.h` declares rather than `wasmer.h`.
+fn wasm_c_api_items(crate_dir: &str) -> Vec<String> {
+    let mut module_dir = PathBuf::from(crate_dir);
+    module_dir.push("src");
+    module_dir.push("wasm_c_api");
+
+    let mut items = vec![];
+    for entry in fs::read_dir(module_dir).expect("Unable to read the `wasm_c_api` module") {
+        let source = fs::read_to_string(entry.unwrap().path()).unwrap();
+        let words: Vec<&str> = source
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|word| !word.is_empty())
+            .collect();
+        for pair in words.windows(2) {
+            let declares = ["fn", "struct", "enum", "union", "type", "const"].contains(&pair[0]);
+            if declares && pair[1].to_lowercase().starts_with("wasm_") {
+                items.push(pair[1].to_string());
+            }
+        }
+    }
+    items
+}
+
 fn main() {
     let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
     let mut crate_wasmer_header_file = PathBuf::from(&crate_dir);
@@ -30,9 +54,15 @@ fn main() {
 
 #endif // WASMER_H_MACROS
 "#;
+    let wasm_c_api_items = wasm_c_api_items(&crate_dir);
+
     // Generate the C bindings in the `OUT_DIR`.
     out_wasmer_header_file.set_extension("h");
-    Builder::new()
+    let mut builder = Builder::new();
+    for item in &wasm_c_api_items {
+        builder = builder.exclude_item(item);
+    }
+    builder
         .with_crate(crate_dir.clone())
         .with_language(Language::C)
         .with_include_guard("WASMER_H")
@@ -45,7 +75,11 @@ fn main() {
 
     // Generate the C++ bindings in the `OUT_DIR`.
     out_wasmer_header_file.set_extension("hh");
-    Builder::new()
+    let mut builder = Builder::new();
+    for item in &wasm_c_api_items {
+        builder = builder.exclude_item(item);
+    }
+    builder
         .with_crate(crate_dir)
         .with_language(Language::Cxx)
         .with_include_guard("WASMER_H")
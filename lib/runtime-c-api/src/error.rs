@@ -1,28 +1,139 @@
 //! Read runtime errors.
 
+use crate::wasmer_result_t;
 use libc::{c_char, c_int};
+use std::any::Any;
 use std::cell::RefCell;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
 use std::ptr;
 use std::slice;
+#[cfg(feature = "metering")]
+use wasmer_middleware_common::metering::ExecutionLimitExceededError;
+use wasmer_runtime_core::error::{self as runtime_error, CallError, CompileError, RuntimeError};
+#[cfg(feature = "wasi")]
+use wasmer_wasi::WasiError;
+
+/// The kind of the last error, to tell the failures apart without
+/// parsing the message of `wasmer_last_error_message`.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum wasmer_error_kind_t {
+    /// There is no error, or its message has been read.
+    WASMER_ERROR_KIND_NONE = 0,
+    /// The API was misused, like with a null pointer, or failed otherwise.
+    WASMER_ERROR_KIND_OTHER = 1,
+    /// A module failed to compile.
+    WASMER_ERROR_KIND_COMPILE = 2,
+    /// The imports of a module failed to link.
+    WASMER_ERROR_KIND_LINK = 3,
+    /// A call trapped, or an imported function failed.
+    WASMER_ERROR_KIND_RUNTIME_TRAP = 4,
+    /// A WASI program exited with `proc_exit`.
+    WASMER_ERROR_KIND_WASI_EXIT = 5,
+    /// A call halted because the instance used its gas limit.
+    WASMER_ERROR_KIND_GAS_EXHAUSTED = 6,
+}
+
+struct LastError {
+    kind: wasmer_error_kind_t,
+    error: Box<dyn Error>,
+}
 
+// Each thread has its own last error, so that the instances running
+// on several threads don't overwrite the errors of each other.
 thread_local! {
-    static LAST_ERROR: RefCell<Option<Box<dyn Error>>> = RefCell::new(None);
+    static LAST_ERROR: RefCell<Option<LastError>> = RefCell::new(None);
 }
 
 pub fn update_last_error<E: Error + 'static>(err: E) {
+    let kind = error_kind(&err);
     LAST_ERROR.with(|prev| {
-        *prev.borrow_mut() = Some(Box::new(err));
+        *prev.borrow_mut() = Some(LastError {
+            kind,
+            error: Box::new(err),
+        });
     });
 }
 
 /// Retrieve the most recent error, clearing it in the process.
 pub(crate) fn take_last_error() -> Option<Box<dyn Error>> {
-    LAST_ERROR.with(|prev| prev.borrow_mut().take())
+    LAST_ERROR.with(|prev| prev.borrow_mut().take().map(|last| last.error))
+}
+
+fn error_kind(error: &dyn Any) -> wasmer_error_kind_t {
+    if error.is::<CompileError>() {
+        wasmer_error_kind_t::WASMER_ERROR_KIND_COMPILE
+    } else if let Some(error) = error.downcast_ref::<RuntimeError>() {
+        runtime_error_kind(error)
+    } else if let Some(error) = error.downcast_ref::<CallError>() {
+        call_error_kind(error)
+    } else if let Some(error) = error.downcast_ref::<runtime_error::Error>() {
+        match error {
+            runtime_error::Error::CompileError(_) => wasmer_error_kind_t::WASMER_ERROR_KIND_COMPILE,
+            runtime_error::Error::LinkError(_) => wasmer_error_kind_t::WASMER_ERROR_KIND_LINK,
+            runtime_error::Error::RuntimeError(error) => runtime_error_kind(error),
+            runtime_error::Error::CallError(error) => call_error_kind(error),
+            _ => wasmer_error_kind_t::WASMER_ERROR_KIND_OTHER,
+        }
+    } else {
+        wasmer_error_kind_t::WASMER_ERROR_KIND_OTHER
+    }
+}
+
+pub(crate) fn runtime_error_kind(error: &RuntimeError) -> wasmer_error_kind_t {
+    match error {
+        #[cfg(feature = "metering")]
+        RuntimeError::Error { data, .. }
+            if data.downcast_ref::<ExecutionLimitExceededError>().is_some() =>
+        {
+            wasmer_error_kind_t::WASMER_ERROR_KIND_GAS_EXHAUSTED
+        }
+        #[cfg(feature = "wasi")]
+        RuntimeError::Error { data, .. } if data.downcast_ref::<WasiError>().is_some() => {
+            wasmer_error_kind_t::WASMER_ERROR_KIND_WASI_EXIT
+        }
+        _ => wasmer_error_kind_t::WASMER_ERROR_KIND_RUNTIME_TRAP,
+    }
+}
+
+fn call_error_kind(error: &CallError) -> wasmer_error_kind_t {
+    match error {
+        CallError::Runtime(error) => runtime_error_kind(error),
+        CallError::Resolve(_) => wasmer_error_kind_t::WASMER_ERROR_KIND_OTHER,
+    }
+}
+
+/// Returns the result of a call which failed with `error`.
+pub(crate) fn call_error_result(error: &CallError) -> wasmer_result_t {
+    match call_error_kind(error) {
+        wasmer_error_kind_t::WASMER_ERROR_KIND_GAS_EXHAUSTED => {
+            wasmer_result_t::WASMER_GAS_EXHAUSTED
+        }
+        _ => wasmer_result_t::WASMER_ERROR,
+    }
+}
+
+/// Gets the kind of the last error of the calling thread, without
+/// clearing it.
+///
+/// # Example
+///
+/// ```c
+/// if (wasmer_last_error_kind() == WASMER_ERROR_KIND_COMPILE) {
+///     printf("The module is invalid\n");
+/// }
+/// ```
+#[no_mangle]
+pub extern "C" fn wasmer_last_error_kind() -> wasmer_error_kind_t {
+    LAST_ERROR.with(|prev| match *prev.borrow() {
+        Some(ref last) => last.kind,
+        None => wasmer_error_kind_t::WASMER_ERROR_KIND_NONE,
+    })
 }
 
-/// Gets the length in bytes of the last error.
+/// Gets the length in bytes of the last error of the calling thread.
 /// This can be used to dynamically allocate a buffer with the correct number of
 /// bytes needed to store a message.
 ///
@@ -35,12 +146,13 @@ pub(crate) fn take_last_error() -> Option<Box<dyn Error>> {
 #[no_mangle]
 pub extern "C" fn wasmer_last_error_length() -> c_int {
     LAST_ERROR.with(|prev| match *prev.borrow() {
-        Some(ref err) => err.to_string().len() as c_int + 1,
+        Some(ref last) => last.error.to_string().len() as c_int + 1,
         None => 0,
     })
 }
 
-/// Stores the last error message into the provided buffer up to the given `length`.
+/// Stores the last error message of the calling thread into the provided buffer up to the given
+/// `length`, and clears the last error.
 /// The `length` parameter must be large enough to store the last error message.
 ///
 /// Returns the length of the string in bytes.
@@ -2,12 +2,13 @@
 //! and table) on an instance.
 
 use crate::{
-    error::{update_last_error, CApiError},
+    error::{call_error_result, update_last_error, CApiError},
     global::wasmer_global_t,
     import::wasmer_import_func_t,
     memory::wasmer_memory_t,
     module::wasmer_module_t,
     table::wasmer_table_t,
+    trap::report_trap,
     value::{wasmer_value, wasmer_value_t, wasmer_value_tag},
     wasmer_byte_array, wasmer_result_t,
 };
@@ -397,6 +398,71 @@ pub unsafe extern "C" fn wasmer_export_to_memory(
     }
 }
 
+/// Gets a global pointer from an export pointer.
+///
+/// Writing the global, if it's mutable, changes the value the instance
+/// sees.
+///
+/// The caller owns the object and should call `wasmer_global_destroy` to free it.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_export_to_global(
+    export: *const wasmer_export_t,
+    global: *mut *mut wasmer_global_t,
+) -> wasmer_result_t {
+    let named_export = &*(export as *const NamedExport);
+    let export = &named_export.export;
+
+    if let Export::Global(exported_global) = export {
+        let glbl = Box::new(exported_global.clone());
+        *global = Box::into_raw(glbl) as *mut wasmer_global_t;
+        wasmer_result_t::WASMER_OK
+    } else {
+        update_last_error(CApiError {
+            msg: "cannot cast the `wasmer_export_t` pointer to a `wasmer_global_t` \
+                  pointer because it does not represent a global export."
+                .to_string(),
+        });
+        wasmer_result_t::WASMER_ERROR
+    }
+}
+
+/// Gets a table pointer from an export pointer.
+///
+/// The caller owns the object and should call `wasmer_table_destroy` to free it.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_export_to_table(
+    export: *const wasmer_export_t,
+    table: *mut *mut wasmer_table_t,
+) -> wasmer_result_t {
+    let named_export = &*(export as *const NamedExport);
+    let export = &named_export.export;
+
+    if let Export::Table(exported_table) = export {
+        let tbl = Box::new(exported_table.clone());
+        *table = Box::into_raw(tbl) as *mut wasmer_table_t;
+        wasmer_result_t::WASMER_OK
+    } else {
+        update_last_error(CApiError {
+            msg: "cannot cast the `wasmer_export_t` pointer to a `wasmer_table_t` \
+                  pointer because it does not represent a table export."
+                .to_string(),
+        });
+        wasmer_result_t::WASMER_ERROR
+    }
+}
+
 /// Gets name from wasmer_export
 #[no_mangle]
 #[allow(clippy::cast_ptr_alignment)]
@@ -415,6 +481,9 @@ pub unsafe extern "C" fn wasmer_export_name(export: *mut wasmer_export_t) -> was
 ///
 /// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
 /// and `wasmer_last_error_message` to get an error message.
+///
+/// Returns `wasmer_result_t::WASMER_GAS_EXHAUSTED` if the call halted because the instance used
+/// its gas limit.
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub unsafe extern "C" fn wasmer_export_func_call(
@@ -483,8 +552,10 @@ pub unsafe extern "C" fn wasmer_export_func_call(
             wasmer_result_t::WASMER_OK
         }
         Err(err) => {
+            report_trap(&err);
+            let result = call_error_result(&err);
             update_last_error(err);
-            wasmer_result_t::WASMER_ERROR
+            result
         }
     }
 }
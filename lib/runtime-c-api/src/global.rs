@@ -1,7 +1,11 @@
 //! Create, set, get and destroy global variables of an instance.
 
-use crate::value::{wasmer_value_t, wasmer_value_tag};
-use wasmer_runtime::Global;
+use crate::{
+    error::{update_last_error, CApiError},
+    value::{wasmer_value_t, wasmer_value_tag},
+    wasmer_result_t,
+};
+use wasmer_runtime::{Global, Value};
 
 #[repr(C)]
 #[derive(Clone)]
@@ -38,12 +42,43 @@ pub extern "C" fn wasmer_global_get(global: *mut wasmer_global_t) -> wasmer_valu
     value
 }
 
-/// Sets the value stored by the given Global
+/// Sets the value stored by the given Global.
+///
+/// The Global can be created with `wasmer_global_new`, or be the global
+/// of an instance, from `wasmer_export_to_global`.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure, if the Global
+/// is immutable or the value isn't of its type. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
-pub extern "C" fn wasmer_global_set(global: *mut wasmer_global_t, value: wasmer_value_t) {
+pub extern "C" fn wasmer_global_set(
+    global: *mut wasmer_global_t,
+    value: wasmer_value_t,
+) -> wasmer_result_t {
     let global = unsafe { &*(global as *mut Global) };
-    global.set(value.into());
+    let descriptor = global.descriptor();
+    let value: Value = value.into();
+    if !descriptor.mutable {
+        update_last_error(CApiError {
+            msg: "the global is immutable".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    if value.ty() != descriptor.ty {
+        update_last_error(CApiError {
+            msg: format!(
+                "the global has type {:?}, but the value has type {:?}",
+                descriptor.ty,
+                value.ty()
+            ),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    global.set(value);
+    wasmer_result_t::WASMER_OK
 }
 
 /// Returns a descriptor (type, mutability) of the given Global
@@ -1,6 +1,12 @@
 //! Create, read, destroy import definitions (function, global, memory
 //! and table) on an instance.
 
+#[cfg(all(not(target_family = "windows"), target_arch = "x86_64"))]
+use crate::{
+    error::take_last_error,
+    instance::wasmer_instance_context_t,
+    value::{wasmer_value, wasmer_value_t},
+};
 use crate::{
     error::{update_last_error, CApiError},
     export::{wasmer_import_export_kind, wasmer_import_export_value},
@@ -9,6 +15,8 @@ use crate::{
     wasmer_byte_array, wasmer_result_t,
 };
 use libc::c_uint;
+#[cfg(all(not(target_family = "windows"), target_arch = "x86_64"))]
+use std::any::Any;
 use std::{convert::TryFrom, ffi::c_void, ptr, slice, sync::Arc};
 use wasmer_runtime::{Global, Memory, Module, Table};
 use wasmer_runtime_core::{
@@ -17,6 +25,8 @@ use wasmer_runtime_core::{
     module::ImportName,
     types::{FuncSig, Type},
 };
+#[cfg(all(not(target_family = "windows"), target_arch = "x86_64"))]
+use wasmer_runtime_core::{import::IsExport, vm::Ctx, DynamicFunc};
 
 #[repr(C)]
 pub struct wasmer_import_t {
@@ -648,10 +658,122 @@ pub unsafe extern "C" fn wasmer_import_func_new(
         func: FuncPointer::new(func as _),
         ctx: Context::Internal,
         signature: Arc::new(FuncSig::new(params, returns)),
+        env_owner: None,
     });
     Box::into_raw(export) as *mut wasmer_import_func_t
 }
 
+/// The callback of a func created by `wasmer_import_func_new_with_env`.
+///
+/// It is called with the context of the calling instance, the
+/// environment of the func, the `params_len` arguments of the call,
+/// and the `results_len` results to write, which is 0 or 1.
+///
+/// It returns `wasmer_result_t::WASMER_ERROR` to trap, after setting
+/// the message of the trap with `wasmer_last_error_message`'s error
+/// if it has one.
+#[cfg(all(not(target_family = "windows"), target_arch = "x86_64"))]
+#[allow(non_camel_case_types)]
+pub type wasmer_import_func_callback_t = unsafe extern "C" fn(
+    ctx: *mut wasmer_instance_context_t,
+    env: *mut c_void,
+    params: *const wasmer_value_t,
+    params_len: c_uint,
+    results: *mut wasmer_value_t,
+    results_len: c_uint,
+) -> wasmer_result_t;
+
+/// The environment of a func created by `wasmer_import_func_new_with_env`.
+#[cfg(all(not(target_family = "windows"), target_arch = "x86_64"))]
+struct ImportFuncEnv {
+    env: *mut c_void,
+    finalizer: Option<unsafe extern "C" fn(env: *mut c_void)>,
+}
+
+// The environment is the caller's to synchronize.
+#[cfg(all(not(target_family = "windows"), target_arch = "x86_64"))]
+unsafe impl Send for ImportFuncEnv {}
+#[cfg(all(not(target_family = "windows"), target_arch = "x86_64"))]
+unsafe impl Sync for ImportFuncEnv {}
+
+#[cfg(all(not(target_family = "windows"), target_arch = "x86_64"))]
+impl Drop for ImportFuncEnv {
+    fn drop(&mut self) {
+        if let Some(finalizer) = self.finalizer {
+            unsafe { finalizer(self.env) };
+        }
+    }
+}
+
+/// Creates new func calling `callback` with the environment `env`.
+///
+/// `finalizer`, if not null, is called with `env` once the func and
+/// the instances importing it are destroyed.
+///
+/// Returns null if the func has more than two integer or two float
+/// returns, which is what is returned in registers. Use
+/// `wasmer_last_error_length` and `wasmer_last_error_message` to get an
+/// error message.
+///
+/// The caller owns the object and should call `wasmer_import_func_destroy` to free it.
+#[cfg(all(not(target_family = "windows"), target_arch = "x86_64"))]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_import_func_new_with_env(
+    callback: wasmer_import_func_callback_t,
+    env: *mut c_void,
+    finalizer: Option<unsafe extern "C" fn(env: *mut c_void)>,
+    params: *const wasmer_value_tag,
+    params_len: c_uint,
+    returns: *const wasmer_value_tag,
+    returns_len: c_uint,
+) -> *mut wasmer_import_func_t {
+    let params: &[wasmer_value_tag] = slice::from_raw_parts(params, params_len as usize);
+    let params: Vec<Type> = params.iter().cloned().map(|x| x.into()).collect();
+    let returns: &[wasmer_value_tag] = slice::from_raw_parts(returns, returns_len as usize);
+    let returns: Vec<Type> = returns.iter().cloned().map(|x| x.into()).collect();
+    let signature = Arc::new(FuncSig::new(params, returns));
+    if !DynamicFunc::is_supported(&signature) {
+        update_last_error(CApiError {
+            msg: "an imported func returns two integers and two floats at most".to_string(),
+        });
+        return ptr::null_mut();
+    }
+    let env = ImportFuncEnv { env, finalizer };
+
+    let func = DynamicFunc::new(signature.clone(), move |ctx, args| {
+        let args: Vec<wasmer_value_t> = args.iter().cloned().map(|x| x.into()).collect();
+        let mut results: Vec<wasmer_value_t> = signature
+            .returns()
+            .iter()
+            .map(|ty| wasmer_value_t {
+                tag: (*ty).into(),
+                value: wasmer_value { I64: 0 },
+            })
+            .collect();
+
+        let result = callback(
+            ctx as *mut Ctx as *mut wasmer_instance_context_t,
+            env.env,
+            args.as_ptr(),
+            args.len() as c_uint,
+            results.as_mut_ptr(),
+            results.len() as c_uint,
+        );
+        match result {
+            wasmer_result_t::WASMER_OK => Ok(results.into_iter().map(|x| x.into()).collect()),
+            _ => {
+                let message = match take_last_error() {
+                    Some(error) => error.to_string(),
+                    None => "the imported func failed".to_string(),
+                };
+                Err(Box::new(message) as Box<dyn Any>)
+            }
+        }
+    });
+
+    Box::into_raw(Box::new(func.to_export())) as *mut wasmer_import_func_t
+}
+
 /// Sets the params buffer to the parameter types of the given wasmer_import_func_t
 ///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
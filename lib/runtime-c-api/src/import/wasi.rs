@@ -1,6 +1,11 @@
 use super::*;
-use crate::get_slice_checked;
-use std::path::PathBuf;
+use crate::{get_slice_checked, instance::wasmer_instance_t};
+use std::{
+    io::{self, Read, Write},
+    path::PathBuf,
+};
+use wasmer_runtime::Instance;
+use wasmer_wasi::state::{WasiState, WasiStateBuilder};
 
 /// Opens a directory that's visible to the WASI module as `alias` but
 /// is backed by the host file at `host_file_path`
@@ -99,3 +104,292 @@ pub unsafe extern "C" fn wasmer_wasi_generate_default_import_object() -> *mut wa
 
     Box::into_raw(import_object) as *mut wasmer_import_object_t
 }
+
+/// Builds the WASI state of the instances created with an import object.
+///
+/// See `wasmer_wasi_state_builder_new`.
+#[repr(C)]
+pub struct wasmer_wasi_state_builder_t;
+
+/// Receives the bytes written by a WASI module to its stdout or its stderr.
+struct CallbackWriter {
+    callback: extern "C" fn(data: *mut c_void, bytes: *const u8, bytes_len: u32),
+    data: *mut c_void,
+}
+
+// The embedder passing the callback is responsible for it being callable from any thread.
+unsafe impl Send for CallbackWriter {}
+
+impl Write for CallbackWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(u32::max_value() as usize);
+        (self.callback)(self.data, buf.as_ptr(), len as u32);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Provides the bytes read by a WASI module from its stdin.
+struct CallbackReader {
+    callback: extern "C" fn(data: *mut c_void, buffer: *mut u8, buffer_len: u32) -> i32,
+    data: *mut c_void,
+}
+
+// The embedder passing the callback is responsible for it being callable from any thread.
+unsafe impl Send for CallbackReader {}
+
+impl Read for CallbackReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(i32::max_value() as usize);
+        match (self.callback)(self.data, buf.as_mut_ptr(), len as u32) {
+            read if read < 0 => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the stdin callback failed",
+            )),
+            read => Ok((read as usize).min(len)),
+        }
+    }
+}
+
+/// Returns the builder behind `builder`, or records an error if it's null.
+unsafe fn state_builder<'a>(
+    builder: *mut wasmer_wasi_state_builder_t,
+) -> Option<&'a mut WasiStateBuilder> {
+    if builder.is_null() {
+        update_last_error(CApiError {
+            msg: "builder ptr is null".to_string(),
+        });
+        return None;
+    }
+    Some(&mut *(builder as *mut WasiStateBuilder))
+}
+
+/// Creates a WASI state builder for a program named `program_name`, which is its first
+/// argument.
+///
+/// The builder is configured with the `wasmer_wasi_state_builder_*` functions, then given to
+/// `wasmer_wasi_generate_import_object_from_builder`.
+///
+/// The caller owns the object and should call `wasmer_wasi_state_builder_destroy` to free it.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_state_builder_new(
+    program_name: wasmer_byte_array,
+) -> *mut wasmer_wasi_state_builder_t {
+    let program_name = match program_name.as_str() {
+        Ok(program_name) => program_name,
+        Err(err) => {
+            update_last_error(err);
+            return ptr::null_mut();
+        }
+    };
+    let builder = Box::new(WasiState::new(program_name));
+
+    Box::into_raw(builder) as *mut wasmer_wasi_state_builder_t
+}
+
+/// Adds an argument to the program.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_state_builder_arg(
+    builder: *mut wasmer_wasi_state_builder_t,
+    arg: wasmer_byte_array,
+) -> wasmer_result_t {
+    let builder = match state_builder(builder) {
+        Some(builder) => builder,
+        None => return wasmer_result_t::WASMER_ERROR,
+    };
+    builder.arg(arg.as_slice());
+
+    wasmer_result_t::WASMER_OK
+}
+
+/// Sets the environment variable `key` of the program to `value`.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_state_builder_env(
+    builder: *mut wasmer_wasi_state_builder_t,
+    key: wasmer_byte_array,
+    value: wasmer_byte_array,
+) -> wasmer_result_t {
+    let builder = match state_builder(builder) {
+        Some(builder) => builder,
+        None => return wasmer_result_t::WASMER_ERROR,
+    };
+    builder.env(key.as_slice(), value.as_slice());
+
+    wasmer_result_t::WASMER_OK
+}
+
+/// Gives the program access to the host directory at `path`, under the same name.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_state_builder_preopen_dir(
+    builder: *mut wasmer_wasi_state_builder_t,
+    path: wasmer_byte_array,
+) -> wasmer_result_t {
+    let builder = match state_builder(builder) {
+        Some(builder) => builder,
+        None => return wasmer_result_t::WASMER_ERROR,
+    };
+    match path.as_str() {
+        Ok(path) => {
+            builder.preopen_dir(path);
+            wasmer_result_t::WASMER_OK
+        }
+        Err(err) => {
+            update_last_error(err);
+            wasmer_result_t::WASMER_ERROR
+        }
+    }
+}
+
+/// Gives the program access to the host directory at `host_path`, as `alias`.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_state_builder_map_dir(
+    builder: *mut wasmer_wasi_state_builder_t,
+    alias: wasmer_byte_array,
+    host_path: wasmer_byte_array,
+) -> wasmer_result_t {
+    let builder = match state_builder(builder) {
+        Some(builder) => builder,
+        None => return wasmer_result_t::WASMER_ERROR,
+    };
+    match (alias.as_str(), host_path.as_str()) {
+        (Ok(alias), Ok(host_path)) => {
+            builder.map_dir(alias, host_path);
+            wasmer_result_t::WASMER_OK
+        }
+        (Err(err), _) | (_, Err(err)) => {
+            update_last_error(err);
+            wasmer_result_t::WASMER_ERROR
+        }
+    }
+}
+
+/// Makes the program read its stdin from `callback` instead of the stdin of the host.
+///
+/// `callback` is called with `data` and a buffer to fill with at most `buffer_len` bytes, and
+/// returns how many bytes it wrote, 0 at the end of the input, or a negative number if it
+/// failed.  It may be called from any thread running an instance.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_state_builder_stdin(
+    builder: *mut wasmer_wasi_state_builder_t,
+    callback: extern "C" fn(data: *mut c_void, buffer: *mut u8, buffer_len: u32) -> i32,
+    data: *mut c_void,
+) -> wasmer_result_t {
+    let builder = match state_builder(builder) {
+        Some(builder) => builder,
+        None => return wasmer_result_t::WASMER_ERROR,
+    };
+    builder.stdin(CallbackReader { callback, data });
+
+    wasmer_result_t::WASMER_OK
+}
+
+/// Makes the program write its stdout to `callback` instead of the stdout of the host.
+///
+/// `callback` is called with `data` and the bytes written.  It may be called from any thread
+/// running an instance.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_state_builder_stdout(
+    builder: *mut wasmer_wasi_state_builder_t,
+    callback: extern "C" fn(data: *mut c_void, bytes: *const u8, bytes_len: u32),
+    data: *mut c_void,
+) -> wasmer_result_t {
+    let builder = match state_builder(builder) {
+        Some(builder) => builder,
+        None => return wasmer_result_t::WASMER_ERROR,
+    };
+    builder.stdout(CallbackWriter { callback, data });
+
+    wasmer_result_t::WASMER_OK
+}
+
+/// Makes the program write its stderr to `callback` instead of the stderr of the host.
+///
+/// Like with `wasmer_wasi_state_builder_stdout`, `callback` is called with `data` and the
+/// bytes written.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_state_builder_stderr(
+    builder: *mut wasmer_wasi_state_builder_t,
+    callback: extern "C" fn(data: *mut c_void, bytes: *const u8, bytes_len: u32),
+    data: *mut c_void,
+) -> wasmer_result_t {
+    let builder = match state_builder(builder) {
+        Some(builder) => builder,
+        None => return wasmer_result_t::WASMER_ERROR,
+    };
+    builder.stderr(CallbackWriter { callback, data });
+
+    wasmer_result_t::WASMER_OK
+}
+
+/// Frees memory for the given WASI state builder.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub extern "C" fn wasmer_wasi_state_builder_destroy(builder: *mut wasmer_wasi_state_builder_t) {
+    if !builder.is_null() {
+        unsafe { Box::from_raw(builder as *mut WasiStateBuilder) };
+    }
+}
+
+/// Creates a WASI import object giving each instance a state built by `builder`.
+///
+/// The builder isn't consumed, the caller still has to destroy it.
+///
+/// Returns null if the builder can't build a state, e.g. if a preopened directory doesn't
+/// exist. Use `wasmer_last_error_length` and `wasmer_last_error_message` to get an error
+/// message.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_generate_import_object_from_builder(
+    builder: *mut wasmer_wasi_state_builder_t,
+) -> *mut wasmer_import_object_t {
+    let builder = match state_builder(builder) {
+        Some(builder) => builder,
+        None => return ptr::null_mut(),
+    };
+    match wasmer_wasi::generate_import_object_from_builder(builder.clone()) {
+        Ok(import_object) => Box::into_raw(Box::new(import_object)) as *mut wasmer_import_object_t,
+        Err(err) => {
+            update_last_error(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Runs a WASI program by calling the `_start` function of `instance`.
+///
+/// The exit code of the program is written to `exit_code`: 0 if `_start` returned, or the
+/// code the program gave to `proc_exit`.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` if the program failed otherwise, e.g. if it
+/// trapped. Use `wasmer_last_error_length` and `wasmer_last_error_message` to get an error
+/// message.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_instance_start(
+    instance: *mut wasmer_instance_t,
+    exit_code: *mut i32,
+) -> wasmer_result_t {
+    if instance.is_null() {
+        update_last_error(CApiError {
+            msg: "instance ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    let result = (&*(instance as *mut Instance)).call("_start", &[]);
+
+    match wasmer_wasi::exit_code(&result) {
+        Some(code) => {
+            if !exit_code.is_null() {
+                *exit_code = code;
+            }
+            wasmer_result_t::WASMER_OK
+        }
+        None => {
+            if let Err(err) = result {
+                update_last_error(err);
+            }
+            wasmer_result_t::WASMER_ERROR
+        }
+    }
+}
@@ -1,20 +1,24 @@
 //! Instantiate a module, call functions, and read exports.
 
 use crate::{
-    error::{update_last_error, CApiError},
+    error::{call_error_result, update_last_error, CApiError},
     export::{wasmer_exports_t, wasmer_import_export_kind, NamedExport, NamedExports},
+    get_slice_checked,
     import::{wasmer_import_object_t, wasmer_import_t},
     memory::wasmer_memory_t,
     module::wasmer_module_t,
+    trap::report_trap,
     value::{wasmer_value, wasmer_value_t, wasmer_value_tag},
     wasmer_result_t,
 };
-use libc::{c_char, c_int, c_void};
-use std::{collections::HashMap, ffi::CStr, slice};
+use libc::{c_char, c_int, c_uint, c_void};
+use std::{collections::HashMap, ffi::CStr, mem, slice};
 use wasmer_runtime::{Ctx, Global, Instance, Memory, Module, Table, Value};
 use wasmer_runtime_core::{
     export::Export,
     import::{ImportObject, Namespace},
+    types::Type,
+    DynFunc,
 };
 
 #[repr(C)]
@@ -159,6 +163,9 @@ pub unsafe extern "C" fn wasmer_instance_context_get(
 ///
 /// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
 /// and `wasmer_last_error_message` to get an error message.
+///
+/// Returns `wasmer_result_t::WASMER_GAS_EXHAUSTED` if the call halted because the instance used
+/// its gas limit.
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub unsafe extern "C" fn wasmer_instance_call(
@@ -224,9 +231,184 @@ pub unsafe extern "C" fn wasmer_instance_call(
             wasmer_result_t::WASMER_OK
         }
         Err(err) => {
+            report_trap(&err);
+            let result = call_error_result(&err);
             update_last_error(err);
-            wasmer_result_t::WASMER_ERROR
+            result
+        }
+    }
+}
+
+/// Opaque pointer to a prepared call of a function exported by an
+/// instance.
+#[repr(C)]
+pub struct wasmer_call_frame_t;
+
+/// A function of an instance, resolved once, and the buffers of its
+/// calls.
+struct CallFrame {
+    /// Borrows the instance, which outlives the frame.
+    func: DynFunc<'static>,
+    params: Vec<Value>,
+    results: Vec<Value>,
+}
+
+/// Prepares the calls of the function exported by the instance with
+/// `name`, and writes the call frame to `frame`.
+///
+/// The function is only looked up once, and the call frame reuses its
+/// buffers across calls, unlike `wasmer_instance_call`.
+///
+/// The caller owns the object and should call `wasmer_call_frame_destroy`
+/// to free it, before destroying the instance.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_call_frame_new(
+    frame: *mut *mut wasmer_call_frame_t,
+    instance: *const wasmer_instance_t,
+    name: *const c_char,
+) -> wasmer_result_t {
+    if instance.is_null() {
+        update_last_error(CApiError {
+            msg: "instance ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    if name.is_null() {
+        update_last_error(CApiError {
+            msg: "name ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(name) => name,
+        Err(_) => {
+            update_last_error(CApiError {
+                msg: "name is not valid UTF-8".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
         }
+    };
+
+    let func = match (&*(instance as *const Instance)).dyn_func(name) {
+        Ok(func) => func,
+        Err(error) => {
+            update_last_error(error);
+            return wasmer_result_t::WASMER_ERROR;
+        }
+    };
+    let signature = func.signature();
+    if signature
+        .params()
+        .iter()
+        .chain(signature.returns())
+        .any(|ty| *ty == Type::V128)
+    {
+        update_last_error(CApiError {
+            msg: format!("the function `{}` has v128 values", name),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    let call_frame = CallFrame {
+        params: Vec::with_capacity(signature.params().len()),
+        results: Vec::with_capacity(signature.returns().len()),
+        func: mem::transmute::<DynFunc<'_>, DynFunc<'static>>(func),
+    };
+    *frame = Box::into_raw(Box::new(call_frame)) as *mut wasmer_call_frame_t;
+    wasmer_result_t::WASMER_OK
+}
+
+/// Calls the function of the call frame with `params`, and writes its
+/// results to `results`.
+///
+/// The values are untagged: their types are the ones of the function,
+/// so `params_len` and `results_len` must be the numbers of its params
+/// and results.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+///
+/// Returns `wasmer_result_t::WASMER_GAS_EXHAUSTED` if the call halted because the instance used
+/// its gas limit.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_call_frame_call(
+    frame: *mut wasmer_call_frame_t,
+    params: *const wasmer_value,
+    params_len: c_uint,
+    results: *mut wasmer_value,
+    results_len: c_uint,
+) -> wasmer_result_t {
+    let frame = &mut *(frame as *mut CallFrame);
+    let signature = frame.func.signature();
+    if params_len as usize != signature.params().len()
+        || results_len as usize != signature.returns().len()
+    {
+        update_last_error(CApiError {
+            msg: format!(
+                "the function takes {} params and returns {} results, not {} and {}",
+                signature.params().len(),
+                signature.returns().len(),
+                params_len,
+                results_len
+            ),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+
+    let params = get_slice_checked(params, params_len as usize);
+    frame.params.clear();
+    frame.params.extend(
+        signature
+            .params()
+            .iter()
+            .zip(params)
+            .map(|(ty, param)| match ty {
+                Type::I32 => Value::I32(param.I32),
+                Type::I64 => Value::I64(param.I64),
+                Type::F32 => Value::F32(param.F32),
+                Type::F64 => Value::F64(param.F64),
+                Type::V128 => unreachable!("v128 values are rejected by `wasmer_call_frame_new`"),
+            }),
+    );
+
+    match frame.func.call_into(&frame.params, &mut frame.results) {
+        Ok(()) => {
+            for (i, value) in frame.results.iter().enumerate() {
+                *results.add(i) = match *value {
+                    Value::I32(x) => wasmer_value { I32: x },
+                    Value::I64(x) => wasmer_value { I64: x },
+                    Value::F32(x) => wasmer_value { F32: x },
+                    Value::F64(x) => wasmer_value { F64: x },
+                    Value::V128(_) => {
+                        unreachable!("v128 values are rejected by `wasmer_call_frame_new`")
+                    }
+                };
+            }
+            wasmer_result_t::WASMER_OK
+        }
+        Err(err) => {
+            report_trap(&err);
+            let result = call_error_result(&err);
+            update_last_error(err);
+            result
+        }
+    }
+}
+
+/// Frees memory for the given call frame.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub extern "C" fn wasmer_call_frame_destroy(frame: *mut wasmer_call_frame_t) {
+    if !frame.is_null() {
+        unsafe { Box::from_raw(frame as *mut CallFrame) };
     }
 }
 
@@ -254,14 +436,67 @@ pub unsafe extern "C" fn wasmer_instance_exports(
 
 /// Sets the `data` field of the instance context. This context will be
 /// passed to all imported function for instance.
+///
+/// The previous data is finalized if it was set with
+/// `wasmer_instance_context_data_set_with_finalizer`.
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_instance_context_data_set(
     instance: *mut wasmer_instance_t,
     data_ptr: *mut c_void,
 ) {
-    let instance_ref = unsafe { &mut *(instance as *mut Instance) };
-    instance_ref.context_mut().data = data_ptr;
+    unsafe { wasmer_instance_context_data_set_with_finalizer(instance, data_ptr, None) }
+}
+
+/// The data of an instance context, finalized with the context.
+struct ContextData {
+    data: *mut c_void,
+    finalizer: unsafe extern "C" fn(data: *mut c_void),
+}
+
+// The data is the caller's to synchronize.
+unsafe impl Send for ContextData {}
+
+impl Drop for ContextData {
+    fn drop(&mut self) {
+        unsafe { (self.finalizer)(self.data) };
+    }
+}
+
+/// Sets the `data` field of the instance context, like
+/// `wasmer_instance_context_data_set`, and calls `finalizer`, if not
+/// null, with the data once the instance is destroyed or the data is
+/// replaced.
+///
+/// The previous data is finalized if it was set with a finalizer.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_context_data_set_with_finalizer(
+    instance: *mut wasmer_instance_t,
+    data_ptr: *mut c_void,
+    finalizer: Option<unsafe extern "C" fn(data: *mut c_void)>,
+) {
+    let ctx = (&mut *(instance as *mut Instance)).context_mut();
+    ctx.data = data_ptr;
+    // Dropping the previous data finalizes it.
+    match finalizer {
+        Some(finalizer) => ctx.set_data(Box::new(ContextData {
+            data: data_ptr,
+            finalizer,
+        })),
+        None => ctx.take_data(),
+    };
+}
+
+/// Gets the `data` field of the instance context from the instance,
+/// like `wasmer_instance_context_data_get` does from the context given
+/// to the imported functions.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_data_get(
+    instance: *const wasmer_instance_t,
+) -> *mut c_void {
+    (&*(instance as *const Instance)).context().data
 }
 
 /// Gets the memory within the context at the index `memory_idx`.
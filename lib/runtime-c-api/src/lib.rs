@@ -16,6 +16,9 @@
 //! [`wasmer.hh`][wasmer_hh]. They are automatically generated, and always
 //! up-to-date in this repository.
 //!
+//! The standard WebAssembly C API, declared by [`wasm.h`][wasm_h], is
+//! implemented too on x86-64 Unix systems. See the `wasm_c_api` module.
+//!
 //! Here is a simple example to use the C API:
 //!
 //! ```c
@@ -83,6 +86,7 @@
 //!
 //! [wasmer_h]: ./wasmer.h
 //! [wasmer_hh]: ./wasmer.hh
+//! [wasm_h]: ./wasm.h
 #![deny(
     dead_code,
     unused_imports,
@@ -99,19 +103,34 @@ pub mod global;
 pub mod import;
 pub mod instance;
 pub mod memory;
+#[cfg(feature = "metering")]
+pub mod metering;
+// The middlewares come with the metering, which needs them and the
+// singlepass backend.
+#[cfg(feature = "metering")]
+pub mod middleware;
 pub mod module;
 pub mod table;
 // `not(target_family = "windows")` is simpler than `unix`.  See build.rs
 // if you want to change the meaning of these `cfg`s in the header file.
 #[cfg(all(not(target_family = "windows"), target_arch = "x86_64"))]
 pub mod trampoline;
+pub mod trap;
 pub mod value;
+// The standard API declares host functions with their signatures at
+// run time, which needs the trampolines.
+#[cfg(all(not(target_family = "windows"), target_arch = "x86_64"))]
+#[allow(non_camel_case_types)]
+pub mod wasm_c_api;
 
 #[allow(non_camel_case_types)]
 #[repr(C)]
 pub enum wasmer_result_t {
     WASMER_OK = 1,
     WASMER_ERROR = 2,
+    /// A call halted because the instance used its gas limit. See the
+    /// `metering` module.
+    WASMER_GAS_EXHAUSTED = 3,
 }
 
 #[repr(C)]
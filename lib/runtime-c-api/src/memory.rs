@@ -1,9 +1,11 @@
 //! Create, read, write, grow, destroy memory of an instance.
 
 use crate::{error::update_last_error, error::CApiError, wasmer_limits_t, wasmer_result_t};
-use std::cell::Cell;
+use libc::c_void;
+use std::{cell::Cell, ptr, sync::Arc};
 use wasmer_runtime::Memory;
 use wasmer_runtime_core::{
+    memory::GrowObserver,
     types::MemoryDescriptor,
     units::{Bytes, Pages},
 };
@@ -55,17 +57,51 @@ pub unsafe extern "C" fn wasmer_memory_new(
 
 /// Grows a Memory by the given number of pages.
 ///
+/// The memory can be created with `wasmer_memory_new`, or be the memory
+/// of an instance, from `wasmer_export_to_memory` or
+/// `wasmer_instance_context_memory`.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub extern "C" fn wasmer_memory_grow(
+    memory: *const wasmer_memory_t,
+    delta: u32,
+) -> wasmer_result_t {
+    unsafe { wasmer_memory_grow_from(memory, delta, ptr::null_mut()) }
+}
+
+/// Grows a Memory by the given number of pages, like
+/// `wasmer_memory_grow`, and writes the length in pages it had before
+/// growing to `previous_length` if it's not null.
+///
+/// The length is read while growing, so unlike with
+/// `wasmer_memory_length`, no other thread can grow the memory in
+/// between.
+///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
 ///
 /// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
 /// and `wasmer_last_error_message` to get an error message.
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
-pub extern "C" fn wasmer_memory_grow(memory: *mut wasmer_memory_t, delta: u32) -> wasmer_result_t {
-    let memory = unsafe { &*(memory as *mut Memory) };
+pub unsafe extern "C" fn wasmer_memory_grow_from(
+    memory: *const wasmer_memory_t,
+    delta: u32,
+    previous_length: *mut u32,
+) -> wasmer_result_t {
+    let memory = &*(memory as *const Memory);
     let delta_result = memory.grow(Pages(delta));
     match delta_result {
-        Ok(_) => wasmer_result_t::WASMER_OK,
+        Ok(Pages(previous)) => {
+            if !previous_length.is_null() {
+                *previous_length = previous;
+            }
+            wasmer_result_t::WASMER_OK
+        }
         Err(grow_error) => {
             update_last_error(grow_error);
             wasmer_result_t::WASMER_ERROR
@@ -73,6 +109,59 @@ pub extern "C" fn wasmer_memory_grow(memory: *mut wasmer_memory_t, delta: u32) -
     }
 }
 
+/// The callback invoked before a memory grows, set with
+/// `wasmer_memory_set_grow_callback`.
+///
+/// It is called with the environment of the callback, the current
+/// length in pages of the memory and the length it is growing to, and
+/// returns `false` to prevent the growth. The guest's `memory.grow`
+/// then returns -1, and `wasmer_memory_grow` fails.
+#[allow(non_camel_case_types)]
+pub type wasmer_memory_grow_callback_t =
+    unsafe extern "C" fn(env: *mut c_void, current_pages: u32, requested_pages: u32) -> bool;
+
+struct GrowCallbackEnv {
+    env: *mut c_void,
+    finalizer: Option<unsafe extern "C" fn(env: *mut c_void)>,
+}
+
+// The environment is the caller's to synchronize.
+unsafe impl Send for GrowCallbackEnv {}
+unsafe impl Sync for GrowCallbackEnv {}
+
+impl Drop for GrowCallbackEnv {
+    fn drop(&mut self) {
+        if let Some(finalizer) = self.finalizer {
+            unsafe { finalizer(self.env) };
+        }
+    }
+}
+
+/// Sets the callback invoked with `env` before the memory grows, whether
+/// through the guest's `memory.grow` or `wasmer_memory_grow`, replacing
+/// the previous one. A null `callback` removes it.
+///
+/// The callback is invoked while the memory is being grown, so it must
+/// not access the memory itself. `finalizer`, if not null, is called
+/// with `env` once the callback is replaced or the memory is destroyed.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_memory_set_grow_callback(
+    memory: *const wasmer_memory_t,
+    callback: Option<wasmer_memory_grow_callback_t>,
+    env: *mut c_void,
+    finalizer: Option<unsafe extern "C" fn(env: *mut c_void)>,
+) {
+    let memory = &*(memory as *const Memory);
+    let observer = callback.map(|callback| -> GrowObserver {
+        let env = GrowCallbackEnv { env, finalizer };
+        Arc::new(move |Pages(current), Pages(requested)| unsafe {
+            callback(env.env, current, requested)
+        })
+    });
+    memory.set_grow_observer(observer);
+}
+
 /// Returns the current length in pages of the given memory
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
@@ -0,0 +1,192 @@
+//! Meter the execution of instances with gas.
+//!
+//! The modules compiled by `wasmer_compile_with_gas_metering` count the
+//! points, or gas, used by their instances. Once an instance has used
+//! its gas limit, its calls halt with
+//! `wasmer_result_t::WASMER_GAS_EXHAUSTED`. The points used are kept
+//! across calls until they are reset with
+//! `wasmer_instance_set_points_used`.
+//!
+//! The metered modules are compiled with the singlepass backend.
+
+use crate::{
+    error::{update_last_error, CApiError},
+    import::wasmer_import_t,
+    instance::wasmer_instance_t,
+    module::{wasmer_module_instantiate, wasmer_module_t},
+    wasmer_result_t,
+};
+use libc::c_int;
+use std::{ptr, slice};
+use wasmer_middleware_common::metering::{get_points_used, set_points_used, Metering};
+use wasmer_runtime::{compile_with, Instance};
+use wasmer_runtime_core::{
+    backend::Compiler,
+    codegen::{MiddlewareChain, StreamingCompiler},
+    vm::InternalField,
+};
+use wasmer_singlepass_backend::ModuleCodeGenerator as SinglePassMCG;
+
+/// The limit the modules are compiled with. The gas limit of an
+/// instance is an offset below it, so that it can change at run time,
+/// and it leaves room for the points counted between two checks.
+pub(crate) const GAS_CEILING: u64 = u64::max_value() / 2;
+
+/// How far below `GAS_CEILING` the gas limit of an instance is. The
+/// points counted by the metering middleware are the points used plus
+/// this offset.
+static GAS_LIMIT_OFFSET: InternalField = InternalField::allocate();
+
+fn get_compiler() -> impl Compiler {
+    let c: StreamingCompiler<SinglePassMCG, _, _, _, _> = StreamingCompiler::new(|| {
+        let mut chain = MiddlewareChain::new();
+        chain.push(Metering::new(GAS_CEILING));
+        chain
+    });
+    c
+}
+
+fn get_gas_limit(instance: &Instance) -> u64 {
+    GAS_CEILING - instance.get_internal(&GAS_LIMIT_OFFSET)
+}
+
+fn get_used(instance: &Instance) -> u64 {
+    get_points_used(instance).saturating_sub(instance.get_internal(&GAS_LIMIT_OFFSET))
+}
+
+fn set_used(instance: &mut Instance, points: u64) {
+    let offset = instance.get_internal(&GAS_LIMIT_OFFSET);
+    set_points_used(instance, offset.saturating_add(points));
+}
+
+fn set_gas_limit(instance: &mut Instance, limit: u64) -> wasmer_result_t {
+    if limit > GAS_CEILING {
+        update_last_error(CApiError {
+            msg: format!("the gas limit can't exceed {}", GAS_CEILING),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    let used = get_used(instance);
+    instance.set_internal(&GAS_LIMIT_OFFSET, GAS_CEILING - limit);
+    set_used(instance, used);
+    wasmer_result_t::WASMER_OK
+}
+
+/// Creates a new Module from the given wasm bytes, metering the gas used
+/// by its instances.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_compile_with_gas_metering(
+    module: *mut *mut wasmer_module_t,
+    wasm_bytes: *mut u8,
+    wasm_bytes_len: u32,
+) -> wasmer_result_t {
+    if wasm_bytes.is_null() {
+        update_last_error(CApiError {
+            msg: "wasm bytes ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    let bytes: &[u8] = slice::from_raw_parts(wasm_bytes, wasm_bytes_len as usize);
+    let new_module = match compile_with(bytes, &get_compiler()) {
+        Ok(module) => module,
+        Err(error) => {
+            update_last_error(error);
+            return wasmer_result_t::WASMER_ERROR;
+        }
+    };
+    *module = Box::into_raw(Box::new(new_module)) as *mut wasmer_module_t;
+    wasmer_result_t::WASMER_OK
+}
+
+/// Creates a new Instance from the given metered module and imports,
+/// with the gas limit `gas_limit`.
+///
+/// The start function of the module, if any, runs before the limit is
+/// set.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_module_instantiate_with_gas_limit(
+    module: *const wasmer_module_t,
+    instance: *mut *mut wasmer_instance_t,
+    imports: *mut wasmer_import_t,
+    imports_len: c_int,
+    gas_limit: u64,
+) -> wasmer_result_t {
+    match wasmer_module_instantiate(module, instance, imports, imports_len) {
+        wasmer_result_t::WASMER_OK => {}
+        result => return result,
+    }
+    let result = set_gas_limit(&mut *(*instance as *mut Instance), gas_limit);
+    if let wasmer_result_t::WASMER_ERROR = result {
+        Box::from_raw(*instance as *mut Instance);
+        *instance = ptr::null_mut();
+    }
+    result
+}
+
+/// Returns the gas limit of the instance of a metered module.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_get_gas_limit(instance: *const wasmer_instance_t) -> u64 {
+    get_gas_limit(&*(instance as *const Instance))
+}
+
+/// Sets the gas limit of the instance of a metered module, keeping the
+/// points it used.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_set_gas_limit(
+    instance: *mut wasmer_instance_t,
+    gas_limit: u64,
+) -> wasmer_result_t {
+    set_gas_limit(&mut *(instance as *mut Instance), gas_limit)
+}
+
+/// Returns the points used by the instance of a metered module.
+///
+/// They can slightly exceed the gas limit once the gas is exhausted,
+/// since the limit is checked at branches and calls.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_get_points_used(
+    instance: *const wasmer_instance_t,
+) -> u64 {
+    get_used(&*(instance as *const Instance))
+}
+
+/// Sets the points used by the instance of a metered module, like 0 to
+/// reset them between calls.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_set_points_used(
+    instance: *mut wasmer_instance_t,
+    points: u64,
+) {
+    set_used(&mut *(instance as *mut Instance), points)
+}
+
+/// Returns the points the instance of a metered module can still use
+/// before its gas is exhausted.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_get_points_remaining(
+    instance: *const wasmer_instance_t,
+) -> u64 {
+    GAS_CEILING.saturating_sub(get_points_used(&*(instance as *const Instance)))
+}
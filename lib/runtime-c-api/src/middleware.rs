@@ -0,0 +1,312 @@
+//! Compose the built-in middlewares, and compile modules with them.
+//!
+//! A middleware chain lists the middlewares a module is compiled with,
+//! in order: the events of a function go through the first middleware
+//! pushed, then through the next one. It is built with
+//! `wasmer_middleware_chain_new` and the `wasmer_middleware_chain_push_*`
+//! functions, and used by `wasmer_compile_with_middlewares`.
+//!
+//! Like the metered modules, the modules are compiled with the
+//! singlepass backend.
+
+use crate::{
+    error::{update_last_error, CApiError},
+    instance::wasmer_instance_context_t,
+    metering::GAS_CEILING,
+    module::wasmer_module_t,
+    value::wasmer_value_t,
+    wasmer_result_t,
+};
+use libc::{c_uint, c_void};
+use std::{ptr, slice, sync::Arc};
+use wasmer_middleware_common::{
+    call_trace::{CallTrace, CallTraceHandler},
+    metering::Metering,
+    opcode_filter::{OpcodeClass, OpcodeFilter},
+};
+use wasmer_runtime::compile_with;
+use wasmer_runtime_core::{
+    codegen::{MiddlewareChain, StreamingCompiler},
+    structures::TypedIndex,
+    types::{FuncIndex, Value},
+    vm::Ctx,
+};
+use wasmer_singlepass_backend::ModuleCodeGenerator as SinglePassMCG;
+
+/// Opaque pointer to a list of middlewares.
+#[repr(C)]
+pub struct wasmer_middleware_chain_t;
+
+/// A class of instructions forbidden by an opcode filter.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum wasmer_opcode_class_t {
+    /// Instructions operating on or producing `f32` and `f64` values.
+    WASMER_OPCODE_CLASS_FLOAT = 0,
+    /// Instructions of the SIMD proposal.
+    WASMER_OPCODE_CLASS_SIMD = 1,
+    /// Atomic instructions of the threads proposal.
+    WASMER_OPCODE_CLASS_ATOMIC = 2,
+    /// `call_indirect`.
+    WASMER_OPCODE_CLASS_INDIRECT_CALL = 3,
+}
+
+impl From<wasmer_opcode_class_t> for OpcodeClass {
+    fn from(class: wasmer_opcode_class_t) -> Self {
+        match class {
+            wasmer_opcode_class_t::WASMER_OPCODE_CLASS_FLOAT => OpcodeClass::Float,
+            wasmer_opcode_class_t::WASMER_OPCODE_CLASS_SIMD => OpcodeClass::Simd,
+            wasmer_opcode_class_t::WASMER_OPCODE_CLASS_ATOMIC => OpcodeClass::Atomic,
+            wasmer_opcode_class_t::WASMER_OPCODE_CLASS_INDIRECT_CALL => OpcodeClass::IndirectCall,
+        }
+    }
+}
+
+/// The callback invoked when a traced function is entered, with the
+/// environment of the callback, the context of the running instance,
+/// which is null if the backend doesn't know it, the index of the
+/// function, and its first arguments if they are captured.
+///
+/// The arguments are only valid during the call.
+#[allow(non_camel_case_types)]
+pub type wasmer_call_trace_enter_t = unsafe extern "C" fn(
+    env: *mut c_void,
+    ctx: *mut wasmer_instance_context_t,
+    func_index: u32,
+    args: *const wasmer_value_t,
+    args_len: c_uint,
+);
+
+/// The callback invoked when a traced function returns, with the
+/// environment of the callback, the context of the running instance,
+/// which is null if the backend doesn't know it, and the index of the
+/// function.
+#[allow(non_camel_case_types)]
+pub type wasmer_call_trace_exit_t =
+    unsafe extern "C" fn(env: *mut c_void, ctx: *mut wasmer_instance_context_t, func_index: u32);
+
+/// The callbacks of a call trace.
+struct CallbackHandler {
+    enter: Option<wasmer_call_trace_enter_t>,
+    exit: Option<wasmer_call_trace_exit_t>,
+    env: *mut c_void,
+}
+
+// The embedder is responsible for the callbacks being callable from
+// the threads running the instances.
+unsafe impl Send for CallbackHandler {}
+unsafe impl Sync for CallbackHandler {}
+
+fn ctx_ptr(ctx: Option<&mut Ctx>) -> *mut wasmer_instance_context_t {
+    match ctx {
+        Some(ctx) => ctx as *mut Ctx as *mut wasmer_instance_context_t,
+        None => ptr::null_mut(),
+    }
+}
+
+impl CallTraceHandler for CallbackHandler {
+    fn enter(&self, ctx: Option<&mut Ctx>, func_index: FuncIndex, args: &[Value]) {
+        if let Some(enter) = self.enter {
+            let args: Vec<wasmer_value_t> = args.iter().cloned().map(Into::into).collect();
+            unsafe {
+                enter(
+                    self.env,
+                    ctx_ptr(ctx),
+                    func_index.index() as u32,
+                    args.as_ptr(),
+                    args.len() as c_uint,
+                )
+            };
+        }
+    }
+
+    fn exit(&self, ctx: Option<&mut Ctx>, func_index: FuncIndex) {
+        if let Some(exit) = self.exit {
+            unsafe { exit(self.env, ctx_ptr(ctx), func_index.index() as u32) };
+        }
+    }
+}
+
+/// A middleware of a chain. The middlewares are created anew for each
+/// compilation.
+#[derive(Clone)]
+enum MiddlewareSpec {
+    Metering,
+    CallTrace {
+        handler: Arc<CallbackHandler>,
+        capture_args: bool,
+    },
+    OpcodeFilter {
+        denied: Vec<OpcodeClass>,
+        trap: bool,
+    },
+}
+
+fn build_chain(specs: &[MiddlewareSpec]) -> MiddlewareChain {
+    let mut chain = MiddlewareChain::new();
+    for spec in specs {
+        match spec {
+            MiddlewareSpec::Metering => chain.push(Metering::new(GAS_CEILING)),
+            MiddlewareSpec::CallTrace {
+                handler,
+                capture_args,
+            } => {
+                let call_trace = CallTrace::with_handler(handler.clone());
+                chain.push(if *capture_args {
+                    call_trace.capture_args()
+                } else {
+                    call_trace
+                });
+            }
+            MiddlewareSpec::OpcodeFilter { denied, trap } => {
+                let filter = OpcodeFilter::deny(denied);
+                chain.push(if *trap { filter.trap() } else { filter });
+            }
+        }
+    }
+    chain
+}
+
+/// Creates an empty middleware chain.
+///
+/// The caller owns the object and should call
+/// `wasmer_middleware_chain_destroy` to free it.
+#[no_mangle]
+pub extern "C" fn wasmer_middleware_chain_new() -> *mut wasmer_middleware_chain_t {
+    let chain: Vec<MiddlewareSpec> = Vec::new();
+    Box::into_raw(Box::new(chain)) as *mut wasmer_middleware_chain_t
+}
+
+/// Appends the metering middleware to the chain.
+///
+/// The instances of the modules compiled with the chain are metered
+/// like the ones of `wasmer_compile_with_gas_metering`: they are created
+/// with `wasmer_module_instantiate_with_gas_limit`, and their gas is
+/// managed by the functions of the `metering` module. The chain should
+/// have at most one metering middleware.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_middleware_chain_push_metering(
+    chain: *mut wasmer_middleware_chain_t,
+) {
+    let chain = &mut *(chain as *mut Vec<MiddlewareSpec>);
+    chain.push(MiddlewareSpec::Metering);
+}
+
+/// Appends a call trace middleware to the chain, which invokes `enter`
+/// with `env` when a local function is entered, and `exit` when it
+/// returns. Either callback can be null.
+///
+/// If `capture_args` is true, the first arguments of the entered
+/// functions are passed to `enter`, which slows the calls down.
+///
+/// The callbacks run in the middle of the traced functions, on the
+/// threads running the instances. `env` must stay valid as long as the
+/// modules compiled with the chain are used.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_middleware_chain_push_call_trace(
+    chain: *mut wasmer_middleware_chain_t,
+    enter: Option<wasmer_call_trace_enter_t>,
+    exit: Option<wasmer_call_trace_exit_t>,
+    env: *mut c_void,
+    capture_args: bool,
+) {
+    let chain = &mut *(chain as *mut Vec<MiddlewareSpec>);
+    chain.push(MiddlewareSpec::CallTrace {
+        handler: Arc::new(CallbackHandler { enter, exit, env }),
+        capture_args,
+    });
+}
+
+/// Appends an opcode filter to the chain, which forbids the
+/// instructions of the `classes_len` classes of `classes`.
+///
+/// A module using a forbidden instruction fails to compile, unless
+/// `trap` is true: then the instruction traps when executed.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_middleware_chain_push_opcode_filter(
+    chain: *mut wasmer_middleware_chain_t,
+    classes: *const wasmer_opcode_class_t,
+    classes_len: c_uint,
+    trap: bool,
+) -> wasmer_result_t {
+    if classes.is_null() && classes_len > 0 {
+        update_last_error(CApiError {
+            msg: "classes ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    let denied = if classes_len == 0 {
+        Vec::new()
+    } else {
+        slice::from_raw_parts(classes, classes_len as usize)
+            .iter()
+            .map(|&class| class.into())
+            .collect()
+    };
+    let chain = &mut *(chain as *mut Vec<MiddlewareSpec>);
+    chain.push(MiddlewareSpec::OpcodeFilter { denied, trap });
+    wasmer_result_t::WASMER_OK
+}
+
+/// Creates a new Module from the given wasm bytes, compiled with the
+/// middlewares of `chain`.
+///
+/// The module doesn't borrow the chain, which can be destroyed first.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_compile_with_middlewares(
+    module: *mut *mut wasmer_module_t,
+    wasm_bytes: *mut u8,
+    wasm_bytes_len: u32,
+    chain: *const wasmer_middleware_chain_t,
+) -> wasmer_result_t {
+    if wasm_bytes.is_null() {
+        update_last_error(CApiError {
+            msg: "wasm bytes ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    if chain.is_null() {
+        update_last_error(CApiError {
+            msg: "middleware chain ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    let specs = (&*(chain as *const Vec<MiddlewareSpec>)).clone();
+    let compiler: StreamingCompiler<SinglePassMCG, _, _, _, _> =
+        StreamingCompiler::new(move || build_chain(&specs));
+
+    let bytes: &[u8] = slice::from_raw_parts(wasm_bytes, wasm_bytes_len as usize);
+    let new_module = match compile_with(bytes, &compiler) {
+        Ok(module) => module,
+        Err(error) => {
+            update_last_error(error);
+            return wasmer_result_t::WASMER_ERROR;
+        }
+    };
+    *module = Box::into_raw(Box::new(new_module)) as *mut wasmer_module_t;
+    wasmer_result_t::WASMER_OK
+}
+
+/// Frees memory for the given middleware chain.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub extern "C" fn wasmer_middleware_chain_destroy(chain: *mut wasmer_middleware_chain_t) {
+    if !chain.is_null() {
+        unsafe { Box::from_raw(chain as *mut Vec<MiddlewareSpec>) };
+    }
+}
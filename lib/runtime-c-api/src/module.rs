@@ -7,8 +7,8 @@ use crate::{
     instance::wasmer_instance_t,
     wasmer_byte_array, wasmer_result_t,
 };
-use libc::c_int;
-use std::{collections::HashMap, slice};
+use libc::{c_char, c_int, c_uint};
+use std::{collections::HashMap, ffi::CStr, slice};
 use wasmer_runtime::{compile, default_compiler, Global, ImportObject, Memory, Module, Table};
 use wasmer_runtime_core::{cache::Artifact, export::Export, import::Namespace, load_cache_with};
 
@@ -138,9 +138,81 @@ pub unsafe extern "C" fn wasmer_module_instantiate(
     wasmer_result_t::WASMER_OK
 }
 
+/// Gets the contents of the custom sections named `name` of the given
+/// Module, in the order they appear in the module, like to read a
+/// manifest embedded in it.
+///
+/// At most `sections_len` sections are written to `sections`, which can
+/// be null if `sections_len` is 0, so that the number of sections can be
+/// read first. The bytes are owned by the module, and are valid until
+/// it is destroyed.
+///
+/// Returns the number of custom sections named `name`.
+/// Returns `-1` if an error occurs. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+///
+/// # Example
+///
+/// ```c
+/// int sections_len = wasmer_module_custom_sections(module, "manifest", NULL, 0);
+/// wasmer_byte_array *sections = malloc(sections_len * sizeof(wasmer_byte_array));
+/// wasmer_module_custom_sections(module, "manifest", sections, sections_len);
+/// ```
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_module_custom_sections(
+    module: *const wasmer_module_t,
+    name: *const c_char,
+    sections: *mut wasmer_byte_array,
+    sections_len: c_uint,
+) -> c_int {
+    if module.is_null() {
+        update_last_error(CApiError {
+            msg: "module ptr is null".to_string(),
+        });
+        return -1;
+    }
+    if name.is_null() {
+        update_last_error(CApiError {
+            msg: "name ptr is null".to_string(),
+        });
+        return -1;
+    }
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(name) => name,
+        Err(_) => {
+            update_last_error(CApiError {
+                msg: "name is not valid UTF-8".to_string(),
+            });
+            return -1;
+        }
+    };
+    if sections.is_null() && sections_len > 0 {
+        update_last_error(CApiError {
+            msg: "sections ptr is null".to_string(),
+        });
+        return -1;
+    }
+
+    let module = &*(module as *const Module);
+    let custom_sections = module.custom_sections(name);
+    for (i, section) in custom_sections
+        .iter()
+        .take(sections_len as usize)
+        .enumerate()
+    {
+        *sections.add(i) = wasmer_byte_array {
+            bytes: section.as_ptr(),
+            bytes_len: section.len() as u32,
+        };
+    }
+    custom_sections.len() as c_int
+}
+
 /// Serialize the given Module.
 ///
 /// The caller owns the object and should call `wasmer_serialized_module_destroy` to free it.
+/// The serialized module doesn't borrow the module, which can be destroyed first.
 ///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
 ///
@@ -178,12 +250,14 @@ pub unsafe extern "C" fn wasmer_module_serialize(
 }
 
 /// Get bytes of the serialized module.
+///
+/// The bytes are owned by the serialized module, and are valid until it is destroyed.
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub unsafe extern "C" fn wasmer_serialized_module_bytes(
     serialized_module: *const wasmer_serialized_module_t,
 ) -> wasmer_byte_array {
-    let serialized_module = &*(serialized_module as *const &[u8]);
+    let serialized_module = &*(serialized_module as *const Vec<u8>);
 
     wasmer_byte_array {
         bytes: serialized_module.as_ptr(),
@@ -193,6 +267,11 @@ pub unsafe extern "C" fn wasmer_serialized_module_bytes(
 
 /// Transform a sequence of bytes into a serialized module.
 ///
+/// The bytes are copied, so the caller keeps the ownership of
+/// `serialized_module_bytes`. They can be the bytes of
+/// `wasmer_serialized_module_bytes`, or of a cache artifact stored by
+/// the file system cache of the runtime.
+///
 /// The caller owns the object and should call `wasmer_serialized_module_destroy` to free it.
 ///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
@@ -206,17 +285,18 @@ pub unsafe extern "C" fn wasmer_serialized_module_from_bytes(
     serialized_module_bytes: *const u8,
     serialized_module_bytes_length: u32,
 ) -> wasmer_result_t {
-    if serialized_module.is_null() {
+    if serialized_module_bytes.is_null() {
         update_last_error(CApiError {
             msg: "`serialized_module_bytes` pointer is null".to_string(),
         });
         return wasmer_result_t::WASMER_ERROR;
     }
 
-    let serialized_module_bytes: &[u8] = slice::from_raw_parts(
+    let serialized_module_bytes: Vec<u8> = slice::from_raw_parts(
         serialized_module_bytes,
         serialized_module_bytes_length as usize,
-    );
+    )
+    .to_vec();
 
     *serialized_module = Box::into_raw(Box::new(serialized_module_bytes)) as _;
     wasmer_result_t::WASMER_OK
@@ -224,6 +304,11 @@ pub unsafe extern "C" fn wasmer_serialized_module_from_bytes(
 
 /// Deserialize the given serialized module.
 ///
+/// The module is compiled with the default backend, and doesn't borrow
+/// the serialized module, which can be destroyed first.
+///
+/// The caller owns the object and should call `wasmer_module_destroy` to free it.
+///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
 ///
 /// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
@@ -241,7 +326,7 @@ pub unsafe extern "C" fn wasmer_module_deserialize(
         return wasmer_result_t::WASMER_ERROR;
     }
 
-    let serialized_module: &[u8] = &*(serialized_module as *const &[u8]);
+    let serialized_module: &[u8] = &*(serialized_module as *const Vec<u8>);
 
     match Artifact::deserialize(serialized_module) {
         Ok(artifact) => match load_cache_with(artifact, &default_compiler()) {
@@ -249,16 +334,16 @@ pub unsafe extern "C" fn wasmer_module_deserialize(
                 *module = Box::into_raw(Box::new(deserialized_module)) as _;
                 wasmer_result_t::WASMER_OK
             }
-            Err(_) => {
+            Err(error) => {
                 update_last_error(CApiError {
-                    msg: "Failed to compile the serialized module".to_string(),
+                    msg: format!("Failed to compile the serialized module: {:?}", error),
                 });
                 wasmer_result_t::WASMER_ERROR
             }
         },
-        Err(_) => {
+        Err(error) => {
             update_last_error(CApiError {
-                msg: "Failed to deserialize the module".to_string(),
+                msg: format!("Failed to deserialize the module: {:?}", error),
             });
             wasmer_result_t::WASMER_ERROR
         }
@@ -272,7 +357,7 @@ pub extern "C" fn wasmer_serialized_module_destroy(
     serialized_module: *mut wasmer_serialized_module_t,
 ) {
     if !serialized_module.is_null() {
-        unsafe { Box::from_raw(serialized_module as *mut &[u8]) };
+        unsafe { Box::from_raw(serialized_module as *mut Vec<u8>) };
     }
 }
 
@@ -1,8 +1,16 @@
-//! Create, grow, destroy tables of an instance.
+//! Create, read, write, grow, destroy tables of an instance.
 
-use crate::{error::update_last_error, wasmer_limits_t, wasmer_result_t};
-use wasmer_runtime::Table;
-use wasmer_runtime_core::types::{ElementType, TableDescriptor};
+use crate::{
+    error::{update_last_error, CApiError},
+    import::wasmer_import_func_t,
+    wasmer_limits_t, wasmer_result_t,
+};
+use std::ptr;
+use wasmer_runtime::{Export, Table};
+use wasmer_runtime_core::{
+    table::{Anyfunc, Element},
+    types::{ElementType, TableDescriptor},
+};
 
 #[repr(C)]
 #[derive(Clone)]
@@ -46,14 +54,17 @@ pub unsafe extern "C" fn wasmer_table_new(
 
 /// Grows a Table by the given number of elements.
 ///
+/// The table can be created with `wasmer_table_new`, or be the table of
+/// an instance, from `wasmer_export_to_table`.
+///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
 ///
 /// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
 /// and `wasmer_last_error_message` to get an error message.
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
-pub extern "C" fn wasmer_table_grow(table: *mut wasmer_table_t, delta: u32) -> wasmer_result_t {
-    let table = unsafe { &*(table as *mut Table) };
+pub extern "C" fn wasmer_table_grow(table: *const wasmer_table_t, delta: u32) -> wasmer_result_t {
+    let table = unsafe { &*(table as *const Table) };
     let delta_result = table.grow(delta);
     match delta_result {
         Ok(_) => wasmer_result_t::WASMER_OK,
@@ -64,6 +75,88 @@ pub extern "C" fn wasmer_table_grow(table: *mut wasmer_table_t, delta: u32) -> w
     }
 }
 
+/// Gets the func at `index` in the Table, and writes it to `func`, or
+/// null if the element is null.
+///
+/// The func can be set at another index, or in another table, with
+/// `wasmer_table_set`.
+///
+/// The caller owns the object and should call `wasmer_import_func_destroy` to free it.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_table_get(
+    table: *const wasmer_table_t,
+    index: u32,
+    func: *mut *mut wasmer_import_func_t,
+) -> wasmer_result_t {
+    let table = &*(table as *const Table);
+    if index >= table.size() {
+        update_last_error(CApiError {
+            msg: format!("the index {} is out of the bounds of the table", index),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    *func = match table.get(index) {
+        Some(Element::Anyfunc(anyfunc)) => match anyfunc.to_export() {
+            Some(export) => Box::into_raw(Box::new(export)) as *mut wasmer_import_func_t,
+            None => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    };
+    wasmer_result_t::WASMER_OK
+}
+
+/// Sets the func at `index` in the Table, like a func created with
+/// `wasmer_import_func_new` or one got with `wasmer_table_get`, so that
+/// the instances can call it with `call_indirect`.
+///
+/// A host func can only be set once the table is used by an instance,
+/// since it is called with the context of that instance.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_table_set(
+    table: *const wasmer_table_t,
+    index: u32,
+    func: *const wasmer_import_func_t,
+) -> wasmer_result_t {
+    let table = &*(table as *const Table);
+    if index >= table.size() {
+        update_last_error(CApiError {
+            msg: format!("the index {} is out of the bounds of the table", index),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+    let anyfunc = match Anyfunc::from_export(&*(func as *const Export)) {
+        Some(anyfunc) => anyfunc,
+        None => {
+            update_last_error(CApiError {
+                msg: "func ptr error in wasmer_table_set".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+    };
+    match table.set(index, Element::Anyfunc(anyfunc)) {
+        Ok(()) => wasmer_result_t::WASMER_OK,
+        Err(()) => {
+            update_last_error(CApiError {
+                msg: "a host func can't be set in a table which isn't used by an instance"
+                    .to_string(),
+            });
+            wasmer_result_t::WASMER_ERROR
+        }
+    }
+}
+
 /// Returns the current length of the given Table
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
@@ -0,0 +1,174 @@
+//! Report the traps of calls, to a callback set by the embedder.
+
+use crate::{
+    error::{runtime_error_kind, wasmer_error_kind_t},
+    wasmer_byte_array,
+};
+use libc::{c_char, c_uint, c_void};
+use std::{cell::Cell, ffi::CString, ptr};
+use wasmer_runtime_core::{
+    error::{CallError, RuntimeError},
+    structures::TypedIndex,
+    typed_func::WasmTrapInfo,
+};
+
+/// What caused a trap.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum wasmer_trap_code_t {
+    /// An `unreachable` instruction was executed.
+    WASMER_TRAP_CODE_UNREACHABLE = 0,
+    /// A `call_indirect` called a function of another signature.
+    WASMER_TRAP_CODE_INCORRECT_CALL_INDIRECT_SIGNATURE = 1,
+    /// A memory was accessed out of its bounds.
+    WASMER_TRAP_CODE_MEMORY_OUT_OF_BOUNDS = 2,
+    /// A `call_indirect` indexed a table out of its bounds.
+    WASMER_TRAP_CODE_CALL_INDIRECT_OOB = 3,
+    /// An arithmetic instruction failed, like a division by zero.
+    WASMER_TRAP_CODE_ILLEGAL_ARITHMETIC = 4,
+    /// An atomic instruction accessed a misaligned address.
+    WASMER_TRAP_CODE_MISALIGNED_ATOMIC_ACCESS = 5,
+    /// The trap has an unknown cause.
+    WASMER_TRAP_CODE_UNKNOWN = 6,
+    /// An imported function failed.
+    WASMER_TRAP_CODE_HOST_ERROR = 7,
+    /// The instance was interrupted.
+    WASMER_TRAP_CODE_INTERRUPTED = 8,
+}
+
+impl From<WasmTrapInfo> for wasmer_trap_code_t {
+    fn from(info: WasmTrapInfo) -> Self {
+        match info {
+            WasmTrapInfo::Unreachable => wasmer_trap_code_t::WASMER_TRAP_CODE_UNREACHABLE,
+            WasmTrapInfo::IncorrectCallIndirectSignature => {
+                wasmer_trap_code_t::WASMER_TRAP_CODE_INCORRECT_CALL_INDIRECT_SIGNATURE
+            }
+            WasmTrapInfo::MemoryOutOfBounds => {
+                wasmer_trap_code_t::WASMER_TRAP_CODE_MEMORY_OUT_OF_BOUNDS
+            }
+            WasmTrapInfo::CallIndirectOOB => wasmer_trap_code_t::WASMER_TRAP_CODE_CALL_INDIRECT_OOB,
+            WasmTrapInfo::IllegalArithmetic => {
+                wasmer_trap_code_t::WASMER_TRAP_CODE_ILLEGAL_ARITHMETIC
+            }
+            WasmTrapInfo::MisalignedAtomicAccess => {
+                wasmer_trap_code_t::WASMER_TRAP_CODE_MISALIGNED_ATOMIC_ACCESS
+            }
+            WasmTrapInfo::Unknown => wasmer_trap_code_t::WASMER_TRAP_CODE_UNKNOWN,
+        }
+    }
+}
+
+/// A frame of the wasm call stack where a trap occurred.
+#[repr(C)]
+pub struct wasmer_trap_frame_t {
+    /// The index of the function.
+    pub func_index: u32,
+    /// The name of the function, or null bytes if the module has no
+    /// name section.
+    pub name: wasmer_byte_array,
+}
+
+/// The callback invoked when a call traps, set with
+/// `wasmer_trap_callback_set`.
+///
+/// It is called with the environment of the callback, the cause of the
+/// trap, its message, and the frames of the wasm call stack, innermost
+/// first: the first frame is the function which trapped. There are no
+/// frames if the call stack wasn't captured.
+///
+/// The message and the frames are only valid during the call.
+#[allow(non_camel_case_types)]
+pub type wasmer_trap_callback_t = unsafe extern "C" fn(
+    env: *mut c_void,
+    code: wasmer_trap_code_t,
+    message: *const c_char,
+    frames: *const wasmer_trap_frame_t,
+    frames_len: c_uint,
+);
+
+// The callback is set per thread, like the last error, and is invoked
+// for the calls made on its thread.
+thread_local! {
+    static TRAP_CALLBACK: Cell<Option<(wasmer_trap_callback_t, *mut c_void)>> = Cell::new(None);
+}
+
+/// Sets the callback invoked with `env` when a call made on the calling
+/// thread traps, before the call returns its error, replacing the
+/// previous one. A null `callback` removes it.
+///
+/// The exit of a WASI program and the exhaustion of the gas of an
+/// instance aren't reported.
+///
+/// # Example
+///
+/// ```c
+/// void on_trap(void *env, wasmer_trap_code_t code, const char *message,
+///              const wasmer_trap_frame_t *frames, unsigned int frames_len) {
+///     fprintf(stderr, "trap: %s\n", message);
+///     if (frames_len > 0) {
+///         fprintf(stderr, "in func %u\n", frames[0].func_index);
+///     }
+/// }
+///
+/// wasmer_trap_callback_set(on_trap, NULL);
+/// ```
+#[no_mangle]
+pub extern "C" fn wasmer_trap_callback_set(
+    callback: Option<wasmer_trap_callback_t>,
+    env: *mut c_void,
+) {
+    TRAP_CALLBACK.with(|trap_callback| trap_callback.set(callback.map(|callback| (callback, env))));
+}
+
+/// Invokes the trap callback of the calling thread, if the call failed
+/// with a trap.
+pub(crate) fn report_trap(error: &CallError) {
+    let (callback, env) = match TRAP_CALLBACK.with(Cell::get) {
+        Some(trap_callback) => trap_callback,
+        None => return,
+    };
+    let error = match error {
+        CallError::Runtime(error) => error,
+        CallError::Resolve(_) => return,
+    };
+    if runtime_error_kind(error) != wasmer_error_kind_t::WASMER_ERROR_KIND_RUNTIME_TRAP {
+        return;
+    }
+
+    let code = match error {
+        RuntimeError::Trap { code, .. } => (*code).into(),
+        RuntimeError::Error { .. } => wasmer_trap_code_t::WASMER_TRAP_CODE_HOST_ERROR,
+        RuntimeError::Interrupted => wasmer_trap_code_t::WASMER_TRAP_CODE_INTERRUPTED,
+    };
+    let message = CString::new(error.to_string()).unwrap_or_default();
+    let frames: Vec<wasmer_trap_frame_t> = error
+        .backtrace()
+        .map(|backtrace| backtrace.frames())
+        .unwrap_or(&[])
+        .iter()
+        .map(|frame| wasmer_trap_frame_t {
+            func_index: frame.func_index.index() as u32,
+            name: match frame.name {
+                Some(ref name) => wasmer_byte_array {
+                    bytes: name.as_ptr(),
+                    bytes_len: name.len() as u32,
+                },
+                None => wasmer_byte_array {
+                    bytes: ptr::null(),
+                    bytes_len: 0,
+                },
+            },
+        })
+        .collect();
+
+    unsafe {
+        callback(
+            env,
+            code,
+            message.as_ptr(),
+            frames.as_ptr(),
+            frames.len() as c_uint,
+        )
+    };
+}
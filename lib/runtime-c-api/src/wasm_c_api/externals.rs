@@ -0,0 +1,685 @@
+//! Create and use functions, globals, tables and memories.
+
+use super::{
+    types::{
+        wasm_externkind_t, wasm_externtype_t, wasm_functype_t, wasm_globaltype_t,
+        wasm_memorytype_t, wasm_tabletype_t, wasm_valtype_t, ExternType, WASM_EXTERN_FUNC,
+        WASM_EXTERN_GLOBAL, WASM_EXTERN_MEMORY, WASM_EXTERN_TABLE, WASM_VAR,
+    },
+    values::{wasm_ref_t, wasm_trap_t, wasm_val_t},
+    wasm_byte_t, wasm_store_t, wasm_vec_t,
+};
+use crate::{
+    error::{update_last_error, CApiError},
+    get_slice_checked,
+};
+use libc::c_void;
+use std::{any::Any, ptr, rc::Rc, slice, sync::Arc};
+use wasmer_runtime::{Global, Instance, Memory, Table, Value};
+use wasmer_runtime_core::{
+    export::Export,
+    import::IsExport,
+    types::{ElementType, FuncSig, MemoryDescriptor, TableDescriptor},
+    units::Pages,
+    DynamicFunc,
+};
+
+pub type wasm_func_callback_t =
+    unsafe extern "C" fn(args: *const wasm_val_t, results: *mut wasm_val_t) -> *mut wasm_trap_t;
+
+pub type wasm_func_callback_with_env_t = unsafe extern "C" fn(
+    env: *mut c_void,
+    args: *const wasm_val_t,
+    results: *mut wasm_val_t,
+) -> *mut wasm_trap_t;
+
+pub type wasm_env_finalizer_t = unsafe extern "C" fn(env: *mut c_void);
+
+/// A function, global, table or memory. The functions, globals,
+/// tables and memories are externals of the matching kind.
+#[derive(Clone)]
+pub struct wasm_extern_t {
+    pub(crate) inner: Extern,
+}
+
+#[derive(Clone)]
+pub(crate) enum Extern {
+    Func(Func),
+    Global(Global),
+    Table(Table),
+    Memory(Memory),
+}
+
+pub type wasm_func_t = wasm_extern_t;
+pub type wasm_global_t = wasm_extern_t;
+pub type wasm_table_t = wasm_extern_t;
+pub type wasm_memory_t = wasm_extern_t;
+
+#[derive(Clone)]
+pub(crate) struct Func {
+    /// What the function is imported as.
+    export: Export,
+    caller: Caller,
+}
+
+/// How a function is called by `wasm_func_call`.
+#[derive(Clone)]
+enum Caller {
+    /// A function of the host, created by `wasm_func_new`.
+    Host(Arc<HostCallback>),
+    /// A function exported by an instance, with its name.
+    Instance(Rc<Instance>, String),
+}
+
+enum Callback {
+    Plain(wasm_func_callback_t),
+    WithEnv {
+        callback: wasm_func_callback_with_env_t,
+        env: *mut c_void,
+        finalizer: Option<wasm_env_finalizer_t>,
+    },
+}
+
+/// The callback of a function of the host, called by the instances
+/// importing the function and by `wasm_func_call`.
+struct HostCallback {
+    callback: Callback,
+    signature: Arc<FuncSig>,
+}
+
+// The environment of the callback is the caller's to synchronize.
+unsafe impl Send for HostCallback {}
+unsafe impl Sync for HostCallback {}
+
+impl HostCallback {
+    /// Calls the callback, and returns its results, or the message of
+    /// the trap it returned.
+    fn call(&self, args: &[Value]) -> Result<Vec<Value>, String> {
+        let args: Vec<wasm_val_t> = args
+            .iter()
+            .map(|arg| wasm_val_t::from_value(arg).expect("The signature has no v128 value."))
+            .collect();
+        let mut results: Vec<wasm_val_t> = self
+            .signature
+            .returns()
+            .iter()
+            .map(|ty| {
+                wasm_val_t::zero(
+                    wasm_valtype_t::kind(*ty).expect("The signature has no v128 value."),
+                )
+            })
+            .collect();
+
+        let trap = unsafe {
+            match self.callback {
+                Callback::Plain(callback) => callback(args.as_ptr(), results.as_mut_ptr()),
+                Callback::WithEnv { callback, env, .. } => {
+                    callback(env, args.as_ptr(), results.as_mut_ptr())
+                }
+            }
+        };
+        if !trap.is_null() {
+            let trap = unsafe { Box::from_raw(trap) };
+            return Err(trap.message);
+        }
+
+        results
+            .iter()
+            .map(|result| {
+                result
+                    .to_value()
+                    .ok_or_else(|| "the host function returned a reference".to_string())
+            })
+            .collect()
+    }
+}
+
+impl Drop for HostCallback {
+    fn drop(&mut self) {
+        if let Callback::WithEnv {
+            env,
+            finalizer: Some(finalizer),
+            ..
+        } = self.callback
+        {
+            unsafe { finalizer(env) };
+        }
+    }
+}
+
+impl Func {
+    pub(crate) fn new(export: Export, instance: Rc<Instance>, name: String) -> Self {
+        Func {
+            export,
+            caller: Caller::Instance(instance, name),
+        }
+    }
+
+    fn signature(&self) -> &FuncSig {
+        match &self.export {
+            Export::Function { signature, .. } => signature,
+            _ => unreachable!("A function is exported as a function."),
+        }
+    }
+}
+
+impl wasm_extern_t {
+    fn new(inner: Extern) -> *mut Self {
+        Box::into_raw(Box::new(wasm_extern_t { inner }))
+    }
+
+    /// Returns what the external is imported as.
+    pub(crate) fn export(&self) -> Export {
+        match &self.inner {
+            Extern::Func(func) => func.export.clone(),
+            Extern::Global(global) => global.to_export(),
+            Extern::Table(table) => table.to_export(),
+            Extern::Memory(memory) => memory.to_export(),
+        }
+    }
+
+    fn kind(&self) -> wasm_externkind_t {
+        match self.inner {
+            Extern::Func(_) => WASM_EXTERN_FUNC,
+            Extern::Global(_) => WASM_EXTERN_GLOBAL,
+            Extern::Table(_) => WASM_EXTERN_TABLE,
+            Extern::Memory(_) => WASM_EXTERN_MEMORY,
+        }
+    }
+
+    fn ty(&self) -> wasm_externtype_t {
+        match &self.inner {
+            Extern::Func(func) => wasm_externtype_t::from_sig(func.signature())
+                .expect("The functions have no v128 value."),
+            Extern::Global(global) => {
+                wasm_externtype_t::from_global_descriptor(&global.descriptor())
+                    .expect("The globals have no v128 value.")
+            }
+            Extern::Table(table) => wasm_externtype_t::from_table_descriptor(&table.descriptor()),
+            Extern::Memory(memory) => {
+                wasm_externtype_t::from_memory_descriptor(&memory.descriptor())
+            }
+        }
+    }
+
+    fn func(&self) -> Option<&Func> {
+        match &self.inner {
+            Extern::Func(func) => Some(func),
+            _ => None,
+        }
+    }
+
+    fn global(&self) -> Option<&Global> {
+        match &self.inner {
+            Extern::Global(global) => Some(global),
+            _ => None,
+        }
+    }
+
+    fn table(&self) -> Option<&Table> {
+        match &self.inner {
+            Extern::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+
+    fn memory(&self) -> Option<&Memory> {
+        match &self.inner {
+            Extern::Memory(memory) => Some(memory),
+            _ => None,
+        }
+    }
+}
+
+/// Creates a function of the host, of type `functype`, calling
+/// `callback`.
+///
+/// Returns null upon failure. Use `wasmer_last_error_length` and
+/// `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_new(
+    _store: *mut wasm_store_t,
+    functype: *const wasm_functype_t,
+    callback: wasm_func_callback_t,
+) -> *mut wasm_func_t {
+    host_func(functype, Callback::Plain(callback))
+}
+
+/// Creates a function of the host, of type `functype`, calling
+/// `callback` with `env`. `finalizer`, if not null, is called with
+/// `env` once the function and the instances importing it are
+/// deleted.
+///
+/// Returns null upon failure. Use `wasmer_last_error_length` and
+/// `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_new_with_env(
+    _store: *mut wasm_store_t,
+    functype: *const wasm_functype_t,
+    callback: wasm_func_callback_with_env_t,
+    env: *mut c_void,
+    finalizer: Option<wasm_env_finalizer_t>,
+) -> *mut wasm_func_t {
+    host_func(
+        functype,
+        Callback::WithEnv {
+            callback,
+            env,
+            finalizer,
+        },
+    )
+}
+
+unsafe fn host_func(functype: *const wasm_functype_t, callback: Callback) -> *mut wasm_func_t {
+    let signature = match (*functype).func_sig() {
+        Some(signature) if DynamicFunc::is_supported(&signature) => Arc::new(signature),
+        _ => {
+            update_last_error(CApiError {
+                msg: "the functions of the host return two integers and two floats at most"
+                    .to_string(),
+            });
+            return ptr::null_mut();
+        }
+    };
+
+    let callback = Arc::new(HostCallback {
+        callback,
+        signature: signature.clone(),
+    });
+    let func = DynamicFunc::new(signature, {
+        let callback = callback.clone();
+        move |_, args| {
+            callback
+                .call(args)
+                .map_err(|message| Box::new(message) as Box<dyn Any>)
+        }
+    });
+
+    wasm_extern_t::new(Extern::Func(Func {
+        export: func.to_export(),
+        caller: Caller::Host(callback),
+    }))
+}
+
+/// Returns the type of the function.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_type(func: *const wasm_func_t) -> *mut wasm_functype_t {
+    Box::into_raw(Box::new((*func).ty()))
+}
+
+/// Returns the number of parameters of the function.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_param_arity(func: *const wasm_func_t) -> usize {
+    (*func)
+        .func()
+        .map_or(0, |func| func.signature().params().len())
+}
+
+/// Returns the number of results of the function.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_result_arity(func: *const wasm_func_t) -> usize {
+    (*func)
+        .func()
+        .map_or(0, |func| func.signature().returns().len())
+}
+
+/// Calls the function with the arguments `args`, and writes its
+/// results to `results`.
+///
+/// Returns null upon success, or the trap the call raised.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_call(
+    func: *const wasm_func_t,
+    args: *const wasm_val_t,
+    results: *mut wasm_val_t,
+) -> *mut wasm_trap_t {
+    let func = match (*func).func() {
+        Some(func) => func,
+        None => return wasm_trap_t::new("the external is not a function".to_string()),
+    };
+    let signature = func.signature();
+    let args: Option<Vec<Value>> = get_slice_checked(args, signature.params().len())
+        .iter()
+        .map(wasm_val_t::to_value)
+        .collect();
+    let args = match args {
+        Some(args) => args,
+        None => return wasm_trap_t::new("references can't be passed to functions yet".to_string()),
+    };
+
+    let returns = match &func.caller {
+        Caller::Host(callback) => callback.call(&args),
+        Caller::Instance(instance, name) => instance
+            .call(name, &args)
+            .map_err(|error| wasm_trap_t::call_error_message(&error)),
+    };
+    match returns {
+        Ok(returns) => {
+            if !returns.is_empty() {
+                let results = slice::from_raw_parts_mut(results, returns.len());
+                for (result, value) in results.iter_mut().zip(returns.iter()) {
+                    *result =
+                        wasm_val_t::from_value(value).expect("The functions have no v128 value.");
+                }
+            }
+            ptr::null_mut()
+        }
+        Err(message) => wasm_trap_t::new(message),
+    }
+}
+
+/// Creates a global of type `globaltype`, holding `value`.
+///
+/// Returns null upon failure. Use `wasmer_last_error_length` and
+/// `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_global_new(
+    _store: *mut wasm_store_t,
+    globaltype: *const wasm_globaltype_t,
+    value: *const wasm_val_t,
+) -> *mut wasm_global_t {
+    let mutable = match &(*globaltype).ty {
+        ExternType::Global {
+            content,
+            mutability,
+        } if content.kind == (*value).kind => *mutability == WASM_VAR,
+        _ => {
+            update_last_error(CApiError {
+                msg: "the value doesn't have the type of the global".to_string(),
+            });
+            return ptr::null_mut();
+        }
+    };
+    let value = match (*value).to_value() {
+        Some(value) => value,
+        None => {
+            update_last_error(CApiError {
+                msg: "globals can't hold references yet".to_string(),
+            });
+            return ptr::null_mut();
+        }
+    };
+    let global = if mutable {
+        Global::new_mutable(value)
+    } else {
+        Global::new(value)
+    };
+    wasm_extern_t::new(Extern::Global(global))
+}
+
+/// Returns the type of the global.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_global_type(global: *const wasm_global_t) -> *mut wasm_globaltype_t {
+    Box::into_raw(Box::new((*global).ty()))
+}
+
+/// Writes the value of the global to `out`.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_global_get(global: *const wasm_global_t, out: *mut wasm_val_t) {
+    if let Some(global) = (*global).global() {
+        let value = wasm_val_t::from_value(&global.get()).expect("The globals have no v128 value.");
+        ptr::write(out, value);
+    }
+}
+
+/// Sets the value of the global, if it's mutable and `value` has its
+/// type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_global_set(global: *mut wasm_global_t, value: *const wasm_val_t) {
+    if let (Some(global), Some(value)) = ((*global).global(), (*value).to_value()) {
+        let desc = global.descriptor();
+        if desc.mutable && desc.ty == value.ty() {
+            global.set(value);
+        }
+    }
+}
+
+pub type wasm_table_size_t = u32;
+
+/// Creates a table of type `tabletype`. Its elements are null, since
+/// references are not implemented yet.
+///
+/// Returns null upon failure. Use `wasmer_last_error_length` and
+/// `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_table_new(
+    _store: *mut wasm_store_t,
+    tabletype: *const wasm_tabletype_t,
+    _init: *mut wasm_ref_t,
+) -> *mut wasm_table_t {
+    let limits = match &(*tabletype).ty {
+        ExternType::Table { limits, .. } => limits,
+        _ => return ptr::null_mut(),
+    };
+    let desc = TableDescriptor {
+        element: ElementType::Anyfunc,
+        minimum: limits.min,
+        maximum: limits.maximum(),
+    };
+    match Table::new(desc) {
+        Ok(table) => wasm_extern_t::new(Extern::Table(table)),
+        Err(error) => {
+            update_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Returns the type of the table.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_table_type(table: *const wasm_table_t) -> *mut wasm_tabletype_t {
+    Box::into_raw(Box::new((*table).ty()))
+}
+
+/// Returns the element `index` of the table. References are not
+/// implemented yet, so it returns null.
+#[no_mangle]
+pub extern "C" fn wasm_table_get(
+    _table: *const wasm_table_t,
+    _index: wasm_table_size_t,
+) -> *mut wasm_ref_t {
+    ptr::null_mut()
+}
+
+/// Sets the element `index` of the table. References are not
+/// implemented yet, so it returns false.
+#[no_mangle]
+pub extern "C" fn wasm_table_set(
+    _table: *mut wasm_table_t,
+    _index: wasm_table_size_t,
+    _element: *mut wasm_ref_t,
+) -> bool {
+    false
+}
+
+/// Returns the number of elements of the table.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_table_size(table: *const wasm_table_t) -> wasm_table_size_t {
+    (*table).table().map_or(0, Table::size)
+}
+
+/// Grows the table by `delta` null elements, and returns whether it
+/// could.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_table_grow(
+    table: *mut wasm_table_t,
+    delta: wasm_table_size_t,
+    _init: *mut wasm_ref_t,
+) -> bool {
+    (*table)
+        .table()
+        .map_or(false, |table| table.grow(delta).is_ok())
+}
+
+pub type wasm_memory_pages_t = u32;
+
+/// Creates a memory of type `memorytype`.
+///
+/// Returns null upon failure. Use `wasmer_last_error_length` and
+/// `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memory_new(
+    _store: *mut wasm_store_t,
+    memorytype: *const wasm_memorytype_t,
+) -> *mut wasm_memory_t {
+    let limits = match &(*memorytype).ty {
+        ExternType::Memory { limits } => limits,
+        _ => return ptr::null_mut(),
+    };
+    let desc = match MemoryDescriptor::new(Pages(limits.min), limits.maximum().map(Pages), false) {
+        Ok(desc) => desc,
+        Err(msg) => {
+            update_last_error(CApiError { msg });
+            return ptr::null_mut();
+        }
+    };
+    match Memory::new(desc) {
+        Ok(memory) => wasm_extern_t::new(Extern::Memory(memory)),
+        Err(error) => {
+            update_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Returns the type of the memory.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memory_type(memory: *const wasm_memory_t) -> *mut wasm_memorytype_t {
+    Box::into_raw(Box::new((*memory).ty()))
+}
+
+/// Returns a pointer to the bytes of the memory. It changes when the
+/// memory grows.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memory_data(memory: *mut wasm_memory_t) -> *mut wasm_byte_t {
+    match (*memory).memory() {
+        Some(memory) => memory.view::<u8>()[..].as_ptr() as *mut wasm_byte_t,
+        None => ptr::null_mut(),
+    }
+}
+
+/// Returns the size of the memory, in bytes.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memory_data_size(memory: *const wasm_memory_t) -> usize {
+    (*memory)
+        .memory()
+        .map_or(0, |memory| memory.size().bytes().0)
+}
+
+/// Returns the size of the memory, in pages.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memory_size(memory: *const wasm_memory_t) -> wasm_memory_pages_t {
+    (*memory).memory().map_or(0, |memory| memory.size().0)
+}
+
+/// Grows the memory by `delta` pages, and returns whether it could.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memory_grow(
+    memory: *mut wasm_memory_t,
+    delta: wasm_memory_pages_t,
+) -> bool {
+    (*memory)
+        .memory()
+        .map_or(false, |memory| memory.grow(Pages(delta)).is_ok())
+}
+
+/// Returns the kind of the external.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_extern_kind(external: *const wasm_extern_t) -> wasm_externkind_t {
+    (*external).kind()
+}
+
+/// Returns the type of the external.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_extern_type(
+    external: *const wasm_extern_t,
+) -> *mut wasm_externtype_t {
+    Box::into_raw(Box::new((*external).ty()))
+}
+
+/// Defines the casts between the externals and the ones of a kind.
+macro_rules! wasm_extern_casts {
+    ($kind:expr, $as_extern:ident, $as_extern_const:ident, $from_extern:ident, $from_extern_const:ident) => {
+        /// Returns the object as an external.
+        #[no_mangle]
+        pub extern "C" fn $as_extern(object: *mut wasm_extern_t) -> *mut wasm_extern_t {
+            object
+        }
+
+        /// Returns the object as an external.
+        #[no_mangle]
+        pub extern "C" fn $as_extern_const(object: *const wasm_extern_t) -> *const wasm_extern_t {
+            object
+        }
+
+        /// Returns the external as an object of its kind, or null if
+        /// it's of another kind.
+        #[no_mangle]
+        pub unsafe extern "C" fn $from_extern(external: *mut wasm_extern_t) -> *mut wasm_extern_t {
+            if (*external).kind() == $kind {
+                external
+            } else {
+                ptr::null_mut()
+            }
+        }
+
+        /// Returns the external as an object of its kind, or null if
+        /// it's of another kind.
+        #[no_mangle]
+        pub unsafe extern "C" fn $from_extern_const(
+            external: *const wasm_extern_t,
+        ) -> *const wasm_extern_t {
+            if (*external).kind() == $kind {
+                external
+            } else {
+                ptr::null()
+            }
+        }
+    };
+}
+
+wasm_extern_casts!(
+    WASM_EXTERN_FUNC,
+    wasm_func_as_extern,
+    wasm_func_as_extern_const,
+    wasm_extern_as_func,
+    wasm_extern_as_func_const
+);
+wasm_extern_casts!(
+    WASM_EXTERN_GLOBAL,
+    wasm_global_as_extern,
+    wasm_global_as_extern_const,
+    wasm_extern_as_global,
+    wasm_extern_as_global_const
+);
+wasm_extern_casts!(
+    WASM_EXTERN_TABLE,
+    wasm_table_as_extern,
+    wasm_table_as_extern_const,
+    wasm_extern_as_table,
+    wasm_extern_as_table_const
+);
+wasm_extern_casts!(
+    WASM_EXTERN_MEMORY,
+    wasm_memory_as_extern,
+    wasm_memory_as_extern_const,
+    wasm_extern_as_memory,
+    wasm_extern_as_memory_const
+);
+
+wasm_declare_copy!(wasm_extern_t, wasm_extern_delete, wasm_extern_copy);
+
+pub type wasm_extern_vec_t = wasm_vec_t<*mut wasm_extern_t>;
+
+wasm_declare_vec!(
+    *mut wasm_extern_t,
+    wasm_extern_vec_new_empty,
+    wasm_extern_vec_new_uninitialized,
+    wasm_extern_vec_new,
+    wasm_extern_vec_copy,
+    wasm_extern_vec_delete
+);
+wasm_declare_copy!(wasm_func_t, wasm_func_delete, wasm_func_copy);
+wasm_declare_copy!(wasm_global_t, wasm_global_delete, wasm_global_copy);
+wasm_declare_copy!(wasm_table_t, wasm_table_delete, wasm_table_copy);
+wasm_declare_copy!(wasm_memory_t, wasm_memory_delete, wasm_memory_copy);
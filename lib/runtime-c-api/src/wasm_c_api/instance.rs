@@ -0,0 +1,97 @@
+//! Instantiate modules and get their exports.
+
+use super::{
+    externals::{wasm_extern_t, wasm_extern_vec_t, Extern, Func},
+    module::wasm_module_t,
+    values::wasm_trap_t,
+    wasm_store_t, wasm_vec_t,
+};
+use crate::get_slice_checked;
+use std::{collections::HashMap, ptr, rc::Rc};
+use wasmer_runtime::{Export, ImportObject, Instance};
+use wasmer_runtime_core::import::Namespace;
+
+#[derive(Clone)]
+pub struct wasm_instance_t {
+    inner: Rc<Instance>,
+    /// The externals the instance imports, kept alive with it.
+    _imports: Vec<wasm_extern_t>,
+}
+
+/// Instantiates the module with `imports`, given in the order of
+/// `wasm_module_imports`.
+///
+/// Returns null upon failure, and writes the trap to `trap` if it's
+/// not null.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_instance_new(
+    _store: *mut wasm_store_t,
+    module: *const wasm_module_t,
+    imports: *const *const wasm_extern_t,
+    trap: *mut *mut wasm_trap_t,
+) -> *mut wasm_instance_t {
+    let module = &(*module).inner;
+    let descriptors = module.imports();
+    let imports: Vec<wasm_extern_t> = get_slice_checked(imports, descriptors.len())
+        .iter()
+        .map(|import| (**import).clone())
+        .collect();
+
+    let mut namespaces = HashMap::new();
+    for (descriptor, import) in descriptors.into_iter().zip(imports.iter()) {
+        namespaces
+            .entry(descriptor.namespace)
+            .or_insert_with(Namespace::new)
+            .insert(descriptor.name, import.export());
+    }
+    let mut import_object = ImportObject::new();
+    for (name, namespace) in namespaces {
+        import_object.register(name, namespace);
+    }
+
+    match module.instantiate(&import_object) {
+        Ok(instance) => Box::into_raw(Box::new(wasm_instance_t {
+            inner: Rc::new(instance),
+            _imports: imports,
+        })),
+        Err(error) => {
+            if !trap.is_null() {
+                *trap = wasm_trap_t::new(wasm_trap_t::error_message(&error));
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Writes the exports of the instance to `out`, in the order of
+/// `wasm_module_exports`.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_instance_exports(
+    instance: *const wasm_instance_t,
+    out: *mut wasm_extern_vec_t,
+) {
+    let instance = &(*instance).inner;
+    let mut exports: HashMap<String, Export> = instance.exports().collect();
+    let externs = instance
+        .module()
+        .exports()
+        .into_iter()
+        .map(|descriptor| {
+            let export = exports
+                .remove(&descriptor.name)
+                .expect("The instance has the exports of its module.");
+            let inner = match export {
+                Export::Function { .. } => {
+                    Extern::Func(Func::new(export, instance.clone(), descriptor.name))
+                }
+                Export::Global(global) => Extern::Global(global),
+                Export::Table(table) => Extern::Table(table),
+                Export::Memory(memory) => Extern::Memory(memory),
+            };
+            Box::into_raw(Box::new(wasm_extern_t { inner }))
+        })
+        .collect();
+    ptr::write(out, wasm_vec_t::new(externs));
+}
+
+wasm_declare_copy!(wasm_instance_t, wasm_instance_delete, wasm_instance_copy);
@@ -0,0 +1,274 @@
+//! The standard WebAssembly C API, from
+//! [wasm-c-api](https://github.com/WebAssembly/wasm-c-api).
+//!
+//! Language bindings written against the standard API work with
+//! wasmer unchanged: they include [`wasm.h`][wasm_h], from the source
+//! tree of this crate, and link to this library. The items of this
+//! module are declared by `wasm.h` rather than `wasmer.h`.
+//!
+//! The engine compiles modules with the default backend. A store
+//! doesn't own the objects created in it: each of them is freed by
+//! its `wasm_*_delete` function, and an instance keeps alive what it
+//! imports.
+//!
+//! The imports given to `wasm_instance_new` are in the order of
+//! `wasm_module_imports`, which lists the imported functions first,
+//! then the memories, the tables and the globals.
+//!
+//! References, host info, foreign objects, frames and sharing modules
+//! between threads are not implemented yet. Modules whose imports or
+//! exports have `v128` values can't be created, since `wasm.h` has no
+//! type for them.
+//!
+//! [wasm_h]: ./wasm.h
+
+use std::{mem, ptr, slice};
+
+/// An element of the vectors of the API.
+pub trait VecElement: Sized {
+    /// Returns the value of the elements of a vector created by
+    /// `wasm_*_vec_new_uninitialized`.
+    fn uninitialized() -> Self;
+
+    /// Copies the element, with what it owns.
+    unsafe fn copy(&self) -> Self;
+
+    /// Releases what the element owns.
+    unsafe fn delete(self) {}
+}
+
+impl VecElement for u8 {
+    fn uninitialized() -> Self {
+        0
+    }
+
+    unsafe fn copy(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Clone> VecElement for *mut T {
+    fn uninitialized() -> Self {
+        ptr::null_mut()
+    }
+
+    unsafe fn copy(&self) -> Self {
+        if self.is_null() {
+            ptr::null_mut()
+        } else {
+            Box::into_raw(Box::new((**self).clone()))
+        }
+    }
+
+    unsafe fn delete(self) {
+        if !self.is_null() {
+            Box::from_raw(self);
+        }
+    }
+}
+
+/// A vector of the API, like `wasm_byte_vec_t`. It owns its elements.
+#[repr(C)]
+pub struct wasm_vec_t<T: VecElement> {
+    pub size: usize,
+    pub data: *mut T,
+}
+
+impl<T: VecElement> wasm_vec_t<T> {
+    pub(crate) fn new(elements: Vec<T>) -> Self {
+        let mut elements = elements.into_boxed_slice();
+        let vec = wasm_vec_t {
+            size: elements.len(),
+            data: elements.as_mut_ptr(),
+        };
+        mem::forget(elements);
+        vec
+    }
+
+    pub(crate) fn empty() -> Self {
+        wasm_vec_t {
+            size: 0,
+            data: ptr::null_mut(),
+        }
+    }
+
+    pub(crate) fn as_slice(&self) -> &[T] {
+        if self.data.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.data, self.size) }
+        }
+    }
+
+    /// Takes the vector `vec` points to, which is left empty, like
+    /// the functions taking the ownership of a vector do.
+    pub(crate) unsafe fn take(vec: *mut Self) -> Self {
+        let taken = ptr::read(vec);
+        ptr::write(vec, Self::empty());
+        taken
+    }
+}
+
+impl<T: VecElement> Clone for wasm_vec_t<T> {
+    fn clone(&self) -> Self {
+        Self::new(
+            self.as_slice()
+                .iter()
+                .map(|element| unsafe { element.copy() })
+                .collect(),
+        )
+    }
+}
+
+impl<T: VecElement> Drop for wasm_vec_t<T> {
+    fn drop(&mut self) {
+        if !self.data.is_null() {
+            let elements = unsafe { Vec::from_raw_parts(self.data, self.size, self.size) };
+            for element in elements {
+                unsafe { element.delete() };
+            }
+        }
+    }
+}
+
+/// Defines the `wasm_*_vec_*` functions of a vector type.
+macro_rules! wasm_declare_vec {
+    ($element:ty, $new_empty:ident, $new_uninitialized:ident, $new:ident, $copy:ident, $delete:ident) => {
+        /// Creates an empty vector.
+        #[no_mangle]
+        pub unsafe extern "C" fn $new_empty(out: *mut crate::wasm_c_api::wasm_vec_t<$element>) {
+            std::ptr::write(out, crate::wasm_c_api::wasm_vec_t::empty());
+        }
+
+        /// Creates a vector of `size` elements, to be set by the caller.
+        #[no_mangle]
+        pub unsafe extern "C" fn $new_uninitialized(
+            out: *mut crate::wasm_c_api::wasm_vec_t<$element>,
+            size: usize,
+        ) {
+            let elements = (0..size)
+                .map(|_| <$element as crate::wasm_c_api::VecElement>::uninitialized())
+                .collect();
+            std::ptr::write(out, crate::wasm_c_api::wasm_vec_t::new(elements));
+        }
+
+        /// Creates a vector of the `size` elements at `data`, taking
+        /// their ownership.
+        #[no_mangle]
+        pub unsafe extern "C" fn $new(
+            out: *mut crate::wasm_c_api::wasm_vec_t<$element>,
+            size: usize,
+            data: *const $element,
+        ) {
+            let elements = crate::get_slice_checked(data, size)
+                .iter()
+                .map(|element| std::ptr::read(element))
+                .collect();
+            std::ptr::write(out, crate::wasm_c_api::wasm_vec_t::new(elements));
+        }
+
+        /// Copies the vector `vec`, with its elements.
+        #[no_mangle]
+        pub unsafe extern "C" fn $copy(
+            out: *mut crate::wasm_c_api::wasm_vec_t<$element>,
+            vec: *const crate::wasm_c_api::wasm_vec_t<$element>,
+        ) {
+            std::ptr::write(out, (*vec).clone());
+        }
+
+        /// Deletes the vector `vec`, with its elements.
+        #[no_mangle]
+        pub unsafe extern "C" fn $delete(vec: *mut crate::wasm_c_api::wasm_vec_t<$element>) {
+            crate::wasm_c_api::wasm_vec_t::take(vec);
+        }
+    };
+}
+
+/// Defines the `wasm_*_delete` function of a type.
+macro_rules! wasm_declare_own {
+    ($ty:ty, $delete:ident) => {
+        /// Deletes the object if not null.
+        #[no_mangle]
+        pub unsafe extern "C" fn $delete(object: *mut $ty) {
+            if !object.is_null() {
+                Box::from_raw(object);
+            }
+        }
+    };
+}
+
+/// Defines the `wasm_*_delete` and `wasm_*_copy` functions of a type.
+macro_rules! wasm_declare_copy {
+    ($ty:ty, $delete:ident, $copy:ident) => {
+        wasm_declare_own!($ty, $delete);
+
+        /// Copies the object.
+        #[no_mangle]
+        pub unsafe extern "C" fn $copy(object: *const $ty) -> *mut $ty {
+            Box::into_raw(Box::new((*object).clone()))
+        }
+    };
+}
+
+pub mod externals;
+pub mod instance;
+pub mod module;
+pub mod types;
+pub mod values;
+
+pub type wasm_byte_t = u8;
+pub type wasm_byte_vec_t = wasm_vec_t<wasm_byte_t>;
+pub type wasm_name_t = wasm_byte_vec_t;
+pub type wasm_message_t = wasm_name_t;
+
+wasm_declare_vec!(
+    wasm_byte_t,
+    wasm_byte_vec_new_empty,
+    wasm_byte_vec_new_uninitialized,
+    wasm_byte_vec_new,
+    wasm_byte_vec_copy,
+    wasm_byte_vec_delete
+);
+
+/// The configuration of an engine. Wasmer has no settings for it yet.
+pub struct wasm_config_t {}
+
+/// Creates a configuration.
+#[no_mangle]
+pub extern "C" fn wasm_config_new() -> *mut wasm_config_t {
+    Box::into_raw(Box::new(wasm_config_t {}))
+}
+
+wasm_declare_own!(wasm_config_t, wasm_config_delete);
+
+/// The engine, compiling the modules with the default backend.
+pub struct wasm_engine_t {}
+
+/// Creates an engine.
+#[no_mangle]
+pub extern "C" fn wasm_engine_new() -> *mut wasm_engine_t {
+    Box::into_raw(Box::new(wasm_engine_t {}))
+}
+
+/// Creates an engine with the configuration `config`, which it takes
+/// the ownership of.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_engine_new_with_config(
+    config: *mut wasm_config_t,
+) -> *mut wasm_engine_t {
+    wasm_config_delete(config);
+    wasm_engine_new()
+}
+
+wasm_declare_own!(wasm_engine_t, wasm_engine_delete);
+
+/// A store, which the objects of the API are created in.
+pub struct wasm_store_t {}
+
+/// Creates a store for the engine `engine`.
+#[no_mangle]
+pub extern "C" fn wasm_store_new(_engine: *mut wasm_engine_t) -> *mut wasm_store_t {
+    Box::into_raw(Box::new(wasm_store_t {}))
+}
+
+wasm_declare_own!(wasm_store_t, wasm_store_delete);
@@ -0,0 +1,174 @@
+//! Compile, validate, inspect, serialize and deserialize modules.
+
+use super::{
+    types::{
+        wasm_exporttype_t, wasm_exporttype_vec_t, wasm_externtype_t, wasm_importtype_t,
+        wasm_importtype_vec_t,
+    },
+    wasm_byte_vec_t, wasm_store_t, wasm_vec_t,
+};
+use crate::error::{update_last_error, CApiError};
+use std::ptr;
+use wasmer_runtime::{compile, default_compiler, Module};
+use wasmer_runtime_core::{cache::Artifact, load_cache_with};
+
+#[derive(Clone)]
+pub struct wasm_module_t {
+    pub(crate) inner: Module,
+}
+
+impl wasm_module_t {
+    /// Returns the module, unless its imports or exports have a type
+    /// which `wasm.h` can't describe.
+    fn new(module: Module) -> *mut Self {
+        let supported = module
+            .imports()
+            .iter()
+            .map(|import| &import.ty)
+            .chain(module.exports().iter().map(|export| &export.ty))
+            .all(|ty| wasm_externtype_t::from_descriptor(ty).is_some());
+        if !supported {
+            update_last_error(CApiError {
+                msg: "the imports and exports of the module can't have v128 values".to_string(),
+            });
+            return ptr::null_mut();
+        }
+        Box::into_raw(Box::new(wasm_module_t { inner: module }))
+    }
+}
+
+/// Compiles the module `binary`.
+///
+/// Returns null upon failure. Use `wasmer_last_error_length` and
+/// `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_new(
+    _store: *mut wasm_store_t,
+    binary: *const wasm_byte_vec_t,
+) -> *mut wasm_module_t {
+    match compile((*binary).as_slice()) {
+        Ok(module) => wasm_module_t::new(module),
+        Err(error) => {
+            update_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Returns whether `binary` is a valid module.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_validate(
+    _store: *mut wasm_store_t,
+    binary: *const wasm_byte_vec_t,
+) -> bool {
+    wasmer_runtime_core::validate((*binary).as_slice())
+}
+
+/// Writes the imports of the module to `out`: the functions first,
+/// then the memories, the tables and the globals.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_imports(
+    module: *const wasm_module_t,
+    out: *mut wasm_importtype_vec_t,
+) {
+    let imports = (*module)
+        .inner
+        .imports()
+        .into_iter()
+        .map(|import| {
+            Box::into_raw(Box::new(wasm_importtype_t {
+                module: wasm_vec_t::new(import.namespace.into_bytes()),
+                name: wasm_vec_t::new(import.name.into_bytes()),
+                ty: Box::new(
+                    wasm_externtype_t::from_descriptor(&import.ty)
+                        .expect("The types are checked when the module is created."),
+                ),
+            }))
+        })
+        .collect();
+    ptr::write(out, wasm_vec_t::new(imports));
+}
+
+/// Writes the exports of the module to `out`, in the order they are
+/// declared.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_exports(
+    module: *const wasm_module_t,
+    out: *mut wasm_exporttype_vec_t,
+) {
+    let exports = (*module)
+        .inner
+        .exports()
+        .into_iter()
+        .map(|export| {
+            Box::into_raw(Box::new(wasm_exporttype_t {
+                name: wasm_vec_t::new(export.name.into_bytes()),
+                ty: Box::new(
+                    wasm_externtype_t::from_descriptor(&export.ty)
+                        .expect("The types are checked when the module is created."),
+                ),
+            }))
+        })
+        .collect();
+    ptr::write(out, wasm_vec_t::new(exports));
+}
+
+/// Writes the serialized module to `out`, to be deserialized with
+/// `wasm_module_deserialize`.
+///
+/// `out` is empty upon failure. Use `wasmer_last_error_length` and
+/// `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_serialize(
+    module: *const wasm_module_t,
+    out: *mut wasm_byte_vec_t,
+) {
+    let serialized = match (*module).inner.cache() {
+        Ok(artifact) => match artifact.serialize() {
+            Ok(serialized) => serialized,
+            Err(_) => {
+                update_last_error(CApiError {
+                    msg: "Failed to serialize the module artifact".to_string(),
+                });
+                vec![]
+            }
+        },
+        Err(_) => {
+            update_last_error(CApiError {
+                msg: "Failed to serialize the module".to_string(),
+            });
+            vec![]
+        }
+    };
+    ptr::write(out, wasm_vec_t::new(serialized));
+}
+
+/// Deserializes a module serialized by `wasm_module_serialize`.
+///
+/// Returns null upon failure. Use `wasmer_last_error_length` and
+/// `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_deserialize(
+    _store: *mut wasm_store_t,
+    serialized: *const wasm_byte_vec_t,
+) -> *mut wasm_module_t {
+    match Artifact::deserialize((*serialized).as_slice()) {
+        Ok(artifact) => match load_cache_with(artifact, &default_compiler()) {
+            Ok(module) => wasm_module_t::new(module),
+            Err(_) => {
+                update_last_error(CApiError {
+                    msg: "Failed to compile the serialized module".to_string(),
+                });
+                ptr::null_mut()
+            }
+        },
+        Err(_) => {
+            update_last_error(CApiError {
+                msg: "Failed to deserialize the module".to_string(),
+            });
+            ptr::null_mut()
+        }
+    }
+}
+
+wasm_declare_copy!(wasm_module_t, wasm_module_delete, wasm_module_copy);
@@ -0,0 +1,612 @@
+//! Describe values, functions, globals, tables, memories, imports and exports.
+
+use super::{wasm_name_t, wasm_vec_t};
+use std::ptr;
+use wasmer_runtime_core::{
+    module::ExternDescriptor,
+    types::{FuncSig, GlobalDescriptor, MemoryDescriptor, TableDescriptor, Type},
+};
+
+pub type wasm_valkind_t = u8;
+
+pub const WASM_I32: wasm_valkind_t = 0;
+pub const WASM_I64: wasm_valkind_t = 1;
+pub const WASM_F32: wasm_valkind_t = 2;
+pub const WASM_F64: wasm_valkind_t = 3;
+pub const WASM_ANYREF: wasm_valkind_t = 128;
+pub const WASM_FUNCREF: wasm_valkind_t = 129;
+
+pub type wasm_mutability_t = u8;
+
+pub const WASM_CONST: wasm_mutability_t = 0;
+pub const WASM_VAR: wasm_mutability_t = 1;
+
+pub type wasm_externkind_t = u8;
+
+pub const WASM_EXTERN_FUNC: wasm_externkind_t = 0;
+pub const WASM_EXTERN_GLOBAL: wasm_externkind_t = 1;
+pub const WASM_EXTERN_TABLE: wasm_externkind_t = 2;
+pub const WASM_EXTERN_MEMORY: wasm_externkind_t = 3;
+
+/// The `max` of limits without a maximum.
+const LIMITS_MAX_DEFAULT: u32 = 0xffff_ffff;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct wasm_limits_t {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl wasm_limits_t {
+    fn new(min: u32, max: Option<u32>) -> Self {
+        wasm_limits_t {
+            min,
+            max: max.unwrap_or(LIMITS_MAX_DEFAULT),
+        }
+    }
+
+    pub(crate) fn maximum(&self) -> Option<u32> {
+        if self.max == LIMITS_MAX_DEFAULT {
+            None
+        } else {
+            Some(self.max)
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct wasm_valtype_t {
+    pub(crate) kind: wasm_valkind_t,
+}
+
+impl wasm_valtype_t {
+    /// Returns the type of values of kind `kind`, unless it's a
+    /// reference.
+    pub(crate) fn value_type(kind: wasm_valkind_t) -> Option<Type> {
+        match kind {
+            WASM_I32 => Some(Type::I32),
+            WASM_I64 => Some(Type::I64),
+            WASM_F32 => Some(Type::F32),
+            WASM_F64 => Some(Type::F64),
+            _ => None,
+        }
+    }
+
+    /// Returns the kind of values of type `ty`, unless it's a `v128`.
+    pub(crate) fn kind(ty: Type) -> Option<wasm_valkind_t> {
+        match ty {
+            Type::I32 => Some(WASM_I32),
+            Type::I64 => Some(WASM_I64),
+            Type::F32 => Some(WASM_F32),
+            Type::F64 => Some(WASM_F64),
+            Type::V128 => None,
+        }
+    }
+
+    fn from_type(ty: Type) -> Option<Self> {
+        Some(wasm_valtype_t {
+            kind: Self::kind(ty)?,
+        })
+    }
+}
+
+pub type wasm_valtype_vec_t = wasm_vec_t<*mut wasm_valtype_t>;
+
+/// The type of an import or an export. The function, global, table
+/// and memory types are extern types of the matching kind.
+#[derive(Clone)]
+pub struct wasm_externtype_t {
+    pub(crate) ty: ExternType,
+}
+
+#[derive(Clone)]
+pub(crate) enum ExternType {
+    Func {
+        params: wasm_valtype_vec_t,
+        results: wasm_valtype_vec_t,
+    },
+    Global {
+        content: wasm_valtype_t,
+        mutability: wasm_mutability_t,
+    },
+    Table {
+        element: wasm_valtype_t,
+        limits: wasm_limits_t,
+    },
+    Memory {
+        limits: wasm_limits_t,
+    },
+}
+
+pub type wasm_functype_t = wasm_externtype_t;
+pub type wasm_globaltype_t = wasm_externtype_t;
+pub type wasm_tabletype_t = wasm_externtype_t;
+pub type wasm_memorytype_t = wasm_externtype_t;
+
+impl wasm_externtype_t {
+    fn new(ty: ExternType) -> *mut Self {
+        Box::into_raw(Box::new(wasm_externtype_t { ty }))
+    }
+
+    pub(crate) fn from_sig(sig: &FuncSig) -> Option<Self> {
+        let valtypes = |types: &[Type]| -> Option<wasm_valtype_vec_t> {
+            let valtypes: Option<Vec<_>> = types
+                .iter()
+                .map(|ty| {
+                    wasm_valtype_t::from_type(*ty).map(|valtype| Box::into_raw(Box::new(valtype)))
+                })
+                .collect();
+            Some(wasm_vec_t::new(valtypes?))
+        };
+        Some(wasm_externtype_t {
+            ty: ExternType::Func {
+                params: valtypes(sig.params())?,
+                results: valtypes(sig.returns())?,
+            },
+        })
+    }
+
+    pub(crate) fn from_global_descriptor(desc: &GlobalDescriptor) -> Option<Self> {
+        Some(wasm_externtype_t {
+            ty: ExternType::Global {
+                content: wasm_valtype_t::from_type(desc.ty)?,
+                mutability: if desc.mutable { WASM_VAR } else { WASM_CONST },
+            },
+        })
+    }
+
+    pub(crate) fn from_table_descriptor(desc: &TableDescriptor) -> Self {
+        wasm_externtype_t {
+            ty: ExternType::Table {
+                element: wasm_valtype_t { kind: WASM_FUNCREF },
+                limits: wasm_limits_t::new(desc.minimum, desc.maximum),
+            },
+        }
+    }
+
+    pub(crate) fn from_memory_descriptor(desc: &MemoryDescriptor) -> Self {
+        wasm_externtype_t {
+            ty: ExternType::Memory {
+                limits: wasm_limits_t::new(desc.minimum.0, desc.maximum.map(|pages| pages.0)),
+            },
+        }
+    }
+
+    /// Returns the type of an import or an export, unless it has a
+    /// `v128` value.
+    pub(crate) fn from_descriptor(desc: &ExternDescriptor) -> Option<Self> {
+        match desc {
+            ExternDescriptor::Function(sig) => Self::from_sig(sig),
+            ExternDescriptor::Global(desc) => Self::from_global_descriptor(desc),
+            ExternDescriptor::Table(desc) => Some(Self::from_table_descriptor(desc)),
+            ExternDescriptor::Memory(desc) => Some(Self::from_memory_descriptor(desc)),
+        }
+    }
+
+    /// Returns the signature of a function type, unless it has a
+    /// reference.
+    pub(crate) fn func_sig(&self) -> Option<FuncSig> {
+        let types = |valtypes: &wasm_valtype_vec_t| -> Option<Vec<Type>> {
+            valtypes
+                .as_slice()
+                .iter()
+                .map(|valtype| wasm_valtype_t::value_type(unsafe { (**valtype).kind }))
+                .collect()
+        };
+        match &self.ty {
+            ExternType::Func { params, results } => {
+                Some(FuncSig::new(types(params)?, types(results)?))
+            }
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> wasm_externkind_t {
+        match self.ty {
+            ExternType::Func { .. } => WASM_EXTERN_FUNC,
+            ExternType::Global { .. } => WASM_EXTERN_GLOBAL,
+            ExternType::Table { .. } => WASM_EXTERN_TABLE,
+            ExternType::Memory { .. } => WASM_EXTERN_MEMORY,
+        }
+    }
+}
+
+/// Creates a value type of kind `kind`.
+#[no_mangle]
+pub extern "C" fn wasm_valtype_new(kind: wasm_valkind_t) -> *mut wasm_valtype_t {
+    Box::into_raw(Box::new(wasm_valtype_t { kind }))
+}
+
+/// Returns the kind of the value type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_valtype_kind(valtype: *const wasm_valtype_t) -> wasm_valkind_t {
+    (*valtype).kind
+}
+
+wasm_declare_copy!(wasm_valtype_t, wasm_valtype_delete, wasm_valtype_copy);
+wasm_declare_vec!(
+    *mut wasm_valtype_t,
+    wasm_valtype_vec_new_empty,
+    wasm_valtype_vec_new_uninitialized,
+    wasm_valtype_vec_new,
+    wasm_valtype_vec_copy,
+    wasm_valtype_vec_delete
+);
+
+/// Creates a function type, taking the ownership of the vectors of
+/// value types `params` and `results`.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_functype_new(
+    params: *mut wasm_valtype_vec_t,
+    results: *mut wasm_valtype_vec_t,
+) -> *mut wasm_functype_t {
+    wasm_externtype_t::new(ExternType::Func {
+        params: wasm_vec_t::take(params),
+        results: wasm_vec_t::take(results),
+    })
+}
+
+/// Returns the types of the parameters of the function type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_functype_params(
+    functype: *const wasm_functype_t,
+) -> *const wasm_valtype_vec_t {
+    match &(*functype).ty {
+        ExternType::Func { params, .. } => params,
+        _ => ptr::null(),
+    }
+}
+
+/// Returns the types of the results of the function type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_functype_results(
+    functype: *const wasm_functype_t,
+) -> *const wasm_valtype_vec_t {
+    match &(*functype).ty {
+        ExternType::Func { results, .. } => results,
+        _ => ptr::null(),
+    }
+}
+
+/// Creates a global type, taking the ownership of the value type
+/// `content`.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_globaltype_new(
+    content: *mut wasm_valtype_t,
+    mutability: wasm_mutability_t,
+) -> *mut wasm_globaltype_t {
+    wasm_externtype_t::new(ExternType::Global {
+        content: *Box::from_raw(content),
+        mutability,
+    })
+}
+
+/// Returns the type of the value of the global type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_globaltype_content(
+    globaltype: *const wasm_globaltype_t,
+) -> *const wasm_valtype_t {
+    match &(*globaltype).ty {
+        ExternType::Global { content, .. } => content,
+        _ => ptr::null(),
+    }
+}
+
+/// Returns whether the value of the global type can be set.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_globaltype_mutability(
+    globaltype: *const wasm_globaltype_t,
+) -> wasm_mutability_t {
+    match (*globaltype).ty {
+        ExternType::Global { mutability, .. } => mutability,
+        _ => WASM_CONST,
+    }
+}
+
+/// Creates a table type, taking the ownership of the value type
+/// `element`.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_tabletype_new(
+    element: *mut wasm_valtype_t,
+    limits: *const wasm_limits_t,
+) -> *mut wasm_tabletype_t {
+    wasm_externtype_t::new(ExternType::Table {
+        element: *Box::from_raw(element),
+        limits: *limits,
+    })
+}
+
+/// Returns the type of the elements of the table type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_tabletype_element(
+    tabletype: *const wasm_tabletype_t,
+) -> *const wasm_valtype_t {
+    match &(*tabletype).ty {
+        ExternType::Table { element, .. } => element,
+        _ => ptr::null(),
+    }
+}
+
+/// Returns the limits of the size of the table type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_tabletype_limits(
+    tabletype: *const wasm_tabletype_t,
+) -> *const wasm_limits_t {
+    match &(*tabletype).ty {
+        ExternType::Table { limits, .. } => limits,
+        _ => ptr::null(),
+    }
+}
+
+/// Creates a memory type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memorytype_new(
+    limits: *const wasm_limits_t,
+) -> *mut wasm_memorytype_t {
+    wasm_externtype_t::new(ExternType::Memory { limits: *limits })
+}
+
+/// Returns the limits of the size of the memory type, in pages.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memorytype_limits(
+    memorytype: *const wasm_memorytype_t,
+) -> *const wasm_limits_t {
+    match &(*memorytype).ty {
+        ExternType::Memory { limits } => limits,
+        _ => ptr::null(),
+    }
+}
+
+/// Returns the kind of the extern type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_externtype_kind(
+    externtype: *const wasm_externtype_t,
+) -> wasm_externkind_t {
+    (*externtype).kind()
+}
+
+/// Defines the casts between the extern types and the ones of a kind.
+macro_rules! wasm_externtype_casts {
+    ($kind:expr, $as_externtype:ident, $as_externtype_const:ident, $from_externtype:ident, $from_externtype_const:ident) => {
+        /// Returns the type as an extern type.
+        #[no_mangle]
+        pub extern "C" fn $as_externtype(ty: *mut wasm_externtype_t) -> *mut wasm_externtype_t {
+            ty
+        }
+
+        /// Returns the type as an extern type.
+        #[no_mangle]
+        pub extern "C" fn $as_externtype_const(
+            ty: *const wasm_externtype_t,
+        ) -> *const wasm_externtype_t {
+            ty
+        }
+
+        /// Returns the extern type as a type of its kind, or null if
+        /// it's of another kind.
+        #[no_mangle]
+        pub unsafe extern "C" fn $from_externtype(
+            externtype: *mut wasm_externtype_t,
+        ) -> *mut wasm_externtype_t {
+            if (*externtype).kind() == $kind {
+                externtype
+            } else {
+                ptr::null_mut()
+            }
+        }
+
+        /// Returns the extern type as a type of its kind, or null if
+        /// it's of another kind.
+        #[no_mangle]
+        pub unsafe extern "C" fn $from_externtype_const(
+            externtype: *const wasm_externtype_t,
+        ) -> *const wasm_externtype_t {
+            if (*externtype).kind() == $kind {
+                externtype
+            } else {
+                ptr::null()
+            }
+        }
+    };
+}
+
+wasm_externtype_casts!(
+    WASM_EXTERN_FUNC,
+    wasm_functype_as_externtype,
+    wasm_functype_as_externtype_const,
+    wasm_externtype_as_functype,
+    wasm_externtype_as_functype_const
+);
+wasm_externtype_casts!(
+    WASM_EXTERN_GLOBAL,
+    wasm_globaltype_as_externtype,
+    wasm_globaltype_as_externtype_const,
+    wasm_externtype_as_globaltype,
+    wasm_externtype_as_globaltype_const
+);
+wasm_externtype_casts!(
+    WASM_EXTERN_TABLE,
+    wasm_tabletype_as_externtype,
+    wasm_tabletype_as_externtype_const,
+    wasm_externtype_as_tabletype,
+    wasm_externtype_as_tabletype_const
+);
+wasm_externtype_casts!(
+    WASM_EXTERN_MEMORY,
+    wasm_memorytype_as_externtype,
+    wasm_memorytype_as_externtype_const,
+    wasm_externtype_as_memorytype,
+    wasm_externtype_as_memorytype_const
+);
+
+wasm_declare_copy!(
+    wasm_externtype_t,
+    wasm_externtype_delete,
+    wasm_externtype_copy
+);
+wasm_declare_vec!(
+    *mut wasm_externtype_t,
+    wasm_externtype_vec_new_empty,
+    wasm_externtype_vec_new_uninitialized,
+    wasm_externtype_vec_new,
+    wasm_externtype_vec_copy,
+    wasm_externtype_vec_delete
+);
+wasm_declare_copy!(wasm_functype_t, wasm_functype_delete, wasm_functype_copy);
+wasm_declare_vec!(
+    *mut wasm_functype_t,
+    wasm_functype_vec_new_empty,
+    wasm_functype_vec_new_uninitialized,
+    wasm_functype_vec_new,
+    wasm_functype_vec_copy,
+    wasm_functype_vec_delete
+);
+wasm_declare_copy!(
+    wasm_globaltype_t,
+    wasm_globaltype_delete,
+    wasm_globaltype_copy
+);
+wasm_declare_vec!(
+    *mut wasm_globaltype_t,
+    wasm_globaltype_vec_new_empty,
+    wasm_globaltype_vec_new_uninitialized,
+    wasm_globaltype_vec_new,
+    wasm_globaltype_vec_copy,
+    wasm_globaltype_vec_delete
+);
+wasm_declare_copy!(wasm_tabletype_t, wasm_tabletype_delete, wasm_tabletype_copy);
+wasm_declare_vec!(
+    *mut wasm_tabletype_t,
+    wasm_tabletype_vec_new_empty,
+    wasm_tabletype_vec_new_uninitialized,
+    wasm_tabletype_vec_new,
+    wasm_tabletype_vec_copy,
+    wasm_tabletype_vec_delete
+);
+wasm_declare_copy!(
+    wasm_memorytype_t,
+    wasm_memorytype_delete,
+    wasm_memorytype_copy
+);
+wasm_declare_vec!(
+    *mut wasm_memorytype_t,
+    wasm_memorytype_vec_new_empty,
+    wasm_memorytype_vec_new_uninitialized,
+    wasm_memorytype_vec_new,
+    wasm_memorytype_vec_copy,
+    wasm_memorytype_vec_delete
+);
+
+#[derive(Clone)]
+pub struct wasm_importtype_t {
+    pub(crate) module: wasm_name_t,
+    pub(crate) name: wasm_name_t,
+    pub(crate) ty: Box<wasm_externtype_t>,
+}
+
+/// Creates an import type, taking the ownership of its names and its
+/// type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_importtype_new(
+    module: *mut wasm_name_t,
+    name: *mut wasm_name_t,
+    ty: *mut wasm_externtype_t,
+) -> *mut wasm_importtype_t {
+    Box::into_raw(Box::new(wasm_importtype_t {
+        module: wasm_vec_t::take(module),
+        name: wasm_vec_t::take(name),
+        ty: Box::from_raw(ty),
+    }))
+}
+
+/// Returns the name of the module of the import type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_importtype_module(
+    importtype: *const wasm_importtype_t,
+) -> *const wasm_name_t {
+    &(*importtype).module
+}
+
+/// Returns the name of the import type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_importtype_name(
+    importtype: *const wasm_importtype_t,
+) -> *const wasm_name_t {
+    &(*importtype).name
+}
+
+/// Returns the type of the import.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_importtype_type(
+    importtype: *const wasm_importtype_t,
+) -> *const wasm_externtype_t {
+    &*(*importtype).ty
+}
+
+wasm_declare_copy!(
+    wasm_importtype_t,
+    wasm_importtype_delete,
+    wasm_importtype_copy
+);
+pub type wasm_importtype_vec_t = wasm_vec_t<*mut wasm_importtype_t>;
+
+wasm_declare_vec!(
+    *mut wasm_importtype_t,
+    wasm_importtype_vec_new_empty,
+    wasm_importtype_vec_new_uninitialized,
+    wasm_importtype_vec_new,
+    wasm_importtype_vec_copy,
+    wasm_importtype_vec_delete
+);
+
+#[derive(Clone)]
+pub struct wasm_exporttype_t {
+    pub(crate) name: wasm_name_t,
+    pub(crate) ty: Box<wasm_externtype_t>,
+}
+
+/// Creates an export type, taking the ownership of its name and its
+/// type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_exporttype_new(
+    name: *mut wasm_name_t,
+    ty: *mut wasm_externtype_t,
+) -> *mut wasm_exporttype_t {
+    Box::into_raw(Box::new(wasm_exporttype_t {
+        name: wasm_vec_t::take(name),
+        ty: Box::from_raw(ty),
+    }))
+}
+
+/// Returns the name of the export type.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_exporttype_name(
+    exporttype: *const wasm_exporttype_t,
+) -> *const wasm_name_t {
+    &(*exporttype).name
+}
+
+/// Returns the type of the export.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_exporttype_type(
+    exporttype: *const wasm_exporttype_t,
+) -> *const wasm_externtype_t {
+    &*(*exporttype).ty
+}
+
+wasm_declare_copy!(
+    wasm_exporttype_t,
+    wasm_exporttype_delete,
+    wasm_exporttype_copy
+);
+pub type wasm_exporttype_vec_t = wasm_vec_t<*mut wasm_exporttype_t>;
+
+wasm_declare_vec!(
+    *mut wasm_exporttype_t,
+    wasm_exporttype_vec_new_empty,
+    wasm_exporttype_vec_new_uninitialized,
+    wasm_exporttype_vec_new,
+    wasm_exporttype_vec_copy,
+    wasm_exporttype_vec_delete
+);
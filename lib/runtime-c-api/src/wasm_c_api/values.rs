@@ -0,0 +1,158 @@
+//! Convert values between the API and the runtime, and create and read traps.
+
+use super::{
+    types::{wasm_valkind_t, WASM_F32, WASM_F64, WASM_I32, WASM_I64},
+    wasm_message_t, wasm_store_t, wasm_vec_t, VecElement,
+};
+use std::ptr;
+use wasmer_runtime::Value;
+use wasmer_runtime_core::error::{CallError, Error, RuntimeError};
+
+/// A reference. References are not implemented yet.
+pub enum wasm_ref_t {}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union wasm_val_inner {
+    pub i32: i32,
+    pub i64: i64,
+    pub f32: f32,
+    pub f64: f64,
+    pub r#ref: *mut wasm_ref_t,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct wasm_val_t {
+    pub kind: wasm_valkind_t,
+    pub of: wasm_val_inner,
+}
+
+impl wasm_val_t {
+    /// Returns the value, unless it's a reference.
+    pub(crate) fn to_value(&self) -> Option<Value> {
+        unsafe {
+            match self.kind {
+                WASM_I32 => Some(Value::I32(self.of.i32)),
+                WASM_I64 => Some(Value::I64(self.of.i64)),
+                WASM_F32 => Some(Value::F32(self.of.f32)),
+                WASM_F64 => Some(Value::F64(self.of.f64)),
+                _ => None,
+            }
+        }
+    }
+
+    /// Converts `value`, unless it's a `v128`.
+    pub(crate) fn from_value(value: &Value) -> Option<Self> {
+        let (kind, of) = match *value {
+            Value::I32(i32) => (WASM_I32, wasm_val_inner { i32 }),
+            Value::I64(i64) => (WASM_I64, wasm_val_inner { i64 }),
+            Value::F32(f32) => (WASM_F32, wasm_val_inner { f32 }),
+            Value::F64(f64) => (WASM_F64, wasm_val_inner { f64 }),
+            Value::V128(_) => return None,
+        };
+        Some(wasm_val_t { kind, of })
+    }
+
+    /// Returns a zero value of kind `kind`.
+    pub(crate) fn zero(kind: wasm_valkind_t) -> Self {
+        wasm_val_t {
+            kind,
+            of: wasm_val_inner { i64: 0 },
+        }
+    }
+}
+
+impl VecElement for wasm_val_t {
+    fn uninitialized() -> Self {
+        wasm_val_t::zero(WASM_I32)
+    }
+
+    unsafe fn copy(&self) -> Self {
+        *self
+    }
+}
+
+/// Deletes the value. Values own nothing until references are
+/// implemented.
+#[no_mangle]
+pub extern "C" fn wasm_val_delete(_value: *mut wasm_val_t) {}
+
+/// Copies the value `value` to `out`.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_val_copy(out: *mut wasm_val_t, value: *const wasm_val_t) {
+    ptr::write(out, *value);
+}
+
+wasm_declare_vec!(
+    wasm_val_t,
+    wasm_val_vec_new_empty,
+    wasm_val_vec_new_uninitialized,
+    wasm_val_vec_new,
+    wasm_val_vec_copy,
+    wasm_val_vec_delete
+);
+
+/// A trap, returned by the calls which trapped, or by the host
+/// functions to trap.
+#[derive(Clone)]
+pub struct wasm_trap_t {
+    pub(crate) message: String,
+}
+
+impl wasm_trap_t {
+    pub(crate) fn new(message: String) -> *mut Self {
+        Box::into_raw(Box::new(wasm_trap_t { message }))
+    }
+
+    /// Returns the message of a trap, without the quotes around the
+    /// messages of host functions.
+    pub(crate) fn runtime_error_message(error: &RuntimeError) -> String {
+        match error {
+            RuntimeError::Error { data, .. } => match data.downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => error.to_string(),
+            },
+            _ => error.to_string(),
+        }
+    }
+
+    pub(crate) fn call_error_message(error: &CallError) -> String {
+        match error {
+            CallError::Runtime(error) => Self::runtime_error_message(error),
+            _ => error.to_string(),
+        }
+    }
+
+    pub(crate) fn error_message(error: &Error) -> String {
+        match error {
+            Error::RuntimeError(error) => Self::runtime_error_message(error),
+            Error::CallError(error) => Self::call_error_message(error),
+            _ => error.to_string(),
+        }
+    }
+}
+
+/// Creates a trap with the null-terminated message `message`.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_trap_new(
+    _store: *mut wasm_store_t,
+    message: *const wasm_message_t,
+) -> *mut wasm_trap_t {
+    let message = (*message).as_slice();
+    let message = match message.split_last() {
+        Some((0, message)) => message,
+        _ => message,
+    };
+    wasm_trap_t::new(String::from_utf8_lossy(message).into_owned())
+}
+
+/// Writes the message of the trap, null-terminated, to `out`.
+#[no_mangle]
+pub unsafe extern "C" fn wasm_trap_message(trap: *const wasm_trap_t, out: *mut wasm_message_t) {
+    let mut message = (*trap).message.clone().into_bytes();
+    message.push(0);
+    ptr::write(out, wasm_vec_t::new(message));
+}
+
+wasm_declare_copy!(wasm_trap_t, wasm_trap_delete, wasm_trap_copy);
@@ -0,0 +1,5 @@
+;; Custom sections are annotations, built with `wat2wasm --enable-annotations`.
+(module
+  (@custom "manifest" "{\"name\":\"hello\",\"version\":\"1.0.0\"}")
+  (@custom "other" "\01\02\03")
+  (@custom "manifest" "{\"extra\":true}"))
@@ -0,0 +1,6 @@
+(module
+  (global $counter (export "counter") (mut i32) (i32.const 1))
+  (global (export "limit") i64 (i64.const 100))
+
+  (func (export "get_counter") (result i32)
+      get_global $counter))
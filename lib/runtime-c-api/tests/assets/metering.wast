@@ -0,0 +1,15 @@
+(module
+  ;; Counts up to its param, paying for each iteration of the loop.
+  (func (export "count") (param i32) (result i32)
+    (local i32)
+    loop
+      get_local 1
+      i32.const 1
+      i32.add
+      set_local 1
+      get_local 1
+      get_local 0
+      i32.lt_s
+      br_if 0
+    end
+    get_local 1))
@@ -0,0 +1,13 @@
+(module
+  (func $double (param i32) (result i32)
+    get_local 0
+    i32.const 2
+    i32.mul)
+  (func (export "quadruple") (param i32) (result i32)
+    get_local 0
+    call $double
+    call $double)
+  (func (export "half") (param f32) (result f32)
+    get_local 0
+    f32.const 0.5
+    f32.mul))
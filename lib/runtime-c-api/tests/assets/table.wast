@@ -0,0 +1,14 @@
+(module
+  (type $i32_to_i32 (func (param i32) (result i32)))
+  (table (export "table") 2 anyfunc)
+  (elem (i32.const 0) $double)
+
+  (func $double (type $i32_to_i32)
+      get_local 0
+      i32.const 2
+      i32.mul)
+
+  (func (export "dispatch") (param i32 i32) (result i32)
+      get_local 1
+      get_local 0
+      call_indirect (type $i32_to_i32)))
@@ -0,0 +1,6 @@
+(module
+  (func $fail
+      unreachable)
+
+  (func (export "fail")
+      call $fail))
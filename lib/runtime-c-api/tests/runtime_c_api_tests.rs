@@ -4,7 +4,13 @@ use std::process::Command;
 fn test_c_api() {
     let project_tests_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests");
 
-    run_command("cmake", project_tests_dir, vec!["."]);
+    let metering = if cfg!(feature = "metering") {
+        "-DWASMER_METERING=ON"
+    } else {
+        "-DWASMER_METERING=OFF"
+    };
+
+    run_command("cmake", project_tests_dir, vec![".", metering]);
     run_command("make", project_tests_dir, vec!["-Wdev", "-Werror=dev"]);
     run_command("make", project_tests_dir, vec!["test", "ARGS=\"-V\""]);
 }
@@ -0,0 +1,62 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+int main()
+{
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/sum.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    wasmer_import_t imports[] = {};
+    wasmer_instance_t *instance = NULL;
+    wasmer_result_t compile_result = wasmer_instantiate(&instance, bytes, len, imports, 0);
+    printf("Compile result:  %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+
+    wasmer_call_frame_t *frame = NULL;
+    wasmer_result_t frame_result = wasmer_call_frame_new(&frame, instance, "sum");
+    printf("Frame result:  %d\n", frame_result);
+    assert(frame_result == WASMER_OK);
+
+    // The frame is reused across calls.
+    wasmer_value params[2];
+    wasmer_value results[1];
+    for (int32_t i = 0; i < 100; i++) {
+        params[0].I32 = i;
+        params[1].I32 = 7;
+        wasmer_result_t call_result = wasmer_call_frame_call(frame, params, 2, results, 1);
+        assert(call_result == WASMER_OK);
+        assert(results[0].I32 == i + 7);
+    }
+
+    // The numbers of values must match the signature.
+    wasmer_result_t call_result = wasmer_call_frame_call(frame, params, 1, results, 1);
+    assert(call_result == WASMER_ERROR);
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+    assert(strcmp(error_str, "the function takes 2 params and returns 1 results, not 1 and 1") == 0);
+    free(error_str);
+
+    wasmer_call_frame_t *missing_frame = NULL;
+    frame_result = wasmer_call_frame_new(&missing_frame, instance, "missing");
+    assert(frame_result == WASMER_ERROR);
+    assert(missing_frame == NULL);
+
+    printf("Destroy frame\n");
+    wasmer_call_frame_destroy(frame);
+    printf("Destroy instance\n");
+    wasmer_instance_destroy(instance);
+    free(bytes);
+    return 0;
+}
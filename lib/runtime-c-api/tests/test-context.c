@@ -44,6 +44,13 @@ int32_t get_counter(wasmer_instance_context_t *ctx) {
     return data->value;
 }
 
+static int finalized_counters = 0;
+
+void finalize_counter(void *data) {
+    finalized_counters += 1;
+    free(data);
+}
+
 counter_data *init_counter(int32_t value, int32_t amount) {
     counter_data* counter = malloc(sizeof(counter_data));
     counter->value = value;
@@ -125,11 +132,20 @@ int main()
     assert_counter(instance, 7);
     assert_counter(instance, 12);
     assert_counter(instance, 17);
+    assert(wasmer_instance_data_get(instance) == counter);
+
+    // Replace the counter with one finalized by the instance
+    counter_data *owned_counter = init_counter(1, 3);
+    wasmer_instance_context_data_set_with_finalizer(instance, owned_counter, finalize_counter);
+    assert(wasmer_instance_data_get(instance) == owned_counter);
+    assert_counter(instance, 4);
+    assert(finalized_counters == 0);
 
     // Clear resources
     wasmer_import_func_destroy(inc_func);
     wasmer_import_func_destroy(get_func);
     wasmer_instance_destroy(instance);
+    assert(finalized_counters == 1);
     free(counter);
     free(wasm_file.bytes);
 
@@ -0,0 +1,59 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+int main()
+{
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/custom_sections.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    wasmer_module_t *module = NULL;
+    wasmer_result_t compile_result = wasmer_compile(&module, bytes, len);
+    printf("Compile result:  %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+
+    int sections_len = wasmer_module_custom_sections(module, "manifest", NULL, 0);
+    printf("Sections len:  %d\n", sections_len);
+    assert(sections_len == 2);
+
+    wasmer_byte_array sections[2];
+    assert(wasmer_module_custom_sections(module, "manifest", sections, 2) == 2);
+    const char *manifest = "{\"name\":\"hello\",\"version\":\"1.0.0\"}";
+    assert(sections[0].bytes_len == strlen(manifest));
+    assert(memcmp(sections[0].bytes, manifest, sections[0].bytes_len) == 0);
+    const char *extra = "{\"extra\":true}";
+    assert(sections[1].bytes_len == strlen(extra));
+    assert(memcmp(sections[1].bytes, extra, sections[1].bytes_len) == 0);
+
+    // Only `sections_len` sections are written.
+    wasmer_byte_array other[1];
+    assert(wasmer_module_custom_sections(module, "manifest", other, 1) == 2);
+    assert(other[0].bytes == sections[0].bytes);
+    assert(wasmer_module_custom_sections(module, "other", other, 1) == 1);
+    assert(other[0].bytes_len == 3);
+    assert(other[0].bytes[2] == 3);
+
+    assert(wasmer_module_custom_sections(module, "missing", NULL, 0) == 0);
+
+    assert(wasmer_module_custom_sections(module, NULL, NULL, 0) == -1);
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+    assert(strcmp(error_str, "name ptr is null") == 0);
+    free(error_str);
+
+    printf("Destroy module\n");
+    wasmer_module_destroy(module);
+    free(bytes);
+    return 0;
+}
@@ -0,0 +1,81 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+void print_last_error()
+{
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+    free(error_str);
+}
+
+int main()
+{
+    assert(wasmer_last_error_kind() == WASMER_ERROR_KIND_NONE);
+
+    // Invalid bytes fail to compile.
+    uint8_t garbage[] = {0, 1, 2, 3};
+    wasmer_module_t *module = NULL;
+    wasmer_result_t compile_result = wasmer_compile(&module, garbage, 4);
+    assert(compile_result == WASMER_ERROR);
+    printf("Error kind: %d\n", wasmer_last_error_kind());
+    assert(wasmer_last_error_kind() == WASMER_ERROR_KIND_COMPILE);
+
+    // Reading the message clears the error.
+    print_last_error();
+    assert(wasmer_last_error_kind() == WASMER_ERROR_KIND_NONE);
+    assert(wasmer_last_error_length() == 0);
+
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/inc.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    compile_result = wasmer_compile(&module, bytes, len);
+    assert(compile_result == WASMER_OK);
+
+    // The module imports functions which aren't given.
+    wasmer_instance_t *instance = NULL;
+    wasmer_result_t instantiate_result = wasmer_module_instantiate(module, &instance, NULL, 0);
+    assert(instantiate_result == WASMER_ERROR);
+    printf("Error kind: %d\n", wasmer_last_error_kind());
+    assert(wasmer_last_error_kind() == WASMER_ERROR_KIND_LINK);
+    print_last_error();
+    wasmer_module_destroy(module);
+    free(bytes);
+
+    file = fopen("assets/sum.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    len = ftell(file);
+    bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    wasmer_import_t imports[] = {};
+    wasmer_result_t result = wasmer_instantiate(&instance, bytes, len, imports, 0);
+    assert(result == WASMER_OK);
+
+    // The function doesn't exist.
+    wasmer_value_t params[] = {};
+    wasmer_value_t results[] = {};
+    wasmer_result_t call_result = wasmer_instance_call(instance, "missing", params, 0, results, 0);
+    assert(call_result == WASMER_ERROR);
+    printf("Error kind: %d\n", wasmer_last_error_kind());
+    assert(wasmer_last_error_kind() == WASMER_ERROR_KIND_OTHER);
+    print_last_error();
+    assert(wasmer_last_error_kind() == WASMER_ERROR_KIND_NONE);
+
+    wasmer_instance_destroy(instance);
+    free(bytes);
+    return 0;
+}
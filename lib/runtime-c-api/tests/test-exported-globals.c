@@ -0,0 +1,97 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+int main()
+{
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/globals.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    wasmer_import_t imports[] = {};
+    wasmer_instance_t *instance = NULL;
+    wasmer_result_t compile_result = wasmer_instantiate(&instance, bytes, len, imports, 0);
+    printf("Compile result: %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+
+    // Enumerate the exported globals.
+    wasmer_exports_t *exports = NULL;
+    wasmer_instance_exports(instance, &exports);
+    int exports_len = wasmer_exports_len(exports);
+    assert(exports_len == 3);
+
+    wasmer_global_t *counter = NULL;
+    wasmer_global_t *limit = NULL;
+    for (int i = 0; i < exports_len; i++) {
+        wasmer_export_t *export = wasmer_exports_get(exports, i);
+        if (wasmer_export_kind(export) != WASM_GLOBAL) {
+            continue;
+        }
+
+        wasmer_byte_array name = wasmer_export_name(export);
+        printf("Global: %.*s\n", (int) name.bytes_len, name.bytes);
+        wasmer_global_t *global = NULL;
+        wasmer_result_t export_to_global_result = wasmer_export_to_global(export, &global);
+        assert(export_to_global_result == WASMER_OK);
+        if (name.bytes_len == 7 && memcmp(name.bytes, "counter", 7) == 0) {
+            counter = global;
+        } else {
+            limit = global;
+        }
+    }
+    assert(counter != NULL && limit != NULL);
+
+    // Read the globals.
+    wasmer_global_descriptor_t counter_desc = wasmer_global_get_descriptor(counter);
+    assert(counter_desc.mutable_);
+    assert(counter_desc.kind == WASM_I32);
+    assert(wasmer_global_get(counter).value.I32 == 1);
+
+    wasmer_global_descriptor_t limit_desc = wasmer_global_get_descriptor(limit);
+    assert(!limit_desc.mutable_);
+    assert(limit_desc.kind == WASM_I64);
+    assert(wasmer_global_get(limit).value.I64 == 100);
+
+    // Write the mutable global, as seen by the instance.
+    wasmer_value_t value;
+    value.tag = WASM_I32;
+    value.value.I32 = 42;
+    wasmer_result_t set_result = wasmer_global_set(counter, value);
+    assert(set_result == WASMER_OK);
+
+    wasmer_value_t params[] = {};
+    wasmer_value_t results[1];
+    wasmer_result_t call_result = wasmer_instance_call(instance, "get_counter", params, 0, results, 1);
+    assert(call_result == WASMER_OK);
+    assert(results[0].value.I32 == 42);
+
+    // Err, write the immutable global.
+    value.tag = WASM_I64;
+    value.value.I64 = 200;
+    set_result = wasmer_global_set(limit, value);
+    assert(set_result == WASMER_ERROR);
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+    assert(0 == strcmp(error_str, "the global is immutable"));
+    free(error_str);
+    assert(wasmer_global_get(limit).value.I64 == 100);
+
+    wasmer_global_destroy(counter);
+    wasmer_global_destroy(limit);
+    wasmer_exports_destroy(exports);
+
+    printf("Destroy instance\n");
+    wasmer_instance_destroy(instance);
+    free(bytes);
+    return 0;
+}
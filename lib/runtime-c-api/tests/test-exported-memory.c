@@ -4,6 +4,24 @@
 #include <stdint.h>
 #include <string.h>
 
+typedef struct {
+    uint32_t max_pages;
+    int calls;
+    int finalized;
+} grow_env;
+
+bool limit_growth(void *env, uint32_t current_pages, uint32_t requested_pages)
+{
+    grow_env *limit = env;
+    limit->calls += 1;
+    return requested_pages <= limit->max_pages;
+}
+
+void finalize(void *env)
+{
+    ((grow_env *) env)->finalized += 1;
+}
+
 int main()
 {
     // Read the wasm file bytes
@@ -67,8 +85,32 @@ int main()
     printf("Returned string from Wasm: %s\n", returned_string);
     assert(strcmp("Hello, World!", (const char *) returned_string) == 0);
 
+    // Grow the memory of the instance, up to 20 pages.
+    grow_env env = {
+        .max_pages = 20,
+        .calls = 0,
+        .finalized = 0,
+    };
+    wasmer_memory_set_grow_callback(memory, limit_growth, &env, finalize);
+
+    uint32_t previous_length = 0;
+    wasmer_result_t grow_result = wasmer_memory_grow_from(memory, 2, &previous_length);
+    assert(grow_result == WASMER_OK);
+    assert(previous_length == 17);
+    assert(wasmer_memory_length(memory) == 19);
+
+    grow_result = wasmer_memory_grow(memory, 2);
+    assert(grow_result == WASMER_ERROR);
+    assert(wasmer_memory_length(memory) == 19);
+    assert(env.calls == 2);
+
+    printf("Destroy memory\n");
+    wasmer_memory_destroy(memory);
+    assert(env.finalized == 0);
+
     printf("Destroy instance\n");
     wasmer_instance_destroy(instance);
+    assert(env.finalized == 1);
 
     return 0;
 }
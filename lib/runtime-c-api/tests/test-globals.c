@@ -16,7 +16,8 @@ int main()
     wasmer_value_t val2;
     val2.tag = WASM_I32;
     val2.value.I32 = 14;
-    wasmer_global_set(global, val2);
+    wasmer_result_t set_result = wasmer_global_set(global, val2);
+    assert(set_result == WASMER_OK);
 
     wasmer_value_t new_get_val = wasmer_global_get(global);
     assert( new_get_val.value.I32 == 14);
@@ -25,6 +26,14 @@ int main()
     assert(desc.mutable_);
     assert(desc.kind == WASM_I32);
 
+    // Err, set a value of another type
+    wasmer_value_t val3;
+    val3.tag = WASM_I64;
+    val3.value.I64 = 21;
+    set_result = wasmer_global_set(global, val3);
+    assert(set_result == WASMER_ERROR);
+    assert(wasmer_global_get(global).value.I32 == 14);
+
     wasmer_global_destroy(global);
     return 0;
 }
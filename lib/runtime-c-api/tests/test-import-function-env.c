@@ -0,0 +1,131 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef struct {
+    int32_t counter;
+    bool fail;
+    int finalized;
+} counter_env;
+
+wasmer_result_t inc(wasmer_instance_context_t *ctx, void *env, const wasmer_value_t *params, unsigned int params_len, wasmer_value_t *results, unsigned int results_len)
+{
+    counter_env *counter = env;
+    if (counter->fail) {
+        return WASMER_ERROR;
+    }
+    counter->counter += 1;
+    return WASMER_OK;
+}
+
+wasmer_result_t mul(wasmer_instance_context_t *ctx, void *env, const wasmer_value_t *params, unsigned int params_len, wasmer_value_t *results, unsigned int results_len)
+{
+    ((counter_env *) env)->counter *= 2;
+    return WASMER_OK;
+}
+
+wasmer_result_t get(wasmer_instance_context_t *ctx, void *env, const wasmer_value_t *params, unsigned int params_len, wasmer_value_t *results, unsigned int results_len)
+{
+    assert(params_len == 0);
+    assert(results_len == 1);
+    assert(results[0].tag == WASM_I32);
+    results[0].value.I32 = ((counter_env *) env)->counter;
+    return WASMER_OK;
+}
+
+void finalize(void *env)
+{
+    ((counter_env *) env)->finalized += 1;
+}
+
+wasmer_import_t func_import(const char *name, wasmer_import_func_t *func)
+{
+    wasmer_import_t import;
+    import.module_name.bytes = (const uint8_t *) "env";
+    import.module_name.bytes_len = 3;
+    import.import_name.bytes = (const uint8_t *) name;
+    import.import_name.bytes_len = strlen(name);
+    import.tag = WASM_FUNCTION;
+    import.value.func = func;
+    return import;
+}
+
+int main()
+{
+    counter_env env = {
+        .counter = 0,
+        .fail = false,
+        .finalized = 0,
+    };
+
+    wasmer_value_tag no_sig[] = {};
+    wasmer_value_tag i32_sig[] = {WASM_I32};
+    wasmer_import_func_t *inc_func = wasmer_import_func_new_with_env(inc, &env, finalize, no_sig, 0, no_sig, 0);
+    wasmer_import_func_t *mul_func = wasmer_import_func_new_with_env(mul, &env, finalize, no_sig, 0, no_sig, 0);
+    wasmer_import_func_t *get_func = wasmer_import_func_new_with_env(get, &env, finalize, no_sig, 0, i32_sig, 1);
+
+    // Only two integers are returned in registers.
+    wasmer_value_tag three_returns_sig[] = {WASM_I32, WASM_I32, WASM_I64};
+    assert(wasmer_import_func_new_with_env(get, &env, finalize, no_sig, 0, three_returns_sig, 3) == NULL);
+
+    wasmer_import_t imports[] = {
+        func_import("inc", inc_func),
+        func_import("mul", mul_func),
+        func_import("get", get_func),
+    };
+
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/inc.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    printf("Instantiating\n");
+    wasmer_instance_t *instance = NULL;
+    wasmer_result_t compile_result = wasmer_instantiate(&instance, bytes, len, imports, 3);
+    printf("Compile result:  %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+
+    wasmer_value_t params[] = {};
+    wasmer_value_t results[1];
+    wasmer_result_t call_result = wasmer_instance_call(instance, "inc_and_get", params, 0, results, 1);
+    printf("Call result:  %d\n", call_result);
+    assert(call_result == WASMER_OK);
+    assert(results[0].value.I32 == 1);
+
+    call_result = wasmer_instance_call(instance, "mul_and_get", params, 0, results, 1);
+    assert(call_result == WASMER_OK);
+    assert(results[0].value.I32 == 2);
+    assert(env.counter == 2);
+
+    // A callback returning an error traps.
+    env.fail = true;
+    call_result = wasmer_instance_call(instance, "inc_and_get", params, 0, results, 1);
+    assert(call_result == WASMER_ERROR);
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+    assert(strstr(error_str, "the imported func failed") != NULL);
+    free(error_str);
+
+    // The environments are finalized once the funcs and the instance
+    // are destroyed.
+    printf("Destroying funcs\n");
+    wasmer_import_func_destroy(inc_func);
+    wasmer_import_func_destroy(mul_func);
+    wasmer_import_func_destroy(get_func);
+    assert(env.finalized == 0);
+
+    printf("Destroy instance\n");
+    wasmer_instance_destroy(instance);
+    assert(env.finalized == 3);
+    free(bytes);
+    return 0;
+}
@@ -0,0 +1,95 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+void print_last_error()
+{
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+    free(error_str);
+}
+
+wasmer_result_t count(wasmer_instance_t *instance, int32_t n)
+{
+    wasmer_value_t params[] = {{.tag = WASM_I32, .value.I32 = n}};
+    wasmer_value_t results[] = {{.tag = WASM_I32, .value.I32 = 0}};
+    wasmer_result_t call_result = wasmer_instance_call(instance, "count", params, 1, results, 1);
+    if (call_result == WASMER_OK) {
+        assert(results[0].value.I32 == n);
+    }
+    return call_result;
+}
+
+int main()
+{
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/metering.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    wasmer_module_t *module = NULL;
+    assert(wasmer_compile_with_gas_metering(&module, NULL, 0) == WASMER_ERROR);
+    print_last_error();
+    wasmer_result_t compile_result = wasmer_compile_with_gas_metering(&module, bytes, len);
+    assert(compile_result == WASMER_OK);
+
+    // The gas limit can't exceed the one the module is compiled with.
+    wasmer_instance_t *instance = NULL;
+    wasmer_result_t instantiate_result = wasmer_module_instantiate_with_gas_limit(module, &instance, NULL, 0, UINT64_MAX);
+    assert(instantiate_result == WASMER_ERROR);
+    assert(instance == NULL);
+    print_last_error();
+
+    instantiate_result = wasmer_module_instantiate_with_gas_limit(module, &instance, NULL, 0, 1000);
+    assert(instantiate_result == WASMER_OK);
+    assert(wasmer_instance_get_gas_limit(instance) == 1000);
+    assert(wasmer_instance_get_points_used(instance) == 0);
+    assert(wasmer_instance_get_points_remaining(instance) == 1000);
+
+    assert(count(instance, 10) == WASMER_OK);
+    uint64_t used = wasmer_instance_get_points_used(instance);
+    printf("Points used: %llu\n", (unsigned long long) used);
+    assert(used > 0 && used < 1000);
+    assert(wasmer_instance_get_points_remaining(instance) == 1000 - used);
+
+    // The points add up across calls, until the gas is exhausted.
+    assert(count(instance, 10) == WASMER_OK);
+    assert(wasmer_instance_get_points_used(instance) == 2 * used);
+    assert(count(instance, 1000000) == WASMER_GAS_EXHAUSTED);
+    printf("Error kind: %d\n", wasmer_last_error_kind());
+    assert(wasmer_last_error_kind() == WASMER_ERROR_KIND_GAS_EXHAUSTED);
+    print_last_error();
+    assert(wasmer_instance_get_points_used(instance) >= 1000);
+    assert(wasmer_instance_get_points_remaining(instance) == 0);
+    assert(count(instance, 10) == WASMER_GAS_EXHAUSTED);
+    print_last_error();
+
+    // Resetting the points used lets the instance run again.
+    wasmer_instance_set_points_used(instance, 0);
+    assert(count(instance, 10) == WASMER_OK);
+    assert(wasmer_instance_get_points_used(instance) == used);
+
+    // Changing the gas limit keeps the points used.
+    assert(wasmer_instance_set_gas_limit(instance, UINT64_MAX) == WASMER_ERROR);
+    print_last_error();
+    assert(wasmer_instance_get_gas_limit(instance) == 1000);
+    assert(wasmer_instance_set_gas_limit(instance, 100000000) == WASMER_OK);
+    assert(wasmer_instance_get_gas_limit(instance) == 100000000);
+    assert(wasmer_instance_get_points_used(instance) == used);
+    assert(wasmer_instance_get_points_remaining(instance) == 100000000 - used);
+    assert(count(instance, 1000000) == WASMER_OK);
+
+    wasmer_instance_destroy(instance);
+    wasmer_module_destroy(module);
+    free(bytes);
+    return 0;
+}
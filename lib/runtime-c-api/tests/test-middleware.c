@@ -0,0 +1,130 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+// The entries of the traced functions are recorded as their index,
+// and their exits as the opposite of their index plus one.
+typedef struct {
+    int32_t events[16];
+    int32_t args[16];
+    int events_len;
+    int args_len;
+} trace_env;
+
+void enter(void *env, wasmer_instance_context_t *ctx, uint32_t func_index, const wasmer_value_t *args, unsigned int args_len)
+{
+    trace_env *trace = env;
+    assert(args_len == 1);
+    assert(args[0].tag == WASM_I32);
+    trace->events[trace->events_len++] = func_index;
+    trace->args[trace->args_len++] = args[0].value.I32;
+}
+
+void exit_func(void *env, wasmer_instance_context_t *ctx, uint32_t func_index)
+{
+    trace_env *trace = env;
+    trace->events[trace->events_len++] = -(int32_t) func_index - 1;
+}
+
+void print_last_error()
+{
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+    free(error_str);
+}
+
+wasmer_result_t quadruple(wasmer_instance_t *instance, int32_t n)
+{
+    wasmer_value_t params[] = {{.tag = WASM_I32, .value.I32 = n}};
+    wasmer_value_t results[] = {{.tag = WASM_I32, .value.I32 = 0}};
+    wasmer_result_t call_result = wasmer_instance_call(instance, "quadruple", params, 1, results, 1);
+    if (call_result == WASMER_OK) {
+        assert(results[0].value.I32 == 4 * n);
+    }
+    return call_result;
+}
+
+int main()
+{
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/middleware.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    wasmer_module_t *module = NULL;
+    assert(wasmer_compile_with_middlewares(&module, bytes, len, NULL) == WASMER_ERROR);
+    print_last_error();
+
+    // The functions are traced, and metered.
+    trace_env trace = {.events_len = 0, .args_len = 0};
+    wasmer_middleware_chain_t *chain = wasmer_middleware_chain_new();
+    wasmer_middleware_chain_push_call_trace(chain, enter, exit_func, &trace, true);
+    wasmer_middleware_chain_push_metering(chain);
+    wasmer_result_t compile_result = wasmer_compile_with_middlewares(&module, bytes, len, chain);
+    assert(compile_result == WASMER_OK);
+    // The module doesn't need the chain.
+    wasmer_middleware_chain_destroy(chain);
+
+    wasmer_instance_t *instance = NULL;
+    wasmer_result_t instantiate_result = wasmer_module_instantiate_with_gas_limit(module, &instance, NULL, 0, 1000);
+    assert(instantiate_result == WASMER_OK);
+    assert(quadruple(instance, 3) == WASMER_OK);
+
+    int32_t expected_events[] = {1, 0, -1, 0, -1, -2};
+    int32_t expected_args[] = {3, 3, 6};
+    assert(trace.events_len == 6);
+    assert(memcmp(trace.events, expected_events, sizeof(expected_events)) == 0);
+    assert(trace.args_len == 3);
+    assert(memcmp(trace.args, expected_args, sizeof(expected_args)) == 0);
+    uint64_t used = wasmer_instance_get_points_used(instance);
+    printf("Points used: %llu\n", (unsigned long long) used);
+    assert(used > 0 && used < 1000);
+
+    wasmer_instance_destroy(instance);
+    wasmer_module_destroy(module);
+
+    // A module using forbidden instructions fails to compile.
+    wasmer_opcode_class_t float_class[] = {WASMER_OPCODE_CLASS_FLOAT};
+    chain = wasmer_middleware_chain_new();
+    assert(wasmer_middleware_chain_push_opcode_filter(chain, NULL, 1, false) == WASMER_ERROR);
+    print_last_error();
+    assert(wasmer_middleware_chain_push_opcode_filter(chain, float_class, 1, false) == WASMER_OK);
+    compile_result = wasmer_compile_with_middlewares(&module, bytes, len, chain);
+    assert(compile_result == WASMER_ERROR);
+    printf("Error kind: %d\n", wasmer_last_error_kind());
+    assert(wasmer_last_error_kind() == WASMER_ERROR_KIND_COMPILE);
+    print_last_error();
+    wasmer_middleware_chain_destroy(chain);
+
+    // Unless they trap, only once executed.
+    chain = wasmer_middleware_chain_new();
+    assert(wasmer_middleware_chain_push_opcode_filter(chain, float_class, 1, true) == WASMER_OK);
+    compile_result = wasmer_compile_with_middlewares(&module, bytes, len, chain);
+    assert(compile_result == WASMER_OK);
+    wasmer_middleware_chain_destroy(chain);
+
+    instantiate_result = wasmer_module_instantiate(module, &instance, NULL, 0);
+    assert(instantiate_result == WASMER_OK);
+    assert(quadruple(instance, 5) == WASMER_OK);
+    wasmer_value_t params[] = {{.tag = WASM_F32, .value.F32 = 3.0}};
+    wasmer_value_t results[] = {{.tag = WASM_F32, .value.F32 = 0.0}};
+    wasmer_result_t call_result = wasmer_instance_call(instance, "half", params, 1, results, 1);
+    assert(call_result == WASMER_ERROR);
+    printf("Error kind: %d\n", wasmer_last_error_kind());
+    assert(wasmer_last_error_kind() == WASMER_ERROR_KIND_RUNTIME_TRAP);
+    print_last_error();
+
+    wasmer_instance_destroy(instance);
+    wasmer_module_destroy(module);
+    free(bytes);
+    return 0;
+}
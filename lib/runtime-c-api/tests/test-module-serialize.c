@@ -2,6 +2,8 @@
 #include "../wasmer.h"
 #include <assert.h>
 #include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
 
 int main()
 {
@@ -63,31 +65,43 @@ int main()
     assert(results[0].value.I32 == 15);
     assert(call_result == WASMER_OK);
 
+    // The serialized module copies the bytes it is created from.
+    uint8_t *serialized_bytes_copy = malloc(serialized_module_bytes.bytes_len);
+    memcpy(serialized_bytes_copy, serialized_module_bytes.bytes, serialized_module_bytes.bytes_len);
+
     wasmer_serialized_module_t *serialized_module_two = NULL;
     wasmer_result_t serialized_module_from_bytes_result = wasmer_serialized_module_from_bytes(
         &serialized_module_two,
-        serialized_module_bytes.bytes,
+        serialized_bytes_copy,
         serialized_module_bytes.bytes_len
     );
     assert(serialized_module_from_bytes_result == WASMER_OK);
+    free(serialized_bytes_copy);
+
+    printf("Destroy the serialized modules\n");
+    wasmer_serialized_module_destroy(serialized_module);
 
     wasmer_module_t *module_three = NULL;
     wasmer_result_t unserialized_result_two = wasmer_module_deserialize(&module_three, serialized_module_two);
     assert(unserialized_result_two == WASMER_OK);
 
+    wasmer_serialized_module_destroy(serialized_module_two);
+
     wasmer_instance_t *instance_two = NULL;
-    wasmer_result_t instantiate_result_two = wasmer_module_instantiate(module_three, &instance, imports, 0);
+    wasmer_result_t instantiate_result_two = wasmer_module_instantiate(module_three, &instance_two, imports, 0);
     assert(instantiate_result_two == WASMER_OK);
 
-    printf("Destroy the serialized module\n");
-    wasmer_serialized_module_destroy(serialized_module);
-    wasmer_serialized_module_destroy(serialized_module_two);
+    wasmer_result_t call_result_two = wasmer_instance_call(instance_two, "sum", params, 2, results, 1);
+    assert(call_result_two == WASMER_OK);
+    assert(results[0].value.I32 == 15);
 
-    printf("Destroy instance\n");
+    printf("Destroy instances\n");
     wasmer_instance_destroy(instance);
+    wasmer_instance_destroy(instance_two);
 
     printf("Destroy modules\n");
     wasmer_module_destroy(module_one);
     wasmer_module_destroy(module_two);
+    wasmer_module_destroy(module_three);
     return 0;
 }
@@ -0,0 +1,100 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+int32_t triple(wasmer_instance_context_t *ctx, int32_t x)
+{
+    return x * 3;
+}
+
+int32_t dispatch(wasmer_instance_t *instance, int32_t index, int32_t x)
+{
+    wasmer_value_t params[] = {
+        {.tag = WASM_I32, .value.I32 = index},
+        {.tag = WASM_I32, .value.I32 = x},
+    };
+    wasmer_value_t results[1];
+    wasmer_result_t call_result = wasmer_instance_call(instance, "dispatch", params, 2, results, 1);
+    assert(call_result == WASMER_OK);
+    return results[0].value.I32;
+}
+
+int main()
+{
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/table.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    wasmer_import_t imports[] = {};
+    wasmer_instance_t *instance = NULL;
+    wasmer_result_t compile_result = wasmer_instantiate(&instance, bytes, len, imports, 0);
+    printf("Compile result: %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+    assert(dispatch(instance, 0, 21) == 42);
+
+    // Get the `table` export.
+    wasmer_exports_t *exports = NULL;
+    wasmer_instance_exports(instance, &exports);
+    wasmer_export_t *export = wasmer_exports_get(exports, 0);
+    assert(wasmer_export_kind(export) == WASM_TABLE);
+
+    wasmer_table_t *table = NULL;
+    wasmer_result_t export_to_table_result = wasmer_export_to_table(export, &table);
+    assert(export_to_table_result == WASMER_OK);
+    assert(wasmer_table_length(table) == 2);
+
+    // Copy the guest func to the null element.
+    wasmer_import_func_t *double_func = NULL;
+    wasmer_result_t get_result = wasmer_table_get(table, 1, &double_func);
+    assert(get_result == WASMER_OK);
+    assert(double_func == NULL);
+
+    get_result = wasmer_table_get(table, 0, &double_func);
+    assert(get_result == WASMER_OK);
+    assert(double_func != NULL);
+    wasmer_result_t set_result = wasmer_table_set(table, 1, double_func);
+    assert(set_result == WASMER_OK);
+    wasmer_import_func_destroy(double_func);
+    assert(dispatch(instance, 1, 4) == 8);
+
+    // Insert a host func in a new element.
+    wasmer_result_t grow_result = wasmer_table_grow(table, 1);
+    assert(grow_result == WASMER_OK);
+    assert(wasmer_table_length(table) == 3);
+
+    wasmer_value_tag i32_sig[] = {WASM_I32};
+    wasmer_import_func_t *triple_func = wasmer_import_func_new((void (*)(void *)) triple, i32_sig, 1, i32_sig, 1);
+    set_result = wasmer_table_set(table, 2, triple_func);
+    assert(set_result == WASMER_OK);
+    wasmer_import_func_destroy(triple_func);
+    assert(dispatch(instance, 2, 5) == 15);
+
+    // Err, set out of the bounds of the table.
+    triple_func = wasmer_import_func_new((void (*)(void *)) triple, i32_sig, 1, i32_sig, 1);
+    set_result = wasmer_table_set(table, 3, triple_func);
+    assert(set_result == WASMER_ERROR);
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+    assert(0 == strcmp(error_str, "the index 3 is out of the bounds of the table"));
+    free(error_str);
+    wasmer_import_func_destroy(triple_func);
+
+    printf("Destroy table\n");
+    wasmer_table_destroy(table);
+    wasmer_exports_destroy(exports);
+
+    printf("Destroy instance\n");
+    wasmer_instance_destroy(instance);
+    free(bytes);
+    return 0;
+}
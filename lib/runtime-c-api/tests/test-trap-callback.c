@@ -0,0 +1,73 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef struct {
+    int calls;
+    wasmer_trap_code_t code;
+    char message[256];
+} trap_env;
+
+void on_trap(void *env, wasmer_trap_code_t code, const char *message, const wasmer_trap_frame_t *frames, unsigned int frames_len)
+{
+    trap_env *trap = env;
+    trap->calls += 1;
+    trap->code = code;
+    strncpy(trap->message, message, sizeof(trap->message) - 1);
+    printf("Trap: `%s`, %u frames\n", message, frames_len);
+    if (frames_len > 0) {
+        printf("In func %u\n", frames[0].func_index);
+    }
+}
+
+int main()
+{
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/trap.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    wasmer_import_t imports[] = {};
+    wasmer_instance_t *instance = NULL;
+    wasmer_result_t compile_result = wasmer_instantiate(&instance, bytes, len, imports, 0);
+    printf("Compile result: %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+
+    trap_env env = {
+        .calls = 0,
+        .message = {0},
+    };
+    wasmer_trap_callback_set(on_trap, &env);
+
+    // The callback is invoked before the call returns.
+    wasmer_value_t params[] = {};
+    wasmer_value_t results[] = {};
+    wasmer_result_t call_result = wasmer_instance_call(instance, "fail", params, 0, results, 0);
+    assert(call_result == WASMER_ERROR);
+    assert(env.calls == 1);
+    assert(env.code != WASMER_TRAP_CODE_HOST_ERROR);
+    assert(strlen(env.message) > 0);
+
+    // A call which fails without trapping isn't reported.
+    call_result = wasmer_instance_call(instance, "missing", params, 0, results, 0);
+    assert(call_result == WASMER_ERROR);
+    assert(env.calls == 1);
+
+    // Remove the callback.
+    wasmer_trap_callback_set(NULL, NULL);
+    call_result = wasmer_instance_call(instance, "fail", params, 0, results, 0);
+    assert(call_result == WASMER_ERROR);
+    assert(env.calls == 1);
+
+    printf("Destroy instance\n");
+    wasmer_instance_destroy(instance);
+    free(bytes);
+    return 0;
+}
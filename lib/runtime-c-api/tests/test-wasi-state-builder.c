@@ -0,0 +1,135 @@
+#include <stdio.h>
+#include <stdlib.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <string.h>
+
+static bool host_print_called = false;
+
+// Host function that will be imported into the Web Assembly Instance
+void host_print(const wasmer_instance_context_t *ctx, int32_t ptr, int32_t len)
+{
+    host_print_called = true;
+    const wasmer_memory_t *memory = wasmer_instance_context_memory(ctx, 0);
+    uint8_t *mem_bytes = wasmer_memory_data(memory);
+    printf("%.*s\n", len, mem_bytes + ptr);
+}
+
+// Collects what the WASI program writes to its stdout
+typedef struct {
+    char bytes[4096];
+    uint32_t len;
+} captured_output;
+
+void capture_stdout(void *data, const uint8_t *bytes, uint32_t bytes_len)
+{
+    captured_output *output = (captured_output *) data;
+    uint32_t room = sizeof(output->bytes) - 1 - output->len;
+    uint32_t len = bytes_len < room ? bytes_len : room;
+    memcpy(output->bytes + output->len, bytes, len);
+    output->len += len;
+    output->bytes[output->len] = 0;
+}
+
+// Use the last_error API to retrieve error messages
+void print_wasmer_error()
+{
+    int error_len = wasmer_last_error_length();
+    printf("Error len: `%d`\n", error_len);
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+}
+
+wasmer_byte_array byte_array(const char *str)
+{
+    wasmer_byte_array array;
+    array.bytes = (const uint8_t *) str;
+    array.bytes_len = strlen(str);
+    return array;
+}
+
+int main()
+{
+    // Configure the WASI state of the program
+    wasmer_wasi_state_builder_t *builder = wasmer_wasi_state_builder_new(byte_array("wasi_test_program"));
+    assert(builder);
+    wasmer_result_t arg_result = wasmer_wasi_state_builder_arg(builder, byte_array("--help"));
+    assert(arg_result == WASMER_OK);
+    wasmer_result_t env_result = wasmer_wasi_state_builder_env(builder, byte_array("COLOR"), byte_array("TRUE"));
+    assert(env_result == WASMER_OK);
+    wasmer_result_t map_dir_result =
+            wasmer_wasi_state_builder_map_dir(builder, byte_array("the_host_current_dir"), byte_array("."));
+    assert(map_dir_result == WASMER_OK);
+    captured_output output = { .len = 0 };
+    wasmer_result_t stdout_result = wasmer_wasi_state_builder_stdout(builder, capture_stdout, &output);
+    assert(stdout_result == WASMER_OK);
+
+    wasmer_import_object_t *import_object = wasmer_wasi_generate_import_object_from_builder(builder);
+    if (!import_object)
+    {
+        print_wasmer_error();
+    }
+    assert(import_object);
+    wasmer_wasi_state_builder_destroy(builder);
+
+    // Add the `host_print` function the program imports
+    wasmer_value_tag params_sig[] = {WASM_I32, WASM_I32};
+    wasmer_value_tag returns_sig[] = {};
+    wasmer_import_func_t *func = wasmer_import_func_new((void (*)(void *)) host_print, params_sig, 2, returns_sig, 0);
+    wasmer_import_t func_import;
+    func_import.module_name = byte_array("env");
+    func_import.import_name = byte_array("host_print");
+    func_import.tag = WASM_FUNCTION;
+    func_import.value.func = func;
+    wasmer_import_t imports[] = {func_import};
+    wasmer_import_object_extend(import_object, imports, 1);
+
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/extended_wasi.wasm", "r");
+    assert(file);
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    wasmer_module_t *module = NULL;
+    wasmer_result_t compile_result = wasmer_compile(&module, bytes, len);
+    printf("Compile result:  %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+
+    wasmer_instance_t *instance = NULL;
+    wasmer_result_t instantiate_result = wasmer_module_import_instantiate(&instance, module, import_object);
+    printf("Instantiate result:  %d\n", instantiate_result);
+    if (instantiate_result != WASMER_OK)
+    {
+        print_wasmer_error();
+    }
+    assert(instantiate_result == WASMER_OK);
+
+    // Run the program, and check what it printed
+    int32_t exit_code = -1;
+    wasmer_result_t start_result = wasmer_wasi_instance_start(instance, &exit_code);
+    printf("Start result:  %d, exit code: %d\n", start_result, exit_code);
+    if (start_result != WASMER_OK)
+    {
+        print_wasmer_error();
+    }
+    assert(start_result == WASMER_OK);
+    assert(exit_code == 0);
+    assert(host_print_called);
+    printf("Captured stdout:\n%s", output.bytes);
+    assert(strstr(output.bytes, "Found 2 args on program wasi_test_program"));
+    assert(strstr(output.bytes, "COLOR=TRUE"));
+
+    wasmer_import_func_destroy(func);
+    wasmer_instance_destroy(instance);
+    wasmer_import_object_destroy(import_object);
+    wasmer_module_destroy(module);
+    free(bytes);
+
+    return 0;
+}
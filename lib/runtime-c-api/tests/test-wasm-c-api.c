@@ -0,0 +1,118 @@
+#include <stdio.h>
+#include "../wasm.h"
+#include <assert.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+static bool finalized = false;
+
+wasm_trap_t *inc(void *env, const wasm_val_t args[], wasm_val_t results[])
+{
+    *(int32_t *) env += 1;
+    return NULL;
+}
+
+wasm_trap_t *mul(void *env, const wasm_val_t args[], wasm_val_t results[])
+{
+    *(int32_t *) env *= 2;
+    return NULL;
+}
+
+wasm_trap_t *get(void *env, const wasm_val_t args[], wasm_val_t results[])
+{
+    results[0].kind = WASM_I32;
+    results[0].of.i32 = *(int32_t *) env;
+    return NULL;
+}
+
+void finalize(void *env)
+{
+    finalized = true;
+}
+
+int main()
+{
+    // Read the Wasm file bytes.
+    FILE *file = fopen("assets/inc.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    fseek(file, 0, SEEK_SET);
+    wasm_byte_vec_t binary;
+    wasm_byte_vec_new_uninitialized(&binary, len);
+    fread(binary.data, 1, len, file);
+    fclose(file);
+
+    wasm_engine_t *engine = wasm_engine_new();
+    wasm_store_t *store = wasm_store_new(engine);
+
+    assert(wasm_module_validate(store, &binary));
+    wasm_module_t *module = wasm_module_new(store, &binary);
+    assert(module);
+    wasm_byte_vec_delete(&binary);
+
+    wasm_importtype_vec_t import_types;
+    wasm_module_imports(module, &import_types);
+    assert(import_types.size == 3);
+    const wasm_name_t *import_name = wasm_importtype_name(import_types.data[2]);
+    assert(import_name->size == 3 && memcmp(import_name->data, "get", 3) == 0);
+    const wasm_functype_t *get_type = wasm_externtype_as_functype_const(wasm_importtype_type(import_types.data[2]));
+    assert(wasm_functype_params(get_type)->size == 0);
+    assert(wasm_functype_results(get_type)->size == 1);
+    assert(wasm_valtype_kind(wasm_functype_results(get_type)->data[0]) == WASM_I32);
+    wasm_importtype_vec_delete(&import_types);
+
+    // Import the host functions, sharing a counter.
+    int32_t counter = 0;
+    wasm_functype_t *void_type = wasm_functype_new_0_0();
+    wasm_functype_t *i32_type = wasm_functype_new_0_1(wasm_valtype_new_i32());
+    wasm_func_t *inc_func = wasm_func_new_with_env(store, void_type, inc, &counter, finalize);
+    wasm_func_t *mul_func = wasm_func_new_with_env(store, void_type, mul, &counter, NULL);
+    wasm_func_t *get_func = wasm_func_new_with_env(store, i32_type, get, &counter, NULL);
+    wasm_functype_delete(void_type);
+    wasm_functype_delete(i32_type);
+    assert(wasm_func_result_arity(get_func) == 1);
+
+    const wasm_extern_t *imports[] = {
+        wasm_func_as_extern(inc_func),
+        wasm_func_as_extern(mul_func),
+        wasm_func_as_extern(get_func),
+    };
+    wasm_trap_t *trap = NULL;
+    wasm_instance_t *instance = wasm_instance_new(store, module, imports, &trap);
+    assert(instance);
+    assert(!trap);
+    wasm_func_delete(inc_func);
+    wasm_func_delete(mul_func);
+    wasm_func_delete(get_func);
+
+    wasm_extern_vec_t exports;
+    wasm_instance_exports(instance, &exports);
+    assert(exports.size == 2);
+    assert(wasm_extern_kind(exports.data[0]) == WASM_EXTERN_FUNC);
+
+    wasm_val_t results[1];
+    const wasm_func_t *inc_and_get = wasm_extern_as_func(exports.data[0]);
+    trap = wasm_func_call(inc_and_get, NULL, results);
+    assert(!trap);
+    printf("inc_and_get: %d\n", results[0].of.i32);
+    assert(results[0].kind == WASM_I32 && results[0].of.i32 == 1);
+
+    const wasm_func_t *mul_and_get = wasm_extern_as_func(exports.data[1]);
+    trap = wasm_func_call(mul_and_get, NULL, results);
+    assert(!trap);
+    printf("mul_and_get: %d\n", results[0].of.i32);
+    assert(results[0].kind == WASM_I32 && results[0].of.i32 == 2);
+    assert(counter == 2);
+
+    // The host functions live as long as the instance.
+    wasm_extern_vec_delete(&exports);
+    assert(!finalized);
+    wasm_instance_delete(instance);
+    assert(finalized);
+
+    wasm_module_delete(module);
+    wasm_store_delete(store);
+    wasm_engine_delete(engine);
+    return 0;
+}
@@ -36,11 +36,115 @@ enum wasmer_import_export_kind {
 };
 typedef uint32_t wasmer_import_export_kind;
 
+/**
+ * The kind of the last error, to tell the failures apart without
+ * parsing the message of `wasmer_last_error_message`.
+ */
+typedef enum {
+  /**
+   * There is no error, or its message has been read.
+   */
+  WASMER_ERROR_KIND_NONE = 0,
+  /**
+   * The API was misused, like with a null pointer, or failed otherwise.
+   */
+  WASMER_ERROR_KIND_OTHER = 1,
+  /**
+   * A module failed to compile.
+   */
+  WASMER_ERROR_KIND_COMPILE = 2,
+  /**
+   * The imports of a module failed to link.
+   */
+  WASMER_ERROR_KIND_LINK = 3,
+  /**
+   * A call trapped, or an imported function failed.
+   */
+  WASMER_ERROR_KIND_RUNTIME_TRAP = 4,
+  /**
+   * A WASI program exited with `proc_exit`.
+   */
+  WASMER_ERROR_KIND_WASI_EXIT = 5,
+  /**
+   * A call halted because the instance used its gas limit.
+   */
+  WASMER_ERROR_KIND_GAS_EXHAUSTED = 6,
+} wasmer_error_kind_t;
+
 typedef enum {
   WASMER_OK = 1,
   WASMER_ERROR = 2,
+  /**
+   * A call halted because the instance used its gas limit. See the
+   * `metering` module.
+   */
+  WASMER_GAS_EXHAUSTED = 3,
 } wasmer_result_t;
 
+/**
+ * A class of instructions forbidden by an opcode filter.
+ */
+typedef enum {
+  /**
+   * Instructions operating on or producing `f32` and `f64` values.
+   */
+  WASMER_OPCODE_CLASS_FLOAT = 0,
+  /**
+   * Instructions of the SIMD proposal.
+   */
+  WASMER_OPCODE_CLASS_SIMD = 1,
+  /**
+   * Atomic instructions of the threads proposal.
+   */
+  WASMER_OPCODE_CLASS_ATOMIC = 2,
+  /**
+   * `call_indirect`.
+   */
+  WASMER_OPCODE_CLASS_INDIRECT_CALL = 3,
+} wasmer_opcode_class_t;
+
+/**
+ * What caused a trap.
+ */
+typedef enum {
+  /**
+   * An `unreachable` instruction was executed.
+   */
+  WASMER_TRAP_CODE_UNREACHABLE = 0,
+  /**
+   * A `call_indirect` called a function of another signature.
+   */
+  WASMER_TRAP_CODE_INCORRECT_CALL_INDIRECT_SIGNATURE = 1,
+  /**
+   * A memory was accessed out of its bounds.
+   */
+  WASMER_TRAP_CODE_MEMORY_OUT_OF_BOUNDS = 2,
+  /**
+   * A `call_indirect` indexed a table out of its bounds.
+   */
+  WASMER_TRAP_CODE_CALL_INDIRECT_OOB = 3,
+  /**
+   * An arithmetic instruction failed, like a division by zero.
+   */
+  WASMER_TRAP_CODE_ILLEGAL_ARITHMETIC = 4,
+  /**
+   * An atomic instruction accessed a misaligned address.
+   */
+  WASMER_TRAP_CODE_MISALIGNED_ATOMIC_ACCESS = 5,
+  /**
+   * The trap has an unknown cause.
+   */
+  WASMER_TRAP_CODE_UNKNOWN = 6,
+  /**
+   * An imported function failed.
+   */
+  WASMER_TRAP_CODE_HOST_ERROR = 7,
+  /**
+   * The instance was interrupted.
+   */
+  WASMER_TRAP_CODE_INTERRUPTED = 8,
+} wasmer_trap_code_t;
+
 enum wasmer_value_tag {
   WASM_I32,
   WASM_I64,
@@ -91,6 +195,14 @@ typedef struct {
   wasmer_value value;
 } wasmer_value_t;
 
+/**
+ * Opaque pointer to a prepared call of a function exported by an
+ * instance.
+ */
+typedef struct {
+
+} wasmer_call_frame_t;
+
 /**
  * Opaque pointer to `NamedExport`.
  */
@@ -98,6 +210,19 @@ typedef struct {
 
 } wasmer_export_t;
 
+/**
+ * The callback invoked before a memory grows, set with
+ * `wasmer_memory_set_grow_callback`.
+ *
+ * It is called with the environment of the callback, the current
+ * length in pages of the memory and the length it is growing to, and
+ * returns `false` to prevent the growth. The guest's `memory.grow`
+ * then returns -1, and `wasmer_memory_grow` fails.
+ */
+typedef bool (*wasmer_memory_grow_callback_t)(void *env,
+                                              uint32_t current_pages,
+                                              uint32_t requested_pages);
+
 typedef struct {
 
 } wasmer_memory_t;
@@ -167,6 +292,55 @@ typedef struct {
 
 } wasmer_instance_context_t;
 
+/**
+ * Opaque pointer to a list of middlewares.
+ */
+typedef struct {
+
+} wasmer_middleware_chain_t;
+
+/**
+ * The callback invoked when a traced function is entered, with the
+ * environment of the callback, the context of the running instance,
+ * which is null if the backend doesn't know it, the index of the
+ * function, and its first arguments if they are captured.
+ *
+ * The arguments are only valid during the call.
+ */
+typedef void (*wasmer_call_trace_enter_t)(void *env,
+                                        wasmer_instance_context_t *ctx,
+                                        uint32_t func_index,
+                                        const wasmer_value_t *args,
+                                        unsigned int args_len);
+
+/**
+ * The callback invoked when a traced function returns, with the
+ * environment of the callback, the context of the running instance,
+ * which is null if the backend doesn't know it, and the index of the
+ * function.
+ */
+typedef void (*wasmer_call_trace_exit_t)(void *env, wasmer_instance_context_t *ctx, uint32_t func_index);
+
+#if (!defined(_WIN32) && defined(ARCH_X86_64))
+/**
+ * The callback of a func created by `wasmer_import_func_new_with_env`.
+ *
+ * It is called with the context of the calling instance, the
+ * environment of the func, the `params_len` arguments of the call,
+ * and the `results_len` results to write, which is 0 or 1.
+ *
+ * It returns `wasmer_result_t::WASMER_ERROR` to trap, after setting
+ * the message of the trap with `wasmer_last_error_message`'s error
+ * if it has one.
+ */
+typedef wasmer_result_t (*wasmer_import_func_callback_t)(wasmer_instance_context_t *ctx,
+                                                         void *env,
+                                                         const wasmer_value_t *params,
+                                                         unsigned int params_len,
+                                                         wasmer_value_t *results,
+                                                         unsigned int results_len);
+#endif
+
 typedef struct {
   bool has_some;
   uint32_t some;
@@ -199,6 +373,38 @@ typedef struct {
 } wasmer_trampoline_buffer_t;
 #endif
 
+/**
+ * A frame of the wasm call stack where a trap occurred.
+ */
+typedef struct {
+  /**
+   * The index of the function.
+   */
+  uint32_t func_index;
+  /**
+   * The name of the function, or null bytes if the module has no
+   * name section.
+   */
+  wasmer_byte_array name;
+} wasmer_trap_frame_t;
+
+/**
+ * The callback invoked when a call traps, set with
+ * `wasmer_trap_callback_set`.
+ *
+ * It is called with the environment of the callback, the cause of the
+ * trap, its message, and the frames of the wasm call stack, innermost
+ * first: the first frame is the function which trapped. There are no
+ * frames if the call stack wasn't captured.
+ *
+ * The message and the frames are only valid during the call.
+ */
+typedef void (*wasmer_trap_callback_t)(void *env,
+                                       wasmer_trap_code_t code,
+                                       const char *message,
+                                       const wasmer_trap_frame_t *frames,
+                                       unsigned int frames_len);
+
 /**
  * Opens a directory that's visible to the WASI module as `alias` but
  * is backed by the host file at `host_file_path`
@@ -214,6 +420,61 @@ typedef struct {
   wasmer_byte_array host_file_path;
 } wasmer_wasi_map_dir_entry_t;
 
+/**
+ * Builds the WASI state of the instances created with an import object.
+ *
+ * See `wasmer_wasi_state_builder_new`.
+ */
+typedef struct {
+
+} wasmer_wasi_state_builder_t;
+
+/**
+ * Calls the function of the call frame with `params`, and writes its
+ * results to `results`.
+ *
+ * The values are untagged: their types are the ones of the function,
+ * so `params_len` and `results_len` must be the numbers of its params
+ * and results.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ *
+ * Returns `wasmer_result_t::WASMER_GAS_EXHAUSTED` if the call halted because the instance used
+ * its gas limit.
+ */
+wasmer_result_t wasmer_call_frame_call(wasmer_call_frame_t *frame,
+                                       const wasmer_value *params,
+                                       unsigned int params_len,
+                                       wasmer_value *results,
+                                       unsigned int results_len);
+
+/**
+ * Frees memory for the given call frame.
+ */
+void wasmer_call_frame_destroy(wasmer_call_frame_t *frame);
+
+/**
+ * Prepares the calls of the function exported by the instance with
+ * `name`, and writes the call frame to `frame`.
+ *
+ * The function is only looked up once, and the call frame reuses its
+ * buffers across calls, unlike `wasmer_instance_call`.
+ *
+ * The caller owns the object and should call `wasmer_call_frame_destroy`
+ * to free it, before destroying the instance.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_call_frame_new(wasmer_call_frame_t **frame,
+                                      const wasmer_instance_t *instance,
+                                      const char *name);
+
 /**
  * Creates a new Module from the given wasm bytes.
  *
@@ -226,6 +487,35 @@ wasmer_result_t wasmer_compile(wasmer_module_t **module,
                                uint8_t *wasm_bytes,
                                uint32_t wasm_bytes_len);
 
+/**
+ * Creates a new Module from the given wasm bytes, metering the gas used
+ * by its instances.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_compile_with_gas_metering(wasmer_module_t **module,
+                                                 uint8_t *wasm_bytes,
+                                                 uint32_t wasm_bytes_len);
+
+/**
+ * Creates a new Module from the given wasm bytes, compiled with the
+ * middlewares of `chain`.
+ *
+ * The module doesn't borrow the chain, which can be destroyed first.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_compile_with_middlewares(wasmer_module_t **module,
+                                                uint8_t *wasm_bytes,
+                                                uint32_t wasm_bytes_len,
+                                                const wasmer_middleware_chain_t *chain);
+
 /**
  * Gets export descriptor kind
  */
@@ -268,6 +558,9 @@ int wasmer_export_descriptors_len(wasmer_export_descriptors_t *exports);
  *
  * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
  * and `wasmer_last_error_message` to get an error message.
+ *
+ * Returns `wasmer_result_t::WASMER_GAS_EXHAUSTED` if the call halted because the instance used
+ * its gas limit.
  */
 wasmer_result_t wasmer_export_func_call(const wasmer_export_func_t *func,
                                         const wasmer_value_t *params,
@@ -335,6 +628,21 @@ wasmer_byte_array wasmer_export_name(wasmer_export_t *export_);
  */
 const wasmer_export_func_t *wasmer_export_to_func(const wasmer_export_t *export_);
 
+/**
+ * Gets a global pointer from an export pointer.
+ *
+ * Writing the global, if it's mutable, changes the value the instance
+ * sees.
+ *
+ * The caller owns the object and should call `wasmer_global_destroy` to free it.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_export_to_global(const wasmer_export_t *export_, wasmer_global_t **global);
+
 /**
  * Gets a memory pointer from an export pointer.
  *
@@ -345,6 +653,18 @@ const wasmer_export_func_t *wasmer_export_to_func(const wasmer_export_t *export_
  */
 wasmer_result_t wasmer_export_to_memory(const wasmer_export_t *export_, wasmer_memory_t **memory);
 
+/**
+ * Gets a table pointer from an export pointer.
+ *
+ * The caller owns the object and should call `wasmer_table_destroy` to free it.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_export_to_table(const wasmer_export_t *export_, wasmer_table_t **table);
+
 /**
  * Frees the memory for the given exports
  */
@@ -382,9 +702,18 @@ wasmer_global_descriptor_t wasmer_global_get_descriptor(wasmer_global_t *global)
 wasmer_global_t *wasmer_global_new(wasmer_value_t value, bool mutable_);
 
 /**
- * Sets the value stored by the given Global
+ * Sets the value stored by the given Global.
+ *
+ * The Global can be created with `wasmer_global_new`, or be the global
+ * of an instance, from `wasmer_export_to_global`.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure, if the Global
+ * is immutable or the value isn't of its type. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
  */
-void wasmer_global_set(wasmer_global_t *global, wasmer_value_t value);
+wasmer_result_t wasmer_global_set(wasmer_global_t *global, wasmer_value_t value);
 
 /**
  * Gets export descriptor kind
@@ -441,6 +770,29 @@ wasmer_import_func_t *wasmer_import_func_new(void (*func)(void *data),
                                              const wasmer_value_tag *returns,
                                              unsigned int returns_len);
 
+#if (!defined(_WIN32) && defined(ARCH_X86_64))
+/**
+ * Creates new func calling `callback` with the environment `env`.
+ *
+ * `finalizer`, if not null, is called with `env` once the func and
+ * the instances importing it are destroyed.
+ *
+ * Returns null if the func has more than two integer or two float
+ * returns, which is what is returned in registers. Use
+ * `wasmer_last_error_length` and `wasmer_last_error_message` to get an
+ * error message.
+ *
+ * The caller owns the object and should call `wasmer_import_func_destroy` to free it.
+ */
+wasmer_import_func_t *wasmer_import_func_new_with_env(wasmer_import_func_callback_t callback,
+                                                      void *env,
+                                                      void (*finalizer)(void *env),
+                                                      const wasmer_value_tag *params,
+                                                      unsigned int params_len,
+                                                      const wasmer_value_tag *returns,
+                                                      unsigned int returns_len);
+#endif
+
 /**
  * Sets the params buffer to the parameter types of the given wasmer_import_func_t
  *
@@ -563,6 +915,9 @@ wasmer_import_object_t *wasmer_import_object_new(void);
  *
  * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
  * and `wasmer_last_error_message` to get an error message.
+ *
+ * Returns `wasmer_result_t::WASMER_GAS_EXHAUSTED` if the call halted because the instance used
+ * its gas limit.
  */
 wasmer_result_t wasmer_instance_call(wasmer_instance_t *instance,
                                      const char *name,
@@ -579,9 +934,24 @@ void *wasmer_instance_context_data_get(const wasmer_instance_context_t *ctx);
 /**
  * Sets the `data` field of the instance context. This context will be
  * passed to all imported function for instance.
+ *
+ * The previous data is finalized if it was set with
+ * `wasmer_instance_context_data_set_with_finalizer`.
  */
 void wasmer_instance_context_data_set(wasmer_instance_t *instance, void *data_ptr);
 
+/**
+ * Sets the `data` field of the instance context, like
+ * `wasmer_instance_context_data_set`, and calls `finalizer`, if not
+ * null, with the data once the instance is destroyed or the data is
+ * replaced.
+ *
+ * The previous data is finalized if it was set with a finalizer.
+ */
+void wasmer_instance_context_data_set_with_finalizer(wasmer_instance_t *instance,
+                                                     void *data_ptr,
+                                                     void (*finalizer)(void *data));
+
 /**
  * Extracts the instance's context and returns it.
  */
@@ -594,6 +964,13 @@ const wasmer_instance_context_t *wasmer_instance_context_get(wasmer_instance_t *
 const wasmer_memory_t *wasmer_instance_context_memory(const wasmer_instance_context_t *ctx,
                                                       uint32_t _memory_idx);
 
+/**
+ * Gets the `data` field of the instance context from the instance,
+ * like `wasmer_instance_context_data_get` does from the context given
+ * to the imported functions.
+ */
+void *wasmer_instance_data_get(const wasmer_instance_t *instance);
+
 /**
  * Frees memory for the given Instance
  */
@@ -606,6 +983,42 @@ void wasmer_instance_destroy(wasmer_instance_t *instance);
  */
 void wasmer_instance_exports(wasmer_instance_t *instance, wasmer_exports_t **exports);
 
+/**
+ * Returns the gas limit of the instance of a metered module.
+ */
+uint64_t wasmer_instance_get_gas_limit(const wasmer_instance_t *instance);
+
+/**
+ * Returns the points the instance of a metered module can still use
+ * before its gas is exhausted.
+ */
+uint64_t wasmer_instance_get_points_remaining(const wasmer_instance_t *instance);
+
+/**
+ * Returns the points used by the instance of a metered module.
+ *
+ * They can slightly exceed the gas limit once the gas is exhausted,
+ * since the limit is checked at branches and calls.
+ */
+uint64_t wasmer_instance_get_points_used(const wasmer_instance_t *instance);
+
+/**
+ * Sets the gas limit of the instance of a metered module, keeping the
+ * points it used.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_instance_set_gas_limit(wasmer_instance_t *instance, uint64_t gas_limit);
+
+/**
+ * Sets the points used by the instance of a metered module, like 0 to
+ * reset them between calls.
+ */
+void wasmer_instance_set_points_used(wasmer_instance_t *instance, uint64_t points);
+
 /**
  * Creates a new Instance from the given wasm bytes and imports.
  *
@@ -621,7 +1034,21 @@ wasmer_result_t wasmer_instantiate(wasmer_instance_t **instance,
                                    int imports_len);
 
 /**
- * Gets the length in bytes of the last error.
+ * Gets the kind of the last error of the calling thread, without
+ * clearing it.
+ *
+ * # Example
+ *
+ * ```c
+ * if (wasmer_last_error_kind() == WASMER_ERROR_KIND_COMPILE) {
+ *     printf("The module is invalid\n");
+ * }
+ * ```
+ */
+wasmer_error_kind_t wasmer_last_error_kind(void);
+
+/**
+ * Gets the length in bytes of the last error of the calling thread.
  * This can be used to dynamically allocate a buffer with the correct number of
  * bytes needed to store a message.
  *
@@ -635,7 +1062,8 @@ wasmer_result_t wasmer_instantiate(wasmer_instance_t **instance,
 int wasmer_last_error_length(void);
 
 /**
- * Stores the last error message into the provided buffer up to the given `length`.
+ * Stores the last error message of the calling thread into the provided buffer up to the given
+ * `length`, and clears the last error.
  * The `length` parameter must be large enough to store the last error message.
  *
  * Returns the length of the string in bytes.
@@ -670,12 +1098,34 @@ void wasmer_memory_destroy(wasmer_memory_t *memory);
 /**
  * Grows a Memory by the given number of pages.
  *
+ * The memory can be created with `wasmer_memory_new`, or be the memory
+ * of an instance, from `wasmer_export_to_memory` or
+ * `wasmer_instance_context_memory`.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_memory_grow(const wasmer_memory_t *memory, uint32_t delta);
+
+/**
+ * Grows a Memory by the given number of pages, like
+ * `wasmer_memory_grow`, and writes the length in pages it had before
+ * growing to `previous_length` if it's not null.
+ *
+ * The length is read while growing, so unlike with
+ * `wasmer_memory_length`, no other thread can grow the memory in
+ * between.
+ *
  * Returns `wasmer_result_t::WASMER_OK` upon success.
  *
  * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
  * and `wasmer_last_error_message` to get an error message.
  */
-wasmer_result_t wasmer_memory_grow(wasmer_memory_t *memory, uint32_t delta);
+wasmer_result_t wasmer_memory_grow_from(const wasmer_memory_t *memory,
+                                        uint32_t delta,
+                                        uint32_t *previous_length);
 
 /**
  * Returns the current length in pages of the given memory
@@ -695,9 +1145,114 @@ uint32_t wasmer_memory_length(const wasmer_memory_t *memory);
  */
 wasmer_result_t wasmer_memory_new(wasmer_memory_t **memory, wasmer_limits_t limits);
 
+/**
+ * Sets the callback invoked with `env` before the memory grows, whether
+ * through the guest's `memory.grow` or `wasmer_memory_grow`, replacing
+ * the previous one. A null `callback` removes it.
+ *
+ * The callback is invoked while the memory is being grown, so it must
+ * not access the memory itself. `finalizer`, if not null, is called
+ * with `env` once the callback is replaced or the memory is destroyed.
+ */
+void wasmer_memory_set_grow_callback(const wasmer_memory_t *memory,
+                                     wasmer_memory_grow_callback_t callback,
+                                     void *env,
+                                     void (*finalizer)(void *env));
+
+/**
+ * Frees memory for the given middleware chain.
+ */
+void wasmer_middleware_chain_destroy(wasmer_middleware_chain_t *chain);
+
+/**
+ * Creates an empty middleware chain.
+ *
+ * The caller owns the object and should call
+ * `wasmer_middleware_chain_destroy` to free it.
+ */
+wasmer_middleware_chain_t *wasmer_middleware_chain_new(void);
+
+/**
+ * Appends a call trace middleware to the chain, which invokes `enter`
+ * with `env` when a local function is entered, and `exit` when it
+ * returns. Either callback can be null.
+ *
+ * If `capture_args` is true, the first arguments of the entered
+ * functions are passed to `enter`, which slows the calls down.
+ *
+ * The callbacks run in the middle of the traced functions, on the
+ * threads running the instances. `env` must stay valid as long as the
+ * modules compiled with the chain are used.
+ */
+void wasmer_middleware_chain_push_call_trace(wasmer_middleware_chain_t *chain,
+                                             wasmer_call_trace_enter_t enter,
+                                             wasmer_call_trace_exit_t exit,
+                                             void *env,
+                                             bool capture_args);
+
+/**
+ * Appends the metering middleware to the chain.
+ *
+ * The instances of the modules compiled with the chain are metered
+ * like the ones of `wasmer_compile_with_gas_metering`: they are created
+ * with `wasmer_module_instantiate_with_gas_limit`, and their gas is
+ * managed by the functions of the `metering` module. The chain should
+ * have at most one metering middleware.
+ */
+void wasmer_middleware_chain_push_metering(wasmer_middleware_chain_t *chain);
+
+/**
+ * Appends an opcode filter to the chain, which forbids the
+ * instructions of the `classes_len` classes of `classes`.
+ *
+ * A module using a forbidden instruction fails to compile, unless
+ * `trap` is true: then the instruction traps when executed.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_middleware_chain_push_opcode_filter(wasmer_middleware_chain_t *chain,
+                                                           const wasmer_opcode_class_t *classes,
+                                                           unsigned int classes_len,
+                                                           bool trap);
+
+/**
+ * Gets the contents of the custom sections named `name` of the given
+ * Module, in the order they appear in the module, like to read a
+ * manifest embedded in it.
+ *
+ * At most `sections_len` sections are written to `sections`, which can
+ * be null if `sections_len` is 0, so that the number of sections can be
+ * read first. The bytes are owned by the module, and are valid until
+ * it is destroyed.
+ *
+ * Returns the number of custom sections named `name`.
+ * Returns `-1` if an error occurs. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ *
+ * # Example
+ *
+ * ```c
+ * int sections_len = wasmer_module_custom_sections(module, "manifest", NULL, 0);
+ * wasmer_byte_array *sections = malloc(sections_len * sizeof(wasmer_byte_array));
+ * wasmer_module_custom_sections(module, "manifest", sections, sections_len);
+ * ```
+ */
+int wasmer_module_custom_sections(const wasmer_module_t *module,
+                                  const char *name,
+                                  wasmer_byte_array *sections,
+                                  unsigned int sections_len);
+
 /**
  * Deserialize the given serialized module.
  *
+ * The module is compiled with the default backend, and doesn't borrow
+ * the serialized module, which can be destroyed first.
+ *
+ * The caller owns the object and should call `wasmer_module_destroy` to free it.
+ *
  * Returns `wasmer_result_t::WASMER_OK` upon success.
  *
  * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
@@ -735,10 +1290,29 @@ wasmer_result_t wasmer_module_instantiate(const wasmer_module_t *module,
                                           wasmer_import_t *imports,
                                           int imports_len);
 
+/**
+ * Creates a new Instance from the given metered module and imports,
+ * with the gas limit `gas_limit`.
+ *
+ * The start function of the module, if any, runs before the limit is
+ * set.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_module_instantiate_with_gas_limit(const wasmer_module_t *module,
+                                                         wasmer_instance_t **instance,
+                                                         wasmer_import_t *imports,
+                                                         int imports_len,
+                                                         uint64_t gas_limit);
+
 /**
  * Serialize the given Module.
  *
  * The caller owns the object and should call `wasmer_serialized_module_destroy` to free it.
+ * The serialized module doesn't borrow the module, which can be destroyed first.
  *
  * Returns `wasmer_result_t::WASMER_OK` upon success.
  *
@@ -750,6 +1324,8 @@ wasmer_result_t wasmer_module_serialize(wasmer_serialized_module_t **serialized_
 
 /**
  * Get bytes of the serialized module.
+ *
+ * The bytes are owned by the serialized module, and are valid until it is destroyed.
  */
 wasmer_byte_array wasmer_serialized_module_bytes(const wasmer_serialized_module_t *serialized_module);
 
@@ -761,6 +1337,11 @@ void wasmer_serialized_module_destroy(wasmer_serialized_module_t *serialized_mod
 /**
  * Transform a sequence of bytes into a serialized module.
  *
+ * The bytes are copied, so the caller keeps the ownership of
+ * `serialized_module_bytes`. They can be the bytes of
+ * `wasmer_serialized_module_bytes`, or of a cache artifact stored by
+ * the file system cache of the runtime.
+ *
  * The caller owns the object and should call `wasmer_serialized_module_destroy` to free it.
  *
  * Returns `wasmer_result_t::WASMER_OK` upon success.
@@ -777,15 +1358,36 @@ wasmer_result_t wasmer_serialized_module_from_bytes(wasmer_serialized_module_t *
  */
 void wasmer_table_destroy(wasmer_table_t *table);
 
+/**
+ * Gets the func at `index` in the Table, and writes it to `func`, or
+ * null if the element is null.
+ *
+ * The func can be set at another index, or in another table, with
+ * `wasmer_table_set`.
+ *
+ * The caller owns the object and should call `wasmer_import_func_destroy` to free it.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_table_get(const wasmer_table_t *table,
+                                 uint32_t index,
+                                 wasmer_import_func_t **func);
+
 /**
  * Grows a Table by the given number of elements.
  *
+ * The table can be created with `wasmer_table_new`, or be the table of
+ * an instance, from `wasmer_export_to_table`.
+ *
  * Returns `wasmer_result_t::WASMER_OK` upon success.
  *
  * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
  * and `wasmer_last_error_message` to get an error message.
  */
-wasmer_result_t wasmer_table_grow(wasmer_table_t *table, uint32_t delta);
+wasmer_result_t wasmer_table_grow(const wasmer_table_t *table, uint32_t delta);
 
 /**
  * Returns the current length of the given Table
@@ -805,6 +1407,23 @@ uint32_t wasmer_table_length(wasmer_table_t *table);
  */
 wasmer_result_t wasmer_table_new(wasmer_table_t **table, wasmer_limits_t limits);
 
+/**
+ * Sets the func at `index` in the Table, like a func created with
+ * `wasmer_import_func_new` or one got with `wasmer_table_get`, so that
+ * the instances can call it with `call_indirect`.
+ *
+ * A host func can only be set once the table is used by an instance,
+ * since it is called with the context of that instance.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_table_set(const wasmer_table_t *table,
+                                 uint32_t index,
+                                 const wasmer_import_func_t *func);
+
 #if (!defined(_WIN32) && defined(ARCH_X86_64))
 /**
  * Adds a callinfo trampoline to the builder.
@@ -860,6 +1479,30 @@ const wasmer_trampoline_callable_t *wasmer_trampoline_buffer_get_trampoline(cons
 void *wasmer_trampoline_get_context(void);
 #endif
 
+/**
+ * Sets the callback invoked with `env` when a call made on the calling
+ * thread traps, before the call returns its error, replacing the
+ * previous one. A null `callback` removes it.
+ *
+ * The exit of a WASI program and the exhaustion of the gas of an
+ * instance aren't reported.
+ *
+ * # Example
+ *
+ * ```c
+ * void on_trap(void *env, wasmer_trap_code_t code, const char *message,
+ *              const wasmer_trap_frame_t *frames, unsigned int frames_len) {
+ *     fprintf(stderr, "trap: %s\n", message);
+ *     if (frames_len > 0) {
+ *         fprintf(stderr, "in func %u\n", frames[0].func_index);
+ *     }
+ * }
+ *
+ * wasmer_trap_callback_set(on_trap, NULL);
+ * ```
+ */
+void wasmer_trap_callback_set(wasmer_trap_callback_t callback, void *env);
+
 /**
  * Returns true for valid wasm bytes and false for invalid bytes
  */
@@ -890,4 +1533,100 @@ wasmer_import_object_t *wasmer_wasi_generate_import_object(const wasmer_byte_arr
                                                            const wasmer_wasi_map_dir_entry_t *mapped_dirs,
                                                            unsigned int mapped_dirs_len);
 
+/**
+ * Creates a WASI import object giving each instance a state built by `builder`.
+ *
+ * The builder isn't consumed, the caller still has to destroy it.
+ *
+ * Returns null if the builder can't build a state, e.g. if a preopened directory doesn't
+ * exist. Use `wasmer_last_error_length` and `wasmer_last_error_message` to get an error
+ * message.
+ */
+wasmer_import_object_t *wasmer_wasi_generate_import_object_from_builder(wasmer_wasi_state_builder_t *builder);
+
+/**
+ * Runs a WASI program by calling the `_start` function of `instance`.
+ *
+ * The exit code of the program is written to `exit_code`: 0 if `_start` returned, or the
+ * code the program gave to `proc_exit`.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` if the program failed otherwise, e.g. if it
+ * trapped. Use `wasmer_last_error_length` and `wasmer_last_error_message` to get an error
+ * message.
+ */
+wasmer_result_t wasmer_wasi_instance_start(wasmer_instance_t *instance, int32_t *exit_code);
+
+/**
+ * Adds an argument to the program.
+ */
+wasmer_result_t wasmer_wasi_state_builder_arg(wasmer_wasi_state_builder_t *builder,
+                                              wasmer_byte_array arg);
+
+/**
+ * Frees memory for the given WASI state builder.
+ */
+void wasmer_wasi_state_builder_destroy(wasmer_wasi_state_builder_t *builder);
+
+/**
+ * Sets the environment variable `key` of the program to `value`.
+ */
+wasmer_result_t wasmer_wasi_state_builder_env(wasmer_wasi_state_builder_t *builder,
+                                              wasmer_byte_array key,
+                                              wasmer_byte_array value);
+
+/**
+ * Gives the program access to the host directory at `host_path`, as `alias`.
+ */
+wasmer_result_t wasmer_wasi_state_builder_map_dir(wasmer_wasi_state_builder_t *builder,
+                                                  wasmer_byte_array alias,
+                                                  wasmer_byte_array host_path);
+
+/**
+ * Creates a WASI state builder for a program named `program_name`, which is its first
+ * argument.
+ *
+ * The builder is configured with the `wasmer_wasi_state_builder_*` functions, then given to
+ * `wasmer_wasi_generate_import_object_from_builder`.
+ *
+ * The caller owns the object and should call `wasmer_wasi_state_builder_destroy` to free it.
+ */
+wasmer_wasi_state_builder_t *wasmer_wasi_state_builder_new(wasmer_byte_array program_name);
+
+/**
+ * Gives the program access to the host directory at `path`, under the same name.
+ */
+wasmer_result_t wasmer_wasi_state_builder_preopen_dir(wasmer_wasi_state_builder_t *builder,
+                                                      wasmer_byte_array path);
+
+/**
+ * Makes the program write its stderr to `callback` instead of the stderr of the host.
+ *
+ * Like with `wasmer_wasi_state_builder_stdout`, `callback` is called with `data` and the
+ * bytes written.
+ */
+wasmer_result_t wasmer_wasi_state_builder_stderr(wasmer_wasi_state_builder_t *builder,
+                                                 void (*callback)(void *data, const uint8_t *bytes, uint32_t bytes_len),
+                                                 void *data);
+
+/**
+ * Makes the program read its stdin from `callback` instead of the stdin of the host.
+ *
+ * `callback` is called with `data` and a buffer to fill with at most `buffer_len` bytes, and
+ * returns how many bytes it wrote, 0 at the end of the input, or a negative number if it
+ * failed.  It may be called from any thread running an instance.
+ */
+wasmer_result_t wasmer_wasi_state_builder_stdin(wasmer_wasi_state_builder_t *builder,
+                                                int32_t (*callback)(void *data, uint8_t *buffer, uint32_t buffer_len),
+                                                void *data);
+
+/**
+ * Makes the program write its stdout to `callback` instead of the stdout of the host.
+ *
+ * `callback` is called with `data` and the bytes written.  It may be called from any thread
+ * running an instance.
+ */
+wasmer_result_t wasmer_wasi_state_builder_stdout(wasmer_wasi_state_builder_t *builder,
+                                                 void (*callback)(void *data, const uint8_t *bytes, uint32_t bytes_len),
+                                                 void *data);
+
 #endif /* WASMER_H */
@@ -0,0 +1,124 @@
+// The other backends don't support the threads feature.
+#![cfg(feature = "backend-llvm")]
+
+use std::{thread, time::Duration};
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    compile_with_config,
+    error::RuntimeError,
+    imports,
+    memory::Memory,
+    module::Module,
+    typed_func::Func,
+    types::MemoryDescriptor,
+    units::Pages,
+};
+use wasmer_runtime_core_tests::get_compiler;
+
+fn compile_threads(wat: &str) -> Module {
+    let mut features = wabt::Features::new();
+    features.enable_threads();
+    let wasm_binary = wabt::wat2wasm_with_features(wat.as_bytes(), features)
+        .expect("WAST not valid or malformed");
+    compile_with_config(
+        &wasm_binary,
+        &get_compiler(),
+        CompilerConfig {
+            features: Features {
+                threads: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+const SHARED_MODULE: &str = r#"
+(module
+  (import "env" "memory" (memory 1 1 shared))
+  (func (export "wait") (param i32 i64) (result i32)
+    i32.const 0
+    get_local 0
+    get_local 1
+    i32.atomic.wait)
+  (func (export "notify") (param i32) (result i32)
+    i32.const 0
+    get_local 0
+    atomic.notify))
+"#;
+
+#[test]
+fn test_wait_on_unshared_memory_traps() {
+    let module = compile_threads(
+        r#"
+(module
+  (memory 1)
+  (func (export "wait") (result i32)
+    i32.const 0
+    i32.const 0
+    i64.const 0
+    i32.atomic.wait)
+  (func (export "notify") (result i32)
+    i32.const 0
+    i32.const 1
+    atomic.notify))
+"#,
+    );
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let wait: Func<(), i32> = instance.func("wait").unwrap();
+    match wait.call() {
+        Err(RuntimeError::Trap { .. }) => {}
+        other => panic!("unexpected result: {:?}", other.map_err(|e| e.to_string())),
+    }
+    // Nothing can wait on the memory, so there is nothing to notify.
+    let notify: Func<(), i32> = instance.func("notify").unwrap();
+    assert_eq!(notify.call(), Ok(0));
+}
+
+#[test]
+fn test_wait_returns_immediately() {
+    let module = compile_threads(SHARED_MODULE);
+    let memory =
+        Memory::new(MemoryDescriptor::new(Pages(1), Some(Pages(1)), true).unwrap()).unwrap();
+    let instance = module
+        .instantiate(&imports! { "env" => { "memory" => memory.clone(), }, })
+        .unwrap();
+    let wait: Func<(i32, i64), i32> = instance.func("wait").unwrap();
+
+    // "not-equal" when the memory doesn't hold the expected value.
+    memory.view::<i32>()[0].set(7);
+    assert_eq!(wait.call(0, -1), Ok(1));
+    // "timed-out" when nothing notifies the waiter.
+    assert_eq!(wait.call(7, 1_000_000), Ok(2));
+}
+
+#[test]
+fn test_notify_wakes_waiter_on_other_thread() {
+    let module = compile_threads(SHARED_MODULE);
+    let memory =
+        Memory::new(MemoryDescriptor::new(Pages(1), Some(Pages(1)), true).unwrap()).unwrap();
+
+    let instantiate = || {
+        module
+            .instantiate(&imports! { "env" => { "memory" => memory.clone(), }, })
+            .unwrap()
+    };
+
+    let waiter = {
+        let instance = instantiate();
+        thread::spawn(move || {
+            let wait: Func<(i32, i64), i32> = instance.func("wait").unwrap();
+            wait.call(0, 10_000_000_000).unwrap()
+        })
+    };
+
+    let instance = instantiate();
+    let notify: Func<i32, i32> = instance.func("notify").unwrap();
+    // The waiter may not be waiting yet.
+    while notify.call(1).unwrap() == 0 {
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(waiter.join().unwrap(), 0);
+}
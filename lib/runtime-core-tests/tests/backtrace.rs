@@ -0,0 +1,42 @@
+// LLVM may inline the callees, merging their frames.
+#![cfg(not(feature = "backend-llvm"))]
+
+use wabt::Wat2Wasm;
+use wasmer_runtime_core::{compile_with, imports, structures::TypedIndex, typed_func::Func};
+use wasmer_runtime_core_tests::get_compiler;
+
+#[test]
+fn test_trap_backtrace() {
+    const MODULE: &str = r#"
+(module
+  (func $inner unreachable)
+  (func $middle call $inner)
+  (func $outer (export "outer") call $middle))
+"#;
+
+    let wasm_binary = Wat2Wasm::new()
+        .write_debug_names(true)
+        .convert(MODULE)
+        .expect("WAST not valid or malformed");
+    let module = compile_with(wasm_binary.as_ref(), &get_compiler()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let outer: Func<(), ()> = instance.func("outer").unwrap();
+    let error = outer.call().unwrap_err();
+    let backtrace = error.backtrace().expect("no backtrace was captured");
+    let frames: Vec<_> = backtrace
+        .frames()
+        .iter()
+        .map(|frame| {
+            (
+                frame.func_index.index(),
+                frame.name.as_ref().map(String::as_str),
+            )
+        })
+        .collect();
+    assert_eq!(
+        frames,
+        vec![(0, Some("inner")), (1, Some("middle")), (2, Some("outer"))]
+    );
+    assert!(error.to_string().contains("middle (func 1)"));
+}
@@ -0,0 +1,77 @@
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, MemoryBoundCheckMode},
+    compile_with_config, imports,
+    typed_func::Func,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (memory 1 4)
+  (func (export "load") (param i32) (result i32)
+    get_local 0
+    i32.load)
+  (func (export "grow") (param i32) (result i32)
+    get_local 0
+    memory.grow))
+"#;
+
+// Without a maximum, the memory is dynamic.
+const DYNAMIC_MODULE: &str = r#"
+(module
+  (memory 1)
+  (func (export "load") (param i32) (result i32)
+    get_local 0
+    i32.load)
+  (func (export "grow") (param i32) (result i32)
+    get_local 0
+    memory.grow))
+"#;
+
+fn test_bound_check_mode(module: &str, mode: MemoryBoundCheckMode) {
+    let wasm_binary = wat2wasm(module.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with_config(
+        &wasm_binary,
+        &get_compiler(),
+        CompilerConfig {
+            memory_bound_check_mode: mode,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let load: Func<i32, i32> = instance.func("load").unwrap();
+    let grow: Func<i32, i32> = instance.func("grow").unwrap();
+
+    assert_eq!(load.call(0xfffc), Ok(0));
+    assert!(load.call(0xfffd).is_err());
+    assert!(load.call(0x10000).is_err());
+
+    // The accesses to the grown memory must not be checked against stale bounds.
+    assert_eq!(grow.call(1), Ok(1));
+    assert_eq!(load.call(0x1fffc), Ok(0));
+    assert!(load.call(0x1fffd).is_err());
+    assert!(load.call(0x20000).is_err());
+}
+
+#[test]
+fn test_default_bound_check() {
+    test_bound_check_mode(MODULE, MemoryBoundCheckMode::Default);
+}
+
+#[test]
+fn test_explicit_bound_check() {
+    test_bound_check_mode(MODULE, MemoryBoundCheckMode::Enable);
+}
+
+#[test]
+fn test_guard_page_bound_check() {
+    // Accesses past the end of the memory still hit the guard pages.
+    test_bound_check_mode(MODULE, MemoryBoundCheckMode::Disable);
+}
+
+#[test]
+fn test_dynamic_memory_bound_check() {
+    // Dynamic memories have no guard pages to rely on, so they are still checked.
+    test_bound_check_mode(DYNAMIC_MODULE, MemoryBoundCheckMode::Disable);
+}
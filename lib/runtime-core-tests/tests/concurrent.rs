@@ -0,0 +1,44 @@
+use std::{sync::Arc, thread};
+use wasmer_runtime_core::{compile_with, imports, types::Value, ConcurrentInstance};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn test_concurrent_instance_serializes_calls() {
+    const MODULE: &str = r#"
+(module
+  (memory 1)
+  (func (export "increment") (result i32)
+    i32.const 0
+    i32.const 0
+    i32.load
+    i32.const 1
+    i32.add
+    i32.store
+    i32.const 0
+    i32.load))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = Arc::new(ConcurrentInstance::new(
+        module.instantiate(&imports! {}).unwrap(),
+    ));
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let instance = Arc::clone(&instance);
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    instance.call("increment", &[]).unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert_eq!(instance.call("increment", &[]).unwrap(), [Value::I32(401)]);
+    let instance = Arc::try_unwrap(instance).ok().unwrap().into_inner();
+    assert_eq!(instance.context().memory(0).view::<u32>()[0].get(), 401);
+}
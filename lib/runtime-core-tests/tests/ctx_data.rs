@@ -0,0 +1,56 @@
+use std::sync::Arc;
+use wasmer_runtime_core::{compile_with, imports, typed_func::Func, types::Value, vm};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+struct Counter {
+    count: i32,
+    // Dropped with the counter, so that the test can see when the counter is dropped.
+    _alive: Arc<()>,
+}
+
+#[test]
+fn test_owned_ctx_data() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "bump" (func $bump (param i32) (result i32)))
+  (func (export "bump") (param i32) (result i32)
+    get_local 0
+    call $bump))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let mut instance = module
+        .instantiate(&imports! {
+            "env" => {
+                "bump" => Func::new(|ctx: &mut vm::Ctx, n: i32| -> i32 {
+                    let counter = ctx.data_mut::<Counter>().unwrap();
+                    counter.count += n;
+                    counter.count
+                }),
+            },
+        })
+        .unwrap();
+
+    let alive = Arc::new(());
+    let previous = instance.context_mut().set_data(Box::new(Counter {
+        count: 0,
+        _alive: Arc::clone(&alive),
+    }));
+    assert!(previous.is_none());
+
+    assert_eq!(
+        instance.call("bump", &[Value::I32(2)]).unwrap(),
+        [Value::I32(2)]
+    );
+    assert_eq!(
+        instance.call("bump", &[Value::I32(3)]).unwrap(),
+        [Value::I32(5)]
+    );
+    assert_eq!(instance.context().data_ref::<Counter>().unwrap().count, 5);
+    assert!(instance.context().data_ref::<String>().is_none());
+
+    assert_eq!(Arc::strong_count(&alive), 2);
+    drop(instance);
+    assert_eq!(Arc::strong_count(&alive), 1);
+}
@@ -0,0 +1,24 @@
+use wasmer_runtime_core::compile_with;
+use wasmer_runtime_core_tests::get_compiler;
+
+#[test]
+fn test_custom_sections() {
+    #[rustfmt::skip]
+    let wasm_binary = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // Custom section "meta" with payload [1, 2].
+        0x00, 0x07, 0x04, b'm', b'e', b't', b'a', 0x01, 0x02,
+        // Custom section "other" with an empty payload.
+        0x00, 0x06, 0x05, b'o', b't', b'h', b'e', b'r',
+        // Custom section "meta" with payload [3].
+        0x00, 0x06, 0x04, b'm', b'e', b't', b'a', 0x03,
+    ];
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    assert_eq!(
+        module.custom_sections("meta"),
+        vec![&[1u8, 2][..], &[3u8][..]]
+    );
+    assert_eq!(module.custom_sections("other"), vec![&b""[..]]);
+    assert!(module.custom_sections("missing").is_empty());
+}
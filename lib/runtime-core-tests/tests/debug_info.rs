@@ -0,0 +1,181 @@
+// Debug info is only emitted by Cranelift.
+#![cfg(all(feature = "backend-cranelift", target_arch = "x86_64"))]
+
+use gimli::write::{self, Address, EndianVec, LineProgram, LineString};
+use gimli::{DebugLine, DebugLineOffset, Encoding, Format, LineEncoding, LittleEndian};
+use std::convert::TryInto;
+use wasmer_runtime_core::{
+    backend::CompilerConfig, compile_with_config, imports, jit_debug, typed_func::Func,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+// In the code section contents, `i32.add` is at offset 7 and `end` at offset 8.
+const MODULE: &str = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    get_local 0
+    get_local 1
+    i32.add))
+"#;
+
+/// Returns the contents of the section called `name` in the ELF file `elf`.
+fn elf_section<'a>(elf: &'a [u8], name: &str) -> &'a [u8] {
+    let read_u16 = |offset: usize| u16::from_le_bytes(elf[offset..offset + 2].try_into().unwrap());
+    let read_u32 = |offset: usize| u32::from_le_bytes(elf[offset..offset + 4].try_into().unwrap());
+    let read_u64 = |offset: usize| u64::from_le_bytes(elf[offset..offset + 8].try_into().unwrap());
+
+    assert_eq!(&elf[..4], b"\x7fELF");
+    let shoff = read_u64(0x28) as usize;
+    let shnum = read_u16(0x3c) as usize;
+    let shstrndx = read_u16(0x3e) as usize;
+    let header = |index: usize| shoff + index * 64;
+    let shstrtab = read_u64(header(shstrndx) + 0x18) as usize;
+
+    for index in 0..shnum {
+        let name_offset = shstrtab + read_u32(header(index)) as usize;
+        let name_end = name_offset + elf[name_offset..].iter().position(|&b| b == 0).unwrap();
+        if &elf[name_offset..name_end] == name.as_bytes() {
+            let offset = read_u64(header(index) + 0x18) as usize;
+            let size = read_u64(header(index) + 0x20) as usize;
+            return &elf[offset..offset + size];
+        }
+    }
+    panic!("no {} section", name);
+}
+
+/// Returns the names of the variables described in the ELF file `elf` that have a location.
+fn variables_with_location(elf: &[u8]) -> Vec<Vec<u8>> {
+    let debug_info = gimli::DebugInfo::new(elf_section(elf, ".debug_info"), LittleEndian);
+    let debug_abbrev = gimli::DebugAbbrev::new(elf_section(elf, ".debug_abbrev"), LittleEndian);
+    let mut variables = vec![];
+    let mut units = debug_info.units();
+    while let Some(unit) = units.next().unwrap() {
+        let abbreviations = unit.abbreviations(&debug_abbrev).unwrap();
+        let mut entries = unit.entries(&abbreviations);
+        while let Some((_, entry)) = entries.next_dfs().unwrap() {
+            if entry.tag() != gimli::DW_TAG_variable
+                || entry.attr(gimli::DW_AT_location).unwrap().is_none()
+            {
+                continue;
+            }
+            match entry.attr_value(gimli::DW_AT_name).unwrap() {
+                Some(gimli::AttributeValue::String(name)) => variables.push(name.slice().to_vec()),
+                _ => panic!("unexpected variable name form"),
+            }
+        }
+    }
+    variables
+}
+
+/// Compiles `wasm` with debug info and returns the file and line of each row of the
+/// registered line table.
+fn compile_and_read_lines(wasm: &[u8]) -> Vec<(Vec<u8>, u64)> {
+    let module = compile_with_config(
+        wasm,
+        &get_compiler(),
+        CompilerConfig {
+            generate_debug_info: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    // The most recently registered object describes this module.
+    let object = jit_debug::registered_objects().remove(0);
+    let debug_line = DebugLine::new(elf_section(&object, ".debug_line"), LittleEndian);
+    let program = debug_line
+        .program(DebugLineOffset(0), 8, None, None)
+        .unwrap();
+    let mut rows = program.rows();
+    let mut lines = vec![];
+    while let Some((header, row)) = rows.next_row().unwrap() {
+        if row.end_sequence() {
+            continue;
+        }
+        let file = match row.file(header).unwrap().path_name() {
+            gimli::AttributeValue::String(path) => path.slice().to_vec(),
+            _ => panic!("unexpected file name form"),
+        };
+        lines.push((file, row.line().unwrap()));
+    }
+
+    // Both parameters are in registers on entry.
+    let variables = variables_with_location(&object);
+    assert!(variables.contains(&b"var0".to_vec()));
+    assert!(variables.contains(&b"var1".to_vec()));
+
+    // The code still runs as usual.
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let add: Func<(i32, i32), i32> = instance.func("add").unwrap();
+    assert_eq!(add.call(1, 2), Ok(3));
+
+    lines
+}
+
+// A single test, since the registered objects are global.
+#[test]
+fn test_debug_info() {
+    // Without debug info in the module, the lines are the code section offsets.
+    let wasm = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let lines = compile_and_read_lines(&wasm);
+    assert!(lines.contains(&(b"module.wasm".to_vec(), 7)));
+    for (file, line) in &lines {
+        assert_eq!(&file[..], b"module.wasm");
+        assert!(
+            *line >= 3 && *line <= 8,
+            "line {} outside of the function",
+            line
+        );
+    }
+
+    // With a `.debug_line` section mapping `i32.add` to `add.c:11`.
+    let encoding = Encoding {
+        format: Format::Dwarf32,
+        version: 4,
+        address_size: 4,
+    };
+    let mut program = LineProgram::new(
+        encoding,
+        LineEncoding::default(),
+        LineString::String(b"/src".to_vec()),
+        LineString::String(b"add.c".to_vec()),
+        None,
+    );
+    let directory = program.default_directory();
+    let file = program.add_file(LineString::String(b"add.c".to_vec()), directory, None);
+    program.begin_sequence(Some(Address::Constant(3)));
+    program.row().file = file;
+    program.row().line = 10;
+    program.generate_row();
+    program.row().address_offset = 4;
+    program.row().line = 11;
+    program.generate_row();
+    program.end_sequence(6);
+    let mut debug_line = write::DebugLine::from(EndianVec::new(LittleEndian));
+    program
+        .write(
+            &mut debug_line,
+            encoding,
+            &write::DebugLineStrOffsets::none(),
+            &write::DebugStrOffsets::none(),
+        )
+        .unwrap();
+
+    let mut section = vec![];
+    section.push(b".debug_line".len() as u8);
+    section.extend_from_slice(b".debug_line");
+    section.extend_from_slice(debug_line.0.slice());
+    let mut wasm = wasm;
+    wasm.push(0);
+    // The section size as a 5 byte LEB128.
+    let mut size = section.len() as u32;
+    for _ in 0..4 {
+        wasm.push((size & 0x7f) as u8 | 0x80);
+        size >>= 7;
+    }
+    wasm.push(size as u8);
+    wasm.extend_from_slice(&section);
+
+    let lines = compile_and_read_lines(&wasm);
+    assert!(lines.contains(&(b"add.c".to_vec(), 11)));
+}
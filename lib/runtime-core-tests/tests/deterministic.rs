@@ -0,0 +1,75 @@
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    compile_with_config,
+    error::CompileError,
+    imports,
+    typed_func::Func,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+fn deterministic_config() -> CompilerConfig {
+    CompilerConfig {
+        deterministic: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_deterministic_nans_are_canonical() {
+    const MODULE: &str = r#"
+(module
+  (func (export "f32_nan") (param f32 f32) (result i32)
+    get_local 0
+    get_local 1
+    f32.div
+    i32.reinterpret/f32)
+  (func (export "f64_nan") (param f64) (result i64)
+    get_local 0
+    f64.sqrt
+    i64.reinterpret/f64)
+  (func (export "f32_add") (param f32 f32) (result f32)
+    get_local 0
+    get_local 1
+    f32.add))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module =
+        compile_with_config(&wasm_binary, &get_compiler(), deterministic_config()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let f32_nan: Func<(f32, f32), i32> = instance.func("f32_nan").unwrap();
+    assert_eq!(f32_nan.call(0.0, 0.0), Ok(0x7fc0_0000));
+    // Even a NaN operand with a payload and a sign comes out canonical.
+    let noisy_nan = f32::from_bits(0xffc0_1234);
+    assert_eq!(f32_nan.call(noisy_nan, 1.0), Ok(0x7fc0_0000));
+
+    let f64_nan: Func<f64, i64> = instance.func("f64_nan").unwrap();
+    assert_eq!(f64_nan.call(-1.0), Ok(0x7ff8_0000_0000_0000));
+
+    // Other results are left alone.
+    let f32_add: Func<(f32, f32), f32> = instance.func("f32_add").unwrap();
+    assert_eq!(f32_add.call(1.5, -0.25), Ok(1.25));
+}
+
+#[test]
+fn test_deterministic_rejects_threads() {
+    let wasm_binary = wat2wasm("(module)".as_bytes()).unwrap();
+    let result = compile_with_config(
+        &wasm_binary,
+        &get_compiler(),
+        CompilerConfig {
+            features: Features {
+                threads: true,
+                ..Default::default()
+            },
+            ..deterministic_config()
+        },
+    );
+    match result {
+        Err(CompileError::UnsupportedFeature { feature }) => {
+            assert_eq!(feature, "threads in deterministic mode")
+        }
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+}
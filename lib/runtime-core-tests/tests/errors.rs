@@ -0,0 +1,127 @@
+use std::any::Any;
+use wasmer_runtime_core::{
+    compile_with,
+    error::{CallError, Error, LinkError},
+    export::ExportKind,
+    global::Global,
+    imports,
+    memory::Memory,
+    typed_func::{raise_trap, Func, WasmTrapInfo},
+    types::{MemoryDescriptor, Value},
+    units::Pages,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn test_structured_link_errors() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "f" (func))
+  (import "env" "memory" (memory 1))
+  (data (i32.const 65530) "too long"))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    let memory = Memory::new(MemoryDescriptor::new(Pages(1), None, false).unwrap()).unwrap();
+    let result = module.instantiate(&imports! {
+        "env" => {
+            "f" => Global::new(Value::I32(0)),
+            "memory" => memory.clone(),
+        },
+    });
+    match result {
+        Err(Error::LinkError(errors)) => match errors[..] {
+            [LinkError::IncorrectImportType {
+                ref name,
+                expected: ExportKind::Function,
+                found: ExportKind::Global,
+                ..
+            }] => assert_eq!(name, "f"),
+            _ => panic!("unexpected link errors: {:?}", errors),
+        },
+        _ => panic!("the instantiation should fail to link"),
+    }
+
+    let result = module.instantiate(&imports! {
+        "env" => {
+            "f" => wasmer_runtime_core::func!(|| {}),
+            "memory" => memory,
+        },
+    });
+    match result {
+        Err(Error::LinkError(errors)) => match errors[..] {
+            [LinkError::DataSegmentDoesNotFit { offset, len }] => {
+                assert_eq!((offset, len), (65530, 8))
+            }
+            _ => panic!("unexpected link errors: {:?}", errors),
+        },
+        _ => panic!("the instantiation should fail to link"),
+    }
+}
+
+#[cfg(feature = "backend-cranelift")]
+#[test]
+fn test_trap_codes() {
+    const MODULE: &str = r#"
+(module
+  (memory 1)
+  (func (export "unreachable") unreachable)
+  (func (export "load") (result i32)
+    i32.const 65536
+    i32.load))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    for &(name, code) in &[
+        ("unreachable", WasmTrapInfo::Unreachable),
+        ("load", WasmTrapInfo::MemoryOutOfBounds),
+    ] {
+        match instance.call(name, &[]) {
+            Err(CallError::Runtime(error)) => assert_eq!(error.trap_code(), Some(code)),
+            _ => panic!("{} should trap", name),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct HostError(u32);
+
+#[test]
+fn test_host_traps_carry_their_payload() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "raise" (func $raise (param i32)))
+  (import "env" "fail" (func $fail (param i32)))
+  (func (export "raise") (param i32) get_local 0 call $raise)
+  (func (export "fail") (param i32) get_local 0 call $fail))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = module
+        .instantiate(&imports! {
+            "env" => {
+                "raise" => Func::new(|code: i32| {
+                    unsafe { raise_trap(Box::new(HostError(code as u32))) };
+                }),
+                "fail" => Func::new(|code: i32| -> Result<(), Box<dyn Any>> {
+                    Err(Box::new(HostError(code as u32)))
+                }),
+            },
+        })
+        .unwrap();
+
+    for &(name, code) in &[("raise", 1), ("fail", 2)] {
+        match instance.call(name, &[Value::I32(code)]) {
+            Err(CallError::Runtime(error)) => {
+                assert_eq!(error.downcast_ref(), Some(&HostError(code as u32)))
+            }
+            _ => panic!("{} should trap", name),
+        }
+    }
+}
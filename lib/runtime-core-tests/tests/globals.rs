@@ -0,0 +1,102 @@
+use std::{thread, time::Duration};
+use wasmer_runtime_core::{compile_with, global::Global, imports, typed_func::Func, types::Value};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn test_host_updates_imported_global() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "deadline" (global $deadline (mut i32)))
+  (func (export "spin") (result i32)
+    (local $iterations i32)
+    block
+      loop
+        get_global $deadline
+        br_if 1
+        get_local $iterations
+        i32.const 1
+        i32.add
+        set_local $iterations
+        br 0
+      end
+    end
+    i32.const 2
+    set_global $deadline
+    get_local $iterations))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    let deadline = Global::new_mutable(Value::I32(0));
+    let import_object = imports! {
+        "env" => {
+            "deadline" => deadline.clone(),
+        },
+    };
+    let instance = module.instantiate(&import_object).unwrap();
+
+    let host_deadline = deadline.clone();
+    let setter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        host_deadline.set(Value::I32(1));
+    });
+
+    // The loop only exits once it sees the value set by the host.
+    let spin: Func<(), i32> = instance.func("spin").unwrap();
+    assert!(spin.call().is_ok());
+    setter.join().unwrap();
+
+    // And the host sees the value set by the instance.
+    assert_eq!(deadline.get(), Value::I32(2));
+}
+
+#[test]
+fn test_host_updates_imported_float_global() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "deadline" (global $deadline (mut f64)))
+  (func (export "spin") (result i32)
+    (local $iterations i32)
+    block
+      loop
+        get_global $deadline
+        f64.const 0
+        f64.gt
+        br_if 1
+        get_local $iterations
+        i32.const 1
+        i32.add
+        set_local $iterations
+        br 0
+      end
+    end
+    f64.const 2.5
+    set_global $deadline
+    get_local $iterations))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    let deadline = Global::new_mutable(Value::F64(0.0));
+    let import_object = imports! {
+        "env" => {
+            "deadline" => deadline.clone(),
+        },
+    };
+    let instance = module.instantiate(&import_object).unwrap();
+
+    let host_deadline = deadline.clone();
+    let setter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        host_deadline.set(Value::F64(1.5));
+    });
+
+    // Float globals are accessed atomically too, through their bits.
+    let spin: Func<(), i32> = instance.func("spin").unwrap();
+    assert!(spin.call().is_ok());
+    setter.join().unwrap();
+
+    assert_eq!(deadline.get(), Value::F64(2.5));
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "backend-llvm")]
+
+use std::fs;
+use wasmer_runtime_core::{
+    backend::CompilerConfig, compile_with_config, imports, typed_func::Func,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (func (export "sum") (param i32) (result i32)
+    get_local 0
+    i32.const 1
+    i32.add
+    i32.const 2
+    i32.add
+    i32.const 3
+    i32.add))
+"#;
+
+/// Compiles `MODULE` with `huge_function_threshold`, returning whether the
+/// function was left unoptimized.
+fn compile_and_check(huge_function_threshold: Option<usize>) -> bool {
+    let ir_path =
+        std::env::temp_dir().join(format!("wasmer-huge-function-{}.ll", std::process::id()));
+    unsafe {
+        wasmer_llvm_backend::GLOBAL_OPTIONS.pre_opt_ir = Some(ir_path.clone());
+    }
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with_config(
+        &wasm_binary,
+        &get_compiler(),
+        CompilerConfig {
+            huge_function_threshold,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    unsafe {
+        wasmer_llvm_backend::GLOBAL_OPTIONS.pre_opt_ir = None;
+    }
+    let ir = fs::read_to_string(&ir_path).unwrap();
+    fs::remove_file(&ir_path).unwrap();
+
+    // The function computes the same result either way.
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let sum: Func<i32, i32> = instance.func("sum").unwrap();
+    assert_eq!(sum.call(4), Ok(10));
+
+    ir.contains("optnone")
+}
+
+// A single test, since the IR output path is global.
+#[test]
+fn test_huge_function_threshold() {
+    assert!(!compile_and_check(None));
+    assert!(!compile_and_check(Some(1000)));
+    assert!(compile_and_check(Some(3)));
+}
@@ -17,9 +17,10 @@ macro_rules! call_and_assert {
                 concat!("Expected right when calling `", stringify!($function), "`.")
             ),
             (
-                Err(RuntimeError::Error { data }),
+                Err(RuntimeError::Error { data, .. }),
                 Err(RuntimeError::Error {
                     data: expected_data,
+                    ..
                 }),
             ) => {
                 if let (Some(data), Some(expected_data)) = (
@@ -261,34 +262,238 @@ test!(
     test_fn_trap,
     function_fn_trap,
     Err(RuntimeError::Error {
-        data: Box::new(format!("foo {}", 2))
+        data: Box::new(format!("foo {}", 2)),
+        backtrace: None,
     })
 );
 test!(
     test_closure_trap,
     function_closure_trap,
     Err(RuntimeError::Error {
-        data: Box::new(format!("bar {}", 2))
+        data: Box::new(format!("bar {}", 2)),
+        backtrace: None,
     })
 );
 test!(
     test_fn_trap_with_vmctx,
     function_fn_trap_with_vmctx,
     Err(RuntimeError::Error {
-        data: Box::new(format!("baz {}", 2 + SHIFT))
+        data: Box::new(format!("baz {}", 2 + SHIFT)),
+        backtrace: None,
     })
 );
 test!(
     test_closure_trap_with_vmctx,
     function_closure_trap_with_vmctx,
     Err(RuntimeError::Error {
-        data: Box::new(format!("qux {}", 2 + SHIFT))
+        data: Box::new(format!("qux {}", 2 + SHIFT)),
+        backtrace: None,
     })
 );
 test!(
     test_closure_trap_with_vmctx_and_env,
     function_closure_trap_with_vmctx_and_env,
     Err(RuntimeError::Error {
-        data: Box::new(format!("! {}", 2 + shift + SHIFT))
+        data: Box::new(format!("! {}", 2 + shift + SHIFT)),
+        backtrace: None,
     })
 );
+
+#[test]
+fn test_closure_env_is_shared_and_dropped() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    const MODULE: &str = r#"
+(module
+  (import "env" "count" (func $count (param i32) (result i32)))
+  (func (export "run") (param i32) (result i32)
+    get_local 0
+    call $count))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    let instance = {
+        let counter = Arc::clone(&counter);
+        let import_object = imports! {
+            "env" => {
+                "count" => Func::new(move |n: i32| -> i32 {
+                    counter.fetch_add(n as usize, Ordering::SeqCst) as i32
+                }),
+            },
+        };
+
+        module.instantiate(&import_object).unwrap()
+    };
+
+    // The import object is gone, the instance keeps the closure alive.
+    assert_eq!(Arc::strong_count(&counter), 2);
+
+    {
+        let run: Func<i32, i32> = instance.func("run").unwrap();
+        assert_eq!(run.call(3), Ok(0));
+        assert_eq!(run.call(4), Ok(3));
+        assert_eq!(counter.load(Ordering::SeqCst), 7);
+    }
+
+    drop(instance);
+    assert_eq!(Arc::strong_count(&counter), 1);
+}
+
+#[test]
+fn test_instance_exports_as_imports() {
+    use std::sync::{Arc, Mutex};
+    use wasmer_runtime_core::{error::LinkError, import::ImportObject};
+
+    const PROVIDER: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "add") (param i32 i32) (result i32)
+    get_local 0
+    get_local 1
+    i32.add))
+"#;
+
+    const CONSUMER: &str = r#"
+(module
+  (import "env" "memory" (memory 1))
+  (import "env" "add" (func $add (param i32 i32) (result i32)))
+  (func (export "run") (param i32) (result i32)
+    i32.const 0
+    get_local 0
+    i32.const 1
+    call $add
+    i32.store
+    i32.const 0
+    i32.load))
+"#;
+
+    let compile = |wat: &str| {
+        let wasm_binary = wat2wasm(wat.as_bytes()).expect("WAST not valid or malformed");
+        compile_with(&wasm_binary, &get_compiler()).unwrap()
+    };
+
+    let provider = compile(PROVIDER).instantiate(&imports! {}).unwrap();
+    let provider = Arc::new(Mutex::new(provider));
+
+    let mut import_object = ImportObject::new();
+    import_object.register("env", Arc::clone(&provider));
+
+    let consumer = compile(CONSUMER).instantiate(&import_object).unwrap();
+    let run: Func<i32, i32> = consumer.func("run").unwrap();
+
+    assert_eq!(run.call(41), Ok(42));
+    // The memory is shared with the providing instance.
+    {
+        let provider = provider.lock().unwrap();
+        assert_eq!(provider.context().memory(0).view::<i32>()[0].get(), 42);
+    }
+
+    // Exports are checked against the imports they are linked to.
+    const MISMATCH: &str = r#"
+(module
+  (import "env" "add" (func $add (param i64) (result i64))))
+"#;
+
+    match compile(MISMATCH).instantiate(&import_object) {
+        Err(wasmer_runtime_core::error::Error::LinkError(errors)) => match errors.as_slice() {
+            [LinkError::IncorrectImportSignature { .. }] => {}
+            errors => panic!("unexpected link errors: {:?}", errors),
+        },
+        _ => panic!("expected a link error"),
+    }
+}
+
+// `DynamicFunc`s are only available there.
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[test]
+fn test_dynamic_func_params_and_errors() {
+    use std::sync::{Arc, Mutex};
+    use wasmer_runtime_core::{
+        typed_func::DynamicFunc,
+        types::{FuncSig, Type, Value},
+    };
+
+    // With the context first, the last five integers and the last two floats are passed on
+    // the stack.
+    const MODULE: &str = r#"
+(module
+  (type $mix (func (param i32 f32 i64 f64 i32 f32 i64 f64 i32 f32
+                          i64 f64 i32 f32 i64 f64 i32 f32 i64 f64) (result f64)))
+  (import "env" "mix" (func $mix (type $mix)))
+  (import "env" "wrong" (func $wrong (param f32) (result f32)))
+  (func (export "mix") (result f64)
+    i32.const 1 f32.const 2 i64.const 3 f64.const 4 i32.const 5
+    f32.const 6 i64.const 7 f64.const 8 i32.const 9 f32.const 10
+    i64.const 11 f64.const 12 i32.const 13 f32.const 14 i64.const 15
+    f64.const 16 i32.const 17 f32.const 18 i64.const 19 f64.const 20
+    call $mix)
+  (func (export "wrong") (param f32) (result f32)
+    get_local 0
+    call $wrong))
+"#;
+
+    let types = [Type::I32, Type::F32, Type::I64, Type::F64];
+    let params: Vec<Type> = (0..20).map(|i| types[i % 4]).collect();
+    let received = Arc::new(Mutex::new(vec![]));
+    let mix = DynamicFunc::new(Arc::new(FuncSig::new(params, vec![Type::F64])), {
+        let received = Arc::clone(&received);
+        move |_, params| {
+            *received.lock().unwrap() = params.to_vec();
+            Ok(vec![Value::F64(params.len() as f64 / 2.0)])
+        }
+    });
+    // Returning values of the wrong types traps.
+    let wrong = DynamicFunc::new(
+        Arc::new(FuncSig::new(vec![Type::F32], vec![Type::F32])),
+        |_, _| Ok(vec![Value::F64(0.0)]),
+    );
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let import_object = imports! {
+        "env" => {
+            "mix" => mix,
+            "wrong" => wrong,
+        },
+    };
+    let instance = module.instantiate(&import_object).unwrap();
+
+    assert_eq!(instance.call("mix", &[]).unwrap(), [Value::F64(10.0)]);
+    let expected: Vec<Value> = (1..=20)
+        .map(|i| match i % 4 {
+            1 => Value::I32(i),
+            2 => Value::F32(i as f32),
+            3 => Value::I64(i as i64),
+            _ => Value::F64(i as f64),
+        })
+        .collect();
+    assert_eq!(*received.lock().unwrap(), expected);
+
+    let wrong: Func<f32, f32> = instance.func("wrong").unwrap();
+    match wrong.call(1.5) {
+        Err(RuntimeError::Error { data, .. }) => assert_eq!(
+            data.downcast_ref::<String>().map(String::as_str),
+            Some("the host function returned values of types [F64], rather than [F32]")
+        ),
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    assert!(!DynamicFunc::is_supported(&FuncSig::new(
+        vec![],
+        vec![Type::F32, Type::F64, Type::F32]
+    )));
+    assert!(!DynamicFunc::is_supported(&FuncSig::new(
+        vec![Type::V128],
+        vec![]
+    )));
+    assert!(DynamicFunc::is_supported(&FuncSig::new(
+        vec![],
+        vec![Type::I32, Type::F32, Type::I64, Type::F64]
+    )));
+}
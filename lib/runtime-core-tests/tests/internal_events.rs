@@ -0,0 +1,56 @@
+// Cranelift lowers the internal events of middlewares like the other backends.
+#![cfg(feature = "backend-cranelift")]
+
+use wasmer_clif_backend::ModuleCodeGenerator as CraneliftMCG;
+use wasmer_runtime_core::{
+    call_depth::{CallDepth, CallStackExhausted},
+    codegen::{MiddlewareChain, StreamingCompiler},
+    compile_with,
+    error::RuntimeError,
+    imports, Func,
+};
+use wasmer_runtime_core_tests::wat2wasm;
+
+#[test]
+fn test_cranelift_enforces_limit_events() {
+    const MODULE: &str = r#"
+(module
+  (func $depth (export "depth") (param i32) (result i32)
+    get_local 0
+    i32.eqz
+    if (result i32)
+      i32.const 1
+    else
+      get_local 0
+      i32.const 1
+      i32.sub
+      call $depth
+      i32.const 1
+      i32.add
+    end))
+"#;
+
+    let compiler: StreamingCompiler<CraneliftMCG, _, _, _, _> = StreamingCompiler::new(|| {
+        let mut chain = MiddlewareChain::new();
+        chain.push(CallDepth::new(10));
+        chain
+    });
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &compiler).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let depth: Func<i32, i32> = instance.func("depth").unwrap();
+    assert_eq!(depth.call(9), Ok(10));
+    match depth.call(10).unwrap_err() {
+        RuntimeError::Error { data, .. } => {
+            assert!(data.downcast_ref::<CallStackExhausted>().is_some());
+        }
+        _ => unreachable!(),
+    }
+
+    // The trap leaves the instance usable.
+    assert_eq!(depth.call(9), Ok(10));
+
+    // The compiled code refers to the handlers of its breakpoints by address.
+    assert!(module.cache().is_err());
+}
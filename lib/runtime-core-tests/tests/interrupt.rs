@@ -0,0 +1,71 @@
+#![cfg(all(unix, target_arch = "x86_64"))]
+
+use wasmer_runtime_core::{compile_with, imports};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+// Only singlepass emits the safepoints interrupts are delivered at, without `track_state`.
+#[cfg(feature = "backend-singlepass")]
+#[test]
+fn test_interrupt_from_another_thread() {
+    use std::{thread, time::Duration};
+    use wasmer_runtime_core::{error::RuntimeError, typed_func::Func};
+
+    const MODULE: &str = r#"
+(module
+  (func (export "spin")
+    loop
+      br 0
+    end)
+  (func (export "answer") (result i32)
+    i32.const 42))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+    let handle = instance.interrupt_handle().unwrap();
+
+    let interrupter = {
+        let handle = handle.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            handle.interrupt();
+        })
+    };
+
+    let spin: Func<(), ()> = instance.func("spin").unwrap();
+    match spin.call() {
+        Err(RuntimeError::Interrupted) => {}
+        result => panic!("expected an interrupt, got {:?}", result),
+    }
+    interrupter.join().unwrap();
+
+    // The instance keeps working after an interrupt.
+    let answer: Func<(), i32> = instance.func("answer").unwrap();
+    assert_eq!(answer.call(), Ok(42));
+
+    // An interrupt requested while the instance is idle stops its next call.
+    handle.interrupt();
+    match answer.call() {
+        Err(RuntimeError::Interrupted) => {}
+        result => panic!("expected an interrupt, got {:?}", result),
+    }
+    assert_eq!(answer.call(), Ok(42));
+}
+
+#[cfg(any(feature = "backend-cranelift", feature = "backend-llvm"))]
+#[test]
+fn test_interrupt_without_safepoints() {
+    use wasmer_runtime_core::error::InterruptError;
+
+    let wasm_binary = wat2wasm(b"(module)".as_ref()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+
+    // Cranelift code, and LLVM code without `track_state`, would never be
+    // interrupted.
+    assert_eq!(
+        instance.interrupt_handle().err(),
+        Some(InterruptError::NoSafepoints)
+    );
+}
@@ -0,0 +1,82 @@
+use wasmer_runtime_core::{error::CompileError, imports, lazy::LazyModule, types::Value};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn test_lazy_module_compiles_on_first_instantiation() {
+    const MODULE: &str = r#"
+(module
+  (func (export "answer") (result i32) i32.const 42))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = LazyModule::new(&wasm_binary, get_compiler(), Default::default()).unwrap();
+    assert!(!module.is_compiled());
+
+    let instance = module.instantiate(&imports! {}).unwrap();
+    assert!(module.is_compiled());
+    assert_eq!(instance.call("answer", &[]).unwrap(), [Value::I32(42)]);
+
+    let instance = module.instantiate(&imports! {}).unwrap();
+    assert_eq!(instance.call("answer", &[]).unwrap(), [Value::I32(42)]);
+}
+
+#[test]
+fn test_lazy_module_validates_up_front() {
+    #[rustfmt::skip]
+    let wasm_binary = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // A function type returning an i32.
+        0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f,
+        0x03, 0x02, 0x01, 0x00,
+        // A body returning an i64 instead.
+        0x0a, 0x06, 0x01, 0x04, 0x00, 0x42, 0x2a, 0x0b,
+    ];
+    match LazyModule::new(&wasm_binary, get_compiler(), Default::default()) {
+        Err(CompileError::ValidationError { .. }) => {}
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("an invalid module was accepted"),
+    }
+}
+
+// LLVM compiles the whole lazy module on first instantiation.
+#[cfg(not(feature = "backend-llvm"))]
+#[test]
+fn test_lazy_module_compiles_called_functions_only() {
+    use wasmer_runtime_core::{structures::TypedIndex, types::LocalFuncIndex};
+
+    const MODULE: &str = r#"
+(module
+  (func $double (param i32) (result i32)
+    get_local 0
+    i32.const 2
+    i32.mul)
+  (func (export "quadruple") (param i32) (result i32)
+    get_local 0
+    call $double
+    call $double)
+  (func (export "unused") (result i32) i32.const 0))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = LazyModule::new(&wasm_binary, get_compiler(), Default::default()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let compiled = || -> Vec<bool> {
+        (0..3)
+            .map(|index| module.is_function_compiled(LocalFuncIndex::new(index)))
+            .collect()
+    };
+    assert_eq!(compiled(), [false, false, false]);
+
+    assert_eq!(
+        instance.call("quadruple", &[Value::I32(3)]).unwrap(),
+        [Value::I32(12)]
+    );
+    assert_eq!(compiled(), [true, true, false]);
+
+    // The patched stubs jump to the compiled functions, in every instance.
+    let instance = module.instantiate(&imports! {}).unwrap();
+    assert_eq!(
+        instance.call("quadruple", &[Value::I32(5)]).unwrap(),
+        [Value::I32(20)]
+    );
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+use wasmer_runtime_core::{
+    compile_with, error::GrowError, export::Export, imports, limits::ResourceLimiter, table::Table,
+    typed_func::Func, units::Pages,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+struct Limits {
+    max_pages: Pages,
+    max_elements: u32,
+}
+
+impl ResourceLimiter for Limits {
+    fn memory_growing(&self, _current: Pages, desired: Pages) -> bool {
+        desired <= self.max_pages
+    }
+
+    fn table_growing(&self, _current: u32, desired: u32) -> bool {
+        desired <= self.max_elements
+    }
+}
+
+#[test]
+fn test_resource_limiter() {
+    const MODULE: &str = r#"
+(module
+  (memory 1 10)
+  (table (export "table") 1 anyfunc)
+  (func (export "grow") (param i32) (result i32)
+    get_local 0
+    memory.grow))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+    instance.set_resource_limiter(Some(Arc::new(Limits {
+        max_pages: Pages(2),
+        max_elements: 3,
+    })));
+
+    {
+        let grow: Func<i32, i32> = instance.func("grow").unwrap();
+        assert_eq!(grow.call(1), Ok(1));
+        assert_eq!(grow.call(1), Ok(-1));
+    }
+
+    let table: Table = match instance.exports().find(|(name, _)| name == "table") {
+        Some((_, Export::Table(table))) => table,
+        _ => panic!("the table is not exported"),
+    };
+    assert_eq!(table.grow(2), Ok(1));
+    match table.grow(1) {
+        Err(GrowError::Vetoed) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(table.size(), 3);
+
+    // Without a limiter, only the maximum declared by the module applies.
+    instance.set_resource_limiter(None);
+    let grow: Func<i32, i32> = instance.func("grow").unwrap();
+    assert_eq!(grow.call(1), Ok(2));
+    assert_eq!(table.grow(1), Ok(3));
+}
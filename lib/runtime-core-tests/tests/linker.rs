@@ -0,0 +1,128 @@
+use wasmer_runtime_core::{
+    compile_with,
+    error::LinkerError,
+    global::Global,
+    imports,
+    linker::{DylinkInfo, Linker},
+    memory::Memory,
+    table::Table,
+    types::{ElementType, MemoryDescriptor, TableDescriptor, Value},
+    units::Pages,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[rustfmt::skip]
+const DYLINK_SECTION: [u8; 14] = [
+    0x00, 0x0c, 0x06, b'd', b'y', b'l', b'i', b'n', b'k',
+    // 4 bytes of data aligned to 4, 1 table element aligned to 1, no needed modules.
+    0x04, 0x02, 0x01, 0x00, 0x00,
+];
+
+fn compile_dylink_module(wat: &str) -> wasmer_runtime_core::Module {
+    let mut wasm_binary = wat2wasm(wat.as_bytes()).expect("WAST not valid or malformed");
+    wasm_binary.extend_from_slice(&DYLINK_SECTION);
+    compile_with(&wasm_binary, &get_compiler()).unwrap()
+}
+
+#[test]
+fn test_linked_modules_share_memory_table_and_symbols() {
+    const SIDE: &str = r#"
+(module
+  (type $t (func (result i32)))
+  (import "env" "memory" (memory 1))
+  (import "env" "__indirect_function_table" (table 0 anyfunc))
+  (import "env" "__memory_base" (global $memory_base i32))
+  (import "env" "__table_base" (global $table_base i32))
+  (data (get_global $memory_base) "side")
+  (elem (get_global $table_base) $answer)
+  (func $answer (type $t) i32.const 42)
+  (func (export "answer") (type $t) call $answer)
+  (func (export "data_addr") (type $t) get_global $memory_base))
+"#;
+    const MAIN: &str = r#"
+(module
+  (type $t (func (result i32)))
+  (import "env" "memory" (memory 1))
+  (import "env" "__indirect_function_table" (table 0 anyfunc))
+  (import "env" "__memory_base" (global $memory_base i32))
+  (import "env" "__table_base" (global $table_base i32))
+  (import "env" "answer" (func $answer (type $t)))
+  (import "env" "offset" (global $offset i32))
+  (func (export "run") (type $t) call $answer get_global $offset i32.add)
+  (func (export "indirect") (param i32) (result i32)
+    get_local 0
+    call_indirect (type $t)))
+"#;
+
+    let memory = Memory::new(MemoryDescriptor::new(Pages(1), None, false).unwrap()).unwrap();
+    let table = Table::new(TableDescriptor {
+        element: ElementType::Anyfunc,
+        minimum: 0,
+        maximum: None,
+    })
+    .unwrap();
+    let mut linker = Linker::new(memory, table);
+
+    let side = compile_dylink_module(SIDE);
+    assert_eq!(
+        DylinkInfo::from_module(&side).unwrap(),
+        DylinkInfo {
+            memory_size: 4,
+            memory_alignment: 2,
+            table_size: 1,
+            table_alignment: 0,
+            needed: vec![],
+        }
+    );
+    let side_index = linker.link(&side, &imports! {}).unwrap();
+    let main = compile_dylink_module(MAIN);
+    let main_index = linker
+        .link(
+            &main,
+            &imports! {
+                "env" => {
+                    "offset" => Global::new(Value::I32(1)),
+                },
+            },
+        )
+        .unwrap();
+
+    // The side module was placed after the initial page, and the main module after it.
+    assert_eq!(linker.memory().size(), Pages(2));
+    assert_eq!(linker.table().size(), 2);
+    let view = linker.memory().view::<u8>();
+    let bytes: Vec<u8> = view[65536..65540].iter().map(|cell| cell.get()).collect();
+    assert_eq!(bytes, b"side");
+
+    let side = linker.instance(side_index).unwrap();
+    assert_eq!(side.call("data_addr", &[]).unwrap(), [Value::I32(65536)]);
+
+    let main = linker.instance(main_index).unwrap();
+    assert_eq!(main.call("run", &[]).unwrap(), [Value::I32(43)]);
+    assert_eq!(
+        main.call("indirect", &[Value::I32(0)]).unwrap(),
+        [Value::I32(42)]
+    );
+    assert!(linker.resolve("answer").is_some());
+    assert!(linker.resolve("missing").is_none());
+}
+
+#[test]
+fn test_linking_requires_a_dylink_section() {
+    let wasm_binary = wat2wasm("(module)".as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    let memory = Memory::new(MemoryDescriptor::new(Pages(1), None, false).unwrap()).unwrap();
+    let table = Table::new(TableDescriptor {
+        element: ElementType::Anyfunc,
+        minimum: 0,
+        maximum: None,
+    })
+    .unwrap();
+    let mut linker = Linker::new(memory, table);
+    match linker.link(&module, &imports! {}) {
+        Err(LinkerError::MissingDylinkSection) => {}
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("a module without a dylink section was linked"),
+    }
+}
@@ -0,0 +1,63 @@
+use wasmer_runtime_core::{compile_with, global::Global, imports, types::Value};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn test_data_segments_are_private_to_each_instance() {
+    const MODULE: &str = r#"
+(module
+  (memory (export "memory") 2)
+  (data (i32.const 8) "hello")
+  (data (i32.const 10) "LL")
+  (data (i32.const 70000) "world"))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    let first = module.instantiate(&imports! {}).unwrap();
+    let second = module.instantiate(&imports! {}).unwrap();
+
+    for instance in &[&first, &second] {
+        let view = instance.context().memory(0).view::<u8>();
+        let bytes: Vec<u8> = view[8..13].iter().map(|cell| cell.get()).collect();
+        assert_eq!(bytes, b"heLLo");
+        let bytes: Vec<u8> = view[70000..70005].iter().map(|cell| cell.get()).collect();
+        assert_eq!(bytes, b"world");
+        assert_eq!(view[0].get(), 0);
+    }
+
+    // Writes are only seen by the instance making them.
+    first.context().memory(0).view::<u8>()[8].set(b'j');
+    assert_eq!(first.context().memory(0).view::<u8>()[8].get(), b'j');
+    assert_eq!(second.context().memory(0).view::<u8>()[8].get(), b'h');
+
+    let third = module.instantiate(&imports! {}).unwrap();
+    assert_eq!(third.context().memory(0).view::<u8>()[8].get(), b'h');
+}
+
+#[test]
+fn test_data_segments_with_imported_offsets() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "offset" (global $offset i32))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "abcd")
+  (data (get_global $offset) "XY"))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    for &(offset, expected) in &[(1, b"aXYd"), (2, b"abXY")] {
+        let instance = module
+            .instantiate(&imports! {
+                "env" => {
+                    "offset" => Global::new(Value::I32(offset)),
+                },
+            })
+            .unwrap();
+        let view = instance.context().memory(0).view::<u8>();
+        let bytes: Vec<u8> = view[0..4].iter().map(|cell| cell.get()).collect();
+        assert_eq!(&bytes[..], &expected[..]);
+    }
+}
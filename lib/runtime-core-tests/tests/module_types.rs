@@ -0,0 +1,95 @@
+use wasmer_runtime_core::{
+    compile_with,
+    module::ExternDescriptor,
+    types::{ElementType, FuncSig, Type},
+    units::Pages,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn test_module_import_and_export_types() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "log" (func $log (param i32 i64)))
+  (import "env" "memory" (memory 1 4))
+  (import "env" "table" (table 2 anyfunc))
+  (import "env" "offset" (global i32))
+  (global $counter (mut f64) (f64.const 0))
+  (func (export "add") (param i32 i32) (result i32)
+    get_local 0
+    get_local 1
+    i32.add)
+  (export "log" (func $log))
+  (export "memory" (memory 0))
+  (export "table" (table 0))
+  (export "counter" (global $counter)))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    let imports = module.imports();
+    let names: Vec<_> = imports
+        .iter()
+        .map(|import| (import.namespace.as_str(), import.name.as_str()))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ("env", "log"),
+            ("env", "memory"),
+            ("env", "table"),
+            ("env", "offset")
+        ]
+    );
+    match imports[0].ty {
+        ExternDescriptor::Function(ref sig) => {
+            assert_eq!(*sig, FuncSig::new(vec![Type::I32, Type::I64], vec![]))
+        }
+        ref ty => panic!("unexpected import type: {:?}", ty),
+    }
+    match imports[3].ty {
+        ExternDescriptor::Global(desc) => {
+            assert!(!desc.mutable);
+            assert_eq!(desc.ty, Type::I32);
+        }
+        ref ty => panic!("unexpected import type: {:?}", ty),
+    }
+
+    let exports = module.exports();
+    let names: Vec<_> = exports.iter().map(|export| export.name.as_str()).collect();
+    assert_eq!(names, ["add", "log", "memory", "table", "counter"]);
+    match exports[0].ty {
+        ExternDescriptor::Function(ref sig) => assert_eq!(
+            *sig,
+            FuncSig::new(vec![Type::I32, Type::I32], vec![Type::I32])
+        ),
+        ref ty => panic!("unexpected export type: {:?}", ty),
+    }
+    match exports[1].ty {
+        ExternDescriptor::Function(ref sig) => assert_eq!(sig.params(), [Type::I32, Type::I64]),
+        ref ty => panic!("unexpected export type: {:?}", ty),
+    }
+    match exports[2].ty {
+        ExternDescriptor::Memory(desc) => {
+            assert_eq!(desc.minimum, Pages(1));
+            assert_eq!(desc.maximum, Some(Pages(4)));
+        }
+        ref ty => panic!("unexpected export type: {:?}", ty),
+    }
+    match exports[3].ty {
+        ExternDescriptor::Table(desc) => {
+            assert_eq!(desc.element, ElementType::Anyfunc);
+            assert_eq!(desc.minimum, 2);
+            assert_eq!(desc.maximum, None);
+        }
+        ref ty => panic!("unexpected export type: {:?}", ty),
+    }
+    match exports[4].ty {
+        ExternDescriptor::Global(desc) => {
+            assert!(desc.mutable);
+            assert_eq!(desc.ty, Type::F64);
+        }
+        ref ty => panic!("unexpected export type: {:?}", ty),
+    }
+}
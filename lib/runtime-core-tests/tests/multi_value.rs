@@ -0,0 +1,147 @@
+#![cfg(feature = "backend-cranelift")]
+
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    compile_with_config, imports,
+    types::Value,
+};
+use wasmer_runtime_core_tests::get_compiler;
+
+#[test]
+fn test_dynamic_multi_value_calls() {
+    const MODULE: &str = r#"
+(module
+  (func $swap (export "swap") (param i32 i64) (result i64 i32)
+    get_local 1
+    get_local 0)
+  (func (export "spread") (param i32) (result f32 i32 i64 f64)
+    f32.const 1.5
+    get_local 0
+    i32.const 2
+    get_local 0
+    i64.extend_s/i32
+    call $swap
+    i64.extend_u/i32
+    i64.mul
+    f64.const -0.25))
+"#;
+
+    let mut features = wabt::Features::new();
+    features.enable_multi_value();
+    let wasm_binary = wabt::wat2wasm_with_features(MODULE.as_bytes(), features)
+        .expect("WAST not valid or malformed");
+    let module = compile_with_config(
+        &wasm_binary,
+        &get_compiler(),
+        CompilerConfig {
+            features: Features {
+                multi_value: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let swap = instance.dyn_func("swap").unwrap();
+    assert_eq!(
+        swap.call(&[Value::I32(1), Value::I64(-2)]).unwrap(),
+        [Value::I64(-2), Value::I32(1)]
+    );
+
+    assert_eq!(
+        instance.call("spread", &[Value::I32(21)]).unwrap(),
+        [
+            Value::F32(1.5),
+            Value::I32(21),
+            Value::I64(42),
+            Value::F64(-0.25)
+        ]
+    );
+}
+
+// `DynamicFunc`s are only available there.
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[test]
+fn test_host_multi_value_imports() {
+    use std::sync::Arc;
+    use wasmer_runtime_core::{
+        func,
+        typed_func::DynamicFunc,
+        types::{FuncSig, Type},
+    };
+
+    const MODULE: &str = r#"
+(module
+  (import "env" "divmod" (func $divmod (param i32 i32) (result i32 i32)))
+  (import "env" "spread" (func $spread (param i32) (result f32 i32 i64 f64)))
+  (func (export "divmod") (param i32 i32) (result i32 i32)
+    get_local 0
+    get_local 1
+    call $divmod)
+  (func (export "spread") (param i32) (result f32 i32 i64 f64)
+    get_local 0
+    call $spread))
+"#;
+
+    let mut features = wabt::Features::new();
+    features.enable_multi_value();
+    let wasm_binary = wabt::wat2wasm_with_features(MODULE.as_bytes(), features)
+        .expect("WAST not valid or malformed");
+    let module = compile_with_config(
+        &wasm_binary,
+        &get_compiler(),
+        CompilerConfig {
+            features: Features {
+                multi_value: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    // The integers come back in `rax` and `rdx`, and the floats in `xmm0` and `xmm1`.
+    let spread = DynamicFunc::new(
+        Arc::new(FuncSig::new(
+            vec![Type::I32],
+            vec![Type::F32, Type::I32, Type::I64, Type::F64],
+        )),
+        |_, params| {
+            let x = match params[0] {
+                Value::I32(x) => x,
+                _ => unreachable!(),
+            };
+            Ok(vec![
+                Value::F32(x as f32 / 2.0),
+                Value::I32(x + 1),
+                Value::I64(x as i64 * 2),
+                Value::F64(-(x as f64)),
+            ])
+        },
+    );
+    let import_object = imports! {
+        "env" => {
+            "divmod" => func!(|a: i32, b: i32| -> (i32, i32) { (a / b, a % b) }),
+            "spread" => spread,
+        },
+    };
+    let instance = module.instantiate(&import_object).unwrap();
+
+    assert_eq!(
+        instance
+            .call("divmod", &[Value::I32(17), Value::I32(5)])
+            .unwrap(),
+        [Value::I32(3), Value::I32(2)]
+    );
+    assert_eq!(
+        instance.call("spread", &[Value::I32(21)]).unwrap(),
+        [
+            Value::F32(10.5),
+            Value::I32(22),
+            Value::I64(42),
+            Value::F64(-21.0)
+        ]
+    );
+}
@@ -0,0 +1,26 @@
+use wabt::Wat2Wasm;
+use wasmer_runtime_core::{compile_with, structures::TypedIndex, types::FuncIndex};
+use wasmer_runtime_core_tests::get_compiler;
+
+#[test]
+fn test_function_names() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "log" (func $log (param i32)))
+  (func $helper)
+  (func (export "run")))
+"#;
+
+    let wasm_binary = Wat2Wasm::new()
+        .write_debug_names(true)
+        .convert(MODULE)
+        .expect("WAST not valid or malformed");
+    let module = compile_with(wasm_binary.as_ref(), &get_compiler()).unwrap();
+
+    assert_eq!(module.function_name(FuncIndex::new(0)), Some("log"));
+    assert_eq!(module.function_name(FuncIndex::new(1)), Some("helper"));
+    assert_eq!(module.function_name(FuncIndex::new(2)), None);
+
+    assert_eq!(module.function_index("helper"), Some(FuncIndex::new(1)));
+    assert_eq!(module.function_index("run"), None);
+}
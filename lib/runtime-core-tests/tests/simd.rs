@@ -0,0 +1,93 @@
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    compile_with_config,
+};
+use wasmer_runtime_core_tests::get_compiler;
+
+const MODULE: &str = r#"
+(module
+  (func (export "add_lanes") (param i32) (result i32)
+    v128.const i32x4 1 2 3 4
+    v128.const i32x4 10 20 30 40
+    i32x4.add
+    get_local 0
+    i32x4.splat
+    i32x4.mul
+    i32x4.extract_lane 2)
+  (func (export "mul_f32") (param f32) (result f32)
+    get_local 0
+    f32x4.splat
+    v128.const f32x4 0.5 1.5 2.5 3.5
+    f32x4.mul
+    f32x4.extract_lane 3)
+  (func (export "sum_pairs") (result i64)
+    v128.const i64x2 1 2
+    v128.const i64x2 100 200
+    i64x2.add
+    i64x2.extract_lane 1))
+"#;
+
+fn simd_wat2wasm(wat: &str) -> Vec<u8> {
+    let mut features = wabt::Features::new();
+    features.enable_simd();
+    wabt::wat2wasm_with_features(wat.as_bytes(), features).expect("WAST not valid or malformed")
+}
+
+fn simd_config() -> CompilerConfig {
+    CompilerConfig {
+        features: Features {
+            simd: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+// Singlepass doesn't support SIMD.
+#[cfg(not(feature = "backend-singlepass"))]
+#[test]
+fn test_simd_constants() {
+    use wasmer_runtime_core::{imports, typed_func::Func};
+
+    let wasm_binary = simd_wat2wasm(MODULE);
+    let module = compile_with_config(&wasm_binary, &get_compiler(), simd_config()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    // Each function loads its `v128.const`s from the constant pool.
+    let add_lanes: Func<i32, i32> = instance.func("add_lanes").unwrap();
+    assert_eq!(add_lanes.call(1), Ok(33));
+    assert_eq!(add_lanes.call(3), Ok(99));
+
+    let mul_f32: Func<f32, f32> = instance.func("mul_f32").unwrap();
+    assert_eq!(mul_f32.call(2.0), Ok(7.0));
+
+    let sum_pairs: Func<(), i64> = instance.func("sum_pairs").unwrap();
+    assert_eq!(sum_pairs.call(), Ok(202));
+}
+
+#[cfg(feature = "backend-singlepass")]
+#[test]
+fn test_simd_rejected_by_singlepass() {
+    // A `v128` local would be truncated to the 64-bit slots of singlepass.
+    const LOCAL: &str = r#"
+(module
+  (func (export "zero") (result i32)
+    (local v128)
+    i32.const 0))
+"#;
+
+    for wat in &[MODULE, LOCAL] {
+        let wasm_binary = simd_wat2wasm(wat);
+        match compile_with_config(&wasm_binary, &get_compiler(), simd_config()) {
+            Err(err) => {
+                let message = format!("{:?}", err);
+                assert!(
+                    message.contains("v128") || message.contains("V128"),
+                    "unexpected error: {}",
+                    message
+                );
+            }
+            Ok(_) => panic!("singlepass should reject SIMD"),
+        }
+    }
+}
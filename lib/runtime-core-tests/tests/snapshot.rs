@@ -0,0 +1,62 @@
+use wasmer_runtime_core::{compile_with, imports, state::InstanceImage, typed_func::Func};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn test_snapshot_and_restore() {
+    const MODULE: &str = r#"
+(module
+  (type $type (func (result i32)))
+  (memory 1)
+  (global $counter (mut i32) (i32.const 0))
+  (table 2 anyfunc)
+  (elem (i32.const 0) $one $two)
+  (func $one (type $type) i32.const 1)
+  (func $two (type $type) i32.const 2)
+  (func (export "step") (result i32)
+    get_global $counter
+    i32.const 1
+    i32.add
+    set_global $counter
+    i32.const 0
+    i32.const 0
+    i32.load
+    get_global $counter
+    i32.add
+    i32.store
+    i32.const 0
+    i32.load)
+  (func (export "dispatch") (param i32) (result i32)
+    get_local 0
+    call_indirect (type $type)))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+
+    let step = |instance: &wasmer_runtime_core::Instance| {
+        let step: Func<(), i32> = instance.func("step").unwrap();
+        step.call().unwrap()
+    };
+
+    // Warm the instance up, then take a snapshot.
+    assert_eq!(step(&instance), 1);
+    let image = InstanceImage::from_bytes(&instance.snapshot().to_bytes()).unwrap();
+    assert_eq!(image.tables, vec![vec![Some(0), Some(1)]]);
+
+    assert_eq!(step(&instance), 3);
+    assert_eq!(step(&instance), 6);
+
+    // Restoring brings the memory and the globals back.
+    instance.restore(&image).unwrap();
+    assert_eq!(step(&instance), 3);
+
+    // The image applies to other instances of the same module.
+    let mut other = module.instantiate(&imports! {}).unwrap();
+    other.restore(&image).unwrap();
+    assert_eq!(step(&other), 3);
+
+    let dispatch: Func<i32, i32> = other.func("dispatch").unwrap();
+    assert_eq!(dispatch.call(0), Ok(1));
+    assert_eq!(dispatch.call(1), Ok(2));
+}
@@ -0,0 +1,59 @@
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+use wasmer_runtime_core::{compile_with, error::CallError, imports, typed_func::Func};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn test_deferred_start_function() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "tick" (func $tick))
+  (func $start call $tick)
+  (start $start))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    let ticks = Arc::new(AtomicU32::new(0));
+    let shared_ticks = ticks.clone();
+    let import_object = imports! {
+        "env" => {
+            "tick" => Func::new(move || { shared_ticks.fetch_add(1, Ordering::SeqCst); }),
+        },
+    };
+
+    module.instantiate(&import_object).unwrap();
+    assert_eq!(ticks.load(Ordering::SeqCst), 1);
+
+    let mut instance = module.instantiate_without_start(&import_object).unwrap();
+    assert_eq!(ticks.load(Ordering::SeqCst), 1);
+    instance.run_start().unwrap();
+    assert_eq!(ticks.load(Ordering::SeqCst), 2);
+
+    // The start function only runs once.
+    instance.run_start().unwrap();
+    assert_eq!(ticks.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_deferred_start_function_traps() {
+    const MODULE: &str = r#"
+(module
+  (func $start unreachable)
+  (start $start))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    assert!(module.instantiate(&imports! {}).is_err());
+
+    let mut instance = module.instantiate_without_start(&imports! {}).unwrap();
+    match instance.run_start() {
+        Err(CallError::Runtime(_)) => {}
+        _ => panic!("the start function should trap"),
+    }
+}
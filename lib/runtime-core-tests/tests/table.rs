@@ -0,0 +1,166 @@
+use std::sync::{
+    atomic::{AtomicI32, Ordering},
+    Arc,
+};
+use wasmer_runtime_core::{
+    compile_with,
+    export::Export,
+    imports,
+    table::{Element, Table},
+    typed_func::Func,
+    vm,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn test_host_table_api() {
+    const MODULE: &str = r#"
+(module
+  (type $type (func (param i32) (result i32)))
+  (table (export "table") 1 anyfunc)
+  (elem (i32.const 0) $double)
+  (func $double (type $type) get_local 0 i32.const 2 i32.mul)
+  (func (export "dispatch") (param i32 i32) (result i32)
+    get_local 1
+    get_local 0
+    call_indirect (type $type)))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let table: Table = match instance.exports().find(|(name, _)| name == "table") {
+        Some((_, Export::Table(table))) => table,
+        _ => panic!("the table is not exported"),
+    };
+    let dispatch: Func<(i32, i32), i32> = instance.func("dispatch").unwrap();
+
+    assert_eq!(table.size(), 1);
+    assert!(table.get(0).is_some());
+    assert!(table.get(1).is_none());
+    assert_eq!(dispatch.call(0, 21), Ok(42));
+
+    // Grow the table and insert host functions, with and without a
+    // captured environment.
+    assert_eq!(table.grow(2), Ok(1));
+    assert!(table.get(1).is_none());
+
+    let offset = Arc::new(AtomicI32::new(100));
+    let shared_offset = offset.clone();
+    let add_offset = Func::new(move |_: &mut vm::Ctx, x: i32| -> i32 {
+        x + shared_offset.load(Ordering::SeqCst)
+    });
+    let negate = Func::new(|x: i32| -> i32 { -x });
+
+    table.set(1, Element::Anyfunc(add_offset.into())).unwrap();
+    table.set(2, Element::Anyfunc(negate.into())).unwrap();
+    assert_eq!(dispatch.call(1, 1), Ok(101));
+    assert_eq!(dispatch.call(2, 7), Ok(-7));
+
+    offset.store(200, Ordering::SeqCst);
+    assert_eq!(dispatch.call(1, 1), Ok(201));
+
+    // Elements can be copied around.
+    let double = match table.get(0) {
+        Some(Element::Anyfunc(anyfunc)) => anyfunc,
+        None => panic!("the element is null"),
+    };
+    table.set(2, Element::Anyfunc(double)).unwrap();
+    assert_eq!(dispatch.call(2, 7), Ok(14));
+
+    assert!(table
+        .set(3, Element::Anyfunc(Func::new(|x: i32| -> i32 { x }).into()))
+        .is_err());
+}
+
+#[test]
+fn test_host_table_set_replaces_functions() {
+    const MODULE: &str = r#"
+(module
+  (type $type (func (param i32) (result i32)))
+  (table (export "table") 1 anyfunc)
+  (func (export "dispatch") (param i32 i32) (result i32)
+    get_local 1
+    get_local 0
+    call_indirect (type $type)))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let table: Table = match instance.exports().find(|(name, _)| name == "table") {
+        Some((_, Export::Table(table))) => table,
+        _ => panic!("the table is not exported"),
+    };
+    let dispatch: Func<(i32, i32), i32> = instance.func("dispatch").unwrap();
+
+    let add = |offset: Arc<AtomicI32>| {
+        Func::new(move |x: i32| -> i32 { x + offset.load(Ordering::SeqCst) })
+    };
+
+    // Setting a function again keeps the latest environment only.
+    let offset = Arc::new(AtomicI32::new(1));
+    for _ in 0..10 {
+        table
+            .set(0, Element::Anyfunc(add(offset.clone()).into()))
+            .unwrap();
+    }
+    assert_eq!(Arc::strong_count(&offset), 2);
+    assert_eq!(dispatch.call(0, 1), Ok(2));
+
+    // Replacing the function drops its environment.
+    table
+        .set(
+            0,
+            Element::Anyfunc(Func::new(|x: i32| -> i32 { -x }).into()),
+        )
+        .unwrap();
+    assert_eq!(Arc::strong_count(&offset), 1);
+    assert_eq!(dispatch.call(0, 1), Ok(-1));
+}
+
+#[test]
+fn test_host_table_shared_between_instances() {
+    const EXPORTER: &str = r#"
+(module
+  (table (export "table") 1 anyfunc)
+  (memory 1))
+"#;
+    const IMPORTER: &str = r#"
+(module
+  (type $type (func (result i32)))
+  (import "env" "table" (table 1 anyfunc))
+  (memory 2)
+  (func (export "dispatch") (param i32) (result i32)
+    get_local 0
+    call_indirect (type $type)))
+"#;
+
+    let compile = |wat: &str| {
+        let wasm_binary = wat2wasm(wat.as_bytes()).expect("WAST not valid or malformed");
+        compile_with(&wasm_binary, &get_compiler()).unwrap()
+    };
+
+    let exporter = compile(EXPORTER).instantiate(&imports! {}).unwrap();
+    let table: Table = match exporter.exports().find(|(name, _)| name == "table") {
+        Some((_, Export::Table(table))) => table,
+        _ => panic!("the table is not exported"),
+    };
+    let importer = compile(IMPORTER)
+        .instantiate(&imports! { "env" => { "table" => table.clone(), }, })
+        .unwrap();
+    let dispatch: Func<i32, i32> = importer.func("dispatch").unwrap();
+
+    // The host function reports the memory size of the instance it's
+    // called with: the one which defines the table first.
+    let memory_size = Func::new(|ctx: &mut vm::Ctx| -> i32 { ctx.memory(0).size().0 as i32 });
+    table.set(0, Element::Anyfunc(memory_size.into())).unwrap();
+    assert_eq!(dispatch.call(0), Ok(1));
+
+    // Then the next instance using the table, once the first one is
+    // dropped.
+    drop(exporter);
+    assert_eq!(dispatch.call(0), Ok(2));
+}
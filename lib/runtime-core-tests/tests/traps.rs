@@ -0,0 +1,133 @@
+// The other backends report faults as errors rather than trap codes.
+#![cfg(feature = "backend-llvm")]
+
+use wasmer_runtime_core::{
+    compile_with, error::RuntimeError, func, imports, typed_func::Func, typed_func::WasmTrapInfo,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+fn trap_code(name: &str) -> WasmTrapInfo {
+    const MODULE: &str = r#"
+(module
+  (memory 1)
+  (func (export "out_of_bounds") (result i32)
+    i32.const 0x10000
+    i32.load)
+  (func (export "divide_by_zero") (param i32) (result i32)
+    i32.const 1
+    get_local 0
+    i32.div_u)
+  (func (export "unreachable")
+    unreachable))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let result = match name {
+        "divide_by_zero" => {
+            let func: Func<i32, i32> = instance.func(name).unwrap();
+            func.call(0).map(|_| ())
+        }
+        "out_of_bounds" => {
+            let func: Func<(), i32> = instance.func(name).unwrap();
+            func.call().map(|_| ())
+        }
+        _ => {
+            let func: Func<(), ()> = instance.func(name).unwrap();
+            func.call()
+        }
+    };
+    match result {
+        Err(RuntimeError::Trap { code, .. }) => code,
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(()) => panic!("{} didn't trap", name),
+    }
+}
+
+// The faults are caught by the signal handler on unix, and by the vectored
+// exception handler on Windows.
+
+#[test]
+fn test_memory_out_of_bounds_trap() {
+    assert_eq!(trap_code("out_of_bounds"), WasmTrapInfo::MemoryOutOfBounds);
+}
+
+#[test]
+fn test_illegal_arithmetic_trap() {
+    assert_eq!(trap_code("divide_by_zero"), WasmTrapInfo::IllegalArithmetic);
+}
+
+#[test]
+fn test_unreachable_trap() {
+    assert_eq!(trap_code("unreachable"), WasmTrapInfo::Unreachable);
+}
+
+#[test]
+fn test_trap_after_trap() {
+    // The handler must leave the thread able to run wasm again.
+    assert_eq!(trap_code("out_of_bounds"), WasmTrapInfo::MemoryOutOfBounds);
+    assert_eq!(trap_code("out_of_bounds"), WasmTrapInfo::MemoryOutOfBounds);
+}
+
+#[test]
+fn test_traps_unwind_through_calls() {
+    // Both unwind several wasm frames with `longjmp`, which walks their unwind
+    // tables on Windows.
+    const MODULE: &str = r#"
+(module
+  (import "env" "fail" (func $fail (param i32) (result i32)))
+  (memory 1)
+  (func $load (param i32) (result i32)
+    get_local 0
+    i32.load)
+  (func $nested (param i32) (result i32)
+    get_local 0
+    call $load
+    i32.const 1
+    i32.add)
+  (func (export "load") (param i32) (result i32)
+    get_local 0
+    call $nested)
+  (func (export "fail") (param i32) (result i32)
+    get_local 0
+    call $fail
+    i32.const 1
+    i32.add))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let import_object = imports! {
+        "env" => {
+            "fail" => func!(|n: i32| -> Result<i32, String> {
+                if n < 0 {
+                    Err(format!("failed with {}", n))
+                } else {
+                    Ok(n)
+                }
+            }),
+        },
+    };
+    let instance = module.instantiate(&import_object).unwrap();
+
+    let load: Func<i32, i32> = instance.func("load").unwrap();
+    assert_eq!(load.call(0), Ok(1));
+    match load.call(0x10000) {
+        Err(RuntimeError::Trap { code, .. }) => assert_eq!(code, WasmTrapInfo::MemoryOutOfBounds),
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    let fail: Func<i32, i32> = instance.func("fail").unwrap();
+    assert_eq!(fail.call(41), Ok(42));
+    match fail.call(-1) {
+        Err(RuntimeError::Error { data, .. }) => assert_eq!(
+            data.downcast_ref::<String>().map(String::as_str),
+            Some("failed with -1")
+        ),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    // The thread can still run wasm after both.
+    assert_eq!(load.call(4), Ok(1));
+}
@@ -0,0 +1,27 @@
+use wasmer_runtime_core::{backend::Features, validate_with_features};
+
+#[test]
+fn test_validate_with_features() {
+    const MODULE: &str = r#"
+(module
+  (func (export "pair") (result i32 i32)
+    i32.const 1
+    i32.const 2))
+"#;
+
+    let mut wabt_features = wabt::Features::new();
+    wabt_features.enable_multi_value();
+    let wasm_binary = wabt::wat2wasm_with_features(MODULE.as_bytes(), wabt_features)
+        .expect("WAST not valid or malformed");
+
+    let error = validate_with_features(&wasm_binary, &Features::default()).unwrap_err();
+    // The failure is located past the header of the module.
+    assert!(error.offset > 8 && error.offset < wasm_binary.len());
+    assert!(!error.message.is_empty());
+
+    let features = Features {
+        multi_value: true,
+        ..Default::default()
+    };
+    assert!(validate_with_features(&wasm_binary, &features).is_ok());
+}
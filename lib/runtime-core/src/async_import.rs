@@ -0,0 +1,297 @@
+//! The async import module lets host imports wait on futures without blocking the executor that
+//! drives the guest.
+//!
+//! The guest is run on a separate stack, a dedicated thread, by `call_async`. When a host import
+//! calls `suspend_on`, the future is handed back to the `AsyncCall` returned by `call_async` and
+//! the guest is suspended until the executor polling the `AsyncCall` has resolved it.
+//!
+//! ```ignore
+//! let import_object = imports! {
+//!     "env" => {
+//!         "fetch" => func!(|key: i32| -> i32 {
+//!             async_import::suspend_on(lookup(key))
+//!         }),
+//!     },
+//! };
+//! let instance = module.instantiate(&import_object)?;
+//!
+//! let result = async_import::call_async(move || {
+//!     let run: Func<i32, i32> = instance.func("run").unwrap();
+//!     run.call(42)
+//! })
+//! .await;
+//! ```
+use std::{
+    any::Any,
+    cell::RefCell,
+    future::Future,
+    marker::PhantomData,
+    panic,
+    pin::Pin,
+    sync::{
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+    thread::{self, Thread},
+};
+
+/// Size of the stack the guest runs on.
+pub const GUEST_STACK_SIZE: usize = 16 * 1024 * 1024;
+
+type AnyBox = Box<dyn Any + Send>;
+type ErasedFuture = Pin<Box<dyn Future<Output = AnyBox> + Send>>;
+
+enum Event {
+    /// The guest is suspended until the future resolves and its output
+    /// is sent back.
+    Suspend(ErasedFuture, Sender<AnyBox>),
+    /// The guest call returned, or panicked.
+    Done(thread::Result<AnyBox>),
+}
+
+/// The guest side of an `AsyncCall`.
+#[derive(Clone)]
+struct Suspender {
+    events: Sender<Event>,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl Suspender {
+    fn send(&self, event: Event) {
+        // The receiver is gone if the `AsyncCall` has been dropped,
+        // in which case there is nobody left to notify.
+        if self.events.send(event).is_ok() {
+            if let Some(waker) = self.waker.lock().unwrap().take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+thread_local! {
+    static SUSPENDER: RefCell<Option<Suspender>> = RefCell::new(None);
+}
+
+/// Erases the output type of a future.
+struct Erase<F: Future>(Pin<Box<F>>);
+
+impl<F> Future for Erase<F>
+where
+    F: Future,
+    F::Output: Send + 'static,
+{
+    type Output = AnyBox;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<AnyBox> {
+        self.0
+            .as_mut()
+            .poll(cx)
+            .map(|output| Box::new(output) as AnyBox)
+    }
+}
+
+/// Waits for `future` from within a host import.
+///
+/// When called during a `call_async`, the guest is suspended and `future` is polled by the
+/// executor driving the `AsyncCall`. Otherwise, the current thread is blocked until `future`
+/// resolves.
+///
+/// # Panics
+///
+/// Panics, and thus traps the guest, if the `AsyncCall` has been dropped in the meantime.
+pub fn suspend_on<F>(future: F) -> F::Output
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let suspender = match SUSPENDER.with(|suspender| suspender.borrow().clone()) {
+        Some(suspender) => suspender,
+        None => return block_on(future),
+    };
+
+    let (reply_sender, reply_receiver) = mpsc::channel();
+    suspender.send(Event::Suspend(
+        Box::pin(Erase(Box::pin(future))),
+        reply_sender,
+    ));
+
+    match reply_receiver.recv() {
+        Ok(output) => *output
+            .downcast::<F::Output>()
+            .expect("broken invariant, mismatched future output"),
+        Err(_) => panic!("the async call has been cancelled"),
+    }
+}
+
+/// Runs `f`, typically calling into an instance, on a separate stack and returns a future
+/// resolving to its result.
+///
+/// Futures passed to `suspend_on` by the host imports that `f` reaches are polled by the
+/// executor polling the returned `AsyncCall`. A panic in `f` is returned as an `Err`.
+pub fn call_async<F, R>(f: F) -> AsyncCall<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let (events, receiver) = mpsc::channel();
+    let waker = Arc::new(Mutex::new(None));
+    let suspender = Suspender { events, waker };
+
+    let guest_suspender = suspender.clone();
+    let spawned = thread::Builder::new()
+        .name("wasmer-guest".to_string())
+        .stack_size(GUEST_STACK_SIZE)
+        .spawn(move || {
+            SUSPENDER.with(|s| *s.borrow_mut() = Some(guest_suspender.clone()));
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(f))
+                .map(|output| Box::new(output) as AnyBox);
+            SUSPENDER.with(|s| s.borrow_mut().take());
+            guest_suspender.send(Event::Done(result));
+        });
+
+    if let Err(e) = spawned {
+        suspender.send(Event::Done(Err(Box::new(format!(
+            "cannot spawn the guest thread: {}",
+            e
+        )))));
+    }
+
+    AsyncCall {
+        events: receiver,
+        waker: suspender.waker,
+        pending: None,
+        _phantom: PhantomData,
+    }
+}
+
+/// A guest call started by `call_async`.
+///
+/// Dropping an `AsyncCall` cancels it: the guest traps the next time it is resumed.
+pub struct AsyncCall<R> {
+    events: Receiver<Event>,
+    waker: Arc<Mutex<Option<Waker>>>,
+    pending: Option<(ErasedFuture, Sender<AnyBox>)>,
+    _phantom: PhantomData<fn() -> R>,
+}
+
+impl<R: 'static> Future for AsyncCall<R> {
+    type Output = thread::Result<R>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        loop {
+            if let Some((future, _)) = &mut self.pending {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(output) => {
+                        let (_, reply) = self.pending.take().unwrap();
+                        // The guest thread is blocked on the reply,
+                        // so it is still there to receive it.
+                        let _ = reply.send(output);
+                    }
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            // Register the waker before checking for events, so that
+            // an event sent in between isn't missed.
+            *self.waker.lock().unwrap() = Some(cx.waker().clone());
+
+            match self.events.try_recv() {
+                Ok(Event::Suspend(future, reply)) => self.pending = Some((future, reply)),
+                Ok(Event::Done(result)) => {
+                    return Poll::Ready(result.map(|output| {
+                        *output
+                            .downcast::<R>()
+                            .expect("broken invariant, mismatched call output")
+                    }));
+                }
+                Err(TryRecvError::Empty) => return Poll::Pending,
+                Err(TryRecvError::Disconnected) => {
+                    unreachable!("the guest thread always reports its outcome")
+                }
+            }
+        }
+    }
+}
+
+fn thread_waker(thread: Thread) -> Waker {
+    unsafe fn clone(data: *const ()) -> RawWaker {
+        let thread = &*(data as *const Thread);
+        RawWaker::new(
+            Box::into_raw(Box::new(thread.clone())) as *const (),
+            &VTABLE,
+        )
+    }
+    unsafe fn wake(data: *const ()) {
+        Box::from_raw(data as *mut Thread).unpark();
+    }
+    unsafe fn wake_by_ref(data: *const ()) {
+        (*(data as *const Thread)).unpark();
+    }
+    unsafe fn drop(data: *const ()) {
+        let _ = Box::from_raw(data as *mut Thread);
+    }
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop);
+
+    let data = Box::into_raw(Box::new(thread)) as *const ();
+    unsafe { Waker::from_raw(RawWaker::new(data, &VTABLE)) }
+}
+
+/// Blocks the current thread until `future` resolves.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = thread_waker(thread::current());
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A future that is pending on its first poll.
+    struct YieldOnce<T>(Option<T>, bool);
+
+    impl<T: Unpin> Future for YieldOnce<T> {
+        type Output = T;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+            if self.1 {
+                Poll::Ready(self.0.take().unwrap())
+            } else {
+                self.1 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn test_suspend_and_resume() {
+        let result = block_on(call_async(|| {
+            let a = suspend_on(YieldOnce(Some(2), false));
+            let b = suspend_on(YieldOnce(Some(3), false));
+            a * b
+        }));
+
+        assert_eq!(result.unwrap(), 6);
+    }
+
+    #[test]
+    fn test_suspend_outside_async_call() {
+        assert_eq!(suspend_on(YieldOnce(Some("ok"), false)), "ok");
+    }
+
+    #[test]
+    fn test_panicking_call() {
+        let result = block_on(call_async(|| -> i32 { panic!("guest failed") }));
+
+        assert!(result.is_err());
+    }
+}
@@ -10,6 +10,7 @@ use crate::{
 use crate::{
     cache::{Artifact, Error as CacheError},
     codegen::BreakpointMap,
+    lazy::LazyBodies,
     module::ModuleInfo,
     sys::Memory,
 };
@@ -28,8 +29,14 @@ pub enum Backend {
     Cranelift,
     Singlepass,
     LLVM,
+    /// Pick one of the other backends for each module, see `Backend::select`.
+    Auto,
 }
 
+/// Modules at least this large are compiled with a fast baseline compiler when the backend is
+/// picked automatically.
+pub const AUTO_BASELINE_MODULE_SIZE: usize = 4 * 1024 * 1024;
+
 impl Backend {
     /// Get a list of the currently enabled (via feature flag) backends.
     pub fn variants() -> &'static [&'static str] {
@@ -40,6 +47,7 @@ impl Backend {
             "singlepass",
             #[cfg(feature = "backend-llvm")]
             "llvm",
+            "auto",
         ]
     }
 
@@ -50,8 +58,66 @@ impl Backend {
             Backend::Cranelift => "cranelift",
             Backend::Singlepass => "singlepass",
             Backend::LLVM => "llvm",
+            Backend::Auto => "auto",
+        }
+    }
+
+    /// Whether this backend can compile modules that use the given features on this host.
+    pub fn supports(&self, features: &Features) -> bool {
+        let host_supported = match self {
+            Backend::Singlepass => cfg!(all(target_arch = "x86_64", not(target_os = "windows"))),
+            _ => true,
+        };
+        host_supported && self.unsupported_feature(features).is_none()
+    }
+
+    /// Returns the name of one of the given features that this backend doesn't support.
+    pub fn unsupported_feature(&self, features: &Features) -> Option<&'static str> {
+        let unsupported = match self {
+            Backend::Cranelift => Features {
+                threads: true,
+                ..Default::default()
+            },
+            Backend::Singlepass => Features {
+                simd: true,
+                threads: true,
+                multi_value: true,
+            },
+            Backend::LLVM => Features {
+                multi_value: true,
+                ..Default::default()
+            },
+            Backend::Auto => Features::default(),
+        };
+        if features.simd && unsupported.simd {
+            Some("simd")
+        } else if features.threads && unsupported.threads {
+            Some("threads")
+        } else if features.multi_value && unsupported.multi_value {
+            Some("multi-value")
+        } else {
+            None
         }
     }
+
+    /// Picks a backend out of `available` to compile `wasm` with.
+    ///
+    /// Large modules prefer the backend with the fastest compilation, others prefer Cranelift
+    /// and then LLVM. Backends that can't handle the requested features on this host are
+    /// skipped. Returns `None` if no available backend fits.
+    ///
+    /// The backend a module was compiled with can be read back from `ModuleInfo::backend`.
+    pub fn select(wasm: &[u8], features: &Features, available: &[Backend]) -> Option<Backend> {
+        let preference: &[Backend] = if wasm.len() >= AUTO_BASELINE_MODULE_SIZE {
+            &[Backend::Singlepass, Backend::Cranelift, Backend::LLVM]
+        } else {
+            &[Backend::Cranelift, Backend::LLVM, Backend::Singlepass]
+        };
+        preference
+            .iter()
+            .cloned()
+            .find(|backend| available.contains(backend) && backend.supports(features))
+    }
 }
 
 impl Default for Backend {
@@ -67,6 +133,7 @@ impl std::str::FromStr for Backend {
             "singlepass" => Ok(Backend::Singlepass),
             "cranelift" => Ok(Backend::Cranelift),
             "llvm" => Ok(Backend::LLVM),
+            "auto" => Ok(Backend::Auto),
             _ => Err(format!("The backend {} doesn't exist", s)),
         }
     }
@@ -82,10 +149,38 @@ mod backend_test {
         // if this test breaks, think hard about why it's breaking
         // can we avoid having these be different?
 
-        for &backend in &[Backend::Cranelift, Backend::LLVM, Backend::Singlepass] {
+        for &backend in &[
+            Backend::Cranelift,
+            Backend::LLVM,
+            Backend::Singlepass,
+            Backend::Auto,
+        ] {
             assert_eq!(backend, Backend::from_str(backend.to_string()).unwrap());
         }
     }
+
+    #[test]
+    fn select_skips_unsupported_backends() {
+        let available = [Backend::Singlepass, Backend::Cranelift, Backend::LLVM];
+        let small = [0u8; 8];
+        assert_eq!(
+            Backend::select(&small, &Features::default(), &available),
+            Some(Backend::Cranelift)
+        );
+
+        let threads = Features {
+            threads: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Backend::select(&small, &threads, &available),
+            Some(Backend::LLVM)
+        );
+        assert_eq!(
+            Backend::select(&small, &threads, &[Backend::Cranelift]),
+            None
+        );
+    }
 }
 
 /// This type cannot be constructed from
@@ -100,10 +195,15 @@ impl Token {
     }
 }
 
+/// Selects how backends guard linear memory accesses.
 #[derive(Copy, Clone, Debug)]
 pub enum MemoryBoundCheckMode {
+    /// Rely on guard pages for static memories and check dynamic memories explicitly.
     Default,
+    /// Check every access explicitly, even when guard pages would catch it.
     Enable,
+    /// Never emit explicit checks where the backend can avoid them. Dynamic memories
+    /// have no guard pages large enough to catch every access, so they are still checked.
     Disable,
 }
 
@@ -114,14 +214,16 @@ impl Default for MemoryBoundCheckMode {
 }
 
 /// Controls which experimental features will be enabled.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Features {
     pub simd: bool,
     pub threads: bool,
+    /// Allow blocks and functions with more than one result.
+    pub multi_value: bool,
 }
 
 /// Configuration data for the compiler
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct CompilerConfig {
     /// Symbol information generated from emscripten; used for more detailed debug messages
     pub symbol_map: Option<HashMap<u32, String>>,
@@ -130,6 +232,29 @@ pub struct CompilerConfig {
     pub track_state: bool,
     pub features: Features,
 
+    /// Makes the compiled code produce the same results on every backend and platform, by
+    /// canonicalizing NaNs and rejecting the threads and SIMD features. See the
+    /// [`deterministic`] module.
+    ///
+    /// [`deterministic`]: ../deterministic/index.html
+    pub deterministic: bool,
+
+    /// Functions with more operators than this are compiled without optimizations,
+    /// keeping compile time bounded for huge generated functions. Used by LLVM.
+    pub huge_function_threshold: Option<usize>,
+
+    /// Emits DWARF line info for the compiled code and registers it with debuggers through
+    /// the [GDB JIT interface]. Used by Cranelift.
+    ///
+    /// [GDB JIT interface]: ../jit_debug/index.html
+    pub generate_debug_info: bool,
+
+    /// Replaces the bodies of the functions, to compile them one at a time as a [`LazyModule`]
+    /// does. Used by Singlepass and Cranelift; LLVM rejects it.
+    ///
+    /// [`LazyModule`]: ../lazy/struct.LazyModule.html
+    pub lazy_bodies: Option<LazyBodies>,
+
     // target info used by LLVM
     pub triple: Option<String>,
     pub cpu_name: Option<String>,
@@ -1,9 +1,10 @@
 use crate::{
     error::{CreationError, LinkError, LinkResult},
-    export::{Context, Export},
+    export::{Context, Export, ExportKind, FuncEnvOwner},
+    fuel::FuelState,
     global::Global,
     import::ImportObject,
-    memory::Memory,
+    memory::{Memory, MemoryAllocator},
     module::{ImportName, ModuleInfo, ModuleInner},
     sig_registry::SigRegistry,
     structures::{BoxedMap, Map, SliceMap, TypedIndex},
@@ -19,6 +20,7 @@ use std::{
     fmt::Debug,
     ptr::{self, NonNull},
     slice,
+    sync::Arc,
 };
 
 /// Size of the array for internal instance usage
@@ -57,6 +59,7 @@ pub struct LocalBacking {
     pub(crate) local_functions: BoxedMap<LocalFuncIndex, *const vm::Func>,
 
     pub(crate) internals: Internals,
+    pub(crate) fuel: FuelState,
 }
 
 // Manually implemented because LocalBacking contains raw pointers directly
@@ -67,8 +70,10 @@ impl LocalBacking {
         module: &ModuleInner,
         imports: &ImportBacking,
         vmctx: *mut vm::Ctx,
+        memory_allocator: Arc<dyn MemoryAllocator>,
+        init_imports: bool,
     ) -> LinkResult<Self> {
-        let mut memories = match Self::generate_memories(module) {
+        let mut memories = match Self::generate_memories(module, memory_allocator) {
             Ok(m) => m,
             Err(e) => {
                 return Err(vec![LinkError::Generic {
@@ -83,7 +88,7 @@ impl LocalBacking {
         Self::validate_memories(module, imports)?;
         Self::validate_tables(module, imports, &mut tables)?;
 
-        let vm_memories = Self::finalize_memories(module, imports, &mut memories)?;
+        let vm_memories = Self::finalize_memories(module, imports, &mut memories, init_imports)?;
         let vm_tables = Self::finalize_tables(module, imports, &mut tables, vmctx)?;
         let vm_globals = Self::finalize_globals(&mut globals);
 
@@ -103,6 +108,7 @@ impl LocalBacking {
             local_functions,
 
             internals: Internals([0; INTERNALS_SIZE]),
+            fuel: FuelState::default(),
         })
     }
 
@@ -133,10 +139,11 @@ impl LocalBacking {
 
     fn generate_memories(
         module: &ModuleInner,
+        allocator: Arc<dyn MemoryAllocator>,
     ) -> Result<BoxedMap<LocalMemoryIndex, Memory>, CreationError> {
         let mut memories = Map::with_capacity(module.info.memories.len());
         for (_, &desc) in &module.info.memories {
-            let memory = Memory::new(desc)?;
+            let memory = Memory::with_allocator(desc, Arc::clone(&allocator))?;
             memories.push(memory);
         }
 
@@ -178,8 +185,9 @@ impl LocalBacking {
                     let memory_desc = module.info.memories[local_memory_index];
                     let data_top = init_base + init.data.len();
                     if memory_desc.minimum.bytes().0 < data_top || data_top < init_base {
-                        return Err(vec![LinkError::Generic {
-                            message: "data segment does not fit".to_string(),
+                        return Err(vec![LinkError::DataSegmentDoesNotFit {
+                            offset: init_base,
+                            len: init.data.len(),
                         }]);
                     }
                 }
@@ -189,8 +197,9 @@ impl LocalBacking {
                     let local_memory = unsafe { &*imports.vm_memories[imported_memory_index] };
                     let data_top = init_base + init.data.len();
                     if local_memory.bound < data_top || data_top < init_base {
-                        return Err(vec![LinkError::Generic {
-                            message: "data segment does not fit".to_string(),
+                        return Err(vec![LinkError::DataSegmentDoesNotFit {
+                            offset: init_base,
+                            len: init.data.len(),
                         }]);
                     }
                 }
@@ -201,15 +210,37 @@ impl LocalBacking {
 
     /// Initialize each locally-defined memory in the Module.
     ///
-    /// This involves copying in the data initializers.
+    /// This involves copying in the data initializers. Those of the imported memories are only
+    /// copied if `init_imports` is set.
     fn finalize_memories(
         module: &ModuleInner,
         imports: &ImportBacking,
         memories: &mut SliceMap<LocalMemoryIndex, Memory>,
+        init_imports: bool,
     ) -> LinkResult<BoxedMap<LocalMemoryIndex, *mut vm::LocalMemory>> {
+        // Map the images of the memories that have one, and copy the data segments of the
+        // others.
+        let mapped: Vec<bool> = memories
+            .iter()
+            .map(|(index, memory)| {
+                module
+                    .memory_images
+                    .get(&module.info, index)
+                    .map_or(false, |image| memory.map_image(&image))
+            })
+            .collect();
+
         // For each init that has some data...
         // Initialize data
         for init in module.info.data_initializers.iter() {
+            match init.memory_index.local_or_import(&module.info) {
+                LocalOrImport::Local(local_memory_index) if mapped[local_memory_index.index()] => {
+                    continue;
+                }
+                LocalOrImport::Import(_) if !init_imports => continue,
+                _ => {}
+            }
+
             let init_base = match init.base {
                 Initializer::Const(Value::I32(offset)) => offset as u32,
                 Initializer::Const(_) => {
@@ -311,8 +342,9 @@ impl LocalBacking {
                     let table = &tables[local_table_index];
 
                     if (table.size() as usize) < init_base + init.elements.len() {
-                        return Err(vec![LinkError::Generic {
-                            message: "elements segment does not fit".to_string(),
+                        return Err(vec![LinkError::ElementSegmentDoesNotFit {
+                            offset: init_base,
+                            len: init.elements.len(),
                         }]);
                     }
                 }
@@ -320,8 +352,9 @@ impl LocalBacking {
                     let table = &imports.tables[import_table_index];
 
                     if (table.size() as usize) < init_base + init.elements.len() {
-                        return Err(vec![LinkError::Generic {
-                            message: "elements segment does not fit".to_string(),
+                        return Err(vec![LinkError::ElementSegmentDoesNotFit {
+                            offset: init_base,
+                            len: init.elements.len(),
                         }]);
                     }
                 }
@@ -332,7 +365,8 @@ impl LocalBacking {
 
     /// This initializes all of the locally-defined tables in the Module, e.g.
     /// putting all the table elements (function pointers)
-    /// in the right places.
+    /// in the right places. The elements of the imported tables are always set, as they
+    /// refer to the functions of this instance.
     #[allow(clippy::cast_ptr_alignment)]
     fn finalize_tables(
         module: &ModuleInner,
@@ -486,6 +520,7 @@ pub struct ImportBacking {
     pub(crate) globals: BoxedMap<ImportedGlobalIndex, Global>,
 
     pub(crate) vm_functions: BoxedMap<ImportedFuncIndex, vm::ImportedFunc>,
+    pub(crate) func_env_owners: BoxedMap<ImportedFuncIndex, Option<FuncEnvOwner>>,
     pub(crate) vm_memories: BoxedMap<ImportedMemoryIndex, *mut vm::LocalMemory>,
     pub(crate) vm_tables: BoxedMap<ImportedTableIndex, *mut vm::LocalTable>,
     pub(crate) vm_globals: BoxedMap<ImportedGlobalIndex, *mut vm::LocalGlobal>,
@@ -504,11 +539,12 @@ impl ImportBacking {
         let mut failed = false;
         let mut link_errors = vec![];
 
-        let vm_functions = import_functions(module, imports, vmctx).unwrap_or_else(|le| {
-            failed = true;
-            link_errors.extend(le);
-            Map::new().into_boxed_map()
-        });
+        let (vm_functions, func_env_owners) = import_functions(module, imports, vmctx)
+            .unwrap_or_else(|le| {
+                failed = true;
+                link_errors.extend(le);
+                (Map::new().into_boxed_map(), Map::new().into_boxed_map())
+            });
 
         let (memories, vm_memories) = import_memories(module, imports).unwrap_or_else(|le| {
             failed = true;
@@ -537,6 +573,7 @@ impl ImportBacking {
                 globals,
 
                 vm_functions,
+                func_env_owners,
                 vm_memories,
                 vm_tables,
                 vm_globals,
@@ -563,9 +600,13 @@ fn import_functions(
     module: &ModuleInner,
     imports: &ImportObject,
     vmctx: *mut vm::Ctx,
-) -> LinkResult<BoxedMap<ImportedFuncIndex, vm::ImportedFunc>> {
+) -> LinkResult<(
+    BoxedMap<ImportedFuncIndex, vm::ImportedFunc>,
+    BoxedMap<ImportedFuncIndex, Option<FuncEnvOwner>>,
+)> {
     let mut link_errors = vec![];
     let mut functions = Map::with_capacity(module.info.imported_functions.len());
+    let mut func_env_owners = Map::with_capacity(module.info.imported_functions.len());
     for (
         index,
         ImportName {
@@ -588,8 +629,10 @@ fn import_functions(
                 func,
                 ctx,
                 signature,
+                env_owner,
             }) => {
                 if *expected_sig == *signature {
+                    func_env_owners.push(env_owner);
                     functions.push(vm::ImportedFunc {
                         func: func.inner(),
                         func_ctx: NonNull::new(Box::into_raw(Box::new(vm::FuncCtx {
@@ -625,22 +668,16 @@ fn import_functions(
                 }
             }
             Some(export_type) => {
-                let export_type_name = match export_type {
-                    Export::Function { .. } => "function",
-                    Export::Memory { .. } => "memory",
-                    Export::Table { .. } => "table",
-                    Export::Global { .. } => "global",
-                }
-                .to_string();
                 link_errors.push(LinkError::IncorrectImportType {
                     namespace: namespace.to_string(),
                     name: name.to_string(),
-                    expected: "function".to_string(),
-                    found: export_type_name,
+                    expected: ExportKind::Function,
+                    found: export_type.kind(),
                 });
             }
             None => {
                 if imports.allow_missing_functions {
+                    func_env_owners.push(None);
                     functions.push(vm::ImportedFunc {
                         func: ptr::null(),
                         func_ctx: unsafe { NonNull::new_unchecked(ptr::null_mut()) }, // TODO: Non-sense…
@@ -658,7 +695,7 @@ fn import_functions(
     if !link_errors.is_empty() {
         Err(link_errors)
     } else {
-        Ok(functions.into_boxed_map())
+        Ok((functions.into_boxed_map(), func_env_owners.into_boxed_map()))
     }
 }
 
@@ -703,18 +740,11 @@ fn import_memories(
                 }
             }
             Some(export_type) => {
-                let export_type_name = match export_type {
-                    Export::Function { .. } => "function",
-                    Export::Memory { .. } => "memory",
-                    Export::Table { .. } => "table",
-                    Export::Global { .. } => "global",
-                }
-                .to_string();
                 link_errors.push(LinkError::IncorrectImportType {
                     namespace: namespace.to_string(),
                     name: name.to_string(),
-                    expected: "memory".to_string(),
-                    found: export_type_name,
+                    expected: ExportKind::Memory,
+                    found: export_type.kind(),
                 });
             }
             None => {
@@ -774,18 +804,11 @@ fn import_tables(
                 }
             }
             Some(export_type) => {
-                let export_type_name = match export_type {
-                    Export::Function { .. } => "function",
-                    Export::Memory { .. } => "memory",
-                    Export::Table { .. } => "table",
-                    Export::Global { .. } => "global",
-                }
-                .to_string();
                 link_errors.push(LinkError::IncorrectImportType {
                     namespace: namespace.to_string(),
                     name: name.to_string(),
-                    expected: "table".to_string(),
-                    found: export_type_name,
+                    expected: ExportKind::Table,
+                    found: export_type.kind(),
                 });
             }
             None => {
@@ -844,18 +867,11 @@ fn import_globals(
                 }
             }
             Some(export_type) => {
-                let export_type_name = match export_type {
-                    Export::Function { .. } => "function",
-                    Export::Memory { .. } => "memory",
-                    Export::Table { .. } => "table",
-                    Export::Global { .. } => "global",
-                }
-                .to_string();
                 link_errors.push(LinkError::IncorrectImportType {
                     namespace: namespace.to_string(),
                     name: name.to_string(),
-                    expected: "global".to_string(),
-                    found: export_type_name,
+                    expected: ExportKind::Global,
+                    found: export_type.kind(),
                 });
             }
             None => {
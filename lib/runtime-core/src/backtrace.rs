@@ -0,0 +1,245 @@
+//! The backtrace module captures the wasm call stack when a trap occurs, so that a
+//! `RuntimeError` can tell which functions were running.
+//!
+//! Backends capture the program counters of the wasm frames from their trap handlers, either
+//! by following frame pointers or with the system unwinder, and they are resolved to function
+//! indices and names once the call has unwound. Only the frames of the modules called from the
+//! host on the current thread are captured.
+use crate::{module::ModuleInner, structures::TypedIndex, types::FuncIndex, vm};
+use std::{cell::RefCell, fmt, mem};
+
+/// The maximum number of frames captured.
+const MAX_FRAMES: usize = 256;
+
+thread_local! {
+    /// The code of the modules called on this thread, innermost last.
+    static CODE: RefCell<Vec<(usize, usize)>> = RefCell::new(Vec::new());
+    /// The program counters captured by the last trap, innermost first.
+    static CAPTURED: RefCell<Frames> = RefCell::new(Frames::new());
+}
+
+/// The program counters of captured frames.
+///
+/// The frames are captured from trap handlers, which may run in a signal handler, so they are
+/// stored in place instead of allocating.
+struct Frames {
+    pcs: [usize; MAX_FRAMES],
+    len: usize,
+}
+
+impl Frames {
+    fn new() -> Self {
+        Frames {
+            pcs: [0; MAX_FRAMES],
+            len: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == MAX_FRAMES
+    }
+
+    /// Records `pc`, returning false if there is no room left.
+    fn push(&mut self, pc: usize) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.pcs[self.len] = pc;
+        self.len += 1;
+        true
+    }
+
+    fn as_slice(&self) -> &[usize] {
+        &self.pcs[..self.len]
+    }
+}
+
+/// A frame of a `WasmBacktrace`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmFrame {
+    /// The index of the function.
+    pub func_index: FuncIndex,
+    /// The name of the function, if the module has a name section.
+    pub name: Option<String>,
+}
+
+/// The wasm call stack at the point where a trap occurred.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WasmBacktrace {
+    frames: Vec<WasmFrame>,
+}
+
+impl WasmBacktrace {
+    /// Returns the frames, innermost first.
+    pub fn frames(&self) -> &[WasmFrame] {
+        &self.frames
+    }
+}
+
+impl fmt::Display for WasmBacktrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, frame) in self.frames.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            match frame.name {
+                Some(ref name) => {
+                    write!(f, "{:>4}: {} (func {})", i, name, frame.func_index.index())?
+                }
+                None => write!(f, "{:>4}: func {}", i, frame.func_index.index())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs a call into wasm, registering the code of the module of `ctx` so that its frames are
+/// captured if the call traps.
+pub(crate) unsafe fn enter<R>(ctx: *mut vm::Ctx, f: impl FnOnce() -> R) -> R {
+    let code = (*(*ctx).module)
+        .runnable_module
+        .get_code()
+        .map(|code| (code.as_ptr() as usize, code.as_ptr() as usize + code.len()));
+    CAPTURED.with(|captured| captured.borrow_mut().clear());
+    if let Some(code) = code {
+        CODE.with(|ranges| ranges.borrow_mut().push(code));
+    }
+    let ret = f();
+    if code.is_some() {
+        CODE.with(|ranges| ranges.borrow_mut().pop());
+    }
+    ret
+}
+
+fn is_wasm(pc: usize) -> bool {
+    CODE.with(|ranges| {
+        ranges
+            .borrow()
+            .iter()
+            .any(|&(start, end)| start <= pc && pc < end)
+    })
+}
+
+/// Captures the wasm frames of a trap at `pc`, following the frame pointers from `fp`.
+///
+/// Called by the trap handlers of backends whose code maintains frame pointers.
+///
+/// # Safety
+///
+/// `fp` must be the frame pointer of the code at `pc`.
+pub unsafe fn capture_frames(pc: usize, fp: usize) {
+    CAPTURED.with(|captured| {
+        let mut frames = captured.borrow_mut();
+        frames.clear();
+        if is_wasm(pc) {
+            frames.push(pc);
+            walk_frame_pointers(&mut frames, fp);
+        }
+    });
+}
+
+unsafe fn walk_frame_pointers(frames: &mut Frames, mut fp: usize) {
+    while !frames.is_full() && fp != 0 && fp % mem::align_of::<usize>() == 0 {
+        // The return address sits right above the saved frame pointer.
+        let return_address = *((fp + mem::size_of::<usize>()) as *const usize);
+        if !is_wasm(return_address) {
+            break;
+        }
+        // Point into the call instruction rather than after it.
+        frames.push(return_address - 1);
+        let next = *(fp as *const usize);
+        if next <= fp {
+            break;
+        }
+        fp = next;
+    }
+}
+
+/// Captures the wasm frames of the current stack with the system unwinder.
+///
+/// Called where backends registering unwind info for their code raise traps. The system
+/// unwinder isn't async-signal-safe, so signal handlers must use `capture_frames` instead.
+#[cfg(unix)]
+pub fn capture_unwind() {
+    use std::os::raw::{c_int, c_void};
+
+    extern "C" {
+        fn _Unwind_Backtrace(
+            trace: extern "C" fn(*mut c_void, *mut c_void) -> c_int,
+            data: *mut c_void,
+        ) -> c_int;
+        fn _Unwind_GetIPInfo(ctx: *mut c_void, ip_before_insn: *mut c_int) -> usize;
+    }
+
+    extern "C" fn trace(ctx: *mut c_void, data: *mut c_void) -> c_int {
+        let frames = unsafe { &mut *(data as *mut Frames) };
+        let mut ip_before_insn = 0;
+        let mut pc = unsafe { _Unwind_GetIPInfo(ctx, &mut ip_before_insn) };
+        if ip_before_insn == 0 && pc > 0 {
+            pc -= 1;
+        }
+        if is_wasm(pc) {
+            frames.push(pc);
+        }
+        // `_URC_NO_REASON` carries on, while `_URC_END_OF_STACK` stops the walk.
+        if frames.is_full() {
+            5
+        } else {
+            0
+        }
+    }
+
+    CAPTURED.with(|captured| {
+        let mut frames = captured.borrow_mut();
+        frames.clear();
+        unsafe {
+            _Unwind_Backtrace(trace, &mut *frames as *mut Frames as *mut c_void);
+        }
+    });
+}
+
+/// Resolves the frames captured by the last trap against the code of `module`.
+pub(crate) fn take(module: &ModuleInner) -> Option<WasmBacktrace> {
+    let captured: Vec<usize> = CAPTURED.with(|captured| {
+        let mut frames = captured.borrow_mut();
+        let pcs = frames.as_slice().to_vec();
+        frames.clear();
+        pcs
+    });
+    if captured.is_empty() {
+        return None;
+    }
+
+    let code = module.runnable_module.get_code()?;
+    let mut offsets: Vec<(usize, usize)> = module
+        .runnable_module
+        .get_local_function_offsets()?
+        .into_iter()
+        .enumerate()
+        .map(|(local_index, offset)| (offset, local_index))
+        .collect();
+    offsets.sort();
+
+    let start = code.as_ptr() as usize;
+    let frames = captured
+        .into_iter()
+        .filter(|&pc| start <= pc && pc < start + code.len())
+        .filter_map(|pc| {
+            let local_index = match offsets.binary_search_by_key(&(pc - start), |&(o, _)| o) {
+                Ok(i) => offsets[i].1,
+                Err(0) => return None,
+                Err(i) => offsets[i - 1].1,
+            };
+            let func_index = FuncIndex::new(module.info.imported_functions.len() + local_index);
+            Some(WasmFrame {
+                func_index,
+                name: module.info.func_names.get(&func_index).cloned(),
+            })
+        })
+        .collect();
+    Some(WasmBacktrace { frames })
+}
@@ -19,6 +19,34 @@ pub enum InvalidFileType {
     InvalidMagic,
 }
 
+/// Indicates why a well-formed cache file can't be used
+#[derive(Debug)]
+pub enum InvalidatedCache {
+    /// The cache file was written in a different format version
+    FormatVersionMismatch {
+        /// The format version this Wasmer reads and writes
+        expected: u64,
+        /// The format version of the cache file
+        found: u64,
+    },
+    /// The cache file was produced by a different version of Wasmer
+    VersionMismatch {
+        /// The `WASMER_VERSION_HASH` of this Wasmer
+        expected: String,
+        /// The `WASMER_VERSION_HASH` recorded in the cache file
+        found: String,
+    },
+    /// The cache file was produced by a different backend than the requested one
+    BackendMismatch {
+        /// The requested backend
+        expected: Backend,
+        /// The backend recorded in the cache file
+        found: Backend,
+    },
+    /// The cached code relies on CPU features that the host lacks
+    MissingCpuFeatures(Vec<String>),
+}
+
 /// Kinds of caching errors
 #[derive(Debug)]
 pub enum Error {
@@ -33,7 +61,7 @@ pub enum Error {
     /// An invalid cache binary given.
     InvalidFile(InvalidFileType),
     /// The cached binary has been invalidated.
-    InvalidatedCache,
+    InvalidatedCache(InvalidatedCache),
     /// The current backend does not support caching.
     UnsupportedBackend(Backend),
 }
@@ -114,55 +142,65 @@ impl WasmHash {
     }
 }
 
-const CURRENT_CACHE_VERSION: u64 = 0;
+const CURRENT_CACHE_VERSION: u64 = 4;
 static WASMER_CACHE_MAGIC: [u8; 8] = *b"WASMER\0\0";
 
 /// The header of a cache file.
+///
+/// `magic` and `version` stay at the same offsets in every format version, so a
+/// cache file from another version can always be recognized as such. In the current
+/// version the header is followed by `metadata_len` bytes of serialized
+/// `ArtifactMetadata` and then `data_len` bytes of serialized `ArtifactInner`.
+///
+/// The integers of the header are little-endian on every host.
 #[repr(C, packed)]
 struct ArtifactHeader {
     magic: [u8; 8], // [W, A, S, M, E, R, \0, \0]
     version: u64,
+    metadata_len: u64,
     data_len: u64,
 }
 
 impl ArtifactHeader {
-    pub fn read_from_slice(buffer: &[u8]) -> Result<(&Self, &[u8]), Error> {
-        if buffer.len() >= mem::size_of::<ArtifactHeader>() {
-            if &buffer[..8] == &WASMER_CACHE_MAGIC {
-                let (header_slice, body_slice) = buffer.split_at(mem::size_of::<ArtifactHeader>());
-                let header = unsafe { &*(header_slice.as_ptr() as *const ArtifactHeader) };
-
-                if header.version == CURRENT_CACHE_VERSION {
-                    Ok((header, body_slice))
-                } else {
-                    Err(Error::InvalidatedCache)
-                }
-            } else {
-                Err(Error::InvalidFile(InvalidFileType::InvalidMagic))
-            }
-        } else {
-            Err(Error::InvalidFile(InvalidFileType::InvalidSize))
+    /// Size of the part of the header that is common to all format versions.
+    const PREFIX_SIZE: usize = 16;
+
+    fn check_prefix(buffer: &[u8]) -> Result<(), Error> {
+        if buffer.len() < Self::PREFIX_SIZE {
+            return Err(Error::InvalidFile(InvalidFileType::InvalidSize));
+        }
+        if buffer[..8] != WASMER_CACHE_MAGIC {
+            return Err(Error::InvalidFile(InvalidFileType::InvalidMagic));
+        }
+        let mut version = [0u8; 8];
+        version.copy_from_slice(&buffer[8..16]);
+        let version = u64::from_le_bytes(version);
+        if version != CURRENT_CACHE_VERSION {
+            return Err(Error::InvalidatedCache(
+                InvalidatedCache::FormatVersionMismatch {
+                    expected: CURRENT_CACHE_VERSION,
+                    found: version,
+                },
+            ));
+        }
+        if buffer.len() < mem::size_of::<ArtifactHeader>() {
+            return Err(Error::InvalidFile(InvalidFileType::InvalidSize));
         }
+        Ok(())
+    }
+
+    pub fn read_from_slice(buffer: &[u8]) -> Result<(&Self, &[u8]), Error> {
+        Self::check_prefix(buffer)?;
+        let (header_slice, body_slice) = buffer.split_at(mem::size_of::<ArtifactHeader>());
+        let header = unsafe { &*(header_slice.as_ptr() as *const ArtifactHeader) };
+        Ok((header, body_slice))
     }
 
     pub fn read_from_slice_mut(buffer: &mut [u8]) -> Result<(&mut Self, &mut [u8]), Error> {
-        if buffer.len() >= mem::size_of::<ArtifactHeader>() {
-            if &buffer[..8] == &WASMER_CACHE_MAGIC {
-                let (header_slice, body_slice) =
-                    buffer.split_at_mut(mem::size_of::<ArtifactHeader>());
-                let header = unsafe { &mut *(header_slice.as_ptr() as *mut ArtifactHeader) };
-
-                if header.version == CURRENT_CACHE_VERSION {
-                    Ok((header, body_slice))
-                } else {
-                    Err(Error::InvalidatedCache)
-                }
-            } else {
-                Err(Error::InvalidFile(InvalidFileType::InvalidMagic))
-            }
-        } else {
-            Err(Error::InvalidFile(InvalidFileType::InvalidSize))
-        }
+        Self::check_prefix(buffer)?;
+        let (header_slice, body_slice) = buffer.split_at_mut(mem::size_of::<ArtifactHeader>());
+        let header = unsafe { &mut *(header_slice.as_ptr() as *mut ArtifactHeader) };
+        Ok((header, body_slice))
     }
 
     pub fn as_slice(&self) -> &[u8] {
@@ -171,6 +209,128 @@ impl ArtifactHeader {
     }
 }
 
+/// Returns the sorted list of CPU features detected on the host.
+///
+/// Compiled code may use any of these, so an `Artifact` records the features
+/// it was produced with and refuses to load on a host that lacks one of them.
+pub fn host_cpu_features() -> Vec<&'static str> {
+    #[allow(unused_mut)]
+    let mut features = vec![];
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        macro_rules! detect {
+            ($($feature:tt),*) => {
+                $(
+                    if is_x86_feature_detected!($feature) {
+                        features.push($feature);
+                    }
+                )*
+            };
+        }
+        detect!(
+            "aes", "avx", "avx2", "bmi1", "bmi2", "fma", "lzcnt", "popcnt", "sse2", "sse3",
+            "sse4.1", "sse4.2", "ssse3"
+        );
+    }
+
+    #[cfg(all(target_arch = "aarch64", target_os = "linux"))]
+    {
+        // The `AT_HWCAP` bits of the kernel's `asm/hwcap.h`, by LLVM feature name.
+        const HWCAPS: &[(&str, u64)] = &[
+            ("aes", 1 << 3),
+            ("crc", 1 << 7),
+            ("dotprod", 1 << 20),
+            ("fp-armv8", 1 << 0),
+            ("fullfp16", 1 << 9),
+            ("lse", 1 << 8),
+            ("neon", 1 << 1),
+            ("rdm", 1 << 12),
+            ("sha2", 1 << 6),
+            ("sha3", 1 << 17),
+            ("sve", 1 << 22),
+        ];
+        let hwcap = unsafe { libc::getauxval(libc::AT_HWCAP) } as u64;
+        for &(feature, bit) in HWCAPS {
+            if hwcap & bit != 0 {
+                features.push(feature);
+            }
+        }
+    }
+
+    // Without a way to detect the features, at least keep the code of other
+    // architectures from loading.
+    #[cfg(not(any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        all(target_arch = "aarch64", target_os = "linux")
+    )))]
+    features.push(std::env::consts::ARCH);
+
+    features
+}
+
+/// A short digest of [`host_cpu_features`], suitable for use in a cache path.
+///
+/// [`host_cpu_features`]: fn.host_cpu_features.html
+pub fn host_cpu_features_digest() -> String {
+    let digest = WasmHash::generate(host_cpu_features().join(",").as_bytes()).encode();
+    digest[..16].to_string()
+}
+
+/// Describes how and where an `Artifact` was produced.
+///
+/// It is stored in front of the compiled code and can be read with
+/// `Artifact::read_metadata` without deserializing the rest of the artifact.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArtifactMetadata {
+    /// The `WASMER_VERSION_HASH` of the Wasmer that produced the artifact.
+    pub wasmer_version: String,
+    /// The backend that compiled the code.
+    pub backend: Backend,
+    /// The CPU features of the host the code was compiled on.
+    pub cpu_features: Vec<String>,
+}
+
+impl ArtifactMetadata {
+    fn for_host(backend: Backend) -> Self {
+        Self {
+            wasmer_version: WASMER_VERSION_HASH.to_string(),
+            backend,
+            cpu_features: host_cpu_features()
+                .into_iter()
+                .map(|feature| feature.to_string())
+                .collect(),
+        }
+    }
+
+    /// Checks that code described by this metadata can run with this version of Wasmer
+    /// on this host.
+    pub fn validate_target(&self) -> Result<(), Error> {
+        if self.wasmer_version != WASMER_VERSION_HASH {
+            return Err(Error::InvalidatedCache(InvalidatedCache::VersionMismatch {
+                expected: WASMER_VERSION_HASH.to_string(),
+                found: self.wasmer_version.clone(),
+            }));
+        }
+
+        let host_features = host_cpu_features();
+        let missing: Vec<String> = self
+            .cpu_features
+            .iter()
+            .filter(|feature| !host_features.contains(&feature.as_str()))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::InvalidatedCache(
+                InvalidatedCache::MissingCpuFeatures(missing),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct ArtifactInner {
     info: Box<ModuleInfo>,
@@ -182,6 +342,7 @@ struct ArtifactInner {
 /// Artifact are produced by caching, are serialized/deserialized to binaries, and contain
 /// module info, backend metadata, and compiled code.
 pub struct Artifact {
+    metadata: ArtifactMetadata,
     inner: ArtifactInner,
 }
 
@@ -192,6 +353,7 @@ impl Artifact {
         compiled_code: Memory,
     ) -> Self {
         Self {
+            metadata: ArtifactMetadata::for_host(info.backend),
             inner: ArtifactInner {
                 info,
                 backend_metadata,
@@ -200,14 +362,48 @@ impl Artifact {
         }
     }
 
+    fn split(bytes: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+        let (header, rest) = ArtifactHeader::read_from_slice(bytes)?;
+        let metadata_len = u64::from_le(header.metadata_len) as usize;
+        let data_len = u64::from_le(header.data_len) as usize;
+        if rest.len() < metadata_len || rest.len() - metadata_len < data_len {
+            return Err(Error::InvalidFile(InvalidFileType::InvalidSize));
+        }
+        let (metadata_slice, rest) = rest.split_at(metadata_len);
+        Ok((metadata_slice, &rest[..data_len]))
+    }
+
+    /// Reads only the metadata of a serialized `Artifact`.
+    ///
+    /// This is cheap compared to `Artifact::deserialize` and lets embedders decide
+    /// whether a cached artifact is still usable.
+    pub fn read_metadata(bytes: &[u8]) -> Result<ArtifactMetadata, Error> {
+        let (metadata_slice, _) = Self::split(bytes)?;
+        serde_bench::deserialize(metadata_slice)
+            .map_err(|e| Error::DeserializeError(format!("{:#?}", e)))
+    }
+
     /// Deserializes an `Artifact` from the given byte slice.
+    ///
+    /// Returns `Error::InvalidatedCache` if the artifact was written in another format
+    /// version, produced by a different version of Wasmer or relies on CPU features the
+    /// host lacks.
     pub fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
-        let (_, body_slice) = ArtifactHeader::read_from_slice(bytes)?;
+        let (metadata_slice, body_slice) = Self::split(bytes)?;
+
+        let metadata: ArtifactMetadata = serde_bench::deserialize(metadata_slice)
+            .map_err(|e| Error::DeserializeError(format!("{:#?}", e)))?;
+        metadata.validate_target()?;
 
-        let inner = serde_bench::deserialize(body_slice)
+        let inner: ArtifactInner = serde_bench::deserialize(body_slice)
             .map_err(|e| Error::DeserializeError(format!("{:#?}", e)))?;
 
-        Ok(Artifact { inner })
+        Ok(Artifact { metadata, inner })
+    }
+
+    /// The metadata describing how this `Artifact` was produced.
+    pub fn metadata(&self) -> &ArtifactMetadata {
+        &self.metadata
     }
 
     /// A reference to the `Artifact`'s stored `ModuleInfo`
@@ -215,6 +411,11 @@ impl Artifact {
         &self.inner.info
     }
 
+    /// The CPU features of the host this `Artifact` was compiled on.
+    pub fn cpu_features(&self) -> &[String] {
+        &self.metadata.cpu_features
+    }
+
     #[doc(hidden)]
     pub fn consume(self) -> (ModuleInfo, Box<[u8]>, Memory) {
         (
@@ -228,19 +429,24 @@ impl Artifact {
     pub fn serialize(&self) -> Result<Vec<u8>, Error> {
         let cache_header = ArtifactHeader {
             magic: WASMER_CACHE_MAGIC,
-            version: CURRENT_CACHE_VERSION,
+            version: CURRENT_CACHE_VERSION.to_le(),
+            metadata_len: 0,
             data_len: 0,
         };
 
         let mut buffer = cache_header.as_slice().to_vec();
 
-        serde_bench::serialize(&mut buffer, &self.inner)
+        serde_bench::serialize(&mut buffer, &self.metadata)
             .map_err(|e| Error::SerializeError(e.to_string()))?;
+        let metadata_len = (buffer.len() - mem::size_of::<ArtifactHeader>()) as u64;
 
-        let data_len = (buffer.len() - mem::size_of::<ArtifactHeader>()) as u64;
+        serde_bench::serialize(&mut buffer, &self.inner)
+            .map_err(|e| Error::SerializeError(e.to_string()))?;
+        let data_len = (buffer.len() - mem::size_of::<ArtifactHeader>()) as u64 - metadata_len;
 
         let (header, _) = ArtifactHeader::read_from_slice_mut(&mut buffer)?;
-        header.data_len = data_len;
+        header.metadata_len = metadata_len.to_le();
+        header.data_len = data_len.to_le();
 
         Ok(buffer)
     }
@@ -267,3 +473,114 @@ pub trait Cache {
 /// A unique ID generated from the version of Wasmer for use with cache versioning
 pub const WASMER_VERSION_HASH: &'static str =
     include_str!(concat!(env!("OUT_DIR"), "/wasmer_version_hash.txt"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_artifact_is_valid() {
+        ArtifactMetadata::for_host(Backend::Cranelift)
+            .validate_target()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_missing_cpu_features_invalidate() {
+        let mut metadata = ArtifactMetadata::for_host(Backend::Cranelift);
+        metadata.cpu_features.push("not-a-feature".to_string());
+        match metadata.validate_target() {
+            Err(Error::InvalidatedCache(InvalidatedCache::MissingCpuFeatures(missing))) => {
+                assert_eq!(missing, vec!["not-a-feature".to_string()])
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_other_wasmer_version_invalidates() {
+        let mut metadata = ArtifactMetadata::for_host(Backend::Cranelift);
+        metadata.wasmer_version = "0".to_string();
+        match metadata.validate_target() {
+            Err(Error::InvalidatedCache(InvalidatedCache::VersionMismatch { found, .. })) => {
+                assert_eq!(found, "0")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    /// Serializes a cache file holding `metadata` and no module.
+    fn cache_file(version: u64, metadata: &ArtifactMetadata) -> Vec<u8> {
+        let mut metadata_bytes = vec![];
+        serde_bench::serialize(&mut metadata_bytes, metadata).unwrap();
+        let mut buffer = WASMER_CACHE_MAGIC.to_vec();
+        buffer.extend_from_slice(&version.to_le_bytes());
+        buffer.extend_from_slice(&(metadata_bytes.len() as u64).to_le_bytes());
+        buffer.extend_from_slice(&0u64.to_le_bytes());
+        buffer.extend_from_slice(&metadata_bytes);
+        buffer
+    }
+
+    #[test]
+    fn test_read_metadata() {
+        let file = cache_file(
+            CURRENT_CACHE_VERSION,
+            &ArtifactMetadata::for_host(Backend::LLVM),
+        );
+        let metadata = Artifact::read_metadata(&file).unwrap();
+        assert_eq!(metadata.backend, Backend::LLVM);
+        assert_eq!(metadata.wasmer_version, WASMER_VERSION_HASH);
+    }
+
+    #[test]
+    fn test_bad_magic_is_invalid() {
+        let mut file = cache_file(
+            CURRENT_CACHE_VERSION,
+            &ArtifactMetadata::for_host(Backend::Cranelift),
+        );
+        file[0] = b'w';
+        match Artifact::read_metadata(&file) {
+            Err(Error::InvalidFile(InvalidFileType::InvalidMagic)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_other_format_version_invalidates() {
+        // Only the magic and the version are read from files of another format version.
+        let mut file = WASMER_CACHE_MAGIC.to_vec();
+        file.extend_from_slice(&1u64.to_le_bytes());
+        match Artifact::read_metadata(&file) {
+            Err(Error::InvalidatedCache(InvalidatedCache::FormatVersionMismatch {
+                expected,
+                found,
+            })) => {
+                assert_eq!(expected, CURRENT_CACHE_VERSION);
+                assert_eq!(found, 1);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_short_file_is_invalid() {
+        let file = cache_file(
+            CURRENT_CACHE_VERSION,
+            &ArtifactMetadata::for_host(Backend::Cranelift),
+        );
+        // Cut in the version, in the lengths, and in the metadata.
+        for &len in &[12, 20, file.len() - 1] {
+            match Artifact::read_metadata(&file[..len]) {
+                Err(Error::InvalidFile(InvalidFileType::InvalidSize)) => {}
+                other => panic!("unexpected result for {} bytes: {:?}", len, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_host_cpu_features_are_sorted() {
+        let features = host_cpu_features();
+        assert!(!features.is_empty());
+        assert!(features.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
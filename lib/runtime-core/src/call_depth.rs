@@ -0,0 +1,119 @@
+//! The call_depth module bounds how deep the calls of an instance can nest, so that a guest
+//! recursing without end traps with `CallStackExhausted` at a depth chosen by the embedder,
+//! instead of overflowing whatever native stack the host thread happens to have.
+//!
+//! Calls are only counted in code compiled with the `CallDepth` middleware, on backends
+//! supporting middlewares. The middleware is given the deepest depth its code allows, and
+//! `Instance::set_max_call_depth` lowers it for an instance.
+use crate::{
+    codegen::{BreakpointInfo, Event, EventSink, FunctionExits, FunctionMiddleware, InternalEvent},
+    internal_limit::InternalLimit,
+    module::ModuleInfo,
+    structures::TypedIndex,
+    types::FuncIndex,
+    vm::{Ctx, InternalField},
+};
+use std::{any::Any, fmt};
+
+pub(crate) static CALL_DEPTH_FIELD: InternalField = InternalField::allocate();
+
+/// The depth of the calls of an instance, limited by `CallDepth`.
+static CALL_DEPTH_LIMIT: InternalLimit = InternalLimit {
+    field: &CALL_DEPTH_FIELD,
+    name: "call_depth.limit",
+};
+
+/// The trap raised when an instance exceeds its maximum call depth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallStackExhausted;
+
+impl fmt::Display for CallStackExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "call stack exhausted")
+    }
+}
+
+impl std::error::Error for CallStackExhausted {}
+
+/// Sets the maximum call depth of the instance of `ctx`, up to the depth its module was
+/// compiled with. This does nothing if the module doesn't count calls.
+///
+/// This should be called between calls, as the depth of the calls in progress is forgotten.
+pub fn set_max_call_depth(ctx: &mut Ctx, depth: u64) {
+    CALL_DEPTH_LIMIT.set_remaining(ctx, depth);
+}
+
+/// Runs a call into wasm, restoring the call depth afterwards, since the frames left by a trap
+/// never decrement it.
+pub(crate) unsafe fn preserve<R>(ctx: *mut Ctx, f: impl FnOnce() -> R) -> R {
+    let depth = (*ctx).get_internal(&CALL_DEPTH_FIELD);
+    let ret = f();
+    (*ctx).set_internal(&CALL_DEPTH_FIELD, depth);
+    ret
+}
+
+fn exhausted(_: BreakpointInfo) -> Result<(), Box<dyn Any>> {
+    Err(Box::new(CallStackExhausted))
+}
+
+/// A middleware counting the depth of calls, trapping with `CallStackExhausted` past the
+/// maximum depth.
+///
+/// The counter is decremented wherever the function exits, as found by `FunctionExits`.
+#[derive(Debug)]
+pub struct CallDepth {
+    max_depth: u64,
+    exits: FunctionExits,
+}
+
+impl CallDepth {
+    /// Creates a new `CallDepth` middleware, with the deepest depth the calls can nest to.
+    pub fn new(max_depth: u64) -> Self {
+        Self {
+            max_depth,
+            exits: FunctionExits::new(),
+        }
+    }
+}
+
+impl FunctionMiddleware for CallDepth {
+    type Error = String;
+
+    fn feed_event<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+    ) -> Result<(), Self::Error> {
+        match op {
+            Event::Internal(InternalEvent::FunctionBegin(id)) => {
+                sink.push(op);
+                sink.push(Event::Internal(InternalEvent::AddInternal(
+                    CALL_DEPTH_FIELD.index() as _,
+                    1,
+                )));
+                sink.push(Event::Internal(InternalEvent::BreakpointIfInternalAtLeast(
+                    CALL_DEPTH_FIELD.index() as _,
+                    self.max_depth.saturating_add(1),
+                    Box::new(exhausted),
+                )));
+                let func_index = FuncIndex::new(module_info.imported_functions.len() + id as usize);
+                self.exits.begin(func_index, module_info, sink);
+            }
+            op => self.exits.push(op, sink, decrement),
+        }
+        Ok(())
+    }
+
+    fn finalize_module(&mut self, module_info: &mut ModuleInfo) {
+        CALL_DEPTH_LIMIT.record(module_info, self.max_depth);
+    }
+}
+
+fn decrement<'a, 'b: 'a>() -> Event<'a, 'b> {
+    Event::Internal(InternalEvent::AddInternal(
+        CALL_DEPTH_FIELD.index() as _,
+        // Adding `-1`, as the field wraps around.
+        u64::max_value(),
+    ))
+}
@@ -4,10 +4,14 @@ use crate::{
     backend::RunnableModule,
     backend::{Backend, CacheGen, Compiler, CompilerConfig, Features, Token},
     cache::{Artifact, Error as CacheError},
+    deterministic,
     error::{CompileError, CompileResult},
+    lazy::{self, LazyBodies},
     module::{ModuleInfo, ModuleInner},
-    structures::Map,
+    parse::type_to_wp_type,
+    structures::{Map, TypedIndex},
     types::{FuncIndex, FuncSig, SigIndex},
+    vm,
 };
 use smallvec::SmallVec;
 use std::any::Any;
@@ -17,7 +21,7 @@ use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::sync::{Arc, RwLock};
 use wasmparser::{self, WasmDecoder};
-use wasmparser::{Operator, Type as WpType};
+use wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
 
 /// A type that defines a function pointer, which is called when breakpoints occur.
 pub type BreakpointHandler =
@@ -49,6 +53,14 @@ pub enum InternalEvent {
     SetInternal(u32),
     /// Indicates getting an internal field.
     GetInternal(u32),
+    /// Indicates adding a constant to an internal field, without going through the wasm stack.
+    AddInternal(u32, u64),
+    /// A breakpoint that is only hit when an internal field is greater than or equal to a
+    /// constant (unsigned).
+    BreakpointIfInternalAtLeast(u32, u64, BreakpointHandler),
+    /// Adds one to the `u64` counter at an index of the buffer whose address is held by an
+    /// internal field. Nothing is counted while the internal field is zero.
+    IncrementCounter(u32, u32),
 }
 
 impl fmt::Debug for InternalEvent {
@@ -59,6 +71,11 @@ impl fmt::Debug for InternalEvent {
             InternalEvent::Breakpoint(_) => write!(f, "Breakpoint"),
             InternalEvent::SetInternal(_) => write!(f, "SetInternal"),
             InternalEvent::GetInternal(_) => write!(f, "GetInternal"),
+            InternalEvent::AddInternal(_, _) => write!(f, "AddInternal"),
+            InternalEvent::BreakpointIfInternalAtLeast(_, _, _) => {
+                write!(f, "BreakpointIfInternalAtLeast")
+            }
+            InternalEvent::IncrementCounter(_, _) => write!(f, "IncrementCounter"),
         }
     }
 }
@@ -67,6 +84,8 @@ impl fmt::Debug for InternalEvent {
 pub struct BreakpointInfo<'a> {
     /// Fault.
     pub fault: Option<&'a dyn Any>,
+    /// The context of the instance that hit the breakpoint, if the backend knows it.
+    pub ctx: Option<&'a mut vm::Ctx>,
 }
 
 /// A trait that represents the functions needed to be implemented to generate code for a module.
@@ -115,10 +134,12 @@ pub struct StreamingCompiler<
     CGEN: Fn() -> MiddlewareChain,
 > {
     middleware_chain_generator: CGEN,
-    _phantom_mcg: PhantomData<MCG>,
-    _phantom_fcg: PhantomData<FCG>,
-    _phantom_rm: PhantomData<RM>,
-    _phantom_e: PhantomData<E>,
+    // The code generators are created for each compilation, so they don't make the compiler
+    // `!Send` or `!Sync`.
+    _phantom_mcg: PhantomData<fn() -> MCG>,
+    _phantom_fcg: PhantomData<fn() -> FCG>,
+    _phantom_rm: PhantomData<fn() -> RM>,
+    _phantom_e: PhantomData<fn() -> E>,
 }
 
 /// A simple generator for a `StreamingCompiler`.
@@ -175,7 +196,7 @@ pub fn validating_parser_config(features: &Features) -> wasmparser::ValidatingPa
             enable_reference_types: false,
             enable_simd: features.simd,
             enable_bulk_memory: false,
-            enable_multi_value: false,
+            enable_multi_value: features.multi_value,
         },
     }
 }
@@ -209,7 +230,29 @@ impl<
         compiler_config: CompilerConfig,
         _: Token,
     ) -> CompileResult<ModuleInner> {
-        if requires_pre_validation(MCG::backend_id()) {
+        if compiler_config.deterministic {
+            if let Some(feature) =
+                deterministic::nondeterministic_feature(&compiler_config.features)
+            {
+                return Err(CompileError::UnsupportedFeature {
+                    feature: format!("{} in deterministic mode", feature),
+                });
+            }
+        }
+
+        if let Some(feature) = MCG::backend_id().unsupported_feature(&compiler_config.features) {
+            return Err(CompileError::UnsupportedFeature {
+                feature: feature.to_string(),
+            });
+        }
+
+        if compiler_config.lazy_bodies.is_some() && MCG::backend_id() == Backend::LLVM {
+            return Err(CompileError::UnsupportedFeature {
+                feature: lazy::LAZY_FUNCTIONS.to_string(),
+            });
+        }
+
+        if requires_pre_validation(MCG::backend_id())? {
             validate_with_features(wasm, &compiler_config.features)?;
         }
 
@@ -222,6 +265,13 @@ impl<
             _ => MCG::new(),
         };
         let mut chain = (self.middleware_chain_generator)();
+        if compiler_config.deterministic && !deterministic::canonicalizes_nans(MCG::backend_id())? {
+            chain.push(deterministic::NanCanonicalization);
+        }
+        // Last, so that the events of the other middlewares in replaced bodies are dropped.
+        if let Some(LazyBodies::Stubs(handler)) = &compiler_config.lazy_bodies {
+            chain.push(lazy::ReplaceBodies::new(handler.clone()));
+        }
         let info = crate::parse::read_module(
             wasm,
             MCG::backend_id(),
@@ -229,6 +279,14 @@ impl<
             &mut chain,
             &compiler_config,
         )?;
+        chain.finalize_module(&mut info.write().unwrap());
+        // Backends may consume custom sections, e.g. the DWARF ones for debug info.
+        info.write()
+            .unwrap()
+            .import_custom_sections(wasm)
+            .map_err(|e| CompileError::InternalError {
+                msg: format!("{:?}", e),
+            })?;
         let (exec_context, cache_gen) =
             mcg.finalize(&info.read().unwrap())
                 .map_err(|x| CompileError::InternalError {
@@ -238,6 +296,7 @@ impl<
             cache_gen,
             runnable_module: Box::new(exec_context),
             info: Arc::try_unwrap(info).unwrap().into_inner().unwrap(),
+            memory_images: Default::default(),
         })
     }
 
@@ -250,17 +309,27 @@ impl<
     }
 }
 
-fn requires_pre_validation(backend: Backend) -> bool {
+fn requires_pre_validation(backend: Backend) -> CompileResult<bool> {
     match backend {
-        Backend::Cranelift => true,
-        Backend::LLVM => false,
-        Backend::Singlepass => false,
+        Backend::Cranelift => Ok(true),
+        Backend::LLVM => Ok(false),
+        Backend::Singlepass => Ok(false),
+        Backend::Auto => Err(auto_backend_error()),
+    }
+}
+
+/// The error for a code generator whose `backend_id` is `Backend::Auto`, which only names a
+/// choice between the other backends.
+pub(crate) fn auto_backend_error() -> CompileError {
+    CompileError::InternalError {
+        msg: "code generators must have a concrete backend, not `Backend::Auto`".to_string(),
     }
 }
 
 /// A sink for parse events.
 pub struct EventSink<'a, 'b> {
     buffer: SmallVec<[Event<'a, 'b>; 2]>,
+    source_offset: u32,
 }
 
 impl<'a, 'b> EventSink<'a, 'b> {
@@ -268,6 +337,93 @@ impl<'a, 'b> EventSink<'a, 'b> {
     pub fn push(&mut self, ev: Event<'a, 'b>) {
         self.buffer.push(ev);
     }
+
+    /// Returns the offset in the module binary of the operator being processed.
+    ///
+    /// For `FunctionBegin` this is the offset of the first operator of the function, and for
+    /// `FunctionEnd` the offset right after its body.
+    pub fn source_offset(&self) -> u32 {
+        self.source_offset
+    }
+}
+
+/// Tracks where a function exits, for middlewares running code whenever it returns.
+///
+/// The body of the function is wrapped in a block, so that every branch out of the function
+/// leaves the block: the function exits on `return` and right after the end of that block.
+#[derive(Debug, Default)]
+pub struct FunctionExits {
+    block_depth: usize,
+}
+
+impl FunctionExits {
+    /// Creates a new `FunctionExits`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the block wrapping the body of the function at `func_index`. This is called when
+    /// the function begins, after the `FunctionBegin` event is pushed.
+    ///
+    /// A function with several results is wrapped in a block of its own type, which takes its
+    /// parameters. They are pushed before the block and dropped right away, since the body
+    /// reads them from locals.
+    pub fn begin<'a, 'b: 'a>(
+        &mut self,
+        func_index: FuncIndex,
+        module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+    ) {
+        self.block_depth = 0;
+        let sig_index = module_info.func_assoc[func_index];
+        let sig = &module_info.signatures[sig_index];
+        let ty = match sig.returns() {
+            [] => WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+            [ty] => WpTypeOrFuncType::Type(type_to_wp_type(*ty)),
+            _ => {
+                for local_index in 0..sig.params().len() as u32 {
+                    sink.push(Event::WasmOwned(Operator::GetLocal { local_index }));
+                }
+                WpTypeOrFuncType::FuncType(sig_index.index() as u32)
+            }
+        };
+        sink.push(Event::WasmOwned(Operator::Block { ty }));
+        if sig.returns().len() > 1 {
+            for _ in sig.params() {
+                sink.push(Event::WasmOwned(Operator::Drop));
+            }
+        }
+    }
+
+    /// Pushes the event `op` of the body to `sink`, with the event made by `on_exit` where the
+    /// function exits: before `return`, and after the end of the block wrapping the body.
+    pub fn push<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        sink: &mut EventSink<'a, 'b>,
+        on_exit: impl FnOnce() -> Event<'a, 'b>,
+    ) {
+        match op {
+            Event::Wasm(&ref operator) | Event::WasmOwned(ref operator) => match *operator {
+                Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                    self.block_depth += 1;
+                }
+                Operator::End if self.block_depth == 0 => {
+                    sink.push(Event::WasmOwned(Operator::End));
+                    sink.push(on_exit());
+                }
+                Operator::End => {
+                    self.block_depth -= 1;
+                }
+                Operator::Return => {
+                    sink.push(on_exit());
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        sink.push(op);
+    }
 }
 
 /// A container for a chain of middlewares.
@@ -286,15 +442,18 @@ impl MiddlewareChain {
         self.chain.push(Box::new(m));
     }
 
-    /// Run this chain with the provided function code generator, event and module info.
+    /// Run this chain with the provided function code generator, event, module info and offset
+    /// of the event in the module binary.
     pub(crate) fn run<E: Debug, FCG: FunctionCodeGenerator<E>>(
         &mut self,
         fcg: Option<&mut FCG>,
         ev: Event,
         module_info: &ModuleInfo,
+        source_offset: u32,
     ) -> Result<(), String> {
         let mut sink = EventSink {
             buffer: SmallVec::new(),
+            source_offset,
         };
         sink.push(ev);
         for m in &mut self.chain {
@@ -312,6 +471,13 @@ impl MiddlewareChain {
 
         Ok(())
     }
+
+    /// Lets the middlewares of this chain record what they need at runtime in the module info.
+    pub(crate) fn finalize_module(&mut self, module_info: &mut ModuleInfo) {
+        for m in &mut self.chain {
+            m.finalize_module(module_info);
+        }
+    }
 }
 
 /// A trait that represents the signature required to implement middleware for a function.
@@ -325,6 +491,10 @@ pub trait FunctionMiddleware {
         module_info: &ModuleInfo,
         sink: &mut EventSink<'a, 'b>,
     ) -> Result<(), Self::Error>;
+
+    /// Records what this middleware needs at runtime in the module info, once every function
+    /// has been fed.
+    fn finalize_module(&mut self, _module_info: &mut ModuleInfo) {}
 }
 
 pub(crate) trait GenericFunctionMiddleware {
@@ -334,6 +504,8 @@ pub(crate) trait GenericFunctionMiddleware {
         module_info: &ModuleInfo,
         sink: &mut EventSink<'a, 'b>,
     ) -> Result<(), String>;
+
+    fn finalize_module(&mut self, module_info: &mut ModuleInfo);
 }
 
 impl<E: Debug, T: FunctionMiddleware<Error = E>> GenericFunctionMiddleware for T {
@@ -346,6 +518,10 @@ impl<E: Debug, T: FunctionMiddleware<Error = E>> GenericFunctionMiddleware for T
         <Self as FunctionMiddleware>::feed_event(self, op, module_info, sink)
             .map_err(|x| format!("{:?}", x))
     }
+
+    fn finalize_module(&mut self, module_info: &mut ModuleInfo) {
+        <Self as FunctionMiddleware>::finalize_module(self, module_info)
+    }
 }
 
 /// The function-scope code generator trait.
@@ -362,6 +538,12 @@ pub trait FunctionCodeGenerator<E: Debug> {
     /// Called before the first call to `feed_opcode`.
     fn begin_body(&mut self, module_info: &ModuleInfo) -> Result<(), E>;
 
+    /// Called before the events of the operator at `code_offset`, the offset from the start of
+    /// the code section contents, are fed. This is how DWARF in wasm modules refers to code.
+    fn begin_operator(&mut self, _code_offset: u32) -> Result<(), E> {
+        Ok(())
+    }
+
     /// Called for each operator.
     fn feed_event(&mut self, op: Event, module_info: &ModuleInfo) -> Result<(), E>;
 
@@ -0,0 +1,153 @@
+//! The deterministic module makes code compiled with `CompilerConfig::deterministic` produce the
+//! same results on every backend and platform.
+//!
+//! WebAssembly only leaves the bit pattern of NaNs and the behavior of threads and SIMD up to
+//! the implementation. In deterministic mode, the threads and SIMD features are rejected, and
+//! every NaN produced by a float operation is replaced by the canonical NaN of its type, either
+//! by the backend itself or by the `NanCanonicalization` middleware.
+//!
+//! Running out of native stack still depends on the backend and the host; embedders needing
+//! identical traps there should bound the call depth with the `CallDepth` middleware.
+use crate::{
+    backend::{Backend, Features},
+    codegen::{auto_backend_error, Event, EventSink, FunctionMiddleware, InternalEvent},
+    error::CompileResult,
+    module::ModuleInfo,
+    vm::InternalField,
+    wasmparser::Operator,
+};
+
+/// Holds the bits of the value being canonicalized, as wasm can't duplicate a stack value.
+static NAN_FIELD: InternalField = InternalField::allocate();
+
+const CANONICAL_NAN_F32: u64 = 0x7fc0_0000;
+const CANONICAL_NAN_F64: u64 = 0x7ff8_0000_0000_0000;
+
+/// Returns the name of one of `features` that deterministic code can't use.
+pub(crate) fn nondeterministic_feature(features: &Features) -> Option<&'static str> {
+    if features.threads {
+        Some("threads")
+    } else if features.simd {
+        Some("simd")
+    } else {
+        None
+    }
+}
+
+/// Returns whether `backend` canonicalizes NaNs itself in deterministic mode, instead of
+/// relying on the `NanCanonicalization` middleware.
+pub(crate) fn canonicalizes_nans(backend: Backend) -> CompileResult<bool> {
+    match backend {
+        // Cranelift has a setting for it, which is cheaper than the middleware.
+        Backend::Cranelift => Ok(true),
+        Backend::LLVM | Backend::Singlepass => Ok(false),
+        Backend::Auto => Err(auto_backend_error()),
+    }
+}
+
+/// NanCanonicalization is a compiler middleware that replaces the NaNs produced by float
+/// operations with the canonical NaN of their type.
+///
+/// It's added to the middlewares of deterministic compilations by the streaming compiler, on
+/// backends that don't canonicalize NaNs themselves. Only scalar operations are handled, as
+/// SIMD is rejected in deterministic mode.
+pub struct NanCanonicalization;
+
+impl FunctionMiddleware for NanCanonicalization {
+    type Error = String;
+    fn feed_event<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        _module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+    ) -> Result<(), Self::Error> {
+        let produces = match op {
+            Event::Wasm(&ref op) | Event::WasmOwned(ref op) => produced_nan(op),
+            _ => None,
+        };
+        sink.push(op);
+        match produces {
+            Some(NanType::F32) => {
+                sink.push(Event::WasmOwned(Operator::I32ReinterpretF32));
+                sink.push(Event::WasmOwned(Operator::I64ExtendUI32));
+                push_select_canonical(sink, CANONICAL_NAN_F32, 0x7fff_ffff, 0x7f80_0000);
+                sink.push(Event::WasmOwned(Operator::I32WrapI64));
+                sink.push(Event::WasmOwned(Operator::F32ReinterpretI32));
+            }
+            Some(NanType::F64) => {
+                sink.push(Event::WasmOwned(Operator::I64ReinterpretF64));
+                push_select_canonical(
+                    sink,
+                    CANONICAL_NAN_F64,
+                    0x7fff_ffff_ffff_ffff,
+                    0x7ff0_0000_0000_0000,
+                );
+                sink.push(Event::WasmOwned(Operator::F64ReinterpretI64));
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+enum NanType {
+    F32,
+    F64,
+}
+
+/// Returns the type of the NaN `op` may produce with an unspecified bit pattern.
+fn produced_nan(op: &Operator) -> Option<NanType> {
+    match *op {
+        Operator::F32Add
+        | Operator::F32Sub
+        | Operator::F32Mul
+        | Operator::F32Div
+        | Operator::F32Sqrt
+        | Operator::F32Min
+        | Operator::F32Max
+        | Operator::F32Ceil
+        | Operator::F32Floor
+        | Operator::F32Trunc
+        | Operator::F32Nearest
+        | Operator::F32DemoteF64 => Some(NanType::F32),
+        Operator::F64Add
+        | Operator::F64Sub
+        | Operator::F64Mul
+        | Operator::F64Div
+        | Operator::F64Sqrt
+        | Operator::F64Min
+        | Operator::F64Max
+        | Operator::F64Ceil
+        | Operator::F64Floor
+        | Operator::F64Trunc
+        | Operator::F64Nearest
+        | Operator::F64PromoteF32 => Some(NanType::F64),
+        _ => None,
+    }
+}
+
+/// Replaces the bits on top of the stack, zero-extended to an `i64`, with `canonical` if they
+/// are a NaN, i.e. if they are above the infinity once the sign is masked off.
+fn push_select_canonical<'a, 'b: 'a>(
+    sink: &mut EventSink<'a, 'b>,
+    canonical: u64,
+    unsigned_mask: u64,
+    infinity: u64,
+) {
+    let field = NAN_FIELD.index() as u32;
+    sink.push(Event::Internal(InternalEvent::SetInternal(field)));
+    sink.push(Event::WasmOwned(Operator::I64Const {
+        value: canonical as i64,
+    }));
+    sink.push(Event::Internal(InternalEvent::GetInternal(field)));
+    sink.push(Event::Internal(InternalEvent::GetInternal(field)));
+    sink.push(Event::WasmOwned(Operator::I64Const {
+        value: unsigned_mask as i64,
+    }));
+    sink.push(Event::WasmOwned(Operator::I64And));
+    sink.push(Event::WasmOwned(Operator::I64Const {
+        value: infinity as i64,
+    }));
+    sink.push(Event::WasmOwned(Operator::I64GtU));
+    sink.push(Event::WasmOwned(Operator::Select));
+}
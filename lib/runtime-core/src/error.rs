@@ -1,5 +1,8 @@
 //! The error module contains the data structures and helper functions used to implement errors that
 //! are produced and returned from the wasmer runtime core.
+use crate::backtrace::WasmBacktrace;
+use crate::export::ExportKind;
+use crate::typed_func::WasmTrapInfo;
 use crate::types::{FuncSig, GlobalDescriptor, MemoryDescriptor, TableDescriptor, Type};
 use core::borrow::Borrow;
 use std::any::Any;
@@ -42,6 +45,11 @@ pub enum CompileError {
         /// An error message.
         msg: String,
     },
+    /// The module uses a feature that the backend doesn't support.
+    UnsupportedFeature {
+        /// The name of the feature, e.g. `simd`.
+        feature: String,
+    },
 }
 
 impl PartialEq for CompileError {
@@ -57,6 +65,9 @@ impl std::fmt::Display for CompileError {
                 write!(f, "Internal compiler error: \"{}\"", msg)
             }
             CompileError::ValidationError { msg } => write!(f, "Validation error \"{}\"", msg),
+            CompileError::UnsupportedFeature { feature } => {
+                write!(f, "Unsupported feature: {}", feature)
+            }
         }
     }
 }
@@ -76,9 +87,9 @@ pub enum LinkError {
         /// Name.
         name: String,
         /// Expected.
-        expected: String,
+        expected: ExportKind,
         /// Found.
-        found: String,
+        found: ExportKind,
     },
     /// The signature of the provided import does not match the expected signature.
     IncorrectImportSignature {
@@ -131,6 +142,20 @@ pub enum LinkError {
         /// Found.
         found: GlobalDescriptor,
     },
+    /// A data segment doesn't fit in its memory.
+    DataSegmentDoesNotFit {
+        /// The offset of the segment.
+        offset: usize,
+        /// The size of the segment in bytes.
+        len: usize,
+    },
+    /// An elements segment doesn't fit in its table.
+    ElementSegmentDoesNotFit {
+        /// The offset of the segment.
+        offset: usize,
+        /// The number of elements of the segment.
+        len: usize,
+    },
     /// A generic error with a message.
     Generic {
         /// Error message.
@@ -163,6 +188,12 @@ impl std::fmt::Display for LinkError {
             LinkError::IncorrectTableDescriptor{namespace, name,expected,found} => {
                 write!(f, "Incorrect table descriptor, namespace: {}, name: {}, expected table descriptor: {:?}, found table descriptor: {:?}", namespace, name, expected, found)
             },
+            LinkError::DataSegmentDoesNotFit { offset, len } => {
+                write!(f, "data segment does not fit, offset: {}, length: {}", offset, len)
+            },
+            LinkError::ElementSegmentDoesNotFit { offset, len } => {
+                write!(f, "elements segment does not fit, offset: {}, length: {}", offset, len)
+            },
             LinkError::Generic { message } => {
                 write!(f, "{}", message)
             },
@@ -181,14 +212,64 @@ impl std::error::Error for LinkError {}
 pub enum RuntimeError {
     /// Trap.
     Trap {
+        /// What caused the trap.
+        code: WasmTrapInfo,
         /// Trap message.
         msg: Box<str>,
+        /// The wasm call stack where the trap occurred, if it was captured.
+        backtrace: Option<WasmBacktrace>,
     },
     /// Error.
     Error {
         /// Error data.
         data: Box<dyn Any>,
+        /// The wasm call stack where the error was raised, if it was captured.
+        backtrace: Option<WasmBacktrace>,
     },
+    /// The instance was interrupted with an `InterruptHandle`.
+    Interrupted,
+}
+
+/// The marker thrown by the fault handler when an instance is interrupted.
+#[cfg_attr(not(all(unix, target_arch = "x86_64")), allow(dead_code))]
+pub(crate) struct Interrupted;
+
+impl RuntimeError {
+    /// Converts the data of an error thrown during a call.
+    pub(crate) fn from_user_error(data: Box<dyn Any>, backtrace: Option<WasmBacktrace>) -> Self {
+        if data.is::<Interrupted>() {
+            RuntimeError::Interrupted
+        } else {
+            RuntimeError::Error { data, backtrace }
+        }
+    }
+
+    /// Returns what caused the trap, if this error is a trap.
+    pub fn trap_code(&self) -> Option<WasmTrapInfo> {
+        match self {
+            RuntimeError::Trap { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// Returns the data of this error if it's a `T`, e.g. the payload of a trap raised by an
+    /// import function.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        match self {
+            RuntimeError::Error { data, .. } => data.downcast_ref::<T>(),
+            _ => None,
+        }
+    }
+
+    /// Returns the wasm call stack where this error occurred, if it was captured.
+    pub fn backtrace(&self) -> Option<&WasmBacktrace> {
+        match self {
+            RuntimeError::Trap { backtrace, .. } | RuntimeError::Error { backtrace, .. } => {
+                backtrace.as_ref()
+            }
+            RuntimeError::Interrupted => None,
+        }
+    }
 }
 
 impl PartialEq for RuntimeError {
@@ -200,18 +281,25 @@ impl PartialEq for RuntimeError {
 impl std::fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            RuntimeError::Trap { ref msg } => {
-                write!(f, "WebAssembly trap occurred during runtime: {}", msg)
+            RuntimeError::Trap { ref msg, .. } => {
+                write!(f, "WebAssembly trap occurred during runtime: {}", msg)?
             }
-            RuntimeError::Error { data } => {
+            RuntimeError::Error { data, .. } => {
                 if let Some(s) = data.downcast_ref::<String>() {
-                    write!(f, "\"{}\"", s)
+                    write!(f, "\"{}\"", s)?
                 } else if let Some(s) = data.downcast_ref::<&str>() {
-                    write!(f, "\"{}\"", s)
+                    write!(f, "\"{}\"", s)?
                 } else {
-                    write!(f, "unknown error")
+                    write!(f, "unknown error")?
                 }
             }
+            RuntimeError::Interrupted => write!(f, "WebAssembly execution was interrupted")?,
+        }
+        match self.backtrace() {
+            Some(backtrace) if !backtrace.frames().is_empty() => {
+                write!(f, "\nwasm backtrace:\n{}", backtrace)
+            }
+            _ => Ok(()),
         }
     }
 }
@@ -469,6 +557,8 @@ pub enum GrowError {
     CouldNotProtectMemory(MemoryProtectionError),
     /// Error creating memory.
     CouldNotCreateMemory(MemoryCreationError),
+    /// The grow observer of the memory or the table vetoed the growth.
+    Vetoed,
 }
 
 impl std::fmt::Display for GrowError {
@@ -480,12 +570,101 @@ impl std::fmt::Display for GrowError {
             GrowError::ExceededMaxPagesForMemory(left, added) => write!(f, "Failed to add pages because would exceed maximum number of pages for the memory. Left: {}, Added: {}", left, added),
             GrowError::CouldNotCreateMemory(e) => write!(f, "Grow Error: {}", e),
             GrowError::CouldNotProtectMemory(e) => write!(f, "Grow Error: {}", e),
+            GrowError::Vetoed => write!(f, "The growth was vetoed by its grow observer"),
         }
     }
 }
 
 impl std::error::Error for GrowError {}
 
+/// An error occurred while restoring an instance from an `InstanceImage`.
+#[derive(Debug)]
+pub enum RestoreError {
+    /// The image was taken from an instance of a different module.
+    IncompatibleImage(String),
+    /// Error growing a memory or table to the size in the image.
+    Grow(GrowError),
+}
+
+impl std::fmt::Display for RestoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RestoreError::IncompatibleImage(msg) => write!(f, "Incompatible image: {}", msg),
+            RestoreError::Grow(e) => write!(f, "Restore Error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RestoreError {}
+
+impl From<GrowError> for RestoreError {
+    fn from(grow_error: GrowError) -> Self {
+        RestoreError::Grow(grow_error)
+    }
+}
+
+/// An error occurred while creating an `InterruptHandle`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterruptError {
+    /// The code of the instance has no safepoints to deliver interrupts at. Cranelift emits
+    /// none, and LLVM only with `track_state` enabled.
+    NoSafepoints,
+    /// Too many instances have an interrupt handle already.
+    TooManyHandles,
+}
+
+impl std::fmt::Display for InterruptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InterruptError::NoSafepoints => {
+                write!(f, "The instance has no safepoints to be interrupted at")
+            }
+            InterruptError::TooManyHandles => {
+                write!(f, "Too many instances have an interrupt handle")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InterruptError {}
+
+/// An error occurred while loading a module into a `Linker`.
+#[derive(Debug)]
+pub enum LinkerError {
+    /// The module has no `dylink` section, so it wasn't built for dynamic linking.
+    MissingDylinkSection,
+    /// The `dylink` section of the module is malformed.
+    InvalidDylinkSection(String),
+    /// Error growing the shared memory or table to make room for the module.
+    Grow(GrowError),
+    /// Error instantiating the module.
+    Instantiate(Error),
+    /// Error calling the initialization functions of the module.
+    Initialize(CallError),
+}
+
+impl std::fmt::Display for LinkerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LinkerError::MissingDylinkSection => write!(f, "The module has no dylink section"),
+            LinkerError::InvalidDylinkSection(msg) => {
+                write!(f, "Invalid dylink section: {}", msg)
+            }
+            LinkerError::Grow(e) => write!(f, "Linker Error: {}", e),
+            LinkerError::Instantiate(e) => write!(f, "Linker Error: {}", e),
+            LinkerError::Initialize(e) => write!(f, "Linker Error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LinkerError {}
+
+impl From<GrowError> for LinkerError {
+    fn from(grow_error: GrowError) -> Self {
+        LinkerError::Grow(grow_error)
+    }
+}
+
 /// A kind of page error.
 #[derive(Debug)]
 pub enum PageError {
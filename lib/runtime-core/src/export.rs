@@ -6,7 +6,7 @@ use crate::{
     module::ModuleInner, table::Table, types::FuncSig, vm,
 };
 use indexmap::map::Iter as IndexMapIter;
-use std::{ptr::NonNull, sync::Arc};
+use std::{any::Any, fmt, ptr::NonNull, sync::Arc};
 
 /// A kind of Context.
 #[derive(Debug, Copy, Clone)]
@@ -36,6 +36,9 @@ pub enum Export {
         ctx: Context,
         /// The signature of the function.
         signature: Arc<FuncSig>,
+        /// Keeps the captured environment of the function alive, if
+        /// it has one.
+        env_owner: Option<FuncEnvOwner>,
     },
     /// Memory export.
     Memory(Memory),
@@ -45,6 +48,65 @@ pub enum Export {
     Global(Global),
 }
 
+impl Export {
+    /// Returns the kind of this export.
+    pub fn kind(&self) -> ExportKind {
+        match self {
+            Export::Function { .. } => ExportKind::Function,
+            Export::Memory(_) => ExportKind::Memory,
+            Export::Table(_) => ExportKind::Table,
+            Export::Global(_) => ExportKind::Global,
+        }
+    }
+}
+
+/// The kinds of exports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    /// A function.
+    Function,
+    /// A memory.
+    Memory,
+    /// A table.
+    Table,
+    /// A global.
+    Global,
+}
+
+impl fmt::Display for ExportKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ExportKind::Function => "function",
+            ExportKind::Memory => "memory",
+            ExportKind::Table => "table",
+            ExportKind::Global => "global",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Shared ownership of the captured environment of a host closure.
+///
+/// The environment is dropped once the last `Func`, `Export` and
+/// instance referring to it are gone.
+#[derive(Clone)]
+pub struct FuncEnvOwner(Arc<dyn Any + Send + Sync>);
+
+impl FuncEnvOwner {
+    pub(crate) fn new<T>(env: Arc<T>) -> Self
+    where
+        T: Send + Sync + 'static,
+    {
+        FuncEnvOwner(env)
+    }
+}
+
+impl fmt::Debug for FuncEnvOwner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FuncEnvOwner").finish()
+    }
+}
+
 /// Const pointer to a `Func`.
 #[derive(Debug, Clone)]
 pub struct FuncPointer(*const vm::Func);
@@ -13,11 +13,15 @@ pub mod raw {
     }
 }
 
+use crate::backtrace;
 use crate::codegen::{BreakpointInfo, BreakpointMap};
+use crate::error::Interrupted;
 use crate::state::x64::{build_instance_image, read_stack, X64Register, GPR, XMM};
 use crate::state::CodeVersion;
 use crate::vm;
-use libc::{mmap, mprotect, siginfo_t, MAP_ANON, MAP_PRIVATE, PROT_NONE, PROT_READ, PROT_WRITE};
+use libc::{
+    mmap, mprotect, munmap, siginfo_t, MAP_ANON, MAP_PRIVATE, PROT_NONE, PROT_READ, PROT_WRITE,
+};
 use nix::sys::signal::{
     sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal, SIGBUS, SIGFPE, SIGILL, SIGINT,
     SIGSEGV, SIGTRAP,
@@ -26,8 +30,8 @@ use std::any::Any;
 use std::cell::{Cell, RefCell, UnsafeCell};
 use std::ffi::c_void;
 use std::process;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Once;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Once};
 
 pub(crate) unsafe fn run_on_alternative_stack(stack_end: *mut u64, stack_begin: *mut u64) -> u64 {
     raw::run_on_alternative_stack(stack_end, stack_begin)
@@ -158,6 +162,126 @@ pub unsafe fn clear_wasm_interrupt() {
     }
 }
 
+/// The maximum number of `InterruptPage`s alive at once.
+const MAX_INTERRUPT_PAGES: usize = 1024;
+
+lazy_static! {
+    /// The addresses of the live `InterruptPage`s, or zero for a free slot. The signal handler
+    /// reads it without locking, so slots are claimed and released atomically.
+    static ref INTERRUPT_PAGES: Vec<AtomicUsize> =
+        (0..MAX_INTERRUPT_PAGES).map(|_| AtomicUsize::new(0)).collect();
+}
+
+/// Whether `INTERRUPT_PAGES` was initialized, so that the signal handler never initializes it.
+static INTERRUPT_PAGES_INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// An interrupt signal mem dedicated to one instance, so that it can be
+/// interrupted without affecting the others.
+pub(crate) struct InterruptPage(*mut u8);
+
+unsafe impl Send for InterruptPage {}
+unsafe impl Sync for InterruptPage {}
+
+impl InterruptPage {
+    /// Allocates a new interrupt page, or returns `None` if there are
+    /// already `MAX_INTERRUPT_PAGES` of them.
+    pub(crate) fn new() -> Option<Self> {
+        let ptr = unsafe {
+            mmap(
+                ::std::ptr::null_mut(),
+                INTERRUPT_SIGNAL_MEM_SIZE,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANON,
+                -1,
+                0,
+            )
+        };
+        if ptr as isize == -1 {
+            panic!("cannot allocate interrupt signal mem");
+        }
+
+        let claimed = INTERRUPT_PAGES.iter().any(|slot| {
+            slot.compare_exchange(0, ptr as usize, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        });
+        INTERRUPT_PAGES_INITIALIZED.store(true, Ordering::SeqCst);
+        if !claimed {
+            unsafe {
+                munmap(ptr, INTERRUPT_SIGNAL_MEM_SIZE);
+            }
+            return None;
+        }
+        Some(InterruptPage(ptr as _))
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut u8 {
+        self.0
+    }
+}
+
+impl Drop for InterruptPage {
+    fn drop(&mut self) {
+        for slot in INTERRUPT_PAGES.iter() {
+            if slot
+                .compare_exchange(self.0 as usize, 0, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                break;
+            }
+        }
+        unsafe {
+            munmap(self.0 as _, INTERRUPT_SIGNAL_MEM_SIZE);
+        }
+    }
+}
+
+/// Re-arms the interrupt page at `addr`, returning `false` if `addr`
+/// isn't an interrupt page. It doesn't lock, since it runs in the
+/// signal handler.
+unsafe fn take_interrupt(addr: usize) -> bool {
+    if !INTERRUPT_PAGES_INITIALIZED.load(Ordering::SeqCst)
+        || !INTERRUPT_PAGES
+            .iter()
+            .any(|slot| slot.load(Ordering::SeqCst) == addr)
+    {
+        return false;
+    }
+    if mprotect(addr as _, INTERRUPT_SIGNAL_MEM_SIZE, PROT_READ | PROT_WRITE) < 0 {
+        panic!("cannot set PROT_READ | PROT_WRITE on interrupt signal mem");
+    }
+    true
+}
+
+/// A handle to interrupt an instance from another thread, created by
+/// `Instance::interrupt_handle`.
+#[derive(Clone)]
+pub struct InterruptHandle {
+    page: Arc<InterruptPage>,
+}
+
+impl InterruptHandle {
+    pub(crate) fn new(page: Arc<InterruptPage>) -> Self {
+        Self { page }
+    }
+
+    /// Makes the instance trap with `RuntimeError::Interrupted` at its next
+    /// safepoint, that is the next function entry or loop iteration.
+    ///
+    /// If the instance isn't running, its next call is interrupted.
+    pub fn interrupt(&self) {
+        unsafe {
+            if mprotect(
+                self.page.as_ptr() as _,
+                INTERRUPT_SIGNAL_MEM_SIZE,
+                PROT_NONE,
+            ) < 0
+            {
+                panic!("cannot set PROT_NONE on interrupt signal mem");
+            }
+        }
+    }
+}
+
 /// Catches an unsafe unwind with the given functions and breakpoints.
 pub unsafe fn catch_unsafe_unwind<R, F: FnOnce() -> R>(
     f: F,
@@ -262,6 +386,9 @@ extern "C" fn signal_trap_handler(
                         bkpt_map.and_then(|x| x.get(&(fault.ip as usize))).map(|x| {
                             x(BreakpointInfo {
                                 fault: Some(&fault),
+                                // Compiled code keeps the context in R15.
+                                ctx: fault.known_registers[X64Register::GPR(GPR::R15).to_index().0]
+                                    .and_then(|ctx| (ctx as *mut vm::Ctx).as_mut()),
                             })
                         })
                     });
@@ -270,6 +397,7 @@ extern "C" fn signal_trap_handler(
                             return false;
                         }
                         Some(Err(e)) => {
+                            capture_backtrace(&fault);
                             unwind_result = e;
                             return true;
                         }
@@ -283,6 +411,9 @@ extern "C" fn signal_trap_handler(
                         if INTERRUPT_SIGNAL_DELIVERED.swap(false, Ordering::SeqCst) {
                             WAS_SIGINT_TRIGGERED.with(|x| x.set(true));
                         }
+                    } else if take_interrupt(fault.faulting_addr as usize) {
+                        unwind_result = Box::new(Interrupted);
+                        return true;
                     }
                 }
                 _ => {}
@@ -305,6 +436,7 @@ extern "C" fn signal_trap_handler(
                 let image = build_instance_image(ctx, es_image);
                 unwind_result = Box::new(image);
             } else {
+                capture_backtrace(&fault);
                 if es_image.frames.len() > 0 {
                     eprintln!(
                         "\n{}",
@@ -324,6 +456,13 @@ extern "C" fn signal_trap_handler(
     }
 }
 
+/// Captures the wasm backtrace of a fault in code maintaining frame pointers.
+pub unsafe fn capture_backtrace(fault: &FaultInfo) {
+    if let Some(rbp) = fault.known_registers[X64Register::GPR(GPR::RBP).to_index().0] {
+        backtrace::capture_frames(fault.ip as usize, rbp as usize);
+    }
+}
+
 extern "C" fn sigint_handler(
     _signum: ::nix::libc::c_int,
     _siginfo: *mut siginfo_t,
@@ -0,0 +1,196 @@
+//! The fuel module meters the execution of instances with the [`Metering`] middleware. The
+//! executed WebAssembly instructions cost points, and an instance that has used up the limit it
+//! was compiled with either gets refueled by its fuel handler and resumes where it stopped, or
+//! traps with `OutOfFuel`.
+//!
+//! Points are only counted by code compiled with the `Metering` middleware, on backends
+//! supporting middlewares. The fuel left in an instance is the limit minus the points it used.
+//! An instance can be given more fuel than the limit: the compiled code still stops at the
+//! limit, and the instance is then refueled from the rest before its fuel handler is called.
+//!
+//! [`Metering`]: struct.Metering.html
+use crate::{
+    codegen::{BreakpointInfo, Event, EventSink, FunctionMiddleware, InternalEvent},
+    internal_limit::{self, InternalLimit},
+    module::ModuleInfo,
+    vm::{Ctx, InternalField},
+    wasmparser::Operator,
+};
+use std::{any::Any, fmt, sync::Arc};
+
+/// The internal field holding the points used by an instance.
+pub static POINTS_USED_FIELD: InternalField = InternalField::allocate();
+
+/// The points used by an instance, limited by `Metering`.
+static POINTS_LIMIT: InternalLimit = InternalLimit {
+    field: &POINTS_USED_FIELD,
+    name: "metering.limit",
+};
+
+/// Called with the context of an instance that has run out of fuel. Returning `Some(n)`, with
+/// `n` greater than zero, refuels the instance with `n` units and resumes its execution, while
+/// returning `None` makes it trap with `OutOfFuel`.
+///
+/// The handler runs on the guest stack, so it can wait for more fuel, e.g. with
+/// `async_import::suspend_on`.
+pub type FuelHandler = Arc<dyn Fn(&mut Ctx) -> Option<u64> + Send + Sync>;
+
+/// The fuel of an instance that isn't kept in its internal fields.
+#[derive(Clone, Default)]
+pub(crate) struct FuelState {
+    pub(crate) handler: Option<FuelHandler>,
+    /// The fuel given to the instance past the limit its module was compiled with.
+    pub(crate) reserve: u64,
+}
+
+impl fmt::Debug for FuelState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FuelState")
+            .field("has_handler", &self.handler.is_some())
+            .field("reserve", &self.reserve)
+            .finish()
+    }
+}
+
+/// The trap raised when an instance runs out of fuel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfFuel;
+
+impl fmt::Display for OutOfFuel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "out of fuel")
+    }
+}
+
+impl std::error::Error for OutOfFuel {}
+
+/// Returns the fuel left in the instance of `ctx`, or `None` if its module isn't metered.
+pub fn fuel_remaining(ctx: &Ctx) -> Option<u64> {
+    let limit = POINTS_LIMIT.compiled(unsafe { &(*ctx.module).info })?;
+    let reserve = unsafe { (*ctx.local_backing).fuel.reserve };
+    Some(internal_limit::remaining(
+        limit.saturating_add(reserve),
+        ctx.get_internal(&POINTS_USED_FIELD),
+    ))
+}
+
+/// Sets the fuel left in the instance of `ctx`. This does nothing if the module isn't metered.
+pub fn set_fuel(ctx: &mut Ctx, fuel: u64) {
+    if let Some(limit) = POINTS_LIMIT.compiled(unsafe { &(*ctx.module).info }) {
+        POINTS_LIMIT.set_remaining(ctx, fuel);
+        unsafe { (*ctx.local_backing).fuel.reserve = fuel - fuel.min(limit) };
+    }
+}
+
+fn refuel(info: BreakpointInfo) -> Result<(), Box<dyn Any>> {
+    if let Some(ctx) = info.ctx {
+        // The points used past the limit are paid from the reserve.
+        if let Some(fuel) = fuel_remaining(ctx).filter(|&fuel| fuel > 0) {
+            set_fuel(ctx, fuel);
+            return Ok(());
+        }
+
+        let handler = unsafe { (*ctx.local_backing).fuel.handler.clone() };
+        if let Some(fuel) = handler.and_then(|handler| handler(ctx)) {
+            if fuel > 0 {
+                set_fuel(ctx, fuel);
+                return Ok(());
+            }
+        }
+    }
+
+    Err(Box::new(OutOfFuel))
+}
+
+/// Metering is a compiler middleware that calculates the cost of WebAssembly instructions at
+/// compile time and will count the cost of executed instructions at runtime. Within the
+/// Metering functionality, this instruction cost is called `points`.
+///
+/// The Metering struct takes a `limit` parameter which is the maximum number of points which
+/// can be used by an instance. If this limit is exceeded, the instance is refueled by its fuel
+/// handler, or traps with `OutOfFuel`. The points used are kept across calls; set the fuel of
+/// the instance, or its points used, to reset them.
+///
+/// The instructions of a basic block are paid for at its end, and the points used are checked
+/// before every branch and call, so an instance can run slightly past its limit.
+///
+/// Each compiler backend with Metering enabled should produce the same cost used at runtime for
+/// the same function calls so we can say that the metering is deterministic.
+#[derive(Debug)]
+pub struct Metering {
+    limit: u64,
+    current_block: u64,
+}
+
+impl Metering {
+    /// Creates a new `Metering` middleware, with the maximum number of points an instance can
+    /// use before running out of fuel.
+    pub fn new(limit: u64) -> Metering {
+        Metering {
+            limit,
+            current_block: 0,
+        }
+    }
+}
+
+impl FunctionMiddleware for Metering {
+    type Error = String;
+
+    fn feed_event<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        _module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+    ) -> Result<(), Self::Error> {
+        match op {
+            Event::Internal(InternalEvent::FunctionBegin(_)) => {
+                self.current_block = 0;
+            }
+            Event::Wasm(&ref op) | Event::WasmOwned(ref op) => {
+                self.current_block += 1;
+                match *op {
+                    Operator::Loop { .. }
+                    | Operator::Block { .. }
+                    | Operator::End
+                    | Operator::If { .. }
+                    | Operator::Else
+                    | Operator::Unreachable
+                    | Operator::Br { .. }
+                    | Operator::BrTable { .. }
+                    | Operator::BrIf { .. }
+                    | Operator::Call { .. }
+                    | Operator::CallIndirect { .. }
+                    | Operator::Return => {
+                        sink.push(Event::Internal(InternalEvent::AddInternal(
+                            POINTS_USED_FIELD.index() as _,
+                            self.current_block,
+                        )));
+                        self.current_block = 0;
+                    }
+                    _ => {}
+                }
+                match *op {
+                    Operator::Br { .. }
+                    | Operator::BrTable { .. }
+                    | Operator::BrIf { .. }
+                    | Operator::Call { .. }
+                    | Operator::CallIndirect { .. } => {
+                        sink.push(Event::Internal(InternalEvent::BreakpointIfInternalAtLeast(
+                            POINTS_USED_FIELD.index() as _,
+                            self.limit,
+                            Box::new(refuel),
+                        )));
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        sink.push(op);
+        Ok(())
+    }
+
+    fn finalize_module(&mut self, module_info: &mut ModuleInfo) {
+        POINTS_LIMIT.record(module_info, self.limit);
+    }
+}
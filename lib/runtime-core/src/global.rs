@@ -8,7 +8,10 @@ use crate::{
 };
 use std::{
     fmt,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
 };
 
 /// Container with a descriptor and a reference to a global value.
@@ -75,22 +78,37 @@ impl Global {
 
     /// Set the value help by this global.
     ///
+    /// The value can be set while an instance importing this global
+    /// runs: it is stored atomically and the instance sees it on its
+    /// next read of the global.
+    ///
     /// This method will panic if the value is
     /// the wrong type.
     pub fn set(&self, value: Value) {
         if self.desc.mutable {
             if self.desc.ty == value.ty() {
-                let local_global = vm::LocalGlobal {
-                    data: match value {
-                        Value::I32(x) => x as u128,
-                        Value::I64(x) => x as u128,
-                        Value::F32(x) => x.to_bits() as u128,
-                        Value::F64(x) => x.to_bits() as u128,
-                        Value::V128(x) => x,
-                    },
-                };
                 let mut storage = self.storage.lock().unwrap();
-                *storage = local_global;
+                let storage: *mut vm::LocalGlobal = &mut *storage;
+
+                // Instances access the storage directly, so the lock
+                // doesn't synchronize with them.
+                unsafe {
+                    match value {
+                        Value::I32(x) => {
+                            (*(storage as *const AtomicU32)).store(x as u32, Ordering::SeqCst)
+                        }
+                        Value::I64(x) => {
+                            (*(storage as *const AtomicU64)).store(x as u64, Ordering::SeqCst)
+                        }
+                        Value::F32(x) => {
+                            (*(storage as *const AtomicU32)).store(x.to_bits(), Ordering::SeqCst)
+                        }
+                        Value::F64(x) => {
+                            (*(storage as *const AtomicU64)).store(x.to_bits(), Ordering::SeqCst)
+                        }
+                        Value::V128(x) => (*storage).data = x,
+                    }
+                }
             } else {
                 panic!("Wrong type for setting this global")
             }
@@ -101,15 +119,25 @@ impl Global {
 
     /// Get the value held by this global.
     pub fn get(&self) -> Value {
-        let storage = self.storage.lock().unwrap();
-        let data = storage.data;
+        let mut storage = self.storage.lock().unwrap();
+        let storage: *mut vm::LocalGlobal = &mut *storage;
 
-        match self.desc.ty {
-            Type::I32 => Value::I32(data as i32),
-            Type::I64 => Value::I64(data as i64),
-            Type::F32 => Value::F32(f32::from_bits(data as u32)),
-            Type::F64 => Value::F64(f64::from_bits(data as u64)),
-            Type::V128 => Value::V128(data),
+        unsafe {
+            match self.desc.ty {
+                Type::I32 => {
+                    Value::I32((*(storage as *const AtomicU32)).load(Ordering::SeqCst) as i32)
+                }
+                Type::I64 => {
+                    Value::I64((*(storage as *const AtomicU64)).load(Ordering::SeqCst) as i64)
+                }
+                Type::F32 => Value::F32(f32::from_bits(
+                    (*(storage as *const AtomicU32)).load(Ordering::SeqCst),
+                )),
+                Type::F64 => Value::F64(f64::from_bits(
+                    (*(storage as *const AtomicU64)).load(Ordering::SeqCst),
+                )),
+                Type::V128 => Value::V128((*storage).data),
+            }
         }
     }
 
@@ -2,6 +2,7 @@
 //! manipulate and access a wasm module's imports including memories, tables, globals, and
 //! functions.
 use crate::export::Export;
+use crate::memory::MemoryAllocator;
 use std::collections::VecDeque;
 use std::collections::{hash_map::Entry, HashMap};
 use std::{
@@ -61,6 +62,7 @@ pub struct ImportObject {
     /// Allow missing functions to be generated and instantiation to continue when required
     /// functions are not provided.
     pub allow_missing_functions: bool,
+    pub(crate) memory_allocator: Option<Arc<dyn MemoryAllocator>>,
 }
 
 impl ImportObject {
@@ -70,6 +72,7 @@ impl ImportObject {
             map: Arc::new(Mutex::new(HashMap::new())),
             state_creator: None,
             allow_missing_functions: false,
+            memory_allocator: None,
         }
     }
 
@@ -82,15 +85,27 @@ impl ImportObject {
             map: Arc::new(Mutex::new(HashMap::new())),
             state_creator: Some(Arc::new(state_creator)),
             allow_missing_functions: false,
+            memory_allocator: None,
         }
     }
 
+    /// Sets the allocator providing the storage of the memories defined
+    /// by the instances created with this `ImportObject`.
+    pub fn set_memory_allocator(&mut self, allocator: Arc<dyn MemoryAllocator>) {
+        self.memory_allocator = Some(allocator);
+    }
+
     pub(crate) fn call_state_creator(&self) -> Option<(*mut c_void, fn(*mut c_void))> {
         self.state_creator.as_ref().map(|state_gen| state_gen())
     }
 
     /// Register anything that implements `LikeNamespace` as a namespace.
     ///
+    /// Registering an `Instance` links its exports, by name, to the
+    /// imports of the instances created with this `ImportObject`.
+    /// Their types are checked at instantiation. Register an
+    /// `Arc<Mutex<Instance>>` to keep using the instance afterwards.
+    ///
     /// # Usage:
     /// ```
     /// # use wasmer_runtime_core::Instance;
@@ -164,6 +179,7 @@ impl ImportObject {
             map: Arc::clone(&self.map),
             state_creator: self.state_creator.clone(),
             allow_missing_functions: false,
+            memory_allocator: self.memory_allocator.clone(),
         }
     }
 
@@ -3,22 +3,36 @@
 use crate::{
     backend::RunnableModule,
     backing::{ImportBacking, LocalBacking},
-    error::{CallError, CallResult, ResolveError, ResolveResult, Result, RuntimeError},
+    backtrace, call_depth,
+    error::{
+        CallError, CallResult, ResolveError, ResolveResult, RestoreError, Result, RuntimeError,
+    },
     export::{Context, Export, ExportIter, FuncPointer},
+    fuel::{self, FuelHandler},
     global::Global,
     import::{ImportObject, LikeNamespace},
+    limits::ResourceLimiter,
     loader::Loader,
-    memory::Memory,
+    memory::{self, Memory},
     module::{ExportIndex, Module, ModuleInfo, ModuleInner},
     sig_registry::SigRegistry,
+    state::{ExecutionStateImage, InstanceImage},
     structures::TypedIndex,
-    table::Table,
+    table::{self, Table},
     typed_func::{Func, Wasm, WasmTrapInfo, WasmTypeList},
     types::{FuncIndex, FuncSig, GlobalIndex, LocalOrImport, MemoryIndex, TableIndex, Type, Value},
+    units::{Bytes, Pages},
     vm::{self, InternalField},
 };
+#[cfg(all(unix, target_arch = "x86_64"))]
+use crate::{
+    error::InterruptError,
+    fault::{InterruptHandle, InterruptPage},
+};
+use parking_lot::ReentrantMutex;
 use smallvec::{smallvec, SmallVec};
 use std::{
+    collections::HashMap,
     mem,
     pin::Pin,
     ptr::NonNull,
@@ -35,13 +49,45 @@ pub(crate) struct InstanceInner {
 // manually implemented because InstanceInner contains a raw pointer to Ctx
 unsafe impl Send for InstanceInner {}
 
+impl InstanceInner {
+    /// The local and imported tables of this instance.
+    fn tables(&self) -> impl Iterator<Item = &Table> {
+        let local_tables = self.backing.tables.iter().map(|(_, table)| table);
+        let imported_tables = self.import_backing.tables.iter().map(|(_, table)| table);
+        local_tables.chain(imported_tables)
+    }
+}
+
 impl Drop for InstanceInner {
     fn drop(&mut self) {
+        // The host functions set in the tables are no longer called
+        // with the context of this instance.
+        if let Some(vmctx) = NonNull::new(self.vmctx) {
+            for table in self.tables() {
+                table.detach(vmctx);
+            }
+        }
+
         // Drop the vmctx.
         unsafe { Box::from_raw(self.vmctx) };
     }
 }
 
+/// What the instantiation of a module initializes, on top of the memories, tables and globals
+/// the instance defines itself.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Instantiation {
+    /// Writes the data and element segments into the imported memories and tables too, and
+    /// calls the start function.
+    Full,
+    /// Like `Full`, but leaves the start function to `Instance::run_start`.
+    WithoutStart,
+    /// Leaves the imported memories as they are, and never calls the start function, for
+    /// another thread of an instance sharing them. The element segments are still written,
+    /// as they refer to the functions of the new instance.
+    Thread,
+}
+
 /// An instantiated WebAssembly module.
 ///
 /// An `Instance` represents a WebAssembly module that
@@ -55,10 +101,17 @@ pub struct Instance {
     inner: Pin<Box<InstanceInner>>,
     #[allow(dead_code)]
     import_object: ImportObject,
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    interrupt_page: Option<Arc<InterruptPage>>,
+    start_pending: bool,
 }
 
 impl Instance {
-    pub(crate) fn new(module: Arc<ModuleInner>, imports: &ImportObject) -> Result<Instance> {
+    pub(crate) fn new(
+        module: Arc<ModuleInner>,
+        imports: &ImportObject,
+        instantiation: Instantiation,
+    ) -> Result<Instance> {
         // We need the backing and import_backing to create a vm::Ctx, but we need
         // a vm::Ctx to create a backing and an import_backing. The solution is to create an
         // uninitialized vm::Ctx and then initialize it in-place.
@@ -66,7 +119,17 @@ impl Instance {
             Box::new(mem::MaybeUninit::<vm::Ctx>::zeroed());
 
         let import_backing = ImportBacking::new(&module, &imports, vmctx.as_mut_ptr())?;
-        let backing = LocalBacking::new(&module, &import_backing, vmctx.as_mut_ptr())?;
+        let memory_allocator = imports
+            .memory_allocator
+            .clone()
+            .unwrap_or_else(memory::default_allocator);
+        let backing = LocalBacking::new(
+            &module,
+            &import_backing,
+            vmctx.as_mut_ptr(),
+            memory_allocator,
+            instantiation != Instantiation::Thread,
+        )?;
 
         let mut inner = Box::pin(InstanceInner {
             backing,
@@ -89,59 +152,94 @@ impl Instance {
         };
         Box::leak(vmctx);
 
-        let instance = Instance {
+        // The host functions set in the tables of this instance are
+        // called with its context.
+        if let Some(vmctx) = NonNull::new(inner.vmctx) {
+            for table in inner.tables() {
+                table.attach(vmctx);
+            }
+        }
+
+        let mut instance = Instance {
             module,
             inner,
             import_object: imports.clone_ref(),
+            #[cfg(all(unix, target_arch = "x86_64"))]
+            interrupt_page: None,
+            start_pending: instantiation != Instantiation::Thread,
         };
 
-        if let Some(start_index) = instance.module.info.start_func {
-            // We know that the start function takes no arguments and returns no values.
-            // Therefore, we can call it without doing any signature checking, etc.
+        if instantiation == Instantiation::Full {
+            instance.call_start()?;
+        }
 
-            let func_ptr = match start_index.local_or_import(&instance.module.info) {
-                LocalOrImport::Local(local_func_index) => instance
-                    .module
-                    .runnable_module
-                    .get_func(&instance.module.info, local_func_index)
-                    .unwrap(),
-                LocalOrImport::Import(import_func_index) => NonNull::new(
-                    instance.inner.import_backing.vm_functions[import_func_index].func as *mut _,
-                )
-                .unwrap(),
-            };
+        Ok(instance)
+    }
 
-            let ctx_ptr = match start_index.local_or_import(&instance.module.info) {
-                LocalOrImport::Local(_) => instance.inner.vmctx,
-                LocalOrImport::Import(imported_func_index) => unsafe {
-                    instance.inner.import_backing.vm_functions[imported_func_index]
-                        .func_ctx
-                        .as_ref()
-                }
-                .vmctx
-                .as_ptr(),
-            };
+    /// Calls the start function of the module, if it has one and it hasn't been called yet.
+    ///
+    /// The start function is called by [`Module::instantiate`], unless the instance was created
+    /// with [`Module::instantiate_without_start`]. In that case, limits such as the fuel or an
+    /// interrupt handle can be set on the instance before calling this.
+    ///
+    /// [`Module::instantiate`]: ../module/struct.Module.html#method.instantiate
+    /// [`Module::instantiate_without_start`]: ../module/struct.Module.html#method.instantiate_without_start
+    pub fn run_start(&mut self) -> CallResult<()> {
+        Ok(self.call_start()?)
+    }
 
-            let sig_index = *instance
-                .module
-                .info
-                .func_assoc
-                .get(start_index)
-                .expect("broken invariant, incorrect func index");
+    fn call_start(&mut self) -> std::result::Result<(), RuntimeError> {
+        if !mem::replace(&mut self.start_pending, false) {
+            return Ok(());
+        }
+        let start_index = match self.module.info.start_func {
+            Some(start_index) => start_index,
+            None => return Ok(()),
+        };
+
+        // We know that the start function takes no arguments and returns no values.
+        // Therefore, we can call it without doing any signature checking, etc.
 
-            let wasm_trampoline = instance
+        let func_ptr = match start_index.local_or_import(&self.module.info) {
+            LocalOrImport::Local(local_func_index) => self
                 .module
                 .runnable_module
-                .get_trampoline(&instance.module.info, sig_index)
-                .expect("wasm trampoline");
+                .get_func(&self.module.info, local_func_index)
+                .unwrap(),
+            LocalOrImport::Import(import_func_index) => NonNull::new(
+                self.inner.import_backing.vm_functions[import_func_index].func as *mut _,
+            )
+            .unwrap(),
+        };
 
-            let start_func: Func<(), (), Wasm> =
-                unsafe { Func::from_raw_parts(wasm_trampoline, func_ptr, None, ctx_ptr) };
+        let ctx_ptr = match start_index.local_or_import(&self.module.info) {
+            LocalOrImport::Local(_) => self.inner.vmctx,
+            LocalOrImport::Import(imported_func_index) => unsafe {
+                self.inner.import_backing.vm_functions[imported_func_index]
+                    .func_ctx
+                    .as_ref()
+            }
+            .vmctx
+            .as_ptr(),
+        };
 
-            start_func.call()?;
-        }
+        let sig_index = *self
+            .module
+            .info
+            .func_assoc
+            .get(start_index)
+            .expect("broken invariant, incorrect func index");
 
-        Ok(instance)
+        let wasm_trampoline = self
+            .module
+            .runnable_module
+            .get_trampoline(&self.module.info, sig_index)
+            .expect("wasm trampoline");
+
+        let start_func: Func<(), (), Wasm> =
+            unsafe { Func::from_raw_parts(wasm_trampoline, func_ptr, None, ctx_ptr) };
+
+        start_func.call()
     }
 
     /// Load an `Instance` using the given loader.
@@ -325,7 +423,8 @@ impl Instance {
     ///
     /// # Note:
     /// This returns `CallResult<Vec<Value>>` in order to support
-    /// the future multi-value returns WebAssembly feature.
+    /// functions returning multiple values, with the multi-value
+    /// feature, which are returned in order.
     ///
     /// # Usage:
     /// ```
@@ -409,9 +508,322 @@ impl Instance {
     pub fn set_internal(&mut self, field: &InternalField, value: u64) {
         self.inner.backing.internals.0[field.index()] = value;
     }
+
+    /// Sets the maximum depth of the calls made by this instance, past which it traps with
+    /// [`CallStackExhausted`].
+    ///
+    /// Calls are only counted in code compiled with the [`CallDepth`] middleware, and the depth
+    /// can't exceed the one it was given. This should be called between calls.
+    ///
+    /// [`CallStackExhausted`]: ../call_depth/struct.CallStackExhausted.html
+    /// [`CallDepth`]: ../call_depth/struct.CallDepth.html
+    pub fn set_max_call_depth(&mut self, depth: u64) {
+        call_depth::set_max_call_depth(self.context_mut(), depth);
+    }
+
+    /// Returns the fuel left in this instance, or `None` if its module isn't compiled with the
+    /// [`Metering`] middleware.
+    ///
+    /// See the [`fuel`] module for how fuel is burned.
+    ///
+    /// [`Metering`]: ../fuel/struct.Metering.html
+    /// [`fuel`]: ../fuel/index.html
+    pub fn fuel_remaining(&self) -> Option<u64> {
+        fuel::fuel_remaining(self.context())
+    }
+
+    /// Sets the fuel left in this instance. This does nothing if its module isn't compiled with
+    /// the [`Metering`] middleware.
+    ///
+    /// This can be called between calls, or by the fuel handler, to resume an instance that
+    /// ran out of fuel.
+    ///
+    /// [`Metering`]: ../fuel/struct.Metering.html
+    pub fn set_fuel(&mut self, fuel: u64) {
+        fuel::set_fuel(self.context_mut(), fuel);
+    }
+
+    /// Sets the handler called when this instance runs out of fuel, or removes it with `None`.
+    ///
+    /// Without a handler, an instance running out of fuel traps with [`OutOfFuel`].
+    ///
+    /// [`OutOfFuel`]: ../fuel/struct.OutOfFuel.html
+    pub fn set_fuel_handler(&mut self, handler: Option<FuelHandler>) {
+        self.inner.backing.fuel.handler = handler;
+    }
+
+    /// Sets the policy consulted before the local memories and tables of this instance grow,
+    /// or removes it with `None`.
+    ///
+    /// The limiter replaces the grow observers of these memories and tables. Imported
+    /// memories and tables are left alone, since they may be shared with other instances.
+    pub fn set_resource_limiter(&mut self, limiter: Option<Arc<dyn ResourceLimiter>>) {
+        for (_, memory) in self.inner.backing.memories.iter() {
+            let observer = limiter.clone().map(|limiter| -> memory::GrowObserver {
+                Arc::new(move |current: Pages, desired: Pages| {
+                    limiter.memory_growing(current, desired)
+                })
+            });
+            memory.set_grow_observer(observer);
+        }
+        for (_, table) in self.inner.backing.tables.iter() {
+            let observer = limiter.clone().map(|limiter| -> table::GrowObserver {
+                Arc::new(move |current: u32, desired: u32| limiter.table_growing(current, desired))
+            });
+            table.set_grow_observer(observer);
+        }
+    }
+
+    /// Returns a handle to interrupt this instance from another thread.
+    ///
+    /// Running code traps with `RuntimeError::Interrupted` at its next safepoint, which
+    /// singlepass emits at function entries and loop heads, and LLVM only with `track_state`
+    /// enabled. An interrupt requested while the instance is idle makes its next call trap.
+    ///
+    /// This fails with `InterruptError::NoSafepoints` for code without safepoints, e.g. code
+    /// compiled by Cranelift, since it would never be interrupted.
+    ///
+    /// Once this is called, the instance no longer observes the process-wide interrupt
+    /// raised by `fault::set_wasm_interrupt`, so it shouldn't be run with tiering.
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    pub fn interrupt_handle(&mut self) -> std::result::Result<InterruptHandle, InterruptError> {
+        if let Some(page) = &self.interrupt_page {
+            return Ok(InterruptHandle::new(page.clone()));
+        }
+
+        // Only code tracking its state has a state map, and safepoints.
+        if self.module.runnable_module.get_module_state_map().is_none() {
+            return Err(InterruptError::NoSafepoints);
+        }
+
+        let page = Arc::new(InterruptPage::new().ok_or(InterruptError::TooManyHandles)?);
+        unsafe {
+            (*self.inner.vmctx).internal.interrupt_signal_mem = page.as_ptr();
+        }
+        self.interrupt_page = Some(page.clone());
+        Ok(InterruptHandle::new(page))
+    }
+
+    /// Captures the memory, the local globals and the local tables of this instance.
+    ///
+    /// The image can be applied with [`restore`] to this instance, or to another instance
+    /// of the same module, and serialized with `InstanceImage::to_bytes`. Only memory 0 is
+    /// captured. Table elements that aren't functions of this instance, e.g. host functions
+    /// set with `Table::set`, are captured as null elements.
+    ///
+    /// [`restore`]: struct.Instance.html#method.restore
+    pub fn snapshot(&self) -> InstanceImage {
+        let module = &self.module;
+        let info = &module.info;
+
+        let memory = if info.memories.len() + info.imported_memories.len() > 0 {
+            let local_memory = unsafe { &*self.context().memory(0).vm_local_memory() };
+            Some(
+                unsafe { std::slice::from_raw_parts(local_memory.base, local_memory.bound) }
+                    .to_vec(),
+            )
+        } else {
+            None
+        };
+
+        let globals = self
+            .inner
+            .backing
+            .globals
+            .iter()
+            .map(|(_, global)| global.get().to_u128())
+            .collect();
+
+        let func_indices: HashMap<(*const vm::Func, *mut vm::Ctx), FuncIndex> = info
+            .func_assoc
+            .iter()
+            .map(|(func_index, _)| (self.inner.table_func(module, func_index), func_index))
+            .collect();
+        let tables = self
+            .inner
+            .backing
+            .tables
+            .iter()
+            .map(|(_, table)| {
+                table.anyfunc_direct_access_mut(|elements| {
+                    elements
+                        .iter()
+                        .map(|element| {
+                            func_indices
+                                .get(&(element.func, element.ctx))
+                                .map(|func_index| func_index.index() as u32)
+                        })
+                        .collect()
+                })
+            })
+            .collect();
+
+        InstanceImage {
+            memory,
+            globals,
+            tables,
+            execution_state: ExecutionStateImage { frames: vec![] },
+        }
+    }
+
+    /// Restores the memory, the local globals and the local tables of this instance from an
+    /// image taken with [`snapshot`].
+    ///
+    /// Memories and tables can't shrink: if they have grown since the snapshot, the part
+    /// past the image is zeroed, respectively nulled.
+    ///
+    /// [`snapshot`]: struct.Instance.html#method.snapshot
+    pub fn restore(&mut self, image: &InstanceImage) -> std::result::Result<(), RestoreError> {
+        let module = Arc::clone(&self.module);
+        let info = &module.info;
+
+        if image.globals.len() != info.globals.len() || image.tables.len() != info.tables.len() {
+            return Err(RestoreError::IncompatibleImage(
+                "the number of globals or tables differs".to_string(),
+            ));
+        }
+
+        let func_count = info.func_assoc.len();
+        if image
+            .tables
+            .iter()
+            .flatten()
+            .any(|element| element.map_or(false, |index| index as usize >= func_count))
+        {
+            return Err(RestoreError::IncompatibleImage(
+                "a table element refers to an unknown function".to_string(),
+            ));
+        }
+
+        if let Some(ref image_memory) = image.memory {
+            if info.memories.len() + info.imported_memories.len() == 0 {
+                return Err(RestoreError::IncompatibleImage(
+                    "the instance has no memory".to_string(),
+                ));
+            }
+
+            let memory = self.context().memory(0).clone();
+            let size = memory.size().bytes().0;
+            if size < image_memory.len() {
+                memory.grow(Bytes(image_memory.len() - size).into())?;
+            }
+
+            let local_memory = unsafe { &*memory.vm_local_memory() };
+            let contents =
+                unsafe { std::slice::from_raw_parts_mut(local_memory.base, local_memory.bound) };
+            let (restored, grown) = contents.split_at_mut(image_memory.len());
+            restored.copy_from_slice(image_memory);
+            for byte in grown {
+                *byte = 0;
+            }
+
+            let ctx = self.context_mut();
+            ctx.internal.memory_base = local_memory.base;
+            ctx.internal.memory_bound = local_memory.bound;
+        }
+
+        for ((_, global), &value) in self.inner.backing.globals.iter_mut().zip(&image.globals) {
+            unsafe { (*global.vm_local_global()).data = value };
+        }
+
+        for ((_, table), image_table) in self.inner.backing.tables.iter().zip(&image.tables) {
+            let size = table.size() as usize;
+            if size < image_table.len() {
+                table.grow((image_table.len() - size) as u32)?;
+            }
+
+            let elements: Vec<vm::Anyfunc> = image_table
+                .iter()
+                .map(|element| match element {
+                    Some(func_index) => {
+                        let func_index = FuncIndex::new(*func_index as usize);
+                        let signature = SigRegistry
+                            .lookup_signature_ref(&info.signatures[info.func_assoc[func_index]]);
+                        let sig_id =
+                            vm::SigId(SigRegistry.lookup_sig_index(signature).index() as u32);
+                        let (func, ctx) = self.inner.table_func(&module, func_index);
+
+                        vm::Anyfunc { func, ctx, sig_id }
+                    }
+                    None => vm::Anyfunc::null(),
+                })
+                .collect();
+
+            table.anyfunc_direct_access_mut(|table_elements| {
+                let (restored, grown) = table_elements.split_at_mut(elements.len());
+                restored.copy_from_slice(&elements);
+                for element in grown {
+                    *element = vm::Anyfunc::null();
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// An `Instance` that can be shared between threads.
+///
+/// An instance has a single context, so the calls made from different threads are run one at a
+/// time. A call made from the thread already running one, e.g. by an import function calling
+/// back into the instance, is reentrant and runs immediately. Memory growth and table mutations
+/// happen inside the calls, so they are serialized with them. Running wasm in parallel still
+/// requires an instance per thread.
+pub struct ConcurrentInstance {
+    instance: ReentrantMutex<Instance>,
+}
+
+impl ConcurrentInstance {
+    /// Wraps `instance` to share it between threads.
+    pub fn new(instance: Instance) -> Self {
+        Self {
+            instance: ReentrantMutex::new(instance),
+        }
+    }
+
+    /// Calls the exported function `name`, waiting for the calls running on other threads to
+    /// finish first.
+    pub fn call(&self, name: &str, params: &[Value]) -> CallResult<Vec<Value>> {
+        self.instance.lock().call(name, params)
+    }
+
+    /// Runs `f` with the instance, waiting for the calls running on other threads to finish
+    /// first.
+    pub fn with<R>(&self, f: impl FnOnce(&Instance) -> R) -> R {
+        f(&self.instance.lock())
+    }
+
+    /// Returns the wrapped instance.
+    pub fn into_inner(self) -> Instance {
+        self.instance.into_inner()
+    }
 }
 
 impl InstanceInner {
+    /// Returns the function pointer and context that a table element
+    /// referring to `func_index` holds.
+    fn table_func(
+        &self,
+        module: &ModuleInner,
+        func_index: FuncIndex,
+    ) -> (*const vm::Func, *mut vm::Ctx) {
+        match func_index.local_or_import(&module.info) {
+            LocalOrImport::Local(local_func_index) => (
+                module
+                    .runnable_module
+                    .get_func(&module.info, local_func_index)
+                    .expect("broken invariant, func resolver not synced with module.exports")
+                    .as_ptr() as *const vm::Func,
+                self.vmctx,
+            ),
+            LocalOrImport::Import(imported_func_index) => {
+                let vm::ImportedFunc { func, func_ctx } =
+                    self.import_backing.vm_functions[imported_func_index];
+                (func, unsafe { func_ctx.as_ref() }.vmctx.as_ptr())
+            }
+        }
+    }
+
     pub(crate) fn get_export_from_index(
         &self,
         module: &ModuleInner,
@@ -420,6 +832,12 @@ impl InstanceInner {
         match export_index {
             ExportIndex::Func(func_index) => {
                 let (func, ctx, signature) = self.get_func_from_index(module, *func_index);
+                let env_owner = match func_index.local_or_import(&module.info) {
+                    LocalOrImport::Local(_) => None,
+                    LocalOrImport::Import(imported_func_index) => {
+                        self.import_backing.func_env_owners[imported_func_index].clone()
+                    }
+                };
 
                 Export::Function {
                     func,
@@ -429,6 +847,7 @@ impl InstanceInner {
                         ctx @ Context::ExternalWithEnv(_, _) => ctx,
                     },
                     signature,
+                    env_owner,
                 }
             }
             ExportIndex::Memory(memory_index) => {
@@ -523,7 +942,7 @@ impl LikeNamespace for Instance {
     }
 
     fn get_exports(&self) -> Vec<(String, Export)> {
-        unimplemented!("Use the exports method instead");
+        self.exports().collect()
     }
 
     fn maybe_insert(&mut self, _name: &str, _export: Export) -> Option<()> {
@@ -540,7 +959,7 @@ impl LikeNamespace for Rc<Instance> {
     }
 
     fn get_exports(&self) -> Vec<(String, Export)> {
-        unimplemented!("Use the exports method instead");
+        self.exports().collect()
     }
 
     fn maybe_insert(&mut self, _name: &str, _export: Export) -> Option<()> {
@@ -561,7 +980,7 @@ impl LikeNamespace for Arc<Mutex<Instance>> {
     }
 
     fn get_exports(&self) -> Vec<(String, Export)> {
-        unimplemented!("Use the exports method instead");
+        self.lock().unwrap().exports().collect()
     }
 
     fn maybe_insert(&mut self, _name: &str, _export: Export) -> Option<()> {
@@ -676,25 +1095,32 @@ pub(crate) fn call_func_with_index_inner(
         let mut trap_info = WasmTrapInfo::Unknown;
         let mut user_error = None;
 
-        let success = invoke(
-            trampoline,
-            ctx_ptr,
-            func_ptr,
-            raw_args.as_ptr(),
-            result_space,
-            &mut trap_info,
-            &mut user_error,
-            invoke_env,
-        );
+        let success = call_depth::preserve(ctx_ptr, || {
+            backtrace::enter(ctx_ptr, || {
+                invoke(
+                    trampoline,
+                    ctx_ptr,
+                    func_ptr,
+                    raw_args.as_ptr(),
+                    result_space,
+                    &mut trap_info,
+                    &mut user_error,
+                    invoke_env,
+                )
+            })
+        });
 
         if success {
             Ok(())
         } else {
+            let backtrace = backtrace::take(&*(*ctx_ptr).module);
             if let Some(data) = user_error {
-                Err(RuntimeError::Error { data })
+                Err(RuntimeError::from_user_error(data, backtrace))
             } else {
                 Err(RuntimeError::Trap {
+                    code: trap_info,
                     msg: trap_info.to_string().into(),
+                    backtrace,
                 })
             }
         }
@@ -742,12 +1168,17 @@ pub(crate) fn call_func_with_index_inner(
 
             run_wasm(results.as_mut_ptr())?;
 
-            rets.extend(
-                results
-                    .iter()
-                    .zip(result_tys.iter())
-                    .map(|(&raw, &ty)| raw_to_value(raw, ty)),
-            );
+            // `V128` results take two slots in the result space.
+            let mut slots = results.iter();
+            for &ty in result_tys {
+                let lo = *slots.next().unwrap();
+                if ty == Type::V128 {
+                    let hi = *slots.next().unwrap();
+                    rets.push(Value::V128((lo as u128) | ((hi as u128) << 64)));
+                } else {
+                    rets.push(raw_to_value(lo, ty));
+                }
+            }
 
             Ok(())
         }
@@ -772,7 +1203,8 @@ impl<'a> DynFunc<'a> {
     ///
     /// # Note:
     /// This returns `CallResult<Vec<Value>>` in order to support
-    /// the future multi-value returns WebAssembly feature.
+    /// functions returning multiple values, with the multi-value
+    /// feature, which are returned in order.
     ///
     /// # Usage:
     /// ```
@@ -787,7 +1219,29 @@ impl<'a> DynFunc<'a> {
     /// ```
     pub fn call(&self, params: &[Value]) -> CallResult<Vec<Value>> {
         let mut results = Vec::new();
+        self.call_into(params, &mut results)?;
+        Ok(results)
+    }
 
+    /// Call an exported WebAssembly function like `call`, but write
+    /// the returned values to `results`, so that its allocation is
+    /// reused across calls.
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::Instance;
+    /// # use wasmer_runtime_core::error::CallResult;
+    /// # use wasmer_runtime_core::types::Value;
+    /// # fn call_foo(instance: &mut Instance) -> CallResult<()> {
+    /// let foo = instance.dyn_func("foo")?;
+    /// let mut results = Vec::new();
+    /// for i in 0..10 {
+    ///     foo.call_into(&[Value::I32(i)], &mut results)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_into(&self, params: &[Value], results: &mut Vec<Value>) -> CallResult<()> {
         call_func_with_index(
             &self.module.info,
             &*self.module.runnable_module,
@@ -795,10 +1249,8 @@ impl<'a> DynFunc<'a> {
             self.instance_inner.vmctx,
             self.func_index,
             params,
-            &mut results,
-        )?;
-
-        Ok(results)
+            results,
+        )
     }
 
     /// Gets the signature of this `Dynfunc`.
@@ -0,0 +1,61 @@
+//! The internal_limit module bounds a count kept in an internal field by middlewares, e.g. the
+//! points used by an instance or the depth of its calls.
+use crate::{
+    module::ModuleInfo,
+    vm::{Ctx, InternalField},
+};
+
+/// A count kept in an internal field, which the code compiled by a middleware checks against a
+/// constant limit. The middleware records the limit in `ModuleInfo::middleware_values`.
+///
+/// An instance is given a lower limit at run time by starting its count that much closer to the
+/// compiled one, so the count left before the limit is the compiled limit minus the field.
+pub(crate) struct InternalLimit {
+    pub(crate) field: &'static InternalField,
+    /// The name the compiled limit is recorded under.
+    pub(crate) name: &'static str,
+}
+
+impl InternalLimit {
+    /// Records the limit compiled into the code of the module of `info`.
+    pub(crate) fn record(&self, info: &mut ModuleInfo, limit: u64) {
+        info.middleware_values.insert(self.name.to_string(), limit);
+    }
+
+    /// Returns the limit compiled into the code of the module of `info`, or `None` if the code
+    /// doesn't check this limit.
+    pub(crate) fn compiled(&self, info: &ModuleInfo) -> Option<u64> {
+        info.middleware_values.get(self.name).cloned()
+    }
+
+    /// Sets the count left before the instance of `ctx` reaches its limit, up to the compiled
+    /// limit. This does nothing if its code doesn't check this limit.
+    pub(crate) fn set_remaining(&self, ctx: &mut Ctx, remaining: u64) {
+        if let Some(limit) = self.compiled(unsafe { &(*ctx.module).info }) {
+            ctx.set_internal(self.field, count(limit, remaining));
+        }
+    }
+}
+
+/// The count left before `limit` when the field holds `count`.
+pub(crate) fn remaining(limit: u64, count: u64) -> u64 {
+    limit.saturating_sub(count)
+}
+
+/// The count for `remaining` to be left before `limit`.
+fn count(limit: u64, remaining: u64) -> u64 {
+    limit - remaining.min(limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remaining_count() {
+        assert_eq!(remaining(100, 0), 100);
+        assert_eq!(remaining(100, count(100, 10)), 10);
+        assert_eq!(remaining(100, count(100, 10) + 15), 0);
+        assert_eq!(remaining(100, count(100, u64::max_value())), 100);
+    }
+}
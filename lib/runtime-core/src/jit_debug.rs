@@ -0,0 +1,145 @@
+//! The GDB JIT interface, which tells debuggers about the debug info of code
+//! generated at runtime.
+//!
+//! A debugger sets a breakpoint on `__jit_debug_register_code` and reads the
+//! list of in-memory object files from `__jit_debug_descriptor` whenever it is
+//! hit. Both LLDB and GDB support this protocol. See
+//! <https://sourceware.org/gdb/onlinedocs/gdb/JIT-Interface.html>.
+
+use std::ptr;
+use std::sync::Mutex;
+
+#[repr(u32)]
+enum JitAction {
+    NoAction = 0,
+    RegisterFn = 1,
+    UnregisterFn = 2,
+}
+
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+#[repr(C)]
+struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+static mut __jit_debug_descriptor: JitDescriptor = JitDescriptor {
+    version: 1,
+    action_flag: JitAction::NoAction as u32,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+/// The debugger puts a breakpoint in this function.
+#[no_mangle]
+#[inline(never)]
+extern "C" fn __jit_debug_register_code() {
+    // Keeps the function from being optimized away or merged with another one.
+    let x = 0;
+    unsafe {
+        ptr::read_volatile(&x);
+    }
+}
+
+lazy_static! {
+    /// The descriptor must not be modified by two threads at once.
+    static ref DESCRIPTOR_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// An object file registered with the debugger. The object is unregistered
+/// when this is dropped.
+pub struct JitDebugEntry {
+    entry: *mut JitCodeEntry,
+    // Keeps the object file alive while the debugger may read it.
+    _object: Box<[u8]>,
+}
+
+unsafe impl Send for JitDebugEntry {}
+unsafe impl Sync for JitDebugEntry {}
+
+/// Registers an in-memory object file, usually an ELF file with DWARF sections
+/// describing code that was just generated, with the debugger.
+pub fn register(object: Vec<u8>) -> JitDebugEntry {
+    let object = object.into_boxed_slice();
+    let entry = Box::into_raw(Box::new(JitCodeEntry {
+        next_entry: ptr::null_mut(),
+        prev_entry: ptr::null_mut(),
+        symfile_addr: object.as_ptr(),
+        symfile_size: object.len() as u64,
+    }));
+
+    let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+    unsafe {
+        let first = __jit_debug_descriptor.first_entry;
+        (*entry).next_entry = first;
+        if !first.is_null() {
+            (*first).prev_entry = entry;
+        }
+        __jit_debug_descriptor.first_entry = entry;
+        __jit_debug_descriptor.relevant_entry = entry;
+        __jit_debug_descriptor.action_flag = JitAction::RegisterFn as u32;
+        __jit_debug_register_code();
+        __jit_debug_descriptor.action_flag = JitAction::NoAction as u32;
+        __jit_debug_descriptor.relevant_entry = ptr::null_mut();
+    }
+
+    JitDebugEntry {
+        entry,
+        _object: object,
+    }
+}
+
+impl Drop for JitDebugEntry {
+    fn drop(&mut self) {
+        let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+        unsafe {
+            let entry = self.entry;
+            let prev = (*entry).prev_entry;
+            let next = (*entry).next_entry;
+            if prev.is_null() {
+                __jit_debug_descriptor.first_entry = next;
+            } else {
+                (*prev).next_entry = next;
+            }
+            if !next.is_null() {
+                (*next).prev_entry = prev;
+            }
+            __jit_debug_descriptor.relevant_entry = entry;
+            __jit_debug_descriptor.action_flag = JitAction::UnregisterFn as u32;
+            __jit_debug_register_code();
+            __jit_debug_descriptor.action_flag = JitAction::NoAction as u32;
+            __jit_debug_descriptor.relevant_entry = ptr::null_mut();
+
+            drop(Box::from_raw(entry));
+        }
+    }
+}
+
+/// Returns copies of the object files currently registered, most recently
+/// registered first, as a debugger would find them.
+pub fn registered_objects() -> Vec<Vec<u8>> {
+    let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+    let mut objects = vec![];
+    unsafe {
+        let mut entry = __jit_debug_descriptor.first_entry;
+        while !entry.is_null() {
+            objects.push(
+                std::slice::from_raw_parts((*entry).symfile_addr, (*entry).symfile_size as usize)
+                    .to_vec(),
+            );
+            entry = (*entry).next_entry;
+        }
+    }
+    objects
+}
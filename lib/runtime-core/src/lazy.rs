@@ -0,0 +1,325 @@
+//! The lazy module defers the generation of machine code for a module until it's used.
+//!
+//! The module is validated when the `LazyModule` is created, so invalid modules are still
+//! rejected up front; only the cost of the code generation is deferred. Modules that are
+//! loaded but never instantiated, e.g. plugins of which only a few are used, don't pay it.
+//!
+//! On Singlepass and Cranelift, the module is first compiled with a stub in place of each
+//! function. The first call to a function runs its stub, which compiles the body of the
+//! function, skipping the others, and patches the entry of the stub to jump to the compiled
+//! code, so functions that are never called are never compiled. LLVM compiles the whole module
+//! on first instantiation instead.
+//!
+//! Patching a function rewrites the code of the module, so the functions of a lazily compiled
+//! module must not be called for the first time while other threads run its code. On
+//! Singlepass, the code of a lazily compiled function can't have breakpoints, so middlewares
+//! setting them, such as `Metering`, make its first call fail. On Cranelift, traps in lazily
+//! compiled functions are reported as unknown traps.
+use crate::{
+    backend::{Compiler, CompilerConfig, RunnableModule},
+    codegen::{BreakpointInfo, Event, EventSink, FunctionMiddleware, InternalEvent},
+    compile_with_config,
+    error::{CompileError, CompileResult, Error, Result},
+    import::ImportObject,
+    instance::Instance,
+    module::{Module, ModuleInfo},
+    structures::TypedIndex,
+    types::LocalFuncIndex,
+    validate_and_report_errors_with_features,
+    vm::{Ctx, Func},
+    wasmparser::Operator,
+};
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt,
+    ptr::NonNull,
+    sync::{Arc, Mutex},
+};
+
+/// The feature reported as unsupported by the backends that can't compile functions one at a
+/// time.
+pub(crate) const LAZY_FUNCTIONS: &str = "lazy function compilation";
+
+/// Called with the local index of a function the first time its stub runs, to compile the
+/// function and patch its stub in the module of the context.
+pub type StubHandler =
+    Arc<dyn Fn(LocalFuncIndex, &mut Ctx) -> std::result::Result<(), Box<dyn Any>> + Send + Sync>;
+
+/// Replaces the bodies of the functions of a module, to compile them one at a time.
+#[derive(Clone)]
+pub enum LazyBodies {
+    /// Every function is a stub, which runs the handler and then calls the function again,
+    /// reaching the code the handler patched its entry to jump to.
+    Stubs(StubHandler),
+    /// Only the body of the function at this local index is compiled. The bodies of the others
+    /// are skipped by the parser, and they trap.
+    Only(LocalFuncIndex),
+}
+
+impl fmt::Debug for LazyBodies {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LazyBodies::Stubs(_) => write!(f, "Stubs"),
+            LazyBodies::Only(index) => f.debug_tuple("Only").field(index).finish(),
+        }
+    }
+}
+
+/// The middleware replacing the bodies of functions with stubs, pushed at the end of the chain
+/// when the compiler config has `LazyBodies::Stubs`.
+pub(crate) struct ReplaceBodies {
+    handler: StubHandler,
+    /// Whether the events of the current body are dropped.
+    replacing: bool,
+    block_depth: usize,
+}
+
+impl ReplaceBodies {
+    pub(crate) fn new(handler: StubHandler) -> ReplaceBodies {
+        ReplaceBodies {
+            handler,
+            replacing: false,
+            block_depth: 0,
+        }
+    }
+
+    fn push_stub<'a, 'b: 'a>(
+        &self,
+        local: LocalFuncIndex,
+        module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+    ) {
+        let handler = self.handler.clone();
+        sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
+            move |info: BreakpointInfo| match info.ctx {
+                Some(ctx) => handler(local, ctx),
+                None => Err(Box::new(CompileError::InternalError {
+                    msg: "no context to compile the function in".to_string(),
+                }) as Box<dyn Any>),
+            },
+        ))));
+        let func_index = local.convert_up(module_info);
+        let sig = &module_info.signatures[module_info.func_assoc[func_index]];
+        for local_index in 0..sig.params().len() as u32 {
+            sink.push(Event::WasmOwned(Operator::GetLocal { local_index }));
+        }
+        sink.push(Event::WasmOwned(Operator::Call {
+            function_index: func_index.index() as u32,
+        }));
+    }
+}
+
+impl FunctionMiddleware for ReplaceBodies {
+    type Error = String;
+
+    fn feed_event<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+    ) -> std::result::Result<(), Self::Error> {
+        match op {
+            Event::Internal(InternalEvent::FunctionBegin(id)) => {
+                sink.push(op);
+                self.block_depth = 0;
+                self.replacing = true;
+                self.push_stub(LocalFuncIndex::new(id as usize), module_info, sink);
+            }
+            Event::Internal(InternalEvent::FunctionEnd) => {
+                self.replacing = false;
+                sink.push(op);
+            }
+            _ if !self.replacing => sink.push(op),
+            // The events of a replaced body are dropped, up to its end.
+            Event::Wasm(&ref operator) | Event::WasmOwned(ref operator) => match *operator {
+                Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                    self.block_depth += 1;
+                }
+                Operator::End if self.block_depth == 0 => {
+                    sink.push(Event::WasmOwned(Operator::End));
+                }
+                Operator::End => {
+                    self.block_depth -= 1;
+                }
+                _ => {}
+            },
+            Event::Internal(_) => {}
+        }
+        Ok(())
+    }
+}
+
+enum LazyState {
+    Pending,
+    /// Compiled with stubs, replaced by functions compiled one at a time.
+    Stubs(Module),
+    /// Compiled as a whole.
+    Compiled(Module),
+    Failed(CompileError),
+}
+
+/// What the stubs of a module need to compile its functions, on the threads calling them.
+struct Functions<C: Compiler> {
+    wasm: Vec<u8>,
+    compiler: C,
+    compiler_config: CompilerConfig,
+    /// The module compiled for each called function, kept alive for its code.
+    compiled: Mutex<HashMap<LocalFuncIndex, Module>>,
+}
+
+impl<C: Compiler> Functions<C> {
+    /// Compiles the module with `lazy_bodies`. This is only called while `compiled` is locked.
+    fn compile_with(&self, lazy_bodies: Option<LazyBodies>) -> CompileResult<Module> {
+        compile_with_config(
+            &self.wasm,
+            &self.compiler,
+            CompilerConfig {
+                lazy_bodies,
+                ..self.compiler_config.clone()
+            },
+        )
+    }
+
+    /// Compiles the body of the function at `local`, unless it was already compiled, and
+    /// patches its stub in the module of `ctx` to jump to its code.
+    fn compile(
+        &self,
+        local: LocalFuncIndex,
+        ctx: &mut Ctx,
+    ) -> std::result::Result<(), Box<dyn Any>> {
+        let module = unsafe { &*ctx.module };
+        let mut compiled = self.compiled.lock().unwrap();
+        if !compiled.contains_key(&local) {
+            let function = self
+                .compile_with(Some(LazyBodies::Only(local)))
+                .map_err(|e| Box::new(e) as Box<dyn Any>)?;
+            let runnable = &function.inner.runnable_module;
+            // The breakpoints of the module running the function are the only ones looked up.
+            if runnable.get_breakpoints().map_or(false, |b| !b.is_empty()) {
+                return Err(Box::new(CompileError::UnsupportedFeature {
+                    feature: "breakpoints in lazily compiled functions".to_string(),
+                }));
+            }
+            // The function calls the other functions, which trap in its module, through the
+            // module of `ctx`.
+            let count = module.info.func_assoc.len() - module.info.imported_functions.len();
+            for other in (0..count).map(LocalFuncIndex::new) {
+                if other != local {
+                    let target = module
+                        .runnable_module
+                        .get_func(&module.info, other)
+                        .unwrap();
+                    unsafe { patch(&**runnable, other, target)? };
+                }
+            }
+            compiled.insert(local, function);
+        }
+
+        let target = compiled[&local]
+            .inner
+            .runnable_module
+            .get_func(&module.info, local)
+            .unwrap();
+        unsafe { patch(&*module.runnable_module, local, target) }
+    }
+}
+
+/// Patches the function at `local` in `runnable` to jump to `target`.
+unsafe fn patch(
+    runnable: &dyn RunnableModule,
+    local: LocalFuncIndex,
+    target: NonNull<Func>,
+) -> std::result::Result<(), Box<dyn Any>> {
+    if runnable.patch_local_function(local.index(), target.as_ptr() as usize) {
+        Ok(())
+    } else {
+        Err(Box::new(CompileError::InternalError {
+            msg: format!("cannot patch the function at local index {}", local.index()),
+        }))
+    }
+}
+
+/// A validated module whose machine code is generated on first use.
+pub struct LazyModule<C: Compiler> {
+    functions: Arc<Functions<C>>,
+    state: Mutex<LazyState>,
+}
+
+impl<C: Compiler + Send + Sync + 'static> LazyModule<C> {
+    /// Validates `wasm` with the features of `compiler_config`, and keeps it to be compiled
+    /// with `compiler` on first use.
+    pub fn new(
+        wasm: &[u8],
+        compiler: C,
+        compiler_config: CompilerConfig,
+    ) -> CompileResult<LazyModule<C>> {
+        validate_and_report_errors_with_features(wasm, compiler_config.features.clone())
+            .map_err(|msg| CompileError::ValidationError { msg })?;
+        Ok(LazyModule {
+            functions: Arc::new(Functions {
+                wasm: wasm.to_vec(),
+                compiler,
+                compiler_config,
+                compiled: Mutex::new(HashMap::new()),
+            }),
+            state: Mutex::new(LazyState::Pending),
+        })
+    }
+
+    /// Returns whether the module has been compiled, with stubs in place of its functions if
+    /// they are compiled one at a time.
+    pub fn is_compiled(&self) -> bool {
+        match *self.state.lock().unwrap() {
+            LazyState::Stubs(_) | LazyState::Compiled(_) => true,
+            LazyState::Pending | LazyState::Failed(_) => false,
+        }
+    }
+
+    /// Returns whether the machine code of the function at `index` has been generated.
+    pub fn is_function_compiled(&self, index: LocalFuncIndex) -> bool {
+        match *self.state.lock().unwrap() {
+            LazyState::Stubs(_) => self.functions.compiled.lock().unwrap().contains_key(&index),
+            LazyState::Compiled(_) => true,
+            LazyState::Pending | LazyState::Failed(_) => false,
+        }
+    }
+
+    /// Returns the compiled module, compiling it on the first call.
+    ///
+    /// A failed compilation isn't retried: its error is returned again by later calls.
+    pub fn module(&self) -> CompileResult<Module> {
+        let mut state = self.state.lock().unwrap();
+        match &*state {
+            LazyState::Stubs(module) | LazyState::Compiled(module) => return Ok(module.clone()),
+            LazyState::Failed(error) => return Err(error.clone()),
+            LazyState::Pending => {}
+        }
+
+        let functions = self.functions.clone();
+        let handler: StubHandler = Arc::new(move |local, ctx| functions.compile(local, ctx));
+        let _compiling = self.functions.compiled.lock().unwrap();
+        let (compiled, stubs) = match self
+            .functions
+            .compile_with(Some(LazyBodies::Stubs(handler)))
+        {
+            Err(CompileError::UnsupportedFeature { ref feature }) if feature == LAZY_FUNCTIONS => {
+                (self.functions.compile_with(None), false)
+            }
+            compiled => (compiled, true),
+        };
+        *state = match &compiled {
+            Ok(module) if stubs => LazyState::Stubs(module.clone()),
+            Ok(module) => LazyState::Compiled(module.clone()),
+            Err(error) => LazyState::Failed(error.clone()),
+        };
+        compiled
+    }
+
+    /// Instantiates the module, compiling it first if it's the first use.
+    pub fn instantiate(&self, import_object: &ImportObject) -> Result<Instance> {
+        self.module()
+            .map_err(Error::CompileError)?
+            .instantiate(import_object)
+    }
+}
@@ -23,70 +23,135 @@
     unreachable_patterns
 )]
 #![cfg_attr(nightly, feature(unwind_attributes))]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![doc(html_favicon_url = "https://wasmer.io/static/icons/favicon.ico")]
 #![doc(html_logo_url = "https://avatars3.githubusercontent.com/u/44205449?s=200&v=4")]
 
+// Without the `std` feature, only the module structures and units are built, on `core` and
+// `alloc`, so that they can be shared with targets without `std`.
+extern crate alloc;
+
 #[macro_use]
 extern crate serde_derive;
 
+#[cfg(feature = "std")]
 #[allow(unused_imports)]
 #[macro_use]
 extern crate lazy_static;
 
+#[cfg(feature = "std")]
 #[macro_use]
 mod macros;
+#[cfg(feature = "std")]
+pub mod async_import;
+#[cfg(feature = "std")]
 #[doc(hidden)]
 pub mod backend;
+#[cfg(feature = "std")]
 mod backing;
+#[cfg(feature = "std")]
+pub mod backtrace;
 
+#[cfg(feature = "std")]
 pub mod cache;
+#[cfg(feature = "std")]
+pub mod call_depth;
+#[cfg(feature = "std")]
 pub mod codegen;
+#[cfg(feature = "std")]
+pub mod deterministic;
+#[cfg(feature = "std")]
 pub mod error;
+#[cfg(feature = "std")]
 pub mod export;
+#[cfg(feature = "std")]
+pub mod fuel;
+#[cfg(feature = "std")]
 pub mod global;
+#[cfg(feature = "std")]
 pub mod import;
+#[cfg(feature = "std")]
 pub mod instance;
+#[cfg(feature = "std")]
+mod internal_limit;
+#[cfg(feature = "std")]
+pub mod jit_debug;
+#[cfg(feature = "std")]
+pub mod lazy;
+#[cfg(feature = "std")]
+pub mod limits;
+#[cfg(feature = "std")]
+pub mod linker;
+#[cfg(feature = "std")]
 pub mod loader;
+#[cfg(feature = "std")]
 pub mod memory;
+#[cfg(feature = "std")]
 pub mod module;
+#[cfg(feature = "std")]
 pub mod parse;
+#[cfg(feature = "std")]
 mod sig_registry;
 pub mod structures;
+#[cfg(feature = "std")]
 mod sys;
+#[cfg(feature = "std")]
 pub mod table;
-#[cfg(all(unix, target_arch = "x86_64"))]
+#[cfg(all(feature = "std", unix, target_arch = "aarch64"))]
+pub mod trampoline_aarch64;
+#[cfg(all(feature = "std", unix, target_arch = "x86_64"))]
 pub mod trampoline_x64;
+#[cfg(feature = "std")]
 pub mod typed_func;
+#[cfg(feature = "std")]
 pub mod types;
 pub mod units;
+#[cfg(feature = "std")]
 pub mod vm;
+#[cfg(feature = "std")]
 #[doc(hidden)]
 pub mod vmcalls;
-#[cfg(all(unix, target_arch = "x86_64"))]
+#[cfg(all(feature = "std", unix, target_arch = "aarch64"))]
+pub use trampoline_aarch64 as trampoline;
+#[cfg(all(feature = "std", unix, target_arch = "x86_64"))]
 pub use trampoline_x64 as trampoline;
-#[cfg(all(unix, target_arch = "x86_64"))]
+#[cfg(all(feature = "std", unix, target_arch = "x86_64"))]
 pub mod fault;
+#[cfg(feature = "std")]
 pub mod state;
 #[cfg(feature = "managed")]
 pub mod tiering;
 
+#[cfg(feature = "std")]
 use self::error::CompileResult;
+#[cfg(feature = "std")]
 #[doc(inline)]
 pub use self::error::Result;
+#[cfg(feature = "std")]
 #[doc(inline)]
 pub use self::import::IsExport;
+#[cfg(feature = "std")]
 #[doc(inline)]
-pub use self::instance::{DynFunc, Instance};
+pub use self::instance::{ConcurrentInstance, DynFunc, Instance};
+#[cfg(feature = "std")]
 #[doc(inline)]
 pub use self::module::Module;
+#[cfg(feature = "std")]
 #[doc(inline)]
 pub use self::typed_func::Func;
+#[cfg(all(feature = "std", unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[doc(inline)]
+pub use self::typed_func::DynamicFunc;
+#[cfg(feature = "std")]
 use std::sync::Arc;
 
+#[cfg(feature = "std")]
 pub use wasmparser;
 
+#[cfg(feature = "std")]
 use self::cache::{Artifact, Error as CacheError};
 
+#[cfg(feature = "std")]
 pub mod prelude {
     //! The prelude module is a helper module used to bring commonly used runtime core imports into
     //! scope.
@@ -107,6 +172,7 @@ pub mod prelude {
 /// and must be used if you wish to use a different backend from the default.
 ///
 /// [`Module`]: struct.Module.html
+#[cfg(feature = "std")]
 pub fn compile_with(
     wasm: &[u8],
     compiler: &dyn backend::Compiler,
@@ -114,15 +180,12 @@ pub fn compile_with(
     let token = backend::Token::generate();
     compiler
         .compile(wasm, Default::default(), token)
-        .map(|mut inner| {
-            let inner_info: &mut crate::module::ModuleInfo = &mut inner.info;
-            inner_info.import_custom_sections(wasm).unwrap();
-            module::Module::new(Arc::new(inner))
-        })
+        .map(|inner| module::Module::new(Arc::new(inner)))
 }
 
 /// The same as `compile_with` but changes the compiler behavior
 /// with the values in the `CompilerConfig`
+#[cfg(feature = "std")]
 pub fn compile_with_config(
     wasm: &[u8],
     compiler: &dyn backend::Compiler,
@@ -137,42 +200,50 @@ pub fn compile_with_config(
 /// Perform validation as defined by the
 /// WebAssembly specification. Returns `true` if validation
 /// succeeded, `false` if validation failed.
+#[cfg(feature = "std")]
 pub fn validate(wasm: &[u8]) -> bool {
     validate_and_report_errors(wasm).is_ok()
 }
 
 /// The same as `validate` but with an Error message on failure
+#[cfg(feature = "std")]
 pub fn validate_and_report_errors(wasm: &[u8]) -> ::std::result::Result<(), String> {
     validate_and_report_errors_with_features(wasm, Default::default())
 }
 
 /// The same as `validate_and_report_errors` but with a Features.
+#[cfg(feature = "std")]
 pub fn validate_and_report_errors_with_features(
     wasm: &[u8],
     features: backend::Features,
 ) -> ::std::result::Result<(), String> {
+    validate_with_features(wasm, &features).map_err(|e| format!("{}", e))
+}
+
+/// Validates the module with the given Features, without compiling it.
+///
+/// On failure, the error tells why the module is invalid, and its
+/// offset is the offset in bytes where the validation failed.
+#[cfg(feature = "std")]
+pub fn validate_with_features(
+    wasm: &[u8],
+    features: &backend::Features,
+) -> ::std::result::Result<(), wasmparser::BinaryReaderError> {
     use wasmparser::WasmDecoder;
-    let config = wasmparser::ValidatingParserConfig {
-        operator_config: wasmparser::OperatorValidatorConfig {
-            enable_simd: features.simd,
-            enable_bulk_memory: false,
-            enable_multi_value: false,
-            enable_reference_types: false,
-            enable_threads: features.threads,
-        },
-    };
+    let config = codegen::validating_parser_config(features);
     let mut parser = wasmparser::ValidatingParser::new(wasm, Some(config));
     loop {
         let state = parser.read();
         match *state {
             wasmparser::ParserState::EndWasm => break Ok(()),
-            wasmparser::ParserState::Error(e) => break Err(format!("{}", e)),
+            wasmparser::ParserState::Error(e) => break Err(e),
             _ => {}
         }
     }
 }
 
 /// Creates a new module from the given cache `Artifact` for the specified compiler backend
+#[cfg(feature = "std")]
 pub unsafe fn load_cache_with(
     cache: Artifact,
     compiler: &dyn backend::Compiler,
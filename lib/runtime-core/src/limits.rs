@@ -0,0 +1,27 @@
+//! The limits module lets embedders decide how far the memories and the tables of an
+//! instance may grow, on top of the maximum declared by the module.
+//!
+//! A `ResourceLimiter` set with `Instance::set_resource_limiter` is consulted before each
+//! growth of a local memory or table of the instance. A denied `memory.grow` returns -1 to
+//! the guest, and `Memory::grow` or `Table::grow` return `GrowError::Vetoed`.
+use crate::units::Pages;
+
+/// A policy deciding whether the memories and the tables of an instance may grow.
+///
+/// The methods are called while the memory or the table is being grown, so they must not
+/// access it. Both allow any growth by default.
+pub trait ResourceLimiter: Send + Sync {
+    /// Called before a memory grows from `current` to `desired` pages. Returning `false`
+    /// denies the growth.
+    fn memory_growing(&self, current: Pages, desired: Pages) -> bool {
+        let _ = (current, desired);
+        true
+    }
+
+    /// Called before a table grows from `current` to `desired` elements. Returning `false`
+    /// denies the growth.
+    fn table_growing(&self, current: u32, desired: u32) -> bool {
+        let _ = (current, desired);
+        true
+    }
+}
@@ -0,0 +1,237 @@
+//! The linker module loads modules built for the WebAssembly dynamic linking convention into a
+//! shared memory and table, so that a main module and the side modules it depends on can call
+//! each other.
+//!
+//! A module built for dynamic linking has a `dylink` custom section telling the size of its
+//! static data and of its table elements. It imports the shared memory and table as
+//! `env.memory` and `env.__indirect_function_table`, and the offsets its data and elements are
+//! relocated to as the `env.__memory_base` and `env.__table_base` globals.
+use crate::{
+    error::LinkerError,
+    export::Export,
+    global::Global,
+    import::ImportObject,
+    instance::Instance,
+    memory::Memory,
+    module::Module,
+    table::Table,
+    types::Value,
+    units::{Pages, WASM_PAGE_SIZE},
+    wasmparser::{BinaryReader, BinaryReaderError},
+};
+use std::collections::HashMap;
+
+/// The name of the custom section describing a module built for dynamic linking.
+pub const DYLINK_SECTION: &str = "dylink";
+
+/// Initialization functions of a module, called in order after it's instantiated if exported.
+const INIT_FUNCTIONS: [&str; 3] = [
+    "__wasm_apply_data_relocs",
+    "__wasm_call_ctors",
+    "__post_instantiate",
+];
+
+/// The contents of the `dylink` section of a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DylinkInfo {
+    /// The size of the static data of the module, in bytes.
+    pub memory_size: u32,
+    /// The alignment of the static data, as a power of 2.
+    pub memory_alignment: u32,
+    /// The number of table elements of the module.
+    pub table_size: u32,
+    /// The alignment of the table elements, as a power of 2.
+    pub table_alignment: u32,
+    /// The names of the modules this module depends on.
+    pub needed: Vec<String>,
+}
+
+impl DylinkInfo {
+    /// Parses the contents of a `dylink` section.
+    pub fn parse(section: &[u8]) -> Result<DylinkInfo, LinkerError> {
+        let mut reader = BinaryReader::new(section);
+        let mut read = || -> Result<DylinkInfo, BinaryReaderError> {
+            let memory_size = reader.read_var_u32()?;
+            let memory_alignment = reader.read_var_u32()?;
+            let table_size = reader.read_var_u32()?;
+            let table_alignment = reader.read_var_u32()?;
+            let needed_count = reader.read_var_u32()?;
+            let mut needed = Vec::new();
+            for _ in 0..needed_count {
+                needed.push(reader.read_string()?.to_string());
+            }
+            Ok(DylinkInfo {
+                memory_size,
+                memory_alignment,
+                table_size,
+                table_alignment,
+                needed,
+            })
+        };
+        let info = read().map_err(|e| LinkerError::InvalidDylinkSection(e.message.to_string()))?;
+        if info.memory_alignment >= 32 || info.table_alignment >= 32 {
+            return Err(LinkerError::InvalidDylinkSection(
+                "alignment out of range".to_string(),
+            ));
+        }
+        Ok(info)
+    }
+
+    /// Reads the `dylink` section of `module`.
+    pub fn from_module(module: &Module) -> Result<DylinkInfo, LinkerError> {
+        match module.custom_sections(DYLINK_SECTION).first() {
+            Some(section) => DylinkInfo::parse(section),
+            None => Err(LinkerError::MissingDylinkSection),
+        }
+    }
+}
+
+/// Loads modules built for dynamic linking into a shared memory and table, one at a time.
+///
+/// The data and table elements of each module are placed after those of the modules loaded
+/// before it, growing the memory and the table as needed. The functions exported by a module
+/// become symbols that the modules loaded after it can import from `env`; the first definition
+/// of a symbol wins. Data symbols, and the `GOT.mem` and `GOT.func` imports, aren't supported.
+pub struct Linker {
+    memory: Memory,
+    table: Table,
+    memory_end: usize,
+    table_end: u32,
+    symbols: HashMap<String, Export>,
+    instances: Vec<Instance>,
+}
+
+impl Linker {
+    /// Creates a linker loading modules into `memory` and `table`.
+    ///
+    /// Modules are placed after the current end of both, so the embedder can reserve the start
+    /// of the memory, e.g. for a stack, by creating it with that size.
+    pub fn new(memory: Memory, table: Table) -> Linker {
+        Linker {
+            memory_end: memory.size().bytes().0,
+            table_end: table.size(),
+            memory,
+            table,
+            symbols: HashMap::new(),
+            instances: Vec::new(),
+        }
+    }
+
+    /// The memory shared by the loaded modules.
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// The table shared by the loaded modules.
+    pub fn table(&self) -> &Table {
+        &self.table
+    }
+
+    /// Loads `module` and returns the index of its instance.
+    ///
+    /// The `env` imports of the module resolve to the shared memory and table and to the
+    /// offsets the module is loaded at, then to the symbols of the modules loaded before, and
+    /// then to `imports`. Once instantiated, the `__wasm_apply_data_relocs`,
+    /// `__wasm_call_ctors` and `__post_instantiate` functions of the module are called if it
+    /// exports them.
+    pub fn link(&mut self, module: &Module, imports: &ImportObject) -> Result<usize, LinkerError> {
+        let dylink = DylinkInfo::from_module(module)?;
+
+        let memory_base = align(self.memory_end, 1 << dylink.memory_alignment);
+        let memory_end = memory_base + dylink.memory_size as usize;
+        if memory_base > u32::max_value() as usize || memory_end as u64 > 1 << 32 {
+            return Err(LinkerError::InvalidDylinkSection(
+                "the static data doesn't fit in a memory".to_string(),
+            ));
+        }
+        let pages = (memory_end + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE;
+        let current_pages = self.memory.size().0 as usize;
+        if pages > current_pages {
+            self.memory.grow(Pages((pages - current_pages) as u32))?;
+        }
+
+        let table_base = align(self.table_end as usize, 1 << dylink.table_alignment);
+        let table_end = table_base + dylink.table_size as usize;
+        if table_end > u32::max_value() as usize {
+            return Err(LinkerError::InvalidDylinkSection(
+                "the table elements don't fit in a table".to_string(),
+            ));
+        }
+        let current_size = self.table.size();
+        if table_end as u32 > current_size {
+            self.table.grow(table_end as u32 - current_size)?;
+        }
+
+        let mut link_imports = ImportObject::new();
+        link_imports.state_creator = imports.state_creator.clone();
+        link_imports.allow_missing_functions = imports.allow_missing_functions;
+        link_imports.memory_allocator = imports.memory_allocator.clone();
+        link_imports.extend(imports.clone_ref());
+        link_imports.extend(
+            self.symbols
+                .iter()
+                .map(|(name, export)| ("env".to_string(), name.clone(), export.clone())),
+        );
+        link_imports.extend(vec![
+            (
+                "env".to_string(),
+                "memory".to_string(),
+                Export::Memory(self.memory.clone()),
+            ),
+            (
+                "env".to_string(),
+                "__indirect_function_table".to_string(),
+                Export::Table(self.table.clone()),
+            ),
+            (
+                "env".to_string(),
+                "__memory_base".to_string(),
+                Export::Global(Global::new(Value::I32(memory_base as i32))),
+            ),
+            (
+                "env".to_string(),
+                "__table_base".to_string(),
+                Export::Global(Global::new(Value::I32(table_base as i32))),
+            ),
+        ]);
+
+        let instance = module
+            .instantiate(&link_imports)
+            .map_err(LinkerError::Instantiate)?;
+        for name in INIT_FUNCTIONS.iter() {
+            if module.info().exports.contains_key(*name) {
+                instance.call(name, &[]).map_err(LinkerError::Initialize)?;
+            }
+        }
+
+        for (name, export) in instance.exports() {
+            if let Export::Function { .. } = export {
+                self.symbols.entry(name).or_insert(export);
+            }
+        }
+        self.memory_end = memory_end;
+        self.table_end = table_end as u32;
+        self.instances.push(instance);
+        Ok(self.instances.len() - 1)
+    }
+
+    /// Returns the instance of the module loaded at `index`.
+    pub fn instance(&self, index: usize) -> Option<&Instance> {
+        self.instances.get(index)
+    }
+
+    /// Returns the instance of the module loaded at `index`, mutably.
+    pub fn instance_mut(&mut self, index: usize) -> Option<&mut Instance> {
+        self.instances.get_mut(index)
+    }
+
+    /// Returns the export of a loaded module defining the symbol `name`.
+    pub fn resolve(&self, name: &str) -> Option<Export> {
+        self.symbols.get(name).cloned()
+    }
+}
+
+/// Rounds `offset` up to a multiple of `alignment`, a power of 2.
+fn align(offset: usize, alignment: usize) -> usize {
+    (offset + alignment - 1) & !(alignment - 1)
+}
@@ -188,6 +188,10 @@ impl CodeMemory {
         if unsafe { mprotect(self.ptr as _, self.size, PROT_READ | PROT_EXEC) } != 0 {
             panic!("cannot set code memory to executable");
         }
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            clear_instruction_cache(self.ptr, self.size);
+        }
     }
 
     /// Makes this code memory writable.
@@ -198,6 +202,26 @@ impl CodeMemory {
     }
 }
 
+/// Makes the instruction cache see the code written at `ptr`, which AArch64 doesn't do by
+/// itself.
+#[cfg(all(unix, target_arch = "aarch64"))]
+unsafe fn clear_instruction_cache(ptr: *mut u8, size: usize) {
+    #[cfg(target_os = "macos")]
+    {
+        extern "C" {
+            fn sys_icache_invalidate(start: *mut libc::c_void, len: usize);
+        }
+        sys_icache_invalidate(ptr as _, size);
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        extern "C" {
+            fn __clear_cache(start: *mut libc::c_char, end: *mut libc::c_char);
+        }
+        __clear_cache(ptr as _, ptr.add(size) as _);
+    }
+}
+
 #[cfg(unix)]
 impl Drop for CodeMemory {
     fn drop(&mut self) {
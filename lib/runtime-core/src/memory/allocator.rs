@@ -0,0 +1,90 @@
+//! The allocator module lets embedders provide the virtual memory backing linear memories, e.g.
+//! to use huge pages, place memories on a given NUMA node or back them with a file.
+use crate::{
+    error::{MemoryCreationError, MemoryProtectionError},
+    memory::MemoryImage,
+    sys,
+};
+use std::{ops::Range, sync::Arc};
+
+/// A region of virtual memory backing a linear memory.
+///
+/// The region is reserved inaccessible, and parts of it are made
+/// readable and writable with `commit` as the linear memory grows.
+///
+/// # Safety
+///
+/// `as_ptr` must point to at least `size` bytes of reserved virtual
+/// memory that stays at the same address until the region is dropped,
+/// and a committed range must be readable, writable and zeroed.
+pub unsafe trait MemoryRegion: Send + Sync {
+    /// The start of the region.
+    fn as_ptr(&self) -> *mut u8;
+
+    /// The size of the region in bytes.
+    fn size(&self) -> usize;
+
+    /// Makes the bytes in `range`, relative to the start of the region,
+    /// readable and writable.
+    ///
+    /// # Safety
+    ///
+    /// `range` must be within the region.
+    unsafe fn commit(&mut self, range: Range<usize>) -> Result<(), MemoryProtectionError>;
+
+    /// Maps `image` copy-on-write at the start of the region, in place of its contents.
+    ///
+    /// Returns `false` if the region doesn't support it, in which case the data segments
+    /// are copied into the region instead.
+    ///
+    /// # Safety
+    ///
+    /// The first `image.size()` bytes of the region must be committed.
+    unsafe fn map_image(&mut self, _image: &MemoryImage) -> bool {
+        false
+    }
+}
+
+/// Provides the virtual memory backing linear memories.
+///
+/// Static memories reserve their whole address space, including guard
+/// pages, once. Dynamic memories reserve a new region each time they
+/// grow and copy their contents over.
+pub trait MemoryAllocator: Send + Sync {
+    /// Reserves an inaccessible region of at least `size` bytes.
+    fn reserve(&self, size: usize) -> Result<Box<dyn MemoryRegion>, MemoryCreationError>;
+}
+
+/// The default allocator, which maps anonymous private memory.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SysAllocator;
+
+impl MemoryAllocator for SysAllocator {
+    fn reserve(&self, size: usize) -> Result<Box<dyn MemoryRegion>, MemoryCreationError> {
+        Ok(Box::new(sys::Memory::with_size(size)?))
+    }
+}
+
+unsafe impl MemoryRegion for sys::Memory {
+    fn as_ptr(&self) -> *mut u8 {
+        sys::Memory::as_ptr(self)
+    }
+
+    fn size(&self) -> usize {
+        sys::Memory::size(self)
+    }
+
+    unsafe fn commit(&mut self, range: Range<usize>) -> Result<(), MemoryProtectionError> {
+        self.protect(range, sys::Protect::ReadWrite)
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn map_image(&mut self, image: &MemoryImage) -> bool {
+        image.map_at(self.as_ptr())
+    }
+}
+
+/// Returns the allocator used when none is set.
+pub fn default_allocator() -> Arc<dyn MemoryAllocator> {
+    Arc::new(SysAllocator)
+}
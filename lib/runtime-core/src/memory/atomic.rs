@@ -0,0 +1,192 @@
+//! The atomic module emulates a futex on top of parking_lot, backing the
+//! `memory.atomic.wait32`, `memory.atomic.wait64` and `memory.atomic.notify`
+//! instructions on shared memories.
+//!
+//! Waiters are keyed by the host address they wait on, so threads running
+//! different instances that import the same shared memory see each other.
+use lazy_static::lazy_static;
+use parking_lot::{Condvar, Mutex};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// The outcome of a wait, as returned to the guest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum WaitResult {
+    /// The waiter was woken by a notify.
+    Ok = 0,
+    /// The value at the address didn't match the expected one.
+    NotEqual = 1,
+    /// The timeout expired before a notify.
+    TimedOut = 2,
+}
+
+struct Waiter {
+    notified: Mutex<bool>,
+    condvar: Condvar,
+}
+
+lazy_static! {
+    static ref WAITERS: Mutex<HashMap<usize, VecDeque<Arc<Waiter>>>> = Mutex::new(HashMap::new());
+}
+
+/// Blocks the current thread until `addr` is notified, if it holds `expected`.
+///
+/// # Safety
+///
+/// `addr` must point to an aligned, live location of a shared memory.
+pub unsafe fn wait32(addr: *const u32, expected: u32, timeout: Option<Duration>) -> WaitResult {
+    let atomic = &*(addr as *const AtomicU32);
+    wait(
+        addr as usize,
+        || atomic.load(Ordering::SeqCst) == expected,
+        timeout,
+    )
+}
+
+/// Blocks the current thread until `addr` is notified, if it holds `expected`.
+///
+/// # Safety
+///
+/// `addr` must point to an aligned, live location of a shared memory.
+pub unsafe fn wait64(addr: *const u64, expected: u64, timeout: Option<Duration>) -> WaitResult {
+    let atomic = &*(addr as *const AtomicU64);
+    wait(
+        addr as usize,
+        || atomic.load(Ordering::SeqCst) == expected,
+        timeout,
+    )
+}
+
+fn wait(addr: usize, matches: impl FnOnce() -> bool, timeout: Option<Duration>) -> WaitResult {
+    let waiter = {
+        // The value is compared while holding the lock that `notify` takes,
+        // so a notify that follows the store can't be missed.
+        let mut waiters = WAITERS.lock();
+        if !matches() {
+            return WaitResult::NotEqual;
+        }
+        let waiter = Arc::new(Waiter {
+            notified: Mutex::new(false),
+            condvar: Condvar::new(),
+        });
+        waiters
+            .entry(addr)
+            .or_insert_with(VecDeque::new)
+            .push_back(waiter.clone());
+        waiter
+    };
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    {
+        let mut notified = waiter.notified.lock();
+        while !*notified {
+            match deadline {
+                Some(deadline) => {
+                    if waiter
+                        .condvar
+                        .wait_until(&mut notified, deadline)
+                        .timed_out()
+                    {
+                        break;
+                    }
+                }
+                None => waiter.condvar.wait(&mut notified),
+            }
+        }
+        if *notified {
+            return WaitResult::Ok;
+        }
+    }
+
+    // Timed out, unless a notify came in before the waiter is removed.
+    let mut waiters = WAITERS.lock();
+    if *waiter.notified.lock() {
+        return WaitResult::Ok;
+    }
+    if let Some(queue) = waiters.get_mut(&addr) {
+        queue.retain(|other| !Arc::ptr_eq(other, &waiter));
+        if queue.is_empty() {
+            waiters.remove(&addr);
+        }
+    }
+    WaitResult::TimedOut
+}
+
+/// Wakes up to `count` threads waiting on `addr`, in the order they started
+/// waiting, and returns how many were woken.
+pub fn notify(addr: *const u8, count: u32) -> u32 {
+    let addr = addr as usize;
+    let mut waiters = WAITERS.lock();
+    let queue = match waiters.get_mut(&addr) {
+        Some(queue) => queue,
+        None => return 0,
+    };
+
+    let mut woken = 0;
+    while woken < count {
+        let waiter = match queue.pop_front() {
+            Some(waiter) => waiter,
+            None => break,
+        };
+        *waiter.notified.lock() = true;
+        waiter.condvar.notify_one();
+        woken += 1;
+    }
+    if queue.is_empty() {
+        waiters.remove(&addr);
+    }
+    woken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_wait_not_equal() {
+        let value = AtomicU32::new(1);
+        let result = unsafe { wait32(&value as *const _ as _, 0, None) };
+        assert_eq!(result, WaitResult::NotEqual);
+    }
+
+    #[test]
+    fn test_wait_timeout() {
+        let value = AtomicU64::new(0);
+        let result = unsafe { wait64(&value as *const _ as _, 0, Some(Duration::from_millis(10))) };
+        assert_eq!(result, WaitResult::TimedOut);
+        assert_eq!(notify(&value as *const _ as _, 1), 0);
+    }
+
+    #[test]
+    fn test_wait_notify_across_threads() {
+        let value = Arc::new(AtomicU32::new(0));
+        let addr = &*value as *const AtomicU32 as usize;
+
+        let waiters: Vec<_> = (0..2)
+            .map(|_| {
+                let value = value.clone();
+                thread::spawn(move || unsafe {
+                    wait32(&*value as *const _ as _, 0, Some(Duration::from_secs(10)))
+                })
+            })
+            .collect();
+
+        let mut woken = 0;
+        while woken < 2 {
+            woken += notify(addr as _, 2 - woken);
+            thread::yield_now();
+        }
+
+        for waiter in waiters {
+            assert_eq!(waiter.join().unwrap(), WaitResult::Ok);
+        }
+    }
+}
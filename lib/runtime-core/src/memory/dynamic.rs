@@ -1,11 +1,12 @@
 use crate::error::GrowError;
 use crate::{
     error::CreationError,
-    sys,
+    memory::{GrowObserver, MemoryAllocator, MemoryImage, MemoryRegion},
     types::MemoryDescriptor,
     units::{Bytes, Pages},
     vm,
 };
+use std::{slice, sync::Arc};
 
 pub const DYNAMIC_GUARD_SIZE: usize = 4096;
 
@@ -23,24 +24,28 @@ pub const DYNAMIC_GUARD_SIZE: usize = 4096;
 /// backing memory, we use mmap (or the platform-equivalent) to allow
 /// us to add a guard-page at the end to help elide some bounds-checks.
 pub struct DynamicMemory {
-    memory: sys::Memory,
+    memory: Box<dyn MemoryRegion>,
+    allocator: Arc<dyn MemoryAllocator>,
     current: Pages,
     max: Option<Pages>,
+    grow_observer: Option<GrowObserver>,
 }
 
 impl DynamicMemory {
     pub(super) fn new(
         desc: MemoryDescriptor,
         local: &mut vm::LocalMemory,
+        allocator: Arc<dyn MemoryAllocator>,
     ) -> Result<Box<Self>, CreationError> {
         let min_bytes: Bytes = desc.minimum.into();
         let memory = {
-            let mut memory = sys::Memory::with_size(min_bytes.0 + DYNAMIC_GUARD_SIZE)
+            let mut memory = allocator
+                .reserve(min_bytes.0 + DYNAMIC_GUARD_SIZE)
                 .map_err(|_| CreationError::UnableToCreateMemory)?;
             if desc.minimum != Pages(0) {
                 unsafe {
                     memory
-                        .protect(0..min_bytes.0, sys::Protect::ReadWrite)
+                        .commit(0..min_bytes.0)
                         .map_err(|_| CreationError::UnableToCreateMemory)?;
                 }
             }
@@ -50,8 +55,10 @@ impl DynamicMemory {
 
         let mut storage = Box::new(DynamicMemory {
             memory,
+            allocator,
             current: desc.minimum,
             max: desc.maximum,
+            grow_observer: None,
         });
         let storage_ptr: *mut DynamicMemory = &mut *storage;
 
@@ -62,6 +69,14 @@ impl DynamicMemory {
         Ok(storage)
     }
 
+    pub(in crate::memory) fn set_grow_observer(&mut self, observer: Option<GrowObserver>) {
+        self.grow_observer = observer;
+    }
+
+    pub(in crate::memory) fn map_image(&mut self, image: &MemoryImage) -> bool {
+        image.size() <= self.current.bytes().0 && unsafe { self.memory.map_image(image) }
+    }
+
     /// The size of this memory in `Pages`.
     pub fn size(&self) -> Pages {
         self.current
@@ -84,16 +99,24 @@ impl DynamicMemory {
             }
         }
 
-        let mut new_memory = sys::Memory::with_size(new_pages.bytes().0 + DYNAMIC_GUARD_SIZE)
+        if let Some(observer) = &self.grow_observer {
+            if !observer(self.current, new_pages) {
+                return Err(GrowError::Vetoed);
+            }
+        }
+
+        let mut new_memory = self
+            .allocator
+            .reserve(new_pages.bytes().0 + DYNAMIC_GUARD_SIZE)
             .map_err(|e| e.into())?;
 
         unsafe {
             new_memory
-                .protect(0..new_pages.bytes().0, sys::Protect::ReadWrite)
+                .commit(0..new_pages.bytes().0)
                 .map_err(|e| e.into())?;
 
-            new_memory.as_slice_mut()[..self.current.bytes().0]
-                .copy_from_slice(&self.memory.as_slice()[..self.current.bytes().0]);
+            slice::from_raw_parts_mut(new_memory.as_ptr(), self.current.bytes().0)
+                .copy_from_slice(self.as_slice());
         }
 
         self.memory = new_memory; //The old memory gets dropped.
@@ -108,11 +131,11 @@ impl DynamicMemory {
 
     /// Get this memory represented as a slice of bytes.
     pub fn as_slice(&self) -> &[u8] {
-        unsafe { &self.memory.as_slice()[0..self.current.bytes().0] }
+        unsafe { slice::from_raw_parts(self.memory.as_ptr(), self.current.bytes().0) }
     }
 
     /// Get this memory represented as a mutable slice of bytes
     pub fn as_slice_mut(&mut self) -> &mut [u8] {
-        unsafe { &mut self.memory.as_slice_mut()[0..self.current.bytes().0] }
+        unsafe { slice::from_raw_parts_mut(self.memory.as_ptr(), self.current.bytes().0) }
     }
 }
@@ -0,0 +1,148 @@
+//! The image module builds the initial contents of the local memories of a module once, so
+//! that new instances map them copy-on-write instead of copying every data segment.
+//!
+//! An image is only built for a memory whose data segments all have constant offsets, and
+//! only on Linux, where it is backed by an anonymous file. Memories whose region doesn't
+//! support mapping an image, e.g. the ones from a custom `MemoryAllocator`, fall back to
+//! copying the data segments.
+use crate::{
+    module::ModuleInfo,
+    structures::TypedIndex,
+    types::{Initializer, LocalMemoryIndex, Value},
+};
+use std::sync::{Arc, Mutex};
+
+/// The initial contents of a local memory, built from its data segments.
+pub struct MemoryImage {
+    #[cfg(target_os = "linux")]
+    fd: i32,
+    len: usize,
+}
+
+impl MemoryImage {
+    /// The size of the image in bytes, a multiple of the page size.
+    pub fn size(&self) -> usize {
+        self.len
+    }
+
+    #[cfg(target_os = "linux")]
+    fn build(segments: &[(usize, &[u8])]) -> Option<Self> {
+        use nix::libc;
+
+        let top = segments
+            .iter()
+            .map(|&(offset, data)| offset + data.len())
+            .max()?;
+        let page_size = page_size::get();
+        let len = (top + page_size - 1) & !(page_size - 1);
+        if len == 0 {
+            return None;
+        }
+
+        unsafe {
+            let fd = libc::memfd_create(b"wasmer-memory-image\0".as_ptr() as _, libc::MFD_CLOEXEC);
+            if fd == -1 {
+                return None;
+            }
+            let image = MemoryImage { fd, len };
+            if libc::ftruncate(fd, len as libc::off_t) == -1 {
+                return None;
+            }
+            // Later segments overwrite earlier ones, like when they are copied.
+            for &(offset, data) in segments {
+                let mut written = 0;
+                while written < data.len() {
+                    let n = libc::pwrite(
+                        fd,
+                        data[written..].as_ptr() as _,
+                        data.len() - written,
+                        (offset + written) as libc::off_t,
+                    );
+                    if n <= 0 {
+                        return None;
+                    }
+                    written += n as usize;
+                }
+            }
+            Some(image)
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn build(_segments: &[(usize, &[u8])]) -> Option<Self> {
+        None
+    }
+
+    /// Maps the image copy-on-write at `base`, replacing the pages there.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be page aligned and point to at least `size()` bytes of readable and writable
+    /// memory owned by the caller.
+    #[cfg(target_os = "linux")]
+    pub(crate) unsafe fn map_at(&self, base: *mut u8) -> bool {
+        use nix::libc;
+
+        let ptr = libc::mmap(
+            base as _,
+            self.len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_FIXED,
+            self.fd,
+            0,
+        );
+        ptr != libc::MAP_FAILED
+    }
+}
+
+impl Drop for MemoryImage {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        unsafe {
+            nix::libc::close(self.fd);
+        }
+    }
+}
+
+/// The images of the local memories of a module, built the first time the module is
+/// instantiated.
+#[derive(Default)]
+pub struct MemoryImages {
+    images: Mutex<Option<Vec<Option<Arc<MemoryImage>>>>>,
+}
+
+impl MemoryImages {
+    /// Returns the image of the local memory at `index`, if it has one.
+    pub(crate) fn get(
+        &self,
+        info: &ModuleInfo,
+        index: LocalMemoryIndex,
+    ) -> Option<Arc<MemoryImage>> {
+        let mut images = self.images.lock().unwrap();
+        let images = images.get_or_insert_with(|| {
+            info.memories
+                .iter()
+                .map(|(index, _)| build_image(info, index).map(Arc::new))
+                .collect()
+        });
+        images[index.index()].clone()
+    }
+}
+
+fn build_image(info: &ModuleInfo, index: LocalMemoryIndex) -> Option<MemoryImage> {
+    let memory_index = index.convert_up(info);
+    let mut segments = Vec::new();
+    for init in &info.data_initializers {
+        if init.memory_index != memory_index {
+            continue;
+        }
+        // Offsets read from globals are only known at instantiation.
+        match init.base {
+            Initializer::Const(Value::I32(offset)) => {
+                segments.push((offset as u32 as usize, &init.data[..]))
+            }
+            _ => return None,
+        }
+    }
+    MemoryImage::build(&segments)
+}
@@ -14,17 +14,29 @@ use std::{cell::Cell, fmt, mem, sync::Arc};
 
 use std::sync::Mutex as StdMutex;
 
+pub use self::allocator::{default_allocator, MemoryAllocator, MemoryRegion, SysAllocator};
 pub use self::dynamic::DynamicMemory;
+pub use self::image::{MemoryImage, MemoryImages};
 pub use self::static_::StaticMemory;
 pub use self::view::{Atomically, MemoryView};
 
 use parking_lot::Mutex;
 
+pub mod allocator;
+pub mod atomic;
 mod dynamic;
+mod image;
 pub mod ptr;
 mod static_;
 mod view;
 
+/// A callback invoked before a memory grows, with its current and its requested size.
+///
+/// Returning `false` vetoes the growth: `memory.grow` then returns -1 to the guest, and
+/// `Memory::grow` returns `GrowError::Vetoed`. The callback is invoked while the memory is
+/// being grown, so it must not access the memory itself.
+pub type GrowObserver = Arc<dyn Fn(Pages, Pages) -> bool + Send + Sync>;
+
 #[derive(Clone)]
 enum MemoryVariant {
     Unshared(UnsharedMemory),
@@ -60,6 +72,17 @@ impl Memory {
     /// }
     /// ```
     pub fn new(desc: MemoryDescriptor) -> Result<Self, CreationError> {
+        Self::with_allocator(desc, default_allocator())
+    }
+
+    /// Create a new `Memory` from a [`MemoryDescriptor`], whose storage
+    /// is provided by `allocator`.
+    ///
+    /// [`MemoryDescriptor`]: struct.MemoryDescriptor.html
+    pub fn with_allocator(
+        desc: MemoryDescriptor,
+        allocator: Arc<dyn MemoryAllocator>,
+    ) -> Result<Self, CreationError> {
         if let Some(max) = desc.maximum {
             if max < desc.minimum {
                 return Err(CreationError::InvalidDescriptor(
@@ -76,9 +99,9 @@ impl Memory {
         }
 
         let variant = if !desc.shared {
-            MemoryVariant::Unshared(UnsharedMemory::new(desc)?)
+            MemoryVariant::Unshared(UnsharedMemory::with_allocator(desc, allocator)?)
         } else {
-            MemoryVariant::Shared(SharedMemory::new(desc)?)
+            MemoryVariant::Shared(SharedMemory::new(desc, &*allocator)?)
         };
 
         Ok(Memory { desc, variant })
@@ -108,6 +131,29 @@ impl Memory {
         }
     }
 
+    /// Sets the callback invoked before this memory grows, whether through
+    /// `memory.grow` or `Memory::grow`, replacing the previous one.
+    ///
+    /// The memories of an instance can be reached with `Ctx::memory`.
+    ///
+    /// # Usage:
+    ///
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use wasmer_runtime_core::memory::Memory;
+    /// # use wasmer_runtime_core::units::Pages;
+    /// # fn limit_memory(memory: &Memory) {
+    /// // Allow at most 16 pages.
+    /// memory.set_grow_observer(Some(Arc::new(|_old: Pages, new: Pages| new <= Pages(16))));
+    /// # }
+    /// ```
+    pub fn set_grow_observer(&self, observer: Option<GrowObserver>) {
+        match &self.variant {
+            MemoryVariant::Unshared(unshared_mem) => unshared_mem.set_grow_observer(observer),
+            MemoryVariant::Shared(shared_mem) => shared_mem.set_grow_observer(observer),
+        }
+    }
+
     /// Return a "view" of the currently accessible memory. By
     /// default, the view is unsyncronized, using regular memory
     /// accesses. You can force a memory view to use atomic accesses
@@ -155,6 +201,15 @@ impl Memory {
             MemoryVariant::Shared(shared_mem) => shared_mem.vm_local_memory(),
         }
     }
+
+    /// Maps `image` copy-on-write at the start of this memory, returning `false` if this
+    /// memory doesn't support it.
+    pub(crate) fn map_image(&self, image: &MemoryImage) -> bool {
+        match &self.variant {
+            MemoryVariant::Unshared(unshared_mem) => unshared_mem.map_image(image),
+            MemoryVariant::Shared(_) => false,
+        }
+    }
 }
 
 impl IsExport for Memory {
@@ -223,6 +278,13 @@ unsafe impl Sync for UnsharedMemoryInternal {}
 impl UnsharedMemory {
     /// Create a new `UnsharedMemory` from the given memory descriptor.
     pub fn new(desc: MemoryDescriptor) -> Result<Self, CreationError> {
+        Self::with_allocator(desc, default_allocator())
+    }
+
+    fn with_allocator(
+        desc: MemoryDescriptor,
+        allocator: Arc<dyn MemoryAllocator>,
+    ) -> Result<Self, CreationError> {
         let mut local = vm::LocalMemory {
             base: std::ptr::null_mut(),
             bound: 0,
@@ -231,10 +293,10 @@ impl UnsharedMemory {
 
         let storage = match desc.memory_type() {
             MemoryType::Dynamic => {
-                UnsharedMemoryStorage::Dynamic(DynamicMemory::new(desc, &mut local)?)
+                UnsharedMemoryStorage::Dynamic(DynamicMemory::new(desc, &mut local, allocator)?)
             }
             MemoryType::Static => {
-                UnsharedMemoryStorage::Static(StaticMemory::new(desc, &mut local)?)
+                UnsharedMemoryStorage::Static(StaticMemory::new(desc, &mut local, &*allocator)?)
             }
             MemoryType::SharedStatic => {
                 return Err(CreationError::InvalidDescriptor(
@@ -269,6 +331,19 @@ impl UnsharedMemory {
         pages
     }
 
+    fn set_grow_observer(&self, observer: Option<GrowObserver>) {
+        let mut storage = self.internal.storage.lock().unwrap();
+
+        match &mut *storage {
+            UnsharedMemoryStorage::Dynamic(dynamic_memory) => {
+                dynamic_memory.set_grow_observer(observer)
+            }
+            UnsharedMemoryStorage::Static(static_memory) => {
+                static_memory.set_grow_observer(observer)
+            }
+        }
+    }
+
     /// Size of this memory in pages.
     pub fn size(&self) -> Pages {
         let storage = self.internal.storage.lock().unwrap();
@@ -282,6 +357,15 @@ impl UnsharedMemory {
     pub(crate) fn vm_local_memory(&self) -> *mut vm::LocalMemory {
         self.internal.local.as_ptr()
     }
+
+    fn map_image(&self, image: &MemoryImage) -> bool {
+        let mut storage = self.internal.storage.lock().unwrap();
+
+        match &mut *storage {
+            UnsharedMemoryStorage::Dynamic(dynamic_memory) => dynamic_memory.map_image(image),
+            UnsharedMemoryStorage::Static(static_memory) => static_memory.map_image(image),
+        }
+    }
 }
 
 impl Clone for UnsharedMemory {
@@ -309,14 +393,14 @@ pub struct SharedMemoryInternal {
 unsafe impl Sync for SharedMemoryInternal {}
 
 impl SharedMemory {
-    fn new(desc: MemoryDescriptor) -> Result<Self, CreationError> {
+    fn new(desc: MemoryDescriptor, allocator: &dyn MemoryAllocator) -> Result<Self, CreationError> {
         let mut local = vm::LocalMemory {
             base: std::ptr::null_mut(),
             bound: 0,
             memory: std::ptr::null_mut(),
         };
 
-        let memory = StaticMemory::new(desc, &mut local)?;
+        let memory = StaticMemory::new(desc, &mut local, allocator)?;
 
         Ok(Self {
             internal: Arc::new(SharedMemoryInternal {
@@ -336,6 +420,12 @@ impl SharedMemory {
         pages
     }
 
+    fn set_grow_observer(&self, observer: Option<GrowObserver>) {
+        let _guard = self.internal.lock.lock();
+        let mut memory = self.internal.memory.lock().unwrap();
+        memory.set_grow_observer(observer);
+    }
+
     /// Size of this memory in pages.
     pub fn size(&self) -> Pages {
         let _guard = self.internal.lock.lock();
@@ -362,6 +452,11 @@ impl Clone for SharedMemory {
 mod memory_tests {
 
     use super::{Memory, MemoryDescriptor, Pages};
+    use crate::error::GrowError;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
 
     #[test]
     fn test_initial_memory_size() {
@@ -370,6 +465,99 @@ mod memory_tests {
         assert_eq!(unshared_memory.size(), Pages(10));
     }
 
+    #[test]
+    fn test_grow_observer() {
+        let memory_desc = MemoryDescriptor::new(Pages(1), Some(Pages(10)), false).unwrap();
+        let memory = Memory::new(memory_desc).unwrap();
+        let grown = Arc::new(AtomicU32::new(0));
+
+        let observed = Arc::clone(&grown);
+        memory.set_grow_observer(Some(Arc::new(move |old: Pages, new: Pages| {
+            observed.fetch_add(new.0 - old.0, Ordering::SeqCst);
+            new <= Pages(4)
+        })));
+
+        assert_eq!(memory.grow(Pages(2)).unwrap(), Pages(1));
+        match memory.grow(Pages(2)) {
+            Err(GrowError::Vetoed) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(memory.size(), Pages(3));
+        assert_eq!(grown.load(Ordering::SeqCst), 4);
+
+        memory.set_grow_observer(None);
+        assert_eq!(memory.grow(Pages(2)).unwrap(), Pages(3));
+    }
+
+    #[test]
+    fn test_checked_view_accesses() {
+        use super::ptr::MemoryAccessError;
+        use crate::types::ValueType;
+
+        #[derive(Debug, Copy, Clone, PartialEq)]
+        #[repr(C)]
+        struct Pair {
+            a: u16,
+            b: u32,
+        }
+        unsafe impl ValueType for Pair {}
+
+        let memory_desc = MemoryDescriptor::new(Pages(1), None, false).unwrap();
+        let memory = Memory::new(memory_desc).unwrap();
+        let end = Pages(1).bytes().0;
+
+        let view = memory.view::<u32>();
+        view.write(2, 0x0102_0304).unwrap();
+        assert_eq!(view.read(2), Ok(0x0102_0304));
+        assert_eq!(view.read(end / 4), Err(MemoryAccessError::OutOfBounds));
+        assert_eq!(memory.view::<u8>().read(8), Ok(4));
+
+        let subview = view.subview(2, 2).unwrap();
+        assert_eq!(subview.len(), 2);
+        assert_eq!(subview.read(0), Ok(0x0102_0304));
+        assert_eq!(subview.read(2), Err(MemoryAccessError::OutOfBounds));
+        assert!(view.subview(end / 4 - 1, 2).is_err());
+        assert!(view.subview(1, usize::max_value()).is_err());
+
+        let bytes = memory.view::<u8>();
+        let pair = Pair { a: 7, b: 9 };
+        bytes.write_struct(17, pair).unwrap();
+        assert_eq!(bytes.read_struct::<Pair>(17), Ok(pair));
+        assert_eq!(
+            bytes.read_struct::<Pair>(end - 4),
+            Err(MemoryAccessError::OutOfBounds)
+        );
+
+        let atomic = view.atomically().subview(2, 1).unwrap();
+        assert_eq!(atomic[0].load(Ordering::SeqCst), 0x0102_0304);
+    }
+
+    #[test]
+    fn test_custom_allocator() {
+        use super::{MemoryAllocator, MemoryRegion, SysAllocator};
+        use crate::error::MemoryCreationError;
+        use std::sync::atomic::AtomicUsize;
+
+        #[derive(Default)]
+        struct CountingAllocator(AtomicUsize);
+
+        impl MemoryAllocator for CountingAllocator {
+            fn reserve(&self, size: usize) -> Result<Box<dyn MemoryRegion>, MemoryCreationError> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                SysAllocator.reserve(size)
+            }
+        }
+
+        let allocator = Arc::new(CountingAllocator::default());
+        let memory_desc = MemoryDescriptor::new(Pages(1), Some(Pages(10)), false).unwrap();
+        let memory = Memory::with_allocator(memory_desc, allocator.clone()).unwrap();
+        assert_eq!(allocator.0.load(Ordering::SeqCst), 1);
+
+        memory.grow(Pages(1)).unwrap();
+        memory.view::<u8>()[Pages(2).bytes().0 - 1].set(1);
+        assert_eq!(memory.view::<u8>()[Pages(2).bytes().0 - 1].get(), 1);
+    }
+
     #[test]
     fn test_invalid_descriptor_returns_error() {
         let memory_desc = MemoryDescriptor::new(Pages(10), None, true);
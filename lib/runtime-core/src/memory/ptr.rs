@@ -10,7 +10,33 @@ use crate::{
     memory::Memory,
     types::{ValueType, WasmExternType},
 };
-use std::{cell::Cell, fmt, marker::PhantomData, mem};
+use std::{borrow::Cow, cell::Cell, fmt, marker::PhantomData, mem, slice, str};
+
+/// The error returned when a `WasmPtr` cannot be accessed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryAccessError {
+    /// The access goes past the end of the memory.
+    OutOfBounds,
+    /// The pointer is not aligned for the type it points to.
+    Unaligned,
+    /// The string is not valid UTF-8.
+    InvalidUtf8(str::Utf8Error),
+    /// No NUL byte was found within the given bound.
+    NotNulTerminated,
+}
+
+impl fmt::Display for MemoryAccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MemoryAccessError::OutOfBounds => write!(f, "memory access out of bounds"),
+            MemoryAccessError::Unaligned => write!(f, "unaligned memory access"),
+            MemoryAccessError::InvalidUtf8(e) => write!(f, "invalid UTF-8 string: {}", e),
+            MemoryAccessError::NotNulTerminated => write!(f, "string is not NUL-terminated"),
+        }
+    }
+}
+
+impl std::error::Error for MemoryAccessError {}
 
 /// Array.
 pub struct Array;
@@ -48,7 +74,48 @@ fn align_pointer(ptr: usize, align: usize) -> usize {
     ptr & !(align - 1)
 }
 
+/// Returns the `len` bytes of `memory` starting at `offset`.
+fn bytes(memory: &Memory, offset: u32, len: usize) -> Result<&[u8], MemoryAccessError> {
+    let end = (offset as usize)
+        .checked_add(len)
+        .ok_or(MemoryAccessError::OutOfBounds)?;
+    if end > memory.size().bytes().0 {
+        return Err(MemoryAccessError::OutOfBounds);
+    }
+    let base = memory.view::<u8>().as_ptr() as *const u8;
+    Ok(unsafe { slice::from_raw_parts(base.add(offset as usize), len) })
+}
+
+/// Checks that `offset` is aligned for `T`, the base of a memory being page aligned.
+fn check_aligned<T>(offset: u32) -> Result<(), MemoryAccessError> {
+    if offset as usize % mem::align_of::<T>() == 0 {
+        Ok(())
+    } else {
+        Err(MemoryAccessError::Unaligned)
+    }
+}
+
 impl<T: Copy + ValueType> WasmPtr<T, Item> {
+    /// Dereference this `WasmPtr`, checking that it is in bounds and aligned.
+    ///
+    /// The cell holds the value in the byte order of linear memory; prefer `read` and `write`.
+    pub fn try_deref(self, memory: &Memory) -> Result<&Cell<T>, MemoryAccessError> {
+        check_aligned::<T>(self.offset)?;
+        let bytes = bytes(memory, self.offset, mem::size_of::<T>())?;
+        Ok(unsafe { &*(bytes.as_ptr() as *const Cell<T>) })
+    }
+
+    /// Read the value this `WasmPtr` points to, converted to the byte order of the host.
+    pub fn read(self, memory: &Memory) -> Result<T, MemoryAccessError> {
+        self.try_deref(memory).map(|cell| cell.get().from_le())
+    }
+
+    /// Write `value` where this `WasmPtr` points to, in the byte order of linear memory.
+    pub fn write(self, memory: &Memory, value: T) -> Result<(), MemoryAccessError> {
+        self.try_deref(memory)?.set(value.to_le());
+        Ok(())
+    }
+
     /// Dereference this `WasmPtr`.
     #[inline]
     pub fn deref<'a>(self, memory: &'a Memory) -> Option<&'a Cell<T>> {
@@ -128,6 +195,41 @@ impl<T: Copy + ValueType> WasmPtr<T, Array> {
         Some(cell_ptrs)
     }
 
+    /// Dereference `length` items of this `WasmPtr` from `index`, checking that they are in
+    /// bounds and aligned.
+    pub fn try_deref(
+        self,
+        memory: &Memory,
+        index: u32,
+        length: u32,
+    ) -> Result<&[Cell<T>], MemoryAccessError> {
+        check_aligned::<T>(self.offset)?;
+        let slice_full_len = index as usize + length as usize;
+        let byte_len = slice_full_len
+            .checked_mul(mem::size_of::<T>())
+            .ok_or(MemoryAccessError::OutOfBounds)?;
+        let bytes = bytes(memory, self.offset, byte_len)?;
+        let cells =
+            unsafe { slice::from_raw_parts(bytes.as_ptr() as *const Cell<T>, slice_full_len) };
+        Ok(&cells[index as usize..])
+    }
+
+    /// Iterate over the values of the first `length` items of this `WasmPtr`, converted to the
+    /// byte order of the host.
+    pub fn iter<'a>(
+        self,
+        memory: &'a Memory,
+        length: u32,
+    ) -> Result<impl Iterator<Item = T> + 'a, MemoryAccessError>
+    where
+        T: 'a,
+    {
+        Ok(self
+            .try_deref(memory, 0, length)?
+            .iter()
+            .map(|cell| cell.get().from_le()))
+    }
+
     /// Get a UTF-8 string representation of this `WasmPtr` with the given length.
     pub fn get_utf8_string<'a>(self, memory: &'a Memory, str_len: u32) -> Option<&'a str> {
         if self.offset as usize + str_len as usize > memory.size().bytes().0 {
@@ -139,6 +241,36 @@ impl<T: Copy + ValueType> WasmPtr<T, Array> {
     }
 }
 
+impl WasmPtr<u8, Array> {
+    /// Read the UTF-8 string of `len` bytes this `WasmPtr` points to.
+    pub fn read_utf8(self, memory: &Memory, len: u32) -> Result<&str, MemoryAccessError> {
+        str::from_utf8(bytes(memory, self.offset, len as usize)?)
+            .map_err(MemoryAccessError::InvalidUtf8)
+    }
+
+    /// Read the string of `len` bytes this `WasmPtr` points to, replacing invalid UTF-8
+    /// sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn read_utf8_lossy(self, memory: &Memory, len: u32) -> Result<Cow<str>, MemoryAccessError> {
+        Ok(String::from_utf8_lossy(bytes(
+            memory,
+            self.offset,
+            len as usize,
+        )?))
+    }
+
+    /// Read the NUL-terminated UTF-8 string this `WasmPtr` points to, looking for the NUL
+    /// byte in the first `max_len` bytes at most. The NUL byte is not included.
+    pub fn read_c_str(self, memory: &Memory, max_len: u32) -> Result<&str, MemoryAccessError> {
+        let available = memory.size().bytes().0.saturating_sub(self.offset as usize);
+        let bytes = bytes(memory, self.offset, (max_len as usize).min(available))?;
+        let len = bytes
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or(MemoryAccessError::NotNulTerminated)?;
+        str::from_utf8(&bytes[..len]).map_err(MemoryAccessError::InvalidUtf8)
+    }
+}
+
 unsafe impl<T: Copy, Ty> WasmExternType for WasmPtr<T, Ty> {
     type Native = i32;
 
@@ -153,7 +285,16 @@ unsafe impl<T: Copy, Ty> WasmExternType for WasmPtr<T, Ty> {
     }
 }
 
-unsafe impl<T: Copy, Ty> ValueType for WasmPtr<T, Ty> {}
+unsafe impl<T: Copy, Ty> ValueType for WasmPtr<T, Ty> {
+    #[inline]
+    fn from_le(self) -> Self {
+        Self::new(u32::from_le(self.offset))
+    }
+    #[inline]
+    fn to_le(self) -> Self {
+        Self::new(self.offset.to_le())
+    }
+}
 
 impl<T: Copy, Ty> Clone for WasmPtr<T, Ty> {
     fn clone(&self) -> Self {
@@ -179,3 +320,84 @@ impl<T: Copy, Ty> fmt::Debug for WasmPtr<T, Ty> {
         write!(f, "WasmPtr({:#x})", self.offset)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{types::MemoryDescriptor, units::Pages};
+
+    #[test]
+    fn test_checked_accesses() {
+        let memory = Memory::new(MemoryDescriptor::new(Pages(1), None, false).unwrap()).unwrap();
+        let end = Pages(1).bytes().0 as u32;
+
+        let ptr: WasmPtr<u32> = WasmPtr::new(8);
+        ptr.write(&memory, 42).unwrap();
+        assert_eq!(ptr.read(&memory), Ok(42));
+        assert_eq!(
+            WasmPtr::<u32>::new(6).read(&memory),
+            Err(MemoryAccessError::Unaligned)
+        );
+        assert_eq!(WasmPtr::<u32>::new(end - 4).read(&memory), Ok(0));
+        assert_eq!(
+            WasmPtr::<u32>::new(end).read(&memory),
+            Err(MemoryAccessError::OutOfBounds)
+        );
+
+        let array: WasmPtr<u32, Array> = WasmPtr::new(4);
+        assert_eq!(
+            array.iter(&memory, 3).unwrap().collect::<Vec<_>>(),
+            [0, 42, 0]
+        );
+        assert!(WasmPtr::<u32, Array>::new(end - 8)
+            .iter(&memory, 3)
+            .is_err());
+    }
+
+    #[test]
+    fn test_little_endian_accesses() {
+        let memory = Memory::new(MemoryDescriptor::new(Pages(1), None, false).unwrap()).unwrap();
+        WasmPtr::<u32>::new(8).write(&memory, 0x0102_0304).unwrap();
+        WasmPtr::<f64>::new(16).write(&memory, 1.5).unwrap();
+
+        let bytes: Vec<u8> = memory.view::<u8>()[8..24].iter().map(Cell::get).collect();
+        assert_eq!(bytes[..4], [4, 3, 2, 1]);
+        assert_eq!(bytes[8..], 1.5f64.to_bits().to_le_bytes());
+        assert_eq!(WasmPtr::<u32>::new(8).read(&memory), Ok(0x0102_0304));
+        assert_eq!(WasmPtr::<f64>::new(16).read(&memory), Ok(1.5));
+    }
+
+    #[test]
+    fn test_strings() {
+        let memory = Memory::new(MemoryDescriptor::new(Pages(1), None, false).unwrap()).unwrap();
+        let end = Pages(1).bytes().0 as u32;
+        for (cell, &byte) in memory.view::<u8>()[16..].iter().zip(b"hi\xff\0".iter()) {
+            cell.set(byte);
+        }
+
+        let ptr: WasmPtr<u8, Array> = WasmPtr::new(16);
+        assert_eq!(ptr.read_utf8(&memory, 2), Ok("hi"));
+        assert!(ptr.read_utf8(&memory, 3).is_err());
+        assert_eq!(ptr.read_utf8_lossy(&memory, 3).unwrap(), "hi\u{fffd}");
+        assert_eq!(
+            WasmPtr::<u8, Array>::new(end - 1).read_utf8(&memory, 2),
+            Err(MemoryAccessError::OutOfBounds)
+        );
+
+        assert_eq!(
+            WasmPtr::<u8, Array>::new(16)
+                .read_c_str(&memory, 16)
+                .unwrap_err(),
+            MemoryAccessError::InvalidUtf8(str::from_utf8(b"hi\xff").unwrap_err())
+        );
+        assert_eq!(
+            WasmPtr::<u8, Array>::new(17).read_c_str(&memory, 2),
+            Err(MemoryAccessError::NotNulTerminated)
+        );
+        assert_eq!(WasmPtr::<u8, Array>::new(19).read_c_str(&memory, 2), Ok(""));
+        assert_eq!(
+            WasmPtr::<u8, Array>::new(end - 1).read_c_str(&memory, 16),
+            Err(MemoryAccessError::NotNulTerminated)
+        );
+    }
+}
@@ -1,5 +1,12 @@
 use crate::error::GrowError;
-use crate::{error::CreationError, sys, types::MemoryDescriptor, units::Pages, vm};
+use crate::{
+    error::CreationError,
+    memory::{GrowObserver, MemoryAllocator, MemoryImage, MemoryRegion},
+    types::MemoryDescriptor,
+    units::Pages,
+    vm,
+};
+use std::slice;
 
 #[doc(hidden)]
 pub const SAFE_STATIC_HEAP_SIZE: usize = 1 << 32; // 4 GiB
@@ -18,23 +25,26 @@ pub const SAFE_STATIC_GUARD_SIZE: usize = 1 << 31; // 2 GiB
 /// it's recommended that a dynamic memory is used. There is currently no user-facing api that
 /// allows them to select the type of memory used however.
 pub struct StaticMemory {
-    memory: sys::Memory,
+    memory: Box<dyn MemoryRegion>,
     current: Pages,
     max: Option<Pages>,
+    grow_observer: Option<GrowObserver>,
 }
 
 impl StaticMemory {
     pub(in crate::memory) fn new(
         desc: MemoryDescriptor,
         local: &mut vm::LocalMemory,
+        allocator: &dyn MemoryAllocator,
     ) -> Result<Box<Self>, CreationError> {
         let memory = {
-            let mut memory = sys::Memory::with_size(SAFE_STATIC_HEAP_SIZE + SAFE_STATIC_GUARD_SIZE)
+            let mut memory = allocator
+                .reserve(SAFE_STATIC_HEAP_SIZE + SAFE_STATIC_GUARD_SIZE)
                 .map_err(|_| CreationError::UnableToCreateMemory)?;
             if desc.minimum != Pages(0) {
                 unsafe {
                     memory
-                        .protect(0..desc.minimum.bytes().0, sys::Protect::ReadWrite)
+                        .commit(0..desc.minimum.bytes().0)
                         .map_err(|_| CreationError::UnableToCreateMemory)?;
                 }
             }
@@ -46,6 +56,7 @@ impl StaticMemory {
             memory,
             current: desc.minimum,
             max: desc.maximum,
+            grow_observer: None,
         });
         let storage_ptr: *mut StaticMemory = &mut *storage;
 
@@ -56,6 +67,14 @@ impl StaticMemory {
         Ok(storage)
     }
 
+    pub(in crate::memory) fn set_grow_observer(&mut self, observer: Option<GrowObserver>) {
+        self.grow_observer = observer;
+    }
+
+    pub(in crate::memory) fn map_image(&mut self, image: &MemoryImage) -> bool {
+        image.size() <= self.current.bytes().0 && unsafe { self.memory.map_image(image) }
+    }
+
     /// The size of this memory in `Pages`.
     pub fn size(&self) -> Pages {
         self.current
@@ -78,12 +97,15 @@ impl StaticMemory {
             }
         }
 
+        if let Some(observer) = &self.grow_observer {
+            if !observer(self.current, new_pages) {
+                return Err(GrowError::Vetoed);
+            }
+        }
+
         let _ = unsafe {
             self.memory
-                .protect(
-                    self.current.bytes().0..new_pages.bytes().0,
-                    sys::Protect::ReadWrite,
-                )
+                .commit(self.current.bytes().0..new_pages.bytes().0)
                 .map_err(|e| e.into())
         }?;
 
@@ -98,11 +120,11 @@ impl StaticMemory {
 
     /// Get this memory represented as a slice of bytes.
     pub fn as_slice(&self) -> &[u8] {
-        unsafe { &self.memory.as_slice()[0..self.current.bytes().0] }
+        unsafe { slice::from_raw_parts(self.memory.as_ptr(), self.current.bytes().0) }
     }
 
     /// Get this memory represented as a mutable slice of bytes.
     pub fn as_slice_mut(&mut self) -> &mut [u8] {
-        unsafe { &mut self.memory.as_slice_mut()[0..self.current.bytes().0] }
+        unsafe { slice::from_raw_parts_mut(self.memory.as_ptr(), self.current.bytes().0) }
     }
 }
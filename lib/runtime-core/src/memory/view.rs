@@ -1,9 +1,10 @@
+use super::ptr::MemoryAccessError;
 use crate::types::ValueType;
 
 use std::sync::atomic::{
     AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicU16, AtomicU32, AtomicU64, AtomicU8,
 };
-use std::{cell::Cell, marker::PhantomData, ops::Deref, slice};
+use std::{cell::Cell, marker::PhantomData, mem, ops::Deref, ptr, slice};
 
 pub trait Atomic {
     type Output;
@@ -49,6 +50,10 @@ pub struct NonAtomically;
 impl Atomicity for NonAtomically {}
 
 /// A view into a memory.
+///
+/// The view dereferences to a slice of cells, or of atomics once made `atomically`. Prefer
+/// `read`, `write` and `subview`, which check their bounds and convert the byte order: the
+/// slice is only valid until the memory grows, and the cells hold little-endian values.
 pub struct MemoryView<'a, T: 'a, A = NonAtomically> {
     ptr: *mut T,
     length: usize,
@@ -68,6 +73,58 @@ where
     }
 }
 
+impl<'a, T, A: Atomicity> MemoryView<'a, T, A> {
+    /// Returns the view of the `len` items starting at `start`.
+    pub fn subview(&self, start: usize, len: usize) -> Result<Self, MemoryAccessError> {
+        let end = start
+            .checked_add(len)
+            .ok_or(MemoryAccessError::OutOfBounds)?;
+        if end > self.length {
+            return Err(MemoryAccessError::OutOfBounds);
+        }
+        Ok(MemoryView {
+            ptr: unsafe { self.ptr.add(start) },
+            length: len,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+impl<'a, T: ValueType> MemoryView<'a, T> {
+    /// Reads the item at `index`, converted to the byte order of the host.
+    pub fn read(&self, index: usize) -> Result<T, MemoryAccessError> {
+        self.get(index)
+            .map(|cell| cell.get().from_le())
+            .ok_or(MemoryAccessError::OutOfBounds)
+    }
+
+    /// Writes `value` at `index`, in the byte order of linear memory.
+    pub fn write(&self, index: usize, value: T) -> Result<(), MemoryAccessError> {
+        self.get(index)
+            .map(|cell| cell.set(value.to_le()))
+            .ok_or(MemoryAccessError::OutOfBounds)
+    }
+}
+
+impl<'a> MemoryView<'a, u8> {
+    /// Reads a `#[repr(C)]` value at the byte `offset`, which doesn't need to be aligned.
+    pub fn read_struct<S: ValueType>(&self, offset: usize) -> Result<S, MemoryAccessError> {
+        let bytes = self.subview(offset, mem::size_of::<S>())?;
+        Ok(unsafe { ptr::read_unaligned(bytes.ptr as *const S) }.from_le())
+    }
+
+    /// Writes a `#[repr(C)]` value at the byte `offset`, which doesn't need to be aligned.
+    pub fn write_struct<S: ValueType>(
+        &self,
+        offset: usize,
+        value: S,
+    ) -> Result<(), MemoryAccessError> {
+        let bytes = self.subview(offset, mem::size_of::<S>())?;
+        unsafe { ptr::write_unaligned(bytes.ptr as *mut S, value.to_le()) };
+        Ok(())
+    }
+}
+
 impl<'a, T: Atomic> MemoryView<'a, T> {
     /// Get atomic access to a memory view.
     pub fn atomically(&self) -> MemoryView<'a, T::Output, Atomically> {
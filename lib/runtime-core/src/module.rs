@@ -5,12 +5,14 @@ use crate::{
     cache::{Artifact, Error as CacheError},
     error,
     import::ImportObject,
+    instance::Instantiation,
+    memory::MemoryImages,
     structures::{Map, TypedIndex},
     types::{
         FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex, GlobalInit, ImportedFuncIndex,
         ImportedGlobalIndex, ImportedMemoryIndex, ImportedTableIndex, Initializer,
-        LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryDescriptor, MemoryIndex,
-        SigIndex, TableDescriptor, TableIndex,
+        LocalGlobalIndex, LocalMemoryIndex, LocalOrImport, LocalTableIndex, MemoryDescriptor,
+        MemoryIndex, SigIndex, TableDescriptor, TableIndex,
     },
     Instance,
 };
@@ -27,6 +29,9 @@ pub struct ModuleInner {
     pub cache_gen: Box<dyn CacheGen>,
 
     pub info: ModuleInfo,
+
+    /// The copy-on-write images of the local memories.
+    pub memory_images: MemoryImages,
 }
 
 /// Container for module data including memories, globals, tables, imports, and exports.
@@ -76,8 +81,15 @@ pub struct ModuleInfo {
     /// Symbol information from emscripten.
     pub em_symbol_map: Option<HashMap<u32, String>>,
 
-    /// Custom sections.
-    pub custom_sections: HashMap<String, Vec<u8>>,
+    /// Custom sections, by name in the order they appear.
+    pub custom_sections: HashMap<String, Vec<Vec<u8>>>,
+
+    /// Function names from the name section.
+    pub func_names: HashMap<FuncIndex, String>,
+
+    /// Values the middlewares record for the runtime, by name, e.g. the limit compiled into
+    /// metered code.
+    pub middleware_values: HashMap<String, u64>,
 }
 
 impl ModuleInfo {
@@ -92,7 +104,10 @@ impl ModuleInfo {
                 let bytes = reader.read_bytes(len)?;
                 let data = bytes.to_vec();
                 let name = name.to_string();
-                self.custom_sections.insert(name, data);
+                self.custom_sections
+                    .entry(name)
+                    .or_insert_with(Vec::new)
+                    .push(data);
             }
         }
         Ok(())
@@ -107,7 +122,7 @@ impl ModuleInfo {
 /// [`compile`]: fn.compile.html
 /// [`compile_with`]: fn.compile_with.html
 pub struct Module {
-    inner: Arc<ModuleInner>,
+    pub(crate) inner: Arc<ModuleInner>,
 }
 
 impl Module {
@@ -138,7 +153,44 @@ impl Module {
     /// # }
     /// ```
     pub fn instantiate(&self, import_object: &ImportObject) -> error::Result<Instance> {
-        Instance::new(Arc::clone(&self.inner), import_object)
+        Instance::new(Arc::clone(&self.inner), import_object, Instantiation::Full)
+    }
+
+    /// Instantiate a WebAssembly module like [`instantiate`], without calling its start
+    /// function.
+    ///
+    /// The start function is called by [`Instance::run_start`], which lets the embedder set
+    /// limits on the instance before running any of the module's code.
+    ///
+    /// [`instantiate`]: struct.Module.html#method.instantiate
+    /// [`Instance::run_start`]: ../instance/struct.Instance.html#method.run_start
+    pub fn instantiate_without_start(
+        &self,
+        import_object: &ImportObject,
+    ) -> error::Result<Instance> {
+        Instance::new(
+            Arc::clone(&self.inner),
+            import_object,
+            Instantiation::WithoutStart,
+        )
+    }
+
+    /// Instantiate a WebAssembly module as another thread of an instance, whose memories it
+    /// imports.
+    ///
+    /// Unlike [`instantiate`], the data segments aren't written into the imported memories
+    /// again, where they would overwrite what the running threads have changed, and the start
+    /// function isn't called. The element segments are written as usual, imported tables
+    /// included, since their functions run with the context of the new instance: a thread
+    /// importing the table of another one must get a copy of it.
+    ///
+    /// [`instantiate`]: struct.Module.html#method.instantiate
+    pub fn instantiate_thread(&self, import_object: &ImportObject) -> error::Result<Instance> {
+        Instance::new(
+            Arc::clone(&self.inner),
+            import_object,
+            Instantiation::Thread,
+        )
     }
 
     /// Create a cache artifact from this module.
@@ -155,6 +207,110 @@ impl Module {
     pub fn info(&self) -> &ModuleInfo {
         &self.inner.info
     }
+
+    /// Returns the contents of the custom sections named `name`, in the order they appear
+    /// in the module.
+    pub fn custom_sections(&self, name: &str) -> Vec<&[u8]> {
+        self.inner
+            .info
+            .custom_sections
+            .get(name)
+            .map(|sections| sections.iter().map(Vec::as_slice).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the name of the function at `index`, from the name section.
+    pub fn function_name(&self, index: FuncIndex) -> Option<&str> {
+        self.inner.info.func_names.get(&index).map(String::as_str)
+    }
+
+    /// Returns the index of the function named `name` in the name section.
+    ///
+    /// If several functions have the same name, any of them is returned.
+    pub fn function_index(&self, name: &str) -> Option<FuncIndex> {
+        self.inner
+            .info
+            .func_names
+            .iter()
+            .find(|(_, func_name)| *func_name == name)
+            .map(|(&index, _)| index)
+    }
+
+    /// Returns the imports of the module with their types.
+    ///
+    /// The imported functions come first, followed by the memories, the tables and the
+    /// globals, each in the order they are declared.
+    pub fn imports(&self) -> Vec<ImportDescriptor> {
+        let info = &self.inner.info;
+        let import = |import_name: &ImportName, ty| ImportDescriptor {
+            namespace: info
+                .namespace_table
+                .get(import_name.namespace_index)
+                .to_string(),
+            name: info.name_table.get(import_name.name_index).to_string(),
+            ty,
+        };
+
+        let functions = info.imported_functions.iter().map(|(index, name)| {
+            let sig = &info.signatures[info.func_assoc[index.convert_up(info)]];
+            import(name, ExternDescriptor::Function(sig.clone()))
+        });
+        let memories = info
+            .imported_memories
+            .iter()
+            .map(|(_, (name, desc))| import(name, ExternDescriptor::Memory(*desc)));
+        let tables = info
+            .imported_tables
+            .iter()
+            .map(|(_, (name, desc))| import(name, ExternDescriptor::Table(*desc)));
+        let globals = info
+            .imported_globals
+            .iter()
+            .map(|(_, (name, desc))| import(name, ExternDescriptor::Global(*desc)));
+
+        functions
+            .chain(memories)
+            .chain(tables)
+            .chain(globals)
+            .collect()
+    }
+
+    /// Returns the exports of the module with their types, in the order they are declared.
+    pub fn exports(&self) -> Vec<ExportDescriptor> {
+        let info = &self.inner.info;
+        info.exports
+            .iter()
+            .map(|(name, &index)| {
+                let ty = match index {
+                    ExportIndex::Func(index) => {
+                        ExternDescriptor::Function(info.signatures[info.func_assoc[index]].clone())
+                    }
+                    ExportIndex::Memory(index) => {
+                        ExternDescriptor::Memory(match index.local_or_import(info) {
+                            LocalOrImport::Local(index) => info.memories[index],
+                            LocalOrImport::Import(index) => info.imported_memories[index].1,
+                        })
+                    }
+                    ExportIndex::Table(index) => {
+                        ExternDescriptor::Table(match index.local_or_import(info) {
+                            LocalOrImport::Local(index) => info.tables[index],
+                            LocalOrImport::Import(index) => info.imported_tables[index].1,
+                        })
+                    }
+                    ExportIndex::Global(index) => {
+                        ExternDescriptor::Global(match index.local_or_import(info) {
+                            LocalOrImport::Local(index) => info.globals[index].desc,
+                            LocalOrImport::Import(index) => info.imported_globals[index].1,
+                        })
+                    }
+                };
+                ExportDescriptor {
+                    name: name.clone(),
+                    ty,
+                }
+            })
+            .collect()
+    }
 }
 
 impl Clone for Module {
@@ -174,6 +330,43 @@ pub struct ImportName {
     pub name_index: NameIndex,
 }
 
+/// The type of an import or an export.
+#[derive(Debug, Clone)]
+pub enum ExternDescriptor {
+    /// A function with its signature.
+    Function(FuncSig),
+    /// A memory with its limits.
+    Memory(MemoryDescriptor),
+    /// A table with its element type and limits.
+    Table(TableDescriptor),
+    /// A global with its type and mutability.
+    Global(GlobalDescriptor),
+}
+
+/// An import of a module, as returned by [`Module::imports`].
+///
+/// [`Module::imports`]: struct.Module.html#method.imports
+#[derive(Debug, Clone)]
+pub struct ImportDescriptor {
+    /// The namespace of the import.
+    pub namespace: String,
+    /// The name of the import.
+    pub name: String,
+    /// The type of the import.
+    pub ty: ExternDescriptor,
+}
+
+/// An export of a module, as returned by [`Module::exports`].
+///
+/// [`Module::exports`]: struct.Module.html#method.exports
+#[derive(Debug, Clone)]
+pub struct ExportDescriptor {
+    /// The name of the export.
+    pub name: String,
+    /// The type of the export.
+    pub ty: ExternDescriptor,
+}
+
 /// Kinds of export indexes.
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ExportIndex {
@@ -5,6 +5,7 @@ use crate::codegen::*;
 use crate::{
     backend::{Backend, CompilerConfig, RunnableModule},
     error::CompileError,
+    lazy::LazyBodies,
     module::{
         DataInitializer, ExportIndex, ImportName, ModuleInfo, StringTable, StringTableBuilder,
         TableInitializer,
@@ -21,8 +22,8 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::{Arc, RwLock};
 use wasmparser::{
-    BinaryReaderError, ExternalKind, FuncType, ImportSectionEntryType, Operator, Type as WpType,
-    WasmDecoder,
+    BinaryReaderError, ExternalKind, FuncType, ImportSectionEntryType, NameEntry, Operator,
+    ParserInput, Type as WpType, WasmDecoder,
 };
 
 /// Kind of load error.
@@ -91,8 +92,12 @@ pub fn read_module<
         em_symbol_map: compiler_config.symbol_map.clone(),
 
         custom_sections: HashMap::new(),
+
+        func_names: HashMap::new(),
+        middleware_values: HashMap::new(),
     }));
 
+    let code_section_offset = code_section_offset(wasm)?;
     let mut parser = wasmparser::ValidatingParser::new(
         wasm,
         Some(validating_parser_config(&compiler_config.features)),
@@ -249,9 +254,20 @@ pub fn read_module<
                         .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?;
                 }
 
+                if let Some(LazyBodies::Only(local)) = compiler_config.lazy_bodies {
+                    if local.index() != id {
+                        parser.push_input(ParserInput::SkipFunctionBody);
+                        feed_skipped_body(fcg, id as u32, &info_read)?;
+                        func_count = func_count.wrapping_add(1);
+                        continue;
+                    }
+                }
+
                 let mut body_begun = false;
 
+                let mut source_offset;
                 loop {
+                    source_offset = parser.current_position() as u32;
                     let state = parser.read();
                     match state {
                         ParserState::Error(err) => return Err(LoadError::Parse(*err)),
@@ -271,11 +287,19 @@ pub fn read_module<
                                         Some(fcg),
                                         Event::Internal(InternalEvent::FunctionBegin(id as u32)),
                                         &info.read().unwrap(),
+                                        source_offset,
                                     )
                                     .map_err(|x| LoadError::Codegen(x))?;
                             }
+                            fcg.begin_operator(source_offset - code_section_offset)
+                                .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?;
                             middlewares
-                                .run(Some(fcg), Event::Wasm(op), &info.read().unwrap())
+                                .run(
+                                    Some(fcg),
+                                    Event::Wasm(op),
+                                    &info.read().unwrap(),
+                                    source_offset,
+                                )
                                 .map_err(|x| LoadError::Codegen(x))?;
                         }
                         ParserState::EndFunctionBody => break,
@@ -287,6 +311,7 @@ pub fn read_module<
                         Some(fcg),
                         Event::Internal(InternalEvent::FunctionEnd),
                         &info.read().unwrap(),
+                        source_offset,
                     )
                     .map_err(|x| LoadError::Codegen(x))?;
                 fcg.finalize()
@@ -381,6 +406,15 @@ pub fn read_module<
 
                 info.write().unwrap().globals.push(global_init);
             }
+            ParserState::NameSectionEntry(NameEntry::Function(ref names)) => {
+                let mut info = info.write().unwrap();
+                for naming in names.iter() {
+                    info.func_names.insert(
+                        FuncIndex::new(naming.index as usize),
+                        naming.name.to_string(),
+                    );
+                }
+            }
             ParserState::EndWasm => {
                 // TODO Consolidate with BeginFunction body if possible
                 if !mcg_info_fed {
@@ -402,6 +436,42 @@ pub fn read_module<
     Ok(info)
 }
 
+/// Generates the code of the function at local index `id`, whose body was skipped, as a body
+/// that traps. Its events don't go through the middlewares.
+fn feed_skipped_body<E: Debug, FCG: FunctionCodeGenerator<E>>(
+    fcg: &mut FCG,
+    id: u32,
+    info: &ModuleInfo,
+) -> Result<(), LoadError> {
+    fcg.begin_body(info)
+        .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?;
+    let events = vec![
+        Event::Internal(InternalEvent::FunctionBegin(id)),
+        Event::WasmOwned(Operator::Unreachable),
+        Event::WasmOwned(Operator::End),
+        Event::Internal(InternalEvent::FunctionEnd),
+    ];
+    for event in events {
+        fcg.feed_event(event, info)
+            .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?;
+    }
+    fcg.finalize()
+        .map_err(|x| LoadError::Codegen(format!("{:?}", x)))
+}
+
+/// Returns the offset of the code section contents in `wasm`, which DWARF in wasm modules
+/// refers to code relative to.
+fn code_section_offset(wasm: &[u8]) -> Result<u32, BinaryReaderError> {
+    let mut reader = wasmparser::ModuleReader::new(wasm)?;
+    while !reader.eof() {
+        let section = reader.read()?;
+        if let wasmparser::SectionCode::Code = section.code {
+            return Ok(section.get_binary_reader().original_position() as u32);
+        }
+    }
+    Ok(0)
+}
+
 /// Convert given `WpType` to `Type`.
 pub fn wp_type_to_type(ty: WpType) -> Result<Type, BinaryReaderError> {
     match ty {
@@ -160,13 +160,17 @@ pub struct ExecutionStateImage {
     pub frames: Vec<WasmFunctionStateDump>,
 }
 
-/// Represents an image of an `Instance` including its memory, globals, and execution state.
+/// Represents an image of an `Instance` including its memory, globals, tables, and execution
+/// state.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InstanceImage {
     /// Memory for this `InstanceImage`
     pub memory: Option<Vec<u8>>,
     /// Stored globals for this `InstanceImage`
     pub globals: Vec<u128>,
+    /// Stored local tables for this `InstanceImage`, as the function index of each element
+    #[serde(default)]
+    pub tables: Vec<Vec<Option<u32>>>,
     /// `ExecutionStateImage` for this `InstanceImage`
     pub execution_state: ExecutionStateImage,
 }
@@ -889,6 +893,7 @@ pub mod x64 {
             InstanceImage {
                 memory: memory,
                 globals: globals,
+                tables: vec![],
                 execution_state: execution_state,
             }
         }
@@ -1,5 +1,6 @@
 use super::{SliceMap, TypedIndex};
-use std::{
+use alloc::boxed::Box;
+use core::{
     marker::PhantomData,
     mem,
     ops::{Deref, DerefMut},
@@ -1,10 +1,11 @@
 use super::{BoxedMap, SliceMap, TypedIndex};
-use std::{
+use alloc::vec::{self, Vec};
+use core::{
     iter::{self, Extend, FromIterator},
     marker::PhantomData,
     mem,
     ops::{Deref, DerefMut},
-    slice, vec,
+    slice,
 };
 
 /// Dense item map
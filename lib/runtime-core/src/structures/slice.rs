@@ -1,5 +1,5 @@
 use super::{Iter, IterMut, TypedIndex};
-use std::{
+use core::{
     marker::PhantomData,
     ops::{Index, IndexMut},
 };
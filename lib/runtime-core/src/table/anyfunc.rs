@@ -1,20 +1,32 @@
 use crate::{
     error::CreationError,
+    export::{Context, Export, FuncEnvOwner, FuncPointer},
     instance::DynFunc,
     sig_registry::SigRegistry,
     structures::TypedIndex,
-    types::{FuncSig, TableDescriptor},
+    types::{FuncSig, SigIndex, TableDescriptor},
     vm,
 };
 
-use std::{ptr, sync::Arc};
+use std::{
+    collections::HashMap,
+    ptr::{self, NonNull},
+    sync::Arc,
+};
 
 enum AnyfuncInner<'a> {
     Host {
         ptr: *const vm::Func,
         signature: Arc<FuncSig>,
     },
+    HostFunc {
+        func: NonNull<vm::Func>,
+        func_env: Option<NonNull<vm::FuncEnv>>,
+        env_owner: Option<FuncEnvOwner>,
+        signature: Arc<FuncSig>,
+    },
     Managed(DynFunc<'a>),
+    Raw(vm::Anyfunc),
 }
 
 /// Anyfunc data type.
@@ -35,6 +47,94 @@ impl<'a> Anyfunc<'a> {
             },
         }
     }
+
+    /// Create an `Anyfunc` from a host function made with `Func::new`.
+    pub(crate) fn host_func(
+        func: NonNull<vm::Func>,
+        func_env: Option<NonNull<vm::FuncEnv>>,
+        env_owner: Option<FuncEnvOwner>,
+        signature: Arc<FuncSig>,
+    ) -> Self {
+        Self {
+            inner: AnyfuncInner::HostFunc {
+                func,
+                func_env,
+                env_owner,
+                signature,
+            },
+        }
+    }
+
+    /// Create an `Anyfunc` from an exported function, or `None` if
+    /// `export` isn't a function.
+    pub fn from_export(export: &Export) -> Option<Self> {
+        match export {
+            Export::Function {
+                func,
+                ctx,
+                signature,
+                env_owner,
+            } => {
+                let func = NonNull::new(func.inner() as *mut vm::Func)?;
+                let inner = match *ctx {
+                    // The function of an instance is called with the
+                    // context of that instance.
+                    Context::External(vmctx) => {
+                        let sig_index = SigRegistry.lookup_sig_index(Arc::clone(signature));
+                        AnyfuncInner::Raw(vm::Anyfunc {
+                            func: func.as_ptr(),
+                            ctx: vmctx,
+                            sig_id: vm::SigId(sig_index.index() as u32),
+                        })
+                    }
+                    Context::ExternalWithEnv(_, func_env) => AnyfuncInner::HostFunc {
+                        func,
+                        func_env,
+                        env_owner: env_owner.clone(),
+                        signature: Arc::clone(signature),
+                    },
+                    Context::Internal => AnyfuncInner::HostFunc {
+                        func,
+                        func_env: None,
+                        env_owner: env_owner.clone(),
+                        signature: Arc::clone(signature),
+                    },
+                };
+                Some(Self { inner })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns this function as an export, to be called or set in
+    /// another table, or `None` if it's a host function which isn't
+    /// set in a table yet.
+    pub fn to_export(&self) -> Option<Export> {
+        let (func, ctx) = match &self.inner {
+            AnyfuncInner::Host { .. } | AnyfuncInner::HostFunc { .. } => return None,
+            AnyfuncInner::Managed(func) => (func.raw(), func.instance_inner.vmctx),
+            AnyfuncInner::Raw(anyfunc) => (anyfunc.func, anyfunc.ctx),
+        };
+        Some(Export::Function {
+            func: unsafe { FuncPointer::new(func) },
+            ctx: Context::External(ctx),
+            signature: self.signature(),
+            env_owner: None,
+        })
+    }
+
+    /// The signature of this function.
+    pub fn signature(&self) -> Arc<FuncSig> {
+        match &self.inner {
+            AnyfuncInner::Host { signature, .. } | AnyfuncInner::HostFunc { signature, .. } => {
+                Arc::clone(signature)
+            }
+            AnyfuncInner::Managed(func) => Arc::clone(&func.signature),
+            AnyfuncInner::Raw(anyfunc) => {
+                SigRegistry.lookup_signature(SigIndex::new(anyfunc.sig_id.0 as usize))
+            }
+        }
+    }
 }
 
 impl<'a> From<DynFunc<'a>> for Anyfunc<'a> {
@@ -45,11 +145,29 @@ impl<'a> From<DynFunc<'a>> for Anyfunc<'a> {
     }
 }
 
+/// A host function set in a table, along with the context it is
+/// called with.
+struct HostFunc {
+    func_ctx: Box<vm::FuncCtx>,
+    _env_owner: Option<FuncEnvOwner>,
+    /// The number of slots holding the function.
+    slots: u32,
+}
+
 pub struct AnyfuncTable {
     pub(crate) backing: Vec<vm::Anyfunc>,
     max: Option<u32>,
+    /// The contexts of the instances using this table, in the order
+    /// they started to use it.
+    instances: Vec<NonNull<vm::Ctx>>,
+    /// The host functions set in this table, by the function pointer
+    /// the host function wrapper looks its context up with.
+    host_funcs: HashMap<*const vm::Func, HostFunc>,
 }
 
+// manually implemented because AnyfuncTable contains raw pointers directly
+unsafe impl Send for AnyfuncTable {}
+
 impl AnyfuncTable {
     pub fn new(
         desc: TableDescriptor,
@@ -60,6 +178,8 @@ impl AnyfuncTable {
         let mut storage = Box::new(AnyfuncTable {
             backing: vec![vm::Anyfunc::null(); initial_table_backing_len],
             max: desc.maximum,
+            instances: vec![],
+            host_funcs: HashMap::new(),
         });
 
         let storage_ptr: *mut AnyfuncTable = &mut *storage;
@@ -75,6 +195,79 @@ impl AnyfuncTable {
         self.backing.len() as u32
     }
 
+    /// Records that the instance of `vmctx` uses this table.
+    pub fn attach(&mut self, vmctx: NonNull<vm::Ctx>) {
+        self.instances.push(vmctx);
+    }
+
+    /// Records that the instance of `vmctx` no longer uses this
+    /// table, because it's being dropped.
+    ///
+    /// The host functions called with the context of that instance
+    /// are called with the context of the next instance using the
+    /// table instead. They are removed from the table when no
+    /// instance uses it anymore.
+    pub fn detach(&mut self, vmctx: NonNull<vm::Ctx>) {
+        self.instances.retain(|&instance| instance != vmctx);
+
+        match self.instances.first() {
+            Some(&next) => {
+                for host_func in self.host_funcs.values_mut() {
+                    if host_func.func_ctx.vmctx == vmctx {
+                        host_func.func_ctx.vmctx = next;
+                    }
+                }
+                for anyfunc in &mut self.backing {
+                    if anyfunc.ctx == vmctx.as_ptr() && self.host_funcs.contains_key(&anyfunc.func)
+                    {
+                        anyfunc.ctx = next.as_ptr();
+                    }
+                }
+            }
+            None => {
+                for anyfunc in &mut self.backing {
+                    if self.host_funcs.contains_key(&anyfunc.func) {
+                        *anyfunc = vm::Anyfunc::null();
+                    }
+                }
+                self.host_funcs.clear();
+            }
+        }
+    }
+
+    /// Returns the context that the host function `func` set in this
+    /// table is called with.
+    pub fn host_func_ctx(&self, func: *const vm::Func) -> Option<NonNull<vm::FuncCtx>> {
+        self.host_funcs
+            .get(&func)
+            .map(|host_func| NonNull::from(&*host_func.func_ctx))
+    }
+
+    /// Releases the host function held by the slot at `index`, if any,
+    /// before the slot is overwritten.
+    fn release(&mut self, index: usize) {
+        let func = self.backing[index].func;
+        let unused = match self.host_funcs.get_mut(&func) {
+            Some(host_func) => {
+                host_func.slots -= 1;
+                host_func.slots == 0
+            }
+            None => false,
+        };
+        if unused {
+            self.host_funcs.remove(&func);
+        }
+    }
+
+    pub fn get(&self, index: u32) -> Option<Anyfunc<'static>> {
+        self.backing
+            .get(index as usize)
+            .filter(|anyfunc| !anyfunc.func.is_null())
+            .map(|&anyfunc| Anyfunc {
+                inner: AnyfuncInner::Raw(anyfunc),
+            })
+    }
+
     pub fn internal_buffer(&mut self) -> &mut [vm::Anyfunc] {
         &mut self.backing
     }
@@ -99,7 +292,7 @@ impl AnyfuncTable {
     }
 
     pub fn set(&mut self, index: u32, element: Anyfunc) -> Result<(), ()> {
-        if let Some(slot) = self.backing.get_mut(index as usize) {
+        if (index as usize) < self.backing.len() {
             let anyfunc = match element.inner {
                 AnyfuncInner::Host { ptr, signature } => {
                     let sig_index = SigRegistry.lookup_sig_index(signature);
@@ -111,6 +304,47 @@ impl AnyfuncTable {
                         sig_id,
                     }
                 }
+                AnyfuncInner::HostFunc {
+                    func,
+                    func_env,
+                    env_owner,
+                    signature,
+                } => {
+                    // Host functions find their environment through the
+                    // context they are called with, so they can only be
+                    // set once the table belongs to an instance.
+                    let vmctx = *self.instances.first().ok_or(())?;
+                    let sig_index = SigRegistry.lookup_sig_index(signature);
+                    let sig_id = vm::SigId(sig_index.index() as u32);
+
+                    self.release(index as usize);
+
+                    // The wrapper of a host function only knows its own
+                    // pointer, so the functions sharing a pointer share
+                    // an environment too, the latest one set.
+                    let host_func =
+                        self.host_funcs
+                            .entry(func.as_ptr())
+                            .or_insert_with(|| HostFunc {
+                                func_ctx: Box::new(vm::FuncCtx {
+                                    vmctx,
+                                    func_env: None,
+                                }),
+                                _env_owner: None,
+                                slots: 0,
+                            });
+                    host_func.func_ctx.func_env = func_env;
+                    host_func._env_owner = env_owner;
+                    host_func.slots += 1;
+
+                    self.backing[index as usize] = vm::Anyfunc {
+                        func: func.as_ptr(),
+                        ctx: host_func.func_ctx.vmctx.as_ptr(),
+                        sig_id,
+                    };
+
+                    return Ok(());
+                }
                 AnyfuncInner::Managed(ref func) => {
                     let sig_index = SigRegistry.lookup_sig_index(Arc::clone(&func.signature));
                     let sig_id = vm::SigId(sig_index.index() as u32);
@@ -121,9 +355,17 @@ impl AnyfuncTable {
                         sig_id,
                     }
                 }
+                AnyfuncInner::Raw(anyfunc) => {
+                    // A host function copied from another slot.
+                    if let Some(host_func) = self.host_funcs.get_mut(&anyfunc.func) {
+                        host_func.slots += 1;
+                    }
+                    anyfunc
+                }
             };
 
-            *slot = anyfunc;
+            self.release(index as usize);
+            self.backing[index as usize] = anyfunc;
 
             Ok(())
         } else {
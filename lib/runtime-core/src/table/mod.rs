@@ -31,10 +31,17 @@ pub enum TableStorage {
     Anyfunc(Box<AnyfuncTable>),
 }
 
+/// A callback invoked before a table grows, with its current and its requested size.
+///
+/// Returning `false` vetoes the growth: `Table::grow` then returns `GrowError::Vetoed`. The
+/// callback is invoked while the table is being grown, so it must not access the table itself.
+pub type GrowObserver = Arc<dyn Fn(u32, u32) -> bool + Send + Sync>;
+
 /// Container with a descriptor and a reference to a table storage.
 pub struct Table {
     desc: TableDescriptor,
     storage: Arc<Mutex<(TableStorage, vm::LocalTable)>>,
+    grow_observer: Arc<Mutex<Option<GrowObserver>>>,
 }
 
 impl Table {
@@ -81,6 +88,7 @@ impl Table {
         Ok(Self {
             desc,
             storage: Arc::new(Mutex::new((storage, local))),
+            grow_observer: Arc::new(Mutex::new(None)),
         })
     }
 
@@ -89,7 +97,24 @@ impl Table {
         self.desc
     }
 
+    /// Get the element at index, or `None` if the index is out of
+    /// bounds or the element is null.
+    pub fn get(&self, index: u32) -> Option<Element<'static>> {
+        let storage = self.storage.lock().unwrap();
+        match &*storage {
+            (TableStorage::Anyfunc(ref anyfunc_table), _) => {
+                anyfunc_table.get(index).map(Element::Anyfunc)
+            }
+        }
+    }
+
     /// Set the element at index.
+    ///
+    /// A host function, made with `Func::new`, can only be set once the
+    /// table is used by an instance. It is called with the context of
+    /// the first instance using the table, or of the next one once that
+    /// instance is dropped. Setting another element in its slot drops
+    /// the host function.
     pub fn set(&self, index: u32, element: Element) -> Result<(), ()> {
         let mut storage = self.storage.lock().unwrap();
         match &mut *storage {
@@ -112,6 +137,30 @@ impl Table {
         }
     }
 
+    /// Records that the instance of `vmctx` uses this table.
+    pub(crate) fn attach(&self, vmctx: ptr::NonNull<vm::Ctx>) {
+        let mut storage = self.storage.lock().unwrap();
+        match &mut *storage {
+            (TableStorage::Anyfunc(ref mut anyfunc_table), _) => anyfunc_table.attach(vmctx),
+        }
+    }
+
+    /// Records that the instance of `vmctx` no longer uses this table.
+    pub(crate) fn detach(&self, vmctx: ptr::NonNull<vm::Ctx>) {
+        let mut storage = self.storage.lock().unwrap();
+        match &mut *storage {
+            (TableStorage::Anyfunc(ref mut anyfunc_table), _) => anyfunc_table.detach(vmctx),
+        }
+    }
+
+    /// Returns the context of a host function set in this table.
+    pub(crate) fn host_func_ctx(&self, func: *const vm::Func) -> Option<ptr::NonNull<vm::FuncCtx>> {
+        let storage = self.storage.lock().unwrap();
+        match &*storage {
+            (TableStorage::Anyfunc(ref anyfunc_table), _) => anyfunc_table.host_func_ctx(func),
+        }
+    }
+
     /// The current size of this table.
     pub fn size(&self) -> u32 {
         let storage = self.storage.lock().unwrap();
@@ -128,12 +177,27 @@ impl Table {
 
         let mut storage = self.storage.lock().unwrap();
         match &mut *storage {
-            (TableStorage::Anyfunc(ref mut anyfunc_table), ref mut local) => anyfunc_table
-                .grow(delta, local)
-                .ok_or(GrowError::TableGrowError),
+            (TableStorage::Anyfunc(ref mut anyfunc_table), ref mut local) => {
+                if let Some(observer) = &*self.grow_observer.lock().unwrap() {
+                    let current = anyfunc_table.current_size();
+                    let desired = current.saturating_add(delta);
+                    if !observer(current, desired) {
+                        return Err(GrowError::Vetoed);
+                    }
+                }
+                anyfunc_table
+                    .grow(delta, local)
+                    .ok_or(GrowError::TableGrowError)
+            }
         }
     }
 
+    /// Sets the callback invoked before this table grows, or removes it with `None`,
+    /// replacing the previous one.
+    pub fn set_grow_observer(&self, observer: Option<GrowObserver>) {
+        *self.grow_observer.lock().unwrap() = observer;
+    }
+
     /// Get a mutable pointer to underlying table storage.
     pub fn vm_local_table(&mut self) -> *mut vm::LocalTable {
         let mut storage = self.storage.lock().unwrap();
@@ -141,6 +205,22 @@ impl Table {
     }
 }
 
+/// Looks up the context of a host function set in one of the tables of
+/// the instance `vmctx` belongs to. An instance has one table at most
+/// until reference types are supported, so this is a single lookup.
+pub(crate) unsafe fn host_func_ctx(
+    vmctx: &vm::Ctx,
+    func: *const vm::Func,
+) -> Option<ptr::NonNull<vm::FuncCtx>> {
+    let local_tables = (*vmctx.local_backing).tables.iter();
+    let imported_tables = (*vmctx.import_backing).tables.iter();
+
+    local_tables
+        .map(|(_, table)| table)
+        .chain(imported_tables.map(|(_, table)| table))
+        .find_map(|table| table.host_func_ctx(func))
+}
+
 impl IsExport for Table {
     fn to_export(&self) -> Export {
         Export::Table(self.clone())
@@ -152,6 +232,7 @@ impl Clone for Table {
         Self {
             desc: self.desc,
             storage: Arc::clone(&self.storage),
+            grow_observer: Arc::clone(&self.grow_observer),
         }
     }
 }
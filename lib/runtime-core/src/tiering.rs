@@ -1,18 +1,28 @@
 //! The tiering module supports switching between code compiled with different optimization levels
 //! as runtime.
-use crate::backend::{Compiler, CompilerConfig};
+//!
+//! Functions switch over one by one: the baseline code counts the entries of each function with
+//! the [`HotnessCounter`] middleware, and the functions entered often enough are patched to call
+//! their optimized code instead.
+//!
+//! [`HotnessCounter`]: struct.HotnessCounter.html
+use crate::backend::{Backend, Compiler, CompilerConfig};
+use crate::codegen::{BreakpointInfo, Event, EventSink, FunctionMiddleware, InternalEvent};
 use crate::compile_with_config;
 use crate::fault::{
     catch_unsafe_unwind, ensure_sighandler, pop_code_version, push_code_version, with_ctx,
 };
 use crate::fault::{set_wasm_interrupt_on_ctx, was_sigint_triggered_fault};
-use crate::import::ImportObject;
 use crate::instance::Instance;
 use crate::module::{Module, ModuleInfo};
 use crate::state::{x64::invoke_call_return_on_stack, CodeVersion, InstanceImage};
-use crate::vm::Ctx;
+use crate::structures::TypedIndex;
+use crate::types::LocalFuncIndex;
+use crate::vm::{self, Ctx, InternalField};
 
-use std::cell::Cell;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::sync::mpsc::{self, Sender};
 use std::sync::{Arc, Mutex};
 
 struct Defer<F: FnOnce()>(Option<F>);
@@ -30,6 +40,151 @@ pub enum ShellExitOperation {
     ContinueWith(InstanceImage),
 }
 
+/// Condition for switching a function over to its optimized code.
+///
+/// The baseline code must be compiled with the [`HotnessCounter`] middleware.
+///
+/// [`HotnessCounter`]: struct.HotnessCounter.html
+pub struct HotnessThreshold {
+    /// Number of entries after which a function is considered hot.
+    pub entries: u64,
+}
+
+/// The internal field holding the address of the entry counts of the local functions.
+static ENTRY_COUNTS_FIELD: InternalField = InternalField::allocate();
+
+/// The internal field counting the function entries since the hotness was last checked.
+static ENTRIES_SINCE_CHECK_FIELD: InternalField = InternalField::allocate();
+
+/// HotnessCounter is a compiler middleware that counts the entries of each local function, and
+/// lets the tiering controller check which functions are hot every `check_period` entries.
+///
+/// The counts are only kept while the instance runs with `run_tiering`.
+pub struct HotnessCounter {
+    check_period: u64,
+}
+
+impl HotnessCounter {
+    /// Creates a new `HotnessCounter` middleware, checking the hotness of the functions every
+    /// `check_period` function entries.
+    pub fn new(check_period: u64) -> HotnessCounter {
+        HotnessCounter {
+            check_period: check_period.max(1),
+        }
+    }
+}
+
+impl FunctionMiddleware for HotnessCounter {
+    type Error = String;
+    fn feed_event<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        _module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+    ) -> Result<(), Self::Error> {
+        let begun = match op {
+            Event::Internal(InternalEvent::FunctionBegin(id)) => Some(id),
+            _ => None,
+        };
+        sink.push(op);
+        if let Some(id) = begun {
+            sink.push(Event::Internal(InternalEvent::IncrementCounter(
+                ENTRY_COUNTS_FIELD.index() as _,
+                id,
+            )));
+            sink.push(Event::Internal(InternalEvent::AddInternal(
+                ENTRIES_SINCE_CHECK_FIELD.index() as _,
+                1,
+            )));
+            sink.push(Event::Internal(InternalEvent::BreakpointIfInternalAtLeast(
+                ENTRIES_SINCE_CHECK_FIELD.index() as _,
+                self.check_period,
+                Box::new(check_hotness),
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The tiering of the instance running on a thread.
+struct Controller {
+    ctx: *mut Ctx,
+    /// The entries of each local function, counted by the baseline code.
+    entry_counts: Box<[Cell<u64>]>,
+    /// The entries after which a function is hot, or `None` if all of them are.
+    threshold: Option<u64>,
+    hot: Vec<bool>,
+    /// Starts the optimizing compilation once a function is hot.
+    start_optimizing: Option<Sender<()>>,
+    /// The addresses of the local functions in the latest optimized code.
+    optimized: Option<Vec<usize>>,
+}
+
+thread_local! {
+    static CONTROLLER: RefCell<Option<Controller>> = RefCell::new(None);
+}
+
+impl Controller {
+    /// Finds the functions that got hot, and switches them over to the optimized code if it is
+    /// ready.
+    unsafe fn update(&mut self) {
+        let mut got_hot = false;
+        for (index, count) in self.entry_counts.iter().enumerate() {
+            if !self.hot[index]
+                && self
+                    .threshold
+                    .map_or(true, |entries| count.get() >= entries)
+            {
+                self.hot[index] = true;
+                got_hot = true;
+                if let Some(ref optimized) = self.optimized {
+                    patch_function(self.ctx, index, optimized[index]);
+                }
+            }
+        }
+        if got_hot {
+            if let Some(start_optimizing) = self.start_optimizing.take() {
+                let _ = start_optimizing.send(());
+            }
+        }
+    }
+
+    /// Switches the hot functions over to the code at `optimized`.
+    unsafe fn adopt(&mut self, optimized: Vec<usize>) {
+        for (index, &hot) in self.hot.iter().enumerate() {
+            if hot {
+                patch_function(self.ctx, index, optimized[index]);
+            }
+        }
+        self.optimized = Some(optimized);
+    }
+}
+
+/// Makes the calls to the local function `index` of the instance of `ctx` run the code at
+/// `target`, whether they come from its code or through its table of local functions.
+unsafe fn patch_function(ctx: *mut Ctx, index: usize, target: usize) {
+    (*(*ctx).module)
+        .runnable_module
+        .patch_local_function(index, target);
+    (*(*ctx).local_backing).local_functions[LocalFuncIndex::new(index)] = target as *const vm::Func;
+}
+
+/// Called by the code compiled with `HotnessCounter` every `check_period` function entries.
+fn check_hotness(info: BreakpointInfo) -> Result<(), Box<dyn Any>> {
+    if let Some(ctx) = info.ctx {
+        ctx.set_internal(&ENTRIES_SINCE_CHECK_FIELD, 0);
+        let ctx = ctx as *mut Ctx;
+        CONTROLLER.with(|controller| {
+            if let Some(controller) = controller.borrow_mut().as_mut() {
+                if controller.ctx == ctx {
+                    unsafe { controller.update() };
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
 /// Context for an interactive shell.
 pub struct InteractiveShellContext {
     /// Optional instance image.
@@ -78,16 +233,33 @@ unsafe fn do_optimize(
 }
 
 /// Runs an instance with tiering.
+///
+/// The module is recompiled with each of `optimized_backends` in the background, and the
+/// functions of the baseline instance are switched over to the optimized code once it is ready.
+/// If `hotness` is set, the optimizing compilation only starts once a function is hot, and only
+/// the hot functions are switched over, as they get hot. Otherwise, all of them are switched
+/// over together. The baseline code counts the entries of its functions if it is compiled with
+/// the `HotnessCounter` middleware, which `hotness` requires.
+///
+/// The baseline instance must be compiled with the singlepass backend, which is the only one
+/// that can be switched over in the middle of a call.
 pub unsafe fn run_tiering<F: Fn(InteractiveShellContext) -> ShellExitOperation>(
     module_info: &ModuleInfo,
     wasm_binary: &[u8],
     mut resume_image: Option<InstanceImage>,
-    import_object: &ImportObject,
     start_raw: extern "C" fn(&mut Ctx),
     baseline: &mut Instance,
     optimized_backends: Vec<Box<dyn Fn() -> Box<dyn Compiler> + Send>>,
+    hotness: Option<HotnessThreshold>,
     interactive_shell: F,
 ) -> Result<(), String> {
+    if baseline.module.info.backend != Backend::Singlepass {
+        return Err(format!(
+            "tiering needs a singlepass baseline, but the instance was compiled with {}",
+            baseline.module.info.backend.to_string()
+        ));
+    }
+
     ensure_sighandler();
 
     let ctx_box = Arc::new(Mutex::new(CtxWrapper(baseline.context_mut() as *mut _)));
@@ -102,11 +274,16 @@ pub unsafe fn run_tiering<F: Fn(InteractiveShellContext) -> ShellExitOperation>(
         outcome: Mutex::new(None),
     });
 
+    let (start_optimizing, optimizing_started) = mpsc::channel();
     {
         let wasm_binary = wasm_binary.to_vec();
         let ctx_box = ctx_box.clone();
         let opt_state = opt_state.clone();
         ::std::thread::spawn(move || {
+            // The sender is dropped with the controller if no function ever gets hot.
+            if optimizing_started.recv().is_err() {
+                return;
+            }
             for backend in optimized_backends {
                 if !ctx_box.lock().unwrap().0.is_null() {
                     do_optimize(&wasm_binary, backend(), &ctx_box, &opt_state);
@@ -115,7 +292,34 @@ pub unsafe fn run_tiering<F: Fn(InteractiveShellContext) -> ShellExitOperation>(
         });
     }
 
-    let mut optimized_instances: Vec<Instance> = vec![];
+    let n_local_functions = module_info.func_assoc.len() - module_info.imported_functions.len();
+    let entry_counts: Box<[Cell<u64>]> = (0..n_local_functions).map(|_| Cell::new(0)).collect();
+    baseline
+        .context_mut()
+        .set_internal(&ENTRY_COUNTS_FIELD, entry_counts.as_ptr() as usize as u64);
+    CONTROLLER.with(|controller| {
+        let mut controller = controller.borrow_mut();
+        *controller = Some(Controller {
+            ctx: baseline.context_mut() as *mut _,
+            entry_counts,
+            threshold: hotness.map(|threshold| threshold.entries),
+            hot: vec![false; n_local_functions],
+            start_optimizing: Some(start_optimizing),
+            optimized: None,
+        });
+        // Without a threshold, every function is hot from the start.
+        controller.as_mut().unwrap().update();
+    });
+    let _deferred_controller_cleanup: Defer<_> = {
+        let ctx = baseline.context_mut() as *mut Ctx;
+        Defer(Some(move || {
+            (*ctx).set_internal(&ENTRY_COUNTS_FIELD, 0);
+            CONTROLLER.with(|controller| controller.borrow_mut().take());
+        }))
+    };
+
+    // Keep the optimized code alive.
+    let mut optimized_modules: Vec<Module> = vec![];
 
     push_code_version(CodeVersion {
         baseline: true,
@@ -135,61 +339,33 @@ pub unsafe fn run_tiering<F: Fn(InteractiveShellContext) -> ShellExitOperation>(
     }));
 
     loop {
-        let new_optimized: Option<&mut Instance> = {
-            let mut outcome = opt_state.outcome.lock().unwrap();
-            if let Some(x) = outcome.take() {
-                let instance = x
-                    .module
-                    .instantiate(&import_object)
-                    .map_err(|e| format!("Can't instantiate module: {:?}", e))?;
-                // Keep the optimized code alive.
-                optimized_instances.push(instance);
-                optimized_instances.last_mut()
-            } else {
-                None
-            }
-        };
-        if let Some(optimized) = new_optimized {
-            let base = module_info.imported_functions.len();
-            let code_ptr = optimized
-                .module
-                .runnable_module
-                .get_code()
-                .unwrap()
-                .as_ptr() as usize;
+        let new_optimized = opt_state.outcome.lock().unwrap().take();
+        if let Some(OptimizationOutcome { module }) = new_optimized {
+            let optimized = &module.inner.runnable_module;
+            let code_ptr = optimized.get_code().unwrap().as_ptr() as usize;
             let target_addresses: Vec<usize> = optimized
-                .module
-                .runnable_module
                 .get_local_function_offsets()
                 .unwrap()
                 .into_iter()
                 .map(|x| code_ptr + x)
                 .collect();
-            assert_eq!(target_addresses.len(), module_info.func_assoc.len() - base);
-            for i in base..module_info.func_assoc.len() {
-                baseline
-                    .module
-                    .runnable_module
-                    .patch_local_function(i - base, target_addresses[i - base]);
-            }
+            assert_eq!(target_addresses.len(), n_local_functions);
 
             push_code_version(CodeVersion {
                 baseline: false,
-                msm: optimized
-                    .module
-                    .runnable_module
-                    .get_module_state_map()
-                    .unwrap(),
-                base: optimized
-                    .module
-                    .runnable_module
-                    .get_code()
-                    .unwrap()
-                    .as_ptr() as usize,
+                msm: optimized.get_module_state_map().unwrap(),
+                base: code_ptr,
             });
             n_versions.set(n_versions.get() + 1);
 
-            baseline.context_mut().local_functions = optimized.context_mut().local_functions;
+            CONTROLLER.with(|controller| {
+                controller
+                    .borrow_mut()
+                    .as_mut()
+                    .unwrap()
+                    .adopt(target_addresses)
+            });
+            optimized_modules.push(module);
         }
         // Assuming we do not want to do breakpoint-based debugging on optimized backends.
         let breakpoints = baseline.module.runnable_module.get_breakpoints();
@@ -0,0 +1,419 @@
+//! Trampoline generator for carrying context with function pointer, on AArch64.
+//!
+//! Only the callinfo trampolines of `DynamicFunc`s are generated here: they pass the context
+//! to their target as a parameter, so unlike the x86-64 context trampolines, they don't need a
+//! register that is preserved across the call to hold it.
+
+use crate::loader::CodeMemory;
+use crate::types::Type;
+use std::cmp;
+use std::fmt;
+
+/// An opaque type for context pointers.
+pub enum CallContext {}
+
+/// An opaque type for generated trampolines' call entries.
+pub enum Trampoline {}
+
+/// Trampoline Buffer Builder.
+pub struct TrampolineBufferBuilder {
+    code: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+/// Trampoline Buffer.
+pub struct TrampolineBuffer {
+    code: CodeMemory,
+    offsets: Vec<usize>,
+}
+
+/// The scratch register the trampolines use, `x9`.
+const SCRATCH: u32 = 9;
+/// The frame pointer, `x29`.
+const FP: u32 = 29;
+/// The stack pointer, encoded as register 31 in the instructions taking it.
+const SP: u32 = 31;
+
+/// Returns the size of the stack slot of a parameter of type `ty` passed on the stack.
+///
+/// AAPCS64 gives every stack parameter 8 bytes, while Apple's variant only aligns them to
+/// their natural size.
+fn stack_slot_size(ty: Type) -> u32 {
+    if cfg!(target_os = "macos") {
+        match ty {
+            Type::I32 | Type::F32 => 4,
+            _ => 8,
+        }
+    } else {
+        8
+    }
+}
+
+impl TrampolineBufferBuilder {
+    /// Creates a new empty `TrampolineBufferBuilder`.
+    pub fn new() -> TrampolineBufferBuilder {
+        TrampolineBufferBuilder {
+            code: vec![],
+            offsets: vec![],
+        }
+    }
+
+    fn emit(&mut self, inst: u32) {
+        self.code.extend_from_slice(&inst.to_le_bytes());
+    }
+
+    /// Emits `movz`/`movk` instructions loading the 64-bit `value` into `xd`.
+    fn emit_mov_imm64(&mut self, rd: u32, value: u64) {
+        // movz x?, #?
+        self.emit(0xd280_0000 | ((value & 0xffff) as u32) << 5 | rd);
+        for hw in 1..4 {
+            let imm = ((value >> (hw * 16)) & 0xffff) as u32;
+            if imm != 0 {
+                // movk x?, #?, lsl #?
+                self.emit(0xf280_0000 | (hw as u32) << 21 | imm << 5 | rd);
+            }
+        }
+    }
+
+    /// Emits a load or a store of `rt` at the unsigned offset `offset` from `rn`, `base` being
+    /// the encoding of the instruction for an offset of 0, and `size` the size of the access.
+    fn emit_load_store(&mut self, base: u32, rt: u32, rn: u32, offset: u32, size: u32) {
+        assert!(
+            offset % size == 0 && offset / size < 4096,
+            "too many parameters for a callinfo trampoline"
+        );
+        self.emit(base | (offset / size) << 10 | rn << 5 | rt);
+    }
+
+    /// Adds a callinfo trampoline for a function whose parameters have the types `params`, and
+    /// whose results have the types `returns`.
+    ///
+    /// This generates a trampoline function that collects its parameters into an array, and
+    /// passes the array into `target` as the second argument when called. The first argument of
+    /// `target` is the `context` specified here.
+    ///
+    /// Each parameter is read from where AAPCS64 passes a value of its type, and takes 8 bytes
+    /// of the array. `target` writes the results over the array, 8 bytes each, and they are
+    /// returned the way the backends return multiple values: integers in `x0` then `x1`, and
+    /// floats in `d0` then `d1`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is a `v128` value, or more than two integer or float results.
+    pub fn add_typed_callinfo_trampoline(
+        &mut self,
+        target: unsafe extern "C" fn(*const CallContext, *mut u64),
+        context: *const CallContext,
+        params: &[Type],
+        returns: &[Type],
+    ) -> usize {
+        assert!(
+            !params.iter().chain(returns).any(|ty| *ty == Type::V128),
+            "v128 values are not supported by callinfo trampolines"
+        );
+        let float_returns = returns
+            .iter()
+            .filter(|ty| **ty == Type::F32 || **ty == Type::F64)
+            .count();
+        assert!(
+            float_returns <= 2 && returns.len() - float_returns <= 2,
+            "at most two integer and two float results are returned in registers"
+        );
+
+        let idx = self.offsets.len();
+        self.offsets.push(self.code.len());
+
+        // The array is at the bottom of the frame, which keeps `sp` 16-byte aligned.
+        let slots = cmp::max(params.len(), returns.len()) as u32;
+        let frame_size = (slots.checked_mul(8).unwrap() + 15) & !15;
+        assert!(
+            frame_size < 1 << 24,
+            "too many parameters for a callinfo trampoline"
+        );
+
+        self.emit(0xa9bf_7bfd); // stp x29, x30, [sp, #-16]!
+        self.emit(0x9100_03fd); // mov x29, sp
+        if frame_size >> 12 != 0 {
+            self.emit(0xd140_03ff | (frame_size >> 12) << 10); // sub sp, sp, #?, lsl #12
+        }
+        if frame_size & 0xfff != 0 {
+            self.emit(0xd100_03ff | (frame_size & 0xfff) << 10); // sub sp, sp, #?
+        }
+
+        let (mut gprs, mut fprs, mut stack_offset) = (0u32, 0u32, 0u32);
+        for (i, ty) in params.iter().enumerate() {
+            let offset = i as u32 * 8;
+            match ty {
+                Type::I32 | Type::I64 if gprs < 8 => {
+                    // str x?, [sp, #?]
+                    self.emit_load_store(0xf900_0000, gprs, SP, offset, 8);
+                    gprs += 1;
+                }
+                Type::F32 | Type::F64 if fprs < 8 => {
+                    // str d?, [sp, #?]
+                    self.emit_load_store(0xfd00_0000, fprs, SP, offset, 8);
+                    fprs += 1;
+                }
+                _ => {
+                    // The stack parameters start above the saved frame pointer and link
+                    // register.
+                    let size = stack_slot_size(*ty);
+                    stack_offset = (stack_offset + size - 1) & !(size - 1);
+                    if size == 4 {
+                        // ldr w9, [x29, #?]
+                        self.emit_load_store(0xb940_0000, SCRATCH, FP, 16 + stack_offset, 4);
+                    } else {
+                        // ldr x9, [x29, #?]
+                        self.emit_load_store(0xf940_0000, SCRATCH, FP, 16 + stack_offset, 8);
+                    }
+                    // str x9, [sp, #?]
+                    self.emit_load_store(0xf900_0000, SCRATCH, SP, offset, 8);
+                    stack_offset += size;
+                }
+            }
+        }
+
+        self.emit_mov_imm64(0, context as u64); // mov x0, ?
+        self.emit(0x9100_03e1); // mov x1, sp
+        self.emit_mov_imm64(SCRATCH, target as usize as u64); // mov x9, ?
+        self.emit(0xd63f_0000 | SCRATCH << 5); // blr x9
+
+        let (mut gprs, mut fprs) = (0u32, 0u32);
+        for (i, ty) in returns.iter().enumerate() {
+            let offset = i as u32 * 8;
+            match ty {
+                Type::F32 | Type::F64 => {
+                    // `s?` is the low half of `d?`, so this also returns `f32`s.
+                    // ldr d?, [sp, #?]
+                    self.emit_load_store(0xfd40_0000, fprs, SP, offset, 8);
+                    fprs += 1;
+                }
+                _ => {
+                    // ldr x?, [sp, #?]
+                    self.emit_load_store(0xf940_0000, gprs, SP, offset, 8);
+                    gprs += 1;
+                }
+            }
+        }
+
+        self.emit(0x9100_03bf); // mov sp, x29
+        self.emit(0xa8c1_7bfd); // ldp x29, x30, [sp], #16
+        self.emit(0xd65f_03c0); // ret
+        idx
+    }
+
+    /// Consumes the builder and builds the trampoline buffer.
+    pub fn build(self) -> TrampolineBuffer {
+        let mut code = CodeMemory::new(self.code.len());
+        code[..self.code.len()].copy_from_slice(&self.code);
+        code.make_executable();
+        TrampolineBuffer {
+            code,
+            offsets: self.offsets,
+        }
+    }
+}
+
+impl TrampolineBuffer {
+    /// Returns the trampoline pointer at index `idx`.
+    pub fn get_trampoline(&self, idx: usize) -> *const Trampoline {
+        &self.code[self.offsets[idx]] as *const u8 as *const Trampoline
+    }
+}
+
+impl fmt::Debug for TrampolineBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TrampolineBuffer {{}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{mem, ptr, slice};
+
+    #[test]
+    fn test_typed_callinfo_trampoline() {
+        struct TestContext {
+            value: f64,
+        }
+        unsafe extern "C" fn do_add(ctx: *const CallContext, args: *mut u64) {
+            let ctx = &*(ctx as *const TestContext);
+            let args: &mut [u64] = slice::from_raw_parts_mut(args, 12);
+            let sum = args
+                .iter()
+                .enumerate()
+                .fold(ctx.value, |sum, (i, x)| match i % 3 {
+                    0 => sum + *x as i32 as f64,
+                    1 => sum + f64::from_bits(*x),
+                    _ => sum + f64::from(f32::from_bits(*x as u32)),
+                });
+            args[0] = sum.to_bits();
+        }
+        let params: Vec<Type> = (0..4)
+            .flat_map(|_| vec![Type::I32, Type::F64, Type::F32])
+            .collect();
+        let mut builder = TrampolineBufferBuilder::new();
+        let ctx = TestContext { value: 100.0 };
+        let idx = builder.add_typed_callinfo_trampoline(
+            do_add,
+            &ctx as *const TestContext as *const _,
+            &params,
+            &[Type::F64],
+        );
+        let buf = builder.build();
+        let t = buf.get_trampoline(idx);
+        let ret = unsafe {
+            mem::transmute::<
+                _,
+                extern "C" fn(i32, f64, f32, i32, f64, f32, i32, f64, f32, i32, f64, f32) -> f64,
+            >(t)(1, 2.0, 3.0, 4, 5.0, 6.0, 7, 8.0, 9.0, 10, 11.0, 12.0)
+        };
+        assert_eq!(ret, 178.0);
+    }
+
+    #[test]
+    fn test_typed_callinfo_trampoline_multi_value() {
+        #[repr(C)]
+        #[derive(Debug, PartialEq)]
+        struct IntInt(i64, i64);
+        #[repr(C)]
+        #[derive(Debug, PartialEq)]
+        struct DoubleDouble(f64, f64);
+
+        // Returns the sum and the product of its parameters.
+        unsafe extern "C" fn sum_and_product(_: *const CallContext, args: *mut u64) {
+            let args: &mut [u64] = slice::from_raw_parts_mut(args, 2);
+            let (a, b) = (args[0] as i64, args[1] as i64);
+            args[0] = (a + b) as u64;
+            args[1] = (a * b) as u64;
+        }
+        unsafe extern "C" fn halves(_: *const CallContext, args: *mut u64) {
+            let args: &mut [u64] = slice::from_raw_parts_mut(args, 2);
+            let a = args[0] as i64;
+            args[0] = (a as f64 / 2.0).to_bits();
+            args[1] = (a as f64 / 4.0).to_bits();
+        }
+
+        let mut builder = TrampolineBufferBuilder::new();
+        let ints = builder.add_typed_callinfo_trampoline(
+            sum_and_product,
+            ptr::null(),
+            &[Type::I64, Type::I64],
+            &[Type::I64, Type::I64],
+        );
+        let floats = builder.add_typed_callinfo_trampoline(
+            halves,
+            ptr::null(),
+            &[Type::I64],
+            &[Type::F64, Type::F64],
+        );
+        let buf = builder.build();
+
+        // Structs of two integers are returned in `x0` and `x1`, and structs of two doubles in
+        // `d0` and `d1`.
+        let ret = unsafe {
+            mem::transmute::<_, extern "C" fn(i64, i64) -> IntInt>(buf.get_trampoline(ints))(3, 4)
+        };
+        assert_eq!(ret, IntInt(7, 12));
+        let ret = unsafe {
+            mem::transmute::<_, extern "C" fn(i64) -> DoubleDouble>(buf.get_trampoline(floats))(5)
+        };
+        assert_eq!(ret, DoubleDouble(2.5, 1.25));
+    }
+
+    #[test]
+    fn test_typed_callinfo_trampoline_stack_params() {
+        // Weighs each parameter by its position, so that reading them out of order changes
+        // the sum.
+        unsafe extern "C" fn weighted_sum(_: *const CallContext, args: *mut u64) {
+            let args: &mut [u64] = slice::from_raw_parts_mut(args, 20);
+            let sum = args.iter().enumerate().fold(0.0, |sum, (i, x)| {
+                let value = match i {
+                    18 => f64::from(*x as i32),
+                    19 => f64::from(f32::from_bits(*x as u32)),
+                    _ if i % 2 == 0 => *x as i64 as f64,
+                    _ => f64::from_bits(*x),
+                };
+                sum + value * (i + 1) as f64
+            });
+            args[0] = sum.to_bits();
+        }
+
+        // The last two integers and the last two floats don't fit in registers, and are
+        // passed on the stack in the order of the parameters.
+        let params: Vec<Type> = (0..20)
+            .map(|i| match i {
+                18 => Type::I32,
+                19 => Type::F32,
+                _ if i % 2 == 0 => Type::I64,
+                _ => Type::F64,
+            })
+            .collect();
+        let mut builder = TrampolineBufferBuilder::new();
+        let idx =
+            builder.add_typed_callinfo_trampoline(weighted_sum, ptr::null(), &params, &[Type::F64]);
+        let buf = builder.build();
+        let ret = unsafe {
+            mem::transmute::<
+                _,
+                extern "C" fn(
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i32,
+                    f32,
+                ) -> f64,
+            >(buf.get_trampoline(idx))(
+                1, 2.0, 3, 4.0, 5, 6.0, 7, 8.0, 9, 10.0, 11, 12.0, 13, 14.0, 15, 16.0, 17, 18.0,
+                19, 20.0,
+            )
+        };
+        // The sum of the squares from 1 to 20.
+        assert_eq!(ret, 2870.0);
+    }
+
+    #[test]
+    fn test_typed_callinfo_trampoline_float_results() {
+        unsafe extern "C" fn to_f32(_: *const CallContext, args: *mut u64) {
+            let a = *args as i32;
+            *args = u64::from((a as f32).to_bits());
+        }
+
+        let mut builder = TrampolineBufferBuilder::new();
+        let idx =
+            builder.add_typed_callinfo_trampoline(to_f32, ptr::null(), &[Type::I32], &[Type::F32]);
+        let buf = builder.build();
+        let ret =
+            unsafe { mem::transmute::<_, extern "C" fn(i32) -> f32>(buf.get_trampoline(idx))(-7) };
+        assert_eq!(ret, -7.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "at most two integer and two float results")]
+    fn test_typed_callinfo_trampoline_too_many_results() {
+        unsafe extern "C" fn nothing(_: *const CallContext, _: *mut u64) {}
+
+        TrampolineBufferBuilder::new().add_typed_callinfo_trampoline(
+            nothing,
+            ptr::null(),
+            &[],
+            &[Type::I32, Type::I64, Type::I32],
+        );
+    }
+}
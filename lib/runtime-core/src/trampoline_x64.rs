@@ -7,9 +7,10 @@
 //! Variadic functions are not supported because `rax` is used by the trampoline code.
 
 use crate::loader::CodeMemory;
+use crate::types::Type;
 use crate::vm::Ctx;
 use std::fmt;
-use std::{mem, slice};
+use std::{cmp, mem, slice};
 
 lazy_static! {
     /// Reads the context pointer from `mm0`.
@@ -219,6 +220,137 @@ impl TrampolineBufferBuilder {
         idx
     }
 
+    /// Adds a callinfo trampoline for a function whose parameters have the types `params`, and
+    /// whose results have the types `returns`.
+    ///
+    /// Unlike `add_callinfo_trampoline`, floating-point parameters are supported: each
+    /// parameter is read from where the System V calling convention passes a value of its type,
+    /// and takes 8 bytes of the array. `target` writes the results over the array, 8 bytes
+    /// each, and they are returned the way the backends return multiple values: integers in
+    /// `rax` then `rdx`, and floats in `xmm0` then `xmm1`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is a `v128` value, or more than two integer or float results.
+    pub fn add_typed_callinfo_trampoline(
+        &mut self,
+        target: unsafe extern "C" fn(*const CallContext, *mut u64),
+        context: *const CallContext,
+        params: &[Type],
+        returns: &[Type],
+    ) -> usize {
+        assert!(
+            !params.iter().chain(returns).any(|ty| *ty == Type::V128),
+            "v128 values are not supported by callinfo trampolines"
+        );
+        let float_returns = returns
+            .iter()
+            .filter(|ty| **ty == Type::F32 || **ty == Type::F64)
+            .count();
+        assert!(
+            float_returns <= 2 && returns.len() - float_returns <= 2,
+            "at most two integer and two float results are returned in registers"
+        );
+
+        let idx = self.offsets.len();
+        self.offsets.push(self.code.len());
+
+        let slots = cmp::max(params.len(), returns.len()) as u32;
+        let mut stack_offset: u32 = slots.checked_mul(8).unwrap();
+        if stack_offset % 16 == 0 {
+            stack_offset += 8;
+        }
+
+        self.code.extend_from_slice(&[0x48, 0x81, 0xec]); // sub ?, %rsp
+        self.code.extend_from_slice(value_to_bytes(&stack_offset));
+        let (mut gprs, mut xmms, mut stack_params) = (0u8, 0u8, 0u32);
+        for (i, ty) in params.iter().enumerate() {
+            let offset = i as u32 * 8;
+            match ty {
+                Type::I32 | Type::I64 if gprs < 6 => {
+                    // mov %?, ?(%rsp)
+                    let prefix: &[u8] = match gprs {
+                        0 => &[0x48, 0x89, 0xbc, 0x24], // rdi
+                        1 => &[0x48, 0x89, 0xb4, 0x24], // rsi
+                        2 => &[0x48, 0x89, 0x94, 0x24], // rdx
+                        3 => &[0x48, 0x89, 0x8c, 0x24], // rcx
+                        4 => &[0x4c, 0x89, 0x84, 0x24], // r8
+                        5 => &[0x4c, 0x89, 0x8c, 0x24], // r9
+                        _ => unreachable!(),
+                    };
+                    self.code.extend_from_slice(prefix);
+                    self.code.extend_from_slice(value_to_bytes(&offset));
+                    gprs += 1;
+                }
+                Type::F32 | Type::F64 if xmms < 8 => {
+                    // movsd %xmm?, ?(%rsp)
+                    self.code
+                        .extend_from_slice(&[0xf2, 0x0f, 0x11, 0x84 | (xmms << 3), 0x24]);
+                    self.code.extend_from_slice(value_to_bytes(&offset));
+                    xmms += 1;
+                }
+                _ => {
+                    self.code.extend_from_slice(&[
+                        0x48, 0x8b, 0x84, 0x24, // mov ?(%rsp), %rax
+                    ]);
+                    self.code.extend_from_slice(value_to_bytes(
+                        &(stack_params * 8 + stack_offset + 8/* ret addr */),
+                    ));
+                    // mov %rax, ?(%rsp)
+                    self.code.extend_from_slice(&[0x48, 0x89, 0x84, 0x24]);
+                    self.code.extend_from_slice(value_to_bytes(&offset));
+                    stack_params += 1;
+                }
+            }
+        }
+        self.code.extend_from_slice(&[
+            0x48, 0xbf, // movabsq ?, %rdi
+        ]);
+        self.code.extend_from_slice(value_to_bytes(&context));
+        self.code.extend_from_slice(&[
+            0x48, 0x89, 0xe6, // mov %rsp, %rsi
+        ]);
+
+        self.code.extend_from_slice(&[
+            0x48, 0xb8, // movabsq ?, %rax
+        ]);
+        self.code.extend_from_slice(value_to_bytes(&target));
+        self.code.extend_from_slice(&[
+            0xff, 0xd0, // callq *%rax
+        ]);
+        let (mut gprs, mut xmms) = (0u8, 0u8);
+        for (i, ty) in returns.iter().enumerate() {
+            let offset = i as u32 * 8;
+            match ty {
+                Type::F32 | Type::F64 => {
+                    // movsd ?(%rsp), %xmm?
+                    self.code
+                        .extend_from_slice(&[0xf2, 0x0f, 0x10, 0x84 | (xmms << 3), 0x24]);
+                    xmms += 1;
+                }
+                _ => {
+                    // mov ?(%rsp), %?
+                    let prefix: &[u8] = match gprs {
+                        0 => &[0x48, 0x8b, 0x84, 0x24], // rax
+                        1 => &[0x48, 0x8b, 0x94, 0x24], // rdx
+                        _ => unreachable!(),
+                    };
+                    self.code.extend_from_slice(prefix);
+                    gprs += 1;
+                }
+            }
+            self.code.extend_from_slice(value_to_bytes(&offset));
+        }
+        self.code.extend_from_slice(&[
+            0x48, 0x81, 0xc4, // add ?, %rsp
+        ]);
+        self.code.extend_from_slice(value_to_bytes(&stack_offset));
+        self.code.extend_from_slice(&[
+            0xc3, //retq
+        ]);
+        idx
+    }
+
     /// Consumes the builder and builds the trampoline buffer.
     pub fn build(self) -> TrampolineBuffer {
         get_context(); // ensure lazy initialization is completed
@@ -249,6 +381,7 @@ impl fmt::Debug for TrampolineBuffer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::ptr;
     #[test]
     fn test_context_trampoline() {
         struct TestContext {
@@ -292,4 +425,210 @@ mod tests {
         };
         assert_eq!(ret, 136);
     }
+    #[test]
+    fn test_typed_callinfo_trampoline() {
+        struct TestContext {
+            value: f64,
+        }
+        unsafe extern "C" fn do_add(ctx: *const CallContext, args: *mut u64) {
+            let ctx = &*(ctx as *const TestContext);
+            let args: &mut [u64] = slice::from_raw_parts_mut(args, 12);
+            let sum = args
+                .iter()
+                .enumerate()
+                .fold(ctx.value, |sum, (i, x)| match i % 3 {
+                    0 => sum + *x as i32 as f64,
+                    1 => sum + f64::from_bits(*x),
+                    _ => sum + f64::from(f32::from_bits(*x as u32)),
+                });
+            args[0] = sum.to_bits();
+        }
+        let params: Vec<Type> = (0..4)
+            .flat_map(|_| vec![Type::I32, Type::F64, Type::F32])
+            .collect();
+        let mut builder = TrampolineBufferBuilder::new();
+        let ctx = TestContext { value: 100.0 };
+        let idx = builder.add_typed_callinfo_trampoline(
+            do_add,
+            &ctx as *const TestContext as *const _,
+            &params,
+            &[Type::F64],
+        );
+        let buf = builder.build();
+        let t = buf.get_trampoline(idx);
+        let ret = unsafe {
+            mem::transmute::<
+                _,
+                extern "C" fn(i32, f64, f32, i32, f64, f32, i32, f64, f32, i32, f64, f32) -> f64,
+            >(t)(1, 2.0, 3.0, 4, 5.0, 6.0, 7, 8.0, 9.0, 10, 11.0, 12.0)
+        };
+        assert_eq!(ret, 178.0);
+    }
+
+    #[test]
+    fn test_typed_callinfo_trampoline_multi_value() {
+        #[repr(C)]
+        #[derive(Debug, PartialEq)]
+        struct IntFloat(i64, f64);
+        #[repr(C)]
+        #[derive(Debug, PartialEq)]
+        struct IntInt(i64, i64);
+
+        // Returns the sum and the product of its parameters.
+        unsafe extern "C" fn sum_and_product(_: *const CallContext, args: *mut u64) {
+            let args: &mut [u64] = slice::from_raw_parts_mut(args, 2);
+            let (a, b) = (args[0] as i64, args[1] as i64);
+            args[0] = (a + b) as u64;
+            args[1] = (a * b) as u64;
+        }
+        unsafe extern "C" fn sum_and_half(_: *const CallContext, args: *mut u64) {
+            let args: &mut [u64] = slice::from_raw_parts_mut(args, 2);
+            let a = args[0] as i64;
+            let b = f64::from_bits(args[1]);
+            args[0] = (a + b as i64) as u64;
+            args[1] = (b / 2.0).to_bits();
+        }
+
+        let mut builder = TrampolineBufferBuilder::new();
+        let ints = builder.add_typed_callinfo_trampoline(
+            sum_and_product,
+            ptr::null(),
+            &[Type::I64, Type::I64],
+            &[Type::I64, Type::I64],
+        );
+        let mixed = builder.add_typed_callinfo_trampoline(
+            sum_and_half,
+            ptr::null(),
+            &[Type::I64, Type::F64],
+            &[Type::I64, Type::F64],
+        );
+        let buf = builder.build();
+
+        // Two-eightbyte structs are returned in the same registers.
+        let ret = unsafe {
+            mem::transmute::<_, extern "C" fn(i64, i64) -> IntInt>(buf.get_trampoline(ints))(3, 4)
+        };
+        assert_eq!(ret, IntInt(7, 12));
+        let ret = unsafe {
+            mem::transmute::<_, extern "C" fn(i64, f64) -> IntFloat>(buf.get_trampoline(mixed))(
+                3, 5.0,
+            )
+        };
+        assert_eq!(ret, IntFloat(8, 2.5));
+    }
+
+    #[test]
+    fn test_typed_callinfo_trampoline_stack_params() {
+        // Weighs each parameter by its position, so that reading them out of order changes
+        // the sum.
+        unsafe extern "C" fn weighted_sum(_: *const CallContext, args: *mut u64) {
+            let args: &mut [u64] = slice::from_raw_parts_mut(args, 18);
+            let sum = args.iter().enumerate().fold(0.0, |sum, (i, x)| {
+                let value = match i {
+                    16 => f64::from(f32::from_bits(*x as u32)),
+                    _ if i % 2 == 0 => *x as i64 as f64,
+                    _ => f64::from_bits(*x),
+                };
+                sum + value * (i + 1) as f64
+            });
+            args[0] = sum.to_bits();
+        }
+
+        // The last two integers and the last two floats don't fit in registers, and are
+        // passed on the stack in the order of the parameters.
+        let params: Vec<Type> = (0..18)
+            .map(|i| match i {
+                16 => Type::F32,
+                _ if i % 2 == 0 && i < 16 => Type::I64,
+                _ => Type::F64,
+            })
+            .collect();
+        let mut builder = TrampolineBufferBuilder::new();
+        let idx =
+            builder.add_typed_callinfo_trampoline(weighted_sum, ptr::null(), &params, &[Type::F64]);
+        let buf = builder.build();
+        let ret = unsafe {
+            mem::transmute::<
+                _,
+                extern "C" fn(
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    i64,
+                    f64,
+                    f32,
+                    f64,
+                ) -> f64,
+            >(buf.get_trampoline(idx))(
+                1, 2.0, 3, 4.0, 5, 6.0, 7, 8.0, 9, 10.0, 11, 12.0, 13, 14.0, 15, 16.0, 17.0, 18.0,
+            )
+        };
+        // The sum of the squares from 1 to 18.
+        assert_eq!(ret, 2109.0);
+    }
+
+    #[test]
+    fn test_typed_callinfo_trampoline_float_results() {
+        #[repr(C)]
+        #[derive(Debug, PartialEq)]
+        struct FloatDouble(f32, f64);
+
+        unsafe extern "C" fn halve_and_add(_: *const CallContext, args: *mut u64) {
+            let args: &mut [u64] = slice::from_raw_parts_mut(args, 2);
+            let a = f32::from_bits(args[0] as u32);
+            let b = f64::from_bits(args[1]);
+            args[0] = u64::from((a / 2.0).to_bits());
+            args[1] = (f64::from(a) + b).to_bits();
+        }
+        unsafe extern "C" fn to_f32(_: *const CallContext, args: *mut u64) {
+            let a = *args as i32;
+            *args = u64::from((a as f32).to_bits());
+        }
+
+        let mut builder = TrampolineBufferBuilder::new();
+        let floats = builder.add_typed_callinfo_trampoline(
+            halve_and_add,
+            ptr::null(),
+            &[Type::F32, Type::F64],
+            &[Type::F32, Type::F64],
+        );
+        let single =
+            builder.add_typed_callinfo_trampoline(to_f32, ptr::null(), &[Type::I32], &[Type::F32]);
+        let buf = builder.build();
+
+        let ret = unsafe {
+            mem::transmute::<_, extern "C" fn(f32, f64) -> FloatDouble>(buf.get_trampoline(floats))(
+                3.0, 0.5,
+            )
+        };
+        assert_eq!(ret, FloatDouble(1.5, 3.5));
+        let ret = unsafe {
+            mem::transmute::<_, extern "C" fn(i32) -> f32>(buf.get_trampoline(single))(-7)
+        };
+        assert_eq!(ret, -7.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "at most two integer and two float results")]
+    fn test_typed_callinfo_trampoline_too_many_results() {
+        unsafe extern "C" fn nothing(_: *const CallContext, _: *mut u64) {}
+
+        TrampolineBufferBuilder::new().add_typed_callinfo_trampoline(
+            nothing,
+            ptr::null(),
+            &[],
+            &[Type::I32, Type::I64, Type::I32],
+        );
+    }
 }
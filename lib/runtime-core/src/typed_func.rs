@@ -1,10 +1,14 @@
 //! The typed func module implements a way of representing a wasm function
 //! with the correct types from rust. Function calls using a typed func have a low overhead.
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+use crate::trampoline::{CallContext, TrampolineBuffer, TrampolineBufferBuilder};
 use crate::{
+    backtrace, call_depth,
     error::RuntimeError,
-    export::{Context, Export, FuncPointer},
+    export::{Context, Export, FuncEnvOwner, FuncPointer},
     import::IsExport,
-    types::{FuncSig, NativeWasmType, Type, WasmExternType},
+    table::{self, Anyfunc},
+    types::{FuncSig, NativeWasmType, Type, Value, WasmExternType},
     vm,
 };
 use std::{
@@ -20,6 +24,7 @@ use std::{
 
 /// Wasm trap info.
 #[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WasmTrapInfo {
     /// Unreachable trap.
     Unreachable = 0,
@@ -133,6 +138,10 @@ pub trait WasmTypeList {
     fn from_c_struct(c_struct: Self::CStruct) -> Self;
 
     /// Transforms Rust values into C values.
+    ///
+    /// Host functions hand their results back through this struct, so a
+    /// host function can return at most two values: larger structs are
+    /// returned through memory, which the backends don't support.
     fn into_c_struct(self) -> Self::CStruct;
 
     /// Get types of the current values.
@@ -185,14 +194,26 @@ impl ExternalFunctionKind for ImplicitVmCtx {}
 
 /// Represents a function that can be converted to a `vm::Func`
 /// (function pointer) that can be called within WebAssembly.
+///
+/// Closures may capture an environment. Since the resulting `Func`
+/// can be shared between threads, the closure must be `Send` and
+/// `Sync`; the environment is dropped once the `Func`, and every
+/// instance it has been imported into, are dropped.
 pub trait ExternalFunction<Kind, Args, Rets>
 where
     Kind: ExternalFunctionKind,
     Args: WasmTypeList,
     Rets: WasmTypeList,
 {
-    /// Conver to function pointer.
-    fn to_raw(self) -> (NonNull<vm::Func>, Option<NonNull<vm::FuncEnv>>);
+    /// Conver to function pointer, along with the function
+    /// environment and its owner, if any.
+    fn to_raw(
+        self,
+    ) -> (
+        NonNull<vm::Func>,
+        Option<NonNull<vm::FuncEnv>>,
+        Option<FuncEnvOwner>,
+    );
 }
 
 /// Represents a TrapEarly type.
@@ -227,11 +248,50 @@ where
     }
 }
 
+/// Raises a trap from an import function, unwinding the wasm stack up to the caller of the
+/// instance, which gets `payload` back as the data of a `RuntimeError::Error`.
+///
+/// Returning `Err(payload)` from the import function has the same effect.
+///
+/// # Safety
+///
+/// This must only be called from an import function called by wasm, and not from within a
+/// `panic::catch_unwind` of the import function: `payload` needn't be `Send`, so it must be
+/// caught by the wrapper of the import function, on the thread that raised it.
+pub unsafe fn raise_trap(payload: Box<dyn Any>) -> ! {
+    panic::resume_unwind(Box::new(HostTrap(payload)))
+}
+
+/// Carries the payload of `raise_trap` to the wrapper of the import function that catches it.
+struct HostTrap(Box<dyn Any>);
+
+// The callers of `raise_trap` guarantee that the payload is caught by the wrapper on the thread
+// that raised it.
+unsafe impl Send for HostTrap {}
+
+/// Returns the data of the trap for the error returned or raised by an import function, without
+/// boxing payloads that are already boxed again.
+fn trap_payload(err: Box<dyn Any>) -> Box<dyn Any> {
+    let err = match err.downcast::<HostTrap>() {
+        Ok(trap) => return trap.0,
+        Err(err) => err,
+    };
+    let err = match err.downcast::<Box<dyn Any>>() {
+        Ok(payload) => return *payload,
+        Err(err) => err,
+    };
+    match err.downcast::<Box<dyn Any + Send>>() {
+        Ok(payload) => *payload,
+        Err(err) => err,
+    }
+}
+
 /// Represents a function that can be used by WebAssembly.
 pub struct Func<'a, Args = (), Rets = (), Inner: Kind = Wasm> {
     inner: Inner,
     func: NonNull<vm::Func>,
     func_env: Option<NonNull<vm::FuncEnv>>,
+    func_env_owner: Option<FuncEnvOwner>,
     vmctx: *mut vm::Ctx,
     _phantom: PhantomData<(&'a (), Args, Rets)>,
 }
@@ -254,6 +314,7 @@ where
             inner,
             func,
             func_env,
+            func_env_owner: None,
             vmctx,
             _phantom: PhantomData,
         }
@@ -271,17 +332,27 @@ where
     Rets: WasmTypeList,
 {
     /// Creates a new `Func`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `Rets` has more than two values, since larger results are returned through
+    /// memory by the native ABI. Use a `DynamicFunc` to return more values.
     pub fn new<F, Kind>(func: F) -> Func<'a, Args, Rets, Host>
     where
         Kind: ExternalFunctionKind,
         F: ExternalFunction<Kind, Args, Rets>,
     {
-        let (func, func_env) = func.to_raw();
+        assert!(
+            Rets::types().len() <= 2,
+            "a host function returns two values at most"
+        );
+        let (func, func_env, func_env_owner) = func.to_raw();
 
         Func {
             inner: Host(()),
             func,
             func_env,
+            func_env_owner,
             vmctx: ptr::null_mut(),
             _phantom: PhantomData,
         }
@@ -391,23 +462,30 @@ where
         let mut trap = WasmTrapInfo::Unknown;
         let mut user_error = None;
 
-        if (wasm.invoke)(
-            wasm.trampoline,
-            ctx,
-            f,
-            args.as_ptr(),
-            rets.as_mut().as_mut_ptr(),
-            &mut trap,
-            &mut user_error,
-            wasm.invoke_env,
-        ) {
+        if call_depth::preserve(ctx, || {
+            backtrace::enter(ctx, || {
+                (wasm.invoke)(
+                    wasm.trampoline,
+                    ctx,
+                    f,
+                    args.as_ptr(),
+                    rets.as_mut().as_mut_ptr(),
+                    &mut trap,
+                    &mut user_error,
+                    wasm.invoke_env,
+                )
+            })
+        }) {
             Ok(Rets::from_ret_array(rets))
         } else {
+            let backtrace = backtrace::take(&*(*ctx).module);
             if let Some(data) = user_error {
-                Err(RuntimeError::Error { data })
+                Err(RuntimeError::from_user_error(data, backtrace))
             } else {
                 Err(RuntimeError::Trap {
+                    code: trap,
                     msg: trap.to_string().into(),
+                    backtrace,
                 })
             }
         }
@@ -424,11 +502,44 @@ where
     }
 }
 
+/// A value of a multi-value return, padded to its own eightbyte.
+///
+/// Host functions return their results as a `#[repr(C)]` struct. Giving every
+/// field its own eightbyte makes the native ABI return a two-value struct in the
+/// same registers that the backends use for a two-value wasm return, e.g. `rax` and
+/// `rdx` for two integers rather than both of them packed into `rax`.
+#[repr(C, align(8))]
+#[derive(Clone, Copy)]
+pub struct Slot<T>(T);
+
+macro_rules! slot_ty {
+    (Slot, $ty:ty) => { Slot<$ty> };
+    (Plain, $ty:ty) => { $ty };
+}
+
+macro_rules! slot_new {
+    (Slot, $e:expr) => {
+        Slot($e)
+    };
+    (Plain, $e:expr) => {
+        $e
+    };
+}
+
+macro_rules! slot_get {
+    (Slot, $e:expr) => {
+        $e.0
+    };
+    (Plain, $e:expr) => {
+        $e
+    };
+}
+
 macro_rules! impl_traits {
-    ( [$repr:ident] $struct_name:ident, $( $x:ident ),* ) => {
+    ( [$repr:ident] [$slot:ident] $struct_name:ident, $( $x:ident ),* ) => {
         /// Struct for typed funcs.
         #[repr($repr)]
-        pub struct $struct_name< $( $x ),* > ( $( <$x as WasmExternType>::Native ),* )
+        pub struct $struct_name< $( $x ),* > ( $( slot_ty!($slot, <$x as WasmExternType>::Native) ),* )
         where
             $( $x: WasmExternType ),*;
 
@@ -455,14 +566,14 @@ macro_rules! impl_traits {
                 #[allow(non_snake_case)]
                 let $struct_name ( $( $x ),* ) = c_struct;
 
-                ( $( WasmExternType::from_native($x) ),* )
+                ( $( WasmExternType::from_native(slot_get!($slot, $x)) ),* )
             }
 
             fn into_c_struct(self) -> Self::CStruct {
                 #[allow(unused_parens, non_snake_case)]
                 let ( $( $x ),* ) = self;
 
-                $struct_name ( $( WasmExternType::to_native($x) ),* )
+                $struct_name ( $( slot_new!($slot, WasmExternType::to_native($x)) ),* )
             }
 
             fn types() -> &'static [Type] {
@@ -486,22 +597,27 @@ macro_rules! impl_traits {
                 let mut trap = WasmTrapInfo::Unknown;
                 let mut user_error = None;
 
-                if (wasm.invoke)(
-                    wasm.trampoline,
-                    ctx,
-                    f,
-                    args.as_ptr(),
-                    rets.as_mut().as_mut_ptr(),
-                    &mut trap,
-                    &mut user_error,
-                    wasm.invoke_env
-                ) {
+                if call_depth::preserve(ctx, || {
+                    backtrace::enter(ctx, || {
+                        (wasm.invoke)(
+                            wasm.trampoline,
+                            ctx,
+                            f,
+                            args.as_ptr(),
+                            rets.as_mut().as_mut_ptr(),
+                            &mut trap,
+                            &mut user_error,
+                            wasm.invoke_env
+                        )
+                    })
+                }) {
                     Ok(Rets::from_ret_array(rets))
                 } else {
+                    let backtrace = backtrace::take(&*(*ctx).module);
                     if let Some(data) = user_error {
-                        Err(RuntimeError::Error { data })
+                        Err(RuntimeError::from_user_error(data, backtrace))
                     } else {
-                        Err(RuntimeError::Trap { msg: trap.to_string().into() })
+                        Err(RuntimeError::Trap { code: trap, msg: trap.to_string().into(), backtrace })
                     }
                 }
             }
@@ -512,10 +628,10 @@ macro_rules! impl_traits {
             $( $x: WasmExternType, )*
             Rets: WasmTypeList,
             Trap: TrapEarly<Rets>,
-            FN: Fn(&mut vm::Ctx $( , $x )*) -> Trap + 'static,
+            FN: Fn(&mut vm::Ctx $( , $x )*) -> Trap + Send + Sync + 'static,
         {
             #[allow(non_snake_case)]
-            fn to_raw(self) -> (NonNull<vm::Func>, Option<NonNull<vm::FuncEnv>>) {
+            fn to_raw(self) -> (NonNull<vm::Func>, Option<NonNull<vm::FuncEnv>>, Option<FuncEnvOwner>) {
                 // The `wrap` function is a wrapper around the
                 // imported function. It manages the argument passed
                 // to the imported function (in this case, the
@@ -550,6 +666,9 @@ macro_rules! impl_traits {
                                 None
                             }
                         })
+                        // A host function set in a table at runtime
+                        // isn't part of the import backing.
+                        .or_else(|| unsafe { table::host_func_ctx(vmctx, self_pointer) })
                         .expect("Import backing is not well-formed, cannot find `func_ctx`.");
                     let func_ctx = unsafe { func_ctx.as_mut() };
 
@@ -597,27 +716,33 @@ macro_rules! impl_traits {
                     // At this point, there is an error that needs to
                     // be trapped.
                     unsafe {
-                        (&*vmctx.module).runnable_module.do_early_trap(err)
+                        (&*vmctx.module).runnable_module.do_early_trap(trap_payload(err))
                     }
                 }
 
                 // Extract the captured environment of the imported
                 // function if any.
-                let func_env: Option<NonNull<vm::FuncEnv>> =
+                let (func_env, func_env_owner) =
                     // `FN` is a function pointer, or a closure
                     // _without_ a captured environment.
                     if mem::size_of::<Self>() == 0 {
-                        NonNull::new(&self as *const _ as *mut vm::FuncEnv)
+                        (NonNull::new(&self as *const _ as *mut vm::FuncEnv), None)
                     }
                     // `FN` is a closure _with_ a captured
-                    // environment.
+                    // environment. It is shared between the `Func`
+                    // and the instances importing it, and dropped
+                    // with the last of them.
                     else {
-                        NonNull::new(Box::into_raw(Box::new(self))).map(NonNull::cast)
+                        let env = Arc::new(self);
+                        let func_env = NonNull::new(&*env as *const Self as *mut vm::FuncEnv);
+
+                        (func_env, Some(FuncEnvOwner::new(env)))
                     };
 
                 (
                     NonNull::new(wrap::<$( $x, )* Rets, Trap, Self> as *mut vm::Func).unwrap(),
-                    func_env
+                    func_env,
+                    func_env_owner,
                 )
             }
         }
@@ -627,10 +752,10 @@ macro_rules! impl_traits {
             $( $x: WasmExternType, )*
             Rets: WasmTypeList,
             Trap: TrapEarly<Rets>,
-            FN: Fn($( $x, )*) -> Trap + 'static,
+            FN: Fn($( $x, )*) -> Trap + Send + Sync + 'static,
         {
             #[allow(non_snake_case)]
-            fn to_raw(self) -> (NonNull<vm::Func>, Option<NonNull<vm::FuncEnv>>) {
+            fn to_raw(self) -> (NonNull<vm::Func>, Option<NonNull<vm::FuncEnv>>, Option<FuncEnvOwner>) {
                 // The `wrap` function is a wrapper around the
                 // imported function. It manages the argument passed
                 // to the imported function (in this case, only the
@@ -665,6 +790,9 @@ macro_rules! impl_traits {
                                 None
                             }
                         })
+                        // A host function set in a table at runtime
+                        // isn't part of the import backing.
+                        .or_else(|| unsafe { table::host_func_ctx(vmctx, self_pointer) })
                         .expect("Import backing is not well-formed, cannot find `func_ctx`.");
                     let func_ctx = unsafe { func_ctx.as_mut() };
 
@@ -709,27 +837,33 @@ macro_rules! impl_traits {
                     // At this point, there is an error that needs to
                     // be trapped.
                     unsafe {
-                        (&*vmctx.module).runnable_module.do_early_trap(err)
+                        (&*vmctx.module).runnable_module.do_early_trap(trap_payload(err))
                     }
                 }
 
                 // Extract the captured environment of the imported
                 // function if any.
-                let func_env: Option<NonNull<vm::FuncEnv>> =
+                let (func_env, func_env_owner) =
                     // `FN` is a function pointer, or a closure
                     // _without_ a captured environment.
                     if mem::size_of::<Self>() == 0 {
-                        NonNull::new(&self as *const _ as *mut vm::FuncEnv)
+                        (NonNull::new(&self as *const _ as *mut vm::FuncEnv), None)
                     }
                     // `FN` is a closure _with_ a captured
-                    // environment.
+                    // environment. It is shared between the `Func`
+                    // and the instances importing it, and dropped
+                    // with the last of them.
                     else {
-                        NonNull::new(Box::into_raw(Box::new(self))).map(NonNull::cast)
+                        let env = Arc::new(self);
+                        let func_env = NonNull::new(&*env as *const Self as *mut vm::FuncEnv);
+
+                        (func_env, Some(FuncEnvOwner::new(env)))
                     };
 
                 (
                     NonNull::new(wrap::<$( $x, )* Rets, Trap, Self> as *mut vm::Func).unwrap(),
-                    func_env
+                    func_env,
+                    func_env_owner,
                 )
             }
         }
@@ -765,19 +899,34 @@ macro_rules! count_idents {
     }};
 }
 
-impl_traits!([C] S0,);
-impl_traits!([transparent] S1, A);
-impl_traits!([C] S2, A, B);
-impl_traits!([C] S3, A, B, C);
-impl_traits!([C] S4, A, B, C, D);
-impl_traits!([C] S5, A, B, C, D, E);
-impl_traits!([C] S6, A, B, C, D, E, F);
-impl_traits!([C] S7, A, B, C, D, E, F, G);
-impl_traits!([C] S8, A, B, C, D, E, F, G, H);
-impl_traits!([C] S9, A, B, C, D, E, F, G, H, I);
-impl_traits!([C] S10, A, B, C, D, E, F, G, H, I, J);
-impl_traits!([C] S11, A, B, C, D, E, F, G, H, I, J, K);
-impl_traits!([C] S12, A, B, C, D, E, F, G, H, I, J, K, L);
+impl_traits!([C] [Plain] S0,);
+impl_traits!([transparent] [Plain] S1, A);
+impl_traits!([C] [Slot] S2, A, B);
+impl_traits!([C] [Slot] S3, A, B, C);
+impl_traits!([C] [Slot] S4, A, B, C, D);
+impl_traits!([C] [Slot] S5, A, B, C, D, E);
+impl_traits!([C] [Slot] S6, A, B, C, D, E, F);
+impl_traits!([C] [Slot] S7, A, B, C, D, E, F, G);
+impl_traits!([C] [Slot] S8, A, B, C, D, E, F, G, H);
+impl_traits!([C] [Slot] S9, A, B, C, D, E, F, G, H, I);
+impl_traits!([C] [Slot] S10, A, B, C, D, E, F, G, H, I, J);
+impl_traits!([C] [Slot] S11, A, B, C, D, E, F, G, H, I, J, K);
+impl_traits!([C] [Slot] S12, A, B, C, D, E, F, G, H, I, J, K, L);
+
+impl<'a, Args, Rets> From<Func<'a, Args, Rets, Host>> for Anyfunc<'a>
+where
+    Args: WasmTypeList,
+    Rets: WasmTypeList,
+{
+    fn from(func: Func<'a, Args, Rets, Host>) -> Self {
+        Anyfunc::host_func(
+            func.func,
+            func.func_env,
+            func.func_env_owner,
+            Arc::new(FuncSig::new(Args::types(), Rets::types())),
+        )
+    }
+}
 
 impl<'a, Args, Rets, Inner> IsExport for Func<'a, Args, Rets, Inner>
 where
@@ -797,6 +946,166 @@ where
             func,
             ctx,
             signature,
+            env_owner: self.func_env_owner.clone(),
+        }
+    }
+}
+
+/// The closure called by a `DynamicFunc`.
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+type DynamicFn = dyn Fn(&mut vm::Ctx, &[Value]) -> Result<Vec<Value>, Box<dyn Any>> + Send + Sync;
+
+/// The environment a `DynamicFunc` is called with.
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+struct DynamicFuncEnv {
+    signature: Arc<FuncSig>,
+    func: Box<DynamicFn>,
+}
+
+/// Keeps the trampoline of a `DynamicFunc`, and its environment, alive.
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+struct DynamicFuncOwner {
+    _trampolines: TrampolineBuffer,
+    _env: Box<DynamicFuncEnv>,
+}
+
+/// A host function whose signature is only known at runtime, like the
+/// ones of language bindings.
+///
+/// It is called with its arguments as `Value`s, and returns its
+/// results as `Value`s. Returning an error traps, like the host
+/// functions returning a `Result`.
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub struct DynamicFunc {
+    func: NonNull<vm::Func>,
+    signature: Arc<FuncSig>,
+    env_owner: FuncEnvOwner,
+}
+
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl DynamicFunc {
+    /// Creates a host function of signature `signature`, calling `func`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the signature isn't supported, see `is_supported`.
+    pub fn new<F>(signature: Arc<FuncSig>, func: F) -> Self
+    where
+        F: Fn(&mut vm::Ctx, &[Value]) -> Result<Vec<Value>, Box<dyn Any>> + Send + Sync + 'static,
+    {
+        let env = Box::new(DynamicFuncEnv {
+            signature: signature.clone(),
+            func: Box::new(func),
+        });
+
+        // The function is called with the `vm::Ctx` of the instance
+        // importing it first.
+        let params: Vec<Type> = Some(Type::I64)
+            .into_iter()
+            .chain(signature.params().iter().cloned())
+            .collect();
+        let mut builder = TrampolineBufferBuilder::new();
+        let idx = builder.add_typed_callinfo_trampoline(
+            enter_dynamic_func,
+            &*env as *const DynamicFuncEnv as *const CallContext,
+            &params,
+            signature.returns(),
+        );
+        let trampolines = builder.build();
+        let func = NonNull::new(trampolines.get_trampoline(idx) as *mut vm::Func)
+            .expect("The trampoline is not null.");
+
+        DynamicFunc {
+            func,
+            signature,
+            env_owner: FuncEnvOwner::new(Arc::new(DynamicFuncOwner {
+                _trampolines: trampolines,
+                _env: env,
+            })),
+        }
+    }
+
+    /// Returns whether a `DynamicFunc` can have the signature
+    /// `signature`: it has no `v128` value, and at most two integer
+    /// and two float results, which is what the backends return in
+    /// registers.
+    pub fn is_supported(signature: &FuncSig) -> bool {
+        let returns = signature.returns();
+        let floats = returns
+            .iter()
+            .filter(|ty| **ty == Type::F32 || **ty == Type::F64)
+            .count();
+        !signature
+            .params()
+            .iter()
+            .chain(returns)
+            .any(|ty| *ty == Type::V128)
+            && floats <= 2
+            && returns.len() - floats <= 2
+    }
+
+    /// Returns the signature of the function.
+    pub fn signature(&self) -> &Arc<FuncSig> {
+        &self.signature
+    }
+}
+
+/// Calls the closure of a `DynamicFunc` with the arguments collected
+/// by its trampoline, and writes the bits of its results over them.
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+unsafe extern "C" fn enter_dynamic_func(env: *const CallContext, args: *mut u64) {
+    let env = &*(env as *const DynamicFuncEnv);
+    let vmctx = &mut *(*args as *mut vm::Ctx);
+    let params: Vec<Value> = env
+        .signature
+        .params()
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| {
+            let bits = *args.add(i + 1);
+            match ty {
+                Type::I32 => Value::I32(bits as i32),
+                Type::I64 => Value::I64(bits as i64),
+                Type::F32 => Value::F32(f32::from_bits(bits as u32)),
+                Type::F64 => Value::F64(f64::from_bits(bits)),
+                Type::V128 => unreachable!("v128 values are rejected by `DynamicFunc::new`"),
+            }
+        })
+        .collect();
+
+    let err = match panic::catch_unwind(panic::AssertUnwindSafe(|| (env.func)(vmctx, &params))) {
+        Ok(Ok(returns)) => {
+            let types: Vec<Type> = returns.iter().map(Value::ty).collect();
+            if types == env.signature.returns() {
+                for (i, value) in returns.iter().enumerate() {
+                    *args.add(i) = value.to_u128() as u64;
+                }
+                return;
+            }
+            Box::new(format!(
+                "the host function returned values of types {:?}, rather than {:?}",
+                types,
+                env.signature.returns()
+            )) as Box<dyn Any>
+        }
+        Ok(Err(err)) => err,
+        Err(err) => err,
+    };
+
+    // At this point, there is an error that needs to be trapped.
+    (&*vmctx.module)
+        .runnable_module
+        .do_early_trap(trap_payload(err))
+}
+
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl IsExport for DynamicFunc {
+    fn to_export(&self) -> Export {
+        Export::Function {
+            func: unsafe { FuncPointer::new(self.func.as_ptr()) },
+            ctx: Context::Internal,
+            signature: self.signature.clone(),
+            env_owner: Some(self.env_owner.clone()),
         }
     }
 }
@@ -861,6 +1170,24 @@ mod tests {
         let _f = Func::new(foo);
     }
 
+    #[test]
+    fn test_multi_value_rets() {
+        type Rets = (i32, i64, f32, f64);
+        assert_eq!(Rets::types(), &[Type::I32, Type::I64, Type::F32, Type::F64]);
+
+        let rets = Rets::from_ret_array([
+            -1i32 as u32 as u64,
+            -2i64 as u64,
+            1.5f32.to_bits() as u64,
+            2.5f64.to_bits(),
+        ]);
+        assert_eq!(rets, (-1, -2, 1.5, 2.5));
+
+        let c_struct = (7i32, 1.5f32).into_c_struct();
+        assert_eq!(mem::size_of_val(&c_struct), 16);
+        assert_eq!(<(i32, f32)>::from_c_struct(c_struct), (7, 1.5));
+    }
+
     #[test]
     fn test_imports() {
         use crate::{func, imports};
@@ -269,15 +269,39 @@ unsafe impl WasmExternType for f64 {
 // }
 
 /// Trait for a Value type.
+///
+/// Linear memory is little-endian, so a value read from it must go through `from_le`, and a
+/// value written to it through `to_le`, for it to be correct on big-endian hosts. Types made of
+/// several fields must override both methods to convert each field.
 pub unsafe trait ValueType: Copy
 where
     Self: Sized,
 {
+    /// Converts a value read from linear memory to the byte order of the host.
+    #[inline]
+    fn from_le(self) -> Self {
+        self
+    }
+
+    /// Converts a value to the byte order of linear memory before it's written there.
+    #[inline]
+    fn to_le(self) -> Self {
+        self
+    }
 }
 
 macro_rules! convert_value_impl {
     ($t:ty) => {
-        unsafe impl ValueType for $t {}
+        unsafe impl ValueType for $t {
+            #[inline]
+            fn from_le(self) -> Self {
+                <$t>::from_le(self)
+            }
+            #[inline]
+            fn to_le(self) -> Self {
+                <$t>::to_le(self)
+            }
+        }
     };
     ( $($t:ty),* ) => {
         $(
@@ -286,7 +310,25 @@ macro_rules! convert_value_impl {
     };
 }
 
-convert_value_impl!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+convert_value_impl!(u8, i8, u16, i16, u32, i32, u64, i64);
+
+macro_rules! convert_float_value_impl {
+    ($t:ty, $bits:ty) => {
+        unsafe impl ValueType for $t {
+            #[inline]
+            fn from_le(self) -> Self {
+                <$t>::from_bits(<$bits>::from_le(self.to_bits()))
+            }
+            #[inline]
+            fn to_le(self) -> Self {
+                <$t>::from_bits(self.to_bits().to_le())
+            }
+        }
+    };
+}
+
+convert_float_value_impl!(f32, u32);
+convert_float_value_impl!(f64, u64);
 
 /// Kinds of element types.
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
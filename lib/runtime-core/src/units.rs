@@ -1,7 +1,8 @@
 //! The units module provides common WebAssembly units like `Pages` and conversion functions into
 //! other units.
+#[cfg(feature = "std")]
 use crate::error::PageError;
-use std::{
+use core::{
     fmt,
     ops::{Add, Sub},
 };
@@ -20,6 +21,7 @@ pub struct Pages(pub u32);
 
 impl Pages {
     /// Checked add of Pages to Pages.
+    #[cfg(feature = "std")]
     pub fn checked_add(self, rhs: Pages) -> Result<Pages, PageError> {
         let added = (self.0 as usize) + (rhs.0 as usize);
         if added <= WASM_MAX_PAGES {
@@ -12,6 +12,7 @@ use crate::{
     vmcalls,
 };
 use std::{
+    any::Any,
     cell::UnsafeCell,
     ffi::c_void,
     mem,
@@ -59,12 +60,17 @@ pub struct Ctx {
     /// [#219](https://github.com/wasmerio/wasmer/pull/219) fixes that
     /// issue, as well as allowing the user to have *per-function*
     /// context, instead of just per-instance.
+    ///
+    /// Prefer `set_data`, which owns the data and drops it with the context.
     pub data: *mut c_void,
 
     /// If there's a function set in this field, it gets called
     /// when the context is destructed, e.g. when an `Instance`
     /// is dropped.
     pub data_finalizer: Option<fn(data: *mut c_void)>,
+
+    /// The data attached with `set_data`.
+    owned_data: Option<Box<dyn Any + Send>>,
 }
 
 /// When an instance context is destructed, we're calling its `data_finalizer`
@@ -312,6 +318,7 @@ impl Ctx {
 
             data: ptr::null_mut(),
             data_finalizer: None,
+            owned_data: None,
         }
     }
 
@@ -365,6 +372,7 @@ impl Ctx {
 
             data,
             data_finalizer: Some(data_finalizer),
+            owned_data: None,
         }
     }
 
@@ -411,6 +419,29 @@ impl Ctx {
         unsafe { (*self.local_backing).dynamic_sigindices.len() }
     }
 
+    /// Attaches `data` to the instance, to be retrieved by the import functions with
+    /// `data_ref` and `data_mut`. It's dropped with the instance, or when other data is
+    /// attached, in which case the previous data is returned instead. The data must be `Send`
+    /// as the instance can be sent to another thread.
+    pub fn set_data<T: Any + Send>(&mut self, data: Box<T>) -> Option<Box<dyn Any + Send>> {
+        mem::replace(&mut self.owned_data, Some(data))
+    }
+
+    /// Returns the data attached with `set_data`, if it's a `T`.
+    pub fn data_ref<T: Any>(&self) -> Option<&T> {
+        self.owned_data.as_ref()?.downcast_ref::<T>()
+    }
+
+    /// Returns the data attached with `set_data` mutably, if it's a `T`.
+    pub fn data_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.owned_data.as_mut()?.downcast_mut::<T>()
+    }
+
+    /// Detaches the data attached with `set_data` and returns it.
+    pub fn take_data(&mut self) -> Option<Box<dyn Any + Send>> {
+        self.owned_data.take()
+    }
+
     /// Returns the value of the specified internal field.
     pub fn get_internal(&self, field: &InternalField) -> u64 {
         unsafe { (*self.internal.internals)[field.index()] }
@@ -1110,7 +1141,11 @@ mod vm_ctx_tests {
                 em_symbol_map: None,
 
                 custom_sections: HashMap::new(),
+
+                func_names: HashMap::new(),
+                middleware_values: HashMap::new(),
             },
+            memory_images: Default::default(),
         }
     }
 }
@@ -1,12 +1,13 @@
 #![allow(clippy::cast_ptr_alignment)]
 
 use crate::{
-    memory::{DynamicMemory, StaticMemory},
+    memory::{atomic, DynamicMemory, StaticMemory},
     structures::TypedIndex,
     types::{ImportedMemoryIndex, LocalMemoryIndex, LocalTableIndex},
     units::Pages,
     vm,
 };
+use std::time::Duration;
 
 // +*****************************+
 // |       LOCAL MEMORIES        |
@@ -138,6 +139,45 @@ pub unsafe extern "C" fn imported_dynamic_memory_size(
     (*memory).size()
 }
 
+// +*****************************+
+// |       ATOMIC WAIT/NOTIFY    |
+// +*****************************+
+
+/// Implements `memory.atomic.wait32` on the host address `addr`.
+///
+/// A negative `timeout`, in nanoseconds, waits forever.
+pub unsafe extern "C" fn memory_atomic_wait32(
+    addr: *const u32,
+    expected: u32,
+    timeout: i64,
+) -> u32 {
+    atomic::wait32(addr, expected, wait_timeout(timeout)) as u32
+}
+
+/// Implements `memory.atomic.wait64` on the host address `addr`.
+///
+/// A negative `timeout`, in nanoseconds, waits forever.
+pub unsafe extern "C" fn memory_atomic_wait64(
+    addr: *const u64,
+    expected: u64,
+    timeout: i64,
+) -> u32 {
+    atomic::wait64(addr, expected, wait_timeout(timeout)) as u32
+}
+
+/// Implements `memory.atomic.notify` on the host address `addr`.
+pub unsafe extern "C" fn memory_atomic_notify(addr: *const u32, count: u32) -> u32 {
+    atomic::notify(addr as *const u8, count)
+}
+
+fn wait_timeout(timeout: i64) -> Option<Duration> {
+    if timeout < 0 {
+        None
+    } else {
+        Some(Duration::from_nanos(timeout as u64))
+    }
+}
+
 // +*****************************+
 // |        LOCAL TABLES         |
 // +*****************************+
@@ -70,7 +70,7 @@ fn main() -> Result<(), error::Error> {
 
     println!("result: {:?}", result);
 
-    if let Err(RuntimeError::Error { data }) = result {
+    if let Err(RuntimeError::Error { data, .. }) = result {
         if let Ok(exit_code) = data.downcast::<ExitCode>() {
             println!("exit code: {:?}", exit_code);
         }
@@ -3,25 +3,41 @@
 //! and loaded to allow skipping compilation and fast startup.
 
 use crate::Module;
+use filetime::FileTime;
 use memmap::Mmap;
 use std::{
-    fs::{create_dir_all, File},
+    fs::{self, create_dir_all, File},
     io::{self, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process,
+    time::SystemTime,
 };
 
-use wasmer_runtime_core::cache::Error as CacheError;
+use wasmer_runtime_core::cache::host_cpu_features_digest;
+use wasmer_runtime_core::cache::{Error as CacheError, InvalidatedCache};
 pub use wasmer_runtime_core::{
     backend::Backend,
     cache::{Artifact, Cache, WasmHash},
 };
 
+/// A `Cache` trait object, for embedders that pick the cache implementation at runtime.
+pub type BoxedCache = Box<dyn Cache<LoadError = CacheError, StoreError = CacheError> + Send + Sync>;
+
 /// Representation of a directory that contains compiled wasm artifacts.
 ///
 /// The `FileSystemCache` type implements the [`Cache`] trait, which allows it to be used
 /// generically when some sort of cache is required.
 ///
+/// Artifacts are stored per backend and per set of host CPU features, so a cache
+/// directory can safely be shared between machines with different processors.
+///
+/// Artifacts are written to a temporary file first and then renamed into place, so
+/// concurrent readers never see a partially written artifact. A cache created with
+/// [`FileSystemCache::with_max_size`] evicts the least recently used artifacts
+/// whenever storing a new one makes it grow past the limit.
+///
 /// [`Cache`]: trait.Cache.html
+/// [`FileSystemCache::with_max_size`]: struct.FileSystemCache.html#method.with_max_size
 ///
 /// # Usage:
 ///
@@ -43,6 +59,7 @@ pub use wasmer_runtime_core::{
 /// ```
 pub struct FileSystemCache {
     path: PathBuf,
+    max_size: Option<u64>,
 }
 
 impl FileSystemCache {
@@ -58,7 +75,10 @@ impl FileSystemCache {
             let metadata = path.metadata()?;
             if metadata.is_dir() {
                 if !metadata.permissions().readonly() {
-                    Ok(Self { path })
+                    Ok(Self {
+                        path,
+                        max_size: None,
+                    })
                 } else {
                     // This directory is readonly.
                     Err(io::Error::new(
@@ -79,9 +99,72 @@ impl FileSystemCache {
         } else {
             // Create the directory and any parent directories if they don't yet exist.
             create_dir_all(&path)?;
-            Ok(Self { path })
+            Ok(Self {
+                path,
+                max_size: None,
+            })
+        }
+    }
+
+    /// Construct a new `FileSystemCache` around the specified directory that keeps
+    /// the total size of the stored artifacts under `max_size` bytes.
+    ///
+    /// The directory must be dedicated to the cache, since any file in it counts
+    /// towards the limit and may be evicted.
+    ///
+    /// # Note:
+    /// This method is unsafe for the same reasons as `FileSystemCache::new`.
+    pub unsafe fn with_max_size<P: Into<PathBuf>>(path: P, max_size: u64) -> io::Result<Self> {
+        let mut cache = Self::new(path)?;
+        cache.max_size = Some(max_size);
+        Ok(cache)
+    }
+
+    fn artifact_dir(&self, backend: Backend) -> PathBuf {
+        let mut path = self.path.clone();
+        path.push(backend.to_string());
+        path.push(host_cpu_features_digest());
+        path
+    }
+
+    /// Removes the least recently used artifacts until the cache fits in `max_size`.
+    fn evict(&self, max_size: u64) -> io::Result<()> {
+        let mut artifacts = vec![];
+        collect_artifacts(&self.path, &mut artifacts)?;
+
+        let mut total_size: u64 = artifacts.iter().map(|&(_, size, _)| size).sum();
+        if total_size <= max_size {
+            return Ok(());
+        }
+
+        artifacts.sort_by_key(|&(_, _, last_used)| last_used);
+        for (path, size, _) in artifacts {
+            if total_size <= max_size {
+                break;
+            }
+            // Another process sharing the cache may have removed it already.
+            if fs::remove_file(path).is_ok() {
+                total_size -= size;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects the path, size and last use time of every artifact under `dir`, skipping
+/// the temporary files of in-progress writes.
+fn collect_artifacts(dir: &Path, out: &mut Vec<(PathBuf, u64, SystemTime)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            collect_artifacts(&entry.path(), out)?;
+        } else if !entry.file_name().to_string_lossy().starts_with('.') {
+            out.push((entry.path(), metadata.len(), metadata.modified()?));
         }
     }
+    Ok(())
 }
 
 impl Cache for FileSystemCache {
@@ -94,13 +177,24 @@ impl Cache for FileSystemCache {
 
     fn load_with_backend(&self, key: WasmHash, backend: Backend) -> Result<Module, CacheError> {
         let filename = key.encode();
-        let mut new_path_buf = self.path.clone();
-        new_path_buf.push(backend.to_string());
+        let mut new_path_buf = self.artifact_dir(backend);
         new_path_buf.push(filename);
-        let file = File::open(new_path_buf)?;
+        let file = File::open(&new_path_buf)?;
         let mmap = unsafe { Mmap::map(&file)? };
+        // The modification time doubles as the last use time for eviction. Failing to
+        // update it only makes eviction less accurate.
+        let _ = filetime::set_file_mtime(&new_path_buf, FileTime::now());
 
         let serialized_cache = Artifact::deserialize(&mmap[..])?;
+        let found = serialized_cache.metadata().backend;
+        if found != backend {
+            return Err(CacheError::InvalidatedCache(
+                InvalidatedCache::BackendMismatch {
+                    expected: backend,
+                    found,
+                },
+            ));
+        }
         unsafe {
             wasmer_runtime_core::load_cache_with(
                 serialized_cache,
@@ -113,17 +207,29 @@ impl Cache for FileSystemCache {
 
     fn store(&mut self, key: WasmHash, module: Module) -> Result<(), CacheError> {
         let filename = key.encode();
-        let backend_str = module.info().backend.to_string();
-        let mut new_path_buf = self.path.clone();
-        new_path_buf.push(backend_str);
+        let mut new_path_buf = self.artifact_dir(module.info().backend);
 
         let serialized_cache = module.cache()?;
         let buffer = serialized_cache.serialize()?;
 
         std::fs::create_dir_all(&new_path_buf)?;
+        let mut temp_path_buf = new_path_buf.clone();
+        temp_path_buf.push(format!(".{}.{}.tmp", filename, process::id()));
         new_path_buf.push(filename);
-        let mut file = File::create(new_path_buf)?;
-        file.write_all(&buffer)?;
+
+        // Write to a temporary file and rename it into place so that readers never
+        // observe a partially written artifact.
+        let write_result = File::create(&temp_path_buf)
+            .and_then(|mut file| file.write_all(&buffer).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temp_path_buf, &new_path_buf));
+        if let Err(e) = write_result {
+            let _ = fs::remove_file(&temp_path_buf);
+            return Err(e.into());
+        }
+
+        if let Some(max_size) = self.max_size {
+            self.evict(max_size)?;
+        }
 
         Ok(())
     }
@@ -177,4 +283,60 @@ mod tests {
         // verify it works
         assert_eq!(value, 43);
     }
+
+    #[test]
+    fn test_file_system_cache_evicts_over_limit() {
+        use crate::compile;
+        use wabt::wat2wasm;
+
+        let wasm = wat2wasm("(module)").unwrap();
+        let module = compile(&wasm).unwrap();
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut fs_cache = unsafe { FileSystemCache::with_max_size(cache_dir.path(), 1).unwrap() };
+
+        // Any artifact is larger than the limit, so it is evicted right away.
+        let key = WasmHash::generate(&wasm);
+        fs_cache.store(key, module).unwrap();
+        assert!(fs_cache.load(key).is_err());
+    }
+
+    #[test]
+    fn test_file_system_cache_evicts_least_recently_used() {
+        use crate::compile;
+        use wabt::wat2wasm;
+
+        let modules: Vec<(WasmHash, Module)> = (0..4)
+            .map(|i| {
+                let wat = format!(
+                    "(module (func (export \"f\") (result i32) i32.const {}))",
+                    i
+                );
+                let wasm = wat2wasm(wat).unwrap();
+                (WasmHash::generate(&wasm), compile(&wasm).unwrap())
+            })
+            .collect();
+        let size = |module: &Module| module.cache().unwrap().serialize().unwrap().len() as u64;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut fs_cache = unsafe { FileSystemCache::new(cache_dir.path()).unwrap() };
+        for (i, (key, module)) in modules[..3].iter().enumerate() {
+            fs_cache.store(*key, module.clone()).unwrap();
+            // Spread the last use times, since the file system's may be too coarse.
+            let mut path = fs_cache.artifact_dir(module.info().backend);
+            path.push(key.encode());
+            filetime::set_file_mtime(&path, FileTime::from_unix_time(1000 + i as i64, 0)).unwrap();
+        }
+        // Loading the oldest artifact makes it the most recently used.
+        fs_cache.load(modules[0].0).unwrap();
+
+        // Storing the fourth artifact only leaves room for three.
+        fs_cache.max_size = Some(size(&modules[0].1) + size(&modules[2].1) + size(&modules[3].1));
+        fs_cache.store(modules[3].0, modules[3].1.clone()).unwrap();
+
+        assert!(fs_cache.load(modules[1].0).is_err());
+        for &i in &[0, 2, 3] {
+            fs_cache.load(modules[i].0).unwrap();
+        }
+    }
 }
@@ -0,0 +1,173 @@
+//! The differential module runs the same invocation on code compiled by two backends and
+//! compares the outcomes. It is meant for tracking down discrepancies between backends, such
+//! as NaN handling or rounding differences.
+
+use crate::{compile_with_config_with, error, ImportObject, Value};
+use wasmer_runtime_core::{
+    backend::{Compiler, CompilerConfig},
+    error::{CallError, RuntimeError},
+    typed_func::WasmTrapInfo,
+};
+
+/// The outcome of running an invocation on one backend.
+#[derive(Debug, Clone)]
+pub struct Execution {
+    /// The returned values, or the trap or error message.
+    pub result: Result<Vec<Value>, String>,
+    /// The trap code, if the call trapped and the backend reports trap codes.
+    pub trap_code: Option<WasmTrapInfo>,
+    /// The contents of memory 0 after the call, if the module has a memory.
+    pub memory: Option<Vec<u8>>,
+}
+
+/// The outcomes of running an invocation on two backends.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// The outcome on the first backend.
+    pub left: Execution,
+    /// The outcome on the second backend.
+    pub right: Execution,
+}
+
+impl Report {
+    /// Returns a description of every difference between the two executions.
+    ///
+    /// NaN values are considered equal regardless of their payload, since the
+    /// specification does not make it deterministic. Trap messages are not compared, but trap
+    /// codes are when both backends report one.
+    pub fn differences(&self) -> Vec<String> {
+        let mut differences = vec![];
+
+        match (&self.left.result, &self.right.result) {
+            (Ok(left), Ok(right)) => {
+                if left.len() != right.len()
+                    || left
+                        .iter()
+                        .zip(right.iter())
+                        .any(|(l, r)| !values_match(l, r))
+                {
+                    differences.push(format!("results differ: {:?} vs {:?}", left, right));
+                }
+            }
+            (Err(_), Err(_)) => {
+                if let (Some(left), Some(right)) = (self.left.trap_code, self.right.trap_code) {
+                    if left != right {
+                        differences.push(format!("trap codes differ: {} vs {}", left, right));
+                    }
+                }
+            }
+            (left, right) => {
+                differences.push(format!("outcomes differ: {:?} vs {:?}", left, right));
+            }
+        }
+
+        match (&self.left.memory, &self.right.memory) {
+            (Some(left), Some(right)) => {
+                if left.len() != right.len() {
+                    differences.push(format!(
+                        "memory sizes differ: {} vs {} bytes",
+                        left.len(),
+                        right.len()
+                    ));
+                } else if let Some(offset) = left.iter().zip(right.iter()).position(|(l, r)| l != r)
+                {
+                    differences.push(format!(
+                        "memory differs first at offset {}: {:#04x} vs {:#04x}",
+                        offset, left[offset], right[offset]
+                    ));
+                }
+            }
+            (None, None) => {}
+            _ => differences.push("only one execution has a memory".to_string()),
+        }
+
+        differences
+    }
+
+    /// Returns `true` if both executions behaved the same.
+    pub fn is_consistent(&self) -> bool {
+        self.differences().is_empty()
+    }
+}
+
+fn values_match(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::F32(l), Value::F32(r)) => (l.is_nan() && r.is_nan()) || l.to_bits() == r.to_bits(),
+        (Value::F64(l), Value::F64(r)) => (l.is_nan() && r.is_nan()) || l.to_bits() == r.to_bits(),
+        _ => left == right,
+    }
+}
+
+fn execute(
+    wasm: &[u8],
+    compiler: &dyn Compiler,
+    compiler_config: CompilerConfig,
+    import_object: &ImportObject,
+    func_name: &str,
+    args: &[Value],
+) -> error::Result<Execution> {
+    let module = compile_with_config_with(wasm, compiler_config, compiler)?;
+    let instance = module.instantiate(import_object)?;
+
+    let mut trap_code = None;
+    let result = instance
+        .dyn_func(func_name)
+        .map_err(|e| format!("{:?}", e))
+        .and_then(|func| {
+            func.call(args).map_err(|e| {
+                if let CallError::Runtime(RuntimeError::Trap { code, .. }) = e {
+                    trap_code = Some(code);
+                }
+                format!("{}", e)
+            })
+        });
+
+    let info = module.info();
+    let memory = if info.memories.len() + info.imported_memories.len() > 0 {
+        let view = instance.context().memory(0).view::<u8>();
+        Some(view.iter().map(|cell| cell.get()).collect())
+    } else {
+        None
+    };
+
+    Ok(Execution {
+        result,
+        trap_code,
+        memory,
+    })
+}
+
+/// Compiles `wasm` with both compilers using `compiler_config`, calls `func_name` with `args`
+/// on each instance and returns both outcomes.
+///
+/// `import_object` is called once per backend so that host state isn't shared between the
+/// two executions. Errors while compiling or instantiating are returned directly, while
+/// traps are part of the report.
+pub fn compare_backends(
+    wasm: &[u8],
+    left: &dyn Compiler,
+    right: &dyn Compiler,
+    compiler_config: &CompilerConfig,
+    import_object: &dyn Fn() -> ImportObject,
+    func_name: &str,
+    args: &[Value],
+) -> error::Result<Report> {
+    Ok(Report {
+        left: execute(
+            wasm,
+            left,
+            compiler_config.clone(),
+            &import_object(),
+            func_name,
+            args,
+        )?,
+        right: execute(
+            wasm,
+            right,
+            compiler_config.clone(),
+            &import_object(),
+            func_name,
+            args,
+        )?,
+    })
+}
@@ -87,13 +87,14 @@
 //! [`wasmer-clif-backend`]: https://crates.io/crates/wasmer-clif-backend
 //! [`compile_with`]: fn.compile_with.html
 
+pub use wasmer_runtime_core::async_import;
 pub use wasmer_runtime_core::backend::Backend;
 pub use wasmer_runtime_core::codegen::{MiddlewareChain, StreamingCompiler};
 pub use wasmer_runtime_core::export::Export;
 pub use wasmer_runtime_core::global::Global;
 pub use wasmer_runtime_core::import::ImportObject;
 pub use wasmer_runtime_core::instance::{DynFunc, Instance};
-pub use wasmer_runtime_core::memory::ptr::{Array, Item, WasmPtr};
+pub use wasmer_runtime_core::memory::ptr::{Array, Item, MemoryAccessError, WasmPtr};
 pub use wasmer_runtime_core::memory::Memory;
 pub use wasmer_runtime_core::module::Module;
 pub use wasmer_runtime_core::table::Table;
@@ -132,8 +133,11 @@ pub mod units {
 }
 
 pub mod cache;
+pub mod differential;
+pub mod pool;
 
-use wasmer_runtime_core::backend::{Compiler, CompilerConfig};
+use std::borrow::Cow;
+use wasmer_runtime_core::backend::{Compiler, CompilerConfig, Features};
 
 /// Compile WebAssembly binary code into a [`Module`].
 /// This function is useful if it is necessary to
@@ -148,16 +152,52 @@ use wasmer_runtime_core::backend::{Compiler, CompilerConfig};
 ///   binary code of the wasm module you want to compile.
 /// # Errors:
 /// If the operation fails, the function returns `Err(error::CompileError::...)`.
+///
+/// With the `wat` feature enabled, `wasm` may also be in the WebAssembly text format.
 pub fn compile(wasm: &[u8]) -> error::CompileResult<Module> {
+    let wasm = wat_to_wasm(wasm)?;
+    wasmer_runtime_core::compile_with(&wasm[..], &default_compiler())
+}
+
+/// Compile WebAssembly text format (`.wat`) code into a [`Module`].
+///
+/// [`Module`]: struct.Module.html
+///
+/// # Errors:
+/// Returns `error::CompileError::ValidationError` if the text can't be parsed.
+#[cfg(feature = "wat")]
+pub fn compile_wat(wat: &str) -> error::CompileResult<Module> {
+    let wasm = wabt::wat2wasm(wat).map_err(|e| error::CompileError::ValidationError {
+        msg: format!("failed to parse the text format: {:?}", e),
+    })?;
     wasmer_runtime_core::compile_with(&wasm[..], &default_compiler())
 }
 
+/// Converts `wasm` to the binary format if it is in the text format.
+#[cfg(feature = "wat")]
+fn wat_to_wasm(wasm: &[u8]) -> error::CompileResult<Cow<[u8]>> {
+    if wasm.starts_with(b"\0asm") {
+        return Ok(Cow::Borrowed(wasm));
+    }
+    wabt::wat2wasm(wasm)
+        .map(Cow::Owned)
+        .map_err(|e| error::CompileError::ValidationError {
+            msg: format!("failed to parse the text format: {:?}", e),
+        })
+}
+
+#[cfg(not(feature = "wat"))]
+fn wat_to_wasm(wasm: &[u8]) -> error::CompileResult<Cow<[u8]>> {
+    Ok(Cow::Borrowed(wasm))
+}
+
 /// The same as `compile` but takes a `CompilerConfig` for the purpose of
 /// changing the compiler's behavior
 pub fn compile_with_config(
     wasm: &[u8],
     compiler_config: CompilerConfig,
 ) -> error::CompileResult<Module> {
+    let wasm = wat_to_wasm(wasm)?;
     wasmer_runtime_core::compile_with_config(&wasm[..], &default_compiler(), compiler_config)
 }
 
@@ -168,6 +208,7 @@ pub fn compile_with_config_with(
     compiler_config: CompilerConfig,
     compiler: &dyn Compiler,
 ) -> error::CompileResult<Module> {
+    let wasm = wat_to_wasm(wasm)?;
     wasmer_runtime_core::compile_with_config(&wasm[..], compiler, compiler_config)
 }
 
@@ -247,6 +288,9 @@ pub fn compiler_for_backend(backend: Backend) -> Option<Box<dyn Compiler>> {
         #[cfg(feature = "llvm")]
         Backend::LLVM => Some(Box::new(wasmer_llvm_backend::LLVMCompiler::new())),
 
+        // The choice depends on the module, see `compiler_for_module`.
+        Backend::Auto => None,
+
         #[cfg(any(
             not(feature = "llvm"),
             not(feature = "singlepass"),
@@ -256,5 +300,26 @@ pub fn compiler_for_backend(backend: Backend) -> Option<Box<dyn Compiler>> {
     }
 }
 
+/// Get the list of backends enabled by feature flags, not including `Backend::Auto`.
+pub fn enabled_backends() -> Vec<Backend> {
+    #[allow(unused_mut)]
+    let mut backends = vec![];
+    #[cfg(feature = "cranelift")]
+    backends.push(Backend::Cranelift);
+    #[cfg(feature = "singlepass")]
+    backends.push(Backend::Singlepass);
+    #[cfg(feature = "llvm")]
+    backends.push(Backend::LLVM);
+    backends
+}
+
+/// Get the `Compiler` that `Backend::Auto` picks for the given module, out of the enabled
+/// backends. Returns `None` if none of them supports the requested features.
+///
+/// The chosen backend can be inspected afterwards with `module.info().backend`.
+pub fn compiler_for_module(wasm: &[u8], features: &Features) -> Option<Box<dyn Compiler>> {
+    Backend::select(wasm, features, &enabled_backends()).and_then(compiler_for_backend)
+}
+
 /// The current version of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
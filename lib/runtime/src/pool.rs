@@ -0,0 +1,127 @@
+//! The pool module keeps instances of a module around to reuse them, so that embedders
+//! running a module once per request don't pay for a full instantiation every time.
+//!
+//! A pooled instance is reset to the state it had right after instantiation when it is
+//! returned to the pool, using the same mechanism as `Instance::snapshot` and
+//! `Instance::restore`.
+
+use crate::{error, ImportObject, Instance, Module};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+};
+use wasmer_runtime_core::state::InstanceImage;
+
+/// A pool of instances of a module.
+///
+/// Only the state owned by an instance is reset: its memory, its local globals and its local
+/// tables. Imported memories, tables and globals, the data of its `Ctx` and the internal
+/// fields, such as the fuel, are left as they are.
+pub struct InstancePool {
+    module: Module,
+    import_object: ImportObject,
+    image: InstanceImage,
+    idle: Mutex<Vec<Instance>>,
+    max_idle: usize,
+}
+
+impl InstancePool {
+    /// Creates a pool of instances of `module` instantiated with `import_object`, keeping
+    /// at most `max_idle` instances around.
+    ///
+    /// The first instance is created right away, and the state the instances are reset to
+    /// is captured from it, once its start function has run.
+    pub fn new(
+        module: &Module,
+        import_object: ImportObject,
+        max_idle: usize,
+    ) -> error::Result<Self> {
+        let instance = module.instantiate(&import_object)?;
+        let image = instance.snapshot();
+        let pool = Self {
+            module: module.clone(),
+            import_object,
+            image,
+            idle: Mutex::new(Vec::new()),
+            max_idle,
+        };
+        pool.release(instance);
+        Ok(pool)
+    }
+
+    /// Creates instances until `count` of them are idle, or the pool is full.
+    pub fn reserve(&self, count: usize) -> error::Result<()> {
+        let count = count.min(self.max_idle);
+        while self.idle_count() < count {
+            let instance = self.module.instantiate(&self.import_object)?;
+            self.release(instance);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of idle instances.
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    /// Takes an instance from the pool, or creates one if none is idle.
+    ///
+    /// The instance goes back to the pool when the returned `PooledInstance` is dropped.
+    pub fn get(&self) -> error::Result<PooledInstance> {
+        let idle = self.idle.lock().unwrap().pop();
+        let instance = match idle {
+            Some(instance) => instance,
+            None => self.module.instantiate(&self.import_object)?,
+        };
+        Ok(PooledInstance {
+            pool: self,
+            instance: Some(instance),
+        })
+    }
+
+    fn release(&self, instance: Instance) {
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < self.max_idle {
+            idle.push(instance);
+        }
+    }
+}
+
+/// An instance taken from an `InstancePool`, which is reset and returned to the pool when
+/// dropped.
+pub struct PooledInstance<'a> {
+    pool: &'a InstancePool,
+    instance: Option<Instance>,
+}
+
+impl<'a> PooledInstance<'a> {
+    /// Takes the instance out of the pool for good.
+    pub fn detach(mut self) -> Instance {
+        self.instance.take().unwrap()
+    }
+}
+
+impl<'a> Deref for PooledInstance<'a> {
+    type Target = Instance;
+
+    fn deref(&self) -> &Instance {
+        self.instance.as_ref().unwrap()
+    }
+}
+
+impl<'a> DerefMut for PooledInstance<'a> {
+    fn deref_mut(&mut self) -> &mut Instance {
+        self.instance.as_mut().unwrap()
+    }
+}
+
+impl<'a> Drop for PooledInstance<'a> {
+    fn drop(&mut self) {
+        if let Some(mut instance) = self.instance.take() {
+            // An instance that can't be reset is discarded.
+            if instance.restore(&self.pool.image).is_ok() {
+                self.pool.release(instance);
+            }
+        }
+    }
+}
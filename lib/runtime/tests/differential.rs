@@ -0,0 +1,110 @@
+use std::sync::{
+    atomic::{AtomicI32, Ordering},
+    Arc,
+};
+use wabt::wat2wasm;
+use wasmer_runtime::{
+    default_compiler,
+    differential::{compare_backends, Execution, Report},
+    func, imports, Value,
+};
+use wasmer_runtime_core::{backend::CompilerConfig, typed_func::WasmTrapInfo};
+
+static WAT: &'static str = r#"
+    (module
+      (import "env" "next" (func $next (result i32)))
+      (memory 1)
+      (func (export "store") (param i32) (result i32)
+        i32.const 0
+        get_local 0
+        i32.store
+        get_local 0
+        i32.const 1
+        i32.add)
+      (func (export "next") (result i32)
+        call $next)
+      (func (export "trap")
+        unreachable))
+"#;
+
+fn compare(func_name: &str, args: &[Value], counter: &Arc<AtomicI32>) -> Report {
+    let wasm = wat2wasm(WAT).unwrap();
+    let compiler = default_compiler();
+    let counter = counter.clone();
+    compare_backends(
+        &wasm,
+        &compiler,
+        &compiler,
+        &CompilerConfig::default(),
+        &|| {
+            let counter = counter.clone();
+            imports! {
+                "env" => {
+                    "next" => func!(move || counter.fetch_add(1, Ordering::SeqCst)),
+                },
+            }
+        },
+        func_name,
+        args,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_consistent_executions() {
+    let counter = Arc::new(AtomicI32::new(0));
+
+    let report = compare("store", &[Value::I32(41)], &counter);
+    assert_eq!(report.left.result.as_ref().unwrap(), &[Value::I32(42)]);
+    assert_eq!(&report.left.memory.as_ref().unwrap()[..4], &[41, 0, 0, 0]);
+    assert!(report.is_consistent());
+
+    let report = compare("trap", &[], &counter);
+    assert!(report.left.result.is_err());
+    assert!(report.is_consistent());
+}
+
+#[test]
+fn test_divergent_executions() {
+    // The host function returns a different value to each execution.
+    let counter = Arc::new(AtomicI32::new(0));
+    let report = compare("next", &[], &counter);
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        report.differences(),
+        ["results differ: [I32(0)] vs [I32(1)]"]
+    );
+}
+
+#[test]
+fn test_divergent_trap_codes() {
+    let trap = |trap_code| Execution {
+        result: Err("trap".to_string()),
+        trap_code,
+        memory: None,
+    };
+
+    // Backends that don't report trap codes only have to trap too.
+    let report = Report {
+        left: trap(Some(WasmTrapInfo::Unreachable)),
+        right: trap(None),
+    };
+    assert!(report.is_consistent());
+
+    let report = Report {
+        left: trap(Some(WasmTrapInfo::Unreachable)),
+        right: trap(Some(WasmTrapInfo::MemoryOutOfBounds)),
+    };
+    assert_eq!(report.differences().len(), 1);
+    assert!(report.differences()[0].starts_with("trap codes differ"));
+
+    let report = Report {
+        left: trap(None),
+        right: Execution {
+            result: Ok(vec![]),
+            trap_code: None,
+            memory: None,
+        },
+    };
+    assert!(!report.is_consistent());
+}
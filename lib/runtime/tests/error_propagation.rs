@@ -40,7 +40,7 @@ fn error_propagation() {
 
     let result = foo.call();
 
-    if let Err(RuntimeError::Error { data }) = result {
+    if let Err(RuntimeError::Error { data, .. }) = result {
         let exit_code = data.downcast::<ExitCode>().unwrap();
         assert_eq!(exit_code.code, 42);
     } else {
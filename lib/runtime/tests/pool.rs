@@ -0,0 +1,56 @@
+use wabt::wat2wasm;
+use wasmer_runtime::{compile, imports, pool::InstancePool, Func};
+
+static WAT: &'static str = r#"
+    (module
+      (memory 1)
+      (global $count (mut i32) (i32.const 0))
+      (func (export "bump") (result i32)
+        get_global $count
+        i32.const 1
+        i32.add
+        set_global $count
+        i32.const 0
+        i32.const 0
+        i32.load
+        get_global $count
+        i32.add
+        i32.store
+        i32.const 0
+        i32.load))
+"#;
+
+#[test]
+fn test_pooled_instances_are_reset() {
+    let wasm = wat2wasm(WAT).unwrap();
+    let module = compile(&wasm).unwrap();
+    let pool = InstancePool::new(&module, imports! {}, 2).unwrap();
+    assert_eq!(pool.idle_count(), 1);
+
+    {
+        let instance = pool.get().unwrap();
+        assert_eq!(pool.idle_count(), 0);
+        let bump: Func<(), i32> = instance.func("bump").unwrap();
+        assert_eq!(bump.call(), Ok(1));
+        assert_eq!(bump.call(), Ok(3));
+    }
+    assert_eq!(pool.idle_count(), 1);
+
+    // The instance is reused, with its memory and globals reset.
+    let first = pool.get().unwrap();
+    let second = pool.get().unwrap();
+    for instance in &[&first, &second] {
+        let bump: Func<(), i32> = instance.func("bump").unwrap();
+        assert_eq!(bump.call(), Ok(1));
+    }
+    drop(first);
+    drop(second);
+    assert_eq!(pool.idle_count(), 2);
+
+    // Detached instances don't go back to the pool.
+    pool.get().unwrap().detach();
+    assert_eq!(pool.idle_count(), 1);
+
+    pool.reserve(5).unwrap();
+    assert_eq!(pool.idle_count(), 2);
+}
@@ -0,0 +1,45 @@
+#![cfg(feature = "wat")]
+
+use wasmer_runtime::{compile, compile_wat, error::CompileError, imports, validate, Func};
+
+static WAT: &'static str = r#"
+    (module
+      (func (export "add_one") (param i32) (result i32)
+        get_local 0
+        i32.const 1
+        i32.add))
+"#;
+
+fn add_one(module: &wasmer_runtime::Module) -> i32 {
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let add_one: Func<i32, i32> = instance.func("add_one").unwrap();
+    add_one.call(41).unwrap()
+}
+
+#[test]
+fn test_compile_wat() {
+    assert_eq!(add_one(&compile_wat(WAT).unwrap()), 42);
+}
+
+#[test]
+fn test_compile_detects_text() {
+    assert_eq!(add_one(&compile(WAT.as_bytes()).unwrap()), 42);
+
+    // Binaries are passed through untouched.
+    let wasm = wabt::wat2wasm(WAT).unwrap();
+    assert!(validate(&wasm));
+    assert_eq!(add_one(&compile(&wasm).unwrap()), 42);
+}
+
+#[test]
+fn test_invalid_text_is_a_validation_error() {
+    for result in &[compile_wat("(module (func"), compile(b"(module (func")] {
+        match result {
+            Err(CompileError::ValidationError { msg }) => {
+                assert!(msg.starts_with("failed to parse the text format"))
+            }
+            Err(error) => panic!("unexpected error: {:?}", error),
+            Ok(_) => panic!("invalid text was compiled"),
+        }
+    }
+}
@@ -378,7 +378,23 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
         Backend::Singlepass
     }
 
-    fn check_precondition(&mut self, _module_info: &ModuleInfo) -> Result<(), CodegenError> {
+    fn check_precondition(&mut self, module_info: &ModuleInfo) -> Result<(), CodegenError> {
+        let uses_v128 = module_info.signatures.iter().any(|(_, sig)| {
+            sig.params()
+                .iter()
+                .chain(sig.returns().iter())
+                .any(|&ty| ty == Type::V128)
+        }) || module_info
+            .globals
+            .iter()
+            .any(|(_, global)| global.desc.ty == Type::V128)
+            || module_info
+                .imported_globals
+                .iter()
+                .any(|(_, (_, desc))| desc.ty == Type::V128);
+        if uses_v128 {
+            return Err(unsupported_v128());
+        }
         Ok(())
     }
 
@@ -1485,13 +1501,16 @@ impl X64FunctionCode {
                 &module_info.imported_memories[import_mem_index].1
             }
         };
+        // Dynamic memories have no guard region large enough to catch every
+        // out-of-bounds access, so they are always checked explicitly.
         let need_check = match config.memory_bound_check_mode {
-            MemoryBoundCheckMode::Default => match mem_desc.memory_type() {
-                MemoryType::Dynamic => true,
-                MemoryType::Static | MemoryType::SharedStatic => false,
-            },
+            MemoryBoundCheckMode::Default | MemoryBoundCheckMode::Disable => {
+                match mem_desc.memory_type() {
+                    MemoryType::Dynamic => true,
+                    MemoryType::Static | MemoryType::SharedStatic => false,
+                }
+            }
             MemoryBoundCheckMode::Enable => true,
-            MemoryBoundCheckMode::Disable => false,
         };
 
         let tmp_addr = m.acquire_temp_gpr().unwrap();
@@ -1760,17 +1779,27 @@ impl X64FunctionCode {
 
 impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
     fn feed_return(&mut self, ty: WpType) -> Result<(), CodegenError> {
+        if ty == WpType::V128 {
+            return Err(unsupported_v128());
+        }
         self.returns.push(ty);
         Ok(())
     }
 
-    fn feed_param(&mut self, _ty: WpType) -> Result<(), CodegenError> {
+    fn feed_param(&mut self, ty: WpType) -> Result<(), CodegenError> {
+        if ty == WpType::V128 {
+            return Err(unsupported_v128());
+        }
         self.num_params += 1;
         self.num_locals += 1;
         Ok(())
     }
 
-    fn feed_local(&mut self, _ty: WpType, n: usize) -> Result<(), CodegenError> {
+    fn feed_local(&mut self, ty: WpType, n: usize) -> Result<(), CodegenError> {
+        // Locals live in 64-bit slots, so a v128 local would be silently truncated.
+        if ty == WpType::V128 {
+            return Err(unsupported_v128());
+        }
         self.num_locals += n;
         Ok(())
     }
@@ -1998,6 +2027,113 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                             Location::Memory(tmp, (idx * 8) as i32),
                         );
                         self.machine.release_temp_gpr(tmp);
+                    }
+                    InternalEvent::AddInternal(idx, value) => {
+                        let idx = idx as usize;
+                        assert!(idx < INTERNALS_SIZE);
+
+                        let tmp = self.machine.acquire_temp_gpr().unwrap();
+
+                        // Load `internals` pointer.
+                        a.emit_mov(
+                            Size::S64,
+                            Location::Memory(
+                                Machine::get_vmctx_reg(),
+                                vm::Ctx::offset_internals() as i32,
+                            ),
+                            Location::GPR(tmp),
+                        );
+
+                        // Add in place, with a single instruction if the value fits in imm32.
+                        if value <= ::std::i32::MAX as u64 {
+                            a.emit_add(
+                                Size::S64,
+                                Location::Imm32(value as u32),
+                                Location::Memory(tmp, (idx * 8) as i32),
+                            );
+                        } else {
+                            let value_tmp = self.machine.acquire_temp_gpr().unwrap();
+                            a.emit_mov(Size::S64, Location::Imm64(value), Location::GPR(value_tmp));
+                            a.emit_add(
+                                Size::S64,
+                                Location::GPR(value_tmp),
+                                Location::Memory(tmp, (idx * 8) as i32),
+                            );
+                            self.machine.release_temp_gpr(value_tmp);
+                        }
+                        self.machine.release_temp_gpr(tmp);
+                    }
+                    InternalEvent::BreakpointIfInternalAtLeast(idx, limit, callback) => {
+                        let idx = idx as usize;
+                        assert!(idx < INTERNALS_SIZE);
+
+                        let tmp = self.machine.acquire_temp_gpr().unwrap();
+                        let limit_tmp = self.machine.acquire_temp_gpr().unwrap();
+
+                        // Load `internals` pointer.
+                        a.emit_mov(
+                            Size::S64,
+                            Location::Memory(
+                                Machine::get_vmctx_reg(),
+                                vm::Ctx::offset_internals() as i32,
+                            ),
+                            Location::GPR(tmp),
+                        );
+                        a.emit_mov(Size::S64, Location::Imm64(limit), Location::GPR(limit_tmp));
+                        a.emit_cmp(
+                            Size::S64,
+                            Location::GPR(limit_tmp),
+                            Location::Memory(tmp, (idx * 8) as i32),
+                        );
+                        self.machine.release_temp_gpr(limit_tmp);
+                        self.machine.release_temp_gpr(tmp);
+
+                        let below_limit = a.get_label();
+                        a.emit_jmp(Condition::Below, below_limit);
+                        a.emit_bkpt();
+                        self.breakpoints
+                            .as_mut()
+                            .unwrap()
+                            .insert(a.get_offset(), callback);
+                        a.emit_label(below_limit);
+                    }
+                    InternalEvent::IncrementCounter(idx, counter) => {
+                        let idx = idx as usize;
+                        assert!(idx < INTERNALS_SIZE);
+                        // The offset of the counter is a 32-bit displacement.
+                        if counter >= (1 << 28) {
+                            return Err(CodegenError {
+                                message: format!("counter {} is out of range", counter),
+                            });
+                        }
+
+                        let tmp = self.machine.acquire_temp_gpr().unwrap();
+
+                        // Load `internals` pointer.
+                        a.emit_mov(
+                            Size::S64,
+                            Location::Memory(
+                                Machine::get_vmctx_reg(),
+                                vm::Ctx::offset_internals() as i32,
+                            ),
+                            Location::GPR(tmp),
+                        );
+                        // Load the buffer address, and skip the increment if there's none.
+                        a.emit_mov(
+                            Size::S64,
+                            Location::Memory(tmp, (idx * 8) as i32),
+                            Location::GPR(tmp),
+                        );
+                        let detached = a.get_label();
+                        a.emit_test_gpr_64(tmp);
+                        a.emit_jmp(Condition::Equal, detached);
+                        a.emit_add(
+                            Size::S64,
+                            Location::Imm32(1),
+                            Location::Memory(tmp, (counter * 8) as i32),
+                        );
+                        a.emit_label(detached);
+                        self.machine.release_temp_gpr(tmp);
                     } //_ => unimplemented!(),
                 }
                 return Ok(());
@@ -7676,6 +7812,11 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 a.emit_pop(Size::S64, Location::GPR(value));
                 self.machine.release_temp_gpr(compare);
             }
+            Operator::I32Wait { .. } | Operator::I64Wait { .. } | Operator::Wake { .. } => {
+                return Err(CodegenError {
+                    message: "memory.atomic.wait and memory.atomic.notify are not supported by the singlepass backend".to_string(),
+                });
+            }
             _ => {
                 return Err(CodegenError {
                     message: format!("not yet implemented: {:?}", op),
@@ -7687,6 +7828,12 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
     }
 }
 
+fn unsupported_v128() -> CodegenError {
+    CodegenError {
+        message: "SIMD (v128) is not supported by the singlepass backend".to_string(),
+    }
+}
+
 fn type_to_wp_type(ty: Type) -> WpType {
     match ty {
         Type::I32 => WpType::I32,
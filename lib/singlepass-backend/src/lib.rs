@@ -11,9 +11,15 @@
 #![doc(html_favicon_url = "https://wasmer.io/static/icons/favicon.ico")]
 #![doc(html_logo_url = "https://avatars3.githubusercontent.com/u/44205449?s=200&v=4")]
 
+#[cfg(target_arch = "aarch64")]
+compile_error!("The singlepass backend only emits x86_64 code and doesn't support aarch64, use the LLVM backend instead");
+#[cfg(all(target_os = "windows", target_arch = "x86_64"))]
+compile_error!("The singlepass backend emits code for the System V calling convention and catches traps with Unix signals, so it doesn't support Windows, use the Cranelift or LLVM backend instead");
 #[cfg(not(any(
     all(target_os = "macos", target_arch = "x86_64"),
     all(target_os = "linux", target_arch = "x86_64"),
+    all(target_os = "windows", target_arch = "x86_64"),
+    target_arch = "aarch64",
 )))]
 compile_error!("This crate doesn't yet support compiling on operating systems other than linux and macos and architectures other than x86_64");
 
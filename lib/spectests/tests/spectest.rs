@@ -225,6 +225,7 @@ mod tests {
                             features: Features {
                                 simd: true,
                                 threads: true,
+                                multi_value: false,
                             },
                             ..Default::default()
                         };
@@ -576,7 +577,8 @@ mod tests {
                                                     // TODO assert message?
                                                     test_report.count_passed()
                                                 }
-                                                RuntimeError::Error { .. } => {
+                                                RuntimeError::Error { .. }
+                                                | RuntimeError::Interrupted => {
                                                     test_report.add_failure(
                                                         SpecFailure {
                                                             file: filename.to_string(),
@@ -619,6 +621,7 @@ mod tests {
                             features: Features {
                                 simd: true,
                                 threads: true,
+                                multi_value: false,
                             },
                             ..Default::default()
                         };
@@ -673,6 +676,7 @@ mod tests {
                             features: Features {
                                 simd: true,
                                 threads: true,
+                                multi_value: false,
                             },
                             ..Default::default()
                         };
@@ -726,6 +730,7 @@ mod tests {
                         features: Features {
                             simd: true,
                             threads: true,
+                            multi_value: false,
                         },
                         ..Default::default()
                     };
@@ -822,6 +827,7 @@ mod tests {
                             features: Features {
                                 simd: true,
                                 threads: true,
+                                multi_value: false,
                             },
                             ..Default::default()
                         };
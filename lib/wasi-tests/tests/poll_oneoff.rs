@@ -0,0 +1,194 @@
+use std::convert::TryInto;
+use std::time::{Duration, Instant};
+use wasmer_runtime::{compile, Func, ImportObject, Instance};
+use wasmer_wasi::{
+    generate_import_object, generate_import_object_from_builder,
+    state::{OutputBuffer, WasiState},
+    types::*,
+};
+
+static WAT: &'static str = r#"
+(module
+  (import "wasi_unstable" "poll_oneoff"
+    (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "poll") (param i32) (result i32)
+    i32.const 0
+    i32.const 512
+    get_local 0
+    i32.const 1024
+    call $poll_oneoff))
+"#;
+
+/// The size of a subscription of `wasi_unstable`, written from the start of the memory.
+const SUBSCRIPTION_SIZE: usize = 56;
+/// The size of an event, written from offset 512.
+const EVENT_SIZE: usize = 32;
+
+#[derive(Debug, PartialEq)]
+struct Event {
+    userdata: u64,
+    error: __wasi_errno_t,
+    type_: __wasi_eventtype_t,
+}
+
+fn instantiate(import_object: &ImportObject) -> Instance {
+    let wasm = wabt::wat2wasm(WAT).unwrap();
+    compile(&wasm).unwrap().instantiate(import_object).unwrap()
+}
+
+/// A subscription to a relative timeout of the monotonic clock.
+fn clock(userdata: u64, timeout: Duration) -> [u8; SUBSCRIPTION_SIZE] {
+    let mut sub = [0; SUBSCRIPTION_SIZE];
+    sub[0..8].copy_from_slice(&userdata.to_le_bytes());
+    sub[8] = __WASI_EVENTTYPE_CLOCK;
+    sub[24..28].copy_from_slice(&__WASI_CLOCK_MONOTONIC.to_le_bytes());
+    sub[32..40].copy_from_slice(&(timeout.as_nanos() as u64).to_le_bytes());
+    sub
+}
+
+/// A subscription to `fd` being ready for `type_`, reading or writing.
+fn fd(userdata: u64, type_: __wasi_eventtype_t, fd: __wasi_fd_t) -> [u8; SUBSCRIPTION_SIZE] {
+    let mut sub = [0; SUBSCRIPTION_SIZE];
+    sub[0..8].copy_from_slice(&userdata.to_le_bytes());
+    sub[8] = type_;
+    sub[16..20].copy_from_slice(&fd.to_le_bytes());
+    sub
+}
+
+/// Polls `subscriptions` and returns the events, sorted by userdata.
+fn poll(instance: &Instance, subscriptions: &[[u8; SUBSCRIPTION_SIZE]]) -> Vec<Event> {
+    let memory = instance.context().memory(0);
+    let view = memory.view::<u8>();
+    for (cell, byte) in view
+        .iter()
+        .zip(subscriptions.iter().flat_map(|sub| sub.iter()))
+    {
+        cell.set(*byte);
+    }
+
+    let poll: Func<i32, i32> = instance.func("poll").unwrap();
+    let errno = poll.call(subscriptions.len() as i32).unwrap();
+    assert_eq!(errno, __WASI_ESUCCESS as i32);
+
+    let bytes = |offset: usize, len: usize| -> Vec<u8> {
+        view[offset..offset + len]
+            .iter()
+            .map(|cell| cell.get())
+            .collect()
+    };
+    let nevents = u32::from_le_bytes(bytes(1024, 4)[..].try_into().unwrap()) as usize;
+    let mut events: Vec<Event> = (0..nevents)
+        .map(|i| {
+            let event = bytes(512 + i * EVENT_SIZE, EVENT_SIZE);
+            Event {
+                userdata: u64::from_le_bytes(event[0..8].try_into().unwrap()),
+                error: u16::from_le_bytes(event[8..10].try_into().unwrap()),
+                type_: event[10],
+            }
+        })
+        .collect();
+    events.sort_by_key(|event| event.userdata);
+    events
+}
+
+#[test]
+fn test_poll_clocks_only() {
+    let instance = instantiate(&generate_import_object(vec![], vec![], vec![], vec![]));
+
+    let start = Instant::now();
+    let events = poll(
+        &instance,
+        &[
+            clock(1, Duration::from_millis(10)),
+            clock(2, Duration::from_secs(10)),
+        ],
+    );
+    assert!(start.elapsed() >= Duration::from_millis(10));
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(
+        events,
+        [Event {
+            userdata: 1,
+            error: __WASI_ESUCCESS,
+            type_: __WASI_EVENTTYPE_CLOCK,
+        }]
+    );
+}
+
+#[test]
+fn test_poll_ready_fds() {
+    let mut builder = WasiState::new("poll");
+    builder.stdout(OutputBuffer::new());
+    let instance = instantiate(&generate_import_object_from_builder(builder).unwrap());
+
+    // The directory can't be waited for, so it's reported right away with an error.
+    let root = 3;
+    let start = Instant::now();
+    let events = poll(
+        &instance,
+        &[
+            fd(1, __WASI_EVENTTYPE_FD_WRITE, __WASI_STDOUT_FILENO),
+            fd(2, __WASI_EVENTTYPE_FD_READ, root),
+            clock(3, Duration::from_secs(10)),
+        ],
+    );
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(
+        events,
+        [
+            Event {
+                userdata: 1,
+                error: __WASI_ESUCCESS,
+                type_: __WASI_EVENTTYPE_FD_WRITE,
+            },
+            Event {
+                userdata: 2,
+                error: __WASI_ENOTSUP,
+                type_: __WASI_EVENTTYPE_FD_READ,
+            },
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_poll_timeout_expires() {
+    use std::{
+        fs::File,
+        os::unix::{io::FromRawFd, io::IntoRawFd, net::UnixStream},
+        path::PathBuf,
+    };
+    use wasmer_wasi::state::{get_wasi_state, HostFile};
+
+    let mut instance = instantiate(&generate_import_object(vec![], vec![], vec![], vec![]));
+    // Nothing is ever written to stdin, but its writer stays open.
+    let (reader, _writer) = UnixStream::pair().unwrap();
+    let stdin = unsafe { File::from_raw_fd(reader.into_raw_fd()) };
+    let state = unsafe { get_wasi_state(instance.context_mut()) };
+    *state.fs.stdin_mut().unwrap() = Some(Box::new(HostFile::new(
+        stdin,
+        PathBuf::from("stdin"),
+        true,
+        false,
+        false,
+    )));
+
+    let start = Instant::now();
+    let events = poll(
+        &instance,
+        &[
+            fd(1, __WASI_EVENTTYPE_FD_READ, __WASI_STDIN_FILENO),
+            clock(2, Duration::from_millis(10)),
+        ],
+    );
+    assert!(start.elapsed() >= Duration::from_millis(10));
+    assert_eq!(
+        events,
+        [Event {
+            userdata: 2,
+            error: __WASI_ESUCCESS,
+            type_: __WASI_EVENTTYPE_CLOCK,
+        }]
+    );
+}
@@ -0,0 +1,51 @@
+use wasmer_runtime::{compile, Instance, Value};
+use wasmer_wasi::{exit_code, generate_import_object, WasiError};
+
+static WAT: &'static str = r#"
+(module
+  (import "wasi_unstable" "proc_exit" (func $proc_exit (param i32)))
+  (func (export "exit") (param i32)
+    get_local 0
+    call $proc_exit
+    unreachable)
+  (func (export "trap")
+    unreachable)
+  (func (export "return")))
+"#;
+
+fn instantiate() -> Instance {
+    let wasm = wabt::wat2wasm(WAT).unwrap();
+    compile(&wasm)
+        .unwrap()
+        .instantiate(&generate_import_object(vec![], vec![], vec![], vec![]))
+        .unwrap()
+}
+
+#[test]
+fn test_exit_is_a_wasi_error() {
+    let instance = instantiate();
+
+    let result = instance.call("exit", &[Value::I32(3)]);
+    let error = WasiError::from_call_error(result.as_ref().unwrap_err());
+    assert_eq!(error, Some(WasiError::Exit(3)));
+    assert_eq!(error.unwrap().to_string(), "WASI exited with code 3");
+    assert_eq!(exit_code(&result), Some(3));
+
+    // The code is passed on as it is, even out of the range of the exit codes of the host.
+    let result = instance.call("exit", &[Value::I32(-1)]);
+    assert_eq!(exit_code(&result), Some(-1));
+}
+
+#[test]
+fn test_traps_are_not_wasi_errors() {
+    let instance = instantiate();
+
+    let result = instance.call("trap", &[]);
+    assert_eq!(
+        WasiError::from_call_error(result.as_ref().unwrap_err()),
+        None
+    );
+    assert_eq!(exit_code(&result), None);
+
+    assert_eq!(exit_code(&instance.call("return", &[])), Some(0));
+}
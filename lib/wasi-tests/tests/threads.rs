@@ -0,0 +1,103 @@
+// The other backends don't support the threads feature.
+#![cfg(feature = "llvm")]
+
+use std::{
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+use wasmer_llvm_backend::LLVMCompiler;
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    compile_with_config, Func,
+};
+use wasmer_wasi::{generate_import_object_with_threads, state::WasiState, types::*};
+
+// The memory holds the counter at 0, the number of finished threads at 4, the number of runs of
+// the start function at 8 and the number of threads which could write to stdout at 12.
+static WAT: &'static str = r#"
+(module
+  (import "env" "memory" (memory 1 1 shared))
+  (import "wasi" "thread-spawn" (func $thread_spawn (param i32) (result i32)))
+  (import "wasi_unstable" "fd_close" (func $fd_close (param i32) (result i32)))
+  (import "wasi_unstable" "fd_write"
+    (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (data (i32.const 0) "\64\00\00\00")
+  ;; an iovec of the "x" at 32
+  (data (i32.const 16) "\20\00\00\00\01\00\00\00")
+  (data (i32.const 32) "x")
+  (start $start)
+  (func $start
+    i32.const 8
+    i32.const 1
+    i32.atomic.rmw.add
+    drop)
+  (func (export "close_stdout") (result i32)
+    i32.const 1
+    call $fd_close)
+  (func (export "spawn") (param i32) (result i32)
+    get_local 0
+    call $thread_spawn)
+  (func (export "wasi_thread_start") (param i32 i32)
+    i32.const 0
+    i32.const 1
+    i32.atomic.rmw.add
+    drop
+    (if (i32.eqz (call $fd_write (i32.const 1) (i32.const 16) (i32.const 1) (i32.const 40)))
+      (then
+        i32.const 12
+        i32.const 1
+        i32.atomic.rmw.add
+        drop))
+    i32.const 4
+    i32.const 1
+    i32.atomic.rmw.add
+    drop))
+"#;
+
+const THREADS: u32 = 8;
+
+#[test]
+fn test_threads_share_memory_and_state() {
+    let mut features = wabt::Features::new();
+    features.enable_threads();
+    let wasm = wabt::wat2wasm_with_features(WAT, features).unwrap();
+    let module = compile_with_config(
+        &wasm,
+        &LLVMCompiler::new(),
+        CompilerConfig {
+            features: Features {
+                threads: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let import_object =
+        generate_import_object_with_threads(WasiState::new("threads"), &module).unwrap();
+    let instance = module.instantiate(&import_object).unwrap();
+
+    // The fds closed by this thread are closed for the spawned threads too.
+    let close_stdout: Func<(), i32> = instance.func("close_stdout").unwrap();
+    assert_eq!(close_stdout.call(), Ok(__WASI_ESUCCESS as i32));
+
+    let spawn: Func<i32, i32> = instance.func("spawn").unwrap();
+    for arg in 0..THREADS {
+        assert!(spawn.call(arg as i32).unwrap() > 0);
+    }
+
+    let memory = instance.context().memory(0);
+    let view = memory.view::<u32>();
+    let counters = view.atomically();
+    let start = Instant::now();
+    while counters[1].load(Ordering::SeqCst) < THREADS {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    // The spawned instances neither initialized the memory again nor ran the start function.
+    assert_eq!(counters[0].load(Ordering::SeqCst), 100 + THREADS);
+    assert_eq!(counters[2].load(Ordering::SeqCst), 1);
+    assert_eq!(counters[3].load(Ordering::SeqCst), 0);
+}
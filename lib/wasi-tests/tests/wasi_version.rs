@@ -0,0 +1,95 @@
+use wasmer_runtime::{compile, Module};
+use wasmer_runtime_core::types::{FuncSig, Type};
+use wasmer_wasi::{check_wasi_imports, get_wasi_version, WasiImportError, WasiVersion};
+
+fn module(imports: &str) -> Module {
+    let wasm = wabt::wat2wasm(format!("(module {})", imports)).unwrap();
+    compile(&wasm).unwrap()
+}
+
+const FD_CLOSE_0: &str = r#"(import "wasi_unstable" "fd_close" (func (param i32) (result i32)))"#;
+const FD_CLOSE_1: &str =
+    r#"(import "wasi_snapshot_preview1" "fd_close" (func (param i32) (result i32)))"#;
+const ENV_FUNC: &str = r#"(import "env" "func" (func))"#;
+
+#[test]
+fn test_version_of_one_wasi_namespace() {
+    for &strict in &[true, false] {
+        assert_eq!(
+            get_wasi_version(&module(FD_CLOSE_0), strict),
+            Some(WasiVersion::Snapshot0)
+        );
+        assert_eq!(
+            get_wasi_version(&module(FD_CLOSE_1), strict),
+            Some(WasiVersion::Snapshot1)
+        );
+        assert_eq!(get_wasi_version(&module(""), strict), None);
+        assert_eq!(get_wasi_version(&module(ENV_FUNC), strict), None);
+    }
+}
+
+#[test]
+fn test_version_of_several_namespaces() {
+    // The newest version is returned, in whichever order they're imported.
+    for imports in &[
+        format!("{} {}", FD_CLOSE_0, FD_CLOSE_1),
+        format!("{} {}", FD_CLOSE_1, FD_CLOSE_0),
+    ] {
+        let module = module(imports);
+        assert_eq!(get_wasi_version(&module, true), None);
+        assert_eq!(
+            get_wasi_version(&module, false),
+            Some(WasiVersion::Snapshot1)
+        );
+    }
+
+    let module = module(&format!("{} {}", ENV_FUNC, FD_CLOSE_0));
+    assert_eq!(get_wasi_version(&module, true), None);
+    assert_eq!(
+        get_wasi_version(&module, false),
+        Some(WasiVersion::Snapshot0)
+    );
+}
+
+#[test]
+fn test_check_imports() {
+    let imports = format!("{} {} {}", FD_CLOSE_0, FD_CLOSE_1, ENV_FUNC);
+    assert_eq!(check_wasi_imports(&module(&imports)), Ok(()));
+
+    let error = check_wasi_imports(&module(
+        r#"(import "wasi_unstable" "fd_close2" (func (param i32) (result i32)))"#,
+    ))
+    .unwrap_err();
+    assert_eq!(
+        error,
+        WasiImportError::UnknownFunction {
+            namespace: "wasi_unstable".to_string(),
+            name: "fd_close2".to_string(),
+            suggested_version: None,
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "\"wasi_unstable\".\"fd_close2\" is not a function of WASI"
+    );
+
+    assert_eq!(
+        check_wasi_imports(&module(
+            r#"(import "wasi_snapshot_preview1" "fd_close" (func (param i64) (result i32)))"#,
+        )),
+        Err(WasiImportError::SignatureMismatch {
+            namespace: "wasi_snapshot_preview1".to_string(),
+            name: "fd_close".to_string(),
+            expected: FuncSig::new(vec![Type::I32], vec![Type::I32]),
+            found: FuncSig::new(vec![Type::I64], vec![Type::I32]),
+        })
+    );
+
+    assert_eq!(
+        check_wasi_imports(&module(r#"(import "wasi_unstable" "memory" (memory 1))"#)),
+        Err(WasiImportError::NotAFunction {
+            namespace: "wasi_unstable".to_string(),
+            name: "memory".to_string(),
+        })
+    );
+}
@@ -30,21 +30,85 @@ pub mod state;
 mod syscalls;
 mod utils;
 
-use self::state::{WasiFs, WasiState};
+use self::state::{
+    HostClock, HostRandom, SharedState, WasiFs, WasiState, WasiStateBuilder,
+    WasiStateCreationError, WasiThreads,
+};
 pub use self::syscalls::types;
 use self::syscalls::*;
 
 use std::ffi::c_void;
 use std::path::PathBuf;
+use std::sync::Arc;
 
-pub use self::utils::is_wasi_module;
+pub use self::utils::{
+    check_wasi_imports, get_wasi_version, is_wasi_module, WasiImportError, WasiVersion,
+};
 
-use wasmer_runtime_core::{func, import::ImportObject, imports};
+use wasmer_runtime_core::{
+    error::{CallError, RuntimeError},
+    func,
+    import::{ImportObject, Namespace},
+    imports,
+    memory::Memory,
+    module::ExternDescriptor,
+    vm::Ctx,
+    Module,
+};
 
-/// This is returned in the Box<dyn Any> RuntimeError::Error variant.
-/// Use `downcast` or `downcast_ref` to retrieve the `ExitCode`.
-pub struct ExitCode {
-    pub code: syscalls::types::__wasi_exitcode_t,
+/// The errors stopping WASI modules which aren't traps.
+///
+/// This is returned in the Box<dyn Any> RuntimeError::Error variant.  Use
+/// `WasiError::from_runtime_error` or `WasiError::from_call_error` to tell it apart from traps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasiError {
+    /// The module called `proc_exit` with this exit code
+    Exit(syscalls::types::__wasi_exitcode_t),
+}
+
+impl WasiError {
+    /// Returns the WASI error which stopped the module, or `None` if it was stopped by a trap,
+    /// by an interrupt or by the error of another import.
+    pub fn from_runtime_error(error: &RuntimeError) -> Option<WasiError> {
+        match error {
+            RuntimeError::Error { data, .. } => data.downcast_ref::<WasiError>().cloned(),
+            _ => None,
+        }
+    }
+
+    /// Like `from_runtime_error`, for the error of `Instance::call` or `Func::call`.
+    pub fn from_call_error(error: &CallError) -> Option<WasiError> {
+        match error {
+            CallError::Runtime(error) => Self::from_runtime_error(error),
+            CallError::Resolve(_) => None,
+        }
+    }
+
+    /// The exit code of the process running the module, as a C `exit` would get it.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            WasiError::Exit(code) => *code as i32,
+        }
+    }
+}
+
+impl std::fmt::Display for WasiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WasiError::Exit(code) => write!(f, "WASI exited with code {}", code),
+        }
+    }
+}
+
+impl std::error::Error for WasiError {}
+
+/// Returns the exit code of the process for the outcome of a call to `_start`: 0 if it
+/// returned, the code given to `proc_exit`, or `None` if the module failed otherwise.
+pub fn exit_code<T>(result: &Result<T, CallError>) -> Option<i32> {
+    match result {
+        Ok(_) => Some(0),
+        Err(error) => WasiError::from_call_error(error).map(|error| error.exit_code()),
+    }
 }
 
 /// Creates a Wasi [`ImportObject`] with [`WasiState`].
@@ -56,11 +120,6 @@ pub fn generate_import_object(
 ) -> ImportObject {
     let state_gen = move || {
         // TODO: look into removing all these unnecessary clones
-        fn state_destructor(data: *mut c_void) {
-            unsafe {
-                drop(Box::from_raw(data as *mut WasiState));
-            }
-        }
         let preopened_files = preopened_files.clone();
         let mapped_dirs = mapped_dirs.clone();
         //let wasi_builder = create_wasi_instance();
@@ -69,6 +128,13 @@ pub fn generate_import_object(
             fs: WasiFs::new(&preopened_files, &mapped_dirs).expect("Could not create WASI FS"),
             args: args.clone(),
             envs: envs.clone(),
+            deterministic: false,
+            network: Default::default(),
+            quotas: Default::default(),
+            clock: std::sync::Arc::new(HostClock),
+            random: std::sync::Arc::new(HostRandom),
+            fs_access_hook: None,
+            threads: None,
         });
 
         (
@@ -76,9 +142,128 @@ pub fn generate_import_object(
             state_destructor as fn(*mut c_void),
         )
     };
+    generate_import_object_with_state(state_gen)
+}
+
+/// Creates a Wasi [`ImportObject`] giving each instance a [`WasiState`] built by `builder`.
+///
+/// Returns the error of the builder if it can't build a state.
+pub fn generate_import_object_from_builder(
+    builder: WasiStateBuilder,
+) -> Result<ImportObject, WasiStateCreationError> {
+    builder.clone().build()?;
+    let state_gen = move || {
+        let state = Box::new(
+            builder
+                .clone()
+                .build()
+                .expect("Could not create WASI state"),
+        );
+
+        (
+            Box::into_raw(state) as *mut c_void,
+            state_destructor as fn(*mut c_void),
+        )
+    };
+    Ok(generate_import_object_with_state(state_gen))
+}
+
+/// Creates a Wasi [`ImportObject`] for `module`, a module using wasi-threads.
+///
+/// On top of the WASI calls, it provides `thread-spawn` in the `wasi` namespace, and a new shared
+/// memory for the memory imported by the module.  Each thread runs in a new instance of `module`
+/// sharing that memory and the [`WasiState`] built by `builder`.
+///
+/// Returns an error if the module doesn't import a shared memory.
+pub fn generate_import_object_with_threads(
+    builder: WasiStateBuilder,
+    module: &Module,
+) -> Result<ImportObject, WasiStateCreationError> {
+    let mut state = builder.build()?;
+    let desc = module
+        .imports()
+        .into_iter()
+        .find_map(|import| match import.ty {
+            ExternDescriptor::Memory(desc) if desc.shared => Some(desc),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            WasiStateCreationError::ThreadsUnsupported(
+                "the module doesn't import a shared memory".to_string(),
+            )
+        })?;
+    let memory = Memory::new(desc)
+        .map_err(|e| WasiStateCreationError::ThreadsUnsupported(format!("{:?}", e)))?;
+
+    let threads = Arc::new(WasiThreads::new(module.clone()));
+    state.threads = Some(threads.clone());
+    let state = SharedState(Arc::new(state));
+    Ok(generate_import_object_for_thread(threads, state, memory))
+}
+
+/// Creates the [`ImportObject`] of a thread of a module using wasi-threads, importing `memory`
+/// and sharing `state` with the other threads.
+pub(crate) fn generate_import_object_for_thread(
+    threads: Arc<WasiThreads>,
+    state: SharedState,
+    memory: Memory,
+) -> ImportObject {
+    let module = threads.module.clone();
+    let state_gen = move || {
+        (
+            Arc::into_raw(state.0.clone()) as *mut c_void,
+            shared_state_destructor as fn(*mut c_void),
+        )
+    };
+    let mut import_object = generate_import_object_with_state(state_gen);
+
+    let mut threads_namespace = Namespace::new();
+    threads_namespace.insert("thread-spawn", func!(threads::thread_spawn));
+
+    // the shared memory goes wherever the module imports it from
+    for import in module.imports() {
+        if let ExternDescriptor::Memory(_) = import.ty {
+            if import.namespace == "wasi" {
+                threads_namespace.insert(import.name, memory.clone());
+            } else {
+                let mut memory_namespace = Namespace::new();
+                memory_namespace.insert(import.name, memory.clone());
+                import_object.register(import.namespace, memory_namespace);
+            }
+        }
+    }
+    import_object.register("wasi", threads_namespace);
+    import_object
+}
+
+fn state_destructor(data: *mut c_void) {
+    unsafe {
+        drop(Box::from_raw(data as *mut WasiState));
+    }
+}
+
+fn shared_state_destructor(data: *mut c_void) {
+    unsafe {
+        drop(Arc::from_raw(data as *const WasiState));
+    }
+}
+
+/// Returns the state shared by the threads of the module of `ctx`, which must be one of them.
+pub(crate) unsafe fn get_shared_state(ctx: &Ctx) -> SharedState {
+    let state = Arc::from_raw(ctx.data as *const WasiState);
+    let shared = state.clone();
+    std::mem::forget(state);
+    SharedState(shared)
+}
+
+fn generate_import_object_with_state<F>(state_gen: F) -> ImportObject
+where
+    F: Fn() -> (*mut c_void, fn(*mut c_void)) + 'static + Send + Sync,
+{
     imports! {
         // This generates the wasi state.
         state_gen,
+        // Both versions are registered, so that modules importing either, or both, link.
         "wasi_unstable" => {
             "args_get" => func!(args_get),
             "args_sizes_get" => func!(args_sizes_get),
@@ -126,5 +311,58 @@ pub fn generate_import_object(
             "sock_send" => func!(sock_send),
             "sock_shutdown" => func!(sock_shutdown),
         },
+        "wasi_snapshot_preview1" => {
+            "args_get" => func!(args_get),
+            "args_sizes_get" => func!(args_sizes_get),
+            "clock_res_get" => func!(clock_res_get),
+            "clock_time_get" => func!(clock_time_get),
+            "environ_get" => func!(environ_get),
+            "environ_sizes_get" => func!(environ_sizes_get),
+            "fd_advise" => func!(fd_advise),
+            "fd_allocate" => func!(fd_allocate),
+            "fd_close" => func!(fd_close),
+            "fd_datasync" => func!(fd_datasync),
+            "fd_fdstat_get" => func!(fd_fdstat_get),
+            "fd_fdstat_set_flags" => func!(fd_fdstat_set_flags),
+            "fd_fdstat_set_rights" => func!(fd_fdstat_set_rights),
+            "fd_filestat_get" => func!(snapshot1::fd_filestat_get),
+            "fd_filestat_set_size" => func!(fd_filestat_set_size),
+            "fd_filestat_set_times" => func!(fd_filestat_set_times),
+            "fd_pread" => func!(fd_pread),
+            "fd_prestat_get" => func!(fd_prestat_get),
+            "fd_prestat_dir_name" => func!(fd_prestat_dir_name),
+            "fd_pwrite" => func!(fd_pwrite),
+            "fd_read" => func!(fd_read),
+            "fd_readdir" => func!(fd_readdir),
+            "fd_renumber" => func!(fd_renumber),
+            "fd_seek" => func!(snapshot1::fd_seek),
+            "fd_sync" => func!(fd_sync),
+            "fd_tell" => func!(fd_tell),
+            "fd_write" => func!(fd_write),
+            "path_create_directory" => func!(path_create_directory),
+            "path_filestat_get" => func!(snapshot1::path_filestat_get),
+            "path_filestat_set_times" => func!(path_filestat_set_times),
+            "path_link" => func!(path_link),
+            "path_open" => func!(path_open),
+            "path_readlink" => func!(path_readlink),
+            "path_remove_directory" => func!(path_remove_directory),
+            "path_rename" => func!(path_rename),
+            "path_symlink" => func!(path_symlink),
+            "path_unlink_file" => func!(path_unlink_file),
+            "poll_oneoff" => func!(snapshot1::poll_oneoff),
+            "proc_exit" => func!(proc_exit),
+            "proc_raise" => func!(proc_raise),
+            "random_get" => func!(random_get),
+            "sched_yield" => func!(sched_yield),
+            "sock_recv" => func!(sock_recv),
+            "sock_send" => func!(sock_send),
+            "sock_shutdown" => func!(sock_shutdown),
+        },
+        // The calls creating sockets, which WASI doesn't have yet.
+        "wasi_experimental_network" => {
+            "sock_accept" => func!(network::sock_accept),
+            "sock_connect" => func!(network::sock_connect),
+            "sock_listen" => func!(network::sock_listen),
+        },
     }
 }
@@ -0,0 +1,65 @@
+//! A hook deciding, call by call, whether WASI modules may touch the paths of the backend.
+//!
+//! Preopened directories and path policies are set once, when the state is built; the hook is
+//! asked before every syscall reaching a path of the backend, with what the syscall is about to
+//! do and the rights of the fd it goes through, so it can allow, deny or log each access.
+
+use crate::syscalls::types::*;
+use std::path::Path;
+
+/// What a syscall is about to do to a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FsOperation {
+    /// Opening an existing file or directory, in `path_open`
+    Open,
+    /// Creating a file, in `path_open`
+    CreateFile,
+    /// Creating a directory, in `path_create_directory`
+    CreateDirectory,
+    /// Listing a directory, in `fd_readdir`
+    ReadDirectory,
+    /// Reading the stat of a file or directory, in `path_filestat_get`
+    Stat,
+    /// Changing the timestamps of a file or directory, in `path_filestat_set_times`
+    SetTimes,
+    /// Renaming a file or directory, in `path_rename`; it's asked for the old path and for the
+    /// new path
+    Rename,
+    /// Removing a file, in `path_unlink_file`
+    RemoveFile,
+    /// Removing a directory, in `path_remove_directory`
+    RemoveDirectory,
+}
+
+/// An access to a path of the backend, about to be made by a syscall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsAccess<'a> {
+    /// The path of the backend, like the ones passed to `WasiStateBuilder::preopen_dir`
+    pub path: &'a Path,
+    pub operation: FsOperation,
+    /// The rights of the fd the path is relative to
+    pub rights: __wasi_rights_t,
+}
+
+/// Decides whether the accesses to the paths of the backend are allowed.
+///
+/// Denied accesses fail with `__WASI_EACCES`.  Any `Fn(&FsAccess) -> bool` is a hook.
+pub trait FsAccessHook: Send + Sync {
+    /// Returns whether `access` is allowed.
+    fn check(&self, access: &FsAccess) -> bool;
+}
+
+impl<F> FsAccessHook for F
+where
+    F: Fn(&FsAccess) -> bool + Send + Sync,
+{
+    fn check(&self, access: &FsAccess) -> bool {
+        self(access)
+    }
+}
+
+impl std::fmt::Debug for dyn FsAccessHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FsAccessHook")
+    }
+}
@@ -0,0 +1,433 @@
+//! WASI files whose I/O is done by an async reactor, like the one of tokio or mio.
+//!
+//! An `AsyncFile` wraps an `AsyncIo`, an I/O object of the embedder returning futures.  Reading
+//! from, writing to and polling the file waits for these futures with `async_import::suspend_on`:
+//! when the module is run by `async_import::call_async`, it's suspended while the executor
+//! polling the call drives the I/O, so no host thread is blocked waiting for the guest's I/O.
+//! Outside of `call_async`, the calls block until the futures resolve.
+//!
+//! ```ignore
+//! let file = AsyncFile::new(Arc::new(MyTokioStream::new(stream)));
+//! state.fs.open_file_at(base_fd, Box::new(file), __WASI_O_CREAT, "conn".to_string(),
+//!                       rights, rights, 0)?;
+//! let result = async_import::call_async(move || start.call()).await;
+//! ```
+
+use crate::state::{
+    iterate_poll_events, poll, PollEvent, PollEventBuilder, PollEventSet, WasiFile, WasiFsError,
+};
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    future::Future,
+    io::{self, Read, Seek, Write},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use wasmer_runtime_core::async_import::suspend_on;
+
+/// A future of an `AsyncIo`.
+pub type IoFuture<T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send>>;
+
+/// An I/O object driven by an async reactor.
+///
+/// The futures returned may outlive `self`, so they must own what they need, e.g. a clone of
+/// the `Arc` holding the stream.
+pub trait AsyncIo: Send + Sync {
+    /// Reads up to `max_len` bytes.  Resolving to no bytes means the end of the stream
+    fn read(&self, max_len: usize) -> IoFuture<Vec<u8>>;
+
+    /// Writes some of `buf`, and resolves to the number of bytes written
+    fn write(&self, buf: Vec<u8>) -> IoFuture<usize>;
+
+    /// Flushes the data written.  Default does nothing
+    fn flush(&self) -> IoFuture<()> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Resolves once reading won't have to wait
+    fn readable(&self) -> IoFuture<()>;
+
+    /// Resolves once writing won't have to wait
+    fn writable(&self) -> IoFuture<()>;
+
+    /// Resolves after `duration`, on the timer of the reactor.  It bounds the time
+    /// `poll_oneoff` waits for this object
+    fn sleep(&self, duration: Duration) -> IoFuture<()>;
+}
+
+/// A file doing its I/O through an [`AsyncIo`].
+///
+/// Like sockets, async files aren't serialized: a `WasiState` unfrozen from bytes has them closed.
+#[derive(Serialize, Deserialize)]
+pub struct AsyncFile {
+    #[serde(skip)]
+    inner: Option<Arc<dyn AsyncIo>>,
+}
+
+impl AsyncFile {
+    pub fn new(io: Arc<dyn AsyncIo>) -> Self {
+        Self { inner: Some(io) }
+    }
+
+    fn io(&self) -> io::Result<&Arc<dyn AsyncIo>> {
+        self.inner
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "the file was not restored"))
+    }
+}
+
+impl std::fmt::Debug for AsyncFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncFile")
+            .field("closed", &self.inner.is_none())
+            .finish()
+    }
+}
+
+impl Read for AsyncFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes = suspend_on(self.io()?.read(buf.len()))?;
+        let len = bytes.len().min(buf.len());
+        buf[..len].copy_from_slice(&bytes[..len]);
+        Ok(len)
+    }
+}
+impl Write for AsyncFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        suspend_on(self.io()?.write(buf.to_vec()))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        suspend_on(self.io()?.flush())
+    }
+}
+impl Seek for AsyncFile {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not seek an async file",
+        ))
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for AsyncFile {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        // the stream isn't a file of the backend, there is nothing to remove
+        Err(WasiFsError::PermissionDenied)
+    }
+
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        // the reactor only tells whether the file is ready
+        Ok(0)
+    }
+
+    fn async_io(&self) -> Option<Arc<dyn AsyncIo>> {
+        self.inner.clone()
+    }
+}
+
+/// Waits for the first of its futures to resolve, or for its timeout.
+struct ReadyAny {
+    /// The index of the file, the event waited for, and its future
+    waits: Vec<(usize, PollEventSet, IoFuture<()>)>,
+    timeout: Option<IoFuture<()>>,
+}
+
+impl Future for ReadyAny {
+    type Output = Vec<(usize, PollEventSet)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut ready = vec![];
+        for (index, event, future) in this.waits.iter_mut() {
+            match future.as_mut().poll(cx) {
+                Poll::Ready(Ok(())) => ready.push((*index, *event)),
+                Poll::Ready(Err(_)) => ready.push((
+                    *index,
+                    PollEventBuilder::new().add(PollEvent::PollError).build(),
+                )),
+                Poll::Pending => (),
+            }
+        }
+        if !ready.is_empty() {
+            return Poll::Ready(ready);
+        }
+        match &mut this.timeout {
+            Some(timeout) if timeout.as_mut().poll(cx).is_ready() => Poll::Ready(vec![]),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+/// Like `poll`, for files among which some are [`AsyncFile`]s.
+///
+/// The other files are polled first, without waiting: if none of them is ready, only the async
+/// files are waited for, on their reactor.
+pub(crate) fn poll_async(
+    selfs: &[&dyn WasiFile],
+    events: &[PollEventSet],
+    seen_events: &mut [PollEventSet],
+    timeout: Option<Duration>,
+) -> Result<u32, WasiFsError> {
+    if !(selfs.len() == events.len() && events.len() == seen_events.len()) {
+        return Err(WasiFsError::InvalidInput);
+    }
+    let mut others = vec![];
+    let mut waits = vec![];
+    let mut sleep = None;
+    for (i, file) in selfs.iter().enumerate() {
+        let io = match file.async_io() {
+            Some(io) => io,
+            None => {
+                others.push(i);
+                continue;
+            }
+        };
+        if let (None, Some(timeout)) = (&sleep, timeout) {
+            sleep = Some(io.sleep(timeout));
+        }
+        for event in iterate_poll_events(events[i]) {
+            let future = match event {
+                PollEvent::PollIn => io.readable(),
+                PollEvent::PollOut => io.writable(),
+                _ => continue,
+            };
+            waits.push((i, PollEventBuilder::new().add(event).build(), future));
+        }
+    }
+
+    if !others.is_empty() {
+        let files: Vec<_> = others.iter().map(|&i| selfs[i]).collect();
+        let others_events: Vec<_> = others.iter().map(|&i| events[i]).collect();
+        let mut others_seen = vec![0; others.len()];
+        let ready = poll(
+            &files,
+            &others_events,
+            &mut others_seen,
+            Some(Duration::from_secs(0)),
+        )?;
+        if ready > 0 {
+            for (&i, seen) in others.iter().zip(others_seen) {
+                seen_events[i] = seen;
+            }
+            return Ok(ready);
+        }
+    }
+
+    if waits.is_empty() {
+        return Ok(0);
+    }
+    let ready = suspend_on(ReadyAny {
+        waits,
+        timeout: sleep,
+    });
+    let mut files_ready = 0;
+    for (i, event) in ready {
+        if seen_events[i] == 0 {
+            files_ready += 1;
+        }
+        seen_events[i] |= event;
+    }
+    Ok(files_ready)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Mutex, thread, time::Instant};
+    use wasmer_runtime_core::async_import::{block_on, call_async};
+
+    /// A future that is pending on its first poll, so that the guest is suspended on it.
+    struct YieldOnce<T>(Option<T>, bool);
+
+    impl<T: Unpin> Future for YieldOnce<T> {
+        type Output = T;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+            if self.1 {
+                Poll::Ready(self.0.take().unwrap())
+            } else {
+                self.1 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    fn yield_once<T: Send + Unpin + 'static>(value: io::Result<T>) -> IoFuture<T> {
+        Box::pin(YieldOnce(Some(value), false))
+    }
+
+    /// A future that never resolves.
+    struct Never;
+
+    impl Future for Never {
+        type Output = io::Result<()>;
+
+        fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Pending
+        }
+    }
+
+    /// A future resolving at `deadline`, woken by a thread sleeping until then.
+    struct Sleep {
+        deadline: Instant,
+        waking: bool,
+    }
+
+    impl Future for Sleep {
+        type Output = io::Result<()>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            let now = Instant::now();
+            if now >= self.deadline {
+                return Poll::Ready(Ok(()));
+            }
+            if !self.waking {
+                self.waking = true;
+                let (duration, waker) = (self.deadline - now, cx.waker().clone());
+                thread::spawn(move || {
+                    thread::sleep(duration);
+                    waker.wake();
+                });
+            }
+            Poll::Pending
+        }
+    }
+
+    /// A stream reading the bytes of `input`, and always writable.
+    struct MockIo {
+        input: Arc<Mutex<Vec<u8>>>,
+        /// Whether the stream is readable: `None` if waiting for it fails
+        readable: Option<bool>,
+    }
+
+    impl MockIo {
+        fn new(input: &[u8], readable: Option<bool>) -> Self {
+            Self {
+                input: Arc::new(Mutex::new(input.to_vec())),
+                readable,
+            }
+        }
+    }
+
+    impl AsyncIo for MockIo {
+        fn read(&self, max_len: usize) -> IoFuture<Vec<u8>> {
+            let mut input = self.input.lock().unwrap();
+            let len = max_len.min(input.len());
+            yield_once(Ok(input.drain(..len).collect()))
+        }
+
+        fn write(&self, buf: Vec<u8>) -> IoFuture<usize> {
+            yield_once(Ok(buf.len()))
+        }
+
+        fn readable(&self) -> IoFuture<()> {
+            match self.readable {
+                Some(true) => yield_once(Ok(())),
+                Some(false) => Box::pin(Never),
+                None => yield_once(Err(io::Error::new(io::ErrorKind::Other, "broken"))),
+            }
+        }
+
+        fn writable(&self) -> IoFuture<()> {
+            yield_once(Ok(()))
+        }
+
+        fn sleep(&self, duration: Duration) -> IoFuture<()> {
+            Box::pin(Sleep {
+                deadline: Instant::now() + duration,
+                waking: false,
+            })
+        }
+    }
+
+    fn event(event: PollEvent) -> PollEventSet {
+        PollEventBuilder::new().add(event).build()
+    }
+
+    #[test]
+    fn test_read_and_write() {
+        let mut file = AsyncFile::new(Arc::new(MockIo::new(b"hello", Some(true))));
+        let result = block_on(call_async(move || {
+            let mut buf = [0; 3];
+            let read = file.read(&mut buf).unwrap();
+            let written = file.write(b"bye").unwrap();
+            (read, buf, written, file.unlink())
+        }));
+        assert_eq!(
+            result.unwrap(),
+            (3, *b"hel", 3, Err(WasiFsError::PermissionDenied))
+        );
+
+        // Outside of `call_async`, the read blocks until the future resolves.
+        let mut file = AsyncFile::new(Arc::new(MockIo::new(b"hello", Some(true))));
+        let mut buf = [0; 8];
+        assert_eq!(file.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(file.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_poll_returns_ready_files() {
+        let result = block_on(call_async(|| {
+            let waiting = AsyncFile::new(Arc::new(MockIo::new(b"", Some(false))));
+            let broken = AsyncFile::new(Arc::new(MockIo::new(b"", None)));
+            let writable = AsyncFile::new(Arc::new(MockIo::new(b"", Some(false))));
+            let files: [&dyn WasiFile; 3] = [&waiting, &broken, &writable];
+            let events = [
+                event(PollEvent::PollIn),
+                event(PollEvent::PollIn),
+                event(PollEvent::PollOut),
+            ];
+            let mut seen_events = [0; 3];
+            let ready = poll_async(&files, &events, &mut seen_events, None).unwrap();
+            (ready, seen_events)
+        }));
+        assert_eq!(
+            result.unwrap(),
+            (
+                2,
+                [0, event(PollEvent::PollError), event(PollEvent::PollOut)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_poll_times_out() {
+        let start = Instant::now();
+        let result = block_on(call_async(|| {
+            let waiting = AsyncFile::new(Arc::new(MockIo::new(b"", Some(false))));
+            let files: [&dyn WasiFile; 1] = [&waiting];
+            let mut seen_events = [0; 1];
+            let ready = poll_async(
+                &files,
+                &[event(PollEvent::PollIn)],
+                &mut seen_events,
+                Some(Duration::from_millis(10)),
+            )
+            .unwrap();
+            (ready, seen_events)
+        }));
+        assert_eq!(result.unwrap(), (0, [0]));
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+}
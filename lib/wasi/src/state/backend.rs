@@ -0,0 +1,198 @@
+//! The filesystems backing `WasiFs`.
+//!
+//! `WasiFs` keeps the fds and inodes that WASI modules see, and only reaches the files they
+//! refer to through a `WasiFsBackend`.  The paths of the preopened and mapped directories, and
+//! the paths stored in the inodes under them, are paths of the backend.
+//!
+//! `HostFs`, which passes everything through to the host filesystem, is the default backend.
+//! Implement `WasiFsBackend` for your own types to serve files from somewhere else.
+
+use crate::state::{
+    host_file_type_to_wasi_file_type, BackendSnapshot, HostFile, WasiFile, WasiFsError,
+};
+use crate::syscalls::types::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+/// An entry of a directory of a [`WasiFsBackend`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    /// The name of the entry in its directory
+    pub name: String,
+    /// The type of the entry, one of the `__WASI_FILETYPE_*` constants
+    pub file_type: __wasi_filetype_t,
+}
+
+/// How a file is opened by [`WasiFsBackend::open`], mirroring `std::fs::OpenOptions`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OpenOptions {
+    pub read: bool,
+    pub write: bool,
+    pub append: bool,
+    pub truncate: bool,
+    /// Create the file if it doesn't exist
+    pub create: bool,
+    /// Create the file, failing if it exists
+    pub create_new: bool,
+}
+
+/// A filesystem that WASI modules can access through [`WasiFs`].
+///
+/// The only stats returned that WASI relies on are the file type, the size and the
+/// timestamps; the device and inode numbers are assigned by [`WasiFs`].
+///
+/// Backends are shared between the instances created from the same `WasiStateBuilder`,
+/// so mutations go through `&self`.
+#[typetag::serde(tag = "type")]
+pub trait WasiFsBackend: std::fmt::Debug + Send + Sync {
+    /// Returns the stat of the file at `path`, following symlinks.
+    fn metadata(&self, path: &Path) -> Result<__wasi_filestat_t, WasiFsError>;
+
+    /// Returns the stat of the file at `path`, without following a symlink at its end.
+    fn symlink_metadata(&self, path: &Path) -> Result<__wasi_filestat_t, WasiFsError>;
+
+    /// Returns the value of the symlink at `path`.
+    fn read_link(&self, path: &Path) -> Result<PathBuf, WasiFsError>;
+
+    /// Returns the entries of the directory at `path`, in any order.
+    fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>, WasiFsError>;
+
+    /// Creates an empty directory at `path`.
+    fn create_dir(&self, path: &Path) -> Result<(), WasiFsError>;
+
+    /// Removes the empty directory at `path`.
+    fn remove_dir(&self, path: &Path) -> Result<(), WasiFsError>;
+
+    /// Removes the file at `path`.
+    fn remove_file(&self, path: &Path) -> Result<(), WasiFsError>;
+
+    /// Moves the file or directory at `from` to `to`.
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), WasiFsError>;
+
+    /// Opens the file at `path` as described by `options`.
+    fn open(&self, path: &Path, options: &OpenOptions) -> Result<Box<dyn WasiFile>, WasiFsError>;
+
+    /// Returns a copy of the files of the backend, for `restore`.  Default is `None`, for the
+    /// backends which can't go back to an earlier state, like the host filesystem.
+    fn snapshot(&self) -> Option<BackendSnapshot> {
+        None
+    }
+
+    /// Puts back the files saved in `snapshot`, taken by `snapshot` on this backend.
+    fn restore(&self, _snapshot: &BackendSnapshot) -> Result<(), WasiFsError> {
+        Err(WasiFsError::UnknownError(__WASI_ENOTSUP))
+    }
+}
+
+/// The backend passing everything through to the filesystem of the host.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct HostFs;
+
+#[typetag::serde]
+impl WasiFsBackend for HostFs {
+    fn metadata(&self, path: &Path) -> Result<__wasi_filestat_t, WasiFsError> {
+        host_metadata_to_stat(&path.metadata()?)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<__wasi_filestat_t, WasiFsError> {
+        host_metadata_to_stat(&path.symlink_metadata()?)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, WasiFsError> {
+        path.read_link().map_err(Into::into)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>, WasiFsError> {
+        fs::read_dir(path)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok(DirEntry {
+                    name: entry.file_name().to_string_lossy().to_string(),
+                    file_type: host_file_type_to_wasi_file_type(entry.file_type()?),
+                })
+            })
+            .collect()
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        fs::create_dir(path).map_err(Into::into)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        fs::remove_dir(path).map_err(Into::into)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), WasiFsError> {
+        fs::remove_file(path).map_err(Into::into)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), WasiFsError> {
+        fs::rename(from, to).map_err(Into::into)
+    }
+
+    fn open(&self, path: &Path, options: &OpenOptions) -> Result<Box<dyn WasiFile>, WasiFsError> {
+        let file = fs::OpenOptions::new()
+            .read(options.read)
+            .write(options.write)
+            .append(options.append)
+            .truncate(options.truncate)
+            .create(options.create)
+            .create_new(options.create_new)
+            .open(path)?;
+        Ok(Box::new(HostFile::new(
+            file,
+            path.to_path_buf(),
+            options.read,
+            options.write,
+            options.append,
+        )))
+    }
+}
+
+fn host_metadata_to_stat(md: &fs::Metadata) -> Result<__wasi_filestat_t, WasiFsError> {
+    let nanos_since_epoch = |time: SystemTime| {
+        time.duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .map_err(|_| WasiFsError::IOError)
+    };
+    Ok(__wasi_filestat_t {
+        st_filetype: host_file_type_to_wasi_file_type(md.file_type()),
+        st_size: md.len(),
+        st_atim: nanos_since_epoch(md.accessed()?)?,
+        st_mtim: nanos_since_epoch(md.modified()?)?,
+        st_ctim: md
+            .created()
+            .ok()
+            .and_then(|ct| nanos_since_epoch(ct).ok())
+            .unwrap_or(0),
+        ..__wasi_filestat_t::default()
+    })
+}
+
+pub(crate) fn default_backend() -> Arc<dyn WasiFsBackend> {
+    Arc::new(HostFs)
+}
+
+pub(crate) fn serialize_backend<S>(
+    backend: &Arc<dyn WasiFsBackend>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    (**backend).serialize(serializer)
+}
+
+pub(crate) fn deserialize_backend<'de, D>(
+    deserializer: D,
+) -> Result<Arc<dyn WasiFsBackend>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Box::<dyn WasiFsBackend>::deserialize(deserializer).map(Arc::from)
+}
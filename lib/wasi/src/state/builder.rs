@@ -1,7 +1,16 @@
 //! Builder code for [`WasiState`]
 
-use crate::state::{WasiFs, WasiState};
+use crate::state::{
+    FsAccessHook, HostClock, HostFs, HostRandom, NetworkGrants, PathPolicy, PolicyFs, ReadStream,
+    WasiClock, WasiFs, WasiFsBackend, WasiQuotas, WasiRandom, WasiState, WriteStream,
+};
+use crate::syscalls::types::*;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::io::{Read, Write};
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// Creates an empty [`WasiStateBuilder`].
 pub(crate) fn create_wasi_state(program_name: &str) -> WasiStateBuilder {
@@ -12,12 +21,165 @@ pub(crate) fn create_wasi_state(program_name: &str) -> WasiStateBuilder {
 }
 
 /// Type for building an instance of [`WasiState`]
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone)]
 pub struct WasiStateBuilder {
     args: Vec<Vec<u8>>,
     envs: Vec<Vec<u8>>,
     preopened_files: Vec<PathBuf>,
     mapped_dirs: Vec<(String, PathBuf)>,
+    /// The capabilities of `preopened_files`, in the same order
+    preopen_capabilities: Vec<PreopenCapabilities>,
+    /// The capabilities of `mapped_dirs`, in the same order
+    mapped_capabilities: Vec<PreopenCapabilities>,
+    deterministic: bool,
+    fs_backend: Option<Arc<dyn WasiFsBackend>>,
+    path_policy: Option<Arc<dyn PathPolicy>>,
+    fs_access_hook: Option<Arc<dyn FsAccessHook>>,
+    stdin: Option<ReadStream>,
+    stdout: Option<WriteStream>,
+    stderr: Option<WriteStream>,
+    network: NetworkGrants,
+    quotas: WasiQuotas,
+    clock: Option<Arc<dyn WasiClock>>,
+    random: Option<Arc<dyn WasiRandom>>,
+}
+
+impl PartialEq for WasiStateBuilder {
+    fn eq(&self, other: &Self) -> bool {
+        let same_backend = match (&self.fs_backend, &other.fs_backend) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        let same_policy = match (&self.path_policy, &other.path_policy) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        let same_access_hook = match (&self.fs_access_hook, &other.fs_access_hook) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        let same_clock = match (&self.clock, &other.clock) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        let same_random = match (&self.random, &other.random) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        self.args == other.args
+            && self.envs == other.envs
+            && self.preopened_files == other.preopened_files
+            && self.mapped_dirs == other.mapped_dirs
+            && self.preopen_capabilities == other.preopen_capabilities
+            && self.mapped_capabilities == other.mapped_capabilities
+            && self.deterministic == other.deterministic
+            && same_backend
+            && same_policy
+            && self.stdin == other.stdin
+            && self.stdout == other.stdout
+            && self.stderr == other.stderr
+            && self.network == other.network
+            && self.quotas == other.quotas
+            && same_access_hook
+            && same_clock
+            && same_random
+    }
+}
+
+/// What a WASI module may do in a preopened directory and the files under it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreopenCapabilities {
+    /// Read files and list directories
+    pub read: bool,
+    /// Write to files, resize them and change their timestamps
+    pub write: bool,
+    /// Create files, directories and links, and rename entries into directories
+    pub create: bool,
+    /// Remove files and directories, and rename entries out of directories
+    pub delete: bool,
+}
+
+impl Default for PreopenCapabilities {
+    fn default() -> Self {
+        Self {
+            read: true,
+            write: true,
+            create: true,
+            delete: true,
+        }
+    }
+}
+
+impl PreopenCapabilities {
+    /// Only reading files and listing directories.
+    pub fn read_only() -> Self {
+        Self {
+            read: true,
+            write: false,
+            create: false,
+            delete: false,
+        }
+    }
+
+    /// Everything but creating new entries.
+    pub fn no_create() -> Self {
+        Self {
+            create: false,
+            ..Self::default()
+        }
+    }
+
+    /// Everything but removing entries.
+    pub fn no_delete() -> Self {
+        Self {
+            delete: false,
+            ..Self::default()
+        }
+    }
+
+    /// The rights of the fds of a directory with these capabilities.
+    fn rights(self) -> __wasi_rights_t {
+        let mut rights = __WASI_RIGHT_FD_SEEK
+            | __WASI_RIGHT_FD_TELL
+            | __WASI_RIGHT_FD_FDSTAT_SET_FLAGS
+            | __WASI_RIGHT_FD_ADVISE
+            | __WASI_RIGHT_PATH_OPEN
+            | __WASI_RIGHT_FD_FILESTAT_GET
+            | __WASI_RIGHT_PATH_FILESTAT_GET
+            | __WASI_RIGHT_POLL_FD_READWRITE;
+        if self.read {
+            rights |= __WASI_RIGHT_FD_READ | __WASI_RIGHT_FD_READDIR | __WASI_RIGHT_PATH_READLINK;
+        }
+        if self.write {
+            rights |= __WASI_RIGHT_FD_WRITE
+                | __WASI_RIGHT_FD_DATASYNC
+                | __WASI_RIGHT_FD_SYNC
+                | __WASI_RIGHT_FD_ALLOCATE
+                | __WASI_RIGHT_FD_FILESTAT_SET_SIZE
+                | __WASI_RIGHT_FD_FILESTAT_SET_TIMES
+                | __WASI_RIGHT_PATH_FILESTAT_SET_SIZE
+                | __WASI_RIGHT_PATH_FILESTAT_SET_TIMES;
+        }
+        if self.create {
+            rights |= __WASI_RIGHT_PATH_CREATE_DIRECTORY
+                | __WASI_RIGHT_PATH_CREATE_FILE
+                | __WASI_RIGHT_PATH_LINK_SOURCE
+                | __WASI_RIGHT_PATH_LINK_TARGET
+                | __WASI_RIGHT_PATH_RENAME_TARGET
+                | __WASI_RIGHT_PATH_SYMLINK;
+        }
+        if self.delete {
+            rights |= __WASI_RIGHT_PATH_RENAME_SOURCE
+                | __WASI_RIGHT_PATH_REMOVE_DIRECTORY
+                | __WASI_RIGHT_PATH_UNLINK_FILE;
+        }
+        rights
+    }
 }
 
 /// Error type returned when bad data is given to [`WasiStateBuilder`].
@@ -26,8 +188,58 @@ pub enum WasiStateCreationError {
     EnvironmentVariableFormatError(String),
     ArgumentContainsNulByte(String),
     PreopenedDirectoryNotFound(PathBuf),
+    /// A preopened or mapped path isn't a directory
+    PreopenedDirectoryNotADirectory(PathBuf),
+    /// Two preopened or mapped directories have the same name in the virtual root
+    PreopenedDirectoryNameConflict(String),
     MappedDirAliasFormattingError(String),
     WasiFsCreationError(String),
+    /// The module can't run threads, e.g. it doesn't import a shared memory
+    ThreadsUnsupported(String),
+}
+
+impl std::fmt::Display for WasiStateCreationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WasiStateCreationError::EnvironmentVariableFormatError(msg) => {
+                write!(f, "invalid environment variable: {}", msg)
+            }
+            WasiStateCreationError::ArgumentContainsNulByte(arg) => {
+                write!(f, "argument \"{}\" contains a nul byte", arg)
+            }
+            WasiStateCreationError::PreopenedDirectoryNotFound(path) => {
+                write!(f, "preopened directory {:?} not found", path)
+            }
+            WasiStateCreationError::PreopenedDirectoryNotADirectory(path) => {
+                write!(f, "preopened path {:?} is not a directory", path)
+            }
+            WasiStateCreationError::PreopenedDirectoryNameConflict(name) => {
+                write!(f, "several directories are preopened as \"{}\"", name)
+            }
+            WasiStateCreationError::MappedDirAliasFormattingError(msg) => {
+                write!(f, "invalid mapped directory alias: {}", msg)
+            }
+            WasiStateCreationError::WasiFsCreationError(msg) => {
+                write!(f, "could not create the WASI filesystem: {}", msg)
+            }
+            WasiStateCreationError::ThreadsUnsupported(msg) => {
+                write!(f, "the module can't run threads: {}", msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WasiStateCreationError {}
+
+#[cfg(unix)]
+fn os_str_to_bytes(s: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    s.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn os_str_to_bytes(s: &OsStr) -> Vec<u8> {
+    s.to_string_lossy().into_owned().into_bytes()
 }
 
 fn validate_mapped_dir_alias(alias: &str) -> Result<(), WasiStateCreationError> {
@@ -50,8 +262,6 @@ fn validate_mapped_dir_alias(alias: &str) -> Result<(), WasiStateCreationError>
     Ok(())
 }
 
-// TODO add other WasiFS APIs here like swapping out stdout, for example (though we need to
-// return stdout somehow, it's unclear what that API should look like)
 impl WasiStateBuilder {
     /// Add an environment variable pair.
     /// Environment variable keys and values must not contain the byte `=` (0x3d)
@@ -132,16 +342,66 @@ impl WasiStateBuilder {
         self
     }
 
+    /// Add multiple arguments given as `OsStr`s, such as the ones of `std::env::args_os`.
+    /// Arguments must not contain the nul (0x0) byte
+    ///
+    /// Outside of Unix, arguments that aren't valid Unicode are converted lossily.
+    pub fn args_os<I, Arg>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = Arg>,
+        Arg: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.args.push(os_str_to_bytes(arg.as_ref()));
+        }
+
+        self
+    }
+
+    /// Add multiple environment variable pairs given as `OsStr`s, such as the ones of
+    /// `std::env::vars_os`.
+    /// Keys and values must not contain the `=` (0x3d) or nul (0x0) byte.
+    ///
+    /// Outside of Unix, keys and values that aren't valid Unicode are converted lossily.
+    pub fn envs_os<I, Key, Value>(&mut self, env_pairs: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (Key, Value)>,
+        Key: AsRef<OsStr>,
+        Value: AsRef<OsStr>,
+    {
+        for (key, value) in env_pairs {
+            self.env(
+                os_str_to_bytes(key.as_ref()),
+                os_str_to_bytes(value.as_ref()),
+            );
+        }
+
+        self
+    }
+
     /// Preopen a directory
     /// This opens the given directory at the virtual root, `/`, and allows
     /// the WASI module to read and write to the given directory.
-    // TODO: design a simple API for passing in permissions here (i.e. read-only)
     pub fn preopen_dir<FilePath>(&mut self, po_dir: FilePath) -> &mut Self
+    where
+        FilePath: AsRef<Path>,
+    {
+        self.preopen_dir_with_capabilities(po_dir, PreopenCapabilities::default())
+    }
+
+    /// Preopen a directory, only allowing the WASI module what `capabilities` grant in it,
+    /// for example `PreopenCapabilities::read_only()`.
+    pub fn preopen_dir_with_capabilities<FilePath>(
+        &mut self,
+        po_dir: FilePath,
+        capabilities: PreopenCapabilities,
+    ) -> &mut Self
     where
         FilePath: AsRef<Path>,
     {
         let path = po_dir.as_ref();
         self.preopened_files.push(path.to_path_buf());
+        self.preopen_capabilities.push(capabilities);
 
         self
     }
@@ -155,8 +415,7 @@ impl WasiStateBuilder {
         FilePath: AsRef<Path>,
     {
         for po_dir in po_dirs {
-            let path = po_dir.as_ref();
-            self.preopened_files.push(path.to_path_buf());
+            self.preopen_dir(po_dir);
         }
 
         self
@@ -164,12 +423,174 @@ impl WasiStateBuilder {
 
     /// Preopen a directory with a different name exposed to the WASI.
     pub fn map_dir<FilePath>(&mut self, alias: &str, po_dir: FilePath) -> &mut Self
+    where
+        FilePath: AsRef<Path>,
+    {
+        self.map_dir_with_capabilities(alias, po_dir, PreopenCapabilities::default())
+    }
+
+    /// Preopen a directory with a different name exposed to the WASI, only allowing the WASI
+    /// module what `capabilities` grant in it.
+    pub fn map_dir_with_capabilities<FilePath>(
+        &mut self,
+        alias: &str,
+        po_dir: FilePath,
+        capabilities: PreopenCapabilities,
+    ) -> &mut Self
     where
         FilePath: AsRef<Path>,
     {
         let path = po_dir.as_ref();
         self.mapped_dirs
             .push((alias.to_string(), path.to_path_buf()));
+        self.mapped_capabilities.push(capabilities);
+
+        self
+    }
+
+    /// Makes the syscalls whose results differ from run to run, the clocks and
+    /// `random_get`, fail with `__WASI_ENOTCAPABLE`.  They stay available if they're set to
+    /// a deterministic [`WasiClock`] or [`WasiRandom`] with [`WasiStateBuilder::clock`] or
+    /// [`WasiStateBuilder::random`].
+    ///
+    /// This is meant to be used with code compiled in deterministic mode.
+    pub fn deterministic(&mut self, deterministic: bool) -> &mut Self {
+        self.deterministic = deterministic;
+
+        self
+    }
+
+    /// Serves the files from `backend` instead of the host filesystem.
+    ///
+    /// The preopened and mapped directories are looked up in `backend`, which is shared by
+    /// all the states built by this builder and its clones.
+    pub fn fs_backend(&mut self, backend: Arc<dyn WasiFsBackend>) -> &mut Self {
+        self.fs_backend = Some(backend);
+
+        self
+    }
+
+    /// Only lets the module reach the paths of the backend that `policy` allows, under the
+    /// paths it rewrites them to.
+    ///
+    /// The preopened and mapped directories are checked against the policy too.
+    pub fn path_policy(&mut self, policy: Arc<dyn PathPolicy>) -> &mut Self {
+        self.path_policy = Some(policy);
+
+        self
+    }
+
+    /// Asks `hook` before every syscall touching a path of the backend, with what the syscall
+    /// is about to do and the rights of the fd it goes through.
+    ///
+    /// The accesses `hook` denies fail with `__WASI_EACCES`.  Unlike the path policy, it's only
+    /// asked for the accesses of the module: the preopened directories aren't checked.
+    pub fn fs_access_hook(&mut self, hook: Arc<dyn FsAccessHook>) -> &mut Self {
+        self.fs_access_hook = Some(hook);
+
+        self
+    }
+
+    /// Makes the module read its stdin from `reader` instead of the stdin of the host.
+    ///
+    /// The reader is shared by all the states built by this builder and its clones.
+    pub fn stdin<R>(&mut self, reader: R) -> &mut Self
+    where
+        R: Read + Send + 'static,
+    {
+        self.stdin = Some(ReadStream::new(reader));
+
+        self
+    }
+
+    /// Makes the module write its stdout to `writer` instead of the stdout of the host.
+    ///
+    /// Pass a clone of an `OutputBuffer` to capture the output into memory, or a `PipeWriter`
+    /// to feed the stdin of another instance.  The writer is shared by all the states built by
+    /// this builder and its clones.
+    pub fn stdout<W>(&mut self, writer: W) -> &mut Self
+    where
+        W: Write + Send + 'static,
+    {
+        self.stdout = Some(WriteStream::new(writer));
+
+        self
+    }
+
+    /// Makes the module write its stderr to `writer` instead of the stderr of the host.
+    ///
+    /// Like [`WasiStateBuilder::stdout`], the writer is shared by all the states built by
+    /// this builder and its clones.
+    pub fn stderr<W>(&mut self, writer: W) -> &mut Self
+    where
+        W: Write + Send + 'static,
+    {
+        self.stderr = Some(WriteStream::new(writer));
+
+        self
+    }
+
+    /// Makes the module read the time from `clock` instead of the clocks of the host.
+    ///
+    /// The clock is shared by all the states built by this builder and its clones.  If it
+    /// is deterministic, it stays readable in deterministic mode.
+    pub fn clock(&mut self, clock: Arc<dyn WasiClock>) -> &mut Self {
+        self.clock = Some(clock);
+
+        self
+    }
+
+    /// Makes `random_get` read from `random` instead of the entropy of the OS.
+    ///
+    /// The source is shared by all the states built by this builder and its clones.  If it
+    /// is deterministic, like `SeededRandom`, it stays readable in deterministic mode.
+    pub fn random(&mut self, random: Arc<dyn WasiRandom>) -> &mut Self {
+        self.random = Some(random);
+
+        self
+    }
+
+    /// Lets the module connect to `addr` with `sock_connect`.
+    ///
+    /// Modules have no network access unless it's granted, one address at a time.
+    pub fn allow_connect(&mut self, addr: SocketAddr) -> &mut Self {
+        self.network.connect.push(addr);
+
+        self
+    }
+
+    /// Lets the module listen on `addr` with `sock_listen`, and accept connections on it.
+    pub fn allow_listen(&mut self, addr: SocketAddr) -> &mut Self {
+        self.network.listen.push(addr);
+
+        self
+    }
+
+    /// Limits the number of fds the module may have open at the same time, counting stdio and
+    /// the preopened directories.
+    ///
+    /// Opening more fails with `__WASI_EMFILE`.
+    pub fn max_open_fds(&mut self, max: usize) -> &mut Self {
+        self.quotas.max_open_fds = Some(max);
+
+        self
+    }
+
+    /// Limits the number of bytes the module may write in total, to files, sockets, stdout and
+    /// stderr.
+    ///
+    /// Writes going over it fail with `__WASI_EDQUOT`.
+    pub fn max_bytes_written(&mut self, max: u64) -> &mut Self {
+        self.quotas.max_bytes_written = Some(max);
+
+        self
+    }
+
+    /// Limits the number of files, directories and links the module may create in total.
+    ///
+    /// Creating more fails with `__WASI_EDQUOT`.
+    pub fn max_entries_created(&mut self, max: u64) -> &mut Self {
+        self.quotas.max_entries_created = Some(max);
 
         self
     }
@@ -223,27 +644,80 @@ impl WasiStateBuilder {
             }
         }
 
-        for po_f in self.preopened_files.iter() {
-            if !po_f.exists() {
-                return Err(WasiStateCreationError::PreopenedDirectoryNotFound(
-                    po_f.clone(),
-                ));
+        for (alias, _) in self.mapped_dirs.iter() {
+            validate_mapped_dir_alias(&alias)?;
+        }
+
+        let mut fs_backend = self.fs_backend.clone().unwrap_or_else(|| Arc::new(HostFs));
+        if let Some(policy) = &self.path_policy {
+            fs_backend = Arc::new(PolicyFs::new(fs_backend, policy.clone()));
+        }
+        // the names the directories get in the virtual root, which must be unique
+        let mut names = HashSet::new();
+        let preopened = self
+            .preopened_files
+            .iter()
+            .map(|po_f| (po_f.to_string_lossy().into_owned(), po_f));
+        let mapped = self
+            .mapped_dirs
+            .iter()
+            .map(|(alias, po_f)| (alias.clone(), po_f));
+        for (name, po_f) in preopened.chain(mapped) {
+            match fs_backend.metadata(po_f) {
+                Err(_) => {
+                    return Err(WasiStateCreationError::PreopenedDirectoryNotFound(
+                        po_f.clone(),
+                    ))
+                }
+                Ok(stat) if stat.st_filetype != __WASI_FILETYPE_DIRECTORY => {
+                    return Err(WasiStateCreationError::PreopenedDirectoryNotADirectory(
+                        po_f.clone(),
+                    ))
+                }
+                Ok(_) => (),
+            }
+            if !names.insert(name.clone()) {
+                return Err(WasiStateCreationError::PreopenedDirectoryNameConflict(name));
             }
         }
 
-        for (alias, po_f) in self.mapped_dirs.iter() {
-            if !po_f.exists() {
-                return Err(WasiStateCreationError::PreopenedDirectoryNotFound(
-                    po_f.clone(),
-                ));
+        let mut fs = WasiFs::new_with_backend(fs_backend, &self.preopened_files, &self.mapped_dirs)
+            .map_err(WasiStateCreationError::WasiFsCreationError)?;
+        // the fds of the preopened directories follow the one of the virtual root, in order
+        let capabilities = self
+            .preopen_capabilities
+            .iter()
+            .chain(self.mapped_capabilities.iter());
+        for (po_fd, capabilities) in fs.preopen_fds[1..].iter().zip(capabilities) {
+            if let Some(fd) = fs.fd_map.get_mut(po_fd) {
+                fd.rights &= capabilities.rights();
+                fd.rights_inheriting &= capabilities.rights();
+            }
+        }
+        if let Some(stdin) = &self.stdin {
+            fs.swap_file(__WASI_STDIN_FILENO, Box::new(stdin.clone()))
+                .map_err(|e| WasiStateCreationError::WasiFsCreationError(format!("{:?}", e)))?;
+        }
+        for (fd, stream) in &[
+            (__WASI_STDOUT_FILENO, &self.stdout),
+            (__WASI_STDERR_FILENO, &self.stderr),
+        ] {
+            if let Some(stream) = stream {
+                fs.swap_file(*fd, Box::new(stream.clone()))
+                    .map_err(|e| WasiStateCreationError::WasiFsCreationError(format!("{:?}", e)))?;
             }
-            validate_mapped_dir_alias(&alias)?;
         }
         Ok(WasiState {
-            fs: WasiFs::new(&self.preopened_files, &self.mapped_dirs)
-                .map_err(WasiStateCreationError::WasiFsCreationError)?,
+            fs,
             args: self.args.clone(),
             envs: self.envs.clone(),
+            deterministic: self.deterministic,
+            network: self.network.clone(),
+            quotas: self.quotas.clone(),
+            clock: self.clock.clone().unwrap_or_else(|| Arc::new(HostClock)),
+            random: self.random.clone().unwrap_or_else(|| Arc::new(HostRandom)),
+            fs_access_hook: self.fs_access_hook.clone(),
+            threads: None,
         })
     }
 }
@@ -251,6 +725,172 @@ impl WasiStateBuilder {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::state::{
+        DirEntry, FsAccess, FsOperation, OpenOptions, OutputBuffer, WasiFile, WasiFsError,
+    };
+    use crate::syscalls::types::*;
+    use serde::{Deserialize, Serialize};
+
+    /// A backend holding nothing but an empty `/data` directory.
+    #[derive(Debug, Serialize, Deserialize)]
+    struct DataDirFs;
+
+    #[typetag::serde]
+    impl WasiFsBackend for DataDirFs {
+        fn metadata(&self, path: &Path) -> Result<__wasi_filestat_t, WasiFsError> {
+            if path == Path::new("/data") {
+                Ok(__wasi_filestat_t {
+                    st_filetype: __WASI_FILETYPE_DIRECTORY,
+                    ..__wasi_filestat_t::default()
+                })
+            } else {
+                Err(WasiFsError::EntityNotFound)
+            }
+        }
+        fn symlink_metadata(&self, path: &Path) -> Result<__wasi_filestat_t, WasiFsError> {
+            self.metadata(path)
+        }
+        fn read_link(&self, _path: &Path) -> Result<PathBuf, WasiFsError> {
+            Err(WasiFsError::InvalidInput)
+        }
+        fn read_dir(&self, _path: &Path) -> Result<Vec<DirEntry>, WasiFsError> {
+            Ok(vec![])
+        }
+        fn create_dir(&self, _path: &Path) -> Result<(), WasiFsError> {
+            Err(WasiFsError::PermissionDenied)
+        }
+        fn remove_dir(&self, _path: &Path) -> Result<(), WasiFsError> {
+            Err(WasiFsError::PermissionDenied)
+        }
+        fn remove_file(&self, _path: &Path) -> Result<(), WasiFsError> {
+            Err(WasiFsError::PermissionDenied)
+        }
+        fn rename(&self, _from: &Path, _to: &Path) -> Result<(), WasiFsError> {
+            Err(WasiFsError::PermissionDenied)
+        }
+        fn open(
+            &self,
+            _path: &Path,
+            _options: &OpenOptions,
+        ) -> Result<Box<dyn WasiFile>, WasiFsError> {
+            Err(WasiFsError::EntityNotFound)
+        }
+    }
+
+    #[test]
+    fn preopened_dirs_are_looked_up_in_the_backend() {
+        let state = create_wasi_state("test_prog")
+            .fs_backend(Arc::new(DataDirFs))
+            .preopen_dir("/data")
+            .build()
+            .unwrap();
+        assert_eq!(state.fs.preopen_fds.len(), 2);
+
+        let output = create_wasi_state("test_prog")
+            .fs_backend(Arc::new(DataDirFs))
+            .map_dir("src", "src")
+            .build();
+        match output {
+            Err(WasiStateCreationError::PreopenedDirectoryNotFound(_)) => assert!(true),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn stdio_can_be_redirected() {
+        let stdout = OutputBuffer::new();
+        let stderr = OutputBuffer::new();
+        let mut state = create_wasi_state("test_prog")
+            .stdin(&b"hello"[..])
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .build()
+            .unwrap();
+
+        let mut input = String::new();
+        let stdin = state.fs.stdin_mut().unwrap().as_mut().unwrap();
+        stdin.read_to_string(&mut input).unwrap();
+        assert_eq!(input, "hello");
+
+        let stdout_file = state.fs.stdout_mut().unwrap().as_mut().unwrap();
+        stdout_file.write_all(b"out").unwrap();
+        let stderr_file = state.fs.stderr_mut().unwrap().as_mut().unwrap();
+        stderr_file.write_all(b"err").unwrap();
+        assert_eq!(stdout.take(), b"out");
+        assert_eq!(stdout.contents(), b"");
+        assert_eq!(stderr.contents(), b"err");
+    }
+
+    #[test]
+    fn network_access_is_granted_per_address() {
+        let granted: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let state = create_wasi_state("test_prog")
+            .allow_connect(granted)
+            .build()
+            .unwrap();
+        assert!(state.network.can_connect(&granted));
+        assert!(!state
+            .network
+            .can_connect(&"127.0.0.1:8081".parse().unwrap()));
+        assert!(!state.network.can_listen(&granted));
+    }
+
+    #[test]
+    fn preopened_dirs_are_validated() {
+        let output = create_wasi_state("test_prog")
+            .fs_backend(Arc::new(DataDirFs))
+            .map_dir("data", "/data")
+            .map_dir("data", "/data")
+            .build();
+        match output {
+            Err(WasiStateCreationError::PreopenedDirectoryNameConflict(_)) => assert!(true),
+            _ => assert!(false),
+        }
+
+        let state = create_wasi_state("test_prog")
+            .fs_backend(Arc::new(DataDirFs))
+            .map_dir_with_capabilities("data", "/data", PreopenCapabilities::read_only())
+            .build()
+            .unwrap();
+        let fd = state.fs.get_fd(state.fs.preopen_fds[1]).unwrap();
+        assert_eq!(fd.rights & __WASI_RIGHT_FD_READ, __WASI_RIGHT_FD_READ);
+        assert_eq!(fd.rights & __WASI_RIGHT_PATH_CREATE_FILE, 0);
+        assert_eq!(fd.rights_inheriting & __WASI_RIGHT_FD_WRITE, 0);
+
+        let state = create_wasi_state("test_prog")
+            .fs_backend(Arc::new(DataDirFs))
+            .map_dir_with_capabilities("data", "/data", PreopenCapabilities::no_delete())
+            .build()
+            .unwrap();
+        let fd = state.fs.get_fd(state.fs.preopen_fds[1]).unwrap();
+        assert_eq!(
+            fd.rights & __WASI_RIGHT_PATH_CREATE_FILE,
+            __WASI_RIGHT_PATH_CREATE_FILE
+        );
+        assert_eq!(fd.rights & __WASI_RIGHT_PATH_UNLINK_FILE, 0);
+        assert_eq!(fd.rights & __WASI_RIGHT_PATH_REMOVE_DIRECTORY, 0);
+        assert_eq!(fd.rights & __WASI_RIGHT_PATH_RENAME_SOURCE, 0);
+    }
+
+    #[test]
+    fn fs_access_hook_is_given_to_the_state() {
+        let state = create_wasi_state("test_prog")
+            .fs_access_hook(Arc::new(|access: &FsAccess| {
+                access.operation == FsOperation::Stat || !access.path.starts_with("/secret")
+            }))
+            .build()
+            .unwrap();
+        let hook = state.fs_access_hook.unwrap();
+
+        let access = |path: &'static str, operation| FsAccess {
+            path: Path::new(path),
+            operation,
+            rights: __WASI_RIGHT_PATH_OPEN,
+        };
+        assert!(hook.check(&access("/secret/key", FsOperation::Stat)));
+        assert!(!hook.check(&access("/secret/key", FsOperation::Open)));
+        assert!(hook.check(&access("/public/file", FsOperation::Open)));
+    }
 
     #[test]
     fn env_var_errors() {
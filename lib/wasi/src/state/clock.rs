@@ -0,0 +1,231 @@
+//! The clocks read by `clock_time_get`, `clock_res_get` and the clock subscriptions of
+//! `poll_oneoff`.
+//!
+//! `HostClock`, which reads the clocks of the host, is the default.  The other clocks put the
+//! time under the control of the embedder, for reproducible runs; implement `WasiClock` for
+//! your own types to provide time from somewhere else.
+
+use crate::syscalls::types::*;
+use crate::syscalls::{platform_clock_res_get, platform_clock_time_get};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    cell::Cell,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+/// A source of time for WASI modules.
+///
+/// Clocks are shared between the instances created from the same `WasiStateBuilder`, so
+/// they advance through `&self`.
+#[typetag::serde(tag = "type")]
+pub trait WasiClock: std::fmt::Debug + Send + Sync {
+    /// Returns the time of the clock `clock_id` in nanoseconds.
+    fn time_get(
+        &self,
+        clock_id: __wasi_clockid_t,
+        precision: __wasi_timestamp_t,
+    ) -> Result<__wasi_timestamp_t, __wasi_errno_t>;
+
+    /// Returns the resolution of the clock `clock_id` in nanoseconds.
+    fn res_get(&self, clock_id: __wasi_clockid_t) -> Result<__wasi_timestamp_t, __wasi_errno_t>;
+
+    /// Whether the times returned only depend on the calls made, in which case the clocks
+    /// stay available in deterministic mode.  Default returns `false`
+    fn is_deterministic(&self) -> bool {
+        false
+    }
+}
+
+fn check_clock_id(clock_id: __wasi_clockid_t) -> Result<(), __wasi_errno_t> {
+    match clock_id {
+        __WASI_CLOCK_REALTIME
+        | __WASI_CLOCK_MONOTONIC
+        | __WASI_CLOCK_PROCESS_CPUTIME_ID
+        | __WASI_CLOCK_THREAD_CPUTIME_ID => Ok(()),
+        _ => Err(__WASI_EINVAL),
+    }
+}
+
+/// The clocks of the host.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct HostClock;
+
+#[typetag::serde]
+impl WasiClock for HostClock {
+    fn time_get(
+        &self,
+        clock_id: __wasi_clockid_t,
+        precision: __wasi_timestamp_t,
+    ) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+        let time = Cell::new(0);
+        match platform_clock_time_get(clock_id, precision, &time) {
+            __WASI_ESUCCESS => Ok(time.get()),
+            err => Err(err),
+        }
+    }
+
+    fn res_get(&self, clock_id: __wasi_clockid_t) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+        let resolution = Cell::new(0);
+        match platform_clock_res_get(clock_id, &resolution) {
+            __WASI_ESUCCESS => Ok(resolution.get()),
+            err => Err(err),
+        }
+    }
+}
+
+/// A clock stopped at a given time, for all the clock ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixedClock {
+    time: __wasi_timestamp_t,
+}
+
+impl FixedClock {
+    /// Creates a clock always reading `time`, in nanoseconds.
+    pub fn new(time: __wasi_timestamp_t) -> Self {
+        Self { time }
+    }
+}
+
+#[typetag::serde]
+impl WasiClock for FixedClock {
+    fn time_get(
+        &self,
+        clock_id: __wasi_clockid_t,
+        _precision: __wasi_timestamp_t,
+    ) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+        check_clock_id(clock_id)?;
+        Ok(self.time)
+    }
+
+    fn res_get(&self, clock_id: __wasi_clockid_t) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+        check_clock_id(clock_id)?;
+        Ok(1)
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+/// A clock running at a multiple of the speed of the host's monotonic clock, for all the
+/// clock ids.
+///
+/// The time elapsed since the creation of the clock isn't serialized: a clock unfrozen from
+/// bytes starts over from its origin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScaledClock {
+    origin: __wasi_timestamp_t,
+    factor: f64,
+    #[serde(skip, default = "Instant::now")]
+    started: Instant,
+}
+
+impl ScaledClock {
+    /// Creates a clock reading `origin` nanoseconds now, then advancing `factor` nanoseconds
+    /// for every nanosecond of the host.
+    pub fn new(origin: __wasi_timestamp_t, factor: f64) -> Self {
+        Self {
+            origin,
+            factor,
+            started: Instant::now(),
+        }
+    }
+}
+
+#[typetag::serde]
+impl WasiClock for ScaledClock {
+    fn time_get(
+        &self,
+        clock_id: __wasi_clockid_t,
+        _precision: __wasi_timestamp_t,
+    ) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+        check_clock_id(clock_id)?;
+        let elapsed = self.started.elapsed().as_nanos() as f64 * self.factor;
+        Ok(self.origin.saturating_add(elapsed as __wasi_timestamp_t))
+    }
+
+    fn res_get(&self, clock_id: __wasi_clockid_t) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+        check_clock_id(clock_id)?;
+        Ok(1)
+    }
+}
+
+/// A clock advancing by a fixed step every time it's read, for all the clock ids.
+///
+/// The times it returns only depend on how many times it was read, so runs reading it are
+/// reproducible.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogicalClock {
+    next: Mutex<__wasi_timestamp_t>,
+    step: __wasi_timestamp_t,
+}
+
+impl LogicalClock {
+    /// Creates a clock reading `start` nanoseconds first, then `step` more on every read.
+    pub fn new(start: __wasi_timestamp_t, step: __wasi_timestamp_t) -> Self {
+        Self {
+            next: Mutex::new(start),
+            step,
+        }
+    }
+}
+
+#[typetag::serde]
+impl WasiClock for LogicalClock {
+    fn time_get(
+        &self,
+        clock_id: __wasi_clockid_t,
+        _precision: __wasi_timestamp_t,
+    ) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+        check_clock_id(clock_id)?;
+        let mut next = self.next.lock().unwrap();
+        let time = *next;
+        *next = next.saturating_add(self.step);
+        Ok(time)
+    }
+
+    fn res_get(&self, clock_id: __wasi_clockid_t) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+        check_clock_id(clock_id)?;
+        Ok(self.step.max(1))
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+pub(crate) fn default_clock() -> Arc<dyn WasiClock> {
+    Arc::new(HostClock)
+}
+
+pub(crate) fn serialize_clock<S>(
+    clock: &Arc<dyn WasiClock>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    (**clock).serialize(serializer)
+}
+
+pub(crate) fn deserialize_clock<'de, D>(deserializer: D) -> Result<Arc<dyn WasiClock>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Box::<dyn WasiClock>::deserialize(deserializer).map(Arc::from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn logical_clock_advances_on_every_read() {
+        let clock = LogicalClock::new(100, 10);
+        assert_eq!(clock.time_get(__WASI_CLOCK_MONOTONIC, 1), Ok(100));
+        assert_eq!(clock.time_get(__WASI_CLOCK_REALTIME, 1), Ok(110));
+        assert_eq!(clock.res_get(__WASI_CLOCK_MONOTONIC), Ok(10));
+        assert_eq!(clock.time_get(42, 1), Err(__WASI_EINVAL));
+    }
+}
@@ -0,0 +1,561 @@
+//! A `WasiFsBackend` holding its files in memory.
+//!
+//! Nothing written by the WASI module reaches the host filesystem, which makes `MemFs` suited
+//! to running untrusted modules, and to tests which would otherwise need temporary directories.
+
+use crate::state::{BackendSnapshot, DirEntry, OpenOptions, WasiFile, WasiFsBackend, WasiFsError};
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Component, Path},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FileData {
+    contents: Vec<u8>,
+    accessed: __wasi_timestamp_t,
+    modified: __wasi_timestamp_t,
+    created: __wasi_timestamp_t,
+}
+
+impl FileData {
+    fn new(contents: Vec<u8>) -> Self {
+        let now = now();
+        FileData {
+            contents,
+            accessed: now,
+            modified: now,
+            created: now,
+        }
+    }
+}
+
+/// The contents of a file, shared by the tree and the handles opened on the file.
+type SharedFile = Arc<Mutex<FileData>>;
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Node {
+    File(SharedFile),
+    Dir(BTreeMap<String, Node>),
+}
+
+impl Node {
+    fn file_type(&self) -> __wasi_filetype_t {
+        match self {
+            Node::File(_) => __WASI_FILETYPE_REGULAR_FILE,
+            Node::Dir(_) => __WASI_FILETYPE_DIRECTORY,
+        }
+    }
+
+    fn stat(&self) -> __wasi_filestat_t {
+        match self {
+            Node::File(data) => {
+                let data = data.lock().unwrap();
+                __wasi_filestat_t {
+                    st_filetype: __WASI_FILETYPE_REGULAR_FILE,
+                    st_size: data.contents.len() as u64,
+                    st_atim: data.accessed,
+                    st_mtim: data.modified,
+                    st_ctim: data.created,
+                    ..__wasi_filestat_t::default()
+                }
+            }
+            Node::Dir(_) => __wasi_filestat_t {
+                st_filetype: __WASI_FILETYPE_DIRECTORY,
+                ..__wasi_filestat_t::default()
+            },
+        }
+    }
+
+    /// Copies the tree, without sharing the contents of the files with `self`.
+    fn deep_clone(&self) -> Node {
+        match self {
+            Node::File(data) => {
+                let data = data.lock().unwrap();
+                Node::File(Arc::new(Mutex::new(FileData {
+                    contents: data.contents.clone(),
+                    accessed: data.accessed,
+                    modified: data.modified,
+                    created: data.created,
+                })))
+            }
+            Node::Dir(entries) => Node::Dir(
+                entries
+                    .iter()
+                    .map(|(name, node)| (name.clone(), node.deep_clone()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn now() -> __wasi_timestamp_t {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// Splits `path` into the names leading to it from the root.
+fn components(path: &Path) -> Result<Vec<String>, WasiFsError> {
+    let mut names: Vec<String> = vec![];
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) => return Err(WasiFsError::InvalidInput),
+            Component::RootDir | Component::CurDir => (),
+            Component::ParentDir => {
+                names.pop();
+            }
+            Component::Normal(name) => {
+                names.push(name.to_str().ok_or(WasiFsError::InvalidInput)?.to_string())
+            }
+        }
+    }
+    Ok(names)
+}
+
+fn lookup<'a>(mut node: &'a mut Node, names: &[String]) -> Result<&'a mut Node, WasiFsError> {
+    for name in names {
+        node = match node {
+            Node::Dir(entries) => entries.get_mut(name).ok_or(WasiFsError::EntityNotFound)?,
+            Node::File(_) => return Err(WasiFsError::BaseNotDirectory),
+        };
+    }
+    Ok(node)
+}
+
+/// Returns the entries of the directory containing `path`, and the name of `path` in it.
+fn lookup_parent<'a>(
+    root: &'a mut Node,
+    path: &Path,
+) -> Result<(&'a mut BTreeMap<String, Node>, String), WasiFsError> {
+    let mut names = components(path)?;
+    // the root has no parent
+    let name = names.pop().ok_or(WasiFsError::InvalidInput)?;
+    match lookup(root, &names)? {
+        Node::Dir(entries) => Ok((entries, name)),
+        Node::File(_) => Err(WasiFsError::BaseNotDirectory),
+    }
+}
+
+/// Removes the file whose contents are `data` from the tree, wherever it is.
+fn remove_file_node(node: &mut Node, data: &SharedFile) -> Option<Node> {
+    if let Node::Dir(entries) = node {
+        let name = entries
+            .iter()
+            .find(|(_, child)| match child {
+                Node::File(child_data) => Arc::ptr_eq(child_data, data),
+                Node::Dir(_) => false,
+            })
+            .map(|(name, _)| name.clone());
+        if let Some(name) = name {
+            return entries.remove(&name);
+        }
+        for child in entries.values_mut() {
+            if let Some(removed) = remove_file_node(child, data) {
+                return Some(removed);
+            }
+        }
+    }
+    None
+}
+
+/// A filesystem held entirely in memory.
+///
+/// Clones of a `MemFs` share the same files, so one clone can be given to WASI while another
+/// is kept to populate the filesystem beforehand and to read what the module wrote afterwards:
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use wasmer_wasi::state::{MemFs, WasiState};
+/// let fs = MemFs::new();
+/// fs.create_dir_all("/data").unwrap();
+/// fs.write_file("/data/input.txt", "hello").unwrap();
+///
+/// let state = WasiState::new("program_name")
+///     .fs_backend(Arc::new(fs.clone()))
+///     .preopen_dir("/data")
+///     .build()
+///     .unwrap();
+/// ```
+///
+/// Symlinks aren't supported.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemFs {
+    root: Arc<Mutex<Node>>,
+}
+
+impl Default for MemFs {
+    fn default() -> Self {
+        MemFs {
+            root: Arc::new(Mutex::new(Node::Dir(BTreeMap::new()))),
+        }
+    }
+}
+
+impl MemFs {
+    /// Creates an empty filesystem, holding nothing but its root directory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the directory at `path` along with its missing parents.
+    pub fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<(), WasiFsError> {
+        let mut root = self.root.lock().unwrap();
+        let mut node = &mut *root;
+        for name in components(path.as_ref())? {
+            node = match node {
+                Node::Dir(entries) => entries
+                    .entry(name)
+                    .or_insert_with(|| Node::Dir(BTreeMap::new())),
+                Node::File(_) => return Err(WasiFsError::BaseNotDirectory),
+            };
+        }
+        match node {
+            Node::Dir(_) => Ok(()),
+            Node::File(_) => Err(WasiFsError::AlreadyExists),
+        }
+    }
+
+    /// Creates the file at `path` holding `contents`, or replaces the contents of the file
+    /// there.
+    pub fn write_file<P, C>(&self, path: P, contents: C) -> Result<(), WasiFsError>
+    where
+        P: AsRef<Path>,
+        C: Into<Vec<u8>>,
+    {
+        let mut root = self.root.lock().unwrap();
+        let (entries, name) = lookup_parent(&mut root, path.as_ref())?;
+        match entries.get(&name) {
+            Some(Node::File(data)) => {
+                let mut data = data.lock().unwrap();
+                data.contents = contents.into();
+                data.modified = now();
+            }
+            Some(Node::Dir(_)) => return Err(WasiFsError::NotAFile),
+            None => {
+                entries.insert(
+                    name,
+                    Node::File(Arc::new(Mutex::new(FileData::new(contents.into())))),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the contents of the file at `path`.
+    pub fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>, WasiFsError> {
+        let mut root = self.root.lock().unwrap();
+        match lookup(&mut root, &components(path.as_ref())?)? {
+            Node::File(data) => Ok(data.lock().unwrap().contents.clone()),
+            Node::Dir(_) => Err(WasiFsError::NotAFile),
+        }
+    }
+}
+
+#[typetag::serde]
+impl WasiFsBackend for MemFs {
+    fn metadata(&self, path: &Path) -> Result<__wasi_filestat_t, WasiFsError> {
+        let mut root = self.root.lock().unwrap();
+        Ok(lookup(&mut root, &components(path)?)?.stat())
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<__wasi_filestat_t, WasiFsError> {
+        self.metadata(path)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<std::path::PathBuf, WasiFsError> {
+        self.metadata(path)?;
+        Err(WasiFsError::InvalidInput)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>, WasiFsError> {
+        let mut root = self.root.lock().unwrap();
+        match lookup(&mut root, &components(path)?)? {
+            Node::Dir(entries) => Ok(entries
+                .iter()
+                .map(|(name, node)| DirEntry {
+                    name: name.clone(),
+                    file_type: node.file_type(),
+                })
+                .collect()),
+            Node::File(_) => Err(WasiFsError::BaseNotDirectory),
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        let mut root = self.root.lock().unwrap();
+        let (entries, name) = lookup_parent(&mut root, path)?;
+        if entries.contains_key(&name) {
+            return Err(WasiFsError::AlreadyExists);
+        }
+        entries.insert(name, Node::Dir(BTreeMap::new()));
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        let mut root = self.root.lock().unwrap();
+        let (entries, name) = lookup_parent(&mut root, path)?;
+        match entries.get(&name) {
+            Some(Node::Dir(children)) if children.is_empty() => {
+                entries.remove(&name);
+                Ok(())
+            }
+            Some(Node::Dir(_)) => Err(WasiFsError::UnknownError(__WASI_ENOTEMPTY)),
+            Some(Node::File(_)) => Err(WasiFsError::BaseNotDirectory),
+            None => Err(WasiFsError::EntityNotFound),
+        }
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), WasiFsError> {
+        let mut root = self.root.lock().unwrap();
+        let (entries, name) = lookup_parent(&mut root, path)?;
+        match entries.get(&name) {
+            Some(Node::File(_)) => {
+                entries.remove(&name);
+                Ok(())
+            }
+            Some(Node::Dir(_)) => Err(WasiFsError::NotAFile),
+            None => Err(WasiFsError::EntityNotFound),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), WasiFsError> {
+        let mut root = self.root.lock().unwrap();
+        // check the destination first, so that a failure leaves the tree untouched
+        lookup_parent(&mut root, to)?;
+        let (entries, name) = lookup_parent(&mut root, from)?;
+        let node = entries.remove(&name).ok_or(WasiFsError::EntityNotFound)?;
+        let (entries, name) = lookup_parent(&mut root, to)?;
+        entries.insert(name, node);
+        Ok(())
+    }
+
+    fn open(&self, path: &Path, options: &OpenOptions) -> Result<Box<dyn WasiFile>, WasiFsError> {
+        let mut root = self.root.lock().unwrap();
+        let (entries, name) = lookup_parent(&mut root, path)?;
+        let data = match entries.get(&name) {
+            Some(Node::File(_)) if options.create_new => return Err(WasiFsError::AlreadyExists),
+            Some(Node::File(data)) => {
+                if options.truncate {
+                    data.lock().unwrap().contents.clear();
+                }
+                Arc::clone(data)
+            }
+            Some(Node::Dir(_)) => return Err(WasiFsError::NotAFile),
+            None if options.create || options.create_new => {
+                let data = Arc::new(Mutex::new(FileData::new(vec![])));
+                entries.insert(name, Node::File(Arc::clone(&data)));
+                data
+            }
+            None => return Err(WasiFsError::EntityNotFound),
+        };
+        Ok(Box::new(MemFile {
+            data,
+            root: Arc::clone(&self.root),
+            cursor: 0,
+            read: options.read,
+            write: options.write || options.append,
+            append: options.append,
+        }))
+    }
+
+    fn snapshot(&self) -> Option<BackendSnapshot> {
+        Some(BackendSnapshot::new(self.root.lock().unwrap().deep_clone()))
+    }
+
+    /// Replaces the files with the ones of `snapshot`.  The files still open keep the contents
+    /// they had, but aren't in the tree anymore.
+    fn restore(&self, snapshot: &BackendSnapshot) -> Result<(), WasiFsError> {
+        let root = snapshot
+            .downcast_ref::<Node>()
+            .ok_or(WasiFsError::InvalidInput)?;
+        *self.root.lock().unwrap() = root.deep_clone();
+        Ok(())
+    }
+}
+
+/// A file opened in a [`MemFs`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemFile {
+    data: SharedFile,
+    root: Arc<Mutex<Node>>,
+    cursor: u64,
+    read: bool,
+    write: bool,
+    append: bool,
+}
+
+impl Read for MemFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.read {
+            return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+        }
+        let mut data = self.data.lock().unwrap();
+        let start = std::cmp::min(self.cursor as usize, data.contents.len());
+        let read = (&data.contents[start..]).read(buf)?;
+        self.cursor += read as u64;
+        data.accessed = now();
+        Ok(read)
+    }
+}
+
+impl Seek for MemFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let len = self.data.lock().unwrap().contents.len() as i64;
+        let new_cursor = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(delta) => len + delta,
+            SeekFrom::Current(delta) => self.cursor as i64 + delta,
+        };
+        if new_cursor < 0 {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+        self.cursor = new_cursor as u64;
+        Ok(self.cursor)
+    }
+}
+
+impl Write for MemFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.write {
+            return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+        }
+        let mut data = self.data.lock().unwrap();
+        if self.append {
+            self.cursor = data.contents.len() as u64;
+        }
+        let start = self.cursor as usize;
+        let end = start + buf.len();
+        if data.contents.len() < end {
+            data.contents.resize(end, 0);
+        }
+        data.contents[start..end].copy_from_slice(buf);
+        self.cursor = end as u64;
+        data.modified = now();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for MemFile {
+    fn last_accessed(&self) -> __wasi_timestamp_t {
+        self.data.lock().unwrap().accessed
+    }
+
+    fn last_modified(&self) -> __wasi_timestamp_t {
+        self.data.lock().unwrap().modified
+    }
+
+    fn created_time(&self) -> __wasi_timestamp_t {
+        self.data.lock().unwrap().created
+    }
+
+    fn set_last_accessed(&self, last_accessed: __wasi_timestamp_t) {
+        self.data.lock().unwrap().accessed = last_accessed;
+    }
+
+    fn set_last_modified(&self, last_modified: __wasi_timestamp_t) {
+        self.data.lock().unwrap().modified = last_modified;
+    }
+
+    fn set_created_time(&self, created_time: __wasi_timestamp_t) {
+        self.data.lock().unwrap().created = created_time;
+    }
+
+    fn size(&self) -> u64 {
+        self.data.lock().unwrap().contents.len() as u64
+    }
+
+    fn set_len(&mut self, new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        self.data
+            .lock()
+            .unwrap()
+            .contents
+            .resize(new_size as usize, 0);
+        Ok(())
+    }
+
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        let mut root = self.root.lock().unwrap();
+        remove_file_node(&mut root, &self.data)
+            .map(|_| ())
+            .ok_or(WasiFsError::EntityNotFound)
+    }
+
+    fn rename_file(&self, new_name: &Path) -> Result<(), WasiFsError> {
+        let mut root = self.root.lock().unwrap();
+        // check the destination first, so that a failure leaves the tree untouched
+        lookup_parent(&mut root, new_name)?;
+        let node = remove_file_node(&mut root, &self.data).ok_or(WasiFsError::EntityNotFound)?;
+        let (entries, name) = lookup_parent(&mut root, new_name)?;
+        entries.insert(name, node);
+        Ok(())
+    }
+
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        let len = self.data.lock().unwrap().contents.len() as u64;
+        Ok(len.saturating_sub(self.cursor) as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn files_are_shared_between_handles_and_the_tree() {
+        let fs = MemFs::new();
+        fs.create_dir_all("/a/b").unwrap();
+        let options = OpenOptions {
+            read: true,
+            write: true,
+            create: true,
+            ..OpenOptions::default()
+        };
+
+        let mut file = fs.open(Path::new("/a/b/file"), &options).unwrap();
+        file.write_all(b"hello world").unwrap();
+        assert_eq!(fs.read_file("/a/b/file").unwrap(), b"hello world");
+
+        file.seek(SeekFrom::Start(6)).unwrap();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "world");
+
+        let entries = fs.read_dir(Path::new("/a")).unwrap();
+        assert_eq!(
+            entries,
+            vec![DirEntry {
+                name: "b".to_string(),
+                file_type: __WASI_FILETYPE_DIRECTORY,
+            }]
+        );
+        assert_eq!(
+            fs.metadata(Path::new("/a/b/file")).unwrap().st_size,
+            "hello world".len() as u64
+        );
+
+        file.rename_file(Path::new("/a/renamed")).unwrap();
+        assert_eq!(fs.read_file("/a/b/file"), Err(WasiFsError::EntityNotFound));
+        assert_eq!(fs.read_file("/a/renamed").unwrap(), b"hello world");
+
+        file.unlink().unwrap();
+        assert_eq!(fs.read_file("/a/renamed"), Err(WasiFsError::EntityNotFound));
+        assert_eq!(
+            fs.remove_dir(Path::new("/a")),
+            Err(WasiFsError::UnknownError(__WASI_ENOTEMPTY))
+        );
+        fs.remove_dir(Path::new("/a/b")).unwrap();
+        fs.remove_dir(Path::new("/a")).unwrap();
+        assert!(fs.read_dir(Path::new("/")).unwrap().is_empty());
+    }
+}
@@ -12,11 +12,42 @@
 //!
 //! You can implement `WasiFile` for your own types to get custom behavior and extend WASI, see the
 //! [WASI plugin example](https://github.com/wasmerio/wasmer/blob/master/examples/plugin.rs).
-
+//!
+//! The files themselves are reached through a `WasiFsBackend`, the host filesystem by default.
+//! Implement it for your own types to serve files from archives, databases, or generated content,
+//! or use `MemFs` to keep them in memory, and `OverlayFs` to layer a writable filesystem over
+//! a read-only one.
+
+mod access;
+mod async_io;
+mod backend;
 mod builder;
+mod clock;
+mod mem_fs;
+mod overlay;
+mod pipe;
+mod policy;
+mod quota;
+mod random;
+mod snapshot;
+mod socket;
+mod threads;
 mod types;
 
+pub use self::access::*;
+pub use self::async_io::*;
+pub use self::backend::*;
 pub use self::builder::*;
+pub use self::clock::*;
+pub use self::mem_fs::*;
+pub use self::overlay::*;
+pub use self::pipe::*;
+pub use self::policy::*;
+pub use self::quota::*;
+pub use self::random::*;
+pub use self::snapshot::*;
+pub use self::socket::*;
+pub(crate) use self::threads::{SharedState, StateLock, WasiThreads};
 pub use self::types::*;
 use crate::syscalls::types::*;
 use generational_arena::Arena;
@@ -28,8 +59,8 @@ use std::{
     cell::Cell,
     fs,
     io::Write,
-    path::{Path, PathBuf},
-    time::SystemTime,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
 };
 use wasmer_runtime_core::{debug, vm::Ctx};
 
@@ -50,6 +81,11 @@ const STDOUT_DEFAULT_RIGHTS: __wasi_rights_t = __WASI_RIGHT_FD_DATASYNC
     | __WASI_RIGHT_FD_FILESTAT_GET
     | __WASI_RIGHT_POLL_FD_READWRITE;
 const STDERR_DEFAULT_RIGHTS: __wasi_rights_t = STDOUT_DEFAULT_RIGHTS;
+const SOCKET_DEFAULT_RIGHTS: __wasi_rights_t = __WASI_RIGHT_FD_READ
+    | __WASI_RIGHT_FD_WRITE
+    | __WASI_RIGHT_FD_FILESTAT_GET
+    | __WASI_RIGHT_POLL_FD_READWRITE
+    | __WASI_RIGHT_SOCK_SHUTDOWN;
 
 /// Get WasiState from a Ctx
 /// This function is unsafe because it must be called on a WASI Ctx
@@ -77,15 +113,14 @@ pub enum Kind {
     File {
         /// the open file, if it's open
         handle: Option<Box<dyn WasiFile>>,
-        /// The path on the host system where the file is located
+        /// The path in the backend where the file is located
         /// This is deprecated and will be removed soon
         path: PathBuf,
     },
     Dir {
         /// Parent directory
         parent: Option<Inode>,
-        /// The path on the host system where the directory is located
-        // TODO: wrap it like WasiFile
+        /// The path in the backend where the directory is located
         path: PathBuf,
         /// The entries of a directory are lazily filled.
         entries: HashMap<String, Inode>,
@@ -148,12 +183,30 @@ pub struct WasiFs {
     inode_counter: Cell<u64>,
     /// for fds still open after the file has been deleted
     pub orphan_fds: HashMap<Inode, InodeVal>,
+    /// the filesystem the files are read from and written to
+    #[serde(
+        default = "backend::default_backend",
+        serialize_with = "backend::serialize_backend",
+        deserialize_with = "backend::deserialize_backend"
+    )]
+    pub(crate) backend: Arc<dyn WasiFsBackend>,
 }
 
 impl WasiFs {
+    /// Creates a `WasiFs` backed by the host filesystem.
     pub fn new(
         preopened_dirs: &[PathBuf],
         mapped_dirs: &[(String, PathBuf)],
+    ) -> Result<Self, String> {
+        Self::new_with_backend(Arc::new(HostFs), preopened_dirs, mapped_dirs)
+    }
+
+    /// Creates a `WasiFs` backed by `backend`, in which the preopened and mapped directories
+    /// are looked up.
+    pub fn new_with_backend(
+        backend: Arc<dyn WasiFsBackend>,
+        preopened_dirs: &[PathBuf],
+        mapped_dirs: &[(String, PathBuf)],
     ) -> Result<Self, String> {
         debug!("wasi::fs::inodes");
         let inodes = Arena::new();
@@ -165,6 +218,7 @@ impl WasiFs {
             next_fd: Cell::new(3),
             inode_counter: Cell::new(1024),
             orphan_fds: HashMap::new(),
+            backend,
         };
         wasi_fs.create_stdin();
         wasi_fs.create_stdout();
@@ -203,14 +257,11 @@ impl WasiFs {
             debug!("Attempting to preopen {}", &dir.to_string_lossy());
             // TODO: think about this
             let default_rights = 0x1FFFFFFF; // all rights
-            let cur_dir_metadata = dir.metadata().map_err(|e| {
-                format!(
-                    "Could not get metadata for file {:?}: {}",
-                    dir,
-                    e.to_string()
-                )
-            })?;
-            let kind = if cur_dir_metadata.is_dir() {
+            let cur_dir_metadata = wasi_fs
+                .backend
+                .metadata(dir)
+                .map_err(|e| format!("Could not get metadata for file {:?}: {:?}", dir, e))?;
+            let kind = if cur_dir_metadata.st_filetype == __WASI_FILETYPE_DIRECTORY {
                 Kind::Dir {
                     parent: Some(root_inode),
                     path: dir.clone(),
@@ -253,14 +304,11 @@ impl WasiFs {
             debug!("Attempting to open {:?} at {}", real_dir, alias);
             // TODO: think about this
             let default_rights = 0x1FFFFFFF; // all rights
-            let cur_dir_metadata = real_dir.metadata().map_err(|e| {
-                format!(
-                    "Could not get metadata for file {:?}: {}",
-                    &real_dir,
-                    e.to_string()
-                )
-            })?;
-            let kind = if cur_dir_metadata.is_dir() {
+            let cur_dir_metadata = wasi_fs
+                .backend
+                .metadata(real_dir)
+                .map_err(|e| format!("Could not get metadata for file {:?}: {:?}", &real_dir, e))?;
+            let kind = if cur_dir_metadata.st_filetype == __WASI_FILETYPE_DIRECTORY {
                 Kind::Dir {
                     parent: Some(root_inode),
                     path: real_dir.clone(),
@@ -301,6 +349,11 @@ impl WasiFs {
         Ok(wasi_fs)
     }
 
+    /// Get the backend the files are read from and written to
+    pub fn backend(&self) -> &dyn WasiFsBackend {
+        &*self.backend
+    }
+
     /// Get the `WasiFile` object at stdout
     pub fn stdout(&self) -> Result<&Option<Box<dyn WasiFile>>, WasiFsError> {
         self.std_dev_get(__WASI_STDOUT_FILENO)
@@ -495,6 +548,11 @@ impl WasiFs {
         let n_components = path.components().count();
         // TODO: rights checks
         'path_iter: for (i, component) in path.components().enumerate() {
+            // an absolute path would be joined to the paths of the backend as is, and so
+            // escape the preopened directories
+            if let Component::RootDir | Component::Prefix(_) = component {
+                return Err(__WASI_ENOTCAPABLE);
+            }
             // used to terminate symlink resolution properly
             let last_component = i + 1 == n_components;
             // for each component traverse file structure
@@ -532,13 +590,17 @@ impl WasiFs {
                                 cd.push(component);
                                 cd
                             };
-                            let metadata = file.symlink_metadata().ok().ok_or(__WASI_EINVAL)?;
-                            let file_type = metadata.file_type();
+                            let metadata = self
+                                .backend
+                                .symlink_metadata(&file)
+                                .ok()
+                                .ok_or(__WASI_EINVAL)?;
+                            let file_type = metadata.st_filetype;
                             // we want to insert newly opened dirs and files, but not transient symlinks
                             // TODO: explain why (think about this deeply when well rested)
                             let mut should_insert = false;
 
-                            let kind = if file_type.is_dir() {
+                            let kind = if file_type == __WASI_FILETYPE_DIRECTORY {
                                 should_insert = true;
                                 // load DIR
                                 Kind::Dir {
@@ -546,21 +608,24 @@ impl WasiFs {
                                     path: file.clone(),
                                     entries: Default::default(),
                                 }
-                            } else if file_type.is_file() {
+                            } else if file_type == __WASI_FILETYPE_REGULAR_FILE {
                                 should_insert = true;
                                 // load file
                                 Kind::File {
                                     handle: None,
                                     path: file.clone(),
                                 }
-                            } else if file_type.is_symlink() {
-                                let link_value = file.read_link().ok().ok_or(__WASI_EIO)?;
+                            } else if file_type == __WASI_FILETYPE_SYMBOLIC_LINK {
+                                let link_value =
+                                    self.backend.read_link(&file).ok().ok_or(__WASI_EIO)?;
                                 debug!("attempting to decompose path {:?}", link_value);
 
                                 let (pre_open_dir_fd, relative_path) = if link_value.is_relative() {
                                     self.path_into_pre_open_and_relative_path(&file)?
                                 } else {
-                                    unimplemented!("Absolute symlinks are not yet supported");
+                                    // the target is a path of the backend, which may be
+                                    // outside of the preopened directories
+                                    return Err(__WASI_ENOTCAPABLE);
                                 };
                                 loop_for_symlink = true;
                                 symlink_count += 1;
@@ -662,24 +727,29 @@ impl WasiFs {
         &self,
         path: &Path,
     ) -> Result<(__wasi_fd_t, PathBuf), __wasi_errno_t> {
-        // for each preopened directory
+        // the innermost preopened directory containing `path`; the virtual root isn't a
+        // directory of the backend, so it never contains it
+        let mut found: Option<(__wasi_fd_t, &Path)> = None;
         for po_fd in &self.preopen_fds {
             let po_inode = self.fd_map[po_fd].inode;
             let po_path = match &self.inodes[po_inode].kind {
                 Kind::Dir { path, .. } => &**path,
-                Kind::Root { .. } => Path::new("/"),
+                Kind::Root { .. } => continue,
                 _ => unreachable!("Preopened FD that's not a directory or the root"),
             };
-            // stem path based on it
-            if let Ok(rest) = path.strip_prefix(po_path) {
-                // if any path meets this criteria
-                // (verify that all remaining components are not symlinks except for maybe last? (or do the more complex logic of resolving intermediary symlinks))
-                // return preopened dir and the rest of the path
-
-                return Ok((*po_fd, rest.to_owned()));
+            let is_innermost = match found {
+                Some((_, found_path)) => po_path.starts_with(found_path),
+                None => true,
+            };
+            if path.starts_with(po_path) && is_innermost {
+                found = Some((*po_fd, po_path));
             }
         }
-        Err(__WASI_EINVAL) // this may not make sense
+        // the rest of the path is then resolved from the preopened directory through the
+        // inodes, so `..` in symlinks can't leave the virtual root
+        let (po_fd, po_path) = found.ok_or(__WASI_EINVAL)?;
+        // unwrap is safe because `path` starts with `po_path`
+        Ok((po_fd, path.strip_prefix(po_path).unwrap().to_owned()))
     }
 
     // if this is still dead code and the year is 2020 or later, please delete this function
@@ -949,6 +1019,21 @@ impl WasiFs {
         Ok(idx)
     }
 
+    /// Creates an fd for the socket `handle`, which is a `WasiTcpStream` or a
+    /// `WasiTcpListener`.
+    pub(crate) fn create_socket_fd(
+        &mut self,
+        handle: Box<dyn WasiFile>,
+    ) -> Result<__wasi_fd_t, __wasi_errno_t> {
+        let kind = Kind::File {
+            handle: Some(handle),
+            path: "".into(),
+        };
+        let inode = self.create_inode_with_default_stat(kind, false, "socket".to_string());
+        self.inodes[inode].stat.st_filetype = __WASI_FILETYPE_SOCKET_STREAM;
+        self.create_fd(SOCKET_DEFAULT_RIGHTS, 0, 0, 0, inode)
+    }
+
     /// This function is unsafe because it's the caller's responsibility to ensure that
     /// all refences to the given inode have been removed from the filesystem
     ///
@@ -1041,22 +1126,20 @@ impl WasiFs {
     }
 
     pub fn get_stat_for_kind(&self, kind: &Kind) -> Option<__wasi_filestat_t> {
-        let md = match kind {
+        match kind {
             Kind::File { handle, path } => match handle {
-                Some(wf) => {
-                    return Some(__wasi_filestat_t {
-                        st_filetype: __WASI_FILETYPE_REGULAR_FILE,
-                        st_size: wf.size(),
-                        st_atim: wf.last_accessed(),
-                        st_mtim: wf.last_modified(),
-                        st_ctim: wf.created_time(),
-
-                        ..__wasi_filestat_t::default()
-                    })
-                }
-                None => path.metadata().ok()?,
+                Some(wf) => Some(__wasi_filestat_t {
+                    st_filetype: __WASI_FILETYPE_REGULAR_FILE,
+                    st_size: wf.size(),
+                    st_atim: wf.last_accessed(),
+                    st_mtim: wf.last_modified(),
+                    st_ctim: wf.created_time(),
+
+                    ..__wasi_filestat_t::default()
+                }),
+                None => self.backend.metadata(path).ok(),
             },
-            Kind::Dir { path, .. } => path.metadata().ok()?,
+            Kind::Dir { path, .. } => self.backend.metadata(path).ok(),
             Kind::Symlink {
                 base_po_dir,
                 path_to_symlink,
@@ -1066,7 +1149,7 @@ impl WasiFs {
                 let base_po_inode_v = &self.inodes[*base_po_inode];
                 match &base_po_inode_v.kind {
                     Kind::Root { .. } => {
-                        path_to_symlink.clone().symlink_metadata().ok()?
+                        self.backend.symlink_metadata(path_to_symlink).ok()
                     }
                     Kind::Dir { path, .. } => {
                         let mut real_path = path.clone();
@@ -1077,37 +1160,14 @@ impl WasiFs {
                         // TODO: adjust size of symlink, too
                         //      for all paths adjusted think about this
                         real_path.push(path_to_symlink);
-                        real_path.symlink_metadata().ok()?
+                        self.backend.symlink_metadata(&real_path).ok()
                     }
                     // if this triggers, there's a bug in the symlink code
                     _ => unreachable!("Symlink pointing to something that's not a directory as its base preopened directory"),
                 }
             }
-            __ => return None,
-        };
-        Some(__wasi_filestat_t {
-            st_filetype: host_file_type_to_wasi_file_type(md.file_type()),
-            st_size: md.len(),
-            st_atim: md
-                .accessed()
-                .ok()?
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .ok()?
-                .as_nanos() as u64,
-            st_mtim: md
-                .modified()
-                .ok()?
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .ok()?
-                .as_nanos() as u64,
-            st_ctim: md
-                .created()
-                .ok()
-                .and_then(|ct| ct.duration_since(SystemTime::UNIX_EPOCH).ok())
-                .map(|ct| ct.as_nanos() as u64)
-                .unwrap_or(0),
-            ..__wasi_filestat_t::default()
-        })
+            __ => None,
+        }
     }
 
     /// Closes an open FD, handling all details such as FD being preopen
@@ -1177,6 +1237,37 @@ pub struct WasiState {
     pub fs: WasiFs,
     pub args: Vec<Vec<u8>>,
     pub envs: Vec<Vec<u8>>,
+    /// Disables the clocks and `random_get`, whose results differ from run to run, unless
+    /// `clock` and `random` are deterministic.
+    #[serde(default)]
+    pub deterministic: bool,
+    /// The addresses the module may connect to and listen on.
+    #[serde(default)]
+    pub network: NetworkGrants,
+    /// The limits of the resources the module may use.
+    #[serde(default)]
+    pub quotas: WasiQuotas,
+    /// The clock read by the module, the clock of the host by default.
+    #[serde(
+        default = "clock::default_clock",
+        serialize_with = "clock::serialize_clock",
+        deserialize_with = "clock::deserialize_clock"
+    )]
+    pub clock: Arc<dyn WasiClock>,
+    /// The source of the bytes returned by `random_get`, the entropy of the OS by default.
+    #[serde(
+        default = "random::default_random",
+        serialize_with = "random::serialize_random",
+        deserialize_with = "random::deserialize_random"
+    )]
+    pub random: Arc<dyn WasiRandom>,
+    /// Asked before the syscalls touching the paths of the backend.  It isn't serialized: a state
+    /// unfrozen from bytes has no hook until it's set again.
+    #[serde(skip)]
+    pub fs_access_hook: Option<Arc<dyn FsAccessHook>>,
+    /// Starts the threads of modules using wasi-threads, when they may.
+    #[serde(skip)]
+    pub(crate) threads: Option<Arc<WasiThreads>>,
 }
 
 impl WasiState {
@@ -0,0 +1,351 @@
+//! A `WasiFsBackend` merging a writable layer over a read-only one, like overlayfs.
+//!
+//! The module sees the files of both layers, those of the upper layer hiding the ones at the
+//! same path in the lower layer.  Everything it changes goes to the upper layer: files of the
+//! lower layer are copied up when they're opened for writing, and removing them only records a
+//! whiteout hiding them.  The lower layer is never written to, so a single base image can back
+//! many instances, each with its own upper layer holding what it wrote.
+//!
+//! More than two sources are stacked by using an `OverlayFs` as the lower layer of another.
+
+use crate::state::{
+    backend::{deserialize_backend, serialize_backend},
+    BackendSnapshot, DirEntry, OpenOptions, WasiFile, WasiFsBackend, WasiFsError,
+};
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// A backend serving the files of `upper` over those of `lower`, and writing to `upper` only.
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use wasmer_wasi::state::{MemFs, OverlayFs, WasiState};
+/// let image = MemFs::new();
+/// image.create_dir_all("/data").unwrap();
+/// image.write_file("/data/input.txt", "hello").unwrap();
+/// let changes = MemFs::new();
+///
+/// let state = WasiState::new("program_name")
+///     .fs_backend(Arc::new(OverlayFs::new(
+///         Arc::new(changes.clone()),
+///         Arc::new(image),
+///     )))
+///     .preopen_dir("/data")
+///     .build()
+///     .unwrap();
+/// ```
+///
+/// Renaming a directory existing in the lower layer fails with `__WASI_EXDEV`, as with overlayfs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OverlayFs {
+    #[serde(
+        serialize_with = "serialize_backend",
+        deserialize_with = "deserialize_backend"
+    )]
+    upper: Arc<dyn WasiFsBackend>,
+    #[serde(
+        serialize_with = "serialize_backend",
+        deserialize_with = "deserialize_backend"
+    )]
+    lower: Arc<dyn WasiFsBackend>,
+    /// The paths removed from the lower layer
+    whiteouts: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl OverlayFs {
+    /// Creates a backend writing to `upper` and reading from `upper`, then `lower`.
+    pub fn new(upper: Arc<dyn WasiFsBackend>, lower: Arc<dyn WasiFsBackend>) -> Self {
+        Self {
+            upper,
+            lower,
+            whiteouts: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// The layer holding everything written.
+    pub fn upper(&self) -> &Arc<dyn WasiFsBackend> {
+        &self.upper
+    }
+
+    /// The paths of the lower layer that were removed or renamed, and are hidden.
+    pub fn whiteouts(&self) -> Vec<PathBuf> {
+        self.whiteouts.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Returns whether `path`, or one of its parents, was removed from the lower layer.
+    fn is_whiteout(&self, path: &Path) -> bool {
+        let whiteouts = self.whiteouts.lock().unwrap();
+        path.ancestors()
+            .any(|ancestor| whiteouts.contains(ancestor))
+    }
+
+    /// Returns whether `path` exists in the lower layer and isn't hidden.
+    fn in_lower(&self, path: &Path) -> bool {
+        !self.is_whiteout(path) && self.lower.symlink_metadata(path).is_ok()
+    }
+
+    fn in_upper(&self, path: &Path) -> bool {
+        self.upper.symlink_metadata(path).is_ok()
+    }
+
+    /// Looks `path` up in the upper layer, then in the lower layer.
+    fn lookup<T, F>(&self, path: &Path, f: F) -> Result<T, WasiFsError>
+    where
+        F: Fn(&dyn WasiFsBackend, &Path) -> Result<T, WasiFsError>,
+    {
+        match f(&*self.upper, path) {
+            Err(WasiFsError::EntityNotFound) if !self.is_whiteout(path) => f(&*self.lower, path),
+            result => result,
+        }
+    }
+
+    /// Creates the parents of `path` missing from the upper layer.
+    fn copy_up_parents(&self, path: &Path) -> Result<(), WasiFsError> {
+        let parents: Vec<_> = path.ancestors().skip(1).collect();
+        for parent in parents.into_iter().rev() {
+            if parent.as_os_str().is_empty() || self.in_upper(parent) {
+                continue;
+            }
+            if !self.in_lower(parent) {
+                return Err(WasiFsError::EntityNotFound);
+            }
+            self.upper.create_dir(parent)?;
+        }
+        Ok(())
+    }
+
+    /// Copies the file at `path` from the lower layer to the upper layer.
+    fn copy_up_file(&self, path: &Path) -> Result<(), WasiFsError> {
+        let stat = self.lower.metadata(path)?;
+        if stat.st_filetype == __WASI_FILETYPE_DIRECTORY {
+            return Err(WasiFsError::NotAFile);
+        }
+        let mut contents = vec![];
+        let read = OpenOptions {
+            read: true,
+            ..OpenOptions::default()
+        };
+        self.lower.open(path, &read)?.read_to_end(&mut contents)?;
+
+        self.copy_up_parents(path)?;
+        let write = OpenOptions {
+            write: true,
+            create_new: true,
+            ..OpenOptions::default()
+        };
+        let mut file = self.upper.open(path, &write)?;
+        file.write_all(&contents)?;
+        file.set_last_accessed(stat.st_atim);
+        file.set_last_modified(stat.st_mtim);
+        file.set_created_time(stat.st_ctim);
+        Ok(())
+    }
+
+    /// Makes the removal of `path` final, hiding it in the lower layer.
+    fn hide(&self, path: &Path) {
+        if self.in_lower(path) {
+            self.whiteouts.lock().unwrap().insert(path.to_path_buf());
+        }
+    }
+
+    /// Makes `path`, about to be created in the upper layer, visible again.
+    fn unhide(&self, path: &Path) {
+        self.whiteouts.lock().unwrap().remove(path);
+    }
+}
+
+#[typetag::serde]
+impl WasiFsBackend for OverlayFs {
+    fn metadata(&self, path: &Path) -> Result<__wasi_filestat_t, WasiFsError> {
+        self.lookup(path, |layer, path| layer.metadata(path))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<__wasi_filestat_t, WasiFsError> {
+        self.lookup(path, |layer, path| layer.symlink_metadata(path))
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, WasiFsError> {
+        self.lookup(path, |layer, path| layer.read_link(path))
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>, WasiFsError> {
+        let mut entries = match self.upper.read_dir(path) {
+            Ok(entries) => entries,
+            Err(WasiFsError::EntityNotFound) => vec![],
+            Err(e) => return Err(e),
+        };
+        let in_upper = self.in_upper(path);
+        if self.is_whiteout(path) {
+            return if in_upper {
+                Ok(entries)
+            } else {
+                Err(WasiFsError::EntityNotFound)
+            };
+        }
+        match self.lower.read_dir(path) {
+            Ok(lower_entries) => {
+                let names: BTreeSet<_> = entries.iter().map(|e| e.name.clone()).collect();
+                entries.extend(lower_entries.into_iter().filter(|entry| {
+                    !names.contains(&entry.name) && !self.is_whiteout(&path.join(&entry.name))
+                }));
+                Ok(entries)
+            }
+            Err(_) if in_upper => Ok(entries),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        if self.in_upper(path) || self.in_lower(path) {
+            return Err(WasiFsError::AlreadyExists);
+        }
+        self.copy_up_parents(path)?;
+        self.upper.create_dir(path)?;
+        self.unhide(path);
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        if self.metadata(path)?.st_filetype != __WASI_FILETYPE_DIRECTORY {
+            return Err(WasiFsError::BaseNotDirectory);
+        }
+        if !self.read_dir(path)?.is_empty() {
+            return Err(WasiFsError::UnknownError(__WASI_ENOTEMPTY));
+        }
+        if self.in_upper(path) {
+            self.upper.remove_dir(path)?;
+        }
+        self.hide(path);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), WasiFsError> {
+        if self.symlink_metadata(path)?.st_filetype == __WASI_FILETYPE_DIRECTORY {
+            return Err(WasiFsError::NotAFile);
+        }
+        if self.in_upper(path) {
+            self.upper.remove_file(path)?;
+        }
+        self.hide(path);
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), WasiFsError> {
+        let stat = self.symlink_metadata(from)?;
+        if stat.st_filetype == __WASI_FILETYPE_DIRECTORY && self.in_lower(from) {
+            return Err(WasiFsError::UnknownError(__WASI_EXDEV));
+        }
+        if !self.in_upper(from) {
+            self.copy_up_file(from)?;
+        }
+        self.copy_up_parents(to)?;
+        self.upper.rename(from, to)?;
+        self.hide(from);
+        self.unhide(to);
+        Ok(())
+    }
+
+    fn open(&self, path: &Path, options: &OpenOptions) -> Result<Box<dyn WasiFile>, WasiFsError> {
+        let writes = options.write || options.append || options.truncate;
+        if !self.in_upper(path) && self.in_lower(path) {
+            if options.create_new {
+                return Err(WasiFsError::AlreadyExists);
+            }
+            if !writes {
+                return self.lower.open(path, options);
+            }
+            self.copy_up_file(path)?;
+        } else if options.create || options.create_new {
+            self.copy_up_parents(path)?;
+        }
+        let file = self.upper.open(path, options)?;
+        self.unhide(path);
+        Ok(file)
+    }
+
+    /// Saves the upper layer and the whiteouts, the lower layer being read-only.
+    fn snapshot(&self) -> Option<BackendSnapshot> {
+        let upper = self.upper.snapshot()?;
+        Some(BackendSnapshot::new((
+            upper,
+            self.whiteouts.lock().unwrap().clone(),
+        )))
+    }
+
+    fn restore(&self, snapshot: &BackendSnapshot) -> Result<(), WasiFsError> {
+        let (upper, whiteouts) = snapshot
+            .downcast_ref::<(BackendSnapshot, BTreeSet<PathBuf>)>()
+            .ok_or(WasiFsError::InvalidInput)?;
+        self.upper.restore(upper)?;
+        *self.whiteouts.lock().unwrap() = whiteouts.clone();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::MemFs;
+
+    #[test]
+    fn writes_go_to_the_upper_layer() {
+        let lower = MemFs::new();
+        lower.create_dir_all("/image/etc").unwrap();
+        lower.write_file("/image/etc/config", "base").unwrap();
+        lower.write_file("/image/readme", "hello").unwrap();
+        let upper = MemFs::new();
+        let overlay = OverlayFs::new(Arc::new(upper.clone()), Arc::new(lower.clone()));
+
+        // files of the lower layer are copied up when written
+        let write = OpenOptions {
+            write: true,
+            truncate: true,
+            ..OpenOptions::default()
+        };
+        let mut file = overlay
+            .open(Path::new("/image/etc/config"), &write)
+            .unwrap();
+        file.write_all(b"changed").unwrap();
+        assert_eq!(upper.read_file("/image/etc/config").unwrap(), b"changed");
+        assert_eq!(lower.read_file("/image/etc/config").unwrap(), b"base");
+
+        // removals only hide the files of the lower layer
+        overlay.remove_file(Path::new("/image/readme")).unwrap();
+        assert_eq!(
+            overlay.metadata(Path::new("/image/readme")),
+            Err(WasiFsError::EntityNotFound)
+        );
+        assert!(lower.read_file("/image/readme").is_ok());
+        assert_eq!(overlay.whiteouts(), vec![PathBuf::from("/image/readme")]);
+
+        overlay.create_dir(Path::new("/image/new")).unwrap();
+        let mut names: Vec<_> = overlay
+            .read_dir(Path::new("/image"))
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["etc", "new"]);
+
+        // the lower layer isn't writable through a rename either
+        overlay
+            .rename(Path::new("/image/etc/config"), Path::new("/image/config"))
+            .unwrap();
+        assert!(lower.read_file("/image/etc/config").is_ok());
+        assert_eq!(
+            overlay.read_dir(Path::new("/image/etc")).unwrap(),
+            Vec::<DirEntry>::new()
+        );
+        assert_eq!(
+            overlay.rename(Path::new("/image/etc"), Path::new("/image/etc2")),
+            Err(WasiFsError::UnknownError(__WASI_EXDEV))
+        );
+    }
+}
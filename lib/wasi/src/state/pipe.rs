@@ -0,0 +1,177 @@
+//! Pipes connecting the stdio of WASI instances running in the same process.
+//!
+//! `pipe` returns the two ends of a bounded buffer.  Give the writer to the `stdout` of the
+//! builder of one instance and the reader to the `stdin` of another, then run them on different
+//! threads, like `producer | consumer` in a shell:
+//!
+//! ```
+//! # use wasmer_wasi::state::{pipe, WasiState};
+//! let (writer, reader) = pipe(64 * 1024);
+//! let producer = WasiState::new("producer").stdout(writer.clone()).build();
+//! let consumer = WasiState::new("consumer").stdin(reader).build();
+//! // once the producer returned
+//! writer.close();
+//! ```
+//!
+//! The writer blocks while the buffer is full, so the producer can't get ahead of the consumer
+//! by more than the capacity of the pipe.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    sync::{Arc, Condvar, Mutex},
+};
+
+#[derive(Debug)]
+struct PipeBuffer {
+    data: VecDeque<u8>,
+    capacity: usize,
+    writer_closed: bool,
+    reader_closed: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    buffer: Mutex<PipeBuffer>,
+    /// Notified when data is written or read, and when an end is closed
+    changed: Condvar,
+}
+
+/// Creates a pipe holding at most `capacity` bytes not read yet, at least one.
+pub fn pipe(capacity: usize) -> (PipeWriter, PipeReader) {
+    let shared = Arc::new(Shared {
+        buffer: Mutex::new(PipeBuffer {
+            data: VecDeque::new(),
+            capacity: capacity.max(1),
+            writer_closed: false,
+            reader_closed: false,
+        }),
+        changed: Condvar::new(),
+    });
+    (
+        PipeWriter {
+            shared: shared.clone(),
+        },
+        PipeReader { shared },
+    )
+}
+
+/// The end of a [`pipe`] written to.
+///
+/// Its clones write to the same pipe.  The reader gets the end of file once `close` is called,
+/// as the builders and the states given the writer keep it open otherwise.
+#[derive(Debug, Clone)]
+pub struct PipeWriter {
+    shared: Arc<Shared>,
+}
+
+impl PipeWriter {
+    /// Closes the pipe for writing: the reader gets the end of file once it read what's left.
+    pub fn close(&self) {
+        self.shared.buffer.lock().unwrap().writer_closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Write for PipeWriter {
+    /// Writes as much of `buf` as fits, waiting for the reader to make room if the pipe is full.
+    ///
+    /// Fails with `BrokenPipe` once either end is closed.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut buffer = self.shared.buffer.lock().unwrap();
+        loop {
+            if buffer.reader_closed || buffer.writer_closed {
+                return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+            }
+            if buffer.data.len() < buffer.capacity {
+                break;
+            }
+            buffer = self.shared.changed.wait(buffer).unwrap();
+        }
+        let len = buf.len().min(buffer.capacity - buffer.data.len());
+        buffer.data.extend(&buf[..len]);
+        self.shared.changed.notify_all();
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The end of a [`pipe`] read from.
+///
+/// Dropping it closes the pipe for reading, as does `close`: writing to the pipe fails from then
+/// on, like writing to a pipe whose reader exited.
+#[derive(Debug)]
+pub struct PipeReader {
+    shared: Arc<Shared>,
+}
+
+impl PipeReader {
+    /// Closes the pipe for reading, discarding what wasn't read.
+    pub fn close(&self) {
+        let mut buffer = self.shared.buffer.lock().unwrap();
+        buffer.reader_closed = true;
+        buffer.data.clear();
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Read for PipeReader {
+    /// Reads what was written, waiting for the writer if the pipe is empty.  Reading nothing
+    /// means the writer closed the pipe.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut buffer = self.shared.buffer.lock().unwrap();
+        while buffer.data.is_empty() {
+            if buffer.writer_closed || buffer.reader_closed || buf.is_empty() {
+                return Ok(0);
+            }
+            buffer = self.shared.changed.wait(buffer).unwrap();
+        }
+        let len = buf.len().min(buffer.data.len());
+        for (byte, data) in buf.iter_mut().zip(buffer.data.drain(..len)) {
+            *byte = data;
+        }
+        self.shared.changed.notify_all();
+        Ok(len)
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn pipes_apply_backpressure_until_closed() {
+        let (mut writer, mut reader) = pipe(4);
+        let producer = thread::spawn(move || {
+            // blocks until the reader made room
+            writer.write_all(b"hello, world").unwrap();
+            writer.close();
+        });
+
+        let mut output = vec![];
+        reader.read_to_end(&mut output).unwrap();
+        producer.join().unwrap();
+        assert_eq!(output, b"hello, world");
+
+        // writing fails once the reader is gone
+        let (mut writer, reader) = pipe(4);
+        drop(reader);
+        assert_eq!(
+            writer.write(b"!").unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Policies restricting the paths of a `WasiFsBackend` that WASI modules can reach.
+//!
+//! `WasiFs` already keeps modules inside their preopened directories: guest paths are
+//! resolved one component at a time through its inodes, absolute paths are refused, and
+//! `..` never leaves the virtual root.  A `PathPolicy` additionally denies or rewrites
+//! specific paths of the backend, for example to hide secrets inside an exposed directory.
+
+use crate::state::{
+    backend::{deserialize_backend, serialize_backend},
+    BackendSnapshot, DirEntry, OpenOptions, WasiFile, WasiFsBackend, WasiFsError,
+};
+use crate::syscalls::types::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Decides which paths of the backend WASI modules may access.
+///
+/// The paths given are paths of the backend, like the ones passed to
+/// `WasiStateBuilder::preopen_dir`, and include the preopened directories themselves.
+#[typetag::serde(tag = "type")]
+pub trait PathPolicy: std::fmt::Debug + Send + Sync {
+    /// Returns the path to access instead of `path`, which is `path` itself to allow it, or
+    /// `None` to deny it.
+    fn resolve(&self, path: &Path) -> Option<PathBuf>;
+}
+
+/// A policy denying and rewriting the paths under given prefixes.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefixPolicy {
+    deny: Vec<PathBuf>,
+    rewrite: Vec<(PathBuf, PathBuf)>,
+}
+
+impl PrefixPolicy {
+    /// Creates a policy allowing every path.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Denies `prefix` and everything under it.
+    pub fn deny<P: AsRef<Path>>(&mut self, prefix: P) -> &mut Self {
+        self.deny.push(prefix.as_ref().to_path_buf());
+        self
+    }
+
+    /// Serves `to`, and the paths under it, in place of `from` and the paths under it.
+    ///
+    /// The first matching rewrite applies; the paths denied are checked before rewriting.
+    pub fn rewrite<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> &mut Self {
+        self.rewrite
+            .push((from.as_ref().to_path_buf(), to.as_ref().to_path_buf()));
+        self
+    }
+}
+
+#[typetag::serde]
+impl PathPolicy for PrefixPolicy {
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        if self.deny.iter().any(|prefix| path.starts_with(prefix)) {
+            return None;
+        }
+        for (from, to) in &self.rewrite {
+            if let Ok(rest) = path.strip_prefix(from) {
+                return Some(to.join(rest));
+            }
+        }
+        Some(path.to_path_buf())
+    }
+}
+
+/// A backend applying a [`PathPolicy`] to every path before passing it on to another backend.
+///
+/// Denied paths fail with `WasiFsError::PermissionDenied`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PolicyFs {
+    #[serde(
+        serialize_with = "serialize_backend",
+        deserialize_with = "deserialize_backend"
+    )]
+    inner: Arc<dyn WasiFsBackend>,
+    #[serde(
+        serialize_with = "serialize_policy",
+        deserialize_with = "deserialize_policy"
+    )]
+    policy: Arc<dyn PathPolicy>,
+}
+
+impl PolicyFs {
+    /// Creates a backend serving the paths of `inner` allowed by `policy`.
+    pub fn new(inner: Arc<dyn WasiFsBackend>, policy: Arc<dyn PathPolicy>) -> Self {
+        Self { inner, policy }
+    }
+
+    fn resolve(&self, path: &Path) -> Result<PathBuf, WasiFsError> {
+        self.policy
+            .resolve(path)
+            .ok_or(WasiFsError::PermissionDenied)
+    }
+}
+
+#[typetag::serde]
+impl WasiFsBackend for PolicyFs {
+    fn metadata(&self, path: &Path) -> Result<__wasi_filestat_t, WasiFsError> {
+        self.inner.metadata(&self.resolve(path)?)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<__wasi_filestat_t, WasiFsError> {
+        self.inner.symlink_metadata(&self.resolve(path)?)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, WasiFsError> {
+        self.inner.read_link(&self.resolve(path)?)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>, WasiFsError> {
+        let entries = self.inner.read_dir(&self.resolve(path)?)?;
+        // hide the entries the module can't access anyway
+        Ok(entries
+            .into_iter()
+            .filter(|entry| self.policy.resolve(&path.join(&entry.name)).is_some())
+            .collect())
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        self.inner.create_dir(&self.resolve(path)?)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        self.inner.remove_dir(&self.resolve(path)?)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), WasiFsError> {
+        self.inner.remove_file(&self.resolve(path)?)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), WasiFsError> {
+        self.inner.rename(&self.resolve(from)?, &self.resolve(to)?)
+    }
+
+    fn open(&self, path: &Path, options: &OpenOptions) -> Result<Box<dyn WasiFile>, WasiFsError> {
+        self.inner.open(&self.resolve(path)?, options)
+    }
+
+    fn snapshot(&self) -> Option<BackendSnapshot> {
+        self.inner.snapshot()
+    }
+
+    fn restore(&self, snapshot: &BackendSnapshot) -> Result<(), WasiFsError> {
+        self.inner.restore(snapshot)
+    }
+}
+
+fn serialize_policy<S>(policy: &Arc<dyn PathPolicy>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    (**policy).serialize(serializer)
+}
+
+fn deserialize_policy<'de, D>(deserializer: D) -> Result<Arc<dyn PathPolicy>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Box::<dyn PathPolicy>::deserialize(deserializer).map(Arc::from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prefix_policy_denies_before_rewriting() {
+        let mut policy = PrefixPolicy::new();
+        policy
+            .deny("/data/secrets")
+            .rewrite("/data/config", "/etc/app");
+
+        assert_eq!(policy.resolve(Path::new("/data/secrets/key")), None);
+        assert_eq!(
+            policy.resolve(Path::new("/data/config/app.toml")),
+            Some(PathBuf::from("/etc/app/app.toml"))
+        );
+        assert_eq!(
+            policy.resolve(Path::new("/data/file")),
+            Some(PathBuf::from("/data/file"))
+        );
+        // prefixes match whole components
+        assert_eq!(
+            policy.resolve(Path::new("/data/secrets2")),
+            Some(PathBuf::from("/data/secrets2"))
+        );
+    }
+}
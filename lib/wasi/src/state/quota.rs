@@ -0,0 +1,101 @@
+//! Limits on the resources a single WASI instance may use.
+//!
+//! Unlike OS rlimits, which apply to the whole process, the quotas apply to each instance on
+//! its own, so a host running many modules can keep any of them from exhausting its resources.
+//! The calls exceeding a quota fail with an errno the module can handle, they don't trap.
+
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+
+/// The limits of a WASI instance, and what it used so far.
+///
+/// Every limit is unset, meaning unlimited, by default.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WasiQuotas {
+    /// The most fds open at the same time, including stdio and the preopened directories.
+    /// Opening more fails with `__WASI_EMFILE`
+    pub max_open_fds: Option<usize>,
+    /// The most bytes written to files, sockets, stdout and stderr in total.  Writes going over
+    /// it fail with `__WASI_EDQUOT`, without writing anything
+    pub max_bytes_written: Option<u64>,
+    /// The most files, directories and links created in total.  Creating more fails with
+    /// `__WASI_EDQUOT`
+    pub max_entries_created: Option<u64>,
+    bytes_written: u64,
+    entries_created: u64,
+}
+
+impl WasiQuotas {
+    /// The number of bytes written so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// The number of files, directories and links created so far.
+    pub fn entries_created(&self) -> u64 {
+        self.entries_created
+    }
+
+    /// Checks that one more fd may be opened while `open_fds` are.
+    pub(crate) fn check_open_fd(&self, open_fds: usize) -> Result<(), __wasi_errno_t> {
+        match self.max_open_fds {
+            Some(max) if open_fds >= max => Err(__WASI_EMFILE),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that `len` more bytes may be written.
+    pub(crate) fn check_write(&self, len: u64) -> Result<(), __wasi_errno_t> {
+        match self.max_bytes_written {
+            Some(max) if self.bytes_written.saturating_add(len) > max => Err(__WASI_EDQUOT),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn record_write(&mut self, len: u64) {
+        self.bytes_written = self.bytes_written.saturating_add(len);
+    }
+
+    /// Checks that one more entry may be created.
+    pub(crate) fn check_create_entry(&self) -> Result<(), __wasi_errno_t> {
+        match self.max_entries_created {
+            Some(max) if self.entries_created >= max => Err(__WASI_EDQUOT),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn record_create_entry(&mut self) {
+        self.entries_created += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quotas_are_enforced_once_reached() {
+        let mut quotas = WasiQuotas {
+            max_open_fds: Some(4),
+            max_bytes_written: Some(10),
+            max_entries_created: Some(1),
+            ..WasiQuotas::default()
+        };
+
+        assert_eq!(quotas.check_open_fd(3), Ok(()));
+        assert_eq!(quotas.check_open_fd(4), Err(__WASI_EMFILE));
+
+        assert_eq!(quotas.check_write(10), Ok(()));
+        quotas.record_write(6);
+        assert_eq!(quotas.check_write(5), Err(__WASI_EDQUOT));
+        assert_eq!(quotas.check_write(4), Ok(()));
+        assert_eq!(quotas.bytes_written(), 6);
+
+        assert_eq!(quotas.check_create_entry(), Ok(()));
+        quotas.record_create_entry();
+        assert_eq!(quotas.check_create_entry(), Err(__WASI_EDQUOT));
+
+        // no limits by default
+        assert_eq!(WasiQuotas::default().check_write(u64::max_value()), Ok(()));
+    }
+}
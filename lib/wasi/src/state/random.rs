@@ -0,0 +1,113 @@
+//! The source of the bytes returned by `random_get`.
+//!
+//! `HostRandom`, which reads the entropy of the OS, is the default.  `SeededRandom` returns
+//! the same bytes on every run with the same seed; implement `WasiRandom` for your own types
+//! to provide entropy from somewhere else.
+
+use crate::syscalls::types::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::{Arc, Mutex};
+
+/// A source of random bytes for WASI modules.
+///
+/// Sources are shared between the instances created from the same `WasiStateBuilder`, so
+/// they advance through `&self`.
+#[typetag::serde(tag = "type")]
+pub trait WasiRandom: std::fmt::Debug + Send + Sync {
+    /// Fills `buf` with random bytes.
+    fn fill(&self, buf: &mut [u8]) -> Result<(), __wasi_errno_t>;
+
+    /// Whether the bytes returned only depend on the calls made, in which case `random_get`
+    /// stays available in deterministic mode.  Default returns `false`
+    fn is_deterministic(&self) -> bool {
+        false
+    }
+}
+
+/// The entropy of the OS.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct HostRandom;
+
+#[typetag::serde]
+impl WasiRandom for HostRandom {
+    fn fill(&self, buf: &mut [u8]) -> Result<(), __wasi_errno_t> {
+        getrandom::getrandom(buf).map_err(|_| __WASI_EIO)
+    }
+}
+
+/// A pseudorandom generator returning the same bytes for the same seed.
+///
+/// It's not cryptographically secure: use it for replays and tests, not for keys.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SeededRandom {
+    state: Mutex<u64>,
+}
+
+impl SeededRandom {
+    /// Creates a generator from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Mutex::new(seed),
+        }
+    }
+}
+
+#[typetag::serde]
+impl WasiRandom for SeededRandom {
+    fn fill(&self, buf: &mut [u8]) -> Result<(), __wasi_errno_t> {
+        let mut state = self.state.lock().unwrap();
+        for chunk in buf.chunks_mut(8) {
+            // SplitMix64
+            *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = *state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^= z >> 31;
+            chunk.copy_from_slice(&z.to_le_bytes()[..chunk.len()]);
+        }
+        Ok(())
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+pub(crate) fn default_random() -> Arc<dyn WasiRandom> {
+    Arc::new(HostRandom)
+}
+
+pub(crate) fn serialize_random<S>(
+    random: &Arc<dyn WasiRandom>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    (**random).serialize(serializer)
+}
+
+pub(crate) fn deserialize_random<'de, D>(deserializer: D) -> Result<Arc<dyn WasiRandom>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Box::<dyn WasiRandom>::deserialize(deserializer).map(Arc::from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seeded_random_is_reproducible() {
+        let mut first = [0; 13];
+        let mut second = [0; 13];
+        SeededRandom::new(42).fill(&mut first).unwrap();
+        SeededRandom::new(42).fill(&mut second).unwrap();
+        assert_eq!(first, second);
+
+        let mut other = [0; 13];
+        SeededRandom::new(43).fill(&mut other).unwrap();
+        assert_ne!(first, other);
+    }
+}
@@ -0,0 +1,194 @@
+//! Snapshots of the filesystem state of a WASI instance, to reset it between invocations.
+//!
+//! An instance kept in a pool to serve many requests piles up the fds its module forgot to
+//! close, and the files it left behind.  Taking a `WasiStateSnapshot` once the instance is
+//! ready, and resetting to it after every request, closes the fds opened since and, for the
+//! backends supporting it like `MemFs`, throws away what was written to the backend.
+
+use crate::state::{Fd, Inode, Kind, WasiFs, WasiFsError, WasiQuotas, WasiState};
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+};
+
+/// The state of a `WasiFsBackend`, returned by `WasiFsBackend::snapshot`.
+///
+/// Its contents are only known to the backend which took it.
+pub struct BackendSnapshot(Box<dyn Any + Send + Sync>);
+
+impl BackendSnapshot {
+    pub fn new<T: Any + Send + Sync>(state: T) -> Self {
+        BackendSnapshot(Box::new(state))
+    }
+
+    /// Returns the state held, if it's a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+}
+
+impl std::fmt::Debug for BackendSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("BackendSnapshot")
+    }
+}
+
+/// The fds of a [`WasiFs`], and the state of its backend if it supports snapshots.
+#[derive(Debug)]
+pub struct WasiFsSnapshot {
+    fds: HashMap<u32, Fd>,
+    preopen_fds: Vec<u32>,
+    next_fd: u32,
+    inodes: HashSet<Inode>,
+    backend: Option<BackendSnapshot>,
+}
+
+impl WasiFsSnapshot {
+    /// Returns whether the contents of the backend were saved, and are restored by `reset`.
+    pub fn has_backend(&self) -> bool {
+        self.backend.is_some()
+    }
+}
+
+impl WasiFs {
+    /// Takes a snapshot of the open fds, and of the backend if it supports it.
+    pub fn snapshot(&self) -> WasiFsSnapshot {
+        WasiFsSnapshot {
+            fds: self.fd_map.clone(),
+            preopen_fds: self.preopen_fds.clone(),
+            next_fd: self.next_fd.get(),
+            inodes: self.inodes.iter().map(|(inode, _)| inode).collect(),
+            backend: self.backend.snapshot(),
+        }
+    }
+
+    /// Goes back to the state of `snapshot`, which must have been taken on this `WasiFs`.
+    ///
+    /// The fds opened since are closed, and the ones open then get their offset and flags back.
+    /// The files and directories looked up since are forgotten, as the restored backend may not
+    /// hold them anymore.  The files left open by the snapshot keep their handles: take it when
+    /// only stdio and the preopened directories are open.
+    pub fn reset(&mut self, snapshot: &WasiFsSnapshot) -> Result<(), WasiFsError> {
+        if let Some(backend) = &snapshot.backend {
+            self.backend.restore(backend)?;
+        }
+
+        let opened: Vec<u32> = self
+            .fd_map
+            .keys()
+            .filter(|fd| !snapshot.fds.contains_key(fd))
+            .cloned()
+            .collect();
+        for fd in opened {
+            // the fd is forgotten even if closing it failed
+            let _ = self.close_fd(fd);
+            self.fd_map.remove(&fd);
+        }
+
+        let created: Vec<Inode> = self
+            .inodes
+            .iter()
+            .map(|(inode, _)| inode)
+            .filter(|inode| !snapshot.inodes.contains(inode))
+            .collect();
+        for inode in created {
+            self.inodes.remove(inode);
+        }
+        let inodes: HashSet<Inode> = self.inodes.iter().map(|(inode, _)| inode).collect();
+        for (_, inodeval) in self.inodes.iter_mut() {
+            match &mut inodeval.kind {
+                Kind::Dir { entries, .. } | Kind::Root { entries } => {
+                    entries.retain(|_, inode| inodes.contains(inode))
+                }
+                _ => (),
+            }
+        }
+        self.name_map.retain(|_, inode| inodes.contains(inode));
+        self.orphan_fds
+            .retain(|inode, _| snapshot.inodes.contains(inode));
+
+        for (fd, entry) in &snapshot.fds {
+            if inodes.contains(&entry.inode) {
+                self.fd_map.insert(*fd, entry.clone());
+            }
+        }
+        self.preopen_fds = snapshot.preopen_fds.clone();
+        self.next_fd.set(snapshot.next_fd);
+        Ok(())
+    }
+}
+
+/// What [`WasiState::reset`] goes back to.
+#[derive(Debug)]
+pub struct WasiStateSnapshot {
+    pub fs: WasiFsSnapshot,
+    quotas: WasiQuotas,
+}
+
+impl WasiState {
+    /// Takes a snapshot of the filesystem state and of the quotas used so far.
+    pub fn snapshot(&self) -> WasiStateSnapshot {
+        WasiStateSnapshot {
+            fs: self.fs.snapshot(),
+            quotas: self.quotas.clone(),
+        }
+    }
+
+    /// Goes back to the state of `snapshot`, see [`WasiFs::reset`].
+    ///
+    /// The bytes written and the entries created are counted from the snapshot again, so each
+    /// invocation gets the whole quotas.
+    pub fn reset(&mut self, snapshot: &WasiStateSnapshot) -> Result<(), WasiFsError> {
+        self.fs.reset(&snapshot.fs)?;
+        self.quotas = snapshot.quotas.clone();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::state::{MemFs, WasiFsBackend, WasiState};
+    use crate::syscalls::types::*;
+    use std::{path::Path, sync::Arc};
+
+    #[test]
+    fn reset_closes_fds_and_restores_the_backend() {
+        let fs = MemFs::new();
+        fs.create_dir_all("/data").unwrap();
+        fs.write_file("/data/input", "hello").unwrap();
+        let mut state = WasiState::new("test_prog")
+            .fs_backend(Arc::new(fs.clone()))
+            .preopen_dir("/data")
+            .build()
+            .unwrap();
+        let snapshot = state.snapshot();
+        assert!(snapshot.fs.has_backend());
+
+        // the first one is the virtual root
+        let preopen_fd = state.fs.preopen_fds[1];
+        fs.write_file("/data/tmp", "left behind").unwrap();
+        fs.write_file("/data/input", "changed").unwrap();
+        let inode = state
+            .fs
+            .get_inode_at_path(preopen_fd, "tmp", false)
+            .unwrap();
+        let fd = state
+            .fs
+            .create_fd(__WASI_RIGHT_FD_READ, 0, 0, 0, inode)
+            .unwrap();
+
+        state.reset(&snapshot).unwrap();
+        assert_eq!(state.fs.get_fd(fd).err(), Some(__WASI_EBADF));
+        assert!(state.fs.get_fd(preopen_fd).is_ok());
+        assert!(fs.metadata(Path::new("/data/tmp")).is_err());
+        assert_eq!(fs.read_file("/data/input").unwrap(), b"hello");
+        // the fds are numbered from the snapshot again
+        let preopen_inode = state.fs.get_fd(preopen_fd).unwrap().inode;
+        assert_eq!(
+            state
+                .fs
+                .create_fd(__WASI_RIGHT_FD_READ, 0, 0, 0, preopen_inode),
+            Ok(fd)
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! TCP sockets for WASI modules.
+//!
+//! Modules can only connect to, or listen on, the addresses granted to them with
+//! `WasiStateBuilder::allow_connect` and `WasiStateBuilder::allow_listen`.  The sockets are
+//! `WasiFile`s, so reading, writing, closing and polling them goes through the usual fd syscalls.
+
+use crate::state::{host_file_bytes_available, WasiFile, WasiFsError};
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, Read, Seek, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+};
+
+/// The addresses a module may connect to and listen on.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkGrants {
+    pub(crate) connect: Vec<SocketAddr>,
+    pub(crate) listen: Vec<SocketAddr>,
+}
+
+impl NetworkGrants {
+    /// Returns whether connecting to `addr` was granted.
+    pub fn can_connect(&self, addr: &SocketAddr) -> bool {
+        self.connect.contains(addr)
+    }
+
+    /// Returns whether listening on `addr` was granted.
+    pub fn can_listen(&self, addr: &SocketAddr) -> bool {
+        self.listen.contains(addr)
+    }
+}
+
+fn not_connected() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "the socket was not restored")
+}
+
+/// A connected TCP socket.
+///
+/// Sockets aren't serialized: a `WasiState` unfrozen from bytes has its sockets closed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WasiTcpStream {
+    #[serde(skip)]
+    inner: Option<TcpStream>,
+}
+
+impl WasiTcpStream {
+    pub fn new(stream: TcpStream) -> Self {
+        Self {
+            inner: Some(stream),
+        }
+    }
+
+    fn stream(&self) -> io::Result<&TcpStream> {
+        self.inner.as_ref().ok_or_else(not_connected)
+    }
+}
+
+impl Read for WasiTcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream()?.read(buf)
+    }
+}
+impl Write for WasiTcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream()?.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream()?.flush()
+    }
+}
+impl Seek for WasiTcpStream {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not seek a socket",
+        ))
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for WasiTcpStream {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        match self.get_raw_fd() {
+            Some(host_fd) => host_file_bytes_available(host_fd),
+            None => Err(WasiFsError::NotConnected),
+        }
+    }
+
+    #[cfg(unix)]
+    fn get_raw_fd(&self) -> Option<i32> {
+        use std::os::unix::io::AsRawFd;
+        self.inner.as_ref().map(AsRawFd::as_raw_fd)
+    }
+
+    fn sock_shutdown(&mut self, how: Shutdown) -> Result<(), WasiFsError> {
+        self.stream()?.shutdown(how).map_err(Into::into)
+    }
+}
+
+/// A TCP socket listening for connections.
+///
+/// Like [`WasiTcpStream`], listeners aren't serialized.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WasiTcpListener {
+    #[serde(skip)]
+    inner: Option<TcpListener>,
+}
+
+impl WasiTcpListener {
+    pub fn new(listener: TcpListener) -> Self {
+        Self {
+            inner: Some(listener),
+        }
+    }
+}
+
+impl Read for WasiTcpListener {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::NotConnected,
+            "can not read from a listening socket",
+        ))
+    }
+}
+impl Write for WasiTcpListener {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::NotConnected,
+            "can not write to a listening socket",
+        ))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+impl Seek for WasiTcpListener {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not seek a socket",
+        ))
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for WasiTcpListener {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        // a pending connection is readable, but holds no bytes
+        Ok(0)
+    }
+
+    #[cfg(unix)]
+    fn get_raw_fd(&self) -> Option<i32> {
+        use std::os::unix::io::AsRawFd;
+        self.inner.as_ref().map(AsRawFd::as_raw_fd)
+    }
+
+    fn sock_accept(&mut self) -> Result<Box<dyn WasiFile>, WasiFsError> {
+        let listener = self.inner.as_ref().ok_or(WasiFsError::NotConnected)?;
+        let (stream, _) = listener.accept()?;
+        Ok(Box::new(WasiTcpStream::new(stream)))
+    }
+}
@@ -0,0 +1,127 @@
+//! What the `thread-spawn` call of wasi-threads needs to start a new thread.
+//!
+//! Every thread runs in its own instance of the module, created with the same imports and
+//! importing the same shared memory: the globals of the instance, such as the stack pointer and
+//! the TLS base, are per thread, while the memory is shared.  The module itself sets up the stack
+//! and TLS of the new thread in `wasi_thread_start`, from the argument it gave to `thread-spawn`.
+//!
+//! The threads also share one WASI state, so an fd opened by one thread is visible to the others.
+//! A syscall holds the lock of the state while it runs, so a blocking one, like `poll_oneoff`
+//! waiting on a clock, holds up the syscalls of the other threads.
+
+use crate::state::WasiState;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc, Condvar, Mutex,
+};
+use std::thread::{self, ThreadId};
+use wasmer_runtime_core::Module;
+
+/// The largest thread id allowed by wasi-threads.
+const MAX_THREAD_ID: u32 = 0x1FFF_FFFF;
+
+/// Spawns the threads of the instances of a module.
+///
+/// It's shared by all the threads of the module, and so are the ids it gives out and the lock of
+/// their WASI state.
+pub(crate) struct WasiThreads {
+    pub(crate) module: Module,
+    next_id: AtomicU32,
+    /// The thread holding the lock of the WASI state, and how many times it took it
+    state_owner: Mutex<Option<(ThreadId, usize)>>,
+    state_released: Condvar,
+}
+
+impl WasiThreads {
+    pub(crate) fn new(module: Module) -> Self {
+        Self {
+            module,
+            // the id 0 isn't valid, the main thread doesn't have one
+            next_id: AtomicU32::new(1),
+            state_owner: Mutex::new(None),
+            state_released: Condvar::new(),
+        }
+    }
+
+    /// Returns a new thread id, or `None` if all of them were given out.
+    pub(crate) fn next_id(&self) -> Option<u32> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        if id <= MAX_THREAD_ID {
+            Some(id)
+        } else {
+            None
+        }
+    }
+
+    /// Locks the WASI state, waiting for the thread holding it to release it.  The thread holding
+    /// it may lock it again, e.g. from a syscall calling another.
+    fn lock_state(&self) {
+        let current = thread::current().id();
+        let mut owner = self.state_owner.lock().unwrap();
+        loop {
+            match *owner {
+                Some((id, ref mut count)) if id == current => {
+                    *count += 1;
+                    return;
+                }
+                None => {
+                    *owner = Some((current, 1));
+                    return;
+                }
+                Some(_) => {}
+            }
+            owner = self.state_released.wait(owner).unwrap();
+        }
+    }
+
+    /// Releases the WASI state once it's been unlocked as many times as it was locked.
+    fn unlock_state(&self) {
+        let mut owner = self.state_owner.lock().unwrap();
+        if let Some((_, count)) = &mut *owner {
+            *count -= 1;
+            if *count > 0 {
+                return;
+            }
+        }
+        *owner = None;
+        self.state_released.notify_one();
+    }
+}
+
+impl std::fmt::Debug for WasiThreads {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasiThreads")
+            .field("next_id", &self.next_id)
+            .field("state_owner", &self.state_owner)
+            .finish()
+    }
+}
+
+/// Holds the lock of a WASI state shared by threads, if the state is shared, until it's dropped.
+pub(crate) struct StateLock(Option<Arc<WasiThreads>>);
+
+impl StateLock {
+    pub(crate) fn new(threads: Option<Arc<WasiThreads>>) -> Self {
+        if let Some(threads) = &threads {
+            threads.lock_state();
+        }
+        StateLock(threads)
+    }
+}
+
+impl Drop for StateLock {
+    fn drop(&mut self) {
+        if let Some(threads) = &self.0 {
+            threads.unlock_state();
+        }
+    }
+}
+
+/// The WASI state shared by the instances of the threads of a module, which their contexts point
+/// to.
+#[derive(Clone)]
+pub(crate) struct SharedState(pub(crate) Arc<WasiState>);
+
+// The state is only used by the syscalls, which hold the lock of its `WasiThreads`.
+unsafe impl Send for SharedState {}
+unsafe impl Sync for SharedState {}
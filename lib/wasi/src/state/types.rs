@@ -1,4 +1,5 @@
 /// types for use in the WASI filesystem
+use crate::state::AsyncIo;
 use crate::syscalls::types::*;
 use serde::{de, Deserialize, Serialize};
 #[cfg(unix)]
@@ -7,6 +8,7 @@ use std::{
     fs,
     io::{self, Read, Seek, Write},
     path::PathBuf,
+    sync::{Arc, Mutex},
     time::SystemTime,
 };
 use wasmer_runtime_core::debug;
@@ -47,6 +49,8 @@ pub enum WasiFsError {
     InvalidInput,
     /// Could not perform the operation because there was not an open connection
     NotConnected,
+    /// The operation is only possible on sockets
+    NotASocket,
     /// The requested file or directory could not be found
     EntityNotFound,
     /// The requested device couldn't be accessed
@@ -82,6 +86,7 @@ impl WasiFsError {
             __WASI_EINTR => WasiFsError::Interrupted,
             __WASI_EINVAL => WasiFsError::InvalidInput,
             __WASI_ENOTCONN => WasiFsError::NotConnected,
+            __WASI_ENOTSOCK => WasiFsError::NotASocket,
             __WASI_ENODEV => WasiFsError::NoDevice,
             __WASI_ENOENT => WasiFsError::EntityNotFound,
             __WASI_EPERM => WasiFsError::PermissionDenied,
@@ -111,6 +116,7 @@ impl WasiFsError {
             WasiFsError::NoDevice => __WASI_ENODEV,
             WasiFsError::NotAFile => __WASI_EINVAL,
             WasiFsError::NotConnected => __WASI_ENOTCONN,
+            WasiFsError::NotASocket => __WASI_ENOTSOCK,
             WasiFsError::EntityNotFound => __WASI_ENOENT,
             WasiFsError::PermissionDenied => __WASI_EPERM,
             WasiFsError::TimedOut => __WASI_ETIMEDOUT,
@@ -184,6 +190,23 @@ pub trait WasiFile: std::fmt::Debug + Write + Read + Seek {
     fn get_raw_fd(&self) -> Option<i32> {
         None
     }
+
+    /// Returns the object doing the I/O of this file if it's an `AsyncFile`, in which case it's
+    /// polled on the reactor of that object.  Default returns `None`
+    fn async_io(&self) -> Option<Arc<dyn AsyncIo>> {
+        None
+    }
+
+    /// Accepts a connection if this is a listening socket.  Default returns `NotASocket`
+    fn sock_accept(&mut self) -> Result<Box<dyn WasiFile>, WasiFsError> {
+        Err(WasiFsError::NotASocket)
+    }
+
+    /// Shuts down the reading and/or writing halves if this is a connected socket.
+    /// Default returns `NotASocket`
+    fn sock_shutdown(&mut self, _how: std::net::Shutdown) -> Result<(), WasiFsError> {
+        Err(WasiFsError::NotASocket)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -300,37 +323,63 @@ impl PollEventBuilder {
     }
 }
 
+/// Waits until one of `selfs` is ready for its `events`, or until `timeout` has passed if
+/// it's given, and writes the events seen into `seen_events`.
+///
+/// Files without a host fd, which can't be polled, are always ready for their events.
+/// Returns the number of files ready.
 #[cfg(unix)]
 pub(crate) fn poll(
     selfs: &[&dyn WasiFile],
     events: &[PollEventSet],
     seen_events: &mut [PollEventSet],
+    timeout: Option<std::time::Duration>,
 ) -> Result<u32, WasiFsError> {
     if !(selfs.len() == events.len() && events.len() == seen_events.len()) {
         return Err(WasiFsError::InvalidInput);
     }
-    let mut fds = selfs
-        .iter()
-        .enumerate()
-        .filter_map(|(i, s)| s.get_raw_fd().map(|rfd| (i, rfd)))
-        .map(|(i, host_fd)| libc::pollfd {
-            fd: host_fd,
-            events: poll_event_set_to_platform_poll_events(events[i]),
-            revents: 0,
-        })
-        .collect::<Vec<_>>();
-    let result = unsafe { libc::poll(fds.as_mut_ptr(), selfs.len() as _, 1) };
+    let mut always_ready = 0;
+    let mut polled = vec![];
+    let mut fds = vec![];
+    for (i, s) in selfs.iter().enumerate() {
+        match s.get_raw_fd() {
+            Some(host_fd) => {
+                polled.push(i);
+                fds.push(libc::pollfd {
+                    fd: host_fd,
+                    events: poll_event_set_to_platform_poll_events(events[i]),
+                    revents: 0,
+                });
+            }
+            None => {
+                seen_events[i] = events[i];
+                always_ready += 1;
+            }
+        }
+    }
+    let timeout_ms = match timeout {
+        _ if always_ready > 0 => 0,
+        // round up, so that the timeout has passed when `poll` times out
+        Some(timeout) => {
+            let ms = (timeout.as_nanos() + 999_999) / 1_000_000;
+            ms.min(libc::c_int::max_value() as u128) as libc::c_int
+        }
+        None => -1,
+    };
+    let result = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, timeout_ms) };
 
     if result < 0 {
-        // TODO: check errno and return value
-        return Err(WasiFsError::IOError);
+        return Err(match io::Error::last_os_error().kind() {
+            io::ErrorKind::Interrupted => WasiFsError::Interrupted,
+            _ => WasiFsError::IOError,
+        });
     }
     // convert result and write back values
-    for (i, fd) in fds.into_iter().enumerate() {
+    for (i, fd) in polled.into_iter().zip(fds.into_iter()) {
         seen_events[i] = platform_poll_events_to_pollevent_set(fd.revents);
     }
-    // unwrap is safe because we check for negative values above
-    Ok(result.try_into().unwrap())
+    // the cast is safe because we check for negative values above
+    Ok(always_ready + result as u32)
 }
 
 #[cfg(not(unix))]
@@ -338,7 +387,8 @@ pub(crate) fn poll(
     _selfs: &[&dyn WasiFile],
     _events: &[PollEventSet],
     _seen_events: &mut [PollEventSet],
-) -> Result<(), WasiFsError> {
+    _timeout: Option<std::time::Duration>,
+) -> Result<u32, WasiFsError> {
     unimplemented!("HostFile::poll in WasiFile is not implemented for non-Unix-like targets yet");
 }
 
@@ -611,7 +661,7 @@ impl From<io::Error> for WasiFsError {
 }
 
 #[cfg(unix)]
-fn host_file_bytes_available(host_fd: i32) -> Result<usize, WasiFsError> {
+pub(crate) fn host_file_bytes_available(host_fd: i32) -> Result<usize, WasiFsError> {
     let mut bytes_found = 0 as libc::c_int;
     let result = unsafe { libc::ioctl(host_fd, libc::FIONREAD, &mut bytes_found) };
 
@@ -626,7 +676,7 @@ fn host_file_bytes_available(host_fd: i32) -> Result<usize, WasiFsError> {
 }
 
 #[cfg(not(unix))]
-fn host_file_bytes_available(_raw_fd: i32) -> Result<usize, WasiFsError> {
+pub(crate) fn host_file_bytes_available(_raw_fd: i32) -> Result<usize, WasiFsError> {
     unimplemented!("host_file_bytes_available not yet implemented for non-Unix-like targets.  This probably means the program tried to use wasi::poll_oneoff")
 }
 
@@ -910,6 +960,216 @@ impl WasiFile for Stdin {
     }
 }
 
+/// A `WasiFile` reading from a reader shared by its clones, used to redirect stdin.
+///
+/// The reader isn't serialized: a `WasiState` unfrozen from bytes reads nothing from it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReadStream {
+    #[serde(skip, default = "empty_reader")]
+    reader: Arc<Mutex<dyn Read + Send>>,
+}
+
+fn empty_reader() -> Arc<Mutex<dyn Read + Send>> {
+    Arc::new(Mutex::new(io::empty()))
+}
+
+impl ReadStream {
+    /// Creates a `ReadStream` reading from `reader`.
+    pub fn new<R: Read + Send + 'static>(reader: R) -> Self {
+        ReadStream {
+            reader: Arc::new(Mutex::new(reader)),
+        }
+    }
+}
+
+/// Streams are equal when they are clones of each other.
+impl PartialEq for ReadStream {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.reader, &other.reader)
+    }
+}
+
+impl std::fmt::Debug for ReadStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ReadStream")
+    }
+}
+
+impl Read for ReadStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.lock().unwrap().read(buf)
+    }
+}
+impl Seek for ReadStream {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not seek a stream",
+        ))
+    }
+}
+impl Write for ReadStream {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not write to an input stream",
+        ))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not write to an input stream",
+        ))
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for ReadStream {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        // readers don't tell how much they hold without blocking
+        Ok(0)
+    }
+}
+
+/// A `WasiFile` writing to a writer shared by its clones, used to redirect stdout and stderr.
+///
+/// The writer isn't serialized: a `WasiState` unfrozen from bytes discards what is written.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WriteStream {
+    #[serde(skip, default = "sink_writer")]
+    writer: Arc<Mutex<dyn Write + Send>>,
+}
+
+fn sink_writer() -> Arc<Mutex<dyn Write + Send>> {
+    Arc::new(Mutex::new(io::sink()))
+}
+
+impl WriteStream {
+    /// Creates a `WriteStream` writing to `writer`.
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        WriteStream {
+            writer: Arc::new(Mutex::new(writer)),
+        }
+    }
+}
+
+/// Streams are equal when they are clones of each other.
+impl PartialEq for WriteStream {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.writer, &other.writer)
+    }
+}
+
+impl std::fmt::Debug for WriteStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("WriteStream")
+    }
+}
+
+impl Read for WriteStream {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not read from an output stream",
+        ))
+    }
+}
+impl Seek for WriteStream {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not seek a stream",
+        ))
+    }
+}
+impl Write for WriteStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.lock().unwrap().flush()
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for WriteStream {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        Ok(0)
+    }
+}
+
+/// A buffer capturing what is written to it, shared by its clones.
+///
+/// Give a clone to `WasiStateBuilder::stdout` or `WasiStateBuilder::stderr`, and read the
+/// output of the module from the original.
+#[derive(Debug, Clone, Default)]
+pub struct OutputBuffer {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl OutputBuffer {
+    /// Creates an empty buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a copy of what has been written so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.buffer.lock().unwrap().clone()
+    }
+
+    /// Returns what has been written so far, emptying the buffer.
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::replace(&mut *self.buffer.lock().unwrap(), vec![])
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 /*
 TODO: Think about using this
 trait WasiFdBacking: std::fmt::Debug {
@@ -1,4 +1,7 @@
 #![allow(unused)]
+pub mod network;
+pub mod snapshot1;
+pub mod threads;
 pub mod types;
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 pub mod unix;
@@ -9,11 +12,11 @@ use self::types::*;
 use crate::{
     ptr::{Array, WasmPtr},
     state::{
-        self, host_file_type_to_wasi_file_type, iterate_poll_events, poll, Fd, HostFile, Inode,
-        InodeVal, Kind, PollEvent, PollEventBuilder, WasiFile, WasiFsError, WasiState,
-        MAX_SYMLINKS,
+        self, iterate_poll_events, poll, poll_async, Fd, FsAccess, FsOperation, Inode, InodeVal,
+        Kind, OpenOptions, PollEvent, PollEventBuilder, StateLock, WasiFile, WasiFsError,
+        WasiState, MAX_SYMLINKS,
     },
-    ExitCode,
+    WasiError,
 };
 use std::borrow::Borrow;
 use std::cell::Cell;
@@ -27,10 +30,15 @@ pub use unix::*;
 #[cfg(any(target_os = "windows"))]
 pub use windows::*;
 
+/// Returns the WASI state of `ctx`, with the lock to hold while using it.  The lock is only
+/// taken for the state shared by the threads of a module using wasi-threads.
+///
 /// This function is not safe
 #[allow(clippy::mut_from_ref)]
-pub(crate) fn get_wasi_state(ctx: &Ctx) -> &mut WasiState {
-    unsafe { state::get_wasi_state(&mut *(ctx as *const Ctx as *mut Ctx)) }
+pub(crate) fn get_wasi_state(ctx: &Ctx) -> (&mut WasiState, StateLock) {
+    let state = unsafe { state::get_wasi_state(&mut *(ctx as *const Ctx as *mut Ctx)) };
+    let lock = StateLock::new(state.threads.clone());
+    (state, lock)
 }
 
 fn write_bytes_inner<T: Write>(
@@ -62,6 +70,14 @@ fn write_bytes<T: Write>(
     result
 }
 
+/// The number of bytes the buffers of `iovs_arr_cell` hold together.
+fn ciovecs_len(iovs_arr_cell: &[Cell<__wasi_ciovec_t>]) -> u64 {
+    iovs_arr_cell
+        .iter()
+        .map(|iov| iov.get().buf_len as u64)
+        .sum()
+}
+
 fn read_bytes<T: Read>(
     mut reader: T,
     memory: &Memory,
@@ -84,6 +100,44 @@ fn has_rights(rights_set: __wasi_rights_t, rights_check_set: __wasi_rights_t) ->
     rights_set | rights_check_set == rights_set
 }
 
+/// Asks the access hook of `state`, if it has one, whether `operation` may touch `path`, a path
+/// of the backend reached through an fd with `rights`.
+fn check_fs_access(
+    state: &WasiState,
+    path: &std::path::Path,
+    operation: FsOperation,
+    rights: __wasi_rights_t,
+) -> Result<(), __wasi_errno_t> {
+    let access = FsAccess {
+        path,
+        operation,
+        rights,
+    };
+    match &state.fs_access_hook {
+        Some(hook) if !hook.check(&access) => {
+            debug!("=> access denied: {:?}", access);
+            Err(__WASI_EACCES)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Like `check_fs_access`, for the path of `inode` in the backend; the inodes without one,
+/// like the virtual root, don't touch the backend.
+fn check_inode_access(
+    state: &WasiState,
+    inode: Inode,
+    operation: FsOperation,
+    rights: __wasi_rights_t,
+) -> Result<(), __wasi_errno_t> {
+    match &state.fs.inodes[inode].kind {
+        Kind::File { path, .. } | Kind::Dir { path, .. } => {
+            check_fs_access(state, path, operation, rights)
+        }
+        _ => Ok(()),
+    }
+}
+
 #[must_use]
 fn write_buffer_array(
     memory: &Memory,
@@ -132,7 +186,7 @@ pub fn args_get(
     argv_buf: WasmPtr<u8, Array>,
 ) -> __wasi_errno_t {
     debug!("wasi::args_get");
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let memory = ctx.memory(0);
 
     let result = write_buffer_array(memory, &*state.args, argv, argv_buf);
@@ -173,7 +227,7 @@ pub fn args_sizes_get(
     let argc = wasi_try!(argc.deref(memory));
     let argv_buf_size = wasi_try!(argv_buf_size.deref(memory));
 
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
 
     let argc_val = state.args.len() as u32;
     let argv_buf_size_val = state.args.iter().map(|v| v.len() as u32 + 1).sum();
@@ -199,10 +253,15 @@ pub fn clock_res_get(
     resolution: WasmPtr<__wasi_timestamp_t>,
 ) -> __wasi_errno_t {
     debug!("wasi::clock_res_get");
+    let (state, _lock) = get_wasi_state(ctx);
+    if state.deterministic && !state.clock.is_deterministic() {
+        return __WASI_ENOTCAPABLE;
+    }
     let memory = ctx.memory(0);
 
     let out_addr = wasi_try!(resolution.deref(memory));
-    platform_clock_res_get(clock_id, out_addr)
+    out_addr.set(wasi_try!(state.clock.res_get(clock_id)));
+    __WASI_ESUCCESS
 }
 
 /// ### `clock_time_get()`
@@ -225,16 +284,16 @@ pub fn clock_time_get(
         "wasi::clock_time_get clock_id: {}, precision: {}",
         clock_id, precision
     );
+    let (state, _lock) = get_wasi_state(ctx);
+    if state.deterministic && !state.clock.is_deterministic() {
+        return __WASI_ENOTCAPABLE;
+    }
     let memory = ctx.memory(0);
 
     let out_addr = wasi_try!(time.deref(memory));
-    let result = platform_clock_time_get(clock_id, precision, out_addr);
-    debug!(
-        "time: {} => {}",
-        wasi_try!(time.deref(memory)).get(),
-        result
-    );
-    result
+    out_addr.set(wasi_try!(state.clock.time_get(clock_id, precision)));
+    debug!("time: {}", out_addr.get());
+    __WASI_ESUCCESS
 }
 
 /// ### `environ_get()`
@@ -251,7 +310,7 @@ pub fn environ_get(
     environ_buf: WasmPtr<u8, Array>,
 ) -> __wasi_errno_t {
     debug!("wasi::environ_get");
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let memory = ctx.memory(0);
 
     write_buffer_array(memory, &*state.envs, environ, environ_buf)
@@ -275,7 +334,7 @@ pub fn environ_sizes_get(
     let environ_count = wasi_try!(environ_count.deref(memory));
     let environ_buf_size = wasi_try!(environ_buf_size.deref(memory));
 
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
 
     let env_var_count = state.envs.len() as u32;
     let env_buf_size = state.envs.iter().map(|v| v.len() as u32 + 1).sum();
@@ -332,7 +391,7 @@ pub fn fd_allocate(
 ) -> __wasi_errno_t {
     debug!("wasi::fd_allocate");
     let memory = ctx.memory(0);
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let fd_entry = wasi_try!(state.fs.get_fd(fd)).clone();
     let inode = fd_entry.inode;
 
@@ -374,7 +433,7 @@ pub fn fd_allocate(
 pub fn fd_close(ctx: &mut Ctx, fd: __wasi_fd_t) -> __wasi_errno_t {
     debug!("wasi::fd_close");
     debug!("=> fd={}", fd);
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
 
     let fd_entry = wasi_try!(state.fs.get_fd(fd)).clone();
 
@@ -390,7 +449,7 @@ pub fn fd_close(ctx: &mut Ctx, fd: __wasi_fd_t) -> __wasi_errno_t {
 ///     The file descriptor to sync
 pub fn fd_datasync(ctx: &mut Ctx, fd: __wasi_fd_t) -> __wasi_errno_t {
     debug!("wasi::fd_datasync");
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let fd_entry = wasi_try!(state.fs.get_fd(fd)).clone();
     if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_DATASYNC) {
         return __WASI_EACCES;
@@ -421,7 +480,7 @@ pub fn fd_fdstat_get(
         fd,
         buf_ptr.offset()
     );
-    let mut state = get_wasi_state(ctx);
+    let (mut state, _lock) = get_wasi_state(ctx);
     let memory = ctx.memory(0);
     let fd_entry = wasi_try!(state.fs.get_fd(fd)).clone();
 
@@ -446,7 +505,7 @@ pub fn fd_fdstat_set_flags(
     flags: __wasi_fdflags_t,
 ) -> __wasi_errno_t {
     debug!("wasi::fd_fdstat_set_flags");
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let fd_entry = wasi_try!(state.fs.fd_map.get_mut(&fd).ok_or(__WASI_EBADF));
 
     if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_FDSTAT_SET_FLAGS) {
@@ -473,7 +532,7 @@ pub fn fd_fdstat_set_rights(
     fs_rights_inheriting: __wasi_rights_t,
 ) -> __wasi_errno_t {
     debug!("wasi::fd_fdstat_set_rights");
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let fd_entry = wasi_try!(state.fs.fd_map.get_mut(&fd).ok_or(__WASI_EBADF));
 
     // ensure new rights are a subset of current rights
@@ -503,14 +562,8 @@ pub fn fd_filestat_get(
     buf: WasmPtr<__wasi_filestat_t>,
 ) -> __wasi_errno_t {
     debug!("wasi::fd_filestat_get");
-    let mut state = get_wasi_state(ctx);
+    let stat = wasi_try!(fd_filestat_get_internal(ctx, fd));
     let memory = ctx.memory(0);
-    let fd_entry = wasi_try!(state.fs.get_fd(fd));
-    if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_FILESTAT_GET) {
-        return __WASI_EACCES;
-    }
-
-    let stat = wasi_try!(state.fs.filestat_fd(fd));
 
     let buf = wasi_try!(buf.deref(memory));
     buf.set(stat);
@@ -518,6 +571,20 @@ pub fn fd_filestat_get(
     __WASI_ESUCCESS
 }
 
+/// Looks up the stat of `fd`, shared by the syscalls of all the WASI versions.
+pub(crate) fn fd_filestat_get_internal(
+    ctx: &mut Ctx,
+    fd: __wasi_fd_t,
+) -> Result<__wasi_filestat_t, __wasi_errno_t> {
+    let (state, _lock) = get_wasi_state(ctx);
+    let fd_entry = state.fs.get_fd(fd)?;
+    if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_FILESTAT_GET) {
+        return Err(__WASI_EACCES);
+    }
+
+    state.fs.filestat_fd(fd)
+}
+
 /// ### `fd_filestat_set_size()`
 /// Change the size of an open file, zeroing out any new bytes
 /// Inputs:
@@ -532,7 +599,7 @@ pub fn fd_filestat_set_size(
 ) -> __wasi_errno_t {
     debug!("wasi::fd_filestat_set_size");
     let memory = ctx.memory(0);
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let fd_entry = wasi_try!(state.fs.get_fd(fd)).clone();
     let inode = fd_entry.inode;
 
@@ -576,7 +643,7 @@ pub fn fd_filestat_set_times(
     fst_flags: __wasi_fstflags_t,
 ) -> __wasi_errno_t {
     debug!("wasi::fd_filestat_set_times");
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let fd_entry = wasi_try!(state.fs.fd_map.get_mut(&fd).ok_or(__WASI_EBADF));
 
     if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_FILESTAT_SET_TIMES) {
@@ -659,7 +726,7 @@ pub fn fd_pread(
 
     let iov_cells = wasi_try!(iovs.deref(memory, 0, iovs_len));
     let nread_cell = wasi_try!(nread.deref(memory));
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
 
     let bytes_read = match fd {
         __WASI_STDIN_FILENO => {
@@ -726,7 +793,7 @@ pub fn fd_prestat_get(
 
     let prestat_ptr = wasi_try!(buf.deref(memory));
 
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     prestat_ptr.set(wasi_try!(state.fs.prestat_fd(fd)));
 
     __WASI_ESUCCESS
@@ -745,7 +812,7 @@ pub fn fd_prestat_dir_name(
     let memory = ctx.memory(0);
     let path_chars = wasi_try!(path.deref(memory, 0, path_len));
 
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let real_fd = wasi_try!(state.fs.fd_map.get(&fd).ok_or(__WASI_EBADF));
     let inode_val = &state.fs.inodes[real_fd.inode];
 
@@ -806,7 +873,8 @@ pub fn fd_pwrite(
     let memory = ctx.memory(0);
     let iovs_arr_cell = wasi_try!(iovs.deref(memory, 0, iovs_len));
     let nwritten_cell = wasi_try!(nwritten.deref(memory));
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
+    wasi_try!(state.quotas.check_write(ciovecs_len(iovs_arr_cell)));
 
     let bytes_written = match fd {
         __WASI_STDIN_FILENO => return __WASI_EINVAL,
@@ -864,6 +932,7 @@ pub fn fd_pwrite(
         }
     };
 
+    state.quotas.record_write(bytes_written as u64);
     nwritten_cell.set(bytes_written);
 
     __WASI_ESUCCESS
@@ -893,7 +962,7 @@ pub fn fd_read(
 
     let iovs_arr_cell = wasi_try!(iovs.deref(memory, 0, iovs_len));
     let nread_cell = wasi_try!(nread.deref(memory));
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
 
     let bytes_read = match fd {
         __WASI_STDIN_FILENO => {
@@ -972,13 +1041,19 @@ pub fn fd_readdir(
 ) -> __wasi_errno_t {
     debug!("wasi::fd_readdir");
     let memory = ctx.memory(0);
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     // TODO: figure out how this is supposed to work;
     // is it supposed to pack the buffer full every time until it can't? or do one at a time?
 
     let buf_arr_cell = wasi_try!(buf.deref(memory, 0, buf_len));
     let bufused_cell = wasi_try!(bufused.deref(memory));
     let working_dir = wasi_try!(state.fs.fd_map.get(&fd).ok_or(__WASI_EBADF));
+    wasi_try!(check_inode_access(
+        state,
+        working_dir.inode,
+        FsOperation::ReadDirectory,
+        working_dir.rights
+    ));
     let mut cur_cookie = cookie;
     let mut buf_idx = 0;
 
@@ -988,18 +1063,18 @@ pub fn fd_readdir(
             // we need to support multiple calls,
             // simple and obviously correct implementation for now:
             // maintain consistent order via lexacographic sorting
-            let mut entries = wasi_try!(wasi_try!(std::fs::read_dir(path).map_err(|_| __WASI_EIO))
-                .collect::<Result<Vec<std::fs::DirEntry>, _>>()
-                .map_err(|_| __WASI_EIO));
-            entries.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
-            wasi_try!(entries
+            let mut entries = wasi_try!(state.fs.backend.read_dir(path).map_err(|_| __WASI_EIO));
+            entries.sort_by(|a, b| a.name.cmp(&b.name));
+            entries
                 .into_iter()
-                .map(|entry| Ok((
-                    entry.file_name().to_string_lossy().to_string(),
-                    host_file_type_to_wasi_file_type(entry.file_type().map_err(|_| __WASI_EIO)?),
-                    0, // TODO: inode
-                )))
-                .collect::<Result<Vec<(String, u8, u64)>, __wasi_errno_t>>())
+                .map(|entry| {
+                    (
+                        entry.name,
+                        entry.file_type,
+                        0, // TODO: inode
+                    )
+                })
+                .collect::<Vec<(String, u8, u64)>>()
         }
         Kind::Root { entries } => {
             let sorted_entries = {
@@ -1068,7 +1143,7 @@ pub fn fd_readdir(
 ///     Location to copy file descriptor to
 pub fn fd_renumber(ctx: &mut Ctx, from: __wasi_fd_t, to: __wasi_fd_t) -> __wasi_errno_t {
     debug!("wasi::fd_renumber: from={}, to={}", from, to);
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let fd_entry = wasi_try!(state.fs.fd_map.get(&from).ok_or(__WASI_EBADF));
     let new_fd_entry = Fd {
         // TODO: verify this is correct
@@ -1102,7 +1177,7 @@ pub fn fd_seek(
 ) -> __wasi_errno_t {
     debug!("wasi::fd_seek: fd={}, offset={}", fd, offset);
     let memory = ctx.memory(0);
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let new_offset_cell = wasi_try!(newoffset.deref(memory));
 
     let fd_entry = wasi_try!(state.fs.fd_map.get_mut(&fd).ok_or(__WASI_EBADF));
@@ -1162,7 +1237,7 @@ pub fn fd_sync(ctx: &mut Ctx, fd: __wasi_fd_t) -> __wasi_errno_t {
     debug!("wasi::fd_sync");
     debug!("=> fd={}", fd);
     let memory = ctx.memory(0);
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let fd_entry = wasi_try!(state.fs.get_fd(fd));
     if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_SYNC) {
         return __WASI_EACCES;
@@ -1200,7 +1275,7 @@ pub fn fd_tell(
 ) -> __wasi_errno_t {
     debug!("wasi::fd_tell");
     let memory = ctx.memory(0);
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let offset_cell = wasi_try!(offset.deref(memory));
 
     let fd_entry = wasi_try!(state.fs.fd_map.get_mut(&fd).ok_or(__WASI_EBADF));
@@ -1239,7 +1314,8 @@ pub fn fd_write(
     let memory = ctx.memory(0);
     let iovs_arr_cell = wasi_try!(iovs.deref(memory, 0, iovs_len));
     let nwritten_cell = wasi_try!(nwritten.deref(memory));
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
+    wasi_try!(state.quotas.check_write(ciovecs_len(iovs_arr_cell)));
 
     let bytes_written = match fd {
         __WASI_STDIN_FILENO => return __WASI_EINVAL,
@@ -1262,7 +1338,7 @@ pub fn fd_write(
             }
         }
         _ => {
-            let state = get_wasi_state(ctx);
+            let (state, _lock) = get_wasi_state(ctx);
             let fd_entry = wasi_try!(state.fs.fd_map.get_mut(&fd).ok_or(__WASI_EBADF));
 
             if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_WRITE) {
@@ -1298,6 +1374,7 @@ pub fn fd_write(
         }
     };
 
+    state.quotas.record_write(bytes_written as u64);
     nwritten_cell.set(bytes_written);
 
     __WASI_ESUCCESS
@@ -1324,7 +1401,7 @@ pub fn path_create_directory(
 ) -> __wasi_errno_t {
     debug!("wasi::path_create_directory");
     let memory = ctx.memory(0);
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
 
     let working_dir = wasi_try!(state.fs.get_fd(fd)).clone();
     if let Kind::Root { .. } = &state.fs.inodes[working_dir.inode].kind {
@@ -1377,10 +1454,26 @@ pub fn path_create_directory(
                     let mut adjusted_path = path.clone();
                     // TODO: double check this doesn't risk breaking the sandbox
                     adjusted_path.push(comp);
-                    if adjusted_path.exists() && !adjusted_path.is_dir() {
-                        return __WASI_ENOTDIR;
-                    } else if !adjusted_path.exists() {
-                        wasi_try!(std::fs::create_dir(&adjusted_path).ok(), __WASI_EIO);
+                    match state.fs.backend.metadata(&adjusted_path) {
+                        Ok(stat) if stat.st_filetype != __WASI_FILETYPE_DIRECTORY => {
+                            return __WASI_ENOTDIR;
+                        }
+                        Ok(_) => (),
+                        Err(_) => {
+                            wasi_try!(state.quotas.check_create_entry());
+                            wasi_try!(check_fs_access(
+                                state,
+                                &adjusted_path,
+                                FsOperation::CreateDirectory,
+                                working_dir.rights
+                            ));
+                            wasi_try!(state
+                                .fs
+                                .backend
+                                .create_dir(&adjusted_path)
+                                .map_err(|_| __WASI_EIO));
+                            state.quotas.record_create_entry();
+                        }
                     }
                     let kind = Kind::Dir {
                         parent: Some(cur_dir_inode),
@@ -1429,32 +1522,47 @@ pub fn path_filestat_get(
     buf: WasmPtr<__wasi_filestat_t>,
 ) -> __wasi_errno_t {
     debug!("wasi::path_filestat_get");
-    let state = get_wasi_state(ctx);
+    let stat = wasi_try!(path_filestat_get_internal(ctx, fd, flags, path, path_len));
     let memory = ctx.memory(0);
 
-    let root_dir = wasi_try!(state.fs.get_fd(fd));
+    let buf_cell = wasi_try!(buf.deref(memory));
+    buf_cell.set(stat);
 
-    if !has_rights(root_dir.rights, __WASI_RIGHT_PATH_FILESTAT_GET) {
-        return __WASI_EACCES;
+    __WASI_ESUCCESS
+}
+
+/// Looks up the stat of `path`, shared by the syscalls of all the WASI versions.
+pub(crate) fn path_filestat_get_internal(
+    ctx: &mut Ctx,
+    fd: __wasi_fd_t,
+    flags: __wasi_lookupflags_t,
+    path: WasmPtr<u8, Array>,
+    path_len: u32,
+) -> Result<__wasi_filestat_t, __wasi_errno_t> {
+    let (state, _lock) = get_wasi_state(ctx);
+    let memory = ctx.memory(0);
+
+    let root_dir = state.fs.get_fd(fd)?;
+    let root_dir_rights = root_dir.rights;
+
+    if !has_rights(root_dir_rights, __WASI_RIGHT_PATH_FILESTAT_GET) {
+        return Err(__WASI_EACCES);
     }
-    let path_string = get_input_str!(memory, path, path_len);
+    let path_string = path
+        .get_utf8_string(memory, path_len)
+        .ok_or(__WASI_EINVAL)?;
 
     debug!("=> base_fd: {}, path: {}", fd, &path_string);
 
-    let file_inode = wasi_try!(state.fs.get_inode_at_path(
-        fd,
-        path_string,
-        flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
-    ));
-    let stat = wasi_try!(state
+    let file_inode =
+        state
+            .fs
+            .get_inode_at_path(fd, path_string, flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0)?;
+    check_inode_access(state, file_inode, FsOperation::Stat, root_dir_rights)?;
+    state
         .fs
         .get_stat_for_kind(&state.fs.inodes[file_inode].kind)
-        .ok_or(__WASI_EIO));
-
-    let buf_cell = wasi_try!(buf.deref(memory));
-    buf_cell.set(stat);
-
-    __WASI_ESUCCESS
+        .ok_or(__WASI_EIO)
 }
 
 /// ### `path_filestat_set_times()`
@@ -1486,7 +1594,7 @@ pub fn path_filestat_set_times(
 ) -> __wasi_errno_t {
     debug!("wasi::path_filestat_set_times");
     let memory = ctx.memory(0);
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let fd_entry = wasi_try!(state.fs.get_fd(fd)).clone();
     let fd_inode = fd_entry.inode;
     if !has_rights(fd_entry.rights, __WASI_RIGHT_PATH_FILESTAT_SET_TIMES) {
@@ -1507,6 +1615,12 @@ pub fn path_filestat_set_times(
         path_string,
         flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
     ));
+    wasi_try!(check_inode_access(
+        state,
+        file_inode,
+        FsOperation::SetTimes,
+        fd_entry.rights
+    ));
     let stat = wasi_try!(state
         .fs
         .get_stat_for_kind(&state.fs.inodes[file_inode].kind)
@@ -1584,7 +1698,7 @@ pub fn path_link(
         debug!("  - will follow symlinks when opening path");
     }
     let memory = ctx.memory(0);
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let old_path_str = get_input_str!(memory, old_path, old_path_len);
     let new_path_str = get_input_str!(memory, new_path, new_path_len);
     let source_fd = wasi_try!(state.fs.get_fd(old_fd));
@@ -1614,6 +1728,7 @@ pub fn path_link(
     if state.fs.inodes[source_inode].stat.st_nlink == __wasi_linkcount_t::max_value() {
         return __WASI_EMLINK;
     }
+    wasi_try!(state.quotas.check_create_entry());
     match &mut state.fs.inodes[target_parent_inode].kind {
         Kind::Dir { entries, .. } => {
             if entries.contains_key(&new_entry_name) {
@@ -1625,6 +1740,7 @@ pub fn path_link(
         Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } => return __WASI_ENOTDIR,
     }
     state.fs.inodes[source_inode].stat.st_nlink += 1;
+    state.quotas.record_create_entry();
 
     __WASI_ESUCCESS
 }
@@ -1676,7 +1792,7 @@ pub fn path_open(
     }
 
     let fd_cell = wasi_try!(fd.deref(memory));
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
 
     // o_flags:
     // - __WASI_O_CREAT (create if it does not exist)
@@ -1685,10 +1801,18 @@ pub fn path_open(
     // - __WASI_O_TRUNC (truncate size to 0)
 
     let working_dir = wasi_try!(state.fs.get_fd(dirfd));
+    let working_dir_rights = working_dir.rights;
     let working_dir_rights_inheriting = working_dir.rights_inheriting;
 
     // ASSUMPTION: open rights apply recursively
-    if !has_rights(working_dir.rights, __WASI_RIGHT_PATH_OPEN) {
+    if !has_rights(working_dir_rights, __WASI_RIGHT_PATH_OPEN) {
+        return __WASI_EACCES;
+    }
+    wasi_try!(state.quotas.check_open_fd(state.fs.fd_map.len()));
+    // truncating and creating files need their own rights on top of the write access
+    if o_flags & __WASI_O_TRUNC != 0
+        && !has_rights(working_dir_rights, __WASI_RIGHT_PATH_FILESTAT_SET_SIZE)
+    {
         return __WASI_EACCES;
     }
     let path_string = get_input_str!(memory, path, path_len);
@@ -1710,6 +1834,12 @@ pub fn path_open(
     let adjusted_rights = /*fs_rights_base &*/ working_dir_rights_inheriting;
     let inode = if let Ok(inode) = maybe_inode {
         // Happy path, we found the file we're trying to open
+        wasi_try!(check_inode_access(
+            state,
+            inode,
+            FsOperation::Open,
+            working_dir_rights
+        ));
         match &mut state.fs.inodes[inode].kind {
             Kind::File {
                 ref mut handle,
@@ -1719,11 +1849,10 @@ pub fn path_open(
                     return __WASI_ENOTDIR;
                 }
                 if o_flags & __WASI_O_EXCL != 0 {
-                    if path.exists() {
+                    if state.fs.backend.metadata(path).is_ok() {
                         return __WASI_EEXIST;
                     }
                 }
-                let mut open_options = std::fs::OpenOptions::new();
                 let write_permission = adjusted_rights & __WASI_RIGHT_FD_WRITE != 0;
                 // append, truncate, and create all require the permission to write
                 let (append_permission, truncate_permission, create_permission) =
@@ -1736,13 +1865,15 @@ pub fn path_open(
                     } else {
                         (false, false, false)
                     };
-                let open_options = open_options
-                    .read(true)
+                let open_options = OpenOptions {
+                    read: true,
                     // TODO: ensure these rights are actually valid given parent, etc.
-                    .write(write_permission)
-                    .create(create_permission)
-                    .append(append_permission)
-                    .truncate(truncate_permission);
+                    write: write_permission,
+                    create: create_permission,
+                    append: append_permission,
+                    truncate: truncate_permission,
+                    ..OpenOptions::default()
+                };
                 open_flags |= Fd::READ;
                 if adjusted_rights & __WASI_RIGHT_FD_WRITE != 0 {
                     open_flags |= Fd::WRITE;
@@ -1753,21 +1884,17 @@ pub fn path_open(
                 if o_flags & __WASI_O_TRUNC != 0 {
                     open_flags |= Fd::TRUNCATE;
                 }
-                *handle = Some(Box::new(HostFile::new(
-                    wasi_try!(open_options.open(&path).map_err(|_| __WASI_EIO)),
-                    path.to_path_buf(),
-                    true,
-                    adjusted_rights & __WASI_RIGHT_FD_WRITE != 0,
-                    false,
-                )));
+                *handle = Some(wasi_try!(state
+                    .fs
+                    .backend
+                    .open(&path, &open_options)
+                    .map_err(|_| __WASI_EIO)));
             }
             Kind::Buffer { .. } => unimplemented!("wasi::path_open for Buffer type files"),
             Kind::Dir { .. } | Kind::Root { .. } => {
                 // TODO: adjust these to be correct
                 if o_flags & __WASI_O_EXCL != 0 {
-                    if path_arg.exists() {
-                        return __WASI_EEXIST;
-                    }
+                    return __WASI_EEXIST;
                 }
             }
             Kind::Symlink {
@@ -1788,6 +1915,11 @@ pub fn path_open(
             if o_flags & __WASI_O_DIRECTORY != 0 {
                 return __WASI_ENOTDIR;
             }
+            if !(has_rights(working_dir_rights, __WASI_RIGHT_PATH_CREATE_FILE)
+                && has_rights(working_dir_rights_inheriting, __WASI_RIGHT_FD_WRITE))
+            {
+                return __WASI_EACCES;
+            }
             debug!("Creating file");
             // strip end file name
 
@@ -1805,30 +1937,37 @@ pub fn path_open(
                 Kind::Root { .. } => return __WASI_EACCES,
                 _ => return __WASI_EINVAL,
             };
+            wasi_try!(state.quotas.check_create_entry());
+            wasi_try!(check_fs_access(
+                state,
+                &new_file_host_path,
+                FsOperation::CreateFile,
+                working_dir_rights
+            ));
             // once we got the data we need from the parent, we lookup the host file
             // todo: extra check that opening with write access is okay
             let handle = {
-                let mut open_options = std::fs::OpenOptions::new();
-                let open_options = open_options
-                    .read(true)
-                    .append(fs_flags & __WASI_FDFLAG_APPEND != 0)
+                let open_options = OpenOptions {
+                    read: true,
+                    append: fs_flags & __WASI_FDFLAG_APPEND != 0,
                     // TODO: ensure these rights are actually valid given parent, etc.
                     // write access is required for creating a file
-                    .write(true)
-                    .create_new(true);
+                    write: true,
+                    create_new: true,
+                    ..OpenOptions::default()
+                };
                 open_flags |= Fd::READ | Fd::WRITE | Fd::CREATE | Fd::TRUNCATE;
 
-                Some(Box::new(HostFile::new(
-                    wasi_try!(open_options.open(&new_file_host_path).map_err(|e| {
-                        debug!("Error opening file {}", e);
+                Some(wasi_try!(state
+                    .fs
+                    .backend
+                    .open(&new_file_host_path, &open_options)
+                    .map_err(|e| {
+                        debug!("Error opening file {:?}", e);
                         __WASI_EIO
-                    })),
-                    new_file_host_path.clone(),
-                    true,
-                    true,
-                    true,
-                )) as Box<dyn WasiFile>)
+                    })))
             };
+            state.quotas.record_create_entry();
 
             let new_inode = {
                 let kind = Kind::File {
@@ -1856,11 +1995,11 @@ pub fn path_open(
         inode, state.fs.inodes[inode]
     );
 
-    // TODO: check and reduce these
+    // the new fd can never pass on more rights than the directory it was opened from
     // TODO: ensure a mutable fd to root can never be opened
     let out_fd = wasi_try!(state.fs.create_fd(
         adjusted_rights,
-        fs_rights_inheriting,
+        fs_rights_inheriting & working_dir_rights_inheriting,
         fs_flags,
         open_flags,
         inode
@@ -1898,7 +2037,7 @@ pub fn path_readlink(
     buf_used: WasmPtr<u32>,
 ) -> __wasi_errno_t {
     debug!("wasi::path_readlink");
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let memory = ctx.memory(0);
 
     let base_dir = wasi_try!(state.fs.fd_map.get(&dir_fd).ok_or(__WASI_EBADF));
@@ -1942,10 +2081,14 @@ pub fn path_remove_directory(
 ) -> __wasi_errno_t {
     // TODO check if fd is a dir, ensure it's within sandbox, etc.
     debug!("wasi::path_remove_directory");
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let memory = ctx.memory(0);
 
     let base_dir = wasi_try!(state.fs.fd_map.get(&fd), __WASI_EBADF);
+    let base_dir_rights = base_dir.rights;
+    if !has_rights(base_dir_rights, __WASI_RIGHT_PATH_REMOVE_DIRECTORY) {
+        return __WASI_EACCES;
+    }
     let path_str = get_input_str!(memory, path, path_len);
 
     let inode = wasi_try!(state.fs.get_inode_at_path(fd, path_str, false));
@@ -1959,7 +2102,7 @@ pub fn path_remove_directory(
             if !entries.is_empty() {
                 return __WASI_ENOTEMPTY;
             } else {
-                if wasi_try!(std::fs::read_dir(path).ok(), __WASI_EIO).count() != 0 {
+                if !wasi_try!(state.fs.backend.read_dir(path).ok(), __WASI_EIO).is_empty() {
                     return __WASI_ENOTEMPTY;
                 }
             }
@@ -1968,6 +2111,12 @@ pub fn path_remove_directory(
         Kind::Root { .. } => return __WASI_EACCES,
         _ => return __WASI_ENOTDIR,
     };
+    wasi_try!(check_fs_access(
+        state,
+        &host_path_to_remove,
+        FsOperation::RemoveDirectory,
+        base_dir_rights
+    ));
 
     match &mut state.fs.inodes[parent_inode].kind {
         Kind::Dir {
@@ -1983,7 +2132,7 @@ pub fn path_remove_directory(
         ),
     }
 
-    if let Err(_) = std::fs::remove_dir(path_str) {
+    if let Err(_) = state.fs.backend.remove_dir(&host_path_to_remove) {
         // reinsert to prevent FS from being in bad state
         if let Kind::Dir {
             ref mut entries, ..
@@ -2024,13 +2173,13 @@ pub fn path_rename(
 ) -> __wasi_errno_t {
     debug!("wasi::path_rename");
     let memory = ctx.memory(0);
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let source_str = get_input_str!(memory, old_path, old_path_len);
     let source_path = std::path::Path::new(source_str);
     let target_str = get_input_str!(memory, new_path, new_path_len);
     let target_path = std::path::Path::new(target_str);
 
-    {
+    let (source_rights, target_rights) = {
         let source_fd = wasi_try!(state.fs.get_fd(old_fd));
         if !has_rights(source_fd.rights, __WASI_RIGHT_PATH_RENAME_SOURCE) {
             return __WASI_EACCES;
@@ -2039,7 +2188,8 @@ pub fn path_rename(
         if !has_rights(target_fd.rights, __WASI_RIGHT_PATH_RENAME_TARGET) {
             return __WASI_EACCES;
         }
-    }
+        (source_fd.rights, target_fd.rights)
+    };
 
     let (source_parent_inode, source_entry_name) =
         wasi_try!(state.fs.get_parent_inode_at_path(old_fd, source_path, true));
@@ -2062,6 +2212,22 @@ pub fn path_rename(
             unreachable!("Fatal internal logic error: parent of inode is not a directory")
         }
     };
+    if let Kind::Dir { entries, .. } = &state.fs.inodes[source_parent_inode].kind {
+        if let Some(&source_inode) = entries.get(&source_entry_name) {
+            wasi_try!(check_inode_access(
+                state,
+                source_inode,
+                FsOperation::Rename,
+                source_rights
+            ));
+        }
+    }
+    wasi_try!(check_fs_access(
+        state,
+        &host_adjusted_target_path,
+        FsOperation::Rename,
+        target_rights
+    ));
     let source_entry = match &mut state.fs.inodes[source_parent_inode].kind {
         Kind::Dir { entries, .. } => wasi_try!(entries.remove(&source_entry_name), __WASI_EINVAL),
         Kind::Root { .. } => return __WASI_ENOTCAPABLE,
@@ -2079,8 +2245,11 @@ pub fn path_rename(
                 h.rename_file(&host_adjusted_target_path)
                     .map_err(|e| e.into_wasi_err())
             } else {
-                let out =
-                    std::fs::rename(&path, &host_adjusted_target_path).map_err(|_| __WASI_EIO);
+                let out = state
+                    .fs
+                    .backend
+                    .rename(&path, &host_adjusted_target_path)
+                    .map_err(|_| __WASI_EIO);
                 *path = host_adjusted_target_path;
                 out
             };
@@ -2131,7 +2300,7 @@ pub fn path_symlink(
     new_path_len: u32,
 ) -> __wasi_errno_t {
     debug!("wasi::path_symlink");
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let memory = ctx.memory(0);
     let old_path_str = get_input_str!(memory, old_path, old_path_len);
     let new_path_str = get_input_str!(memory, new_path, new_path_len);
@@ -2161,6 +2330,7 @@ pub fn path_symlink(
             unreachable!("get_parent_inode_at_path returned something other than a Dir or Root")
         }
     }
+    wasi_try!(state.quotas.check_create_entry());
 
     let mut source_path = std::path::Path::new(old_path_str);
     let mut relative_path = std::path::PathBuf::new();
@@ -2189,6 +2359,7 @@ pub fn path_symlink(
     {
         entries.insert(entry_name, new_inode);
     }
+    state.quotas.record_create_entry();
 
     __WASI_ESUCCESS
 }
@@ -2209,17 +2380,24 @@ pub fn path_unlink_file(
     path_len: u32,
 ) -> __wasi_errno_t {
     debug!("wasi::path_unlink_file");
-    let state = get_wasi_state(ctx);
+    let (state, _lock) = get_wasi_state(ctx);
     let memory = ctx.memory(0);
 
     let base_dir = wasi_try!(state.fs.fd_map.get(&fd).ok_or(__WASI_EBADF));
-    if !has_rights(base_dir.rights, __WASI_RIGHT_PATH_UNLINK_FILE) {
+    let base_dir_rights = base_dir.rights;
+    if !has_rights(base_dir_rights, __WASI_RIGHT_PATH_UNLINK_FILE) {
         return __WASI_EACCES;
     }
     let path_str = get_input_str!(memory, path, path_len);
     debug!("Requested file: {}", path_str);
 
     let inode = wasi_try!(state.fs.get_inode_at_path(fd, path_str, false));
+    wasi_try!(check_inode_access(
+        state,
+        inode,
+        FsOperation::RemoveFile,
+        base_dir_rights
+    ));
     let (parent_inode, childs_name) =
         wasi_try!(state
             .fs
@@ -2249,9 +2427,7 @@ pub fn path_unlink_file(
                     wasi_try!(h.unlink().map_err(WasiFsError::into_wasi_err));
                 } else {
                     // File is closed
-                    // problem with the abstraction, we can't call unlink because there's no handle
-                    // TODO: replace this code
-                    wasi_try!(std::fs::remove_file(path).map_err(|_| __WASI_EIO));
+                    wasi_try!(state.fs.backend.remove_file(path).map_err(|_| __WASI_EIO));
                 }
             }
             Kind::Dir { .. } | Kind::Root { .. } => return __WASI_EISDIR,
@@ -2307,21 +2483,50 @@ pub fn poll_oneoff(
     debug!("wasi::poll_oneoff");
     debug!("  => nsubscriptions = {}", nsubscriptions);
     let memory = ctx.memory(0);
-    let state = get_wasi_state(ctx);
 
-    let subscription_array = wasi_try!(in_.deref(memory, 0, nsubscriptions));
-    let event_array = wasi_try!(out_.deref(memory, 0, nsubscriptions));
+    let subscriptions: Vec<__wasi_subscription_t> = wasi_try!(in_.deref(memory, 0, nsubscriptions))
+        .iter()
+        .map(Cell::get)
+        .collect();
+    poll_oneoff_internal(ctx, &subscriptions, out_, nevents)
+}
+
+/// Polls for the events of `subscriptions`, shared by the syscalls of all the WASI versions
+/// once they've read their subscriptions.
+///
+/// Blocks until an fd is ready or the earliest clock subscription times out, and only writes
+/// out the events that occurred.
+pub(crate) fn poll_oneoff_internal(
+    ctx: &mut Ctx,
+    subscriptions: &[__wasi_subscription_t],
+    out_: WasmPtr<__wasi_event_t, Array>,
+    nevents: WasmPtr<u32>,
+) -> __wasi_errno_t {
+    let memory = ctx.memory(0);
+    let (state, _lock) = get_wasi_state(ctx);
+
+    if subscriptions.is_empty() {
+        return __WASI_EINVAL;
+    }
+    let event_array = wasi_try!(out_.deref(memory, 0, subscriptions.len() as u32));
     let mut events_seen = 0;
     let out_ptr = wasi_try!(nevents.deref(memory));
 
+    // the fd subscriptions, by index in `subscriptions`
+    let mut fd_subscriptions = vec![];
     let mut fds = vec![];
     let mut in_events = vec![];
-
-    for sub in subscription_array.iter() {
-        let s: WasiSubscription = wasi_try!(sub.get().try_into());
+    // the clock subscriptions, by index in `subscriptions`, with their timeout from now
+    let mut clock_subscriptions = vec![];
+    let mut timeout: Option<__wasi_timestamp_t> = None;
+    // the events of the fds that can't be waited for, which are reported right away
+    let mut immediate_events = vec![];
+
+    for (i, sub) in subscriptions.iter().enumerate() {
+        let s: WasiSubscription = wasi_try!((*sub).try_into());
         let mut peb = PollEventBuilder::new();
 
-        let fd = match s.event_type {
+        let (fd, in_event) = match s.event_type {
             EventType::Read(__wasi_subscription_fs_readwrite_t { fd }) => {
                 match fd {
                     __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => (),
@@ -2332,8 +2537,7 @@ pub fn poll_oneoff(
                         }
                     }
                 }
-                in_events.push(peb.add(PollEvent::PollIn).build());
-                Some(fd)
+                (fd, peb.add(PollEvent::PollIn).build())
             }
             EventType::Write(__wasi_subscription_fs_readwrite_t { fd }) => {
                 match fd {
@@ -2346,69 +2550,111 @@ pub fn poll_oneoff(
                         }
                     }
                 }
-                in_events.push(peb.add(PollEvent::PollOut).build());
-                Some(fd)
+                (fd, peb.add(PollEvent::PollOut).build())
+            }
+            EventType::Clock(clock) => {
+                let from_now = if clock.flags & __WASI_SUBSCRIPTION_CLOCK_ABSTIME != 0 {
+                    let now = wasi_try!(state.clock.time_get(clock.clock_id, 1));
+                    clock.timeout.saturating_sub(now)
+                } else {
+                    clock.timeout
+                };
+                clock_subscriptions.push((i, from_now));
+                timeout = Some(timeout.map_or(from_now, |t| t.min(from_now)));
+                continue;
             }
-            _ => unimplemented!("Clock eventtypes in wasi::poll_oneoff"),
         };
 
-        if let Some(fd) = fd {
-            let wasi_file_ref: &dyn WasiFile = match fd {
-                __WASI_STDERR_FILENO => wasi_try!(
-                    wasi_try!(state.fs.stderr().map_err(WasiFsError::into_wasi_err)).as_ref(),
-                    __WASI_EBADF
-                )
-                .as_ref(),
-                __WASI_STDIN_FILENO => wasi_try!(
-                    wasi_try!(state.fs.stdin().map_err(WasiFsError::into_wasi_err)).as_ref(),
-                    __WASI_EBADF
-                )
-                .as_ref(),
-                __WASI_STDOUT_FILENO => wasi_try!(
-                    wasi_try!(state.fs.stdout().map_err(WasiFsError::into_wasi_err)).as_ref(),
-                    __WASI_EBADF
-                )
-                .as_ref(),
-                _ => {
-                    let fd_entry = wasi_try!(state.fs.get_fd(fd));
-                    let inode = fd_entry.inode;
-                    if !has_rights(fd_entry.rights, __WASI_RIGHT_POLL_FD_READWRITE) {
-                        return __WASI_EACCES;
-                    }
+        let wasi_file_ref: &dyn WasiFile = match fd {
+            __WASI_STDERR_FILENO => wasi_try!(
+                wasi_try!(state.fs.stderr().map_err(WasiFsError::into_wasi_err)).as_ref(),
+                __WASI_EBADF
+            )
+            .as_ref(),
+            __WASI_STDIN_FILENO => wasi_try!(
+                wasi_try!(state.fs.stdin().map_err(WasiFsError::into_wasi_err)).as_ref(),
+                __WASI_EBADF
+            )
+            .as_ref(),
+            __WASI_STDOUT_FILENO => wasi_try!(
+                wasi_try!(state.fs.stdout().map_err(WasiFsError::into_wasi_err)).as_ref(),
+                __WASI_EBADF
+            )
+            .as_ref(),
+            _ => {
+                let fd_entry = wasi_try!(state.fs.get_fd(fd));
+                let inode = fd_entry.inode;
+                if !has_rights(fd_entry.rights, __WASI_RIGHT_POLL_FD_READWRITE) {
+                    return __WASI_EACCES;
+                }
 
-                    match &state.fs.inodes[inode].kind {
-                        Kind::File { handle, .. } => {
-                            if let Some(h) = handle {
-                                h.as_ref()
-                            } else {
-                                return __WASI_EBADF;
-                            }
-                        }
-                        Kind::Dir { .. }
-                        | Kind::Root { .. }
-                        | Kind::Buffer { .. }
-                        | Kind::Symlink { .. } => {
-                            unimplemented!("polling read on non-files not yet supported")
+                match &state.fs.inodes[inode].kind {
+                    Kind::File { handle, .. } => {
+                        if let Some(h) = handle {
+                            h.as_ref()
+                        } else {
+                            return __WASI_EBADF;
                         }
                     }
+                    // buffers are in memory, so they never block
+                    Kind::Buffer { buffer } => {
+                        let nbytes = match s.event_type {
+                            EventType::Read(_) => {
+                                (buffer.len() as u64).saturating_sub(fd_entry.offset)
+                            }
+                            _ => 0,
+                        };
+                        immediate_events.push(fd_event(sub, __WASI_ESUCCESS, nbytes, 0));
+                        continue;
+                    }
+                    Kind::Dir { .. } | Kind::Root { .. } | Kind::Symlink { .. } => {
+                        immediate_events.push(fd_event(sub, __WASI_ENOTSUP, 0, 0));
+                        continue;
+                    }
                 }
-            };
-            fds.push(wasi_file_ref);
+            }
+        };
+        fd_subscriptions.push(i);
+        fds.push(wasi_file_ref);
+        in_events.push(in_event);
+    }
+
+    if !immediate_events.is_empty() {
+        timeout = Some(0);
+    }
+    let start = std::time::Instant::now();
+    let mut seen_events = vec![Default::default(); in_events.len()];
+    if fds.is_empty() {
+        // only clocks were subscribed to, so sleep until the earliest times out
+        if let Some(timeout) = timeout {
+            std::thread::sleep(std::time::Duration::from_nanos(timeout));
+        }
+    } else {
+        // async files are waited for on their reactor instead
+        let wait = if fds.iter().any(|file| file.async_io().is_some()) {
+            poll_async
         } else {
-            unimplemented!("Clock events are not yet implemented!");
+            poll
+        };
+        match wait(
+            fds.as_slice(),
+            in_events.as_slice(),
+            seen_events.as_mut_slice(),
+            timeout.map(std::time::Duration::from_nanos),
+        ) {
+            // a signal interrupting the wait is reported as no event
+            Ok(_) | Err(WasiFsError::Interrupted) => (),
+            Err(e) => return e.into_wasi_err(),
         }
     }
-    let mut seen_events = vec![Default::default(); in_events.len()];
-    wasi_try!(poll(
-        fds.as_slice(),
-        in_events.as_slice(),
-        seen_events.as_mut_slice()
-    )
-    .map_err(|e| e.into_wasi_err()));
+    let elapsed = start.elapsed().as_nanos() as __wasi_timestamp_t;
 
     for (i, seen_event) in seen_events.into_iter().enumerate() {
+        if seen_event == 0 {
+            continue;
+        }
         let mut flags = 0;
-        let mut error = __WASI_EAGAIN;
+        let mut error = __WASI_ESUCCESS;
         let mut bytes_available = 0;
         let event_iter = iterate_poll_events(seen_event);
         for event in event_iter {
@@ -2416,29 +2662,38 @@ pub fn poll_oneoff(
                 PollEvent::PollError => error = __WASI_EIO,
                 PollEvent::PollHangUp => flags = __WASI_EVENT_FD_READWRITE_HANGUP,
                 PollEvent::PollInvalid => error = __WASI_EINVAL,
-                PollEvent::PollIn => {
-                    bytes_available =
-                        wasi_try!(fds[i].bytes_available().map_err(|e| e.into_wasi_err()));
-                    error = __WASI_ESUCCESS;
-                }
-                PollEvent::PollOut => {
+                PollEvent::PollIn | PollEvent::PollOut => {
                     bytes_available =
                         wasi_try!(fds[i].bytes_available().map_err(|e| e.into_wasi_err()));
-                    error = __WASI_ESUCCESS;
                 }
             }
         }
-        let event = __wasi_event_t {
-            userdata: subscription_array[i].get().userdata,
+        let event = fd_event(
+            &subscriptions[fd_subscriptions[i]],
             error,
-            type_: subscription_array[i].get().type_,
-            u: unsafe {
-                __wasi_event_u {
-                    fd_readwrite: __wasi_event_fd_readwrite_t {
-                        nbytes: bytes_available as u64,
-                        flags,
-                    },
-                }
+            bytes_available as u64,
+            flags,
+        );
+        event_array[events_seen].set(event);
+        events_seen += 1;
+    }
+    for event in immediate_events {
+        event_array[events_seen].set(event);
+        events_seen += 1;
+    }
+    for (i, from_now) in clock_subscriptions {
+        if from_now > elapsed {
+            continue;
+        }
+        let event = __wasi_event_t {
+            userdata: subscriptions[i].userdata,
+            error: __WASI_ESUCCESS,
+            type_: __WASI_EVENTTYPE_CLOCK,
+            u: __wasi_event_u {
+                fd_readwrite: __wasi_event_fd_readwrite_t {
+                    nbytes: 0,
+                    flags: 0,
+                },
             },
         };
         event_array[events_seen].set(event);
@@ -2448,9 +2703,32 @@ pub fn poll_oneoff(
     __WASI_ESUCCESS
 }
 
-pub fn proc_exit(ctx: &mut Ctx, code: __wasi_exitcode_t) -> Result<Infallible, ExitCode> {
+/// The event of the fd subscription `subscription`.
+fn fd_event(
+    subscription: &__wasi_subscription_t,
+    error: __wasi_errno_t,
+    nbytes: u64,
+    flags: __wasi_eventrwflags_t,
+) -> __wasi_event_t {
+    __wasi_event_t {
+        userdata: subscription.userdata,
+        error,
+        type_: subscription.type_,
+        u: __wasi_event_u {
+            fd_readwrite: __wasi_event_fd_readwrite_t { nbytes, flags },
+        },
+    }
+}
+
+/// ### `proc_exit()`
+/// Terminate the process normally.  The embedder gets `WasiError::Exit(rval)` as the error of
+/// the call to the module.
+/// Inputs:
+/// - `__wasi_exitcode_t rval`
+///     The exit code returned by the process
+pub fn proc_exit(ctx: &mut Ctx, code: __wasi_exitcode_t) -> Result<Infallible, WasiError> {
     debug!("wasi::proc_exit, {}", code);
-    Err(ExitCode { code })
+    Err(WasiError::Exit(code))
 }
 pub fn proc_raise(ctx: &mut Ctx, sig: __wasi_signal_t) -> __wasi_errno_t {
     debug!("wasi::proc_raise");
@@ -2466,18 +2744,17 @@ pub fn proc_raise(ctx: &mut Ctx, sig: __wasi_signal_t) -> __wasi_errno_t {
 ///     The number of bytes that will be written
 pub fn random_get(ctx: &mut Ctx, buf: WasmPtr<u8, Array>, buf_len: u32) -> __wasi_errno_t {
     debug!("wasi::random_get buf_len: {}", buf_len);
+    let (state, _lock) = get_wasi_state(ctx);
+    if state.deterministic && !state.random.is_deterministic() {
+        return __WASI_ENOTCAPABLE;
+    }
     let memory = ctx.memory(0);
 
     let buf = wasi_try!(buf.deref(memory, 0, buf_len));
 
-    let res = unsafe {
-        let u8_buffer = &mut *(buf as *const [_] as *mut [_] as *mut [u8]);
-        getrandom::getrandom(u8_buffer)
-    };
-    match res {
-        Ok(()) => __WASI_ESUCCESS,
-        Err(_) => __WASI_EIO,
-    }
+    let u8_buffer = unsafe { &mut *(buf as *const [_] as *mut [_] as *mut [u8]) };
+    wasi_try!(state.random.fill(u8_buffer));
+    __WASI_ESUCCESS
 }
 
 /// ### `sched_yield()`
@@ -2488,6 +2765,47 @@ pub fn sched_yield(ctx: &mut Ctx) -> __wasi_errno_t {
     __WASI_ESUCCESS
 }
 
+/// Returns the open socket at `fd`, checking that the fd has `rights`.
+fn get_socket_mut(
+    state: &mut WasiState,
+    fd: __wasi_fd_t,
+    rights: __wasi_rights_t,
+) -> Result<&mut Box<dyn WasiFile>, __wasi_errno_t> {
+    let fd_entry = state.fs.get_fd(fd)?;
+    if !has_rights(fd_entry.rights, rights) {
+        return Err(__WASI_EACCES);
+    }
+    let inode = fd_entry.inode;
+    let inode = &mut state.fs.inodes[inode];
+    if inode.stat.st_filetype != __WASI_FILETYPE_SOCKET_STREAM {
+        return Err(__WASI_ENOTSOCK);
+    }
+    match &mut inode.kind {
+        Kind::File {
+            handle: Some(handle),
+            ..
+        } => Ok(handle),
+        _ => Err(__WASI_EBADF),
+    }
+}
+
+/// ### `sock_recv()`
+/// Receive a message from a socket
+/// Inputs:
+/// - `__wasi_fd_t sock`
+///     The socket to receive from
+/// - `__wasi_iovec_t *ri_data`
+///     The buffers where the data will be stored
+/// - `u32 ri_data_len`
+///     The number of buffers in `ri_data`
+/// - `__wasi_riflags_t ri_flags`
+///     `__WASI_SOCK_RECV_WAITALL` to fill all the buffers; `__WASI_SOCK_RECV_PEEK` is not
+///     supported
+/// Output:
+/// - `u32 *ro_datalen`
+///     The number of bytes received
+/// - `__wasi_roflags_t *ro_flags`
+///     Always 0, since messages are never truncated on stream sockets
 pub fn sock_recv(
     ctx: &mut Ctx,
     sock: __wasi_fd_t,
@@ -2497,9 +2815,61 @@ pub fn sock_recv(
     ro_datalen: WasmPtr<u32>,
     ro_flags: WasmPtr<__wasi_roflags_t>,
 ) -> __wasi_errno_t {
-    debug!("wasi::sock_recv");
-    unimplemented!("wasi::sock_recv")
+    debug!("wasi::sock_recv: sock={}", sock);
+    let memory = ctx.memory(0);
+    let iovs_arr_cell = wasi_try!(ri_data.deref(memory, 0, ri_data_len));
+    let ro_datalen_cell = wasi_try!(ro_datalen.deref(memory));
+    let ro_flags_cell = wasi_try!(ro_flags.deref(memory));
+    let (state, _lock) = get_wasi_state(ctx);
+
+    if ri_flags & __WASI_SOCK_RECV_PEEK != 0 {
+        return __WASI_ENOTSUP;
+    }
+    let wait_all = ri_flags & __WASI_SOCK_RECV_WAITALL != 0;
+    let socket = wasi_try!(get_socket_mut(state, sock, __WASI_RIGHT_FD_READ));
+
+    let mut bytes_read = 0;
+    for iov in iovs_arr_cell {
+        let iov_inner = iov.get();
+        let bytes = wasi_try!(iov_inner.buf.deref(memory, 0, iov_inner.buf_len));
+        let raw_bytes: &mut [u8] = unsafe { &mut *(bytes as *const [_] as *mut [_] as *mut [u8]) };
+        // stop at the first short read, unless asked to wait for all the buffers to be filled
+        let mut filled = 0;
+        while filled < raw_bytes.len() {
+            let read = wasi_try!(socket
+                .read(&mut raw_bytes[filled..])
+                .map_err(|e| WasiFsError::from(e).into_wasi_err()));
+            filled += read;
+            if read == 0 || !wait_all {
+                break;
+            }
+        }
+        bytes_read += filled as u32;
+        if filled < raw_bytes.len() {
+            break;
+        }
+    }
+
+    ro_datalen_cell.set(bytes_read);
+    ro_flags_cell.set(0);
+
+    __WASI_ESUCCESS
 }
+
+/// ### `sock_send()`
+/// Send a message on a socket
+/// Inputs:
+/// - `__wasi_fd_t sock`
+///     The socket to send on
+/// - `const __wasi_ciovec_t *si_data`
+///     The buffers holding the data to send
+/// - `u32 si_data_len`
+///     The number of buffers in `si_data`
+/// - `__wasi_siflags_t si_flags`
+///     Unused, there are no flags defined yet
+/// Output:
+/// - `u32 *so_datalen`
+///     The number of bytes sent
 pub fn sock_send(
     ctx: &mut Ctx,
     sock: __wasi_fd_t,
@@ -2508,10 +2878,45 @@ pub fn sock_send(
     si_flags: __wasi_siflags_t,
     so_datalen: WasmPtr<u32>,
 ) -> __wasi_errno_t {
-    debug!("wasi::sock_send");
-    unimplemented!("wasi::sock_send")
+    debug!("wasi::sock_send: sock={}", sock);
+    let memory = ctx.memory(0);
+    let iovs_arr_cell = wasi_try!(si_data.deref(memory, 0, si_data_len));
+    let so_datalen_cell = wasi_try!(so_datalen.deref(memory));
+    let (state, _lock) = get_wasi_state(ctx);
+
+    wasi_try!(state.quotas.check_write(ciovecs_len(iovs_arr_cell)));
+    let socket = wasi_try!(get_socket_mut(state, sock, __WASI_RIGHT_FD_WRITE));
+    let bytes_written = wasi_try!(write_bytes(socket, memory, iovs_arr_cell));
+    state.quotas.record_write(bytes_written as u64);
+    so_datalen_cell.set(bytes_written);
+
+    __WASI_ESUCCESS
 }
+
+/// ### `sock_shutdown()`
+/// Shut down the reading and/or writing halves of a socket
+/// Inputs:
+/// - `__wasi_fd_t sock`
+///     The socket to shut down
+/// - `__wasi_sdflags_t how`
+///     `__WASI_SHUT_RD`, `__WASI_SHUT_WR`, or both
 pub fn sock_shutdown(ctx: &mut Ctx, sock: __wasi_fd_t, how: __wasi_sdflags_t) -> __wasi_errno_t {
-    debug!("wasi::sock_shutdown");
-    unimplemented!("wasi::sock_shutdown")
+    debug!("wasi::sock_shutdown: sock={}, how={}", sock, how);
+    let (state, _lock) = get_wasi_state(ctx);
+
+    if how & !(__WASI_SHUT_RD | __WASI_SHUT_WR) != 0 {
+        return __WASI_EINVAL;
+    }
+    let how = match (how & __WASI_SHUT_RD != 0, how & __WASI_SHUT_WR != 0) {
+        (true, true) => std::net::Shutdown::Both,
+        (true, false) => std::net::Shutdown::Read,
+        (false, true) => std::net::Shutdown::Write,
+        (false, false) => return __WASI_EINVAL,
+    };
+    let socket = wasi_try!(get_socket_mut(state, sock, __WASI_RIGHT_SOCK_SHUTDOWN));
+    wasi_try!(socket
+        .sock_shutdown(how)
+        .map_err(WasiFsError::into_wasi_err));
+
+    __WASI_ESUCCESS
 }
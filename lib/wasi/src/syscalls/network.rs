@@ -0,0 +1,122 @@
+//! The syscalls of the `wasi_experimental_network` namespace, which create TCP sockets.
+//!
+//! WASI can only use sockets it's given; these calls fill the gap until it can create them.
+//! Addresses are passed as UTF-8 strings such as `127.0.0.1:8080` or `[::1]:8080`; host
+//! names are not resolved.  Only the addresses granted by the `WasiStateBuilder` are
+//! reachable, anything else fails with `__WASI_ENOTCAPABLE`.
+use super::types::*;
+use super::{get_socket_mut, get_wasi_state};
+use crate::ptr::{Array, WasmPtr};
+use crate::state::{WasiFsError, WasiTcpListener, WasiTcpStream};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use wasmer_runtime_core::{debug, memory::Memory, vm::Ctx};
+
+fn read_socket_addr(
+    memory: &Memory,
+    addr: WasmPtr<u8, Array>,
+    addr_len: u32,
+) -> Result<SocketAddr, __wasi_errno_t> {
+    addr.get_utf8_string(memory, addr_len)
+        .and_then(|addr| addr.parse().ok())
+        .ok_or(__WASI_EINVAL)
+}
+
+/// ### `sock_connect()`
+/// Open a TCP connection
+/// Inputs:
+/// - `const char *addr`
+///     The address to connect to
+/// - `u32 addr_len`
+///     The length of `addr`
+/// Output:
+/// - `__wasi_fd_t *fd`
+///     The new socket
+pub fn sock_connect(
+    ctx: &mut Ctx,
+    addr: WasmPtr<u8, Array>,
+    addr_len: u32,
+    fd: WasmPtr<__wasi_fd_t>,
+) -> __wasi_errno_t {
+    debug!("wasi::sock_connect");
+    let memory = ctx.memory(0);
+    let (state, _lock) = get_wasi_state(ctx);
+
+    let addr = wasi_try!(read_socket_addr(memory, addr, addr_len));
+    debug!("=> addr: {}", addr);
+    if !state.network.can_connect(&addr) {
+        return __WASI_ENOTCAPABLE;
+    }
+    let fd_cell = wasi_try!(fd.deref(memory));
+    wasi_try!(state.quotas.check_open_fd(state.fs.fd_map.len()));
+
+    let stream =
+        wasi_try!(TcpStream::connect(addr).map_err(|e| WasiFsError::from(e).into_wasi_err()));
+    let new_fd = wasi_try!(state
+        .fs
+        .create_socket_fd(Box::new(WasiTcpStream::new(stream))));
+    fd_cell.set(new_fd);
+
+    __WASI_ESUCCESS
+}
+
+/// ### `sock_listen()`
+/// Listen for TCP connections
+/// Inputs:
+/// - `const char *addr`
+///     The address to listen on
+/// - `u32 addr_len`
+///     The length of `addr`
+/// Output:
+/// - `__wasi_fd_t *fd`
+///     The new listening socket, to pass to `sock_accept`
+pub fn sock_listen(
+    ctx: &mut Ctx,
+    addr: WasmPtr<u8, Array>,
+    addr_len: u32,
+    fd: WasmPtr<__wasi_fd_t>,
+) -> __wasi_errno_t {
+    debug!("wasi::sock_listen");
+    let memory = ctx.memory(0);
+    let (state, _lock) = get_wasi_state(ctx);
+
+    let addr = wasi_try!(read_socket_addr(memory, addr, addr_len));
+    debug!("=> addr: {}", addr);
+    if !state.network.can_listen(&addr) {
+        return __WASI_ENOTCAPABLE;
+    }
+    let fd_cell = wasi_try!(fd.deref(memory));
+    wasi_try!(state.quotas.check_open_fd(state.fs.fd_map.len()));
+
+    let listener =
+        wasi_try!(TcpListener::bind(addr).map_err(|e| WasiFsError::from(e).into_wasi_err()));
+    let new_fd = wasi_try!(state
+        .fs
+        .create_socket_fd(Box::new(WasiTcpListener::new(listener))));
+    fd_cell.set(new_fd);
+
+    __WASI_ESUCCESS
+}
+
+/// ### `sock_accept()`
+/// Accept a connection on a listening socket, blocking until there's one.  The listening
+/// socket becomes readable in `poll_oneoff` when a connection is pending.
+/// Inputs:
+/// - `__wasi_fd_t sock`
+///     The listening socket
+/// Output:
+/// - `__wasi_fd_t *fd`
+///     The socket of the new connection
+pub fn sock_accept(ctx: &mut Ctx, sock: __wasi_fd_t, fd: WasmPtr<__wasi_fd_t>) -> __wasi_errno_t {
+    debug!("wasi::sock_accept: sock={}", sock);
+    let memory = ctx.memory(0);
+    let (state, _lock) = get_wasi_state(ctx);
+    let fd_cell = wasi_try!(fd.deref(memory));
+    wasi_try!(state.quotas.check_open_fd(state.fs.fd_map.len()));
+
+    let listener = wasi_try!(get_socket_mut(state, sock, __WASI_RIGHT_FD_READ));
+    let stream = wasi_try!(listener.sock_accept().map_err(WasiFsError::into_wasi_err));
+    let new_fd = wasi_try!(state.fs.create_socket_fd(stream));
+    fd_cell.set(new_fd);
+
+    __WASI_ESUCCESS
+}
@@ -0,0 +1,118 @@
+//! The syscalls of `wasi_snapshot_preview1` whose signature or behavior differ from
+//! `wasi_unstable`; all the others are shared between both versions.
+use super::types::{snapshot1, *};
+use super::{fd_filestat_get_internal, path_filestat_get_internal, poll_oneoff_internal};
+use crate::ptr::{Array, WasmPtr};
+use std::convert::TryInto;
+use wasmer_runtime_core::{debug, vm::Ctx};
+
+/// ### `fd_filestat_get()`
+/// Get the metadata of an open file, with a 64-bit link count
+/// Input:
+/// - `__wasi_fd_t fd`
+///     The open file descriptor whose metadata will be read
+/// Output:
+/// - `snapshot1::__wasi_filestat_t *buf`
+///     Where the metadata from `fd` will be written
+pub fn fd_filestat_get(
+    ctx: &mut Ctx,
+    fd: __wasi_fd_t,
+    buf: WasmPtr<snapshot1::__wasi_filestat_t>,
+) -> __wasi_errno_t {
+    debug!("wasi::snapshot1::fd_filestat_get");
+    let stat = wasi_try!(fd_filestat_get_internal(ctx, fd));
+    let memory = ctx.memory(0);
+
+    let buf = wasi_try!(buf.deref(memory));
+    buf.set(stat.into());
+
+    __WASI_ESUCCESS
+}
+
+/// ### `fd_seek()`
+/// Update file descriptor offset, with the whence values of `wasi_snapshot_preview1`
+/// Inputs:
+/// - `__wasi_fd_t fd`
+///     File descriptor to mutate
+/// - `__wasi_filedelta_t offset`
+///     Number of bytes to adjust offset by
+/// - `snapshot1::__wasi_whence_t whence`
+///     What the offset is relative to
+/// Output:
+/// - `__wasi_filesize_t *fd`
+///     The new offset relative to the start of the file
+pub fn fd_seek(
+    ctx: &mut Ctx,
+    fd: __wasi_fd_t,
+    offset: __wasi_filedelta_t,
+    whence: snapshot1::__wasi_whence_t,
+    newoffset: WasmPtr<__wasi_filesize_t>,
+) -> __wasi_errno_t {
+    debug!("wasi::snapshot1::fd_seek");
+    let whence = wasi_try!(snapshot1::whence_to_unstable(whence));
+    super::fd_seek(ctx, fd, offset, whence, newoffset)
+}
+
+/// ### `path_filestat_get()`
+/// Access metadata about a file or directory, with a 64-bit link count
+/// Inputs:
+/// - `__wasi_fd_t fd`
+///     The directory that `path` is relative to
+/// - `__wasi_lookupflags_t flags`
+///     Flags to control how `path` is understood
+/// - `const char *path`
+///     String containing the file path
+/// - `u32 path_len`
+///     The length of the `path` string
+/// Output:
+/// - `snapshot1::__wasi_filestat_t *buf`
+///     The location where the metadata will be stored
+pub fn path_filestat_get(
+    ctx: &mut Ctx,
+    fd: __wasi_fd_t,
+    flags: __wasi_lookupflags_t,
+    path: WasmPtr<u8, Array>,
+    path_len: u32,
+    buf: WasmPtr<snapshot1::__wasi_filestat_t>,
+) -> __wasi_errno_t {
+    debug!("wasi::snapshot1::path_filestat_get");
+    let stat = wasi_try!(path_filestat_get_internal(ctx, fd, flags, path, path_len));
+    let memory = ctx.memory(0);
+
+    let buf_cell = wasi_try!(buf.deref(memory));
+    buf_cell.set(stat.into());
+
+    __WASI_ESUCCESS
+}
+
+/// ### `poll_oneoff()`
+/// Concurrently poll for a set of events, with the clock subscriptions of
+/// `wasi_snapshot_preview1`
+/// Inputs:
+/// - `const snapshot1::__wasi_subscription_t *in`
+///     The events to subscribe to
+/// - `__wasi_event_t *out`
+///     The events that have occured
+/// - `u32 nsubscriptions`
+///     The number of subscriptions and the number of events
+/// Output:
+/// - `u32 nevents`
+///     The number of events seen
+pub fn poll_oneoff(
+    ctx: &mut Ctx,
+    in_: WasmPtr<snapshot1::__wasi_subscription_t, Array>,
+    out_: WasmPtr<__wasi_event_t, Array>,
+    nsubscriptions: u32,
+    nevents: WasmPtr<u32>,
+) -> __wasi_errno_t {
+    debug!("wasi::snapshot1::poll_oneoff");
+    debug!("  => nsubscriptions = {}", nsubscriptions);
+    let memory = ctx.memory(0);
+
+    let subscription_array = wasi_try!(in_.deref(memory, 0, nsubscriptions));
+    let subscriptions: Vec<__wasi_subscription_t> = wasi_try!(subscription_array
+        .iter()
+        .map(|sub| sub.get().try_into())
+        .collect());
+    poll_oneoff_internal(ctx, &subscriptions, out_, nevents)
+}
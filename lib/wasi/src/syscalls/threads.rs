@@ -0,0 +1,67 @@
+//! The `thread-spawn` call of wasi-threads, in the `wasi` namespace.
+//!
+//! It's only available to the instances created from `generate_import_object_with_threads`.
+use super::get_wasi_state;
+use super::types::*;
+use std::thread;
+use wasmer_runtime_core::{debug, vm::Ctx, Func};
+
+/// The export of the module running new threads.
+const THREAD_START: &str = "wasi_thread_start";
+
+/// ### `thread_spawn()`
+/// Start a new thread running the `wasi_thread_start` export of the module in a new instance
+/// sharing the memory and the WASI state of this one.  The new instance doesn't initialize the
+/// shared memory again nor run the start function.
+/// Inputs:
+/// - `u32 start_arg`
+///     Passed on to `wasi_thread_start`, usually a pointer to the stack and TLS of the new thread
+/// Output:
+/// - `i32`
+///     The id of the new thread, or a negated errno if it couldn't be started
+pub fn thread_spawn(ctx: &mut Ctx, start_arg: u32) -> i32 {
+    debug!("wasi::thread_spawn: start_arg={}", start_arg);
+    let memory = ctx.memory(0).clone();
+    let threads = {
+        let (state, _lock) = get_wasi_state(ctx);
+        match &state.threads {
+            Some(threads) => threads.clone(),
+            None => return -(__WASI_ENOSYS as i32),
+        }
+    };
+    let id = match threads.next_id() {
+        Some(id) => id,
+        None => return -(__WASI_EAGAIN as i32),
+    };
+
+    // the instances of the threads are the only ones with `threads`, and share their state
+    let state = unsafe { crate::get_shared_state(ctx) };
+    let import_object = crate::generate_import_object_for_thread(threads.clone(), state, memory);
+    let instance = match threads.module.instantiate_thread(&import_object) {
+        Ok(instance) => instance,
+        Err(e) => {
+            debug!(
+                "wasi::thread_spawn: could not instantiate the module: {:?}",
+                e
+            );
+            return -(__WASI_EAGAIN as i32);
+        }
+    };
+    if instance.func::<(i32, i32), ()>(THREAD_START).is_err() {
+        return -(__WASI_ENOSYS as i32);
+    }
+
+    let spawned = thread::Builder::new()
+        .name(format!("wasi-thread-{}", id))
+        .spawn(move || {
+            let start: Func<(i32, i32), ()> = instance.func(THREAD_START).unwrap();
+            if let Err(e) = start.call(id as i32, start_arg as i32) {
+                debug!("wasi::thread_spawn: thread {} stopped: {}", id, e);
+            }
+        });
+    if spawned.is_err() {
+        return -(__WASI_EAGAIN as i32);
+    }
+
+    id as i32
+}
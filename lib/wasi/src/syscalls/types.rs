@@ -701,3 +701,259 @@ pub type __wasi_whence_t = u8;
 pub const __WASI_WHENCE_CUR: u8 = 0;
 pub const __WASI_WHENCE_END: u8 = 1;
 pub const __WASI_WHENCE_SET: u8 = 2;
+
+/// The types of `wasi_snapshot_preview1` whose layout or values differ from `wasi_unstable`.
+pub mod snapshot1 {
+    use super::*;
+
+    pub type __wasi_whence_t = u8;
+    pub const __WASI_WHENCE_SET: u8 = 0;
+    pub const __WASI_WHENCE_CUR: u8 = 1;
+    pub const __WASI_WHENCE_END: u8 = 2;
+
+    /// Returns the `wasi_unstable` value of `whence`.
+    pub fn whence_to_unstable(
+        whence: __wasi_whence_t,
+    ) -> Result<super::__wasi_whence_t, __wasi_errno_t> {
+        match whence {
+            __WASI_WHENCE_SET => Ok(super::__WASI_WHENCE_SET),
+            __WASI_WHENCE_CUR => Ok(super::__WASI_WHENCE_CUR),
+            __WASI_WHENCE_END => Ok(super::__WASI_WHENCE_END),
+            _ => Err(__WASI_EINVAL),
+        }
+    }
+
+    pub type __wasi_linkcount_t = u64;
+
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    #[repr(C)]
+    pub struct __wasi_filestat_t {
+        pub st_dev: __wasi_device_t,
+        pub st_ino: __wasi_inode_t,
+        pub st_filetype: __wasi_filetype_t,
+        pub st_nlink: __wasi_linkcount_t,
+        pub st_size: __wasi_filesize_t,
+        pub st_atim: __wasi_timestamp_t,
+        pub st_mtim: __wasi_timestamp_t,
+        pub st_ctim: __wasi_timestamp_t,
+    }
+
+    impl From<super::__wasi_filestat_t> for __wasi_filestat_t {
+        fn from(stat: super::__wasi_filestat_t) -> Self {
+            __wasi_filestat_t {
+                st_dev: stat.st_dev,
+                st_ino: stat.st_ino,
+                st_filetype: stat.st_filetype,
+                st_nlink: stat.st_nlink as __wasi_linkcount_t,
+                st_size: stat.st_size,
+                st_atim: stat.st_atim,
+                st_mtim: stat.st_mtim,
+                st_ctim: stat.st_ctim,
+            }
+        }
+    }
+
+    unsafe impl ValueType for __wasi_filestat_t {}
+
+    /// The clock subscription, which lost the `identifier` of `wasi_unstable`.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    #[repr(C)]
+    pub struct __wasi_subscription_clock_t {
+        pub clock_id: __wasi_clockid_t,
+        pub timeout: __wasi_timestamp_t,
+        pub precision: __wasi_timestamp_t,
+        pub flags: __wasi_subclockflags_t,
+    }
+
+    #[derive(Copy, Clone)]
+    #[repr(C)]
+    pub union __wasi_subscription_u {
+        clock: __wasi_subscription_clock_t,
+        fd_readwrite: __wasi_subscription_fs_readwrite_t,
+    }
+
+    #[derive(Copy, Clone)]
+    #[repr(C)]
+    pub struct __wasi_subscription_t {
+        pub userdata: __wasi_userdata_t,
+        pub type_: __wasi_eventtype_t,
+        pub u: __wasi_subscription_u,
+    }
+
+    unsafe impl ValueType for __wasi_subscription_t {}
+
+    impl std::convert::TryFrom<__wasi_subscription_t> for super::__wasi_subscription_t {
+        type Error = __wasi_errno_t;
+
+        fn try_from(ws: __wasi_subscription_t) -> Result<Self, Self::Error> {
+            let u = match ws.type_ {
+                __WASI_EVENTTYPE_CLOCK => {
+                    let clock = unsafe { ws.u.clock };
+                    super::__wasi_subscription_u {
+                        clock: super::__wasi_subscription_clock_t {
+                            // The identifier has no counterpart in `wasi_snapshot_preview1`.
+                            userdata: 0,
+                            clock_id: clock.clock_id,
+                            timeout: clock.timeout,
+                            precision: clock.precision,
+                            flags: clock.flags,
+                        },
+                    }
+                }
+                __WASI_EVENTTYPE_FD_READ | __WASI_EVENTTYPE_FD_WRITE => {
+                    super::__wasi_subscription_u {
+                        fd_readwrite: unsafe { ws.u.fd_readwrite },
+                    }
+                }
+                _ => return Err(__WASI_EINVAL),
+            };
+
+            Ok(Self {
+                userdata: ws.userdata,
+                type_: ws.type_,
+                u,
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::convert::TryInto;
+        use std::mem::{size_of, zeroed};
+
+        /// The offset of the field at `field` in the value at `base`.
+        fn offset<T, F>(base: &T, field: &F) -> usize {
+            field as *const F as usize - base as *const T as usize
+        }
+
+        #[test]
+        fn test_filestat_layout() {
+            let stat: __wasi_filestat_t = unsafe { zeroed() };
+            assert_eq!(size_of::<__wasi_filestat_t>(), 64);
+            assert_eq!(offset(&stat, &stat.st_filetype), 16);
+            assert_eq!(offset(&stat, &stat.st_nlink), 24);
+            assert_eq!(offset(&stat, &stat.st_size), 32);
+            assert_eq!(offset(&stat, &stat.st_ctim), 56);
+
+            // The link count of `wasi_unstable` is only 32 bits wide.
+            let stat: super::__wasi_filestat_t = unsafe { zeroed() };
+            assert_eq!(size_of::<super::__wasi_filestat_t>(), 56);
+            assert_eq!(offset(&stat, &stat.st_nlink), 20);
+            assert_eq!(offset(&stat, &stat.st_size), 24);
+        }
+
+        #[test]
+        fn test_filestat_conversion() {
+            let stat = super::__wasi_filestat_t {
+                st_dev: 1,
+                st_ino: 2,
+                st_filetype: __WASI_FILETYPE_REGULAR_FILE,
+                st_nlink: u32::max_value(),
+                st_size: 4,
+                st_atim: 5,
+                st_mtim: 6,
+                st_ctim: 7,
+            };
+            assert_eq!(
+                __wasi_filestat_t::from(stat),
+                __wasi_filestat_t {
+                    st_dev: 1,
+                    st_ino: 2,
+                    st_filetype: __WASI_FILETYPE_REGULAR_FILE,
+                    st_nlink: u32::max_value() as u64,
+                    st_size: 4,
+                    st_atim: 5,
+                    st_mtim: 6,
+                    st_ctim: 7,
+                }
+            );
+        }
+
+        #[test]
+        fn test_whence_remap() {
+            assert_eq!(
+                whence_to_unstable(__WASI_WHENCE_SET),
+                Ok(super::__WASI_WHENCE_SET)
+            );
+            assert_eq!(
+                whence_to_unstable(__WASI_WHENCE_CUR),
+                Ok(super::__WASI_WHENCE_CUR)
+            );
+            assert_eq!(
+                whence_to_unstable(__WASI_WHENCE_END),
+                Ok(super::__WASI_WHENCE_END)
+            );
+            assert_eq!(whence_to_unstable(3), Err(__WASI_EINVAL));
+        }
+
+        #[test]
+        fn test_subscription_layout() {
+            let sub: __wasi_subscription_t = unsafe { zeroed() };
+            assert_eq!(size_of::<__wasi_subscription_t>(), 48);
+            assert_eq!(offset(&sub, &sub.type_), 8);
+            assert_eq!(offset(&sub, &sub.u), 16);
+
+            let clock: __wasi_subscription_clock_t = unsafe { zeroed() };
+            assert_eq!(size_of::<__wasi_subscription_clock_t>(), 32);
+            assert_eq!(offset(&clock, &clock.timeout), 8);
+            assert_eq!(offset(&clock, &clock.precision), 16);
+            assert_eq!(offset(&clock, &clock.flags), 24);
+
+            // The clock of `wasi_unstable` starts with its identifier.
+            assert_eq!(size_of::<super::__wasi_subscription_t>(), 56);
+            assert_eq!(size_of::<super::__wasi_subscription_clock_t>(), 40);
+        }
+
+        #[test]
+        fn test_subscription_conversion() {
+            let clock = __wasi_subscription_t {
+                userdata: 42,
+                type_: __WASI_EVENTTYPE_CLOCK,
+                u: __wasi_subscription_u {
+                    clock: __wasi_subscription_clock_t {
+                        clock_id: __WASI_CLOCK_MONOTONIC,
+                        timeout: 1_000,
+                        precision: 10,
+                        flags: __WASI_SUBSCRIPTION_CLOCK_ABSTIME,
+                    },
+                },
+            };
+            let sub: super::__wasi_subscription_t = clock.try_into().unwrap();
+            let sub: WasiSubscription = sub.try_into().unwrap();
+            assert_eq!(sub.user_data, 42);
+            match sub.event_type {
+                EventType::Clock(clock) => assert_eq!(
+                    clock,
+                    super::__wasi_subscription_clock_t {
+                        userdata: 0,
+                        clock_id: __WASI_CLOCK_MONOTONIC,
+                        timeout: 1_000,
+                        precision: 10,
+                        flags: __WASI_SUBSCRIPTION_CLOCK_ABSTIME,
+                    }
+                ),
+                other => panic!("unexpected event type: {:?}", other),
+            }
+
+            let read = __wasi_subscription_t {
+                userdata: 7,
+                type_: __WASI_EVENTTYPE_FD_READ,
+                u: __wasi_subscription_u {
+                    fd_readwrite: __wasi_subscription_fs_readwrite_t { fd: 3 },
+                },
+            };
+            let sub: super::__wasi_subscription_t = read.try_into().unwrap();
+            let sub: WasiSubscription = sub.try_into().unwrap();
+            assert_eq!(sub.user_data, 7);
+            match sub.event_type {
+                EventType::Read(read) => assert_eq!(read.fd, 3),
+                other => panic!("unexpected event type: {:?}", other),
+            }
+
+            let invalid = __wasi_subscription_t { type_: 3, ..read };
+            let sub: Result<super::__wasi_subscription_t, _> = invalid.try_into();
+            assert_eq!(sub.err(), Some(__WASI_EINVAL));
+        }
+    }
+}
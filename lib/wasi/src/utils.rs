@@ -1,4 +1,35 @@
-use wasmer_runtime_core::module::Module;
+use wasmer_runtime_core::{
+    export::Export,
+    module::{ExternDescriptor, Module},
+    types::FuncSig,
+};
+
+/// The versions of WASI, each imported from its own namespace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasiVersion {
+    /// `wasi_unstable`, also known as snapshot 0.
+    Snapshot0,
+    /// `wasi_snapshot_preview1`.
+    Snapshot1,
+}
+
+impl WasiVersion {
+    /// Returns the namespace the functions of this version are imported from.
+    pub fn namespace(self) -> &'static str {
+        match self {
+            WasiVersion::Snapshot0 => "wasi_unstable",
+            WasiVersion::Snapshot1 => "wasi_snapshot_preview1",
+        }
+    }
+
+    fn from_namespace(namespace: &str) -> Option<Self> {
+        match namespace {
+            "wasi_unstable" => Some(WasiVersion::Snapshot0),
+            "wasi_snapshot_preview1" => Some(WasiVersion::Snapshot1),
+            _ => None,
+        }
+    }
+}
 
 /// Check if a provided module is compiled with WASI support
 pub fn is_wasi_module(module: &Module) -> bool {
@@ -10,9 +41,154 @@ pub fn is_wasi_module(module: &Module) -> bool {
             .info()
             .namespace_table
             .get(import_name.namespace_index);
-        if namespace != "wasi_unstable" {
+        if WasiVersion::from_namespace(namespace).is_none() {
             return false;
         }
     }
     true
 }
+
+/// Returns the version of WASI the module imports functions from, or `None` if it doesn't
+/// import from WASI.
+///
+/// In strict mode, the module must import from a single version of WASI, and from nothing
+/// else, to have one.  Otherwise the newest version it imports from is returned: the import
+/// objects of this crate provide every version, so a module importing from several versions
+/// links as well.
+pub fn get_wasi_version(module: &Module, strict: bool) -> Option<WasiVersion> {
+    let mut version = None;
+    for (_, import_name) in &module.info().imported_functions {
+        let namespace = module
+            .info()
+            .namespace_table
+            .get(import_name.namespace_index);
+        match (WasiVersion::from_namespace(namespace), version) {
+            (None, _) if strict => return None,
+            (None, _) => {}
+            (Some(found), Some(seen)) if strict && found != seen => return None,
+            (Some(WasiVersion::Snapshot1), _) => version = Some(WasiVersion::Snapshot1),
+            (Some(WasiVersion::Snapshot0), None) => version = Some(WasiVersion::Snapshot0),
+            (Some(WasiVersion::Snapshot0), Some(_)) => {}
+        }
+    }
+    version
+}
+
+/// An import of a module that WASI can't provide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WasiImportError {
+    /// The module imports a function its version of WASI doesn't have
+    UnknownFunction {
+        namespace: String,
+        name: String,
+        /// The version of WASI providing a function of that name, if there's one
+        suggested_version: Option<WasiVersion>,
+    },
+    /// The module imports a function of WASI with the wrong signature
+    SignatureMismatch {
+        namespace: String,
+        name: String,
+        expected: FuncSig,
+        found: FuncSig,
+    },
+    /// The module imports something other than a function from WASI
+    NotAFunction { namespace: String, name: String },
+}
+
+impl std::fmt::Display for WasiImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WasiImportError::UnknownFunction {
+                namespace,
+                name,
+                suggested_version,
+            } => {
+                write!(
+                    f,
+                    "\"{}\".\"{}\" is not a function of WASI",
+                    namespace, name
+                )?;
+                if let Some(version) = suggested_version {
+                    write!(
+                        f,
+                        ", did you mean to import it from \"{}\"?",
+                        version.namespace()
+                    )?;
+                }
+                Ok(())
+            }
+            WasiImportError::SignatureMismatch {
+                namespace,
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "\"{}\".\"{}\" is imported as {}, but WASI provides it as {}",
+                namespace, name, found, expected
+            ),
+            WasiImportError::NotAFunction { namespace, name } => write!(
+                f,
+                "\"{}\".\"{}\" is imported as something other than a function",
+                namespace, name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WasiImportError {}
+
+/// Checks that the imports of the module from WASI are all provided by the import objects of
+/// this crate, before instantiating it.
+///
+/// Instantiating a module importing a function WASI doesn't have fails with a generic link
+/// error; this names the import, and the version of WASI having it if there's one.  Imports
+/// from outside of WASI aren't checked.
+pub fn check_wasi_imports(module: &Module) -> Result<(), WasiImportError> {
+    let import_object = crate::generate_import_object(vec![], vec![], vec![], vec![]);
+    let get_function = |namespace: &str, name: &str| {
+        let export = import_object.maybe_with_namespace(namespace, |ns| ns.get_export(name));
+        match export {
+            Some(Export::Function { signature, .. }) => Some(signature),
+            _ => None,
+        }
+    };
+
+    for import in module.imports() {
+        if WasiVersion::from_namespace(&import.namespace).is_none() {
+            continue;
+        }
+        let found = match import.ty {
+            ExternDescriptor::Function(sig) => sig,
+            _ => {
+                return Err(WasiImportError::NotAFunction {
+                    namespace: import.namespace,
+                    name: import.name,
+                })
+            }
+        };
+        match get_function(&import.namespace, &import.name) {
+            Some(expected) if *expected != found => {
+                return Err(WasiImportError::SignatureMismatch {
+                    namespace: import.namespace,
+                    name: import.name,
+                    expected: (*expected).clone(),
+                    found,
+                })
+            }
+            Some(_) => {}
+            None => {
+                let suggested_version = [WasiVersion::Snapshot1, WasiVersion::Snapshot0]
+                    .iter()
+                    .cloned()
+                    .find(|version| get_function(version.namespace(), &import.name).is_some());
+                return Err(WasiImportError::UnknownFunction {
+                    namespace: import.namespace,
+                    name: import.name,
+                    suggested_version,
+                });
+            }
+        }
+    }
+    Ok(())
+}
@@ -13,23 +13,30 @@ use std::env;
 use std::fs::{read_to_string, File};
 use std::io;
 use std::io::Read;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::process::exit;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use std::collections::HashMap;
 use structopt::StructOpt;
 
+use wasmer::inspect::ModuleSummary;
+use wasmer::webassembly::InstanceABI;
 use wasmer::*;
 use wasmer_clif_backend::CraneliftCompiler;
 #[cfg(feature = "backend-llvm")]
 use wasmer_llvm_backend::{LLVMCompiler, LLVMOptions};
 use wasmer_runtime::{
     cache::{Cache as BaseCache, FileSystemCache, WasmHash},
-    Func, Value, VERSION,
+    differential::compare_backends,
+    enabled_backends, Func, Value, VERSION,
 };
 #[cfg(feature = "managed")]
-use wasmer_runtime_core::tiering::{run_tiering, InteractiveShellContext, ShellExitOperation};
+use wasmer_runtime_core::tiering::{
+    run_tiering, HotnessCounter, HotnessThreshold, InteractiveShellContext, ShellExitOperation,
+};
 use wasmer_runtime_core::{
     self,
     backend::{Backend, Compiler, CompilerConfig, Features, MemoryBoundCheckMode},
@@ -50,6 +57,10 @@ mod wasmer_wasi {
         false
     }
 
+    pub fn check_wasi_imports(_module: &Module) -> Result<(), String> {
+        Ok(())
+    }
+
     pub fn generate_import_object(
         _args: Vec<Vec<u8>>,
         _envs: Vec<Vec<u8>>,
@@ -72,10 +83,14 @@ enum CLIOptions {
     #[structopt(name = "cache")]
     Cache(Cache),
 
-    /// Validate a Web Assembly binary
+    /// Validate a WebAssembly file without compiling it. Formats accepted: wasm, wat
     #[structopt(name = "validate")]
     Validate(Validate),
 
+    /// Print the imports, exports and requirements of a WebAssembly module
+    #[structopt(name = "inspect")]
+    Inspect(Inspect),
+
     /// Update wasmer to the latest version
     #[structopt(name = "self-update")]
     SelfUpdate,
@@ -91,6 +106,10 @@ struct PrestandardFeatures {
     #[structopt(long = "enable-threads")]
     threads: bool,
 
+    /// Enable support for the multi-value proposal.
+    #[structopt(long = "enable-multi-value")]
+    multi_value: bool,
+
     /// Enable support for all pre-standard proposals.
     #[structopt(long = "enable-all")]
     all: bool,
@@ -111,6 +130,11 @@ pub struct LLVMCLIOptions {
     /// Emit LLVM generated native code object file.
     #[structopt(long = "llvm-object-file", parse(from_os_str))]
     obj_file: Option<PathBuf>,
+
+    /// Compile the functions with more operators than this without optimizations, to keep
+    /// the compile time of huge functions bounded.
+    #[structopt(long = "llvm-huge-function-threshold")]
+    huge_function_threshold: Option<usize>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -136,6 +160,16 @@ struct Run {
     #[structopt(long = "invoke", short = "i")]
     invoke: Option<String>,
 
+    /// Also run the invoked function with this backend and compare the results, traps and
+    /// final memory with the main backend. `auto` picks another enabled backend supporting the
+    /// module
+    #[structopt(
+        long = "differential-backend",
+        case_insensitive = true,
+        possible_values = Backend::variants(),
+    )]
+    differential_backend: Option<Backend>,
+
     /// Emscripten symbol map
     #[structopt(long = "em-symbol-map", parse(from_os_str), group = "emscripten")]
     em_symbol_map: Option<PathBuf>,
@@ -144,6 +178,18 @@ struct Run {
     #[structopt(long = "em-entrypoint", group = "emscripten")]
     em_entrypoint: Option<String>,
 
+    /// Emscripten memory initializer, the `.mem` file of the programs built with threads
+    #[structopt(long = "em-mem-init", parse(from_os_str), group = "emscripten")]
+    em_mem_init: Option<PathBuf>,
+
+    /// Let the emscripten program connect to this address, like `127.0.0.1:8080`
+    #[structopt(long = "em-allow-connect", multiple = true)]
+    em_allow_connect: Vec<SocketAddr>,
+
+    /// Let the emscripten program bind to this address, to listen on it
+    #[structopt(long = "em-allow-listen", multiple = true)]
+    em_allow_listen: Vec<SocketAddr>,
+
     /// WASI pre-opened directory
     #[structopt(long = "dir", multiple = true, group = "wasi")]
     pre_opened_directories: Vec<PathBuf>,
@@ -179,11 +225,22 @@ struct Run {
     )]
     optimized_backends: Vec<Backend>,
 
+    /// Number of function entries in the baseline code after which the optimized backends
+    /// start compiling. By default they start immediately.
+    #[cfg(feature = "managed")]
+    #[structopt(long = "tier-up-threshold")]
+    tier_up_threshold: Option<u64>,
+
     /// Whether or not state tracking should be disabled during compilation.
     /// State tracking is necessary for tier switching and backtracing.
     #[structopt(long = "track-state")]
     track_state: bool,
 
+    /// Generate debug info for the compiled code, so that debuggers can map it back to the
+    /// wasm module. Implies `--disable-cache`, since debug info is not cached.
+    #[structopt(long = "generate-debug-info", short = "g")]
+    generate_debug_info: bool,
+
     /// The command name is a string that will override the first argument passed
     /// to the wasm program. This is used in wapm to provide nicer output in
     /// help commands and error messages of the running wasm program
@@ -251,7 +308,7 @@ enum Cache {
 
 #[derive(Debug, StructOpt)]
 struct Validate {
-    /// Input file
+    /// Input file. Formats accepted: wasm, wat
     #[structopt(parse(from_os_str))]
     path: PathBuf,
 
@@ -259,6 +316,13 @@ struct Validate {
     features: PrestandardFeatures,
 }
 
+#[derive(Debug, StructOpt)]
+struct Inspect {
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+}
+
 /// Read the contents of a file
 fn read_file_contents(path: &PathBuf) -> Result<Vec<u8>, io::Error> {
     let mut buffer: Vec<u8> = Vec::new();
@@ -329,7 +393,7 @@ fn get_env_var_args(input: &[String]) -> Result<Vec<(&str, &str)>, String> {
 
 /// Execute a wasm/wat file
 fn execute_wasm(options: &Run) -> Result<(), String> {
-    let disable_cache = options.disable_cache;
+    let disable_cache = options.disable_cache || options.generate_debug_info;
 
     let mapped_dirs = get_mapped_dirs(&options.mapped_dirs[..])?;
     let env_vars = get_env_var_args(&options.env_vars[..])?;
@@ -387,11 +451,6 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         None
     };
 
-    // Don't error on --enable-all for other backends.
-    if options.features.simd && options.backend != Backend::LLVM {
-        return Err("SIMD is only supported in the LLVM backend for now".to_string());
-    }
-
     if !utils::is_wasm_binary(&wasm_binary) {
         let mut features = wabt::Features::new();
         if options.features.simd || options.features.all {
@@ -400,18 +459,54 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         if options.features.threads || options.features.all {
             features.enable_threads();
         }
+        if options.features.multi_value || options.features.all {
+            features.enable_multi_value();
+        }
         wasm_binary = wabt::wat2wasm_with_features(wasm_binary, features)
             .map_err(|e| format!("Can't convert from wast to wasm: {:?}", e))?;
     }
 
-    let compiler: Box<dyn Compiler> = match get_compiler_by_backend(options.backend) {
+    let requested_features = Features {
+        simd: options.features.simd,
+        threads: options.features.threads,
+        multi_value: options.features.multi_value,
+    };
+    let backend = match options.backend {
+        Backend::Auto => Backend::select(&wasm_binary, &requested_features, &enabled_backends())
+            .ok_or_else(|| "no enabled backend supports the requested features".to_string())?,
+        backend => backend,
+    };
+
+    // Don't error on --enable-all for other backends.
+    if options.features.simd && backend != Backend::LLVM && backend != Backend::Cranelift {
+        return Err(
+            "SIMD is only supported in the LLVM and Cranelift backends for now".to_string(),
+        );
+    }
+
+    if options.features.multi_value && backend != Backend::Cranelift {
+        return Err("multi-value is only supported in the Cranelift backend for now".to_string());
+    }
+
+    let compiler: Box<dyn Compiler> = match get_compiler_by_backend(backend) {
         Some(x) => x,
         None => return Err("the requested backend is not enabled".into()),
     };
 
+    // Count function entries in the baseline code so that tiering can wait for it to get hot.
+    #[cfg(feature = "managed")]
+    let compiler: Box<dyn Compiler> =
+        if options.tier_up_threshold.is_some() && backend == Backend::Singlepass {
+            Box::new(get_entry_counting_singlepass_compiler(
+                options.tier_up_threshold.unwrap(),
+            ))
+        } else {
+            compiler
+        };
+
     #[cfg(feature = "backend-llvm")]
     {
-        if options.backend == Backend::LLVM {
+        if backend == Backend::LLVM {
             let options = options.backend_llvm_options.clone();
             unsafe {
                 wasmer_llvm_backend::GLOBAL_OPTIONS = LLVMOptions {
@@ -425,6 +520,27 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
 
     let track_state = options.track_state;
 
+    #[cfg(feature = "backend-llvm")]
+    let huge_function_threshold = options.backend_llvm_options.huge_function_threshold;
+    #[cfg(not(feature = "backend-llvm"))]
+    let huge_function_threshold = None;
+
+    // The configuration of every compilation of the module, including the differential one.
+    let generate_debug_info = options.generate_debug_info;
+    let features = Features {
+        simd: options.features.simd || options.features.all,
+        threads: options.features.threads || options.features.all,
+        multi_value: options.features.multi_value || options.features.all,
+    };
+    let compiler_config = || CompilerConfig {
+        symbol_map: em_symbol_map.clone(),
+        track_state,
+        huge_function_threshold,
+        generate_debug_info,
+        features: features.clone(),
+        ..Default::default()
+    };
+
     #[cfg(feature = "loader-kernel")]
     let is_kernel_loader = if let Some(LoaderName::Kernel) = options.loader {
         true
@@ -439,34 +555,16 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         webassembly::compile_with_config_with(
             &wasm_binary[..],
             CompilerConfig {
-                symbol_map: em_symbol_map.clone(),
                 memory_bound_check_mode: MemoryBoundCheckMode::Disable,
                 enforce_stack_check: true,
-                track_state,
-                features: Features {
-                    simd: options.features.simd || options.features.all,
-                    threads: options.features.threads || options.features.all,
-                },
-                ..Default::default()
+                ..compiler_config()
             },
             &*compiler,
         )
         .map_err(|e| format!("Can't compile module: {:?}", e))?
     } else if disable_cache {
-        webassembly::compile_with_config_with(
-            &wasm_binary[..],
-            CompilerConfig {
-                symbol_map: em_symbol_map.clone(),
-                track_state,
-                features: Features {
-                    simd: options.features.simd || options.features.all,
-                    threads: options.features.threads || options.features.all,
-                },
-                ..Default::default()
-            },
-            &*compiler,
-        )
-        .map_err(|e| format!("Can't compile module: {:?}", e))?
+        webassembly::compile_with_config_with(&wasm_binary[..], compiler_config(), &*compiler)
+            .map_err(|e| format!("Can't compile module: {:?}", e))?
     } else {
         // If we have cache enabled
         let wasmer_cache_dir = get_cache_dir();
@@ -479,10 +577,8 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         };
         let mut load_cache_key = || -> Result<_, String> {
             if let Some(ref prehashed_cache_key) = options.cache_key {
-                if let Ok(module) =
-                    WasmHash::decode(prehashed_cache_key).and_then(|prehashed_key| {
-                        cache.load_with_backend(prehashed_key, options.backend)
-                    })
+                if let Ok(module) = WasmHash::decode(prehashed_cache_key)
+                    .and_then(|prehashed_key| cache.load_with_backend(prehashed_key, backend))
                 {
                     debug!("using prehashed key: {}", prehashed_cache_key);
                     return Ok(module);
@@ -495,7 +591,7 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
             // cache.load will return the Module if it's able to deserialize it properly, and an error if:
             // * The file is not found
             // * The file exists, but it's corrupted or can't be converted to a module
-            match cache.load_with_backend(hash, options.backend) {
+            match cache.load_with_backend(hash, backend) {
                 Ok(module) => {
                     // We are able to load the module from cache
                     Ok(module)
@@ -503,15 +599,7 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                 Err(_) => {
                     let module = webassembly::compile_with_config_with(
                         &wasm_binary[..],
-                        CompilerConfig {
-                            symbol_map: em_symbol_map.clone(),
-                            track_state,
-                            features: Features {
-                                simd: options.features.simd || options.features.all,
-                                threads: options.features.threads || options.features.all,
-                            },
-                            ..Default::default()
-                        },
+                        compiler_config(),
                         &*compiler,
                     )
                     .map_err(|e| format!("Can't compile module: {:?}", e))?;
@@ -567,7 +655,42 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
     // TODO: refactor this
     if wasmer_emscripten::is_emscripten_module(&module) {
         let mut emscripten_globals = wasmer_emscripten::EmscriptenGlobals::new(&module)?;
+        // side modules loaded with `dlopen` are compiled by the backend of the main module
+        let features = features.clone();
+        if let Some(em_mem_init_path) = &options.em_mem_init {
+            let em_mem_init = std::fs::read(em_mem_init_path).map_err(|err| {
+                format!(
+                    "Can't read memory initializer file {}: {}",
+                    em_mem_init_path.as_os_str().to_string_lossy(),
+                    err,
+                )
+            })?;
+            wasmer_emscripten::emscripten_load_memory_initializer(
+                &emscripten_globals.memory,
+                &em_mem_init,
+            )?;
+        }
+        for addr in &options.em_allow_connect {
+            emscripten_globals.network.allow_connect(*addr);
+        }
+        for addr in &options.em_allow_listen {
+            emscripten_globals.network.allow_listen(*addr);
+        }
+        emscripten_globals.side_module_compiler = Some(Arc::new(move |wasm: &[u8]| {
+            let compiler = get_compiler_by_backend(backend)
+                .ok_or_else(|| "the requested backend is not enabled".to_string())?;
+            webassembly::compile_with_config_with(
+                wasm,
+                CompilerConfig {
+                    features: features.clone(),
+                    ..Default::default()
+                },
+                &*compiler,
+            )
+            .map_err(|e| format!("Can't compile side module: {:?}", e))
+        }));
         let import_object = wasmer_emscripten::generate_emscripten_env(&mut emscripten_globals);
+        wasmer_emscripten::check_emscripten_imports(&module, &import_object)?;
         let mut instance = module
             .instantiate(&import_object)
             .map_err(|e| format!("Can't instantiate emscripten module: {:?}", e))?;
@@ -588,6 +711,8 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         .map_err(|e| format!("{:?}", e))?;
     } else {
         if cfg!(feature = "wasi") && wasmer_wasi::is_wasi_module(&module) {
+            wasmer_wasi::check_wasi_imports(&module)
+                .map_err(|e| format!("Can't instantiate WASI module: {}", e))?;
             let import_object = wasmer_wasi::generate_import_object(
                 if let Some(cn) = &options.command_name {
                     [cn.clone()]
@@ -634,7 +759,6 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                         } else {
                             None
                         },
-                        &import_object,
                         start_raw,
                         &mut instance,
                         options
@@ -644,6 +768,9 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                                 Box::new(move || get_compiler_by_backend(backend).unwrap())
                             })
                             .collect(),
+                        options
+                            .tier_up_threshold
+                            .map(|entries| HotnessThreshold { entries }),
                         interactive_shell,
                     )?
                 };
@@ -656,23 +783,26 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
 
                 if let Err(ref err) = result {
                     match err {
-                        RuntimeError::Trap { msg } => {
+                        RuntimeError::Trap { msg, .. } => {
                             return Err(format!("wasm trap occured: {}", msg))
                         }
                         #[cfg(feature = "wasi")]
-                        RuntimeError::Error { data } => {
-                            if let Some(error_code) = data.downcast_ref::<wasmer_wasi::ExitCode>() {
-                                std::process::exit(error_code.code as i32)
+                        RuntimeError::Error { .. } => {
+                            if let Some(error) = wasmer_wasi::WasiError::from_runtime_error(err) {
+                                std::process::exit(error.exit_code())
                             }
                         }
                         #[cfg(not(feature = "wasi"))]
                         RuntimeError::Error { .. } => (),
+                        RuntimeError::Interrupted => (),
                     }
                     return Err(format!("error: {:?}", err));
                 }
             }
         } else {
-            let import_object = wasmer_runtime_core::import::ImportObject::new();
+            // The differential run instantiates the module again with the same imports.
+            let new_import_object = wasmer_runtime_core::import::ImportObject::new;
+            let import_object = new_import_object();
             let instance = module
                 .instantiate(&import_object)
                 .map_err(|e| format!("Can't instantiate module: {:?}", e))?;
@@ -692,6 +822,57 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                 Some(fun) => fun,
                 _ => "main",
             };
+
+            if let Some(other_backend) = options.differential_backend {
+                let other_backend = match other_backend {
+                    // Any other enabled backend that supports the module.
+                    Backend::Auto => {
+                        let others: Vec<Backend> = enabled_backends()
+                            .into_iter()
+                            .filter(|other| *other != backend)
+                            .collect();
+                        let selected = Backend::select(&wasm_binary, &requested_features, &others);
+                        selected.ok_or_else(|| {
+                            format!(
+                                "no enabled backend but {} supports the requested features",
+                                backend.to_string()
+                            )
+                        })?
+                    }
+                    other_backend => other_backend,
+                };
+                let other_compiler = match get_compiler_by_backend(other_backend) {
+                    Some(x) => x,
+                    None => {
+                        return Err(format!(
+                            "the differential backend {} is not enabled",
+                            other_backend.to_string()
+                        ))
+                    }
+                };
+                let report = compare_backends(
+                    &wasm_binary,
+                    &*get_compiler_by_backend(backend).unwrap(),
+                    &*other_compiler,
+                    &compiler_config(),
+                    &new_import_object,
+                    invoke_fn,
+                    &args,
+                )
+                .map_err(|e| format!("{:?}", e))?;
+                let differences = report.differences();
+                if !differences.is_empty() {
+                    return Err(format!(
+                        "{} and {} disagree:\n{}",
+                        backend.to_string(),
+                        other_backend.to_string(),
+                        differences.join("\n")
+                    ));
+                }
+                println!("{:?}", report.left.result);
+                return Ok(());
+            }
+
             instance
                 .dyn_func(&invoke_fn)
                 .map_err(|e| format!("{:?}", e))?
@@ -788,9 +969,13 @@ fn run(options: Run) {
 
 fn validate_wasm(validate: Validate) -> Result<(), String> {
     let wasm_path = validate.path;
-    let wasm_path_as_str = wasm_path.to_str().unwrap();
+    let features = Features {
+        simd: validate.features.simd || validate.features.all,
+        threads: validate.features.threads || validate.features.all,
+        multi_value: validate.features.multi_value || validate.features.all,
+    };
 
-    let wasm_binary: Vec<u8> = read_file_contents(&wasm_path).map_err(|err| {
+    let mut wasm_binary: Vec<u8> = read_file_contents(&wasm_path).map_err(|err| {
         format!(
             "Can't read the file {}: {}",
             wasm_path.as_os_str().to_string_lossy(),
@@ -799,20 +984,26 @@ fn validate_wasm(validate: Validate) -> Result<(), String> {
     })?;
 
     if !utils::is_wasm_binary(&wasm_binary) {
-        return Err(format!(
-            "Cannot recognize \"{}\" as a WASM binary",
-            wasm_path_as_str,
-        ));
+        let mut wabt_features = wabt::Features::new();
+        if features.simd {
+            wabt_features.enable_simd();
+        }
+        if features.threads {
+            wabt_features.enable_threads();
+        }
+        if features.multi_value {
+            wabt_features.enable_multi_value();
+        }
+        wasm_binary = wabt::wat2wasm_with_features(wasm_binary, wabt_features)
+            .map_err(|e| format!("Can't convert from wast to wasm: {:?}", e))?;
     }
 
-    wasmer_runtime_core::validate_and_report_errors_with_features(
-        &wasm_binary,
-        Features {
-            simd: validate.features.simd || validate.features.all,
-            threads: validate.features.threads || validate.features.all,
-        },
-    )
-    .map_err(|err| format!("Validation failed: {}", err))?;
+    wasmer_runtime_core::validate_with_features(&wasm_binary, &features).map_err(|err| {
+        format!(
+            "Validation failed at offset {}: {}",
+            err.offset, err.message
+        )
+    })?;
 
     Ok(())
 }
@@ -828,6 +1019,71 @@ fn validate(validate: Validate) {
     }
 }
 
+fn inspect_wasm(inspect: Inspect) -> Result<(), String> {
+    let wasm_path = inspect.path;
+    let mut wasm_binary: Vec<u8> = read_file_contents(&wasm_path).map_err(|err| {
+        format!(
+            "Can't read the file {}: {}",
+            wasm_path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+
+    if !utils::is_wasm_binary(&wasm_binary) {
+        let mut features = wabt::Features::new();
+        features.enable_simd();
+        features.enable_threads();
+        features.enable_multi_value();
+        wasm_binary = wabt::wat2wasm_with_features(wasm_binary, features)
+            .map_err(|e| format!("Can't convert from wast to wasm: {:?}", e))?;
+    }
+
+    let summary = ModuleSummary::parse(&wasm_binary)
+        .map_err(|err| format!("Can't read the module: {}", err))?;
+
+    let abi = match summary.abi {
+        InstanceABI::Emscripten => "emscripten",
+        InstanceABI::WASI => "wasi",
+        InstanceABI::None => "none",
+    };
+    println!("ABI: {}", abi);
+    println!("Required features: {}", summary.features);
+
+    println!("Imports:");
+    for import in &summary.imports {
+        println!(
+            "  \"{}\" \"{}\": {}",
+            import.namespace, import.name, import.ty
+        );
+    }
+    println!("Exports:");
+    for export in &summary.exports {
+        println!("  \"{}\": {}", export.name, export.ty);
+    }
+    println!("Memories:");
+    for (index, memory) in summary.memories.iter().enumerate() {
+        println!("  {}: {} pages", index, memory);
+    }
+    println!("Tables:");
+    for (index, table) in summary.tables.iter().enumerate() {
+        println!("  {}: {}", index, table);
+    }
+    println!("Custom sections:");
+    for (name, len) in &summary.custom_sections {
+        println!("  \"{}\": {} bytes", name, len);
+    }
+
+    Ok(())
+}
+
+/// Runs logic for the `inspect` subcommand
+fn inspect(inspect: Inspect) {
+    if let Err(message) = inspect_wasm(inspect) {
+        eprintln!("Error: {}", message);
+        exit(-1);
+    }
+}
+
 fn get_compiler_by_backend(backend: Backend) -> Option<Box<dyn Compiler>> {
     Some(match backend {
         #[cfg(feature = "backend-singlepass")]
@@ -839,9 +1095,23 @@ fn get_compiler_by_backend(backend: Backend) -> Option<Box<dyn Compiler>> {
         Backend::LLVM => Box::new(LLVMCompiler::new()),
         #[cfg(not(feature = "backend-llvm"))]
         Backend::LLVM => return None,
+        Backend::Auto => return None,
     })
 }
 
+#[cfg(feature = "managed")]
+fn get_entry_counting_singlepass_compiler(threshold: u64) -> impl Compiler {
+    use wasmer_runtime_core::codegen::{MiddlewareChain, StreamingCompiler};
+    use wasmer_singlepass_backend::ModuleCodeGenerator as SinglePassMCG;
+    let c: StreamingCompiler<SinglePassMCG, _, _, _, _> = StreamingCompiler::new(move || {
+        let mut chain = MiddlewareChain::new();
+        // Checking as often as a function can get hot switches it over soon enough.
+        chain.push(HotnessCounter::new(threshold));
+        chain
+    });
+    c
+}
+
 fn main() {
     let options = CLIOptions::from_args();
     match options {
@@ -868,6 +1138,9 @@ fn main() {
         CLIOptions::Validate(validate_options) => {
             validate(validate_options);
         }
+        CLIOptions::Inspect(inspect_options) => {
+            inspect(inspect_options);
+        }
     }
 }
 
@@ -0,0 +1,468 @@
+//! Summarize a WebAssembly module without compiling it, for the
+//! `inspect` subcommand.
+
+use crate::webassembly::InstanceABI;
+use std::fmt;
+use wasmer_middleware_common::opcode_filter::OpcodeClass;
+use wasmer_runtime_core::{
+    parse::wp_type_to_type,
+    types::{FuncSig, Type},
+    wasmparser::{
+        BinaryReaderError, ExternalKind, FuncType, ImportSectionEntryType, ModuleReader, Operator,
+        ResizableLimits, SectionCode,
+    },
+};
+
+/// The limits of a memory, in pages, or of a table, in elements.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    pub minimum: u32,
+    pub maximum: Option<u32>,
+    /// Whether the memory is shared between threads.
+    pub shared: bool,
+}
+
+impl Limits {
+    fn new(limits: &ResizableLimits, shared: bool) -> Self {
+        Limits {
+            minimum: limits.initial,
+            maximum: limits.maximum,
+            shared,
+        }
+    }
+}
+
+impl fmt::Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "min: {}", self.minimum)?;
+        match self.maximum {
+            Some(maximum) => write!(f, ", max: {}", maximum)?,
+            None => write!(f, ", no max")?,
+        }
+        if self.shared {
+            write!(f, ", shared")?;
+        }
+        Ok(())
+    }
+}
+
+/// The type of an import or of an export.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExternType {
+    Function(FuncSig),
+    Table(Limits),
+    Memory(Limits),
+    Global { ty: Type, mutable: bool },
+}
+
+impl fmt::Display for ExternType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExternType::Function(signature) => write!(f, "func {}", signature),
+            ExternType::Table(limits) => write!(f, "table ({})", limits),
+            ExternType::Memory(limits) => write!(f, "memory ({} pages)", limits),
+            ExternType::Global { ty, mutable: true } => write!(f, "global mut {}", ty),
+            ExternType::Global { ty, mutable: false } => write!(f, "global {}", ty),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub namespace: String,
+    pub name: String,
+    pub ty: ExternType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Export {
+    pub name: String,
+    pub ty: ExternType,
+}
+
+/// The pre-standard proposals a module uses, which must be enabled
+/// to run it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequiredFeatures {
+    pub simd: bool,
+    pub threads: bool,
+    pub bulk_memory: bool,
+    pub multi_value: bool,
+}
+
+impl fmt::Display for RequiredFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let features: Vec<&str> = [
+            (self.simd, "simd"),
+            (self.threads, "threads"),
+            (self.bulk_memory, "bulk-memory"),
+            (self.multi_value, "multi-value"),
+        ]
+        .iter()
+        .filter(|(required, _)| *required)
+        .map(|(_, name)| *name)
+        .collect();
+        if features.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", features.join(", "))
+        }
+    }
+}
+
+/// What a module imports, exports and needs to run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleSummary {
+    pub imports: Vec<Import>,
+    pub exports: Vec<Export>,
+    /// The limits of the memories, imported ones first.
+    pub memories: Vec<Limits>,
+    /// The limits of the tables, imported ones first.
+    pub tables: Vec<Limits>,
+    pub features: RequiredFeatures,
+    /// The names and sizes of the custom sections, in the order they
+    /// appear in the module.
+    pub custom_sections: Vec<(String, usize)>,
+    pub abi: InstanceABI,
+}
+
+fn parse_error(error: BinaryReaderError) -> String {
+    // Errors of broken invariants have no offset.
+    if error.offset == -1isize as usize {
+        error.message.to_string()
+    } else {
+        format!("{} at offset {}", error.message, error.offset)
+    }
+}
+
+fn get<T: Clone>(items: &[T], index: u32, message: &'static str) -> Result<T, BinaryReaderError> {
+    items.get(index as usize).cloned().ok_or(BinaryReaderError {
+        message,
+        offset: -1isize as usize,
+    })
+}
+
+fn func_sig(ty: &FuncType) -> Result<FuncSig, BinaryReaderError> {
+    let params = ty
+        .params
+        .iter()
+        .cloned()
+        .map(wp_type_to_type)
+        .collect::<Result<Vec<_>, _>>()?;
+    let returns = ty
+        .returns
+        .iter()
+        .cloned()
+        .map(wp_type_to_type)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(FuncSig::new(params, returns))
+}
+
+/// Marks the proposals `op` belongs to, with the classes of
+/// `OpcodeFilter` where there is one.
+fn detect_features(op: &Operator, features: &mut RequiredFeatures) {
+    if OpcodeClass::Simd.contains(op) {
+        features.simd = true;
+    }
+    if OpcodeClass::Atomic.contains(op) {
+        features.threads = true;
+    }
+    match *op {
+        Operator::MemoryInit { .. }
+        | Operator::DataDrop { .. }
+        | Operator::MemoryCopy { .. }
+        | Operator::MemoryFill { .. }
+        | Operator::TableInit { .. }
+        | Operator::ElemDrop { .. }
+        | Operator::TableCopy { .. } => features.bulk_memory = true,
+        _ => {}
+    }
+}
+
+/// Detects the ABI of a module from its imports, like
+/// `wasmer_wasi::is_wasi_module` and
+/// `wasmer_emscripten::is_emscripten_module` do for compiled modules.
+fn detect_abi(imports: &[Import]) -> InstanceABI {
+    let functions: Vec<&Import> = imports
+        .iter()
+        .filter(|import| match import.ty {
+            ExternType::Function(_) => true,
+            _ => false,
+        })
+        .collect();
+    let is_emscripten = functions.iter().any(|import| {
+        import.namespace == "env"
+            && (import.name == "_emscripten_memcpy_big"
+                || import.name == "emscripten_memcpy_big"
+                || import.name == "__map_file")
+    });
+    let is_wasi = !functions.is_empty()
+        && functions.iter().all(|import| {
+            import.namespace == "wasi_unstable" || import.namespace == "wasi_snapshot_preview1"
+        });
+    if is_emscripten {
+        InstanceABI::Emscripten
+    } else if is_wasi {
+        InstanceABI::WASI
+    } else {
+        InstanceABI::None
+    }
+}
+
+impl ModuleSummary {
+    /// Reads the summary of the module `wasm`.
+    ///
+    /// The module is only decoded, not validated, so that the summary
+    /// of a module which fails to compile can tell why.
+    pub fn parse(wasm: &[u8]) -> Result<Self, String> {
+        Self::parse_sections(wasm).map_err(parse_error)
+    }
+
+    fn parse_sections(wasm: &[u8]) -> Result<Self, BinaryReaderError> {
+        let mut signatures = Vec::new();
+        let mut functions = Vec::new();
+        let mut globals = Vec::new();
+        let mut imports = Vec::new();
+        let mut exports = Vec::new();
+        let mut memories = Vec::new();
+        let mut tables = Vec::new();
+        let mut features = RequiredFeatures::default();
+        let mut custom_sections = Vec::new();
+
+        let mut reader = ModuleReader::new(wasm)?;
+        while !reader.eof() {
+            let section = reader.read()?;
+            match section.code {
+                SectionCode::Type => {
+                    for ty in section.get_type_section_reader()? {
+                        let signature = func_sig(&ty?)?;
+                        if signature.returns().len() > 1 {
+                            features.multi_value = true;
+                        }
+                        signatures.push(signature);
+                    }
+                }
+                SectionCode::Import => {
+                    for import in section.get_import_section_reader()? {
+                        let import = import?;
+                        let ty = match import.ty {
+                            ImportSectionEntryType::Function(index) => {
+                                let signature = get(&signatures, index, "invalid type index")?;
+                                functions.push(signature.clone());
+                                ExternType::Function(signature)
+                            }
+                            ImportSectionEntryType::Table(ty) => {
+                                let limits = Limits::new(&ty.limits, false);
+                                tables.push(limits.clone());
+                                ExternType::Table(limits)
+                            }
+                            ImportSectionEntryType::Memory(ty) => {
+                                let limits = Limits::new(&ty.limits, ty.shared);
+                                memories.push(limits.clone());
+                                ExternType::Memory(limits)
+                            }
+                            ImportSectionEntryType::Global(ty) => {
+                                let global = ExternType::Global {
+                                    ty: wp_type_to_type(ty.content_type)?,
+                                    mutable: ty.mutable,
+                                };
+                                globals.push(global.clone());
+                                global
+                            }
+                        };
+                        imports.push(Import {
+                            namespace: import.module.to_string(),
+                            name: import.field.to_string(),
+                            ty,
+                        });
+                    }
+                }
+                SectionCode::Function => {
+                    for index in section.get_function_section_reader()? {
+                        functions.push(get(&signatures, index?, "invalid type index")?);
+                    }
+                }
+                SectionCode::Table => {
+                    for ty in section.get_table_section_reader()? {
+                        tables.push(Limits::new(&ty?.limits, false));
+                    }
+                }
+                SectionCode::Memory => {
+                    for ty in section.get_memory_section_reader()? {
+                        let ty = ty?;
+                        memories.push(Limits::new(&ty.limits, ty.shared));
+                    }
+                }
+                SectionCode::Global => {
+                    for global in section.get_global_section_reader()? {
+                        let ty = global?.ty;
+                        globals.push(ExternType::Global {
+                            ty: wp_type_to_type(ty.content_type)?,
+                            mutable: ty.mutable,
+                        });
+                    }
+                }
+                SectionCode::Export => {
+                    for export in section.get_export_section_reader()? {
+                        let export = export?;
+                        let index = export.index;
+                        let ty = match export.kind {
+                            ExternalKind::Function => ExternType::Function(get(
+                                &functions,
+                                index,
+                                "invalid function index",
+                            )?),
+                            ExternalKind::Table => {
+                                ExternType::Table(get(&tables, index, "invalid table index")?)
+                            }
+                            ExternalKind::Memory => {
+                                ExternType::Memory(get(&memories, index, "invalid memory index")?)
+                            }
+                            ExternalKind::Global => get(&globals, index, "invalid global index")?,
+                        };
+                        exports.push(Export {
+                            name: export.field.to_string(),
+                            ty,
+                        });
+                    }
+                }
+                SectionCode::Code => {
+                    for body in section.get_code_section_reader()? {
+                        let mut operators = body?.get_operators_reader()?;
+                        while !operators.eof() {
+                            detect_features(&operators.read()?, &mut features);
+                        }
+                    }
+                }
+                SectionCode::DataCount => features.bulk_memory = true,
+                SectionCode::Custom { name, .. } => {
+                    let len = section.get_binary_reader().bytes_remaining();
+                    custom_sections.push((name.to_string(), len));
+                }
+                _ => {}
+            }
+        }
+
+        if memories.iter().any(|memory| memory.shared) {
+            features.threads = true;
+        }
+        let uses_v128 = signatures
+            .iter()
+            .flat_map(|signature| signature.params().iter().chain(signature.returns()))
+            .chain(globals.iter().filter_map(|global| match global {
+                ExternType::Global { ty, .. } => Some(ty),
+                _ => None,
+            }))
+            .any(|ty| *ty == Type::V128);
+        if uses_v128 {
+            features.simd = true;
+        }
+
+        let abi = detect_abi(&imports);
+        Ok(ModuleSummary {
+            imports,
+            exports,
+            memories,
+            tables,
+            features,
+            custom_sections,
+            abi,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_a_wasi_module() {
+        let wasm = wabt::wat2wasm(
+            r#"
+            (module
+              (import "wasi_unstable" "proc_exit" (func (param i32)))
+              (memory (export "memory") 1 16)
+              (table 2 anyfunc)
+              (global (export "counter") (mut i32) (i32.const 0))
+              (func (export "_start")
+                i32.const 0
+                call 0))
+            "#,
+        )
+        .unwrap();
+        let summary = ModuleSummary::parse(&wasm).unwrap();
+
+        assert_eq!(
+            summary.imports,
+            vec![Import {
+                namespace: "wasi_unstable".to_string(),
+                name: "proc_exit".to_string(),
+                ty: ExternType::Function(FuncSig::new(vec![Type::I32], vec![])),
+            }]
+        );
+        let memory = Limits {
+            minimum: 1,
+            maximum: Some(16),
+            shared: false,
+        };
+        assert_eq!(
+            summary.exports,
+            vec![
+                Export {
+                    name: "memory".to_string(),
+                    ty: ExternType::Memory(memory.clone()),
+                },
+                Export {
+                    name: "counter".to_string(),
+                    ty: ExternType::Global {
+                        ty: Type::I32,
+                        mutable: true,
+                    },
+                },
+                Export {
+                    name: "_start".to_string(),
+                    ty: ExternType::Function(FuncSig::new(vec![], vec![])),
+                },
+            ]
+        );
+        assert_eq!(summary.memories, vec![memory]);
+        assert_eq!(
+            summary.tables,
+            vec![Limits {
+                minimum: 2,
+                maximum: None,
+                shared: false,
+            }]
+        );
+        assert_eq!(summary.features, RequiredFeatures::default());
+        assert_eq!(summary.abi, InstanceABI::WASI);
+    }
+
+    #[test]
+    fn detects_required_features() {
+        let mut features = wabt::Features::new();
+        features.enable_threads();
+        let wasm = wabt::wat2wasm_with_features(
+            r#"
+            (module
+              (memory 1 1 shared)
+              (func (result i32)
+                i32.const 0
+                i32.atomic.load))
+            "#,
+            features,
+        )
+        .unwrap();
+        let summary = ModuleSummary::parse(&wasm).unwrap();
+
+        assert_eq!(
+            summary.features,
+            RequiredFeatures {
+                threads: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(summary.features.to_string(), "threads");
+        assert_eq!(summary.abi, InstanceABI::None);
+    }
+}
@@ -15,6 +15,7 @@ extern crate wasmer_runtime_core;
 // extern crate wasmer_emscripten;
 
 #[macro_use]
+pub mod inspect;
 pub mod update;
 pub mod utils;
 pub mod webassembly;
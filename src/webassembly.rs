@@ -11,7 +11,7 @@ pub struct ResultObject {
     pub instance: Box<Instance>,
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum InstanceABI {
     Emscripten,
     WASI,